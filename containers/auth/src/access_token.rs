@@ -0,0 +1,203 @@
+use crate::identity::IdentityStore;
+use crate::types::{AccountKind, AuthMethod, License, User};
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+use uuid::Uuid;
+use verseguy_storage::Storage;
+
+/// Prefix of every personal access token, so leaked tokens are easy to detect
+pub const TOKEN_PREFIX: &str = "vgp_";
+
+/// Scope granting every permission
+pub const SCOPE_ALL: &str = "*";
+
+/// Personal access token metadata. The plaintext token is never stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PersonalAccessToken {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// SHA-256 of the plaintext token (hex)
+    pub token_hash: String,
+    /// First characters of the token, for display
+    pub hint: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl PersonalAccessToken {
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|exp| exp > Utc::now())
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == SCOPE_ALL || s == scope)
+    }
+}
+
+/// Issues, validates and revokes personal access tokens, and manages service accounts.
+///
+/// Tokens are stored under `pat:{id}` with a `pat_hash:{sha256}` index for lookup.
+#[derive(Clone)]
+pub struct AccessTokenService {
+    storage: Storage,
+    identities: IdentityStore,
+}
+
+impl AccessTokenService {
+    pub fn new(storage: Storage) -> Self {
+        Self {
+            identities: IdentityStore::new(storage.clone()),
+            storage,
+        }
+    }
+
+    fn hash(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    /// Create a token for `user_id`; returns the plaintext token (shown once) and its record
+    pub fn create(
+        &self,
+        user_id: &str,
+        name: &str,
+        scopes: Vec<String>,
+        expires_in_days: Option<i64>,
+    ) -> Result<(String, PersonalAccessToken)> {
+        if name.trim().is_empty() {
+            anyhow::bail!("Token name required");
+        }
+        if scopes.is_empty() {
+            anyhow::bail!("At least one scope required");
+        }
+        if self.identities.get_user(user_id)?.is_none() {
+            anyhow::bail!("User not found");
+        }
+
+        let mut secret = [0u8; 32];
+        OsRng.fill_bytes(&mut secret);
+        let token = format!("{}{}", TOKEN_PREFIX, URL_SAFE_NO_PAD.encode(secret));
+
+        let now = Utc::now();
+        let record = PersonalAccessToken {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            name: name.to_string(),
+            scopes,
+            token_hash: Self::hash(&token),
+            hint: token[..TOKEN_PREFIX.len() + 4].to_string(),
+            created_at: now,
+            expires_at: expires_in_days.map(|d| now + Duration::days(d)),
+            last_used_at: None,
+            revoked_at: None,
+        };
+
+        self.storage
+            .put(format!("pat:{}", record.id).as_bytes(), &record)
+            .context("Failed to store access token")?;
+        self.storage
+            .put(
+                format!("pat_hash:{}", record.token_hash).as_bytes(),
+                &record.id,
+            )
+            .context("Failed to index access token")?;
+        info!("Created access token {} for user {}", record.id, user_id);
+
+        Ok((token, record))
+    }
+
+    /// List all tokens (including revoked/expired) belonging to a user
+    pub fn list(&self, user_id: &str) -> Result<Vec<PersonalAccessToken>> {
+        let all: Vec<PersonalAccessToken> = self.storage.prefix_scan(b"pat:")?;
+        let mut tokens: Vec<PersonalAccessToken> =
+            all.into_iter().filter(|t| t.user_id == user_id).collect();
+        tokens.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        Ok(tokens)
+    }
+
+    /// Revoke a token owned by `user_id`
+    pub fn revoke(&self, user_id: &str, token_id: &str) -> Result<PersonalAccessToken> {
+        let key = format!("pat:{}", token_id);
+        let mut record: PersonalAccessToken = self
+            .storage
+            .get(key.as_bytes())?
+            .filter(|t: &PersonalAccessToken| t.user_id == user_id)
+            .ok_or_else(|| anyhow::anyhow!("Token not found"))?;
+
+        if record.revoked_at.is_none() {
+            record.revoked_at = Some(Utc::now());
+            self.storage.put(key.as_bytes(), &record)?;
+            self.storage
+                .delete(format!("pat_hash:{}", record.token_hash).as_bytes())?;
+            info!("Revoked access token {} for user {}", record.id, user_id);
+        }
+        Ok(record)
+    }
+
+    /// Validate a plaintext token and record its use
+    pub fn validate(&self, token: &str) -> Result<PersonalAccessToken> {
+        if !token.starts_with(TOKEN_PREFIX) {
+            anyhow::bail!("Not a personal access token");
+        }
+        let id: String = self
+            .storage
+            .get(format!("pat_hash:{}", Self::hash(token)).as_bytes())?
+            .ok_or_else(|| anyhow::anyhow!("Invalid access token"))?;
+        let key = format!("pat:{}", id);
+        let mut record: PersonalAccessToken = self
+            .storage
+            .get(key.as_bytes())?
+            .ok_or_else(|| anyhow::anyhow!("Invalid access token"))?;
+
+        if !record.is_active() {
+            anyhow::bail!("Access token expired or revoked");
+        }
+
+        record.last_used_at = Some(Utc::now());
+        self.storage.put(key.as_bytes(), &record)?;
+        Ok(record)
+    }
+
+    /// Create a non-interactive service account that can only authenticate with tokens
+    pub fn create_service_account(&self, name: &str, license: License) -> Result<User> {
+        if name.trim().len() < 3 {
+            anyhow::bail!("Service account name too short");
+        }
+        let now = Utc::now();
+        let user = User {
+            id: Uuid::new_v4().to_string(),
+            username: name.to_string(),
+            email: None,
+            password_hash: None,
+            auth_method: AuthMethod::Service,
+            identities: Vec::new(),
+            roles: Vec::new(),
+            kind: AccountKind::Service,
+            license,
+            created_at: now,
+            updated_at: now,
+        };
+        self.identities.save_user(&user)?;
+        info!("Created service account {} ({})", user.username, user.id);
+        Ok(user)
+    }
+
+    /// List all service accounts
+    pub fn list_service_accounts(&self) -> Result<Vec<User>> {
+        let users: Vec<User> = self.storage.prefix_scan(b"user:id:")?;
+        Ok(users
+            .into_iter()
+            .filter(|u| u.kind == AccountKind::Service)
+            .collect())
+    }
+}
@@ -0,0 +1,218 @@
+use crate::types::{AuthMethod, LinkedIdentity, User};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use uuid::Uuid;
+use verseguy_storage::Storage;
+
+/// Provider name used for username/password identities
+pub const LOCAL_PROVIDER: &str = "local";
+
+/// How long a re-authentication ticket stays valid
+const REAUTH_TTL_MINUTES: i64 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ReauthTicket {
+    user_id: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Identity index and account-linking operations.
+///
+/// Users are stored under `user:id:{id}`; every linked identity is indexed under
+/// `identity:{provider}:{provider_user_id}` pointing at the owning user id.
+/// Linking and unlinking require a single-use re-authentication ticket obtained
+/// from `LocalAuth::reauthenticate` or `OAuthHandler::handle_reauth_callback`.
+/// `IdentityStore::issue_reauth_ticket` is crate-private so tickets can only be
+/// minted after a credential check.
+#[derive(Clone)]
+pub struct IdentityStore {
+    storage: Storage,
+}
+
+impl IdentityStore {
+    pub fn new(storage: Storage) -> Self {
+        Self { storage }
+    }
+
+    fn index_key(provider: &str, provider_user_id: &str) -> String {
+        format!("identity:{}:{}", provider, provider_user_id)
+    }
+
+    pub fn get_user(&self, user_id: &str) -> Result<Option<User>> {
+        self.storage
+            .get(format!("user:id:{}", user_id).as_bytes())
+            .context("Failed to load user")
+    }
+
+    /// Persist a user and its username index (only for accounts with a local identity)
+    pub fn save_user(&self, user: &User) -> Result<()> {
+        self.storage
+            .put(format!("user:id:{}", user.id).as_bytes(), user)
+            .context("Failed to save user")?;
+        if user.local_password_hash().is_some() {
+            self.storage
+                .put(format!("user:username:{}", user.username).as_bytes(), user)
+                .context("Failed to save username index")?;
+        }
+        Ok(())
+    }
+
+    /// Look up the user owning `(provider, provider_user_id)`
+    pub fn find_user(&self, provider: &str, provider_user_id: &str) -> Result<Option<User>> {
+        let key = Self::index_key(provider, provider_user_id);
+        let user_id: Option<String> = self
+            .storage
+            .get(key.as_bytes())
+            .context("Failed to read identity index")?;
+        if let Some(user_id) = user_id {
+            return self.get_user(&user_id);
+        }
+
+        // Legacy OAuth mapping: user stored under its bare id
+        let legacy_key = format!("user_by_oauth:{}:{}", provider, provider_user_id);
+        let legacy_id: Option<String> = self.storage.get(legacy_key.as_bytes())?;
+        if let Some(user_id) = legacy_id {
+            let user: Option<User> = self.storage.get(user_id.as_bytes())?;
+            if let Some(mut user) = user {
+                info!("Migrating legacy OAuth user {} to identity index", user.id);
+                self.attach(&mut user, provider, provider_user_id)?;
+                self.save_user(&user)?;
+                self.storage.delete(legacy_key.as_bytes())?;
+                self.storage.delete(user_id.as_bytes())?;
+                return Ok(Some(user));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Add an identity to `user` and index it, without re-authentication.
+    ///
+    /// Used when creating accounts; callers must persist `user` afterwards.
+    pub(crate) fn attach(
+        &self,
+        user: &mut User,
+        provider: &str,
+        provider_user_id: &str,
+    ) -> Result<()> {
+        let key = Self::index_key(provider, provider_user_id);
+        let owner: Option<String> = self.storage.get(key.as_bytes())?;
+        if owner.as_deref().is_some_and(|o| o != user.id) {
+            anyhow::bail!("Identity is already linked to another account");
+        }
+
+        Self::materialize_legacy_identity(user);
+        if !user
+            .identities
+            .iter()
+            .any(|i| i.provider == provider && i.provider_user_id == provider_user_id)
+        {
+            user.identities.push(LinkedIdentity {
+                provider: provider.to_string(),
+                provider_user_id: provider_user_id.to_string(),
+                linked_at: Utc::now(),
+            });
+        }
+        self.storage
+            .put(key.as_bytes(), &user.id)
+            .context("Failed to write identity index")?;
+        Ok(())
+    }
+
+    /// Accounts created before identity linking only carry `auth_method`
+    fn materialize_legacy_identity(user: &mut User) {
+        if !user.identities.is_empty() {
+            return;
+        }
+        if let AuthMethod::Local { username, .. } = &user.auth_method {
+            user.identities.push(LinkedIdentity {
+                provider: LOCAL_PROVIDER.to_string(),
+                provider_user_id: username.clone(),
+                linked_at: user.created_at,
+            });
+        }
+    }
+
+    /// Issue a short-lived, single-use ticket proving the user just re-authenticated
+    pub(crate) fn issue_reauth_ticket(&self, user_id: &str) -> Result<String> {
+        let ticket = Uuid::new_v4().to_string();
+        let rec = ReauthTicket {
+            user_id: user_id.to_string(),
+            expires_at: Utc::now() + Duration::minutes(REAUTH_TTL_MINUTES),
+        };
+        self.storage
+            .put(format!("reauth:{}", ticket).as_bytes(), &rec)
+            .context("Failed to store re-authentication ticket")?;
+        Ok(ticket)
+    }
+
+    /// Consume a re-authentication ticket and return the user id it was issued for
+    pub fn consume_reauth_ticket(&self, ticket: &str) -> Result<String> {
+        let key = format!("reauth:{}", ticket);
+        let rec: ReauthTicket = self
+            .storage
+            .get(key.as_bytes())?
+            .ok_or_else(|| anyhow::anyhow!("Re-authentication required"))?;
+        self.storage.delete(key.as_bytes())?;
+        if rec.expires_at < Utc::now() {
+            anyhow::bail!("Re-authentication expired");
+        }
+        Ok(rec.user_id)
+    }
+
+    /// Link a verified `(provider, provider_user_id)` to an existing account.
+    ///
+    /// Public linking goes through `LocalAuth::link_password` and
+    /// `OAuthHandler::get_link_url`, which check the re-authentication ticket.
+    pub(crate) fn link_to(
+        &self,
+        user_id: &str,
+        provider: &str,
+        provider_user_id: &str,
+    ) -> Result<User> {
+        let mut user = self
+            .get_user(user_id)?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+        self.attach(&mut user, provider, provider_user_id)?;
+        user.updated_at = Utc::now();
+        self.save_user(&user)?;
+        info!("Linked {} identity to user {}", provider, user.id);
+        Ok(user)
+    }
+
+    /// Remove an identity from the account the ticket was issued for.
+    ///
+    /// The last remaining identity cannot be removed.
+    pub fn unlink(&self, ticket: &str, provider: &str, provider_user_id: &str) -> Result<User> {
+        let user_id = self.consume_reauth_ticket(ticket)?;
+        let mut user = self
+            .get_user(&user_id)?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+
+        Self::materialize_legacy_identity(&mut user);
+        let before = user.identities.len();
+        user.identities
+            .retain(|i| !(i.provider == provider && i.provider_user_id == provider_user_id));
+        if user.identities.len() == before {
+            anyhow::bail!("Identity is not linked to this account");
+        }
+        if user.identities.is_empty() {
+            anyhow::bail!("Cannot unlink the last sign-in method");
+        }
+
+        if provider == LOCAL_PROVIDER {
+            user.password_hash = None;
+            self.storage
+                .delete(format!("user:username:{}", user.username).as_bytes())?;
+        }
+        self.storage
+            .delete(Self::index_key(provider, provider_user_id).as_bytes())?;
+
+        user.updated_at = Utc::now();
+        self.save_user(&user)?;
+        info!("Unlinked {} identity from user {}", provider, user.id);
+        Ok(user)
+    }
+}
@@ -1,12 +1,21 @@
+pub mod access_token;
+pub mod identity;
 pub mod local;
 pub mod oauth;
 pub mod oauth_types;
+pub mod oidc;
+pub mod password;
+pub mod pkce;
 pub mod session;
 pub mod types;
 
-pub use local::LocalAuth;
+pub use access_token::{AccessTokenService, PersonalAccessToken};
+pub use identity::IdentityStore;
+pub use local::{LocalAuth, LocalAuthConfig};
 pub use oauth::OAuthHandler;
+pub use oidc::{IdTokenClaims, OidcDiscovery};
+pub use password::{Argon2Config, BreachedPasswordList, PasswordPolicy};
 pub use session::SessionService;
 
 pub use oauth_types::*;
-pub use types::{AuthMethod, License, Session, User};
+pub use types::{AccountKind, AuthMethod, License, LinkedIdentity, Session, User};
@@ -1,21 +1,77 @@
-use crate::{AuthMethod, License, User};
+use crate::identity::{IdentityStore, LOCAL_PROVIDER};
+use crate::password::{Argon2Config, BreachedPasswordList, PasswordPolicy};
+use crate::{AccountKind, AuthMethod, License, User};
 use anyhow::Result;
 use argon2::{
     Argon2,
-    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    password_hash::{PasswordHash, PasswordVerifier},
 };
 use chrono::Utc;
-use rand::rngs::OsRng;
+use tracing::info;
 use uuid::Uuid;
 use verseguy_storage::RocksDBStorage;
 
+/// Per-deployment configuration for local authentication
+#[derive(Debug, Clone, Default)]
+pub struct LocalAuthConfig {
+    pub policy: PasswordPolicy,
+    pub argon2: Argon2Config,
+    /// Optional offline breached-password corpus
+    pub breached: Option<BreachedPasswordList>,
+}
+
 pub struct LocalAuth {
     storage: RocksDBStorage,
+    identities: IdentityStore,
+    config: LocalAuthConfig,
 }
 
 impl LocalAuth {
     pub fn new(storage: RocksDBStorage) -> Self {
-        Self { storage }
+        Self::with_config(storage, LocalAuthConfig::default())
+    }
+
+    pub fn with_config(storage: RocksDBStorage, config: LocalAuthConfig) -> Self {
+        Self {
+            identities: IdentityStore::new(storage.clone()),
+            storage,
+            config,
+        }
+    }
+
+    /// Verify a local password, returning whether the hash needs upgrading
+    fn verify(&self, user: &User, password: &str, error: &str) -> Result<bool> {
+        let hash = user
+            .local_password_hash()
+            .ok_or_else(|| anyhow::anyhow!("User does not use local auth"))?;
+        let parsed = PasswordHash::new(hash).map_err(|e| anyhow::anyhow!(e))?;
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .map_err(|_| anyhow::anyhow!("{}", error))?;
+        Ok(self.config.argon2.needs_rehash(&parsed))
+    }
+
+    /// Store a new password hash on the user, keeping the legacy `auth_method` in sync
+    fn set_password_hash(user: &mut User, hash: String) {
+        if matches!(user.auth_method, AuthMethod::Local { .. }) {
+            user.auth_method = AuthMethod::Local {
+                username: user.username.clone(),
+                password_hash: hash.clone(),
+            };
+        }
+        user.password_hash = Some(hash);
+        user.updated_at = Utc::now();
+    }
+
+    /// Validate a candidate password against the policy and breach corpus
+    fn validate_password(&self, username: &str, password: &str) -> Result<()> {
+        self.config.policy.validate(username, password)?;
+        if let Some(breached) = &self.config.breached
+            && breached.is_breached(password)?
+        {
+            anyhow::bail!("Password appears in a known data breach");
+        }
+        Ok(())
     }
 
     pub async fn register(&self, username: String, password: String) -> Result<User> {
@@ -23,9 +79,7 @@ impl LocalAuth {
         if username.len() < 3 {
             anyhow::bail!("Username too short");
         }
-        if password.len() < 8 {
-            anyhow::bail!("Password too short");
-        }
+        self.validate_password(&username, &password)?;
 
         // Check existing
         let key = format!("user:username:{}", username);
@@ -34,53 +88,55 @@ impl LocalAuth {
         }
 
         // Hash password
-        let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-        let password_hash = argon2
-            .hash_password(password.as_bytes(), &salt)
-            .map_err(|e| anyhow::anyhow!(e))?
-            .to_string();
+        let password_hash = self.config.argon2.hash_password(&password)?;
 
         // Create user
-        let user = User {
+        let mut user = User {
             id: Uuid::new_v4().to_string(),
             username: username.clone(),
             email: None,
             password_hash: Some(password_hash.clone()),
             auth_method: AuthMethod::Local {
                 username: username.clone(),
-                password_hash: password_hash.clone(),
+                password_hash,
             },
+            identities: Vec::new(),
+            roles: Vec::new(),
+            kind: AccountKind::Human,
             license: License::Free,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
 
         // Save
-        let user_key = format!("user:id:{}", user.id);
-        self.storage.put(user_key.as_bytes(), &user)?;
-        self.storage
-            .put(format!("user:username:{}", username).as_bytes(), &user)?;
+        self.identities
+            .attach(&mut user, LOCAL_PROVIDER, &username)?;
+        self.identities.save_user(&user)?;
 
         Ok(user)
     }
 
     pub async fn login(&self, username: &str, password: &str) -> Result<User> {
         let key = format!("user:username:{}", username);
-        let user = self
+        let mut user = self
             .storage
             .get::<_, User>(key.as_bytes())?
             .ok_or_else(|| anyhow::anyhow!("Invalid credentials"))?;
 
-        let hash = match &user.auth_method {
-            AuthMethod::Local { password_hash, .. } => password_hash,
-            _ => anyhow::bail!("Not a local user"),
-        };
+        if user.is_service_account() || user.local_password_hash().is_none() {
+            anyhow::bail!("Not a local user");
+        }
 
-        let parsed = PasswordHash::new(hash).map_err(|e| anyhow::anyhow!(e))?;
-        Argon2::default()
-            .verify_password(password.as_bytes(), &parsed)
-            .map_err(|_| anyhow::anyhow!("Invalid credentials"))?;
+        // Transparently upgrade hashes created with outdated Argon2 parameters
+        if self.verify(&user, password, "Invalid credentials")? {
+            info!(
+                "Rehashing password for user {} with updated parameters",
+                user.id
+            );
+            let new_hash = self.config.argon2.hash_password(password)?;
+            Self::set_password_hash(&mut user, new_hash);
+            self.identities.save_user(&user)?;
+        }
 
         Ok(user)
     }
@@ -92,46 +148,74 @@ impl LocalAuth {
         new_password: &str,
     ) -> Result<()> {
         // Fetch user
-        let user_key = format!("user:id:{}", user_id);
-        let mut user: User = self
-            .storage
-            .get::<_, User>(user_key.as_bytes())?
+        let mut user = self
+            .identities
+            .get_user(user_id)?
             .ok_or_else(|| anyhow::anyhow!("User not found"))?;
 
         // Verify old password
-        let current_hash = match &user.auth_method {
-            AuthMethod::Local { password_hash, .. } => password_hash,
-            _ => anyhow::bail!("User does not use local auth"),
-        };
-
-        let parsed = PasswordHash::new(current_hash).map_err(|e| anyhow::anyhow!(e))?;
-        Argon2::default()
-            .verify_password(old_password.as_bytes(), &parsed)
-            .map_err(|_| anyhow::anyhow!("Current password is incorrect"))?;
+        self.verify(&user, old_password, "Current password is incorrect")?;
 
         // Validate new password
-        if new_password.len() < 8 {
-            anyhow::bail!("New password must be at least 8 characters");
-        }
+        self.validate_password(&user.username, new_password)?;
 
         // Hash new password
-        let salt = SaltString::generate(&mut OsRng);
-        let new_hash = Argon2::default()
-            .hash_password(new_password.as_bytes(), &salt)
-            .map_err(|e| anyhow::anyhow!(e))?
-            .to_string();
+        let new_hash = self.config.argon2.hash_password(new_password)?;
 
         // Update user
-        user.auth_method = AuthMethod::Local {
-            username: user.username.clone(),
-            password_hash: new_hash.clone(),
-        };
+        Self::set_password_hash(&mut user, new_hash);
 
         // Save
-        self.storage.put(user_key.as_bytes(), &user)?;
-        self.storage
-            .put(format!("user:username:{}", user.username).as_bytes(), &user)?;
+        self.identities.save_user(&user)
+    }
 
-        Ok(())
+    /// Re-authenticate with the current password and obtain a ticket for
+    /// sensitive account operations such as linking or unlinking identities
+    pub async fn reauthenticate(&self, user_id: &str, password: &str) -> Result<String> {
+        let user = self
+            .identities
+            .get_user(user_id)?
+            .ok_or_else(|| anyhow::anyhow!("Invalid credentials"))?;
+        self.verify(&user, password, "Invalid credentials")?;
+        self.identities.issue_reauth_ticket(&user.id)
+    }
+
+    /// Add a local password to an account that currently only signs in via OAuth
+    pub async fn link_password(&self, reauth_ticket: &str, password: &str) -> Result<User> {
+        let user_id = self.identities.consume_reauth_ticket(reauth_ticket)?;
+        let mut user = self
+            .identities
+            .get_user(&user_id)?
+            .ok_or_else(|| anyhow::anyhow!("User not found"))?;
+
+        if user.local_password_hash().is_some() {
+            anyhow::bail!("Account already has a local password");
+        }
+        let key = format!("user:username:{}", user.username);
+        if self.storage.get::<_, User>(key.as_bytes())?.is_some() {
+            anyhow::bail!("Username exists");
+        }
+        self.validate_password(&user.username, password)?;
+
+        let new_hash = self.config.argon2.hash_password(password)?;
+        Self::set_password_hash(&mut user, new_hash);
+        let username = user.username.clone();
+        self.identities
+            .attach(&mut user, LOCAL_PROVIDER, &username)?;
+        self.identities.save_user(&user)?;
+        info!("Linked local password to user {}", user.id);
+
+        Ok(user)
+    }
+
+    /// Remove a linked identity after re-authentication
+    pub async fn unlink_identity(
+        &self,
+        reauth_ticket: &str,
+        provider: &str,
+        provider_user_id: &str,
+    ) -> Result<User> {
+        self.identities
+            .unlink(reauth_ticket, provider, provider_user_id)
     }
 }
@@ -1,27 +1,49 @@
 use anyhow::{Context, Result};
+use jsonwebtoken::jwk::JwkSet;
 use reqwest::Client;
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
-use tracing::info;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
 use url::Url;
 use uuid::Uuid;
 
-use crate::oauth_types::{OAuthConfig, OAuthProvider, OAuthState, OAuthUserInfo, TokenResponse};
-use crate::types::{AuthMethod, License, User};
+use crate::identity::IdentityStore;
+use crate::oauth_types::{
+    CustomOidcConfig, OAuthConfig, OAuthProvider, OAuthPurpose, OAuthState, OAuthUserInfo,
+    TokenResponse,
+};
+use crate::oidc::{self, OidcDiscovery};
+use crate::pkce::{self, PkcePair};
+use crate::types::{AccountKind, AuthMethod, License, User};
 use verseguy_storage::Storage;
 
-/// OAuth handler implementing Provider configs and state management
+/// How long an authorization round-trip may take (max 10 minutes)
+const STATE_TTL_MINUTES: i64 = 10;
+
+/// Minimum time between two fetches of the same JWKS, so tokens naming unknown keys cannot
+/// make us hammer the provider
+const JWKS_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// OAuth handler implementing Provider configs and state management.
+///
+/// Pending states are persisted under `oauth_state:{state}` so callbacks survive
+/// restarts and can be handled by any instance sharing the storage.
 pub struct OAuthHandler {
     storage: Storage,
+    identities: IdentityStore,
     client: Client,
     configs: HashMap<OAuthProvider, OAuthConfig>,
-    states: Arc<RwLock<HashMap<String, OAuthState>>>,
+    jwks: Arc<RwLock<HashMap<String, JwkSet>>>,
+    /// When each JWKS url was last fetched
+    jwks_fetched: Arc<Mutex<HashMap<String, Instant>>>,
 }
 
 impl OAuthHandler {
     /// Create new OAuth handler
     pub fn new(storage: Storage) -> Self {
         Self {
+            identities: IdentityStore::new(storage.clone()),
             storage,
             client: match Client::builder()
                 .timeout(std::time::Duration::from_secs(30))
@@ -35,36 +57,93 @@ impl OAuthHandler {
                 }
             },
             configs: HashMap::new(),
-            states: Arc::new(RwLock::new(HashMap::new())),
+            jwks: Arc::new(RwLock::new(HashMap::new())),
+            jwks_fetched: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Fetch OpenID Provider metadata for an issuer
+    pub async fn discover(&self, issuer: &str) -> Result<OidcDiscovery> {
+        let resp = self
+            .client
+            .get(OidcDiscovery::url_for(issuer))
+            .send()
+            .await
+            .context("Failed to fetch OIDC discovery document")?;
+
+        if !resp.status().is_success() {
+            anyhow::bail!("OIDC discovery failed: {}", resp.status());
+        }
+
+        let discovery: OidcDiscovery = resp
+            .json()
+            .await
+            .context("Failed to parse OIDC discovery document")?;
+        if discovery.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            anyhow::bail!("OIDC discovery issuer mismatch: {}", discovery.issuer);
         }
+        Ok(discovery)
     }
 
     /// Register OAuth provider config
     pub fn register_provider(&mut self, config: OAuthConfig) {
         info!("Registering OAuth provider: {:?}", config.provider);
-        self.configs.insert(config.provider, config);
+        self.configs.insert(config.provider.clone(), config);
+    }
+
+    /// Discover and register a deployment-specific OIDC provider
+    pub async fn register_custom_provider(&mut self, cfg: CustomOidcConfig) -> Result<()> {
+        if cfg.id.is_empty() || OAuthProvider::RESERVED_IDS.contains(&cfg.id.as_str()) {
+            anyhow::bail!("Invalid custom provider id: {:?}", cfg.id);
+        }
+        let discovery = self.discover(&cfg.issuer).await?;
+        self.register_provider(OAuthConfig::custom(cfg, &discovery));
+        Ok(())
     }
 
     /// Generate authorization URL and store state for CSRF protection
     pub fn get_auth_url(&self, provider: OAuthProvider) -> Result<String> {
+        self.build_auth_url(provider, OAuthPurpose::Login)
+    }
+
+    /// Generate an authorization URL that links the provider identity to the
+    /// account the re-authentication ticket was issued for
+    pub fn get_link_url(&self, provider: OAuthProvider, reauth_ticket: &str) -> Result<String> {
+        let user_id = self.identities.consume_reauth_ticket(reauth_ticket)?;
+        self.build_auth_url(provider, OAuthPurpose::Link { user_id })
+    }
+
+    /// Generate an authorization URL used to re-authenticate `user_id` with an
+    /// already linked provider; complete it with `handle_reauth_callback`
+    pub fn get_reauth_url(&self, provider: OAuthProvider, user_id: &str) -> Result<String> {
+        self.build_auth_url(
+            provider,
+            OAuthPurpose::Reauth {
+                user_id: user_id.to_string(),
+            },
+        )
+    }
+
+    fn build_auth_url(&self, provider: OAuthProvider, purpose: OAuthPurpose) -> Result<String> {
         let cfg = self
             .configs
             .get(&provider)
             .ok_or_else(|| anyhow::anyhow!("Unsupported provider"))?;
 
         let state = Uuid::new_v4().to_string();
-        let now = chrono::Utc::now();
+        let pkce = PkcePair::generate();
+        let nonce = cfg.is_oidc().then(pkce::random_token);
         let s = OAuthState {
             state: state.clone(),
             provider,
-            created_at: now,
+            created_at: chrono::Utc::now(),
+            purpose,
+            code_verifier: pkce.verifier,
+            nonce: nonce.clone(),
         };
-        match self.states.write() {
-            Ok(mut states) => {
-                states.insert(state.clone(), s);
-            }
-            Err(e) => return Err(anyhow::anyhow!("oauth states RwLock poisoned: {:?}", e)),
-        }
+        self.storage
+            .put(format!("oauth_state:{}", state).as_bytes(), &s)
+            .context("Failed to store OAuth state")?;
 
         // Build auth URL
         let mut url = Url::parse(&cfg.auth_url).context("Invalid auth url")?;
@@ -72,77 +151,77 @@ impl OAuthHandler {
             .append_pair("client_id", &cfg.client_id)
             .append_pair("redirect_uri", &cfg.redirect_uri)
             .append_pair("response_type", "code")
-            .append_pair("state", &state);
+            .append_pair("state", &state)
+            .append_pair("code_challenge", &pkce.challenge)
+            .append_pair("code_challenge_method", "S256");
+        if let Some(nonce) = &nonce {
+            url.query_pairs_mut().append_pair("nonce", nonce);
+        }
 
-        // Default scopes
-        match provider {
-            OAuthProvider::Google => {
-                url.query_pairs_mut()
-                    .append_pair("scope", "openid email profile");
-            }
-            OAuthProvider::Discord => {
-                url.query_pairs_mut().append_pair("scope", "identify email");
-            }
-            OAuthProvider::Twitch => {
-                url.query_pairs_mut()
-                    .append_pair("scope", "user:read:email");
-            }
+        if !cfg.scopes.is_empty() {
+            url.query_pairs_mut()
+                .append_pair("scope", &cfg.scopes.join(" "));
         }
 
         Ok(url.to_string())
     }
 
-    /// Handle OAuth callback: validate state, exchange code, create or return user
+    /// Handle OAuth callback: validate state, exchange code, create or return user.
+    ///
+    /// For link flows the verified identity is attached to the existing account instead.
     pub async fn handle_callback(&self, code: String, state: String) -> Result<User> {
-        // Verify state (CSRF protection)
-        let oauth_state = {
-            let mut states = match self.states.write() {
-                Ok(s) => s,
-                Err(e) => return Err(anyhow::anyhow!("oauth states RwLock poisoned: {:?}", e)),
-            };
-            states
-                .remove(&state)
-                .ok_or_else(|| anyhow::anyhow!("Invalid or expired state"))?
-        };
-
-        // Check state age (max 10 minutes)
-        let age = chrono::Utc::now() - oauth_state.created_at;
-        if age > chrono::Duration::minutes(10) {
-            anyhow::bail!("State expired");
+        let oauth_state = self.take_state(&state)?;
+        let (token, user_info) = self.authenticate(&oauth_state, code).await?;
+        let OAuthState {
+            provider, purpose, ..
+        } = oauth_state;
+        let roles = self.roles_for(&provider, &user_info);
+
+        match purpose {
+            OAuthPurpose::Login => {}
+            OAuthPurpose::Link { user_id } => {
+                return self
+                    .identities
+                    .link_to(&user_id, provider.as_str(), &user_info.id);
+            }
+            OAuthPurpose::Reauth { .. } => {
+                anyhow::bail!(
+                    "Re-authentication state must be completed with handle_reauth_callback"
+                )
+            }
         }
 
-        let provider = oauth_state.provider;
-
-        // Exchange code for token
-        let token = self.exchange_code(provider, code).await?;
-
-        // Get user info
-        let user_info = self.get_user_info(provider, &token.access_token).await?;
-
         // Check existing mapping
-        let email_key = format!("user_by_oauth:{}:{}", provider.as_str(), user_info.id);
-        let existing_user_id: Option<String> = self
-            .storage
-            .get(email_key.as_bytes())
-            .context("Failed to check existing OAuth user")?;
-
-        if let Some(user_id) = existing_user_id {
-            return self
-                .storage
-                .get(user_id.as_bytes())
-                .context("Failed to get user")?
-                .ok_or_else(|| anyhow::anyhow!("User not found"));
+        if let Some(mut user) = self
+            .identities
+            .find_user(provider.as_str(), &user_info.id)
+            .context("Failed to check existing OAuth user")?
+        {
+            if let Some(roles) = roles
+                && roles != user.roles
+            {
+                info!(
+                    "Syncing roles for user {} from {}",
+                    user.id,
+                    provider.as_str()
+                );
+                user.roles = roles;
+                user.updated_at = chrono::Utc::now();
+                self.identities.save_user(&user)?;
+            }
+            return Ok(user);
         }
 
         let user_id = Uuid::new_v4().to_string();
         let now = chrono::Utc::now();
         let expires_at = now + chrono::Duration::seconds(token.expires_in);
 
-        let user = User {
+        let mut user = User {
             id: user_id.clone(),
             username: user_info
-                .name
+                .username
                 .clone()
+                .or_else(|| user_info.name.clone())
                 .unwrap_or_else(|| format!("user_{}", &user_id[..8])),
             email: user_info.email.clone(),
             password_hash: None,
@@ -152,26 +231,219 @@ impl OAuthHandler {
                 refresh_token: token.refresh_token.clone(),
                 expires_at,
             },
-
+            identities: Vec::new(),
+            roles: roles.unwrap_or_default(),
+            kind: AccountKind::Human,
             license: License::Pro,
             created_at: now,
             updated_at: now,
         };
 
-        self.storage
-            .put(user_id.as_bytes(), &user)
-            .context("Failed to save user")?;
-        self.storage
-            .put(email_key.as_bytes(), &user_id)
+        self.identities
+            .attach(&mut user, provider.as_str(), &user_info.id)
             .context("Failed to save OAuth mapping")?;
+        self.identities
+            .save_user(&user)
+            .context("Failed to save user")?;
 
         Ok(user)
     }
 
-    async fn exchange_code(&self, provider: OAuthProvider, code: String) -> Result<TokenResponse> {
+    /// Complete a re-authentication round-trip started with `get_reauth_url`.
+    ///
+    /// Returns a short-lived ticket accepted by link/unlink operations.
+    pub async fn handle_reauth_callback(&self, code: String, state: String) -> Result<String> {
+        let oauth_state = self.take_state(&state)?;
+        let user_id = match &oauth_state.purpose {
+            OAuthPurpose::Reauth { user_id } => user_id.clone(),
+            _ => anyhow::bail!("State was not issued for re-authentication"),
+        };
+        let (_, user_info) = self.authenticate(&oauth_state, code).await?;
+        let provider = oauth_state.provider;
+
+        let owner = self
+            .identities
+            .find_user(provider.as_str(), &user_info.id)?
+            .ok_or_else(|| anyhow::anyhow!("Identity is not linked to any account"))?;
+        if owner.id != user_id {
+            anyhow::bail!("Identity is not linked to this account");
+        }
+
+        self.identities.issue_reauth_ticket(&user_id)
+    }
+
+    /// Remove a linked identity after re-authentication
+    pub fn unlink_identity(
+        &self,
+        reauth_ticket: &str,
+        provider: &str,
+        provider_user_id: &str,
+    ) -> Result<User> {
+        self.identities
+            .unlink(reauth_ticket, provider, provider_user_id)
+    }
+
+    /// Roles derived from provider groups, or `None` when role sync is disabled
+    fn roles_for(
+        &self,
+        provider: &OAuthProvider,
+        user_info: &OAuthUserInfo,
+    ) -> Option<Vec<String>> {
+        let cfg = self.configs.get(provider)?;
+        if cfg.group_roles.is_empty() {
+            return None;
+        }
+        Some(cfg.roles_for_groups(&user_info.groups))
+    }
+
+    /// Verify and remove a pending state (CSRF protection). The state is removed in the same
+    /// step it is read, so it can only be redeemed once. Storage does not expire records: the
+    /// TTL is only enforced here, and abandoned states stay until [`Self::purge_expired_states`].
+    fn take_state(&self, state: &str) -> Result<OAuthState> {
+        let key = format!("oauth_state:{}", state);
+        let oauth_state: OAuthState = self
+            .storage
+            .take(key.as_bytes())
+            .context("Failed to load OAuth state")?
+            .ok_or_else(|| anyhow::anyhow!("Invalid or expired state"))?;
+
+        let age = chrono::Utc::now() - oauth_state.created_at;
+        if age > chrono::Duration::minutes(STATE_TTL_MINUTES) {
+            anyhow::bail!("State expired");
+        }
+
+        Ok(oauth_state)
+    }
+
+    /// Remove abandoned authorization states older than the TTL
+    pub fn purge_expired_states(&self) -> Result<usize> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::minutes(STATE_TTL_MINUTES);
+        let states: Vec<OAuthState> = self.storage.prefix_scan(b"oauth_state:")?;
+        let mut purged = 0;
+        for s in states.into_iter().filter(|s| s.created_at < cutoff) {
+            self.storage
+                .delete(format!("oauth_state:{}", s.state).as_bytes())?;
+            purged += 1;
+        }
+        debug!("Purged {} expired OAuth states", purged);
+        Ok(purged)
+    }
+
+    /// Exchange the code and establish the provider identity.
+    ///
+    /// OIDC providers are identified by the verified ID token; plain OAuth
+    /// providers fall back to the userinfo endpoint.
+    async fn authenticate(
+        &self,
+        oauth_state: &OAuthState,
+        code: String,
+    ) -> Result<(TokenResponse, OAuthUserInfo)> {
+        let provider = &oauth_state.provider;
+        let cfg = self
+            .configs
+            .get(provider)
+            .ok_or_else(|| anyhow::anyhow!("Provider not configured"))?;
+
+        // Exchange code for token
+        let token = self
+            .exchange_code(provider, code, &oauth_state.code_verifier)
+            .await?;
+
+        let user_info = match &cfg.issuer {
+            Some(issuer) => {
+                let id_token = token
+                    .id_token
+                    .as_deref()
+                    .ok_or_else(|| anyhow::anyhow!("Provider did not return an ID token"))?;
+                self.verify_id_token(cfg, issuer, id_token, oauth_state.nonce.as_deref())
+                    .await?
+                    .to_user_info(&cfg.claims)?
+            }
+            None => self.get_user_info(provider, &token.access_token).await?,
+        };
+        if user_info.id.is_empty() {
+            anyhow::bail!("Provider did not return a user id");
+        }
+
+        Ok((token, user_info))
+    }
+
+    async fn verify_id_token(
+        &self,
+        cfg: &OAuthConfig,
+        issuer: &str,
+        id_token: &str,
+        nonce: Option<&str>,
+    ) -> Result<oidc::IdTokenClaims> {
+        let jwks_url = cfg
+            .jwks_url
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("OIDC provider has no JWKS url"))?;
+
+        let cached = match self.jwks.read() {
+            Ok(cache) => cache.get(jwks_url).cloned(),
+            Err(e) => return Err(anyhow::anyhow!("jwks cache RwLock poisoned: {:?}", e)),
+        };
+        let jwks = match cached {
+            Some(jwks) if oidc::has_signing_key(id_token, &jwks)? => jwks,
+            // Unknown key or no cache yet: the provider may have rotated its keys
+            _ => self.refresh_jwks(jwks_url).await?,
+        };
+        oidc::verify_id_token(id_token, &jwks, issuer, &cfg.client_id, nonce)
+    }
+
+    /// Fetch and cache the key set, at most once per [`JWKS_REFETCH_INTERVAL`]. Only successful
+    /// fetches count, so a failed one is retried and reported instead of hidden behind the cache.
+    async fn refresh_jwks(&self, jwks_url: &str) -> Result<JwkSet> {
+        match self.jwks_fetched.lock() {
+            Ok(fetched) => {
+                if let Some(at) = fetched.get(jwks_url)
+                    && at.elapsed() < JWKS_REFETCH_INTERVAL
+                {
+                    anyhow::bail!("No matching key in JWKS");
+                }
+            }
+            Err(e) => return Err(anyhow::anyhow!("jwks fetch Mutex poisoned: {:?}", e)),
+        }
+
+        let jwks = self.fetch_jwks(jwks_url).await?;
+        match self.jwks.write() {
+            Ok(mut cache) => {
+                cache.insert(jwks_url.to_string(), jwks.clone());
+            }
+            Err(e) => return Err(anyhow::anyhow!("jwks cache RwLock poisoned: {:?}", e)),
+        }
+        match self.jwks_fetched.lock() {
+            Ok(mut fetched) => {
+                fetched.insert(jwks_url.to_string(), Instant::now());
+            }
+            Err(e) => return Err(anyhow::anyhow!("jwks fetch Mutex poisoned: {:?}", e)),
+        }
+        Ok(jwks)
+    }
+
+    async fn fetch_jwks(&self, jwks_url: &str) -> Result<JwkSet> {
+        let resp = self
+            .client
+            .get(jwks_url)
+            .send()
+            .await
+            .context("Failed to fetch JWKS")?;
+        if !resp.status().is_success() {
+            anyhow::bail!("JWKS fetch failed: {}", resp.status());
+        }
+        resp.json().await.context("Failed to parse JWKS")
+    }
+
+    async fn exchange_code(
+        &self,
+        provider: &OAuthProvider,
+        code: String,
+        code_verifier: &str,
+    ) -> Result<TokenResponse> {
         let config = self
             .configs
-            .get(&provider)
+            .get(provider)
             .ok_or_else(|| anyhow::anyhow!("Provider not configured"))?;
 
         let params = [
@@ -180,6 +452,7 @@ impl OAuthHandler {
             ("code", code),
             ("grant_type", "authorization_code".to_string()),
             ("redirect_uri", config.redirect_uri.clone()),
+            ("code_verifier", code_verifier.to_string()),
         ];
 
         let resp = self
@@ -237,12 +510,12 @@ impl OAuthHandler {
 
     async fn get_user_info(
         &self,
-        provider: OAuthProvider,
+        provider: &OAuthProvider,
         access_token: &str,
     ) -> Result<OAuthUserInfo> {
         let cfg = self
             .configs
-            .get(&provider)
+            .get(provider)
             .ok_or_else(|| anyhow::anyhow!("Provider not configured"))?;
         let resp = self
             .client
@@ -1,25 +1,87 @@
+use crate::oidc::OidcDiscovery;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// OAuth provider enumeration
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum OAuthProvider {
     Google,
     Discord,
     Twitch,
+    /// Deployment-specific OIDC provider (Keycloak, Authentik, ...)
+    Custom {
+        id: String,
+    },
 }
 
 impl OAuthProvider {
-    pub fn as_str(&self) -> &'static str {
+    /// Names reserved for built-in providers and local auth
+    pub const RESERVED_IDS: [&'static str; 4] = ["google", "discord", "twitch", "local"];
+
+    pub fn custom(id: impl Into<String>) -> Self {
+        OAuthProvider::Custom { id: id.into() }
+    }
+
+    pub fn as_str(&self) -> &str {
         match self {
             OAuthProvider::Google => "google",
             OAuthProvider::Discord => "discord",
             OAuthProvider::Twitch => "twitch",
+            OAuthProvider::Custom { id } => id,
+        }
+    }
+}
+
+/// Which claims carry the user's profile data.
+///
+/// Names may be dotted paths into nested claims, e.g. `realm_access.roles`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClaimMapping {
+    pub username: String,
+    pub email: String,
+    pub avatar: String,
+    pub groups: String,
+}
+
+impl Default for ClaimMapping {
+    fn default() -> Self {
+        Self {
+            username: "preferred_username".to_string(),
+            email: "email".to_string(),
+            avatar: "picture".to_string(),
+            groups: "groups".to_string(),
         }
     }
 }
 
+/// Deployment configuration for a custom OIDC provider, resolved via discovery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomOidcConfig {
+    pub id: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub claims: ClaimMapping,
+    /// Provider group name -> authorization role
+    #[serde(default)]
+    pub group_roles: HashMap<String, String>,
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["openid".into(), "email".into(), "profile".into()]
+}
+
+fn scopes(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
 /// OAuth provider configuration
 #[derive(Debug, Clone)]
 pub struct OAuthConfig {
@@ -30,6 +92,16 @@ pub struct OAuthConfig {
     pub auth_url: String,
     pub token_url: String,
     pub userinfo_url: String,
+    /// OIDC issuer; when set, the ID token is required and verified
+    pub issuer: Option<String>,
+    /// JWKS endpoint used to verify ID token signatures
+    pub jwks_url: Option<String>,
+    /// Scopes requested in the authorization URL
+    pub scopes: Vec<String>,
+    /// ID token claim names (OIDC providers only)
+    pub claims: ClaimMapping,
+    /// Provider group name -> authorization role; empty disables role sync
+    pub group_roles: HashMap<String, String>,
 }
 
 impl OAuthConfig {
@@ -42,6 +114,11 @@ impl OAuthConfig {
             auth_url: "https://accounts.google.com/o/oauth2/v2/auth".to_string(),
             token_url: "https://oauth2.googleapis.com/token".to_string(),
             userinfo_url: "https://www.googleapis.com/oauth2/v2/userinfo".to_string(),
+            issuer: Some("https://accounts.google.com".to_string()),
+            jwks_url: Some("https://www.googleapis.com/oauth2/v3/certs".to_string()),
+            scopes: scopes(&["openid", "email", "profile"]),
+            claims: ClaimMapping::default(),
+            group_roles: HashMap::new(),
         }
     }
 
@@ -54,6 +131,11 @@ impl OAuthConfig {
             auth_url: "https://discord.com/api/oauth2/authorize".to_string(),
             token_url: "https://discord.com/api/oauth2/token".to_string(),
             userinfo_url: "https://discord.com/api/users/@me".to_string(),
+            issuer: None,
+            jwks_url: None,
+            scopes: scopes(&["identify", "email"]),
+            claims: ClaimMapping::default(),
+            group_roles: HashMap::new(),
         }
     }
 
@@ -66,8 +148,81 @@ impl OAuthConfig {
             auth_url: "https://id.twitch.tv/oauth2/authorize".to_string(),
             token_url: "https://id.twitch.tv/oauth2/token".to_string(),
             userinfo_url: "https://api.twitch.tv/helix/users".to_string(),
+            issuer: None,
+            jwks_url: None,
+            scopes: scopes(&["user:read:email"]),
+            claims: ClaimMapping::default(),
+            group_roles: HashMap::new(),
         }
     }
+
+    /// Build an OIDC config from discovery metadata
+    pub fn from_discovery(
+        provider: OAuthProvider,
+        client_id: String,
+        client_secret: String,
+        redirect_uri: String,
+        discovery: &OidcDiscovery,
+    ) -> Self {
+        Self {
+            provider,
+            client_id,
+            client_secret,
+            redirect_uri,
+            auth_url: discovery.authorization_endpoint.clone(),
+            token_url: discovery.token_endpoint.clone(),
+            userinfo_url: discovery.userinfo_endpoint.clone().unwrap_or_default(),
+            issuer: Some(discovery.issuer.clone()),
+            jwks_url: Some(discovery.jwks_uri.clone()),
+            scopes: default_oidc_scopes(),
+            claims: ClaimMapping::default(),
+            group_roles: HashMap::new(),
+        }
+    }
+
+    /// Build the config for a custom OIDC provider from its discovery metadata
+    pub fn custom(cfg: CustomOidcConfig, discovery: &OidcDiscovery) -> Self {
+        Self {
+            scopes: cfg.scopes,
+            claims: cfg.claims,
+            group_roles: cfg.group_roles,
+            ..Self::from_discovery(
+                OAuthProvider::Custom { id: cfg.id },
+                cfg.client_id,
+                cfg.client_secret,
+                cfg.redirect_uri,
+                discovery,
+            )
+        }
+    }
+
+    /// Map provider groups to authorization roles (sorted, de-duplicated)
+    pub fn roles_for_groups(&self, groups: &[String]) -> Vec<String> {
+        let mut roles: Vec<String> = groups
+            .iter()
+            .filter_map(|g| self.group_roles.get(g).cloned())
+            .collect();
+        roles.sort();
+        roles.dedup();
+        roles
+    }
+
+    pub fn is_oidc(&self) -> bool {
+        self.issuer.is_some()
+    }
+}
+
+/// What an authorization round-trip is for
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum OAuthPurpose {
+    /// Sign in, creating an account for unknown identities
+    #[default]
+    Login,
+    /// Link the provider identity to an existing account
+    Link { user_id: String },
+    /// Prove control of an identity already linked to the account
+    Reauth { user_id: String },
 }
 
 /// OAuth state (CSRF protection)
@@ -76,6 +231,14 @@ pub struct OAuthState {
     pub state: String,
     pub provider: OAuthProvider,
     pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub purpose: OAuthPurpose,
+    /// PKCE code verifier sent with the token request
+    #[serde(default)]
+    pub code_verifier: String,
+    /// OIDC nonce expected in the ID token
+    #[serde(default)]
+    pub nonce: Option<String>,
 }
 
 /// Token response
@@ -85,6 +248,8 @@ pub struct TokenResponse {
     pub refresh_token: Option<String>,
     pub expires_in: i64,
     pub token_type: String,
+    #[serde(default)]
+    pub id_token: Option<String>,
 }
 
 /// User info from OAuth provider
@@ -98,4 +263,8 @@ pub struct OAuthUserInfo {
     pub name: Option<String>,
     #[serde(default)]
     pub username: Option<String>,
+    #[serde(default)]
+    pub avatar: Option<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
 }
@@ -0,0 +1,138 @@
+use crate::oauth_types::{ClaimMapping, OAuthUserInfo};
+use anyhow::{Context, Result};
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Header, Validation, decode, decode_header};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Subset of the OpenID Provider metadata (`/.well-known/openid-configuration`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcDiscovery {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    #[serde(default)]
+    pub userinfo_endpoint: Option<String>,
+    pub jwks_uri: String,
+    #[serde(default)]
+    pub scopes_supported: Vec<String>,
+}
+
+impl OidcDiscovery {
+    /// Well-known discovery URL for an issuer
+    pub fn url_for(issuer: &str) -> String {
+        format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        )
+    }
+}
+
+/// Standard ID token claims used for sign-in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub exp: i64,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: Option<bool>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub preferred_username: Option<String>,
+    /// Any further claims (groups, picture, provider-specific data)
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+impl IdTokenClaims {
+    /// Resolve provider profile data using the configured claim names
+    pub fn to_user_info(&self, mapping: &ClaimMapping) -> Result<OAuthUserInfo> {
+        let value = serde_json::to_value(self).context("Failed to serialize claims")?;
+        let string = |path: &str| {
+            claim(&value, path)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        let groups = match claim(&value, &mapping.groups) {
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect(),
+            Some(Value::String(group)) => vec![group.clone()],
+            _ => Vec::new(),
+        };
+
+        Ok(OAuthUserInfo {
+            id: self.sub.clone(),
+            email: string(&mapping.email),
+            name: self.name.clone(),
+            username: string(&mapping.username),
+            avatar: string(&mapping.avatar),
+            groups,
+        })
+    }
+}
+
+/// Look up a claim by dotted path (e.g. `realm_access.roles`)
+pub fn claim<'a>(claims: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(claims, |v, key| v.get(key))
+}
+
+/// The key in `jwks` an ID token with this header is signed with: the one named by `kid`, or
+/// the only key when the token names none
+fn signing_key<'a>(header: &Header, jwks: &'a JwkSet) -> Option<&'a Jwk> {
+    match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+}
+
+/// Whether `jwks` holds the key `id_token` names. A token naming an unknown key may have been
+/// signed with a rotated key, which is the only reason to fetch the key set again.
+pub fn has_signing_key(id_token: &str, jwks: &JwkSet) -> Result<bool> {
+    let header = decode_header(id_token).context("Malformed ID token header")?;
+    Ok(signing_key(&header, jwks).is_some())
+}
+
+/// Verify an ID token signature against a JWKS and check `iss`, `aud`, `exp` and `nonce`
+pub fn verify_id_token(
+    id_token: &str,
+    jwks: &JwkSet,
+    issuer: &str,
+    client_id: &str,
+    expected_nonce: Option<&str>,
+) -> Result<IdTokenClaims> {
+    let header = decode_header(id_token).context("Malformed ID token header")?;
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        anyhow::bail!("ID token uses a symmetric algorithm");
+    }
+
+    let jwk =
+        signing_key(&header, jwks).ok_or_else(|| anyhow::anyhow!("No matching key in JWKS"))?;
+    let key = DecodingKey::from_jwk(jwk).context("Unsupported JWK")?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let data = decode::<IdTokenClaims>(id_token, &key, &validation)
+        .context("ID token verification failed")?;
+
+    if let Some(expected) = expected_nonce
+        && data.claims.nonce.as_deref() != Some(expected)
+    {
+        anyhow::bail!("ID token nonce mismatch");
+    }
+
+    Ok(data.claims)
+}
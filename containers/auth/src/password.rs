@@ -0,0 +1,182 @@
+use anyhow::{Context, Result};
+use argon2::{
+    Algorithm, Argon2, Params, Version,
+    password_hash::{PasswordHash, PasswordHasher, SaltString},
+};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::path::{Path, PathBuf};
+
+/// Password policy applied on registration and password change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasswordPolicy {
+    /// Minimum password length in characters
+    pub min_length: usize,
+    /// Maximum password length in characters (guards against hashing DoS)
+    pub max_length: usize,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    /// Reject passwords that contain the username (case-insensitive)
+    pub disallow_username: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            require_uppercase: false,
+            require_lowercase: false,
+            require_digit: false,
+            require_symbol: false,
+            disallow_username: true,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Stricter policy for production deployments
+    pub fn strict() -> Self {
+        Self {
+            min_length: 12,
+            require_uppercase: true,
+            require_lowercase: true,
+            require_digit: true,
+            require_symbol: true,
+            ..Default::default()
+        }
+    }
+
+    /// Validate a password against this policy
+    pub fn validate(&self, username: &str, password: &str) -> Result<()> {
+        let len = password.chars().count();
+        if len < self.min_length {
+            anyhow::bail!("Password must be at least {} characters", self.min_length);
+        }
+        if len > self.max_length {
+            anyhow::bail!("Password must be at most {} characters", self.max_length);
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            anyhow::bail!("Password must contain an uppercase letter");
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            anyhow::bail!("Password must contain a lowercase letter");
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            anyhow::bail!("Password must contain a digit");
+        }
+        if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+            anyhow::bail!("Password must contain a symbol");
+        }
+        if self.disallow_username
+            && !username.is_empty()
+            && password.to_lowercase().contains(&username.to_lowercase())
+        {
+            anyhow::bail!("Password must not contain the username");
+        }
+        Ok(())
+    }
+}
+
+/// Argon2id cost parameters used for new password hashes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Argon2Config {
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    /// Number of iterations
+    pub iterations: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Config {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl Argon2Config {
+    /// Build an Argon2id hasher with these parameters
+    pub fn hasher(&self) -> Result<Argon2<'static>> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|e| anyhow::anyhow!("Invalid Argon2 parameters: {}", e))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+
+    /// Hash a password with a fresh salt
+    pub fn hash_password(&self, password: &str) -> Result<String> {
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(self
+            .hasher()?
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| anyhow::anyhow!(e))?
+            .to_string())
+    }
+
+    /// Whether an existing hash was produced with different parameters and should be rehashed
+    pub fn needs_rehash(&self, hash: &PasswordHash<'_>) -> bool {
+        if hash.algorithm != Algorithm::Argon2id.ident() {
+            return true;
+        }
+        match Params::try_from(hash) {
+            Ok(p) => {
+                p.m_cost() != self.memory_kib
+                    || p.t_cost() != self.iterations
+                    || p.p_cost() != self.parallelism
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+/// Offline breached-password check using k-anonymity hash-prefix files.
+///
+/// The directory holds one file per 5-character SHA-1 prefix (upper-case hex,
+/// optionally with a `.txt` extension), each line being `SUFFIX:COUNT` as served
+/// by the Have I Been Pwned range API. Only the matching range file is read.
+#[derive(Debug, Clone)]
+pub struct BreachedPasswordList {
+    dir: PathBuf,
+}
+
+impl BreachedPasswordList {
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref();
+        if !dir.is_dir() {
+            anyhow::bail!("Breached password directory not found: {:?}", dir);
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    /// Returns true if the password appears in the breach corpus
+    pub fn is_breached(&self, password: &str) -> Result<bool> {
+        let digest = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = digest.split_at(5);
+
+        let mut path = self.dir.join(prefix);
+        if !path.exists() {
+            path = self.dir.join(format!("{}.txt", prefix));
+            if !path.exists() {
+                return Ok(false);
+            }
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read breach range file {:?}", path))?;
+        Ok(content.lines().any(|line| {
+            let entry = line.split(':').next().unwrap_or("").trim();
+            entry.eq_ignore_ascii_case(suffix)
+        }))
+    }
+}
@@ -16,6 +16,28 @@ pub enum AuthMethod {
         refresh_token: Option<String>,
         expires_at: DateTime<Utc>,
     },
+    /// Non-interactive service account (personal access tokens only)
+    Service,
+}
+
+/// Whether an account belongs to a person or an automation
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountKind {
+    #[default]
+    Human,
+    /// Bots and scripts; cannot log in interactively
+    Service,
+}
+
+/// An external or local identity linked to a user account
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LinkedIdentity {
+    /// Provider name (`local` for username/password, otherwise the OAuth provider)
+    pub provider: String,
+    /// Provider-side subject identifier (the username for `local`)
+    pub provider_user_id: String,
+    pub linked_at: DateTime<Utc>,
 }
 
 /// License tier
@@ -34,12 +56,49 @@ pub struct User {
     pub email: Option<String>,
     /// Password hash (Argon2) - only for local auth
     pub password_hash: Option<String>,
+    /// Method the account was originally created with
     pub auth_method: AuthMethod,
+    /// All identities that can sign in to this account
+    #[serde(default)]
+    pub identities: Vec<LinkedIdentity>,
+    /// Authorization roles synced from identity provider groups
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub kind: AccountKind,
     pub license: License,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl User {
+    pub fn is_service_account(&self) -> bool {
+        self.kind == AccountKind::Service
+    }
+
+    pub fn has_identity(&self, provider: &str) -> bool {
+        self.identities.iter().any(|i| i.provider == provider)
+    }
+
+    /// Password hash usable for local login, if a local identity is linked.
+    ///
+    /// Accounts created before identity linking have no `identities` and fall
+    /// back to the legacy `AuthMethod::Local` hash.
+    pub fn local_password_hash(&self) -> Option<&str> {
+        if self.identities.is_empty() {
+            return match &self.auth_method {
+                AuthMethod::Local { password_hash, .. } => Some(password_hash),
+                _ => None,
+            };
+        }
+        if self.has_identity(crate::identity::LOCAL_PROVIDER) {
+            self.password_hash.as_deref()
+        } else {
+            None
+        }
+    }
+}
+
 /// Session
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
@@ -0,0 +1,83 @@
+#![allow(clippy::disallowed_methods)]
+use tempfile::TempDir;
+use verseguy_auth::License;
+use verseguy_auth::access_token::{AccessTokenService, TOKEN_PREFIX};
+use verseguy_auth::local::LocalAuth;
+use verseguy_storage::RocksDBStorage;
+use verseguy_test_utils::must;
+
+#[tokio::test]
+async fn create_validate_and_revoke_token() {
+    let temp = must(TempDir::new());
+    let storage = must(RocksDBStorage::open(temp.path()));
+    let auth = LocalAuth::new(storage.clone());
+    let tokens = AccessTokenService::new(storage);
+
+    let user = must(
+        auth.register("scripter".to_string(), "password123".to_string())
+            .await,
+    );
+
+    let (plain, record) = must(tokens.create(
+        &user.id,
+        "ci",
+        vec!["plugins:publish".to_string()],
+        Some(30),
+    ));
+    assert!(plain.starts_with(TOKEN_PREFIX));
+    assert!(!record.token_hash.contains(&plain));
+    assert!(record.has_scope("plugins:publish"));
+    assert!(!record.has_scope("users:delete"));
+
+    let validated = must(tokens.validate(&plain));
+    assert_eq!(validated.user_id, user.id);
+    assert!(validated.last_used_at.is_some());
+
+    let listed = must(tokens.list(&user.id));
+    assert_eq!(listed.len(), 1);
+    assert!(listed[0].last_used_at.is_some());
+
+    must(tokens.revoke(&user.id, &record.id));
+    assert!(tokens.validate(&plain).is_err());
+}
+
+#[tokio::test]
+async fn expired_and_foreign_tokens_are_rejected() {
+    let temp = must(TempDir::new());
+    let storage = must(RocksDBStorage::open(temp.path()));
+    let auth = LocalAuth::new(storage.clone());
+    let tokens = AccessTokenService::new(storage);
+
+    let alice = must(
+        auth.register("alice".to_string(), "password123".to_string())
+            .await,
+    );
+    let bob = must(
+        auth.register("bobby".to_string(), "password123".to_string())
+            .await,
+    );
+
+    let (plain, record) = must(tokens.create(&alice.id, "old", vec!["*".to_string()], Some(-1)));
+    assert!(tokens.validate(&plain).is_err());
+
+    // Bob cannot revoke Alice's token
+    assert!(tokens.revoke(&bob.id, &record.id).is_err());
+    assert!(tokens.validate("vgp_not-a-real-token").is_err());
+}
+
+#[tokio::test]
+async fn service_accounts_cannot_log_in_interactively() {
+    let temp = must(TempDir::new());
+    let storage = must(RocksDBStorage::open(temp.path()));
+    let auth = LocalAuth::new(storage.clone());
+    let tokens = AccessTokenService::new(storage);
+
+    let bot = must(tokens.create_service_account("release-bot", License::Pro));
+    assert!(bot.is_service_account());
+    assert!(auth.login("release-bot", "").await.is_err());
+    assert!(auth.reauthenticate(&bot.id, "").await.is_err());
+
+    let (plain, _) = must(tokens.create(&bot.id, "deploy", vec!["*".to_string()], None));
+    assert_eq!(must(tokens.validate(&plain)).user_id, bot.id);
+    assert_eq!(must(tokens.list_service_accounts()).len(), 1);
+}
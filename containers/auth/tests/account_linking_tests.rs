@@ -0,0 +1,85 @@
+#![allow(clippy::disallowed_methods)]
+use tempfile::TempDir;
+use verseguy_auth::IdentityStore;
+use verseguy_auth::local::LocalAuth;
+use verseguy_storage::RocksDBStorage;
+use verseguy_test_utils::{must, must_opt};
+
+#[tokio::test]
+async fn register_indexes_local_identity() {
+    let temp = must(TempDir::new());
+    let storage = must(RocksDBStorage::open(temp.path()));
+    let auth = LocalAuth::new(storage.clone());
+
+    let user = must(
+        auth.register("linker".to_string(), "password123".to_string())
+            .await,
+    );
+    assert!(user.has_identity("local"));
+
+    let identities = IdentityStore::new(storage);
+    let found = must_opt(must(identities.find_user("local", "linker")), "not indexed");
+    assert_eq!(found.id, user.id);
+}
+
+#[tokio::test]
+async fn reauthentication_requires_correct_password() {
+    let temp = must(TempDir::new());
+    let storage = must(RocksDBStorage::open(temp.path()));
+    let auth = LocalAuth::new(storage);
+
+    let user = must(
+        auth.register("reauth".to_string(), "password123".to_string())
+            .await,
+    );
+
+    assert!(
+        auth.reauthenticate(&user.id, "wrongpassword")
+            .await
+            .is_err()
+    );
+    must(auth.reauthenticate(&user.id, "password123").await);
+}
+
+#[tokio::test]
+async fn cannot_unlink_last_identity_and_tickets_are_single_use() {
+    let temp = must(TempDir::new());
+    let storage = must(RocksDBStorage::open(temp.path()));
+    let auth = LocalAuth::new(storage);
+
+    let user = must(
+        auth.register("lonely".to_string(), "password123".to_string())
+            .await,
+    );
+
+    let ticket = must(auth.reauthenticate(&user.id, "password123").await);
+    assert!(
+        auth.unlink_identity(&ticket, "local", "lonely")
+            .await
+            .is_err()
+    );
+
+    // The failed attempt still consumed the ticket
+    assert!(
+        auth.unlink_identity(&ticket, "local", "lonely")
+            .await
+            .is_err()
+    );
+
+    // Password login is unaffected
+    must(auth.login("lonely", "password123").await);
+}
+
+#[tokio::test]
+async fn link_password_rejects_account_with_local_password() {
+    let temp = must(TempDir::new());
+    let storage = must(RocksDBStorage::open(temp.path()));
+    let auth = LocalAuth::new(storage);
+
+    let user = must(
+        auth.register("haspass".to_string(), "password123".to_string())
+            .await,
+    );
+    let ticket = must(auth.reauthenticate(&user.id, "password123").await);
+    assert!(auth.link_password(&ticket, "another-secret").await.is_err());
+}
@@ -0,0 +1,97 @@
+#![allow(clippy::disallowed_methods)]
+use serde_json::json;
+use tempfile::tempdir;
+use verseguy_auth::oauth_types::{ClaimMapping, CustomOidcConfig, OAuthConfig, OAuthProvider};
+use verseguy_auth::{IdTokenClaims, OAuthHandler, OidcDiscovery};
+use verseguy_storage::Storage;
+use verseguy_test_utils::must;
+
+fn keycloak_config() -> CustomOidcConfig {
+    must(serde_json::from_value(json!({
+        "id": "keycloak",
+        "issuer": "https://sso.example.com/realms/org",
+        "client_id": "verseguy",
+        "client_secret": "secret",
+        "redirect_uri": "https://localhost/callback",
+        "scopes": ["openid", "profile", "roles"],
+        "claims": { "groups": "realm_access.roles", "avatar": "avatar_url" },
+        "group_roles": { "fleet-admins": "admin", "pilots": "member" }
+    })))
+}
+
+fn discovery() -> OidcDiscovery {
+    OidcDiscovery {
+        issuer: "https://sso.example.com/realms/org".to_string(),
+        authorization_endpoint: "https://sso.example.com/realms/org/auth".to_string(),
+        token_endpoint: "https://sso.example.com/realms/org/token".to_string(),
+        userinfo_endpoint: None,
+        jwks_uri: "https://sso.example.com/realms/org/certs".to_string(),
+        scopes_supported: Vec::new(),
+    }
+}
+
+#[test]
+fn claim_mapping_supports_nested_group_claims() {
+    let cfg = OAuthConfig::custom(keycloak_config(), &discovery());
+    assert_eq!(cfg.provider, OAuthProvider::custom("keycloak"));
+    assert_eq!(cfg.claims.username, ClaimMapping::default().username);
+
+    let claims: IdTokenClaims = must(serde_json::from_value(json!({
+        "iss": "https://sso.example.com/realms/org",
+        "sub": "kc-1",
+        "exp": 0,
+        "preferred_username": "pilot1",
+        "email": "pilot1@example.com",
+        "avatar_url": "https://cdn.example.com/p1.png",
+        "realm_access": { "roles": ["pilots", "fleet-admins", "offline_access"] }
+    })));
+    let info = must(claims.to_user_info(&cfg.claims));
+    assert_eq!(info.id, "kc-1");
+    assert_eq!(info.username.as_deref(), Some("pilot1"));
+    assert_eq!(
+        info.avatar.as_deref(),
+        Some("https://cdn.example.com/p1.png")
+    );
+    assert_eq!(info.groups.len(), 3);
+
+    assert_eq!(cfg.roles_for_groups(&info.groups), vec!["admin", "member"]);
+}
+
+#[tokio::test]
+async fn custom_provider_uses_configured_scopes() {
+    let dir = must(tempdir());
+    let storage = must(Storage::open(dir.path()));
+
+    let mut handler = OAuthHandler::new(storage);
+    handler.register_provider(OAuthConfig::custom(keycloak_config(), &discovery()));
+
+    let url = must(url::Url::parse(&must(
+        handler.get_auth_url(OAuthProvider::custom("keycloak")),
+    )));
+    assert!(
+        url.as_str()
+            .starts_with("https://sso.example.com/realms/org/auth")
+    );
+    let scope = url
+        .query_pairs()
+        .find(|(k, _)| k == "scope")
+        .map(|(_, v)| v.to_string());
+    assert_eq!(scope.as_deref(), Some("openid profile roles"));
+
+    assert!(
+        handler
+            .get_auth_url(OAuthProvider::custom("other"))
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn reserved_custom_ids_are_rejected() {
+    let dir = must(tempdir());
+    let storage = must(Storage::open(dir.path()));
+    let mut handler = OAuthHandler::new(storage);
+
+    let mut cfg = keycloak_config();
+    cfg.id = "google".to_string();
+    assert!(handler.register_custom_provider(cfg).await.is_err());
+}
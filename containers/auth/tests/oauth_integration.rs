@@ -16,6 +16,7 @@ async fn test_oauth_flow_with_mock() {
     };
 
     let _m1 = server.mock("POST", "/token")
+        .match_body(mockito::Matcher::Regex("code_verifier=".to_string()))
         .with_status(200)
         .with_header("content-type", "application/json")
         .with_body(r#"{ "access_token": "mock_at", "refresh_token": "mock_rt", "expires_in": 3600, "token_type": "Bearer" }"#)
@@ -36,13 +37,18 @@ async fn test_oauth_flow_with_mock() {
         auth_url: format!("{}/auth", server.url()),
         token_url: format!("{}/token", server.url()),
         userinfo_url: format!("{}/userinfo", server.url()),
+        issuer: None,
+        jwks_url: None,
+        scopes: vec!["openid".to_string()],
+        claims: Default::default(),
+        group_roles: Default::default(),
     };
 
     let dir = must(tempdir());
     let storage = must(Storage::open(dir.path()));
 
-    let mut handler = OAuthHandler::new(storage);
-    handler.register_provider(cfg);
+    let mut handler = OAuthHandler::new(storage.clone());
+    handler.register_provider(cfg.clone());
 
     // get url and extract state
     let url = must(handler.get_auth_url(OAuthProvider::Google));
@@ -58,6 +64,10 @@ async fn test_oauth_flow_with_mock() {
         "state param missing",
     );
 
+    // State is persisted, so a fresh handler (e.g. after restart) can complete the flow
+    let mut handler = OAuthHandler::new(storage);
+    handler.register_provider(cfg);
+
     // simulate callback with code (code first, state second)
     let user = match handler.handle_callback("fakecode".to_string(), state).await {
         Ok(u) => u,
@@ -67,3 +77,99 @@ async fn test_oauth_flow_with_mock() {
     let email = must_opt(user.email, "user email missing");
     assert_eq!(email, "test@example.com");
 }
+
+fn state_param(url: &str) -> String {
+    let parsed = match url::Url::parse(url) {
+        Ok(u) => u,
+        Err(e) => panic!("failed to parse url: {}", e),
+    };
+    must_opt(
+        parsed
+            .query_pairs()
+            .find(|(k, _)| k == "state")
+            .map(|(_, v)| v.to_string()),
+        "state param missing",
+    )
+}
+
+#[tokio::test]
+async fn test_link_oauth_identity_to_local_account() {
+    let mut server = match tokio::task::spawn_blocking(|| Server::new()).await {
+        Ok(s) => s,
+        Err(e) => panic!("failed to create mock server: {}", e),
+    };
+
+    let _m1 = server.mock("POST", "/token")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{ "access_token": "mock_at", "refresh_token": "mock_rt", "expires_in": 3600, "token_type": "Bearer" }"#)
+        .create();
+
+    let _m2 = server
+        .mock("GET", "/userinfo")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{ "id": "discord-42", "email": "link@example.com", "name": "Linker" }"#)
+        .create();
+
+    let cfg = OAuthConfig {
+        provider: OAuthProvider::Discord,
+        client_id: "cid".to_string(),
+        client_secret: "csecret".to_string(),
+        redirect_uri: "http://localhost/callback".to_string(),
+        auth_url: format!("{}/auth", server.url()),
+        token_url: format!("{}/token", server.url()),
+        userinfo_url: format!("{}/userinfo", server.url()),
+        issuer: None,
+        jwks_url: None,
+        scopes: vec!["openid".to_string()],
+        claims: Default::default(),
+        group_roles: Default::default(),
+    };
+
+    let dir = must(tempdir());
+    let storage = must(Storage::open(dir.path()));
+
+    let local = verseguy_auth::LocalAuth::new(storage.clone());
+    let user = must(
+        local
+            .register("localuser".to_string(), "password123".to_string())
+            .await,
+    );
+
+    let mut handler = OAuthHandler::new(storage);
+    handler.register_provider(cfg);
+
+    // Linking requires a re-authentication ticket
+    assert!(
+        handler
+            .get_link_url(OAuthProvider::Discord, "bogus")
+            .is_err()
+    );
+
+    let ticket = must(local.reauthenticate(&user.id, "password123").await);
+    let url = must(handler.get_link_url(OAuthProvider::Discord, &ticket));
+    let linked = must(
+        handler
+            .handle_callback("fakecode".to_string(), state_param(&url))
+            .await,
+    );
+    assert_eq!(linked.id, user.id);
+    assert!(linked.has_identity("discord"));
+
+    // Logging in with Discord now resolves to the local account
+    let url = must(handler.get_auth_url(OAuthProvider::Discord));
+    let logged = must(
+        handler
+            .handle_callback("fakecode".to_string(), state_param(&url))
+            .await,
+    );
+    assert_eq!(logged.id, user.id);
+
+    // Password login still works and the local identity can now be unlinked
+    must(local.login("localuser", "password123").await);
+    let ticket = must(local.reauthenticate(&user.id, "password123").await);
+    let after = must(local.unlink_identity(&ticket, "local", "localuser").await);
+    assert!(!after.has_identity("local"));
+    assert!(local.login("localuser", "password123").await.is_err());
+}
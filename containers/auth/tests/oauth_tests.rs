@@ -42,3 +42,43 @@ async fn test_get_auth_url_providers() {
         Ok(_) => panic!("expected Err for missing provider"),
     }
 }
+
+#[tokio::test]
+async fn test_auth_url_uses_pkce_and_oidc_nonce() {
+    let dir = must(tempdir());
+    let storage = must(Storage::open(dir.path()));
+
+    let mut handler = OAuthHandler::new(storage);
+    handler.register_provider(OAuthConfig::google(
+        "google-client-id".to_string(),
+        "google-secret".to_string(),
+        "https://localhost/callback".to_string(),
+    ));
+    handler.register_provider(OAuthConfig::discord(
+        "discord-client-id".to_string(),
+        "discord-secret".to_string(),
+        "https://localhost/callback".to_string(),
+    ));
+
+    let google = must(url::Url::parse(&must(
+        handler.get_auth_url(OAuthProvider::Google),
+    )));
+    let params: std::collections::HashMap<_, _> = google.query_pairs().into_owned().collect();
+    assert_eq!(
+        params.get("code_challenge_method").map(String::as_str),
+        Some("S256")
+    );
+    assert!(params.contains_key("code_challenge"));
+    assert!(params.contains_key("nonce"));
+
+    // Plain OAuth providers still get PKCE but no nonce
+    let discord = must(url::Url::parse(&must(
+        handler.get_auth_url(OAuthProvider::Discord),
+    )));
+    let params: std::collections::HashMap<_, _> = discord.query_pairs().into_owned().collect();
+    assert!(params.contains_key("code_challenge"));
+    assert!(!params.contains_key("nonce"));
+
+    // Fresh states are not purged
+    assert_eq!(must(handler.purge_expired_states()), 0);
+}
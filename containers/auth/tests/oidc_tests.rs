@@ -0,0 +1,104 @@
+#![allow(clippy::disallowed_methods)]
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde_json::json;
+use verseguy_auth::oidc::{has_signing_key, verify_id_token};
+use verseguy_auth::pkce::{PkcePair, challenge_s256};
+use verseguy_test_utils::must;
+
+const ISSUER: &str = "https://idp.example.com";
+const CLIENT_ID: &str = "verseguy";
+
+#[test]
+fn pkce_s256_matches_rfc7636_vector() {
+    assert_eq!(
+        challenge_s256("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+        "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+    );
+
+    let pair = PkcePair::generate();
+    assert_eq!(pair.verifier.len(), 43);
+    assert_eq!(pair.challenge, challenge_s256(&pair.verifier));
+}
+
+fn signing_key() -> (EncodingKey, JwkSet) {
+    let pkcs8 = must(Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()));
+    let pair = must(Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()));
+    let jwks: JwkSet = must(serde_json::from_value(json!({
+        "keys": [{
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": URL_SAFE_NO_PAD.encode(pair.public_key().as_ref()),
+            "kid": "k1",
+            "alg": "EdDSA",
+            "use": "sig"
+        }]
+    })));
+    (EncodingKey::from_ed_der(pkcs8.as_ref()), jwks)
+}
+
+fn id_token(key: &EncodingKey, aud: &str, nonce: &str) -> String {
+    let mut header = Header::new(Algorithm::EdDSA);
+    header.kid = Some("k1".to_string());
+    let claims = json!({
+        "iss": ISSUER,
+        "aud": aud,
+        "sub": "subject-1",
+        "exp": chrono::Utc::now().timestamp() + 300,
+        "nonce": nonce,
+        "email": "oidc@example.com",
+    });
+    must(encode(&header, &claims, key))
+}
+
+#[test]
+fn verifies_valid_id_token() {
+    let (key, jwks) = signing_key();
+    let token = id_token(&key, CLIENT_ID, "n-1");
+
+    let claims = must(verify_id_token(
+        &token,
+        &jwks,
+        ISSUER,
+        CLIENT_ID,
+        Some("n-1"),
+    ));
+    assert_eq!(claims.sub, "subject-1");
+    assert_eq!(claims.email.as_deref(), Some("oidc@example.com"));
+}
+
+#[test]
+fn rejects_wrong_nonce_audience_and_key() {
+    let (key, jwks) = signing_key();
+
+    let token = id_token(&key, CLIENT_ID, "n-1");
+    assert!(verify_id_token(&token, &jwks, ISSUER, CLIENT_ID, Some("other")).is_err());
+    assert!(verify_id_token(&token, &jwks, "https://evil.example.com", CLIENT_ID, None).is_err());
+
+    let token = id_token(&key, "someone-else", "n-1");
+    assert!(verify_id_token(&token, &jwks, ISSUER, CLIENT_ID, Some("n-1")).is_err());
+
+    // Token signed by a key that is not in the JWKS
+    let (other_key, _) = signing_key();
+    let token = id_token(&other_key, CLIENT_ID, "n-1");
+    assert!(verify_id_token(&token, &jwks, ISSUER, CLIENT_ID, Some("n-1")).is_err());
+}
+
+#[test]
+fn finds_the_signing_key_by_kid() {
+    let (key, jwks) = signing_key();
+    let token = id_token(&key, CLIENT_ID, "n-1");
+    assert!(must(has_signing_key(&token, &jwks)));
+
+    // A rotated key set no longer holds `k1`
+    let (_, mut rotated) = signing_key();
+    for k in &mut rotated.keys {
+        k.common.key_id = Some("k2".to_string());
+    }
+    assert!(!must(has_signing_key(&token, &rotated)));
+    assert!(has_signing_key("not-a-jwt", &jwks).is_err());
+}
@@ -0,0 +1,114 @@
+#![allow(clippy::disallowed_methods)]
+use sha1::{Digest, Sha1};
+use tempfile::TempDir;
+use verseguy_auth::local::{LocalAuth, LocalAuthConfig};
+use verseguy_auth::password::{Argon2Config, BreachedPasswordList, PasswordPolicy};
+use verseguy_auth::{AuthMethod, User};
+use verseguy_storage::RocksDBStorage;
+use verseguy_test_utils::must;
+
+#[test]
+fn strict_policy_rejects_weak_passwords() {
+    let policy = PasswordPolicy::strict();
+
+    assert!(policy.validate("alice", "Sh0rt!").is_err());
+    assert!(policy.validate("alice", "alllowercase1!x").is_err());
+    assert!(policy.validate("alice", "NoDigitsHere!!").is_err());
+    assert!(policy.validate("alice", "NoSymbols12345").is_err());
+    assert!(policy.validate("alice", "Alice-Passw0rd!").is_err());
+    must(policy.validate("alice", "C0rrect-Horse-Battery"));
+}
+
+#[test]
+fn policy_enforces_max_length() {
+    let policy = PasswordPolicy {
+        max_length: 16,
+        ..Default::default()
+    };
+    assert!(policy.validate("bob", &"x".repeat(17)).is_err());
+    must(policy.validate("bob", &"x".repeat(16)));
+}
+
+#[tokio::test]
+async fn register_rejects_breached_password() {
+    let temp = must(TempDir::new());
+    let storage = must(RocksDBStorage::open(temp.path().join("db")));
+
+    // Build a single range file containing "password123"
+    let range_dir = temp.path().join("ranges");
+    must(std::fs::create_dir_all(&range_dir));
+    let digest = hex::encode_upper(Sha1::digest(b"password123"));
+    let (prefix, suffix) = digest.split_at(5);
+    must(std::fs::write(
+        range_dir.join(prefix),
+        format!("0000000000000000000000000000000000A:1\n{}:42\n", suffix),
+    ));
+
+    let auth = LocalAuth::with_config(
+        storage,
+        LocalAuthConfig {
+            breached: Some(must(BreachedPasswordList::open(&range_dir))),
+            ..Default::default()
+        },
+    );
+
+    assert!(
+        auth.register("carol".to_string(), "password123".to_string())
+            .await
+            .is_err()
+    );
+    must(
+        auth.register("carol".to_string(), "unlisted-passphrase".to_string())
+            .await,
+    );
+}
+
+#[tokio::test]
+async fn login_rehashes_when_argon2_params_change() {
+    let temp = must(TempDir::new());
+    let storage = must(RocksDBStorage::open(temp.path()));
+
+    let weak = Argon2Config {
+        memory_kib: 8 * 1024,
+        iterations: 1,
+        parallelism: 1,
+    };
+    let auth = LocalAuth::with_config(
+        storage.clone(),
+        LocalAuthConfig {
+            argon2: weak,
+            ..Default::default()
+        },
+    );
+    let user = must(
+        auth.register("dave".to_string(), "password123".to_string())
+            .await,
+    );
+    let old_hash = user.password_hash.clone();
+
+    // Deployment raises the cost parameters
+    let auth = LocalAuth::with_config(
+        storage.clone(),
+        LocalAuthConfig {
+            argon2: Argon2Config {
+                iterations: 3,
+                ..weak
+            },
+            ..Default::default()
+        },
+    );
+    let logged = must(auth.login("dave", "password123").await);
+    assert_ne!(logged.password_hash, old_hash);
+
+    let stored: User = verseguy_test_utils::must_opt(
+        must(storage.get(format!("user:id:{}", user.id).as_bytes())),
+        "user missing",
+    );
+    match stored.auth_method {
+        AuthMethod::Local { password_hash, .. } => assert!(password_hash.contains("t=3")),
+        other => panic!("unexpected auth method: {:?}", other),
+    }
+
+    // Password still verifies after the upgrade
+    must(auth.login("dave", "password123").await);
+}
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use serde::Serialize;
-use verseguy_auth::{Session, User};
+use serde_json::Value;
+use verseguy_auth::User;
 use verseguy_storage::RocksDBStorage;
 
 #[derive(Serialize)]
@@ -8,7 +9,17 @@ pub struct UserExport {
     pub id: String,
     pub username: String,
     pub user: User,
-    pub sessions: Vec<Session>,
+    pub sessions: Vec<Value>,
+}
+
+/// Stored sessions of `user_id`. `SessionService` writes `SessionRecord`s keyed by `sid`;
+/// older records are `Session`s keyed by `id`, so both are read as plain JSON.
+fn sessions_of(storage: &RocksDBStorage, user_id: &str) -> Result<Vec<Value>> {
+    let sessions: Vec<Value> = storage.prefix_scan(b"session:")?;
+    Ok(sessions
+        .into_iter()
+        .filter(|s| s.get("user_id").and_then(Value::as_str) == Some(user_id))
+        .collect())
 }
 
 pub fn export_user_data(storage: &RocksDBStorage, user_id: &str) -> Result<String> {
@@ -18,11 +29,7 @@ pub fn export_user_data(storage: &RocksDBStorage, user_id: &str) -> Result<Strin
     let user = user_opt.ok_or_else(|| anyhow::anyhow!("user not found"))?;
 
     // Find sessions for this user
-    let sessions: Vec<Session> = storage.prefix_scan(b"session:")?;
-    let user_sessions: Vec<Session> = sessions
-        .into_iter()
-        .filter(|s| s.user_id == user_id)
-        .collect();
+    let user_sessions = sessions_of(storage, user_id)?;
 
     let export = UserExport {
         id: user_id.to_string(),
@@ -46,11 +53,22 @@ pub fn delete_user_data(storage: &RocksDBStorage, user_id: &str) -> Result<bool>
     // Delete user records
     storage.delete(format!("user:id:{}", user_id).as_bytes())?;
     storage.delete(format!("user:username:{}", user.username).as_bytes())?;
+    for identity in &user.identities {
+        storage.delete(
+            format!(
+                "identity:{}:{}",
+                identity.provider, identity.provider_user_id
+            )
+            .as_bytes(),
+        )?;
+    }
 
     // Delete sessions
-    let sessions: Vec<Session> = storage.prefix_scan(b"session:")?;
-    for s in sessions.into_iter().filter(|s| s.user_id == user_id) {
-        storage.delete(format!("session:{}", s.id).as_bytes())?;
+    for s in sessions_of(storage, user_id)? {
+        let id = s.get("sid").or_else(|| s.get("id")).and_then(Value::as_str);
+        if let Some(id) = id {
+            storage.delete(format!("session:{}", id).as_bytes())?;
+        }
     }
 
     Ok(true)
@@ -20,12 +20,20 @@ fn export_and_delete_user_data() {
             username: "tester".to_string(),
             password_hash: "h".to_string(),
         },
+        identities: vec![verseguy_auth::LinkedIdentity {
+            provider: "local".to_string(),
+            provider_user_id: "tester".to_string(),
+            linked_at: chrono::Utc::now(),
+        }],
+        roles: Vec::new(),
+        kind: Default::default(),
         license: License::Free,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
     must(storage.put(format!("user:id:{}", user.id).as_bytes(), &user));
     must(storage.put(format!("user:username:{}", user.username).as_bytes(), &user));
+    must(storage.put(b"identity:local:tester", &user.id));
 
     // Insert a session (updated struct)
     let rec = verseguy_auth::Session {
@@ -36,10 +44,15 @@ fn export_and_delete_user_data() {
         expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
     };
     must(storage.put(format!("session:{}", rec.id).as_bytes(), &rec));
+    // and one as written by logging in
+    let sessions = verseguy_auth::SessionService::new(b"secret".to_vec());
+    let token = must(sessions.create_and_store_session(&user.id, &License::Free, 1, &storage));
 
     // Export
     let out = must(export_user_data(&storage, &user.id));
     assert!(out.contains("tester"));
+    let export: serde_json::Value = must(serde_json::from_str(&out));
+    assert_eq!(export["sessions"].as_array().map(Vec::len), Some(2));
 
     // Delete
     let ok = must(delete_user_data(&storage, &user.id));
@@ -48,4 +61,11 @@ fn export_and_delete_user_data() {
     // Ensure deleted
     let u_opt: Option<User> = must(storage.get(format!("user:id:{}", user.id).as_bytes()));
     assert!(u_opt.is_none());
+    let idx: Option<String> = must(storage.get(b"identity:local:tester"));
+    assert!(idx.is_none());
+    let left: Vec<serde_json::Value> = must(storage.prefix_scan(b"session:"));
+    assert!(left.is_empty());
+    assert!(sessions
+        .validate_token_and_storage(&token, &storage)
+        .is_err());
 }
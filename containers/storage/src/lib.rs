@@ -2,13 +2,15 @@ use anyhow::{Context, Result};
 use rocksdb::{DB, IteratorMode, Options};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tracing::{debug, info};
 
 /// Storage container using RocksDB
 #[derive(Clone)]
 pub struct Storage {
     db: Arc<DB>,
+    /// Serializes [`Storage::take`] across all clones of this handle
+    take_lock: Arc<Mutex<()>>,
 }
 
 impl Storage {
@@ -29,7 +31,10 @@ impl Storage {
 
         info!("Storage opened successfully");
 
-        Ok(Self { db: Arc::new(db) })
+        Ok(Self {
+            db: Arc::new(db),
+            take_lock: Arc::new(Mutex::new(())),
+        })
     }
 
     /// Put value with key
@@ -92,6 +97,45 @@ impl Storage {
         Ok(())
     }
 
+    /// Read and delete a value in one step: of two concurrent takes of the same key only one
+    /// gets the value. The database can only be opened by one process at a time, so this holds
+    /// for every handle sharing it.
+    pub fn take<K, V>(&self, key: K) -> Result<Option<V>>
+    where
+        K: AsRef<[u8]>,
+        V: for<'de> Deserialize<'de>,
+    {
+        let key_ref = key.as_ref();
+        let key_str = std::str::from_utf8(key_ref).unwrap_or("<binary>");
+        debug!("TAKE: {}", key_str);
+
+        let value_bytes = {
+            let _guard = self
+                .take_lock
+                .lock()
+                .map_err(|e| anyhow::anyhow!("take lock poisoned: {:?}", e))?;
+            let value_bytes = self
+                .db
+                .get(key_ref)
+                .context(format!("Failed to read key: {}", key_str))?;
+            if value_bytes.is_some() {
+                self.db
+                    .delete(key_ref)
+                    .context(format!("Failed to delete key: {}", key_str))?;
+            }
+            value_bytes
+        };
+
+        match value_bytes {
+            Some(bytes) => {
+                let value =
+                    serde_json::from_slice(&bytes).context("Failed to deserialize value")?;
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Scan with prefix
     pub fn prefix_scan<K, V>(&self, prefix: K) -> Result<Vec<V>>
     where
@@ -123,6 +167,15 @@ impl Storage {
         Ok(results)
     }
 
+    /// Write a consistent copy of the database to `dir`, which must not exist yet; safe while
+    /// the database is in use
+    pub fn checkpoint<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        let dir = dir.as_ref();
+        rocksdb::checkpoint::Checkpoint::new(&*self.db)
+            .and_then(|c| c.create_checkpoint(dir))
+            .context(format!("Failed to create checkpoint at {:?}", dir))
+    }
+
     /// Get underlying DB path if exposed
     pub fn path(&self) -> Option<&Path> {
         Some(self.db.path())
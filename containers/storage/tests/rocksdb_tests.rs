@@ -69,3 +69,52 @@ fn test_delete_and_prefix() {
     let users: Vec<TestItem> = must(db.prefix_scan(b"user:"));
     assert_eq!(users.len(), 2);
 }
+
+#[test]
+fn test_take_returns_a_value_once() {
+    let temp_dir = must(TempDir::new());
+    let db = must(RocksDBStorage::open(temp_dir.path()));
+
+    let item = TestItem {
+        id: "1".into(),
+        name: "Alice".into(),
+    };
+    must(db.put(b"state:1", &item));
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let db = db.clone();
+            std::thread::spawn(move || must(db.take::<_, TestItem>(b"state:1")))
+        })
+        .collect();
+    let taken: Vec<TestItem> = handles
+        .into_iter()
+        .filter_map(|h| match h.join() {
+            Ok(v) => v,
+            Err(_) => panic!("take thread panicked"),
+        })
+        .collect();
+    assert_eq!(taken, vec![item]);
+
+    let got: Option<TestItem> = must(db.get(b"state:1"));
+    assert!(got.is_none());
+}
+
+#[test]
+fn test_checkpoint_is_an_openable_copy() {
+    let temp_dir = must(TempDir::new());
+    let db = must(RocksDBStorage::open(temp_dir.path().join("db")));
+    let item = TestItem {
+        id: "1".to_string(),
+        name: "Alice".to_string(),
+    };
+    must(db.put(b"user:1", &item));
+
+    let copy_path = temp_dir.path().join("copy");
+    must(db.checkpoint(&copy_path));
+    must(db.delete(b"user:1"));
+
+    let copy = must(RocksDBStorage::open(&copy_path));
+    let got: Option<TestItem> = must(copy.get(b"user:1"));
+    assert_eq!(got, Some(item));
+}
@@ -0,0 +1,13 @@
+//! Print the generated OpenAPI document, including the `/v1` domain API.
+//!
+//! `cargo run -p verseguy-api --example openapi > openapi.json`
+
+fn main() {
+    match verseguy_api::openapi_spec().to_pretty_json() {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("failed to render the OpenAPI document: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
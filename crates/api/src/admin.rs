@@ -0,0 +1,224 @@
+use axum::{
+    extract::{Json, Path},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use utoipa::ToSchema;
+use verseguy_shared_error::{AppError, ProblemDetails};
+
+use crate::clients::{
+    register_client, ClientRegistrationRequest, ClientRegistrationResponse, ClientStore,
+};
+
+/// Registered clients as returned by `GET /admin/clients`
+#[derive(Serialize, ToSchema)]
+pub struct ClientList {
+    pub clients: Vec<ClientRegistrationResponse>,
+}
+
+/// Admin endpoints require `x-admin-token` to match `VERSEGUY_API_ADMIN_TOKEN`; they are
+/// disabled when the variable is unset.
+fn require_admin(headers: &HeaderMap) -> Result<(), AppError> {
+    match std::env::var("VERSEGUY_API_ADMIN_TOKEN") {
+        Ok(token) if !token.is_empty() => {
+            let header_token = headers
+                .get("x-admin-token")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("");
+            if !bool::from(header_token.as_bytes().ct_eq(token.as_bytes())) {
+                return Err(AppError::Forbidden("invalid admin token".into()));
+            }
+            Ok(())
+        }
+        _ => Err(AppError::Forbidden("admin disabled".into())),
+    }
+}
+
+fn store_error() -> Response {
+    AppError::internal("client store failure").into_response()
+}
+
+/// Dynamic client registration (RFC 7591)
+#[utoipa::path(
+    post,
+    path = "/admin/clients",
+    tag = "admin",
+    params(("x-admin-token" = String, Header, description = "Value of VERSEGUY_API_ADMIN_TOKEN")),
+    request_body = ClientRegistrationRequest,
+    responses(
+        (
+            status = 201,
+            description = "Client registered; `client_secret` is only returned once",
+            body = ClientRegistrationResponse
+        ),
+        (
+            status = 400,
+            description = "invalid_client_metadata or invalid_redirect_uri",
+            body = ProblemDetails
+        ),
+        (status = 403, description = "Missing or invalid admin token", body = ProblemDetails),
+    )
+)]
+pub async fn register_client_handler(
+    Extension(clients): Extension<Arc<dyn ClientStore>>,
+    headers: HeaderMap,
+    Json(req): Json<ClientRegistrationRequest>,
+) -> Response {
+    if let Err(e) = require_admin(&headers) {
+        return e.into_response();
+    }
+    let (client, secret) = match register_client(req) {
+        Ok(r) => r,
+        Err(e) => return AppError::from(e).into_response(),
+    };
+    if clients.insert(client.clone()).is_err() {
+        return store_error();
+    }
+    tracing::info!("registered oauth client {}", client.client_id);
+    (
+        StatusCode::CREATED,
+        Json(ClientRegistrationResponse::new(&client, secret)),
+    )
+        .into_response()
+}
+
+/// List registered OAuth clients; secrets are never returned
+#[utoipa::path(
+    get,
+    path = "/admin/clients",
+    tag = "admin",
+    params(("x-admin-token" = String, Header, description = "Value of VERSEGUY_API_ADMIN_TOKEN")),
+    responses(
+        (status = 200, description = "Registered clients", body = ClientList),
+        (status = 403, description = "Missing or invalid admin token", body = ProblemDetails),
+    )
+)]
+pub async fn list_clients_handler(
+    Extension(clients): Extension<Arc<dyn ClientStore>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(e) = require_admin(&headers) {
+        return e.into_response();
+    }
+    match clients.list() {
+        Ok(list) => {
+            let clients = list
+                .iter()
+                .map(|c| ClientRegistrationResponse::new(c, None))
+                .collect();
+            Json(ClientList { clients }).into_response()
+        }
+        Err(_) => store_error(),
+    }
+}
+
+/// Get a registered OAuth client
+#[utoipa::path(
+    get,
+    path = "/admin/clients/{id}",
+    tag = "admin",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        ("x-admin-token" = String, Header, description = "Value of VERSEGUY_API_ADMIN_TOKEN"),
+    ),
+    responses(
+        (status = 200, description = "Client metadata", body = ClientRegistrationResponse),
+        (status = 403, description = "Missing or invalid admin token", body = ProblemDetails),
+        (status = 404, description = "Unknown client", body = ProblemDetails),
+    )
+)]
+pub async fn get_client_handler(
+    Extension(clients): Extension<Arc<dyn ClientStore>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(e) = require_admin(&headers) {
+        return e.into_response();
+    }
+    match clients.get(&id) {
+        Ok(Some(c)) => Json(ClientRegistrationResponse::new(&c, None)).into_response(),
+        Ok(None) => AppError::NotFound("unknown client".into()).into_response(),
+        Err(_) => store_error(),
+    }
+}
+
+/// Delete a registered OAuth client
+#[utoipa::path(
+    delete,
+    path = "/admin/clients/{id}",
+    tag = "admin",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        ("x-admin-token" = String, Header, description = "Value of VERSEGUY_API_ADMIN_TOKEN"),
+    ),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 403, description = "Missing or invalid admin token", body = ProblemDetails),
+        (status = 404, description = "Unknown client", body = ProblemDetails),
+    )
+)]
+pub async fn delete_client_handler(
+    Extension(clients): Extension<Arc<dyn ClientStore>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(e) = require_admin(&headers) {
+        return e.into_response();
+    }
+    match clients.remove(&id) {
+        Ok(Some(_)) => StatusCode::NO_CONTENT.into_response(),
+        Ok(None) => AppError::NotFound("unknown client".into()).into_response(),
+        Err(_) => store_error(),
+    }
+}
+
+/// Issue a new secret for a confidential client; the previous secret stops working immediately
+#[utoipa::path(
+    post,
+    path = "/admin/clients/{id}/secret",
+    tag = "admin",
+    params(
+        ("id" = String, Path, description = "Client id"),
+        ("x-admin-token" = String, Header, description = "Value of VERSEGUY_API_ADMIN_TOKEN"),
+    ),
+    responses(
+        (status = 200, description = "New `client_secret`", body = ClientRegistrationResponse),
+        (status = 400, description = "Public clients have no secret", body = ProblemDetails),
+        (status = 403, description = "Missing or invalid admin token", body = ProblemDetails),
+        (status = 404, description = "Unknown client", body = ProblemDetails),
+    )
+)]
+pub async fn rotate_secret_handler(
+    Extension(clients): Extension<Arc<dyn ClientStore>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(e) = require_admin(&headers) {
+        return e.into_response();
+    }
+    let mut client = match clients.get(&id) {
+        Ok(Some(c)) => c,
+        Ok(None) => return AppError::NotFound("unknown client".into()).into_response(),
+        Err(_) => return store_error(),
+    };
+    let secret = match client.rotate_secret() {
+        Some(s) => s,
+        None => {
+            return AppError::coded(
+                StatusCode::BAD_REQUEST,
+                "invalid_client_metadata",
+                "public clients have no secret",
+            )
+            .into_response()
+        }
+    };
+    if clients.insert(client.clone()).is_err() {
+        return store_error();
+    }
+    tracing::info!("rotated secret for oauth client {}", client.client_id);
+    Json(ClientRegistrationResponse::new(&client, Some(secret))).into_response()
+}
@@ -0,0 +1,115 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use verseguy_shared_error::AppError;
+
+use crate::jwt::{AccessClaims, JwtIssuer, TokenError};
+
+/// Scope a route requires from the bearer token; `None` accepts any valid token
+pub trait RequiredScope: Send + Sync + 'static {
+    const SCOPE: Option<&'static str>;
+}
+
+pub struct AnyScope;
+pub struct ReadScope;
+pub struct WriteScope;
+
+impl RequiredScope for AnyScope {
+    const SCOPE: Option<&'static str> = None;
+}
+
+impl RequiredScope for ReadScope {
+    const SCOPE: Option<&'static str> = Some("read");
+}
+
+impl RequiredScope for WriteScope {
+    const SCOPE: Option<&'static str> = Some("write");
+}
+
+/// Extractor that validates an `Authorization: Bearer` access token and enforces `S`.
+///
+/// ```ignore
+/// async fn handler(auth: Bearer<ReadScope>) -> impl IntoResponse { auth.claims.sub }
+/// ```
+pub struct Bearer<S: RequiredScope = AnyScope> {
+    pub claims: AccessClaims,
+    scope: PhantomData<S>,
+}
+
+/// RFC 6750 challenge; requests without credentials get no error code
+fn challenge(status: StatusCode, error: Option<&'static str>, scope: Option<&str>) -> Response {
+    let mut value = "Bearer realm=\"verseguy-api\"".to_string();
+    if let Some(e) = error {
+        value = format!("{}, error=\"{}\"", value, e);
+    }
+    if let Some(s) = scope {
+        value = format!("{}, scope=\"{}\"", value, s);
+    }
+    let detail = match (error, scope) {
+        (Some("insufficient_scope"), Some(s)) => format!("the token lacks the {} scope", s),
+        (Some(_), _) => "the access token is invalid or expired".to_string(),
+        (None, _) => "a bearer token is required".to_string(),
+    };
+    let problem = AppError::coded(status, error.unwrap_or("unauthorized"), detail);
+    (status, [(header::WWW_AUTHENTICATE, value)], problem).into_response()
+}
+
+impl<S, St> FromRequestParts<St> for Bearer<S>
+where
+    S: RequiredScope,
+    St: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &St) -> Result<Self, Self::Rejection> {
+        let issuer = match parts.extensions.get::<Arc<JwtIssuer>>() {
+            Some(i) => i.clone(),
+            None => return Err(AppError::internal("JwtIssuer extension missing").into_response()),
+        };
+
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|s| s.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim())
+            .filter(|t| !t.is_empty());
+        let token = match token {
+            Some(t) => t,
+            None => return Err(challenge(StatusCode::UNAUTHORIZED, None, S::SCOPE)),
+        };
+
+        let claims = match issuer.verify(token).await {
+            Ok(c) => c,
+            Err(TokenError::Store(_)) => {
+                return Err(AppError::internal("token store failure").into_response())
+            }
+            Err(_) => {
+                return Err(challenge(
+                    StatusCode::UNAUTHORIZED,
+                    Some("invalid_token"),
+                    S::SCOPE,
+                ))
+            }
+        };
+        if let Some(required) = S::SCOPE {
+            if !claims.has_scope(required) {
+                return Err(challenge(
+                    StatusCode::FORBIDDEN,
+                    Some("insufficient_scope"),
+                    Some(required),
+                ));
+            }
+        }
+
+        Ok(Bearer {
+            claims,
+            scope: PhantomData,
+        })
+    }
+}
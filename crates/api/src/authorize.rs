@@ -0,0 +1,524 @@
+use axum::{
+    extract::Extension,
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use verseguy_shared_error::{AppError, ProblemDetails};
+
+use crate::clients::{ClientStore, OAuthClient, GRANT_AUTHORIZATION_CODE};
+use crate::session::{session_token, SessionVerifier};
+use crate::store::{StoreError, TokenStore};
+
+/// Lifetime of an authorization code
+pub const CODE_TTL_SECS: i64 = 300;
+/// Lifetime of a consent screen before the user has to start over
+pub const PENDING_TTL_SECS: i64 = 600;
+
+/// Authorization code awaiting exchange at the token endpoint
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CodeRecord {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    /// VerseGuy user id that approved the request
+    pub subject: String,
+    /// S256 PKCE challenge, if the client sent one
+    pub code_challenge: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Authorization request waiting for the user's decision on the consent screen
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PendingAuthorization {
+    pub user_id: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Scopes a user has granted to a client
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ConsentRecord {
+    pub scopes: Vec<String>,
+    pub granted_at: DateTime<Utc>,
+}
+
+async fn put_json<T: Serialize>(
+    store: &dyn TokenStore,
+    key: &str,
+    value: &T,
+    ttl_secs: Option<i64>,
+) -> Result<(), StoreError> {
+    match serde_json::to_string(value) {
+        Ok(s) => store.put_aux(key, s, ttl_secs).await,
+        Err(e) => Err(StoreError::Backend(format!("serialize: {}", e))),
+    }
+}
+
+fn parse_json<T: DeserializeOwned>(raw: Option<String>) -> Result<Option<T>, StoreError> {
+    match raw {
+        Some(s) => match serde_json::from_str(&s) {
+            Ok(v) => Ok(Some(v)),
+            Err(e) => Err(StoreError::Backend(format!("deserialize: {}", e))),
+        },
+        None => Ok(None),
+    }
+}
+
+fn consent_key(user_id: &str, client_id: &str) -> String {
+    format!("consent:{}:{}", user_id, client_id)
+}
+
+/// Redeem an authorization code; the record is removed so a code works only once
+pub async fn redeem_code(
+    store: &dyn TokenStore,
+    code: &str,
+) -> Result<Option<CodeRecord>, StoreError> {
+    parse_json(store.take_aux(&format!("code:{}", code)).await?)
+}
+
+async fn issue_code(
+    store: &dyn TokenStore,
+    pending: &PendingAuthorization,
+) -> Result<String, StoreError> {
+    let code = Uuid::new_v4().simple().to_string();
+    let rec = CodeRecord {
+        client_id: pending.client_id.clone(),
+        redirect_uri: pending.redirect_uri.clone(),
+        scope: pending.scope.clone(),
+        subject: pending.user_id.clone(),
+        code_challenge: pending.code_challenge.clone(),
+        expires_at: Utc::now() + chrono::Duration::seconds(CODE_TTL_SECS),
+    };
+    put_json(store, &format!("code:{}", code), &rec, Some(CODE_TTL_SECS)).await?;
+    Ok(code)
+}
+
+/// S256 code challenge for a PKCE verifier (RFC 7636)
+pub fn pkce_challenge(verifier: &str) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Check a code verifier against the stored challenge; a verifier without a challenge is
+/// rejected too, so a downgrade cannot go unnoticed
+pub fn verify_pkce(challenge: Option<&str>, verifier: Option<&str>) -> bool {
+    match (challenge, verifier) {
+        (None, None) => true,
+        (Some(c), Some(v)) => {
+            let valid_len = (43..=128).contains(&v.len());
+            let valid_chars = v
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || "-._~".contains(ch));
+            valid_len && valid_chars && pkce_challenge(v) == c
+        }
+        _ => false,
+    }
+}
+
+fn is_s256_challenge(challenge: &str) -> bool {
+    challenge.len() == 43
+        && challenge
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+}
+
+/// Append URL-encoded query parameters to a registered redirect URI
+pub fn build_redirect(redirect_uri: &str, params: &[(&str, &str)]) -> String {
+    let query = serde_urlencoded::to_string(params).unwrap_or_default();
+    let sep = if redirect_uri.contains('?') { '&' } else { '?' };
+    format!("{}{}{}", redirect_uri, sep, query)
+}
+
+fn found(location: String) -> Response {
+    match axum::http::Response::builder()
+        .status(StatusCode::FOUND)
+        .header(axum::http::header::LOCATION, location)
+        .body(axum::body::Body::empty())
+    {
+        Ok(r) => r.into_response(),
+        Err(_) => AppError::internal("failed to build redirect").into_response(),
+    }
+}
+
+fn redirect_error(redirect_uri: &str, error: &str, state: Option<&str>) -> Response {
+    let mut params = vec![("error", error)];
+    if let Some(s) = state {
+        params.push(("state", s));
+    }
+    found(build_redirect(redirect_uri, &params))
+}
+
+async fn redirect_code(store: &dyn TokenStore, pending: &PendingAuthorization) -> Response {
+    let code = match issue_code(store, pending).await {
+        Ok(c) => c,
+        Err(_) => return AppError::internal("token store failure").into_response(),
+    };
+    let mut params = vec![("code", code.as_str())];
+    if let Some(s) = pending.state.as_deref() {
+        params.push(("state", s));
+    }
+    found(build_redirect(&pending.redirect_uri, &params))
+}
+
+async fn has_consent(store: &dyn TokenStore, user_id: &str, client_id: &str, scope: &str) -> bool {
+    let raw = store
+        .get_aux(&consent_key(user_id, client_id))
+        .await
+        .ok()
+        .flatten();
+    match parse_json::<ConsentRecord>(raw).ok().flatten() {
+        Some(c) => scope
+            .split_whitespace()
+            .all(|s| c.scopes.iter().any(|g| g == s)),
+        None => false,
+    }
+}
+
+fn current_user(sessions: &dyn SessionVerifier, headers: &HeaderMap) -> Option<String> {
+    session_token(headers).and_then(|t| sessions.verify(&t))
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn consent_page(client: &OAuthClient, scope: &str, consent_id: &str) -> Response {
+    let name = client.client_name.as_deref().unwrap_or(&client.client_id);
+    let scopes: String = scope
+        .split_whitespace()
+        .map(|s| format!("<li>{}</li>", escape_html(s)))
+        .collect();
+    let html = format!(
+        r#"<!doctype html>
+<html>
+  <head><meta charset='utf-8'/><title>Authorize {name}</title></head>
+  <body>
+    <h1>Authorize {name}</h1>
+    <p>{name} is requesting access to your VerseGuy account with the following scopes:</p>
+    <ul>{scopes}</ul>
+    <form method="post" action="/oauth/authorize">
+      <input type="hidden" name="consent_id" value="{consent_id}"/>
+      <button type="submit" name="decision" value="approve">Allow</button>
+      <button type="submit" name="decision" value="deny">Deny</button>
+    </form>
+  </body>
+</html>"#,
+        name = escape_html(name),
+        scopes = scopes,
+        consent_id = escape_html(consent_id),
+    );
+    (
+        StatusCode::OK,
+        [("cache-control", "no-store"), ("x-frame-options", "DENY")],
+        Html(html),
+    )
+        .into_response()
+}
+
+/// Query parameters of the authorization endpoint. The handler reads the raw query so it can
+/// answer with redirects instead of rejections; this type only documents them.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuthorizeParams {
+    /// Must be `code`
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    /// Space-separated scopes; defaults to all scopes registered for the client
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    /// PKCE S256 challenge (RFC 7636); required for public clients
+    pub code_challenge: Option<String>,
+    /// Only `S256` is accepted
+    pub code_challenge_method: Option<String>,
+}
+
+/// Authorization endpoint (RFC 6749 section 4.1 with PKCE). Requires a VerseGuy session and
+/// shows a consent screen unless the user already granted the requested scopes.
+#[utoipa::path(
+    get,
+    path = "/oauth/authorize",
+    tag = "oauth",
+    params(AuthorizeParams),
+    responses(
+        (
+            status = 200,
+            description = "Consent page when the user has not yet approved the client",
+            content_type = "text/html"
+        ),
+        (
+            status = 302,
+            description = "Redirect with `code` and `state`, or with `error` (`login_required`, \
+                `invalid_scope`, `invalid_request`, `unauthorized_client`)"
+        ),
+        (
+            status = 400,
+            description = "Unknown client or unregistered redirect_uri",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn authorize_handler(
+    Extension(store): Extension<Arc<dyn TokenStore>>,
+    Extension(clients): Extension<Arc<dyn ClientStore>>,
+    Extension(sessions): Extension<Arc<dyn SessionVerifier>>,
+    req: axum::http::Request<axum::body::Body>,
+) -> Response {
+    let q = req.uri().query().unwrap_or("");
+    let params: HashMap<String, String> = match serde_urlencoded::from_str(q) {
+        Ok(m) => m,
+        Err(_) => return AppError::BadRequest("malformed query".into()).into_response(),
+    };
+
+    // require response_type=code
+    if params.get("response_type").map(|s| s.as_str()) != Some("code") {
+        return AppError::coded(
+            StatusCode::BAD_REQUEST,
+            "unsupported_response_type",
+            "only response_type=code is supported",
+        )
+        .into_response();
+    }
+
+    // validate client_id and redirect_uri against the registry; errors are not redirected
+    // because the redirect_uri cannot be trusted yet
+    let client_id = params.get("client_id").map(|s| s.as_str()).unwrap_or("");
+    let redirect = match params.get("redirect_uri") {
+        Some(r) => r.clone(),
+        None => return AppError::BadRequest("missing redirect_uri".into()).into_response(),
+    };
+    let client = match clients.get(client_id) {
+        Ok(Some(c)) => c,
+        Ok(None) => {
+            return AppError::coded(StatusCode::BAD_REQUEST, "invalid_client", "unknown client")
+                .into_response()
+        }
+        Err(_) => return AppError::internal("token store failure").into_response(),
+    };
+    if !client.allows_redirect(&redirect) {
+        return AppError::coded(
+            StatusCode::BAD_REQUEST,
+            "invalid_redirect_uri",
+            "redirect_uri is not registered for the client",
+        )
+        .into_response();
+    }
+    let state = params.get("state").map(|s| s.as_str());
+    if !client.allows_grant(GRANT_AUTHORIZATION_CODE) {
+        return redirect_error(&redirect, "unauthorized_client", state);
+    }
+
+    // requested scopes must be registered for the client; default to all registered scopes
+    let scope = match params.get("scope") {
+        Some(requested) if !client.allows_scopes(requested) => {
+            return redirect_error(&redirect, "invalid_scope", state);
+        }
+        Some(requested) => requested.clone(),
+        None => client.scopes.join(" "),
+    };
+
+    // PKCE: only S256 is accepted, and public clients must use it
+    let code_challenge = params.get("code_challenge").cloned();
+    match &code_challenge {
+        Some(c) => {
+            let method = params.get("code_challenge_method").map(|s| s.as_str());
+            if method != Some("S256") || !is_s256_challenge(c) {
+                return redirect_error(&redirect, "invalid_request", state);
+            }
+        }
+        None if !client.is_confidential() => {
+            return redirect_error(&redirect, "invalid_request", state);
+        }
+        None => {}
+    }
+
+    let user_id = match current_user(sessions.as_ref(), req.headers()) {
+        Some(u) => u,
+        None => {
+            if let Ok(login) = std::env::var("VERSEGUY_API_LOGIN_URL") {
+                let return_to = req
+                    .uri()
+                    .path_and_query()
+                    .map(|p| p.as_str())
+                    .unwrap_or("/oauth/authorize");
+                return found(build_redirect(&login, &[("return_to", return_to)]));
+            }
+            return redirect_error(&redirect, "login_required", state);
+        }
+    };
+
+    let pending = PendingAuthorization {
+        user_id,
+        client_id: client.client_id.clone(),
+        redirect_uri: redirect,
+        scope,
+        state: state.map(|s| s.to_string()),
+        code_challenge,
+        expires_at: Utc::now() + chrono::Duration::seconds(PENDING_TTL_SECS),
+    };
+
+    if client.first_party
+        || has_consent(
+            store.as_ref(),
+            &pending.user_id,
+            &client.client_id,
+            &pending.scope,
+        )
+        .await
+    {
+        return redirect_code(store.as_ref(), &pending).await;
+    }
+
+    let consent_id = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let key = format!("pending:{}", consent_id);
+    if put_json(store.as_ref(), &key, &pending, Some(PENDING_TTL_SECS))
+        .await
+        .is_err()
+    {
+        return AppError::internal("token store failure").into_response();
+    }
+    consent_page(&client, &pending.scope, &consent_id)
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ConsentForm {
+    consent_id: String,
+    /// `approve` or `deny`
+    decision: String,
+}
+
+/// Consent decision posted from the consent screen; must come from the same session
+#[utoipa::path(
+    post,
+    path = "/oauth/authorize",
+    tag = "oauth",
+    request_body(content = ConsentForm, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (
+            status = 302,
+            description = "Redirect with `code`, or with `error=access_denied` when denied"
+        ),
+        (
+            status = 400,
+            description = "Unknown, expired or foreign consent request",
+            body = ProblemDetails
+        ),
+        (status = 401, description = "No valid session", body = ProblemDetails),
+    )
+)]
+pub async fn consent_handler(
+    Extension(store): Extension<Arc<dyn TokenStore>>,
+    Extension(clients): Extension<Arc<dyn ClientStore>>,
+    Extension(sessions): Extension<Arc<dyn SessionVerifier>>,
+    req: axum::http::Request<axum::body::Body>,
+) -> Response {
+    let user_id = match current_user(sessions.as_ref(), req.headers()) {
+        Some(u) => u,
+        None => {
+            return AppError::coded(StatusCode::UNAUTHORIZED, "login_required", "sign in first")
+                .into_response()
+        }
+    };
+    let bytes = match axum::body::to_bytes(req.into_body(), 64 * 1024).await {
+        Ok(b) => b,
+        Err(_) => return AppError::BadRequest("failed to read body".into()).into_response(),
+    };
+    let form: ConsentForm = match serde_urlencoded::from_bytes(&bytes) {
+        Ok(f) => f,
+        Err(_) => return AppError::BadRequest("malformed form body".into()).into_response(),
+    };
+
+    let key = format!("pending:{}", form.consent_id);
+    let pending = match store
+        .take_aux(&key)
+        .await
+        .and_then(parse_json::<PendingAuthorization>)
+    {
+        Ok(Some(p)) => p,
+        Ok(None) => return AppError::BadRequest("invalid consent request".into()).into_response(),
+        Err(_) => return AppError::internal("token store failure").into_response(),
+    };
+    if pending.user_id != user_id || pending.expires_at < Utc::now() {
+        return AppError::BadRequest("invalid consent request".into()).into_response();
+    }
+    // the client may have been deleted or changed while the consent screen was open
+    match clients.get(&pending.client_id) {
+        Ok(Some(c)) if c.allows_redirect(&pending.redirect_uri) => {}
+        Ok(_) => {
+            return AppError::coded(StatusCode::BAD_REQUEST, "invalid_client", "unknown client")
+                .into_response()
+        }
+        Err(_) => return AppError::internal("token store failure").into_response(),
+    }
+
+    if form.decision != "approve" {
+        return redirect_error(
+            &pending.redirect_uri,
+            "access_denied",
+            pending.state.as_deref(),
+        );
+    }
+
+    let consent_key = consent_key(&pending.user_id, &pending.client_id);
+    let mut scopes: Vec<String> =
+        match parse_json::<ConsentRecord>(store.get_aux(&consent_key).await.ok().flatten()) {
+            Ok(Some(c)) => c.scopes,
+            _ => Vec::new(),
+        };
+    for s in pending.scope.split_whitespace() {
+        if !scopes.iter().any(|g| g == s) {
+            scopes.push(s.to_string());
+        }
+    }
+    let consent = ConsentRecord {
+        scopes,
+        granted_at: Utc::now(),
+    };
+    if put_json(store.as_ref(), &consent_key, &consent, None)
+        .await
+        .is_err()
+    {
+        return AppError::internal("token store failure").into_response();
+    }
+    redirect_code(store.as_ref(), &pending).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pkce_s256_matches_rfc7636_vector() {
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = pkce_challenge(verifier);
+        assert_eq!(challenge, "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+        assert!(verify_pkce(Some(&challenge), Some(verifier)));
+        let wrong = "wrong-verifier-wrong-verifier-wrong-verifier";
+        assert!(!verify_pkce(Some(&challenge), Some(wrong)));
+        assert!(!verify_pkce(Some(&challenge), None));
+        assert!(!verify_pkce(None, Some(verifier)));
+    }
+
+    #[test]
+    fn redirect_parameters_are_encoded() {
+        let loc = build_redirect("https://app.example/cb", &[("state", "a b&c=d")]);
+        assert_eq!(loc, "https://app.example/cb?state=a+b%26c%3Dd");
+        let loc = build_redirect("https://app.example/cb?x=1", &[("code", "abc")]);
+        assert_eq!(loc, "https://app.example/cb?x=1&code=abc");
+    }
+}
@@ -0,0 +1,562 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use subtle::ConstantTimeEq;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use verseguy_shared_error::AppError;
+
+use crate::store::StoreError;
+
+pub const GRANT_AUTHORIZATION_CODE: &str = "authorization_code";
+pub const GRANT_CLIENT_CREDENTIALS: &str = "client_credentials";
+pub const GRANT_REFRESH_TOKEN: &str = "refresh_token";
+
+const SUPPORTED_GRANTS: &[&str] = &[
+    GRANT_AUTHORIZATION_CODE,
+    GRANT_CLIENT_CREDENTIALS,
+    GRANT_REFRESH_TOKEN,
+];
+
+/// Confidential clients authenticate with a secret; public clients (SPAs, native apps) have none
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientType {
+    Confidential,
+    Public,
+}
+
+/// A registered OAuth client. Only a SHA-256 hash of the secret is stored.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct OAuthClient {
+    pub client_id: String,
+    pub client_name: Option<String>,
+    pub client_type: ClientType,
+    pub secret_hash: Option<String>,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>,
+    /// Trusted first-party clients skip the consent screen
+    #[serde(default)]
+    pub first_party: bool,
+    pub created_at: DateTime<Utc>,
+    pub secret_rotated_at: Option<DateTime<Utc>>,
+}
+
+pub fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// Generate a random client secret (244 bits of entropy)
+pub fn generate_secret() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+impl OAuthClient {
+    pub fn is_confidential(&self) -> bool {
+        self.client_type == ClientType::Confidential
+    }
+
+    /// Redirect URIs must match a registered value exactly (no prefix or wildcard matching)
+    pub fn allows_redirect(&self, redirect_uri: &str) -> bool {
+        self.redirect_uris.iter().any(|u| u == redirect_uri)
+    }
+
+    pub fn allows_grant(&self, grant_type: &str) -> bool {
+        self.grant_types.iter().any(|g| g == grant_type)
+    }
+
+    /// Check that every space-separated scope in `requested` is registered for this client
+    pub fn allows_scopes(&self, requested: &str) -> bool {
+        requested
+            .split_whitespace()
+            .all(|s| self.scopes.iter().any(|allowed| allowed == s))
+    }
+
+    /// Compare in constant time, so response timing does not reveal the stored hash
+    pub fn verify_secret(&self, secret: &str) -> bool {
+        match &self.secret_hash {
+            Some(h) => h.as_bytes().ct_eq(hash_secret(secret).as_bytes()).into(),
+            None => false,
+        }
+    }
+
+    /// Replace the secret of a confidential client; returns the new plaintext secret
+    pub fn rotate_secret(&mut self) -> Option<String> {
+        if !self.is_confidential() {
+            return None;
+        }
+        let secret = generate_secret();
+        self.secret_hash = Some(hash_secret(&secret));
+        self.secret_rotated_at = Some(Utc::now());
+        Some(secret)
+    }
+}
+
+/// Dynamic client registration request (RFC 7591 section 2)
+#[derive(Clone, Deserialize, Debug, ToSchema)]
+pub struct ClientRegistrationRequest {
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    #[serde(default)]
+    pub client_name: Option<String>,
+    #[serde(default)]
+    pub grant_types: Option<Vec<String>>,
+    /// `client_secret_basic` (default), `client_secret_post` or `none` for public clients
+    #[serde(default)]
+    pub token_endpoint_auth_method: Option<String>,
+    /// Space-separated list of scopes the client may request
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+/// Client information response (RFC 7591 section 3.2.1)
+#[derive(Clone, Serialize, Debug, ToSchema)]
+pub struct ClientRegistrationResponse {
+    pub client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    pub client_id_issued_at: i64,
+    /// 0 means the secret does not expire
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret_expires_at: Option<i64>,
+    pub client_name: Option<String>,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub token_endpoint_auth_method: String,
+    pub scope: String,
+}
+
+impl ClientRegistrationResponse {
+    pub fn new(client: &OAuthClient, client_secret: Option<String>) -> Self {
+        let auth_method = if client.is_confidential() {
+            "client_secret_basic"
+        } else {
+            "none"
+        };
+        ClientRegistrationResponse {
+            client_id: client.client_id.clone(),
+            client_secret_expires_at: client_secret.as_ref().map(|_| 0),
+            client_secret,
+            client_id_issued_at: client.created_at.timestamp(),
+            client_name: client.client_name.clone(),
+            redirect_uris: client.redirect_uris.clone(),
+            grant_types: client.grant_types.clone(),
+            token_endpoint_auth_method: auth_method.to_string(),
+            scope: client.scopes.join(" "),
+        }
+    }
+}
+
+/// Registration error (RFC 7591 section 3.2.2)
+#[derive(Clone, Serialize, Debug)]
+pub struct RegistrationError {
+    pub error: &'static str,
+    pub error_description: String,
+}
+
+impl RegistrationError {
+    fn metadata(desc: &str) -> Self {
+        RegistrationError {
+            error: "invalid_client_metadata",
+            error_description: desc.to_string(),
+        }
+    }
+
+    fn redirect(desc: &str) -> Self {
+        RegistrationError {
+            error: "invalid_redirect_uri",
+            error_description: desc.to_string(),
+        }
+    }
+}
+
+impl From<RegistrationError> for AppError {
+    fn from(e: RegistrationError) -> Self {
+        AppError::coded(StatusCode::BAD_REQUEST, e.error, e.error_description)
+    }
+}
+
+/// Validate a registration request and build the client, returning its plaintext secret
+pub fn register_client(
+    req: ClientRegistrationRequest,
+) -> Result<(OAuthClient, Option<String>), RegistrationError> {
+    let client_type = match req.token_endpoint_auth_method.as_deref() {
+        None | Some("client_secret_basic") | Some("client_secret_post") => ClientType::Confidential,
+        Some("none") => ClientType::Public,
+        Some(_) => {
+            return Err(RegistrationError::metadata(
+                "unsupported token_endpoint_auth_method",
+            ))
+        }
+    };
+
+    let grant_types = req
+        .grant_types
+        .unwrap_or_else(|| vec![GRANT_AUTHORIZATION_CODE.to_string()]);
+    if grant_types.is_empty() {
+        return Err(RegistrationError::metadata("grant_types must not be empty"));
+    }
+    if let Some(g) = grant_types
+        .iter()
+        .find(|g| !SUPPORTED_GRANTS.contains(&g.as_str()))
+    {
+        return Err(RegistrationError::metadata(&format!(
+            "unsupported grant_type: {}",
+            g
+        )));
+    }
+    if client_type == ClientType::Public
+        && grant_types.iter().any(|g| g == GRANT_CLIENT_CREDENTIALS)
+    {
+        return Err(RegistrationError::metadata(
+            "public clients cannot use client_credentials",
+        ));
+    }
+
+    let needs_redirect = grant_types.iter().any(|g| g == GRANT_AUTHORIZATION_CODE);
+    if needs_redirect && req.redirect_uris.is_empty() {
+        return Err(RegistrationError::redirect(
+            "authorization_code clients require at least one redirect_uri",
+        ));
+    }
+    for uri in &req.redirect_uris {
+        let absolute = uri.starts_with("https://") || uri.starts_with("http://");
+        if !absolute || uri.contains('#') {
+            return Err(RegistrationError::redirect(&format!(
+                "redirect_uri must be an absolute URI without fragment: {}",
+                uri
+            )));
+        }
+    }
+
+    let scopes: Vec<String> = req
+        .scope
+        .as_deref()
+        .unwrap_or("read")
+        .split_whitespace()
+        .map(|s| s.to_string())
+        .collect();
+
+    let secret = match client_type {
+        ClientType::Confidential => Some(generate_secret()),
+        ClientType::Public => None,
+    };
+    let client = OAuthClient {
+        client_id: Uuid::new_v4().to_string(),
+        client_name: req.client_name,
+        client_type,
+        secret_hash: secret.as_deref().map(hash_secret),
+        redirect_uris: req.redirect_uris,
+        grant_types,
+        scopes,
+        first_party: false,
+        created_at: Utc::now(),
+        secret_rotated_at: None,
+    };
+    Ok((client, secret))
+}
+
+pub trait ClientStore: Send + Sync + 'static {
+    fn insert(&self, client: OAuthClient) -> Result<(), StoreError>;
+    fn get(&self, client_id: &str) -> Result<Option<OAuthClient>, StoreError>;
+    fn remove(&self, client_id: &str) -> Result<Option<OAuthClient>, StoreError>;
+    fn list(&self) -> Result<Vec<OAuthClient>, StoreError>;
+}
+
+/// Simple in-memory client registry; used by default and by tests
+pub struct InMemoryClientStore {
+    inner: Mutex<HashMap<String, OAuthClient>>,
+}
+
+impl InMemoryClientStore {
+    pub fn new() -> Self {
+        InMemoryClientStore {
+            inner: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Registry pre-seeded with the local development client (`demo` / `secret`)
+    /// used by the docs UI and the examples in the README. Its secret is public, so servers
+    /// only seed it when `VERSEGUY_API_DEMO_CLIENT=1`.
+    pub fn with_demo_client() -> Self {
+        let store = Self::new();
+        let demo = OAuthClient {
+            client_id: "demo".to_string(),
+            client_name: Some("Local demo client".to_string()),
+            client_type: ClientType::Confidential,
+            secret_hash: Some(hash_secret("secret")),
+            redirect_uris: vec![
+                "https://example.com/cb".to_string(),
+                "http://localhost:3000/static/swagger-ui/oauth-callback.html".to_string(),
+            ],
+            grant_types: SUPPORTED_GRANTS.iter().map(|g| g.to_string()).collect(),
+            scopes: vec!["read".to_string(), "write".to_string()],
+            first_party: true,
+            created_at: Utc::now(),
+            secret_rotated_at: None,
+        };
+        if let Ok(mut m) = store.inner.lock() {
+            m.insert(demo.client_id.clone(), demo);
+        }
+        store
+    }
+}
+
+impl Default for InMemoryClientStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientStore for InMemoryClientStore {
+    fn insert(&self, client: OAuthClient) -> Result<(), StoreError> {
+        match self.inner.lock() {
+            Ok(mut m) => {
+                m.insert(client.client_id.clone(), client);
+                Ok(())
+            }
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+
+    fn get(&self, client_id: &str) -> Result<Option<OAuthClient>, StoreError> {
+        match self.inner.lock() {
+            Ok(m) => Ok(m.get(client_id).cloned()),
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+
+    fn remove(&self, client_id: &str) -> Result<Option<OAuthClient>, StoreError> {
+        match self.inner.lock() {
+            Ok(mut m) => Ok(m.remove(client_id)),
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<OAuthClient>, StoreError> {
+        match self.inner.lock() {
+            Ok(m) => Ok(m.values().cloned().collect()),
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+}
+
+/// Sled-backed client registry for persistence
+pub struct SledClientStore {
+    db: sled::Db,
+}
+
+impl SledClientStore {
+    pub fn new(path: &str) -> Result<Self, StoreError> {
+        match sled::open(path) {
+            Ok(db) => Ok(SledClientStore { db }),
+            Err(e) => Err(StoreError::Backend(format!("sled open: {}", e))),
+        }
+    }
+}
+
+impl ClientStore for SledClientStore {
+    fn insert(&self, client: OAuthClient) -> Result<(), StoreError> {
+        match serde_json::to_vec(&client) {
+            Ok(bytes) => match self.db.insert(client.client_id.as_bytes(), bytes) {
+                Ok(_) => {
+                    let _ = self.db.flush();
+                    Ok(())
+                }
+                Err(e) => Err(StoreError::Backend(format!("sled insert: {}", e))),
+            },
+            Err(e) => Err(StoreError::Backend(format!("serialize: {}", e))),
+        }
+    }
+
+    fn get(&self, client_id: &str) -> Result<Option<OAuthClient>, StoreError> {
+        match self.db.get(client_id.as_bytes()) {
+            Ok(Some(iv)) => match serde_json::from_slice(&iv) {
+                Ok(c) => Ok(Some(c)),
+                Err(e) => Err(StoreError::Backend(format!("deserialize: {}", e))),
+            },
+            Ok(None) => Ok(None),
+            Err(e) => Err(StoreError::Backend(format!("sled get: {}", e))),
+        }
+    }
+
+    fn remove(&self, client_id: &str) -> Result<Option<OAuthClient>, StoreError> {
+        match self.db.remove(client_id.as_bytes()) {
+            Ok(Some(iv)) => match serde_json::from_slice(&iv) {
+                Ok(c) => {
+                    let _ = self.db.flush();
+                    Ok(Some(c))
+                }
+                Err(e) => Err(StoreError::Backend(format!("deserialize: {}", e))),
+            },
+            Ok(None) => Ok(None),
+            Err(e) => Err(StoreError::Backend(format!("sled remove: {}", e))),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<OAuthClient>, StoreError> {
+        let mut out = Vec::new();
+        for item in self.db.iter() {
+            match item {
+                Ok((_, iv)) => match serde_json::from_slice(&iv) {
+                    Ok(c) => out.push(c),
+                    Err(e) => return Err(StoreError::Backend(format!("deserialize: {}", e))),
+                },
+                Err(e) => return Err(StoreError::Backend(format!("sled iter: {}", e))),
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Client registry backend selection, injected into the router via `build_app_with_stores`
+#[derive(Clone, Debug)]
+pub enum ClientStoreConfig {
+    /// Per-process registry; seeded with the `demo` client only when `demo_client` is set
+    Memory {
+        demo_client: bool,
+    },
+    Sled {
+        path: String,
+    },
+}
+
+impl ClientStoreConfig {
+    /// Read the backend from the environment:
+    ///
+    /// - `VERSEGUY_API_CLIENT_STORE`: `memory` (default) or `sled`
+    /// - `VERSEGUY_API_CLIENT_STORE_PATH`: Sled database path, default `data/verseguy_clients`
+    /// - `VERSEGUY_API_DEMO_CLIENT=1`: seed the in-memory registry with the `demo` client, for
+    ///   local development only
+    pub fn from_env() -> Result<Self, StoreError> {
+        let demo_client = std::env::var("VERSEGUY_API_DEMO_CLIENT")
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        match std::env::var("VERSEGUY_API_CLIENT_STORE")
+            .unwrap_or_default()
+            .as_str()
+        {
+            "" | "memory" => Ok(ClientStoreConfig::Memory { demo_client }),
+            "sled" => Ok(ClientStoreConfig::Sled {
+                path: std::env::var("VERSEGUY_API_CLIENT_STORE_PATH")
+                    .unwrap_or_else(|_| "data/verseguy_clients".into()),
+            }),
+            other => Err(StoreError::Backend(format!(
+                "unknown client store backend: {}",
+                other
+            ))),
+        }
+    }
+
+    /// Open the configured backend; a registry that cannot be opened is an error rather than
+    /// a fallback to another one
+    pub fn open(&self) -> Result<Arc<dyn ClientStore>, StoreError> {
+        match self {
+            ClientStoreConfig::Memory { demo_client: true } => {
+                tracing::warn!("seeding the client registry with the public demo client");
+                Ok(Arc::new(InMemoryClientStore::with_demo_client()))
+            }
+            ClientStoreConfig::Memory { demo_client: false } => {
+                Ok(Arc::new(InMemoryClientStore::new()))
+            }
+            ClientStoreConfig::Sled { path } => Ok(Arc::new(SledClientStore::new(path)?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registration_validates_metadata() {
+        let req = ClientRegistrationRequest {
+            redirect_uris: vec!["https://app.example/cb".into()],
+            client_name: Some("app".into()),
+            grant_types: None,
+            token_endpoint_auth_method: None,
+            scope: Some("read write".into()),
+        };
+        let (client, secret) = match register_client(req.clone()) {
+            Ok(r) => r,
+            Err(e) => panic!("registration failed: {:?}", e),
+        };
+        let secret = match secret {
+            Some(s) => s,
+            None => panic!("confidential client without secret"),
+        };
+        assert!(client.verify_secret(&secret));
+        assert!(!client.verify_secret("wrong"));
+        assert!(client.allows_redirect("https://app.example/cb"));
+        assert!(!client.allows_redirect("https://app.example/cb/../evil"));
+        assert!(client.allows_scopes("read write"));
+        assert!(!client.allows_scopes("admin"));
+
+        let public_cc = ClientRegistrationRequest {
+            grant_types: Some(vec![GRANT_CLIENT_CREDENTIALS.into()]),
+            token_endpoint_auth_method: Some("none".into()),
+            ..req.clone()
+        };
+        assert!(register_client(public_cc).is_err());
+
+        let fragment = ClientRegistrationRequest {
+            redirect_uris: vec!["https://app.example/cb#x".into()],
+            ..req
+        };
+        match register_client(fragment) {
+            Err(e) => assert_eq!(e.error, "invalid_redirect_uri"),
+            Ok(_) => panic!("fragment redirect accepted"),
+        }
+    }
+
+    #[test]
+    fn client_store_config_seeds_demo_only_when_asked() {
+        std::env::set_var("VERSEGUY_API_CLIENT_STORE", "floppy");
+        assert!(ClientStoreConfig::from_env().is_err());
+        std::env::set_var("VERSEGUY_API_CLIENT_STORE", "memory");
+        std::env::remove_var("VERSEGUY_API_DEMO_CLIENT");
+        assert!(matches!(
+            ClientStoreConfig::from_env(),
+            Ok(ClientStoreConfig::Memory { demo_client: false })
+        ));
+        let config = ClientStoreConfig::Memory { demo_client: false };
+        let store = match config.open() {
+            Ok(s) => s,
+            Err(e) => panic!("open failed: {:?}", e),
+        };
+        assert!(matches!(store.get("demo"), Ok(None)));
+        std::env::set_var("VERSEGUY_API_DEMO_CLIENT", "1");
+        assert!(matches!(
+            ClientStoreConfig::from_env(),
+            Ok(ClientStoreConfig::Memory { demo_client: true })
+        ));
+        std::env::remove_var("VERSEGUY_API_DEMO_CLIENT");
+        std::env::remove_var("VERSEGUY_API_CLIENT_STORE");
+
+        let demo = match InMemoryClientStore::with_demo_client().get("demo") {
+            Ok(Some(c)) => c,
+            other => panic!("demo client missing: {:?}", other),
+        };
+        assert!(demo
+            .redirect_uris
+            .iter()
+            .all(|u| u.starts_with("https://") || u.starts_with("http://localhost")));
+    }
+
+    #[test]
+    fn unusable_sled_path_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("verseguy_clients_{}", uuid::Uuid::new_v4()));
+        if let Err(e) = std::fs::write(&dir, b"not a database") {
+            panic!("write failed: {}", e);
+        }
+        let config = ClientStoreConfig::Sled {
+            path: dir.to_string_lossy().into_owned(),
+        };
+        assert!(config.open().is_err());
+        let _ = std::fs::remove_file(&dir);
+    }
+}
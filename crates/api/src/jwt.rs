@@ -0,0 +1,260 @@
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::store::TokenStore;
+
+/// Default access token lifetime in seconds
+pub const ACCESS_TOKEN_TTL_SECS: i64 = 3600;
+
+#[derive(Debug)]
+pub enum TokenError {
+    Invalid(String),
+    Revoked,
+    Encoding(String),
+    /// The revocation list could not be read or written
+    Store(String),
+    /// `VERSEGUY_API_JWT_SECRET` is unset outside dev mode
+    MissingSecret,
+}
+
+/// Claims carried by access tokens issued from `/oauth/token`
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct AccessClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    pub jti: String,
+    pub client_id: String,
+    /// Space-separated granted scopes
+    pub scope: String,
+}
+
+impl AccessClaims {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope.split_whitespace().any(|s| s == scope)
+    }
+}
+
+/// Signs and validates HS256 access tokens. Revoked token ids are kept as `revoked:{jti}`
+/// auxiliary records of the token store until the token expires, so every instance sharing the
+/// store sees them and they survive restarts.
+pub struct JwtIssuer {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    issuer: String,
+    audience: String,
+    ttl_secs: i64,
+    revocations: Arc<dyn TokenStore>,
+}
+
+impl JwtIssuer {
+    pub fn new(
+        secret: &[u8],
+        issuer: &str,
+        audience: &str,
+        revocations: Arc<dyn TokenStore>,
+    ) -> Self {
+        JwtIssuer {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            issuer: issuer.to_string(),
+            audience: audience.to_string(),
+            ttl_secs: ACCESS_TOKEN_TTL_SECS,
+            revocations,
+        }
+    }
+
+    /// Issuer with a random per-process signing key (tests and local development)
+    pub fn ephemeral(revocations: Arc<dyn TokenStore>) -> Self {
+        let secret = crate::clients::generate_secret();
+        Self::new(
+            secret.as_bytes(),
+            "verseguy-api",
+            "verseguy-api",
+            revocations,
+        )
+    }
+
+    /// Configure from `VERSEGUY_API_JWT_SECRET`, `VERSEGUY_API_ISSUER` and `VERSEGUY_API_AUDIENCE`.
+    /// A missing secret is an error unless `VERSEGUY_API_DEV_MODE=1`, which signs with a random
+    /// key so tokens do not survive a restart.
+    pub fn from_env(revocations: Arc<dyn TokenStore>) -> Result<Self, TokenError> {
+        let secret = match std::env::var("VERSEGUY_API_JWT_SECRET") {
+            Ok(s) if !s.is_empty() => s,
+            _ if crate::dev_mode() => {
+                tracing::warn!("VERSEGUY_API_JWT_SECRET not set; using an ephemeral signing key");
+                crate::clients::generate_secret()
+            }
+            _ => return Err(TokenError::MissingSecret),
+        };
+        let issuer =
+            std::env::var("VERSEGUY_API_ISSUER").unwrap_or_else(|_| "verseguy-api".to_string());
+        let audience =
+            std::env::var("VERSEGUY_API_AUDIENCE").unwrap_or_else(|_| "verseguy-api".to_string());
+        Ok(Self::new(
+            secret.as_bytes(),
+            &issuer,
+            &audience,
+            revocations,
+        ))
+    }
+
+    pub fn ttl_secs(&self) -> i64 {
+        self.ttl_secs
+    }
+
+    pub fn issue(
+        &self,
+        sub: &str,
+        client_id: &str,
+        scope: &str,
+    ) -> Result<(String, AccessClaims), TokenError> {
+        let now = Utc::now().timestamp();
+        let claims = AccessClaims {
+            iss: self.issuer.clone(),
+            sub: sub.to_string(),
+            aud: self.audience.clone(),
+            exp: now + self.ttl_secs,
+            iat: now,
+            jti: Uuid::new_v4().to_string(),
+            client_id: client_id.to_string(),
+            scope: scope.to_string(),
+        };
+        match encode(&Header::new(Algorithm::HS256), &claims, &self.encoding) {
+            Ok(token) => Ok((token, claims)),
+            Err(e) => Err(TokenError::Encoding(e.to_string())),
+        }
+    }
+
+    fn decode_with(&self, token: &str, validate_exp: bool) -> Result<AccessClaims, TokenError> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        validation.validate_exp = validate_exp;
+        match decode::<AccessClaims>(token, &self.decoding, &validation) {
+            Ok(data) => Ok(data.claims),
+            Err(e) => Err(TokenError::Invalid(e.to_string())),
+        }
+    }
+
+    /// Validate signature, issuer, audience, expiry and revocation
+    pub async fn verify(&self, token: &str) -> Result<AccessClaims, TokenError> {
+        let claims = self.decode(token)?;
+        if self.is_revoked(&claims.jti).await? {
+            return Err(TokenError::Revoked);
+        }
+        Ok(claims)
+    }
+
+    /// Validate signature, issuer, audience and expiry without consulting the revocation list
+    pub fn decode(&self, token: &str) -> Result<AccessClaims, TokenError> {
+        self.decode_with(token, true)
+    }
+
+    /// Validate signature, issuer and audience but accept expired tokens
+    pub fn decode_allow_expired(&self, token: &str) -> Result<AccessClaims, TokenError> {
+        self.decode_with(token, false)
+    }
+
+    /// Record the token as revoked until it expires; expired tokens need no record
+    pub async fn revoke(&self, claims: &AccessClaims) -> Result<(), TokenError> {
+        let remaining = claims.exp - Utc::now().timestamp();
+        if remaining <= 0 {
+            return Ok(());
+        }
+        self.revocations
+            .put_aux(
+                &revoked_key(&claims.jti),
+                claims.exp.to_string(),
+                Some(remaining),
+            )
+            .await
+            .map_err(|e| TokenError::Store(format!("{:?}", e)))
+    }
+
+    /// Errors when the revocation list cannot be read, so callers fail closed
+    pub async fn is_revoked(&self, jti: &str) -> Result<bool, TokenError> {
+        match self.revocations.get_aux(&revoked_key(jti)).await {
+            Ok(entry) => Ok(entry.is_some()),
+            Err(e) => Err(TokenError::Store(format!("{:?}", e))),
+        }
+    }
+}
+
+fn revoked_key(jti: &str) -> String {
+    format!("revoked:{}", jti)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::store::InMemoryTokenStore;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(rt) => rt,
+            Err(e) => panic!("failed to build runtime: {}", e),
+        }
+    }
+
+    #[test]
+    fn issue_verify_and_revoke() {
+        runtime().block_on(async {
+            let store: Arc<dyn TokenStore> = Arc::new(InMemoryTokenStore::new());
+            let issuer = JwtIssuer::new(b"test-secret", "iss", "aud", store.clone());
+            let (token, claims) = match issuer.issue("demo", "demo", "read write") {
+                Ok(t) => t,
+                Err(e) => panic!("issue failed: {:?}", e),
+            };
+            match issuer.verify(&token).await {
+                Ok(c) => {
+                    assert!(c.has_scope("read"));
+                    assert!(!c.has_scope("admin"));
+                }
+                Err(e) => panic!("verify failed: {:?}", e),
+            }
+
+            let other = JwtIssuer::new(b"test-secret", "iss", "other-aud", store.clone());
+            assert!(other.verify(&token).await.is_err());
+
+            assert!(issuer.revoke(&claims).await.is_ok());
+            assert!(matches!(
+                issuer.verify(&token).await,
+                Err(TokenError::Revoked)
+            ));
+
+            // a second instance sharing the store, e.g. after a restart, sees the revocation
+            let restarted = JwtIssuer::new(b"test-secret", "iss", "aud", store);
+            assert!(matches!(
+                restarted.verify(&token).await,
+                Err(TokenError::Revoked)
+            ));
+        });
+    }
+
+    #[test]
+    fn from_env_requires_a_secret_outside_dev_mode() {
+        let store: Arc<dyn TokenStore> = Arc::new(InMemoryTokenStore::new());
+        std::env::remove_var("VERSEGUY_API_JWT_SECRET");
+        std::env::remove_var("VERSEGUY_API_DEV_MODE");
+        assert!(matches!(
+            JwtIssuer::from_env(store.clone()),
+            Err(TokenError::MissingSecret)
+        ));
+        std::env::set_var("VERSEGUY_API_DEV_MODE", "1");
+        assert!(JwtIssuer::from_env(store.clone()).is_ok());
+        std::env::remove_var("VERSEGUY_API_DEV_MODE");
+        std::env::set_var("VERSEGUY_API_JWT_SECRET", "configured");
+        assert!(JwtIssuer::from_env(store).is_ok());
+        std::env::remove_var("VERSEGUY_API_JWT_SECRET");
+    }
+}
@@ -1,45 +1,153 @@
-use axum::{
-    extract::Json,
-    http::StatusCode,
-    response::IntoResponse,
-    routing::{get, post},
-    Router,
-};
+use axum::{extract::Json, http::StatusCode, response::IntoResponse, routing::get, Router};
 use serde::Serialize;
+use utoipa::{OpenApi, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
 use uuid::Uuid;
+use verseguy_shared_error::{AppError, ProblemDetails};
 
-/// Build a minimal API router with basic endpoints using the default global store.
+/// Build a minimal API router backed by an in-memory token store and an in-memory client
+/// registry seeded with the `demo` client (tests, local development). Servers open their
+/// configured backends with `TokenStoreConfig` and `ClientStoreConfig` and use
+/// `build_app_with_stores`.
 pub fn build_app() -> Router {
-    build_app_with_store(TOKEN_STORE.clone())
+    build_app_with_store(
+        std::sync::Arc::new(store::InMemoryTokenStore::new()),
+        std::sync::Arc::new(clients::InMemoryClientStore::with_demo_client()),
+    )
+}
+
+/// Build a router with explicit token and client registry backends whose access tokens are
+/// signed with a random per-process key (tests and local development)
+pub fn build_app_with_store(
+    store: std::sync::Arc<dyn store::TokenStore>,
+    clients: std::sync::Arc<dyn ClientStore>,
+) -> Router {
+    let issuer = std::sync::Arc::new(JwtIssuer::ephemeral(store.clone()));
+    build_router(store, clients, issuer, None)
+}
+
+/// Build a router with explicit token and client registry backends. The signing key comes from
+/// `VERSEGUY_API_JWT_SECRET`, which is required outside dev mode.
+pub fn build_app_with_stores(
+    store: std::sync::Arc<dyn store::TokenStore>,
+    clients: std::sync::Arc<dyn ClientStore>,
+) -> Result<Router, TokenError> {
+    let issuer = std::sync::Arc::new(JwtIssuer::from_env(store.clone())?);
+    Ok(build_router(store, clients, issuer, None))
+}
+
+/// Build a router that also serves the `/v1` domain API (organizations, members, ranks, ships
+/// and operations) from `domain`
+pub fn build_app_with_domain(
+    store: std::sync::Arc<dyn store::TokenStore>,
+    clients: std::sync::Arc<dyn ClientStore>,
+    domain: std::sync::Arc<v1::DomainServices>,
+) -> Result<Router, TokenError> {
+    let issuer = std::sync::Arc::new(JwtIssuer::from_env(store.clone())?);
+    Ok(build_router(store, clients, issuer, Some(domain)))
+}
+
+/// `VERSEGUY_API_DEV_MODE=1` relaxes startup checks for local development, e.g. allows an
+/// ephemeral JWT signing key
+pub fn dev_mode() -> bool {
+    std::env::var("VERSEGUY_API_DEV_MODE")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// Routes documented in the OpenAPI document. Each handler is registered together with its
+/// `#[utoipa::path]` operation, so a route cannot be added without showing up in the spec.
+fn api_routes() -> OpenApiRouter {
+    OpenApiRouter::with_openapi(openapi::ApiDoc::openapi())
+        .routes(routes!(health_handler))
+        .routes(routes!(metrics_handler))
+        .routes(routes!(protected_handler))
+        .routes(routes!(
+            admin::list_clients_handler,
+            admin::register_client_handler
+        ))
+        .routes(routes!(
+            admin::get_client_handler,
+            admin::delete_client_handler
+        ))
+        .routes(routes!(admin::rotate_secret_handler))
+}
+
+/// OAuth endpoints authenticating a client, limited per client
+fn oauth_routes() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(token_handler))
+        .routes(routes!(introspect_handler))
+        .routes(routes!(revoke_handler))
+}
+
+fn authorize_routes() -> OpenApiRouter {
+    OpenApiRouter::new().routes(routes!(
+        authorize::authorize_handler,
+        authorize::consent_handler
+    ))
+}
+
+/// The complete OpenAPI document, including the `/v1` domain API
+pub fn openapi_spec() -> utoipa::openapi::OpenApi {
+    api_routes()
+        .merge(oauth_routes())
+        .merge(authorize_routes())
+        .merge(v1::routes())
+        .into_openapi()
 }
 
-/// Build a router that uses the provided TokenStore (useful for tests and injected backends)
-pub fn build_app_with_store(store: std::sync::Arc<dyn store::TokenStore>) -> Router {
+fn build_router(
+    store: std::sync::Arc<dyn store::TokenStore>,
+    clients: std::sync::Arc<dyn ClientStore>,
+    issuer: std::sync::Arc<JwtIssuer>,
+    domain: Option<std::sync::Arc<v1::DomainServices>>,
+) -> Router {
     use axum::Extension;
-    Router::new()
-        .route("/health", get(health_handler))
-        .route("/metrics", get(metrics_handler))
-        .route("/protected", get(protected_handler))
-        .route("/oauth/token", post(token_handler))
-        .route("/oauth/authorize", get(authorize_handler))
-        .route("/openapi.yaml", get(openapi_handler))
+    let sessions: std::sync::Arc<dyn SessionVerifier> =
+        std::sync::Arc::new(JwtSessionVerifier::from_env());
+    let limits = rate_limit::RateLimits::from_env(issuer.clone());
+    let mut api = api_routes()
+        .merge(oauth_routes().route_layer(limits.oauth()))
+        .merge(authorize_routes().route_layer(limits.authorize()));
+    if let Some(domain) = domain {
+        api = api.merge(
+            v1::routes()
+                .route_layer(limits.v1())
+                .layer(Extension(domain)),
+        );
+    }
+    // the served document describes exactly the routes of this router
+    let (app, spec) = api.split_for_parts();
+    app.route("/openapi.json", get(openapi_json_handler))
+        .route("/openapi.yaml", get(openapi_yaml_handler))
         .route("/docs", get(docs_handler))
         .route("/static/{*file}", get(static_handler))
+        .layer(Extension(std::sync::Arc::new(spec)))
         .layer(Extension(store))
+        .layer(Extension(clients))
+        .layer(Extension(issuer))
+        .layer(Extension(sessions))
+        .layer(axum::middleware::from_fn(verseguy_shared_error::request_id))
 }
 
-async fn openapi_handler() -> impl IntoResponse {
-    // Embed the OpenAPI YAML for serving
-    let yaml = include_str!("../openapi.yaml");
-    (
-        axum::http::StatusCode::OK,
-        [("content-type", "text/yaml")],
-        yaml,
-    )
+type Spec = axum::Extension<std::sync::Arc<utoipa::openapi::OpenApi>>;
+
+async fn openapi_json_handler(axum::Extension(spec): Spec) -> impl IntoResponse {
+    Json(spec.as_ref().clone())
+}
+
+async fn openapi_yaml_handler(axum::Extension(spec): Spec) -> axum::response::Response {
+    match spec.to_yaml() {
+        Ok(yaml) => (StatusCode::OK, [("content-type", "text/yaml")], yaml).into_response(),
+        Err(e) => {
+            AppError::internal(format!("failed to render openapi yaml: {}", e)).into_response()
+        }
+    }
 }
 
 async fn docs_handler() -> impl IntoResponse {
-    // Local Swagger UI that points to `/openapi.yaml` and uses offline assets served from /static/
+    // Local Swagger UI that points to `/openapi.json` and uses offline assets served from /static/
     const HTML: &str = r#"<!doctype html>
 <html>
   <head>
@@ -66,13 +174,13 @@ async fn docs_handler() -> impl IntoResponse {
         // if standalone preset is available, use it
         const presets = typeof SwaggerUIStandalonePreset !== 'undefined' ? [SwaggerUIStandalonePreset] : [];
         try {
-          const ui = SwaggerUIBundle({ url: '/openapi.yaml', dom_id: '#swagger-ui', presets: presets });
+          const ui = SwaggerUIBundle({ url: '/openapi.json', dom_id: '#swagger-ui', presets: presets });
         } catch (e) {
           // fall back to interactive UI if initialization fails
-          if (window.renderInteractiveUI) window.renderInteractiveUI('/openapi.yaml', '#swagger-ui');
+          if (window.renderInteractiveUI) window.renderInteractiveUI('/openapi.json', '#swagger-ui');
         }
       } else if (window.renderInteractiveUI) {
-        window.renderInteractiveUI('/openapi.yaml', '#swagger-ui');
+        window.renderInteractiveUI('/openapi.json', '#swagger-ui');
       } else {
         document.getElementById('swagger-ui').innerText = 'No UI available';
       }
@@ -137,252 +245,599 @@ async fn static_handler(path: axum::extract::Path<String>) -> impl IntoResponse
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "system",
+    responses((status = 200, description = "Service is up", body = String))
+)]
 async fn health_handler() -> impl IntoResponse {
     (StatusCode::OK, "ok")
 }
 
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "system",
+    responses((status = 200, description = "Metrics (text)", body = String))
+)]
 async fn metrics_handler() -> impl IntoResponse {
     // placeholder; in real implementation this would return metrics
     (StatusCode::OK, "metrics: {}")
 }
 
-/// Minimal protected endpoint: requires header `x-api-key: secret` (placeholder auth)
-async fn protected_handler(req: axum::http::Request<axum::body::Body>) -> impl IntoResponse {
-    // simple placeholder check: accept either a non-empty x-api-key header or a Bearer token in Authorization
-    if let Some(v) = req.headers().get("x-api-key") {
-        if !v.as_bytes().is_empty() {
-            return (StatusCode::OK, "authorized");
-        }
-    }
-
-    if let Some(h) = req.headers().get("authorization") {
-        if let Ok(s) = h.to_str() {
-            if s.to_ascii_lowercase().starts_with("bearer ") {
-                let token = s[7..].trim();
-                if !token.is_empty() {
-                    return (StatusCode::OK, "authorized");
-                }
-            }
-        }
-    }
-
-    (StatusCode::UNAUTHORIZED, "unauthorized")
+/// Minimal protected endpoint: requires a valid access token with the `read` scope
+#[utoipa::path(
+    get,
+    path = "/protected",
+    tag = "system",
+    security(("oauth2" = ["read"])),
+    responses(
+        (status = 200, description = "authorized", body = String),
+        (
+            status = 401,
+            description = "Missing, invalid, expired or revoked token",
+            body = ProblemDetails
+        ),
+        (
+            status = 403,
+            description = "Token lacks the `read` scope (insufficient_scope)",
+            body = ProblemDetails
+        ),
+    )
+)]
+async fn protected_handler(auth: Bearer<ReadScope>) -> impl IntoResponse {
+    tracing::debug!("protected resource accessed by {}", auth.claims.client_id);
+    (StatusCode::OK, "authorized")
 }
 
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 /// OAuth2 token endpoint (initial: client_credentials grant)
 use std::collections::HashMap;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct TokenResponse {
+    /// Signed JWT (HS256) with `iss`, `aud`, `sub`, `client_id`, `scope` and `jti` claims
     access_token: String,
+    /// Always `bearer`
     token_type: &'static str,
+    /// Lifetime of the access token in seconds
     expires_in: u64,
     refresh_token: Option<String>,
+    /// Scopes granted by the token
     scope: Option<String>,
 }
 
+pub mod admin;
+pub mod auth;
+pub mod authorize;
+pub mod clients;
+pub mod jwt;
+pub mod openapi;
+pub mod rate_limit;
+pub mod session;
 pub mod store;
-use crate::store::{TokenRecord, TOKEN_STORE};
-use once_cell::sync::Lazy;
-use std::sync::Mutex;
-
-struct CodeRecord {
-    client_id: String,
-    redirect_uri: String,
-    expires_at: DateTime<Utc>,
+pub mod v1;
+use crate::auth::{Bearer, ReadScope};
+use crate::clients::{
+    ClientStore, OAuthClient, GRANT_AUTHORIZATION_CODE, GRANT_CLIENT_CREDENTIALS,
+    GRANT_REFRESH_TOKEN,
+};
+use crate::jwt::{AccessClaims, JwtIssuer, TokenError};
+use crate::session::{JwtSessionVerifier, SessionVerifier};
+use crate::store::{Rotation, TokenRecord, REFRESH_TOKEN_TTL_SECS};
+use base64::Engine;
+
+/// OAuth 2.0 error (RFC 6749 section 5.2); `code` is the protocol's `error` value
+fn oauth_error(status: StatusCode, code: &'static str) -> AppError {
+    let detail = match code {
+        "invalid_client" => "client authentication failed",
+        "invalid_request" => "missing or mismatched request parameters",
+        "invalid_refresh_token" => "unknown, expired or revoked refresh token",
+        "refresh_token_reused" => "refresh token was already used; the grant has been revoked",
+        "unauthorized_client" => "the client may not use this grant type",
+        "expired_code" => "the authorization code has expired",
+        "invalid_code" => "unknown or already redeemed authorization code",
+        "invalid_grant" => "PKCE verification failed or the grant belongs to another client",
+        "invalid_scope" => "requested scope exceeds the scopes registered for the client",
+        "unsupported_grant_type" => {
+            "supported grant types are client_credentials, \
+                                     authorization_code and refresh_token"
+        }
+        _ => "",
+    };
+    AppError::coded(status, code, detail)
 }
 
-static CODE_STORE: Lazy<Mutex<HashMap<String, CodeRecord>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
+/// Extract `client_secret_basic` credentials from the Authorization header
+fn basic_credentials(headers: &axum::http::HeaderMap) -> Option<(String, String)> {
+    let value = headers.get("authorization")?.to_str().ok()?;
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let pair = String::from_utf8(decoded).ok()?;
+    let (id, secret) = pair.split_once(':')?;
+    Some((id.to_string(), secret.to_string()))
+}
 
-async fn authorize_handler(req: axum::http::Request<axum::body::Body>) -> impl IntoResponse {
-    // parse query params
-    let q = req.uri().query().unwrap_or("");
-    let params: HashMap<String, String> = match serde_urlencoded::from_str(q) {
-        Ok(m) => m,
-        Err(_) => return (StatusCode::BAD_REQUEST, "invalid_query").into_response(),
+/// Authenticate the calling client with HTTP Basic (`client_secret_basic`) or form fields
+/// (`client_secret_post`). Public clients only identify themselves with `client_id`.
+fn authenticate_client(
+    clients: &dyn ClientStore,
+    headers: &axum::http::HeaderMap,
+    params: &HashMap<String, String>,
+) -> Result<OAuthClient, AppError> {
+    let (client_id, secret) = match basic_credentials(headers) {
+        Some((id, secret)) => (id, Some(secret)),
+        None => (
+            params.get("client_id").cloned().unwrap_or_default(),
+            params.get("client_secret").cloned(),
+        ),
     };
+    let client = match clients.get(&client_id) {
+        Ok(Some(c)) => c,
+        Ok(None) => return Err(oauth_error(StatusCode::UNAUTHORIZED, "invalid_client")),
+        Err(_) => return Err(AppError::internal("token store failure")),
+    };
+    if client.is_confidential() {
+        match secret {
+            Some(s) if client.verify_secret(&s) => {}
+            _ => return Err(oauth_error(StatusCode::UNAUTHORIZED, "invalid_client")),
+        }
+    }
+    Ok(client)
+}
 
-    // require response_type=code
-    if params.get("response_type").map(|s| s.as_str()) != Some("code") {
-        return (StatusCode::BAD_REQUEST, "unsupported response_type").into_response();
+/// Issue a signed access token and, with `refresh`, a refresh token that starts a new token
+/// family
+async fn issue_tokens(
+    store: &dyn crate::store::TokenStore,
+    issuer: &JwtIssuer,
+    sub: &str,
+    client_id: &str,
+    scope: &str,
+    refresh: bool,
+) -> Result<TokenResponse, AppError> {
+    if !refresh {
+        let (access_token, _) = match issuer.issue(sub, client_id, scope) {
+            Ok(t) => t,
+            Err(_) => return Err(AppError::internal("failed to sign access token")),
+        };
+        return Ok(TokenResponse {
+            access_token,
+            token_type: "bearer",
+            expires_in: issuer.ttl_secs() as u64,
+            refresh_token: None,
+            scope: Some(scope.to_string()),
+        });
     }
+    let rec = refresh_record(issuer, sub, client_id, scope, Uuid::new_v4().to_string())?;
+    if store
+        .insert(rec.refresh_token.clone(), rec.clone())
+        .await
+        .is_err()
+    {
+        return Err(AppError::internal("token store failure"));
+    }
+    Ok(token_response(issuer, rec, scope))
+}
 
-    // validate client_id and redirect_uri (simplified)
-    let client = params.get("client_id").map(|s| s.as_str()).unwrap_or("");
-    let redirect = match params.get("redirect_uri") {
-        Some(r) => r.clone(),
-        None => return (StatusCode::BAD_REQUEST, "missing redirect_uri").into_response(),
+/// Sign an access token and wrap it in a fresh refresh token record for `family_id`
+fn refresh_record(
+    issuer: &JwtIssuer,
+    sub: &str,
+    client_id: &str,
+    scope: &str,
+    family_id: String,
+) -> Result<TokenRecord, AppError> {
+    let (access_token, _) = match issuer.issue(sub, client_id, scope) {
+        Ok(t) => t,
+        Err(_) => return Err(AppError::internal("failed to sign access token")),
     };
-    if client != "demo" {
-        return (StatusCode::BAD_REQUEST, "unknown client").into_response();
+    Ok(TokenRecord {
+        access_token,
+        refresh_token: Uuid::new_v4().to_string(),
+        expires_at: Utc::now() + chrono::Duration::seconds(REFRESH_TOKEN_TTL_SECS),
+        family_id,
+        rotated_at: None,
+    })
+}
+
+fn token_response(issuer: &JwtIssuer, rec: TokenRecord, scope: &str) -> TokenResponse {
+    TokenResponse {
+        access_token: rec.access_token,
+        token_type: "bearer",
+        expires_in: issuer.ttl_secs() as u64,
+        refresh_token: Some(rec.refresh_token),
+        scope: Some(scope.to_string()),
     }
+}
 
-    // generate code and store it
-    let code = Uuid::new_v4().to_string();
-    let expires_at = Utc::now() + chrono::Duration::seconds(300);
-    let crec = CodeRecord {
-        client_id: client.to_string(),
-        redirect_uri: redirect.clone(),
-        expires_at,
+/// Drop every refresh token of a family and revoke the access tokens issued with them
+async fn revoke_family(
+    store: &dyn crate::store::TokenStore,
+    issuer: &JwtIssuer,
+    family_id: &str,
+) -> Result<(), AppError> {
+    let removed = match store.revoke_family(family_id).await {
+        Ok(r) => r,
+        Err(_) => return Err(AppError::internal("token store failure")),
     };
-    match CODE_STORE.lock() {
-        Ok(mut store) => {
-            store.insert(code.clone(), crec);
+    for rec in removed {
+        if let Ok(c) = issuer.decode_allow_expired(&rec.access_token) {
+            revoke_access_token(issuer, &c).await?;
         }
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "lock_error").into_response(),
     }
+    Ok(())
+}
 
-    // build redirect URL: redirect_uri?code=...&state=... (if provided)
-    let mut location = format!("{}?code={}", redirect, code);
-    if let Some(state) = params.get("state") {
-        location = format!("{}&state={}", location, state);
+async fn revoke_access_token(issuer: &JwtIssuer, claims: &AccessClaims) -> Result<(), AppError> {
+    match issuer.revoke(claims).await {
+        Ok(()) => Ok(()),
+        Err(_) => Err(AppError::internal("token store failure")),
     }
+}
 
-    // redirect
-    let res = match axum::http::Response::builder()
-        .status(StatusCode::FOUND)
-        .header(axum::http::header::LOCATION, location)
-        .body(axum::body::Body::empty())
-    {
-        Ok(r) => r,
-        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "build_error").into_response(),
+async fn read_form(
+    req: axum::http::Request<axum::body::Body>,
+) -> Result<HashMap<String, String>, AppError> {
+    let bytes = match axum::body::to_bytes(req.into_body(), 1024 * 1024).await {
+        Ok(b) => b,
+        Err(_) => return Err(AppError::BadRequest("failed to read body".into())),
     };
-    res.into_response()
+    match serde_urlencoded::from_bytes(&bytes) {
+        Ok(m) => Ok(m),
+        Err(_) => Err(AppError::BadRequest("malformed form body".into())),
+    }
 }
 
+/// Token endpoint for the client_credentials, refresh_token and authorization_code grants.
+/// Refresh tokens are only issued by the authorization_code grant, to clients registered for
+/// the refresh_token grant, and only the client they were issued to can exchange them. They
+/// rotate: each exchange returns a new refresh token and invalidates the old one; presenting an
+/// already used refresh token revokes every token of the same grant.
+#[utoipa::path(
+    post,
+    path = "/oauth/token",
+    tag = "oauth",
+    request_body(
+        content = openapi::TokenRequest,
+        content_type = "application/x-www-form-urlencoded"
+    ),
+    responses(
+        (
+            status = 200,
+            description = "Token response",
+            body = TokenResponse,
+            examples(
+                ("client_credentials" = (
+                    summary = "Client credentials response example",
+                    value = json!({
+                        "access_token": "abc",
+                        "token_type": "bearer",
+                        "expires_in": 3600,
+                        "scope": "read"
+                    })
+                )),
+                ("refresh_token" = (
+                    summary = "Refresh token response example",
+                    value = json!({
+                        "access_token": "def",
+                        "token_type": "bearer",
+                        "expires_in": 3600,
+                        "refresh_token": "r-456"
+                    })
+                )),
+                ("authorization_code" = (
+                    summary = "Authorization code response example",
+                    value = json!({
+                        "access_token": "ghi",
+                        "token_type": "bearer",
+                        "expires_in": 3600,
+                        "refresh_token": "r-789"
+                    })
+                )),
+            )
+        ),
+        (
+            status = 400,
+            description = "Malformed request, unsupported grant or invalid code",
+            body = ProblemDetails
+        ),
+        (
+            status = 401,
+            description = "invalid_client, invalid_refresh_token or refresh_token_reused",
+            body = ProblemDetails
+        ),
+    )
+)]
 async fn token_handler(
     axum::Extension(store): axum::Extension<std::sync::Arc<dyn crate::store::TokenStore>>,
+    axum::Extension(clients): axum::Extension<std::sync::Arc<dyn ClientStore>>,
+    axum::Extension(issuer): axum::Extension<std::sync::Arc<JwtIssuer>>,
     req: axum::http::Request<axum::body::Body>,
-) -> Result<Json<TokenResponse>, (StatusCode, &'static str)> {
-    // Read body and parse urlencoded form
-    let bytes = match axum::body::to_bytes(req.into_body(), 1024 * 1024).await {
-        Ok(b) => b,
-        Err(_) => return Err((StatusCode::BAD_REQUEST, "invalid_body")),
-    };
-    let params: HashMap<String, String> = match serde_urlencoded::from_bytes(&bytes) {
-        Ok(m) => m,
-        Err(_) => return Err((StatusCode::BAD_REQUEST, "invalid_form")),
-    };
+) -> Result<Json<TokenResponse>, AppError> {
+    let headers = req.headers().clone();
+    let params = read_form(req).await?;
 
     let grant = params.get("grant_type").map(|s| s.as_str()).unwrap_or("");
 
-    // Handle refresh_token grant
-    if grant == "refresh_token" {
-        if let Some(rtok) = params.get("refresh_token") {
-            match store.get(rtok) {
-                Ok(Some(rec)) => {
-                    // check expiry
-                    if rec.expires_at < Utc::now() {
-                        return Err((StatusCode::UNAUTHORIZED, "expired_refresh_token"));
-                    }
-
-                    let new_access = Uuid::new_v4().to_string();
-                    let new_rec = TokenRecord {
-                        access_token: new_access.clone(),
-                        refresh_token: rec.refresh_token.clone(),
-                        expires_at: Utc::now() + chrono::Duration::seconds(3600),
-                    };
-                    if store.insert(rtok.to_string(), new_rec).is_err() {
-                        return Err((StatusCode::INTERNAL_SERVER_ERROR, "store_error"));
-                    }
-
-                    let resp = TokenResponse {
-                        access_token: new_access,
-                        token_type: "bearer",
-                        expires_in: 3600,
-                        refresh_token: Some(rec.refresh_token.clone()),
-                        scope: None,
-                    };
-                    return Ok(Json(resp));
-                }
-                Ok(None) => return Err((StatusCode::UNAUTHORIZED, "invalid_refresh_token")),
-                Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "store_error")),
+    // Handle refresh_token grant: every use rotates the token, replaying an old one
+    // revokes the whole family
+    if grant == GRANT_REFRESH_TOKEN {
+        let client = authenticate_client(clients.as_ref(), &headers, &params)?;
+        if !client.allows_grant(GRANT_REFRESH_TOKEN) {
+            return Err(oauth_error(StatusCode::BAD_REQUEST, "unauthorized_client"));
+        }
+        let rtok = match params.get("refresh_token") {
+            Some(t) => t,
+            None => {
+                return Err(oauth_error(
+                    StatusCode::UNAUTHORIZED,
+                    "invalid_refresh_token",
+                ))
+            }
+        };
+        let rec = match store.get(rtok).await {
+            Ok(Some(rec)) => rec,
+            Ok(None) => {
+                return Err(oauth_error(
+                    StatusCode::UNAUTHORIZED,
+                    "invalid_refresh_token",
+                ))
             }
+            Err(_) => return Err(AppError::internal("token store failure")),
+        };
+        // the previous access token carries the subject, client and scope
+        let prev = match issuer.decode_allow_expired(&rec.access_token) {
+            Ok(c) => c,
+            Err(_) => {
+                return Err(oauth_error(
+                    StatusCode::UNAUTHORIZED,
+                    "invalid_refresh_token",
+                ))
+            }
+        };
+        // checked before reuse detection so other clients cannot revoke the family
+        if prev.client_id != client.client_id {
+            return Err(oauth_error(StatusCode::BAD_REQUEST, "invalid_grant"));
         }
-        return Err((StatusCode::UNAUTHORIZED, "invalid_refresh_token"));
+        if rec.rotated_at.is_some() {
+            tracing::warn!(
+                "refresh token reuse detected, revoking family {}",
+                rec.family()
+            );
+            revoke_family(store.as_ref(), &issuer, rec.family()).await?;
+            return Err(oauth_error(
+                StatusCode::UNAUTHORIZED,
+                "refresh_token_reused",
+            ));
+        }
+        let next = refresh_record(
+            &issuer,
+            &prev.sub,
+            &prev.client_id,
+            &prev.scope,
+            rec.family().to_string(),
+        )?;
+        return match store.rotate(rtok, next.clone()).await {
+            Ok(Rotation::Rotated(_)) => Ok(Json(token_response(&issuer, next, &prev.scope))),
+            Ok(Rotation::Reused(_)) => {
+                // lost a race against another exchange of the same token
+                revoke_family(store.as_ref(), &issuer, rec.family()).await?;
+                Err(oauth_error(
+                    StatusCode::UNAUTHORIZED,
+                    "refresh_token_reused",
+                ))
+            }
+            Ok(Rotation::Invalid) => Err(oauth_error(
+                StatusCode::UNAUTHORIZED,
+                "invalid_refresh_token",
+            )),
+            Err(_) => Err(AppError::internal("token store failure")),
+        };
     }
 
     // Authorization Code grant
-    if grant == "authorization_code" {
+    if grant == GRANT_AUTHORIZATION_CODE {
+        let client = authenticate_client(clients.as_ref(), &headers, &params)?;
+        if !client.allows_grant(GRANT_AUTHORIZATION_CODE) {
+            return Err(oauth_error(StatusCode::BAD_REQUEST, "unauthorized_client"));
+        }
         if let Some(code) = params.get("code") {
-            let mut code_store = match CODE_STORE.lock() {
-                Ok(s) => s,
-                Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "lock_error")),
+            let crec = match authorize::redeem_code(store.as_ref(), code).await {
+                Ok(c) => c,
+                Err(_) => return Err(AppError::internal("token store failure")),
             };
-            if let Some(crec) = code_store.remove(code) {
+            if let Some(crec) = crec {
                 // check expiry
                 if crec.expires_at < Utc::now() {
-                    return Err((StatusCode::UNAUTHORIZED, "expired_code"));
+                    return Err(oauth_error(StatusCode::UNAUTHORIZED, "expired_code"));
                 }
                 // verify client and redirect_uri
-                let client_ok =
-                    params.get("client_id").map(|s| s.as_str()) == Some(crec.client_id.as_str());
+                let client_ok = client.client_id == crec.client_id;
                 let redirect_ok = params.get("redirect_uri").map(|s| s.as_str())
                     == Some(crec.redirect_uri.as_str());
                 if !client_ok || !redirect_ok {
-                    return Err((StatusCode::BAD_REQUEST, "invalid_request"));
+                    return Err(oauth_error(StatusCode::BAD_REQUEST, "invalid_request"));
                 }
-                // issue tokens
-                let access_token = Uuid::new_v4().to_string();
-                let refresh_token = Uuid::new_v4().to_string();
-                let expires_at = Utc::now() + chrono::Duration::seconds(3600);
-                let trec = TokenRecord {
-                    access_token: access_token.clone(),
-                    refresh_token: refresh_token.clone(),
-                    expires_at,
-                };
-                if store.insert(refresh_token.clone(), trec).is_err() {
-                    return Err((StatusCode::INTERNAL_SERVER_ERROR, "store_error"));
+                let verifier = params.get("code_verifier").map(|s| s.as_str());
+                if !authorize::verify_pkce(crec.code_challenge.as_deref(), verifier) {
+                    return Err(oauth_error(StatusCode::BAD_REQUEST, "invalid_grant"));
                 }
-                let resp = TokenResponse {
-                    access_token,
-                    token_type: "bearer",
-                    expires_in: 3600,
-                    refresh_token: Some(refresh_token),
-                    scope: None,
-                };
+                let resp = issue_tokens(
+                    store.as_ref(),
+                    &issuer,
+                    &crec.subject,
+                    &client.client_id,
+                    &crec.scope,
+                    client.allows_grant(GRANT_REFRESH_TOKEN),
+                )
+                .await?;
                 return Ok(Json(resp));
             }
         }
-        return Err((StatusCode::BAD_REQUEST, "invalid_code"));
+        return Err(oauth_error(StatusCode::BAD_REQUEST, "invalid_code"));
     }
 
     // client_credentials
-    if grant != "client_credentials" {
-        return Err((StatusCode::BAD_REQUEST, "unsupported grant_type"));
+    if grant != GRANT_CLIENT_CREDENTIALS {
+        return Err(oauth_error(
+            StatusCode::BAD_REQUEST,
+            "unsupported_grant_type",
+        ));
     }
 
-    let client_id = params.get("client_id").map(|s| s.as_str());
-    let client_secret = params.get("client_secret").map(|s| s.as_str());
+    let client = authenticate_client(clients.as_ref(), &headers, &params)?;
+    if !client.is_confidential() || !client.allows_grant(GRANT_CLIENT_CREDENTIALS) {
+        return Err(oauth_error(StatusCode::BAD_REQUEST, "unauthorized_client"));
+    }
+    let scope = match params.get("scope") {
+        Some(requested) if !client.allows_scopes(requested) => {
+            return Err(oauth_error(StatusCode::BAD_REQUEST, "invalid_scope"));
+        }
+        Some(requested) => requested.clone(),
+        None => client.scopes.join(" "),
+    };
 
-    match (client_id, client_secret) {
-        (Some("demo"), Some("secret")) => {
-            let access_token = Uuid::new_v4().to_string();
-            let refresh_token = Uuid::new_v4().to_string();
-            let expires_at = Utc::now() + chrono::Duration::seconds(3600);
+    let resp = issue_tokens(
+        store.as_ref(),
+        &issuer,
+        &client.client_id,
+        &client.client_id,
+        &scope,
+        false,
+    )
+    .await?;
+    Ok(Json(resp))
+}
 
-            // Store record
-            let rec = TokenRecord {
-                access_token: access_token.clone(),
-                refresh_token: refresh_token.clone(),
-                expires_at,
-            };
-            if TOKEN_STORE.insert(refresh_token.clone(), rec).is_err() {
-                return Err((StatusCode::INTERNAL_SERVER_ERROR, "store_error"));
+/// Token introspection (RFC 7662); callers must authenticate as a confidential client
+#[utoipa::path(
+    post,
+    path = "/oauth/introspect",
+    tag = "oauth",
+    request_body(
+        content = openapi::TokenForm,
+        content_type = "application/x-www-form-urlencoded"
+    ),
+    responses(
+        (
+            status = 200,
+            description = "`{\"active\": false}` for unknown, expired or revoked tokens",
+            body = openapi::IntrospectionResponse
+        ),
+        (status = 400, description = "Missing token", body = ProblemDetails),
+        (status = 401, description = "invalid_client", body = ProblemDetails),
+    )
+)]
+async fn introspect_handler(
+    axum::Extension(store): axum::Extension<std::sync::Arc<dyn crate::store::TokenStore>>,
+    axum::Extension(clients): axum::Extension<std::sync::Arc<dyn ClientStore>>,
+    axum::Extension(issuer): axum::Extension<std::sync::Arc<JwtIssuer>>,
+    req: axum::http::Request<axum::body::Body>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let headers = req.headers().clone();
+    let params = read_form(req).await?;
+    let caller = authenticate_client(clients.as_ref(), &headers, &params)?;
+    if !caller.is_confidential() {
+        return Err(oauth_error(StatusCode::UNAUTHORIZED, "invalid_client"));
+    }
+    let token = match params.get("token") {
+        Some(t) => t,
+        None => return Err(oauth_error(StatusCode::BAD_REQUEST, "invalid_request")),
+    };
+
+    let inactive = serde_json::json!({ "active": false });
+    let access = match issuer.verify(token).await {
+        Err(TokenError::Store(_)) => return Err(AppError::internal("token store failure")),
+        other => other,
+    };
+    if let Ok(c) = access {
+        return Ok(Json(serde_json::json!({
+            "active": true,
+            "token_type": "access_token",
+            "scope": c.scope,
+            "client_id": c.client_id,
+            "sub": c.sub,
+            "aud": c.aud,
+            "iss": c.iss,
+            "exp": c.exp,
+            "iat": c.iat,
+            "jti": c.jti,
+        })));
+    }
+
+    match store.get(token).await {
+        Ok(Some(rec)) if rec.is_active(Utc::now()) => {
+            match issuer.decode_allow_expired(&rec.access_token) {
+                Ok(c) => Ok(Json(serde_json::json!({
+                    "active": true,
+                    "token_type": "refresh_token",
+                    "scope": c.scope,
+                    "client_id": c.client_id,
+                    "sub": c.sub,
+                    "exp": rec.expires_at.timestamp(),
+                }))),
+                Err(_) => Ok(Json(inactive)),
             }
+        }
+        Ok(_) => Ok(Json(inactive)),
+        Err(_) => Err(AppError::internal("token store failure")),
+    }
+}
 
-            let resp = TokenResponse {
-                access_token,
-                token_type: "bearer",
-                expires_in: 3600,
-                refresh_token: Some(refresh_token),
-                scope: params.get("scope").cloned(),
-            };
-            Ok(Json(resp))
+/// Token revocation (RFC 7009). Clients may only revoke their own tokens; unknown
+/// tokens still return 200 so callers cannot probe for valid values.
+#[utoipa::path(
+    post,
+    path = "/oauth/revoke",
+    tag = "oauth",
+    request_body(
+        content = openapi::TokenForm,
+        content_type = "application/x-www-form-urlencoded"
+    ),
+    responses(
+        (status = 200, description = "Token revoked (or unknown)"),
+        (status = 400, description = "Missing token", body = ProblemDetails),
+        (status = 401, description = "invalid_client", body = ProblemDetails),
+    )
+)]
+async fn revoke_handler(
+    axum::Extension(store): axum::Extension<std::sync::Arc<dyn crate::store::TokenStore>>,
+    axum::Extension(clients): axum::Extension<std::sync::Arc<dyn ClientStore>>,
+    axum::Extension(issuer): axum::Extension<std::sync::Arc<JwtIssuer>>,
+    req: axum::http::Request<axum::body::Body>,
+) -> Result<StatusCode, AppError> {
+    let headers = req.headers().clone();
+    let params = read_form(req).await?;
+    let client = authenticate_client(clients.as_ref(), &headers, &params)?;
+    let token = match params.get("token") {
+        Some(t) => t,
+        None => return Err(oauth_error(StatusCode::BAD_REQUEST, "invalid_request")),
+    };
+
+    // access token
+    if let Ok(c) = issuer.decode_allow_expired(token) {
+        if c.client_id == client.client_id {
+            revoke_access_token(&issuer, &c).await?;
         }
-        _ => Err((StatusCode::UNAUTHORIZED, "invalid_client")),
+        return Ok(StatusCode::OK);
+    }
+
+    // refresh token: revoke the whole grant, i.e. its token family and their access tokens
+    match store.get(token).await {
+        Ok(Some(rec)) => {
+            if let Ok(c) = issuer.decode_allow_expired(&rec.access_token) {
+                if c.client_id == client.client_id {
+                    revoke_family(store.as_ref(), &issuer, rec.family()).await?;
+                }
+            }
+            Ok(StatusCode::OK)
+        }
+        Ok(None) => Ok(StatusCode::OK),
+        Err(_) => Err(AppError::internal("token store failure")),
     }
 }
 
@@ -461,6 +916,7 @@ mod tests {
         };
 
         rt.block_on(async {
+            std::env::set_var("VERSEGUY_SESSION_SECRET", "test-session-secret");
             let app = build_app();
             let body = "grant_type=client_credentials&client_id=demo&client_secret=secret";
             let req = match axum::http::Request::builder()
@@ -487,38 +943,25 @@ mod tests {
                 Err(e) => panic!("invalid json response: {}", e),
             };
             assert!(v.get("access_token").is_some());
-            assert!(v.get("refresh_token").is_some());
+            // RFC 6749 section 4.4.3: no refresh token for client credentials
+            assert!(v.get("refresh_token").is_none());
             assert_eq!(v.get("token_type").and_then(|t| t.as_str()), Some("bearer"));
 
-            // Now attempt refresh
-            let rtok = match v.get("refresh_token").and_then(|r| r.as_str()) {
-                Some(s) => s.to_string(),
-                None => panic!("no refresh_token in response: {:?}", v),
-            };
-
-            // perform refresh token exchange
-            let body_refresh = format!("grant_type=refresh_token&refresh_token={}", rtok);
-            let req_refresh = match axum::http::Request::builder()
-                .method("POST")
-                .uri("/oauth/token")
-                .header("content-type", "application/x-www-form-urlencoded")
-                .body(Body::from(body_refresh))
-            {
-                Ok(r) => r,
-                Err(e) => panic!("failed to build request: {}", e),
-            };
-
-            let resp_refresh = match app.clone().oneshot(req_refresh).await {
-                Ok(r) => r,
-                Err(e) => panic!("refresh request failed: {}", e),
-            };
-            assert_eq!(resp_refresh.status(), StatusCode::OK);
-
             // Now test authorization code flow: request code via /oauth/authorize
             let auth_uri = "/oauth/authorize?response_type=code&client_id=demo&redirect_uri=https://example.com/cb&state=xyz";
+            let claims = serde_json::json!({ "sub": "user-1", "exp": Utc::now().timestamp() + 600 });
+            let session = match jsonwebtoken::encode(
+                &jsonwebtoken::Header::default(),
+                &claims,
+                &jsonwebtoken::EncodingKey::from_secret(b"test-session-secret"),
+            ) {
+                Ok(t) => t,
+                Err(e) => panic!("failed to sign session: {}", e),
+            };
             let req_auth = match axum::http::Request::builder()
                 .method("GET")
                 .uri(auth_uri)
+                .header("cookie", format!("verseguy_session={}", session))
                 .body(Body::empty())
             {
                 Ok(r) => r,
@@ -584,6 +1027,34 @@ mod tests {
                 Err(e) => panic!("invalid json response: {}", e),
             };
             assert!(v2.get("access_token").is_some());
+            let rtok = match v2.get("refresh_token").and_then(|r| r.as_str()) {
+                Some(s) => s.to_string(),
+                None => panic!("no refresh_token in response: {:?}", v2),
+            };
+
+            // the refresh token can only be exchanged by the authenticated client it belongs to
+            for (credentials, expected) in [
+                ("", StatusCode::UNAUTHORIZED),
+                ("&client_id=demo&client_secret=wrong", StatusCode::UNAUTHORIZED),
+                ("&client_id=demo&client_secret=secret", StatusCode::OK),
+            ] {
+                let body =
+                    format!("grant_type=refresh_token&refresh_token={}{}", rtok, credentials);
+                let req = match axum::http::Request::builder()
+                    .method("POST")
+                    .uri("/oauth/token")
+                    .header("content-type", "application/x-www-form-urlencoded")
+                    .body(Body::from(body))
+                {
+                    Ok(r) => r,
+                    Err(e) => panic!("failed to build request: {}", e),
+                };
+                let resp = match app.clone().oneshot(req).await {
+                    Ok(r) => r,
+                    Err(e) => panic!("refresh request failed: {}", e),
+                };
+                assert_eq!(resp.status(), expected, "credentials {:?}", credentials);
+            }
         });
     }
 }
@@ -0,0 +1,113 @@
+//! OpenAPI document of the API. Paths come from the `#[utoipa::path]` annotations of the
+//! handlers registered with `routes!`, so the served spec cannot drift from the router; this
+//! module holds the document root, the security scheme and schemas of form bodies that the
+//! handlers parse by hand.
+
+use serde::Deserialize;
+use utoipa::openapi::security::{
+    AuthorizationCode, ClientCredentials, Flow, OAuth2, Scopes, SecurityScheme,
+};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "VerseGuy API",
+        description = "OAuth 2.0 authorization server and domain API of VerseGuy"
+    ),
+    servers((url = "http://localhost:3000")),
+    modifiers(&SecurityAddon),
+    tags(
+        (name = "system", description = "Health and metrics"),
+        (name = "oauth", description = "OAuth 2.0 authorization server"),
+        (name = "admin", description = "OAuth client registry, guarded by `x-admin-token`"),
+        (name = "orgs", description = "Organizations, members and ranks"),
+        (name = "fleet", description = "Ships and loadouts of an organization's members"),
+        (name = "operations", description = "Operations and their participants"),
+    )
+)]
+pub struct ApiDoc;
+
+/// `oauth2` security scheme referenced by every protected operation
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let scopes = || Scopes::from_iter([("read", "read access"), ("write", "write access")]);
+        let flows = [
+            Flow::AuthorizationCode(AuthorizationCode::new(
+                "/oauth/authorize",
+                "/oauth/token",
+                scopes(),
+            )),
+            Flow::ClientCredentials(ClientCredentials::new("/oauth/token", scopes())),
+        ];
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("oauth2", SecurityScheme::OAuth2(OAuth2::new(flows)));
+    }
+}
+
+/// Form body of the token endpoint, one variant per grant type
+#[derive(Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum TokenRequest {
+    ClientCredentials(TokenRequestClientCredentials),
+    Refresh(TokenRequestRefresh),
+    AuthorizationCode(TokenRequestAuthorizationCode),
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TokenRequestClientCredentials {
+    #[schema(example = "client_credentials")]
+    pub grant_type: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Space-separated scopes; defaults to all scopes registered for the client
+    pub scope: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TokenRequestRefresh {
+    #[schema(example = "refresh_token")]
+    pub grant_type: String,
+    pub refresh_token: String,
+    /// The client the refresh token was issued to
+    pub client_id: String,
+    /// Required for confidential clients unless sent with HTTP Basic
+    pub client_secret: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TokenRequestAuthorizationCode {
+    #[schema(example = "authorization_code")]
+    pub grant_type: String,
+    pub code: String,
+    pub redirect_uri: String,
+    pub client_id: Option<String>,
+    /// PKCE verifier; required when the authorization request sent a `code_challenge`
+    pub code_verifier: Option<String>,
+}
+
+/// Form body of the introspection and revocation endpoints
+#[derive(Deserialize, ToSchema)]
+pub struct TokenForm {
+    pub token: String,
+    /// `access_token` or `refresh_token`
+    pub token_type_hint: Option<String>,
+}
+
+/// Introspection response (RFC 7662 section 2.2); only `active` is set for inactive tokens
+#[derive(Deserialize, ToSchema)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    /// `access_token` or `refresh_token`
+    pub token_type: Option<String>,
+    pub scope: Option<String>,
+    pub client_id: Option<String>,
+    pub sub: Option<String>,
+    pub aud: Option<String>,
+    pub iss: Option<String>,
+    pub exp: Option<i64>,
+    pub iat: Option<i64>,
+    pub jti: Option<String>,
+}
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+use verseguy_ratelimit::{
+    KeyBy, Limit, MemoryStore, Policy, RateLimitError, RateLimitLayer, RateLimitStore, RedisStore,
+    Subject,
+};
+
+use crate::jwt::JwtIssuer;
+
+/// Limits of the API's route groups:
+///
+/// - `oauth` (`/oauth/token`, `/oauth/introspect`, `/oauth/revoke`): per OAuth client
+/// - `authorize` (`/oauth/authorize`): per client IP
+/// - `v1` (domain API): per user of the access token
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub oauth: Limit,
+    pub authorize: Limit,
+    pub v1: Limit,
+    /// Share buckets between instances through Redis instead of counting in memory
+    pub redis_url: Option<String>,
+    /// Proxies in front of the API; the client IP is then taken from `X-Forwarded-For`
+    pub trusted_proxies: usize,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            oauth: Limit::per_minute(60),
+            authorize: Limit::per_minute(30),
+            v1: Limit::per_minute(300),
+            redis_url: None,
+            trusted_proxies: 0,
+        }
+    }
+}
+
+fn env_limit(name: &str, default: Limit) -> Result<Limit, RateLimitError> {
+    match std::env::var(name) {
+        Ok(v) => v.parse(),
+        Err(_) => Ok(default),
+    }
+}
+
+impl RateLimitConfig {
+    /// Read the limits from the environment as `<count>/<s|min|h|day>`:
+    ///
+    /// - `VERSEGUY_API_RATE_LIMIT_OAUTH` (default `60/min`)
+    /// - `VERSEGUY_API_RATE_LIMIT_AUTHORIZE` (default `30/min`)
+    /// - `VERSEGUY_API_RATE_LIMIT_V1` (default `300/min`)
+    /// - `VERSEGUY_API_RATE_LIMIT_REDIS_URL`: Redis URL, unset for in-memory buckets
+    /// - `VERSEGUY_API_TRUST_PROXY`: number of proxies appending to `X-Forwarded-For` (default 0)
+    pub fn from_env() -> Result<Self, RateLimitError> {
+        let d = RateLimitConfig::default();
+        Ok(RateLimitConfig {
+            oauth: env_limit("VERSEGUY_API_RATE_LIMIT_OAUTH", d.oauth)?,
+            authorize: env_limit("VERSEGUY_API_RATE_LIMIT_AUTHORIZE", d.authorize)?,
+            v1: env_limit("VERSEGUY_API_RATE_LIMIT_V1", d.v1)?,
+            redis_url: std::env::var("VERSEGUY_API_RATE_LIMIT_REDIS_URL")
+                .ok()
+                .filter(|u| !u.is_empty()),
+            trusted_proxies: match std::env::var("VERSEGUY_API_TRUST_PROXY") {
+                Ok(v) => v
+                    .trim()
+                    .parse()
+                    .map_err(|_| RateLimitError::InvalidProxyCount(v))?,
+                Err(_) => d.trusted_proxies,
+            },
+        })
+    }
+
+    pub fn open_store(&self) -> Result<Arc<dyn RateLimitStore>, RateLimitError> {
+        match &self.redis_url {
+            Some(url) => Ok(Arc::new(RedisStore::new(url)?)),
+            None => Ok(Arc::new(MemoryStore::new())),
+        }
+    }
+}
+
+/// Layers of the configured route groups, sharing one bucket store
+pub(crate) struct RateLimits {
+    config: RateLimitConfig,
+    store: Arc<dyn RateLimitStore>,
+    issuer: Arc<JwtIssuer>,
+}
+
+impl RateLimits {
+    /// Configuration from the environment; a broken configuration falls back to the defaults
+    /// with in-memory buckets rather than serving without limits
+    pub(crate) fn from_env(issuer: Arc<JwtIssuer>) -> Self {
+        let opened = RateLimitConfig::from_env().and_then(|c| c.open_store().map(|s| (s, c)));
+        let (store, config) = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                tracing::error!("invalid rate limit configuration, using defaults: {}", e);
+                let store: Arc<dyn RateLimitStore> = Arc::new(MemoryStore::new());
+                (store, RateLimitConfig::default())
+            }
+        };
+        RateLimits {
+            config,
+            store,
+            issuer,
+        }
+    }
+
+    pub(crate) fn oauth(&self) -> RateLimitLayer {
+        self.layer(Policy::new("oauth", KeyBy::OAuthClient, self.config.oauth))
+    }
+
+    pub(crate) fn authorize(&self) -> RateLimitLayer {
+        self.layer(Policy::new("authorize", KeyBy::Ip, self.config.authorize))
+    }
+
+    pub(crate) fn v1(&self) -> RateLimitLayer {
+        self.layer(Policy::new("v1", KeyBy::User, self.config.v1))
+    }
+
+    fn layer(&self, policy: Policy) -> RateLimitLayer {
+        let issuer = self.issuer.clone();
+        RateLimitLayer::new(policy, self.store.clone())
+            .trusted_proxies(self.config.trusted_proxies)
+            .resolve_subject(move |headers| {
+                let claims = headers
+                    .get(axum::http::header::AUTHORIZATION)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.strip_prefix("Bearer "))
+                    // only picks the bucket; revocation is checked by the handler
+                    .and_then(|token| issuer.decode(token.trim()).ok());
+                match claims {
+                    Some(claims) => Subject {
+                        user_id: Some(claims.sub),
+                        client_id: Some(claims.client_id),
+                        tier: None,
+                    },
+                    None => Subject::default(),
+                }
+            })
+    }
+}
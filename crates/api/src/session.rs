@@ -0,0 +1,59 @@
+use axum::http::{header, HeaderMap};
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+
+/// Cookie carrying the VerseGuy session JWT issued by the master server on login
+pub const SESSION_COOKIE: &str = "verseguy_session";
+
+/// Resolves a VerseGuy session token to the logged-in user id
+pub trait SessionVerifier: Send + Sync + 'static {
+    fn verify(&self, token: &str) -> Option<String>;
+}
+
+#[derive(Deserialize)]
+struct SessionClaims {
+    sub: String,
+}
+
+/// Verifies session JWTs signed by the master server (HS256, shared secret)
+pub struct JwtSessionVerifier {
+    key: Option<DecodingKey>,
+}
+
+impl JwtSessionVerifier {
+    pub fn new(secret: &[u8]) -> Self {
+        JwtSessionVerifier {
+            key: Some(DecodingKey::from_secret(secret)),
+        }
+    }
+
+    /// Read the shared secret from `VERSEGUY_SESSION_SECRET`; without it no session is accepted
+    pub fn from_env() -> Self {
+        match std::env::var("VERSEGUY_SESSION_SECRET") {
+            Ok(s) if !s.is_empty() => Self::new(s.as_bytes()),
+            _ => JwtSessionVerifier { key: None },
+        }
+    }
+}
+
+impl SessionVerifier for JwtSessionVerifier {
+    fn verify(&self, token: &str) -> Option<String> {
+        let key = self.key.as_ref()?;
+        let validation = Validation::new(Algorithm::HS256);
+        decode::<SessionClaims>(token, key, &validation)
+            .ok()
+            .map(|d| d.claims.sub)
+    }
+}
+
+/// Read the session token from the `verseguy_session` cookie
+pub fn session_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value.to_string())
+}
@@ -0,0 +1,145 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::{AuxEntry, Rotation, StoreError, TokenRecord, TokenStore};
+
+/// Simple in-memory store; used by default and by tests
+pub struct InMemoryTokenStore {
+    inner: Mutex<HashMap<String, TokenRecord>>,
+    aux: Mutex<HashMap<String, AuxEntry>>,
+}
+
+impl InMemoryTokenStore {
+    pub fn new() -> Self {
+        InMemoryTokenStore {
+            inner: Mutex::new(HashMap::new()),
+            aux: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for InMemoryTokenStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl TokenStore for InMemoryTokenStore {
+    async fn insert(&self, refresh_token: String, record: TokenRecord) -> Result<(), StoreError> {
+        match self.inner.lock() {
+            Ok(mut m) => {
+                m.insert(refresh_token, record);
+                Ok(())
+            }
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+
+    async fn get(&self, refresh_token: &str) -> Result<Option<TokenRecord>, StoreError> {
+        match self.inner.lock() {
+            Ok(m) => Ok(m
+                .get(refresh_token)
+                .filter(|r| !r.is_expired(Utc::now()))
+                .cloned()),
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+
+    async fn remove(&self, refresh_token: &str) -> Result<Option<TokenRecord>, StoreError> {
+        match self.inner.lock() {
+            Ok(mut m) => Ok(m.remove(refresh_token)),
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+
+    async fn rotate(&self, old: &str, new: TokenRecord) -> Result<Rotation, StoreError> {
+        let mut m = match self.inner.lock() {
+            Ok(m) => m,
+            Err(_) => return Err(StoreError::Backend("lock_error".into())),
+        };
+        let now = Utc::now();
+        let rec = match m.get_mut(old) {
+            Some(rec) if !rec.is_expired(now) => rec,
+            _ => return Ok(Rotation::Invalid),
+        };
+        if rec.rotated_at.is_some() {
+            return Ok(Rotation::Reused(rec.clone()));
+        }
+        rec.rotated_at = Some(now);
+        let rotated = rec.clone();
+        m.insert(new.refresh_token.clone(), new);
+        Ok(Rotation::Rotated(rotated))
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> Result<Vec<TokenRecord>, StoreError> {
+        match self.inner.lock() {
+            Ok(mut m) => {
+                let tokens: Vec<String> = m
+                    .iter()
+                    .filter(|(_, rec)| rec.family() == family_id)
+                    .map(|(token, _)| token.clone())
+                    .collect();
+                Ok(tokens.iter().filter_map(|t| m.remove(t)).collect())
+            }
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+
+    async fn purge_expired(&self) -> Result<usize, StoreError> {
+        let now = Utc::now();
+        let tokens = match self.inner.lock() {
+            Ok(mut m) => {
+                let before = m.len();
+                m.retain(|_, rec| !rec.is_expired(now));
+                before - m.len()
+            }
+            Err(_) => return Err(StoreError::Backend("lock_error".into())),
+        };
+        match self.aux.lock() {
+            Ok(mut m) => {
+                let before = m.len();
+                m.retain(|_, entry| !entry.is_expired(now));
+                Ok(tokens + before - m.len())
+            }
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+
+    async fn put_aux(
+        &self,
+        key: &str,
+        value: String,
+        ttl_secs: Option<i64>,
+    ) -> Result<(), StoreError> {
+        match self.aux.lock() {
+            Ok(mut m) => {
+                m.insert(key.to_string(), AuxEntry::new(value, ttl_secs));
+                Ok(())
+            }
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+
+    async fn get_aux(&self, key: &str) -> Result<Option<String>, StoreError> {
+        match self.aux.lock() {
+            Ok(m) => Ok(m
+                .get(key)
+                .filter(|e| !e.is_expired(Utc::now()))
+                .map(|e| e.value.clone())),
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+
+    async fn take_aux(&self, key: &str) -> Result<Option<String>, StoreError> {
+        match self.aux.lock() {
+            Ok(mut m) => Ok(m
+                .remove(key)
+                .filter(|e| !e.is_expired(Utc::now()))
+                .map(|e| e.value)),
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+}
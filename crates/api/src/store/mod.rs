@@ -0,0 +1,418 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+mod memory;
+mod redis_store;
+mod rocks_store;
+mod sled_store;
+
+pub use memory::InMemoryTokenStore;
+pub use redis_store::RedisTokenStore;
+pub use rocks_store::RocksTokenStore;
+pub use sled_store::SledTokenStore;
+
+/// Lifetime of a refresh token; every rotation starts a new window
+pub const REFRESH_TOKEN_TTL_SECS: i64 = 30 * 24 * 3600;
+/// Default interval of the background expiry sweep for in-process backends
+pub const DEFAULT_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+/// Default number of pooled Redis connections
+pub const DEFAULT_REDIS_POOL_SIZE: usize = 4;
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum StoreError {
+    Backend(String),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TokenRecord {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: DateTime<Utc>,
+    /// All refresh tokens rotated from the same grant share a family id
+    #[serde(default)]
+    pub family_id: String,
+    /// Set once the token has been exchanged; the record is kept to detect reuse
+    #[serde(default)]
+    pub rotated_at: Option<DateTime<Utc>>,
+}
+
+impl TokenRecord {
+    /// Family id, falling back to the token itself for records stored before rotation existed
+    pub fn family(&self) -> &str {
+        if self.family_id.is_empty() {
+            &self.refresh_token
+        } else {
+            &self.family_id
+        }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+
+    /// Not expired and not yet exchanged for a successor
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        !self.is_expired(now) && self.rotated_at.is_none()
+    }
+}
+
+/// Outcome of [`TokenStore::rotate`]
+#[derive(Debug)]
+pub enum Rotation {
+    /// The old token was live; it is now marked as rotated and the successor is stored
+    Rotated(TokenRecord),
+    /// The old token had already been rotated: a replay, so the family must be revoked
+    Reused(TokenRecord),
+    /// Unknown or expired token
+    Invalid,
+}
+
+/// Auxiliary record with an optional expiry, as persisted by the embedded backends
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct AuxEntry {
+    value: String,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl AuxEntry {
+    fn new(value: String, ttl_secs: Option<i64>) -> Self {
+        AuxEntry {
+            value,
+            expires_at: ttl_secs.map(|t| Utc::now() + chrono::Duration::seconds(t)),
+        }
+    }
+
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        matches!(self.expires_at, Some(e) if e <= now)
+    }
+}
+
+/// Refresh token storage. Expired records are never returned; backends drop them through key
+/// expiry (Redis) or a background sweep (see [`spawn_expiry_sweep`]).
+#[async_trait]
+pub trait TokenStore: Send + Sync + 'static {
+    async fn insert(&self, refresh_token: String, record: TokenRecord) -> Result<(), StoreError>;
+    async fn get(&self, refresh_token: &str) -> Result<Option<TokenRecord>, StoreError>;
+    async fn remove(&self, refresh_token: &str) -> Result<Option<TokenRecord>, StoreError>;
+
+    /// Atomically mark `old` as rotated and store `new` under `new.refresh_token`.
+    /// Of two concurrent rotations of the same token only one sees `Rotated`.
+    async fn rotate(&self, old: &str, new: TokenRecord) -> Result<Rotation, StoreError>;
+    /// Remove every refresh token of a family and return the removed records
+    async fn revoke_family(&self, family_id: &str) -> Result<Vec<TokenRecord>, StoreError>;
+    /// Drop expired refresh tokens and auxiliary records; returns how many were removed
+    async fn purge_expired(&self) -> Result<usize, StoreError>;
+
+    /// Auxiliary JSON records (authorization codes, pending and granted consent), kept in a
+    /// namespace separate from refresh tokens. `ttl_secs: None` keeps the record until removed.
+    async fn put_aux(
+        &self,
+        key: &str,
+        value: String,
+        ttl_secs: Option<i64>,
+    ) -> Result<(), StoreError>;
+    async fn get_aux(&self, key: &str) -> Result<Option<String>, StoreError>;
+    /// Remove and return an auxiliary record atomically, so codes can only be redeemed once
+    async fn take_aux(&self, key: &str) -> Result<Option<String>, StoreError>;
+}
+
+/// Periodically purge expired records. The task holds a weak reference and ends once the
+/// store has been dropped.
+pub fn spawn_expiry_sweep(
+    store: &Arc<dyn TokenStore>,
+    every: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let weak: Weak<dyn TokenStore> = Arc::downgrade(store);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        loop {
+            ticker.tick().await;
+            let store = match weak.upgrade() {
+                Some(s) => s,
+                None => break,
+            };
+            match store.purge_expired().await {
+                Ok(0) => {}
+                Ok(n) => tracing::debug!("token store sweep removed {} expired records", n),
+                Err(e) => tracing::warn!("token store sweep failed: {:?}", e),
+            }
+        }
+    })
+}
+
+/// Token store backend selection, injected into the router via `build_app_with_stores`
+#[derive(Clone, Debug)]
+pub enum TokenStoreConfig {
+    /// Ephemeral, per-process store; the default for tests and local development
+    Memory,
+    Sled {
+        path: String,
+        sweep_interval: Duration,
+    },
+    Redis {
+        url: String,
+        pool_size: usize,
+    },
+    /// RocksDB through `verseguy_storage`, so the API can share the node's database
+    RocksDb {
+        path: String,
+        sweep_interval: Duration,
+    },
+}
+
+impl TokenStoreConfig {
+    /// Read the backend from the environment:
+    ///
+    /// - `VERSEGUY_API_TOKEN_STORE`: `memory` (default), `sled`, `redis` or `rocksdb`
+    /// - `VERSEGUY_API_TOKEN_STORE_URL`: Redis URL, default `redis://127.0.0.1/`
+    /// - `VERSEGUY_API_TOKEN_STORE_PATH`: database path for Sled and RocksDB, default
+    ///   `data/verseguy_tokens`
+    /// - `VERSEGUY_API_TOKEN_STORE_POOL`: Redis pool size, default 4
+    pub fn from_env() -> Result<Self, StoreError> {
+        let path = env::var("VERSEGUY_API_TOKEN_STORE_PATH")
+            .unwrap_or_else(|_| "data/verseguy_tokens".into());
+        match env::var("VERSEGUY_API_TOKEN_STORE")
+            .unwrap_or_default()
+            .as_str()
+        {
+            "" | "memory" => Ok(TokenStoreConfig::Memory),
+            "sled" => Ok(TokenStoreConfig::Sled {
+                path,
+                sweep_interval: DEFAULT_SWEEP_INTERVAL,
+            }),
+            "rocksdb" => Ok(TokenStoreConfig::RocksDb {
+                path,
+                sweep_interval: DEFAULT_SWEEP_INTERVAL,
+            }),
+            "redis" => Ok(TokenStoreConfig::Redis {
+                url: env::var("VERSEGUY_API_TOKEN_STORE_URL")
+                    .unwrap_or_else(|_| "redis://127.0.0.1/".into()),
+                pool_size: env::var("VERSEGUY_API_TOKEN_STORE_POOL")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or(DEFAULT_REDIS_POOL_SIZE),
+            }),
+            other => Err(StoreError::Backend(format!(
+                "unknown token store backend: {}",
+                other
+            ))),
+        }
+    }
+
+    /// Open the configured backend. In-process backends get a background expiry sweep, so
+    /// this must run inside a Tokio runtime.
+    pub async fn open(&self) -> Result<Arc<dyn TokenStore>, StoreError> {
+        match self {
+            TokenStoreConfig::Memory => {
+                let store: Arc<dyn TokenStore> = Arc::new(InMemoryTokenStore::new());
+                spawn_expiry_sweep(&store, DEFAULT_SWEEP_INTERVAL);
+                Ok(store)
+            }
+            TokenStoreConfig::Sled {
+                path,
+                sweep_interval,
+            } => {
+                let store: Arc<dyn TokenStore> = Arc::new(SledTokenStore::new(path)?);
+                spawn_expiry_sweep(&store, *sweep_interval);
+                Ok(store)
+            }
+            TokenStoreConfig::Redis { url, pool_size } => {
+                Ok(Arc::new(RedisTokenStore::connect(url, *pool_size).await?))
+            }
+            TokenStoreConfig::RocksDb {
+                path,
+                sweep_interval,
+            } => {
+                let store: Arc<dyn TokenStore> = Arc::new(RocksTokenStore::open(path)?);
+                spawn_expiry_sweep(&store, *sweep_interval);
+                Ok(store)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(rt) => rt,
+            Err(e) => panic!("failed to build runtime: {}", e),
+        }
+    }
+
+    fn record(refresh: &str, family: &str, ttl: i64) -> TokenRecord {
+        TokenRecord {
+            access_token: format!("access-{}", refresh),
+            refresh_token: refresh.into(),
+            expires_at: Utc::now() + chrono::Duration::seconds(ttl),
+            family_id: family.into(),
+            rotated_at: None,
+        }
+    }
+
+    #[test]
+    fn in_memory_store_insert_get_remove() {
+        runtime().block_on(async {
+            let s = InMemoryTokenStore::new();
+            let rec = TokenRecord {
+                access_token: "a".into(),
+                refresh_token: "r1".into(),
+                expires_at: Utc::now() + chrono::Duration::seconds(60),
+                family_id: String::new(),
+                rotated_at: None,
+            };
+            assert!(s.insert("r1".into(), rec.clone()).await.is_ok());
+            match s.get("r1").await {
+                Ok(Some(g)) => assert_eq!(g.access_token, "a"),
+                Ok(None) => panic!("missing record"),
+                Err(_) => panic!("store error"),
+            }
+            match s.remove("r1").await {
+                Ok(Some(_)) => (),
+                Ok(None) => panic!("expected removal"),
+                Err(_) => panic!("store error"),
+            }
+            match s.get("r1").await {
+                Ok(None) => (),
+                Ok(Some(_)) => panic!("should be gone"),
+                Err(_) => panic!("store error"),
+            }
+        });
+    }
+
+    #[test]
+    fn in_memory_store_rotation_and_reuse() {
+        runtime().block_on(async {
+            let s = InMemoryTokenStore::new();
+            assert!(s.insert("r1".into(), record("r1", "f1", 60)).await.is_ok());
+            assert!(s
+                .insert("other".into(), record("other", "f2", 60))
+                .await
+                .is_ok());
+
+            match s.rotate("r1", record("r2", "f1", 60)).await {
+                Ok(Rotation::Rotated(old)) => assert!(old.rotated_at.is_some()),
+                other => panic!("expected rotation, got {:?}", other),
+            }
+            match s.get("r1").await {
+                Ok(Some(old)) => assert!(!old.is_active(Utc::now())),
+                other => panic!("rotated record should be kept, got {:?}", other),
+            }
+
+            // presenting r1 again is a replay
+            match s.rotate("r1", record("r3", "f1", 60)).await {
+                Ok(Rotation::Reused(_)) => (),
+                other => panic!("expected reuse, got {:?}", other),
+            }
+            match s.revoke_family("f1").await {
+                Ok(removed) => assert_eq!(removed.len(), 2),
+                Err(_) => panic!("store error"),
+            }
+            assert!(matches!(s.get("r2").await, Ok(None)));
+            assert!(matches!(s.get("r3").await, Ok(None)));
+            assert!(matches!(s.get("other").await, Ok(Some(_))));
+            let unknown = s.rotate("unknown", record("r4", "f1", 60)).await;
+            assert!(matches!(unknown, Ok(Rotation::Invalid)));
+        });
+    }
+
+    #[test]
+    fn in_memory_store_hides_and_purges_expired() {
+        runtime().block_on(async {
+            let s = InMemoryTokenStore::new();
+            assert!(s
+                .insert("old".into(), record("old", "f1", -1))
+                .await
+                .is_ok());
+            assert!(s
+                .insert("live".into(), record("live", "f1", 60))
+                .await
+                .is_ok());
+            assert!(matches!(s.get("old").await, Ok(None)));
+            let rotated = s.rotate("old", record("n", "f1", 60)).await;
+            assert!(matches!(rotated, Ok(Rotation::Invalid)));
+
+            assert!(s
+                .insert("old2".into(), record("old2", "f1", -1))
+                .await
+                .is_ok());
+            assert!(s.put_aux("code:x", "{}".into(), Some(-1)).await.is_ok());
+            assert!(s.put_aux("consent:u:c", "{}".into(), None).await.is_ok());
+            assert!(matches!(s.get_aux("code:x").await, Ok(None)));
+            assert!(matches!(s.purge_expired().await, Ok(2)));
+            assert!(matches!(s.get("live").await, Ok(Some(_))));
+            assert!(matches!(s.get_aux("consent:u:c").await, Ok(Some(_))));
+        });
+    }
+
+    #[test]
+    fn config_from_env_rejects_unknown_backends() {
+        std::env::set_var("VERSEGUY_API_TOKEN_STORE", "floppy");
+        assert!(TokenStoreConfig::from_env().is_err());
+        std::env::set_var("VERSEGUY_API_TOKEN_STORE", "memory");
+        assert!(matches!(
+            TokenStoreConfig::from_env(),
+            Ok(TokenStoreConfig::Memory)
+        ));
+        std::env::remove_var("VERSEGUY_API_TOKEN_STORE");
+    }
+
+    #[test]
+    fn redis_store_insert_get_remove() {
+        runtime().block_on(async {
+            // Try to connect to Redis; if not available, skip the test gracefully
+            let url = std::env::var("VERSEGUY_API_TOKEN_STORE_URL")
+                .unwrap_or_else(|_| "redis://127.0.0.1/".to_string());
+            let s = match RedisTokenStore::connect(&url, 2).await {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("Skipping redis test (connection failed): {:?}", e);
+                    return;
+                }
+            };
+
+            let rec = TokenRecord {
+                access_token: "a".into(),
+                refresh_token: "r1".into(),
+                expires_at: Utc::now() + chrono::Duration::seconds(60),
+                family_id: String::new(),
+                rotated_at: None,
+            };
+
+            match s.insert("r1".into(), rec.clone()).await {
+                Ok(_) => (),
+                Err(e) => {
+                    eprintln!("Skipping redis test (insert failed): {:?}", e);
+                    return;
+                }
+            }
+
+            match s.get("r1").await {
+                Ok(Some(g)) => assert_eq!(g.access_token, "a"),
+                Ok(None) => panic!("missing record"),
+                Err(e) => {
+                    eprintln!("Skipping redis test (get failed): {:?}", e);
+                    return;
+                }
+            }
+
+            match s.remove("r1").await {
+                Ok(Some(_)) => (),
+                Ok(None) => panic!("expected removal"),
+                Err(e) => {
+                    eprintln!("Skipping redis test (remove failed): {:?}", e);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,280 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionManager;
+use redis::Client as RedisClient;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{Rotation, StoreError, TokenRecord, TokenStore};
+
+/// Key prefix for auxiliary records in Redis
+const REDIS_AUX_PREFIX: &str = "verseguy:aux:";
+/// Key prefix for the per-family sets of refresh tokens
+const REDIS_FAMILY_PREFIX: &str = "verseguy:family:";
+/// Attempts before a rotation that keeps losing its compare-and-set gives up
+const ROTATE_ATTEMPTS: usize = 5;
+
+/// Swap the old record only if it still holds the value we read (KEYS: old, new, family;
+/// ARGV: expected old, rotated old, new record, new ttl)
+const ROTATE_SCRIPT: &str = r"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then return 0 end
+redis.call('SET', KEYS[1], ARGV[2], 'KEEPTTL')
+redis.call('SET', KEYS[2], ARGV[3], 'EX', ARGV[4])
+redis.call('SADD', KEYS[3], KEYS[2])
+redis.call('EXPIRE', KEYS[3], ARGV[4])
+return 1
+";
+
+/// Seconds until `expires_at`, at least one so Redis accepts it as an expiry
+fn ttl_secs(expires_at: DateTime<Utc>) -> i64 {
+    (expires_at - Utc::now()).num_seconds().max(1)
+}
+
+fn family_key(family_id: &str) -> String {
+    format!("{}{}", REDIS_FAMILY_PREFIX, family_id)
+}
+
+fn aux_key(key: &str) -> String {
+    format!("{}{}", REDIS_AUX_PREFIX, key)
+}
+
+/// Redis-backed store. Connections are pooled multiplexed connections that reconnect on
+/// their own; records expire through native `SET EX` TTLs.
+pub struct RedisTokenStore {
+    pool: Vec<ConnectionManager>,
+    next: AtomicUsize,
+}
+
+impl RedisTokenStore {
+    /// Open `pool_size` connections (at least one) to `url`
+    pub async fn connect(url: &str, pool_size: usize) -> Result<Self, StoreError> {
+        let client = match RedisClient::open(url) {
+            Ok(c) => c,
+            Err(e) => return Err(StoreError::Backend(format!("redis open: {}", e))),
+        };
+        let mut pool = Vec::new();
+        for _ in 0..pool_size.max(1) {
+            match ConnectionManager::new(client.clone()).await {
+                Ok(conn) => pool.push(conn),
+                Err(e) => return Err(StoreError::Backend(format!("redis conn: {}", e))),
+            }
+        }
+        Ok(RedisTokenStore {
+            pool,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Next connection, round-robin
+    fn conn(&self) -> ConnectionManager {
+        let i = self.next.fetch_add(1, Ordering::Relaxed) % self.pool.len();
+        self.pool[i].clone()
+    }
+}
+
+#[async_trait]
+impl TokenStore for RedisTokenStore {
+    async fn insert(&self, refresh_token: String, record: TokenRecord) -> Result<(), StoreError> {
+        let payload = match serde_json::to_string(&record) {
+            Ok(s) => s,
+            Err(e) => return Err(StoreError::Backend(format!("serialize: {}", e))),
+        };
+        let ttl = ttl_secs(record.expires_at);
+        let family = family_key(record.family());
+        let mut conn = self.conn();
+        match redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(&refresh_token)
+            .arg(payload)
+            .arg("EX")
+            .arg(ttl)
+            .ignore()
+            .cmd("SADD")
+            .arg(&family)
+            .arg(&refresh_token)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&family)
+            .arg(ttl)
+            .ignore()
+            .query_async::<()>(&mut conn)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(StoreError::Backend(format!("redis set: {}", e))),
+        }
+    }
+
+    async fn get(&self, refresh_token: &str) -> Result<Option<TokenRecord>, StoreError> {
+        let mut conn = self.conn();
+        match redis::cmd("GET")
+            .arg(refresh_token)
+            .query_async::<Option<String>>(&mut conn)
+            .await
+        {
+            Ok(Some(s)) => match serde_json::from_str::<TokenRecord>(&s) {
+                Ok(rec) if rec.is_expired(Utc::now()) => Ok(None),
+                Ok(rec) => Ok(Some(rec)),
+                Err(e) => Err(StoreError::Backend(format!("deserialize: {}", e))),
+            },
+            Ok(None) => Ok(None),
+            Err(e) => Err(StoreError::Backend(format!("redis get: {}", e))),
+        }
+    }
+
+    async fn remove(&self, refresh_token: &str) -> Result<Option<TokenRecord>, StoreError> {
+        let rec = match self.get(refresh_token).await? {
+            Some(rec) => rec,
+            None => return Ok(None),
+        };
+        let mut conn = self.conn();
+        match redis::pipe()
+            .cmd("DEL")
+            .arg(refresh_token)
+            .ignore()
+            .cmd("SREM")
+            .arg(family_key(rec.family()))
+            .arg(refresh_token)
+            .ignore()
+            .query_async::<()>(&mut conn)
+            .await
+        {
+            Ok(_) => Ok(Some(rec)),
+            Err(e) => Err(StoreError::Backend(format!("redis del: {}", e))),
+        }
+    }
+
+    async fn rotate(&self, old: &str, new: TokenRecord) -> Result<Rotation, StoreError> {
+        let payload = match serde_json::to_string(&new) {
+            Ok(s) => s,
+            Err(e) => return Err(StoreError::Backend(format!("serialize: {}", e))),
+        };
+        let ttl = ttl_secs(new.expires_at);
+        let family = family_key(new.family());
+        let script = redis::Script::new(ROTATE_SCRIPT);
+        let mut conn = self.conn();
+
+        for _ in 0..ROTATE_ATTEMPTS {
+            let current = match redis::cmd("GET")
+                .arg(old)
+                .query_async::<Option<String>>(&mut conn)
+                .await
+            {
+                Ok(c) => c,
+                Err(e) => return Err(StoreError::Backend(format!("redis get: {}", e))),
+            };
+            let raw = match current {
+                Some(raw) => raw,
+                None => return Ok(Rotation::Invalid),
+            };
+            let now = Utc::now();
+            let mut rec = match serde_json::from_str::<TokenRecord>(&raw) {
+                Ok(rec) if !rec.is_expired(now) => rec,
+                Ok(_) => return Ok(Rotation::Invalid),
+                Err(e) => return Err(StoreError::Backend(format!("deserialize: {}", e))),
+            };
+            if rec.rotated_at.is_some() {
+                return Ok(Rotation::Reused(rec));
+            }
+            rec.rotated_at = Some(now);
+            let updated = match serde_json::to_string(&rec) {
+                Ok(s) => s,
+                Err(e) => return Err(StoreError::Backend(format!("serialize: {}", e))),
+            };
+            let swapped = script
+                .key(old)
+                .key(&new.refresh_token)
+                .key(&family)
+                .arg(&raw)
+                .arg(&updated)
+                .arg(&payload)
+                .arg(ttl)
+                .invoke_async::<i64>(&mut conn)
+                .await;
+            match swapped {
+                Ok(1) => return Ok(Rotation::Rotated(rec)),
+                // someone else changed the record in between; re-read and decide again
+                Ok(_) => continue,
+                Err(e) => return Err(StoreError::Backend(format!("redis rotate: {}", e))),
+            }
+        }
+        Err(StoreError::Backend(
+            "redis rotate: too much contention".into(),
+        ))
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> Result<Vec<TokenRecord>, StoreError> {
+        let family = family_key(family_id);
+        let mut conn = self.conn();
+        let tokens: Vec<String> = match redis::cmd("SMEMBERS")
+            .arg(&family)
+            .query_async(&mut conn)
+            .await
+        {
+            Ok(t) => t,
+            Err(e) => return Err(StoreError::Backend(format!("redis smembers: {}", e))),
+        };
+        let mut removed = Vec::new();
+        for token in &tokens {
+            if let Some(rec) = self.remove(token).await? {
+                removed.push(rec);
+            }
+        }
+        match redis::cmd("DEL")
+            .arg(&family)
+            .query_async::<i64>(&mut conn)
+            .await
+        {
+            Ok(_) => Ok(removed),
+            Err(e) => Err(StoreError::Backend(format!("redis del: {}", e))),
+        }
+    }
+
+    async fn purge_expired(&self) -> Result<usize, StoreError> {
+        // Redis drops expired keys itself
+        Ok(0)
+    }
+
+    async fn put_aux(
+        &self,
+        key: &str,
+        value: String,
+        ttl_secs: Option<i64>,
+    ) -> Result<(), StoreError> {
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(aux_key(key)).arg(value);
+        if let Some(ttl) = ttl_secs {
+            cmd.arg("EX").arg(ttl.max(1));
+        }
+        let mut conn = self.conn();
+        match cmd.query_async::<()>(&mut conn).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(StoreError::Backend(format!("redis set: {}", e))),
+        }
+    }
+
+    async fn get_aux(&self, key: &str) -> Result<Option<String>, StoreError> {
+        let mut conn = self.conn();
+        match redis::cmd("GET")
+            .arg(aux_key(key))
+            .query_async::<Option<String>>(&mut conn)
+            .await
+        {
+            Ok(v) => Ok(v),
+            Err(e) => Err(StoreError::Backend(format!("redis get: {}", e))),
+        }
+    }
+
+    async fn take_aux(&self, key: &str) -> Result<Option<String>, StoreError> {
+        // GETDEL (Redis >= 6.2) reads and deletes in one step
+        let mut conn = self.conn();
+        match redis::cmd("GETDEL")
+            .arg(aux_key(key))
+            .query_async::<Option<String>>(&mut conn)
+            .await
+        {
+            Ok(v) => Ok(v),
+            Err(e) => Err(StoreError::Backend(format!("redis getdel: {}", e))),
+        }
+    }
+}
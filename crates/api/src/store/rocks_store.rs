@@ -0,0 +1,212 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::sync::Mutex;
+use verseguy_storage::Storage;
+
+use super::{AuxEntry, Rotation, StoreError, TokenRecord, TokenStore};
+
+const TOKEN_PREFIX: &str = "oauth:token:";
+const FAMILY_PREFIX: &str = "oauth:family:";
+const AUX_PREFIX: &str = "oauth:aux:";
+
+fn token_key(refresh_token: &str) -> String {
+    format!("{}{}", TOKEN_PREFIX, refresh_token)
+}
+
+fn family_key(family_id: &str, refresh_token: &str) -> String {
+    format!("{}{}:{}", FAMILY_PREFIX, family_id, refresh_token)
+}
+
+fn aux_key(key: &str) -> String {
+    format!("{}{}", AUX_PREFIX, key)
+}
+
+fn backend(op: &str, e: impl std::fmt::Display) -> StoreError {
+    StoreError::Backend(format!("rocksdb {}: {}", op, e))
+}
+
+/// RocksDB-backed store on top of `verseguy_storage`, so the API can keep its tokens in the
+/// same database as the rest of the node. Keys live under the `oauth:` prefix.
+pub struct RocksTokenStore {
+    storage: Storage,
+    /// `verseguy_storage` has no compare-and-swap; rotations are serialized in-process, which
+    /// is sufficient because a RocksDB directory can only be opened by one process
+    write_lock: Mutex<()>,
+}
+
+impl RocksTokenStore {
+    /// Share an already opened storage handle
+    pub fn new(storage: Storage) -> Self {
+        RocksTokenStore {
+            storage,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn open(path: &str) -> Result<Self, StoreError> {
+        match Storage::open(path) {
+            Ok(storage) => Ok(Self::new(storage)),
+            Err(e) => Err(backend("open", e)),
+        }
+    }
+
+    fn read(&self, refresh_token: &str) -> Result<Option<TokenRecord>, StoreError> {
+        match self.storage.get(token_key(refresh_token)) {
+            Ok(rec) => Ok(rec),
+            Err(e) => Err(backend("get", e)),
+        }
+    }
+
+    fn write(&self, record: &TokenRecord) -> Result<(), StoreError> {
+        if let Err(e) = self.storage.put(token_key(&record.refresh_token), record) {
+            return Err(backend("put", e));
+        }
+        let index = family_key(record.family(), &record.refresh_token);
+        match self.storage.put(index, &record.refresh_token) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(backend("put", e)),
+        }
+    }
+
+    fn delete(&self, record: &TokenRecord) -> Result<(), StoreError> {
+        if let Err(e) = self.storage.delete(token_key(&record.refresh_token)) {
+            return Err(backend("delete", e));
+        }
+        match self
+            .storage
+            .delete(family_key(record.family(), &record.refresh_token))
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(backend("delete", e)),
+        }
+    }
+
+    fn read_aux(&self, key: &str) -> Result<Option<String>, StoreError> {
+        match self.storage.get::<_, (String, AuxEntry)>(aux_key(key)) {
+            Ok(Some((_, entry))) if !entry.is_expired(Utc::now()) => Ok(Some(entry.value)),
+            Ok(_) => Ok(None),
+            Err(e) => Err(backend("get", e)),
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, ()>, StoreError> {
+        match self.write_lock.lock() {
+            Ok(g) => Ok(g),
+            Err(_) => Err(StoreError::Backend("lock_error".into())),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenStore for RocksTokenStore {
+    async fn insert(&self, refresh_token: String, record: TokenRecord) -> Result<(), StoreError> {
+        let record = TokenRecord {
+            refresh_token,
+            ..record
+        };
+        let _guard = self.lock()?;
+        self.write(&record)
+    }
+
+    async fn get(&self, refresh_token: &str) -> Result<Option<TokenRecord>, StoreError> {
+        Ok(self
+            .read(refresh_token)?
+            .filter(|r| !r.is_expired(Utc::now())))
+    }
+
+    async fn remove(&self, refresh_token: &str) -> Result<Option<TokenRecord>, StoreError> {
+        let _guard = self.lock()?;
+        match self.read(refresh_token)? {
+            Some(rec) => {
+                self.delete(&rec)?;
+                Ok(Some(rec))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn rotate(&self, old: &str, new: TokenRecord) -> Result<Rotation, StoreError> {
+        let _guard = self.lock()?;
+        let now = Utc::now();
+        let mut rec = match self.read(old)? {
+            Some(rec) if !rec.is_expired(now) => rec,
+            _ => return Ok(Rotation::Invalid),
+        };
+        if rec.rotated_at.is_some() {
+            return Ok(Rotation::Reused(rec));
+        }
+        rec.rotated_at = Some(now);
+        self.write(&rec)?;
+        self.write(&new)?;
+        Ok(Rotation::Rotated(rec))
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> Result<Vec<TokenRecord>, StoreError> {
+        let _guard = self.lock()?;
+        let tokens: Vec<String> = match self.storage.prefix_scan(family_key(family_id, "")) {
+            Ok(t) => t,
+            Err(e) => return Err(backend("scan", e)),
+        };
+        let mut removed = Vec::new();
+        for token in tokens {
+            if let Some(rec) = self.read(&token)? {
+                self.delete(&rec)?;
+                removed.push(rec);
+            }
+        }
+        Ok(removed)
+    }
+
+    async fn purge_expired(&self) -> Result<usize, StoreError> {
+        let _guard = self.lock()?;
+        let now = Utc::now();
+        let records: Vec<TokenRecord> = match self.storage.prefix_scan(TOKEN_PREFIX) {
+            Ok(r) => r,
+            Err(e) => return Err(backend("scan", e)),
+        };
+        let mut removed = 0;
+        for rec in records.iter().filter(|r| r.is_expired(now)) {
+            self.delete(rec)?;
+            removed += 1;
+        }
+
+        // aux keys are not stored in their values, so rebuild them from the scan
+        let entries: Vec<(String, AuxEntry)> = match self.storage.prefix_scan(AUX_PREFIX) {
+            Ok(e) => e,
+            Err(e) => return Err(backend("scan", e)),
+        };
+        for (key, entry) in entries.iter().filter(|(_, e)| e.is_expired(now)) {
+            if let Err(e) = self.storage.delete(aux_key(key)) {
+                return Err(backend("delete", e));
+            }
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    async fn put_aux(
+        &self,
+        key: &str,
+        value: String,
+        ttl_secs: Option<i64>,
+    ) -> Result<(), StoreError> {
+        let entry = (key.to_string(), AuxEntry::new(value, ttl_secs));
+        match self.storage.put(aux_key(key), &entry) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(backend("put", e)),
+        }
+    }
+
+    async fn get_aux(&self, key: &str) -> Result<Option<String>, StoreError> {
+        self.read_aux(key)
+    }
+
+    async fn take_aux(&self, key: &str) -> Result<Option<String>, StoreError> {
+        let _guard = self.lock()?;
+        let value = self.read_aux(key)?;
+        match self.storage.delete(aux_key(key)) {
+            Ok(_) => Ok(value),
+            Err(e) => Err(backend("delete", e)),
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sled::transaction::{ConflictableTransactionError, TransactionError};
+use sled::Transactional;
+
+use super::{AuxEntry, Rotation, StoreError, TokenRecord, TokenStore};
+
+/// Sled-backed store for persistence
+#[derive(Clone)]
+pub struct SledTokenStore {
+    db: sled::Db,
+    aux: sled::Tree,
+    /// Family index: `{family_id}:{refresh_token}` -> empty
+    families: sled::Tree,
+}
+
+fn family_key(family_id: &str, refresh_token: &str) -> String {
+    format!("{}:{}", family_id, refresh_token)
+}
+
+fn decode<T: serde::de::DeserializeOwned>(iv: &[u8]) -> Result<T, StoreError> {
+    match serde_json::from_slice(iv) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(StoreError::Backend(format!("deserialize: {}", e))),
+    }
+}
+
+fn encode<T: serde::Serialize>(value: &T) -> Result<Vec<u8>, StoreError> {
+    match serde_json::to_vec(value) {
+        Ok(b) => Ok(b),
+        Err(e) => Err(StoreError::Backend(format!("serialize: {}", e))),
+    }
+}
+
+/// Auxiliary records written before expiry support are plain strings without an envelope
+fn decode_aux(iv: &[u8]) -> AuxEntry {
+    match serde_json::from_slice(iv) {
+        Ok(entry) => entry,
+        Err(_) => AuxEntry {
+            value: String::from_utf8_lossy(iv).into_owned(),
+            expires_at: None,
+        },
+    }
+}
+
+impl SledTokenStore {
+    pub fn new(path: &str) -> Result<Self, StoreError> {
+        let db = match sled::open(path) {
+            Ok(db) => db,
+            Err(e) => return Err(StoreError::Backend(format!("sled open: {}", e))),
+        };
+        let aux = match db.open_tree("aux") {
+            Ok(t) => t,
+            Err(e) => return Err(StoreError::Backend(format!("sled open tree: {}", e))),
+        };
+        match db.open_tree("families") {
+            Ok(families) => Ok(SledTokenStore { db, aux, families }),
+            Err(e) => Err(StoreError::Backend(format!("sled open tree: {}", e))),
+        }
+    }
+
+    async fn flush(&self) {
+        let _ = self.db.flush_async().await;
+    }
+
+    /// Run `op` on the blocking thread pool: scans and transactions can block for a while
+    async fn blocking<T, F>(&self, op: F) -> Result<T, StoreError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&SledTokenStore) -> Result<T, StoreError> + Send + 'static,
+    {
+        let store = self.clone();
+        match tokio::task::spawn_blocking(move || op(&store)).await {
+            Ok(result) => result,
+            Err(e) => Err(StoreError::Backend(format!("sled task: {}", e))),
+        }
+    }
+
+    fn remove_blocking(&self, refresh_token: &str) -> Result<Option<TokenRecord>, StoreError> {
+        match self.db.remove(refresh_token.as_bytes()) {
+            Ok(Some(iv)) => {
+                let rec: TokenRecord = decode(&iv)?;
+                let key = family_key(rec.family(), refresh_token);
+                if let Err(e) = self.families.remove(key.as_bytes()) {
+                    return Err(StoreError::Backend(format!("sled remove: {}", e)));
+                }
+                Ok(Some(rec))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => Err(StoreError::Backend(format!("sled remove: {}", e))),
+        }
+    }
+
+    fn rotate_blocking(&self, old: &str, new: &TokenRecord) -> Result<Rotation, StoreError> {
+        let new_bytes = encode(new)?;
+        let new_index = family_key(new.family(), &new.refresh_token);
+        let now = Utc::now();
+        let tokens: &sled::Tree = &self.db;
+
+        let result = (tokens, &self.families).transaction(|(t, f)| {
+            let current = match t.get(old.as_bytes())? {
+                Some(iv) => iv,
+                None => return Ok(Rotation::Invalid),
+            };
+            let mut rec: TokenRecord = match serde_json::from_slice(&current) {
+                Ok(r) => r,
+                Err(e) => {
+                    return Err(ConflictableTransactionError::Abort(format!(
+                        "deserialize: {}",
+                        e
+                    )))
+                }
+            };
+            if rec.is_expired(now) {
+                return Ok(Rotation::Invalid);
+            }
+            if rec.rotated_at.is_some() {
+                return Ok(Rotation::Reused(rec));
+            }
+            rec.rotated_at = Some(now);
+            let updated = match serde_json::to_vec(&rec) {
+                Ok(b) => b,
+                Err(e) => {
+                    return Err(ConflictableTransactionError::Abort(format!(
+                        "serialize: {}",
+                        e
+                    )))
+                }
+            };
+            t.insert(old.as_bytes(), updated)?;
+            t.insert(new.refresh_token.as_bytes(), new_bytes.clone())?;
+            f.insert(new_index.as_bytes(), Vec::<u8>::new())?;
+            Ok(Rotation::Rotated(rec))
+        });
+
+        match result {
+            Ok(outcome) => Ok(outcome),
+            Err(TransactionError::Abort(e)) => Err(StoreError::Backend(e)),
+            Err(TransactionError::Storage(e)) => {
+                Err(StoreError::Backend(format!("sled transaction: {}", e)))
+            }
+        }
+    }
+
+    fn revoke_family_blocking(&self, family_id: &str) -> Result<Vec<TokenRecord>, StoreError> {
+        let prefix = family_key(family_id, "");
+        let mut tokens = Vec::new();
+        for entry in self.families.scan_prefix(prefix.as_bytes()) {
+            match entry {
+                Ok((key, _)) => {
+                    tokens.push(String::from_utf8_lossy(&key[prefix.len()..]).into_owned())
+                }
+                Err(e) => return Err(StoreError::Backend(format!("sled scan: {}", e))),
+            }
+        }
+        let mut removed = Vec::new();
+        for token in tokens {
+            if let Some(rec) = self.remove_blocking(&token)? {
+                removed.push(rec);
+            }
+        }
+        Ok(removed)
+    }
+
+    fn purge_blocking(&self) -> Result<usize, StoreError> {
+        let now = Utc::now();
+        let mut expired = Vec::new();
+        for entry in self.db.iter() {
+            match entry {
+                Ok((key, iv)) => {
+                    let rec: TokenRecord = decode(&iv)?;
+                    if rec.is_expired(now) {
+                        expired.push(String::from_utf8_lossy(&key).into_owned());
+                    }
+                }
+                Err(e) => return Err(StoreError::Backend(format!("sled scan: {}", e))),
+            }
+        }
+        for token in &expired {
+            self.remove_blocking(token)?;
+        }
+
+        let mut removed = expired.len();
+        for entry in self.aux.iter() {
+            match entry {
+                Ok((key, iv)) => {
+                    let aux = decode_aux(&iv);
+                    if aux.is_expired(now) {
+                        if let Err(e) = self.aux.remove(key) {
+                            return Err(StoreError::Backend(format!("sled remove: {}", e)));
+                        }
+                        removed += 1;
+                    }
+                }
+                Err(e) => return Err(StoreError::Backend(format!("sled scan: {}", e))),
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[async_trait]
+impl TokenStore for SledTokenStore {
+    async fn insert(&self, refresh_token: String, record: TokenRecord) -> Result<(), StoreError> {
+        let bytes = encode(&record)?;
+        if let Err(e) = self.db.insert(refresh_token.as_bytes(), bytes) {
+            return Err(StoreError::Backend(format!("sled insert: {}", e)));
+        }
+        let key = family_key(record.family(), &refresh_token);
+        if let Err(e) = self.families.insert(key.as_bytes(), Vec::<u8>::new()) {
+            return Err(StoreError::Backend(format!("sled insert: {}", e)));
+        }
+        self.flush().await;
+        Ok(())
+    }
+
+    async fn get(&self, refresh_token: &str) -> Result<Option<TokenRecord>, StoreError> {
+        match self.db.get(refresh_token.as_bytes()) {
+            Ok(Some(iv)) => {
+                let rec: TokenRecord = decode(&iv)?;
+                if rec.is_expired(Utc::now()) {
+                    return Ok(None);
+                }
+                Ok(Some(rec))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => Err(StoreError::Backend(format!("sled get: {}", e))),
+        }
+    }
+
+    async fn remove(&self, refresh_token: &str) -> Result<Option<TokenRecord>, StoreError> {
+        let refresh_token = refresh_token.to_string();
+        let removed = self
+            .blocking(move |store| store.remove_blocking(&refresh_token))
+            .await?;
+        if removed.is_some() {
+            self.flush().await;
+        }
+        Ok(removed)
+    }
+
+    async fn rotate(&self, old: &str, new: TokenRecord) -> Result<Rotation, StoreError> {
+        let old = old.to_string();
+        let outcome = self
+            .blocking(move |store| store.rotate_blocking(&old, &new))
+            .await?;
+        if matches!(outcome, Rotation::Rotated(_)) {
+            self.flush().await;
+        }
+        Ok(outcome)
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> Result<Vec<TokenRecord>, StoreError> {
+        let family_id = family_id.to_string();
+        let removed = self
+            .blocking(move |store| store.revoke_family_blocking(&family_id))
+            .await?;
+        self.flush().await;
+        Ok(removed)
+    }
+
+    async fn purge_expired(&self) -> Result<usize, StoreError> {
+        let removed = self.blocking(|store| store.purge_blocking()).await?;
+        if removed > 0 {
+            self.flush().await;
+        }
+        Ok(removed)
+    }
+
+    async fn put_aux(
+        &self,
+        key: &str,
+        value: String,
+        ttl_secs: Option<i64>,
+    ) -> Result<(), StoreError> {
+        let bytes = encode(&AuxEntry::new(value, ttl_secs))?;
+        if let Err(e) = self.aux.insert(key.as_bytes(), bytes) {
+            return Err(StoreError::Backend(format!("sled insert: {}", e)));
+        }
+        self.flush().await;
+        Ok(())
+    }
+
+    async fn get_aux(&self, key: &str) -> Result<Option<String>, StoreError> {
+        match self.aux.get(key.as_bytes()) {
+            Ok(Some(iv)) => {
+                let entry = decode_aux(&iv);
+                if entry.is_expired(Utc::now()) {
+                    return Ok(None);
+                }
+                Ok(Some(entry.value))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => Err(StoreError::Backend(format!("sled get: {}", e))),
+        }
+    }
+
+    async fn take_aux(&self, key: &str) -> Result<Option<String>, StoreError> {
+        match self.aux.remove(key.as_bytes()) {
+            Ok(Some(iv)) => {
+                self.flush().await;
+                let entry = decode_aux(&iv);
+                if entry.is_expired(Utc::now()) {
+                    return Ok(None);
+                }
+                Ok(Some(entry.value))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => Err(StoreError::Backend(format!("sled remove: {}", e))),
+        }
+    }
+}
@@ -0,0 +1,412 @@
+use axum::{
+    extract::rejection::{JsonRejection, QueryRejection},
+    extract::{Json, Path, Query},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use plugins_base_fleet::types::{Component, Insurance, Loadout, Ship, ShipStatus};
+use plugins_base_organization::types::Permission;
+use serde::Deserialize;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use verseguy_shared_error::{AppError, ProblemDetails};
+
+use super::{
+    conditional_get, contains_ci, created, json_body, query, require_match, with_etag,
+    DomainServices, Page, PageParams,
+};
+use crate::auth::{Bearer, ReadScope, WriteScope};
+
+type Domain = Extension<Arc<DomainServices>>;
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ShipFilter {
+    pub owner_id: Option<String>,
+    pub status: Option<ShipStatus>,
+    /// Substring of the manufacturer, case-insensitive
+    pub manufacturer: Option<String>,
+    /// Substring of the model, case-insensitive
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddShip {
+    pub owner_id: String,
+    pub model: String,
+    pub manufacturer: String,
+    pub name: Option<String>,
+    pub pledge_date: Option<DateTime<Utc>>,
+    pub cost: Option<f64>,
+    pub insurance: Option<Insurance>,
+    pub status: Option<ShipStatus>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateShip {
+    pub name: Option<String>,
+    pub insurance: Option<Insurance>,
+    pub status: Option<ShipStatus>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddLoadout {
+    pub name: String,
+    #[serde(default)]
+    pub components: Vec<Component>,
+}
+
+/// The fleet of an organization is the set of ships owned by its members
+fn org_ships(domain: &DomainServices, org_id: &str) -> Result<Vec<Ship>, AppError> {
+    let members = match domain.orgs.list_members(org_id) {
+        Ok(m) => m,
+        Err(e) => return Err(AppError::internal(e)),
+    };
+    let mut ships = Vec::new();
+    for member in members {
+        match domain.fleet.list_ships_for_owner(&member.user_id) {
+            Ok(owned) => ships.extend(owned),
+            Err(e) => return Err(AppError::internal(e)),
+        }
+    }
+    Ok(ships)
+}
+
+fn find_ship(domain: &DomainServices, org_id: &str, ship_id: &str) -> Result<Ship, AppError> {
+    match org_ships(domain, org_id)?
+        .into_iter()
+        .find(|s| s.id == ship_id)
+    {
+        Some(ship) => Ok(ship),
+        None => Err(AppError::NotFound("ship not found".into())),
+    }
+}
+
+/// List ships owned by the organization's members
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}/ships",
+    tag = "fleet",
+    params(("id" = String, Path, description = "Organization id"), PageParams, ShipFilter),
+    security(("oauth2" = ["read"])),
+    responses(
+        (status = 200, description = "One page of ships", body = Page<Ship>),
+        (status = 400, description = "Invalid filter or page parameters", body = ProblemDetails),
+        (status = 403, description = "Not a member of the organization", body = ProblemDetails),
+        (status = 404, description = "Organization not found", body = ProblemDetails),
+    )
+)]
+pub async fn list_ships(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    Path(id): Path<String>,
+    page: Result<Query<PageParams>, QueryRejection>,
+    filter: Result<Query<ShipFilter>, QueryRejection>,
+) -> Result<Response, AppError> {
+    let (page, filter) = (query(page)?, query(filter)?);
+    domain.member_org(&id, &auth.claims.sub)?;
+    let mut ships = org_ships(&domain, &id)?;
+    ships.retain(|s| {
+        filter.owner_id.as_deref().is_none_or(|o| s.owner_id == o)
+            && filter.status.is_none_or(|st| s.status == st)
+            && filter
+                .manufacturer
+                .as_deref()
+                .is_none_or(|m| contains_ci(&s.manufacturer, m))
+            && filter
+                .model
+                .as_deref()
+                .is_none_or(|m| contains_ci(&s.model, m))
+    });
+    ships.sort_by(|a, b| {
+        a.created_at
+            .cmp(&b.created_at)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    Ok(Json(page.apply(ships)?).into_response())
+}
+
+/// Register a ship for one of the organization's members
+#[utoipa::path(
+    post,
+    path = "/v1/orgs/{id}/ships",
+    tag = "fleet",
+    params(("id" = String, Path, description = "Organization id")),
+    request_body = AddShip,
+    security(("oauth2" = ["write"])),
+    responses(
+        (status = 201, description = "Ship added", body = Ship, headers(("ETag" = String))),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing the `ManageFleet` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization not found", body = ProblemDetails),
+        (status = 422, description = "The owner is not a member", body = ProblemDetails),
+    )
+)]
+pub async fn add_ship(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path(id): Path<String>,
+    body: Result<Json<AddShip>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageFleet)?;
+    match domain.orgs.get_member(&id, &req.owner_id) {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(AppError::Validation("owner is not a member".into())),
+        Err(e) => return Err(AppError::internal(e)),
+    }
+    let now = Utc::now();
+    let ship = Ship {
+        id: Uuid::new_v4().to_string(),
+        owner_id: req.owner_id,
+        model: req.model,
+        manufacturer: req.manufacturer,
+        name: req.name,
+        pledge_date: req.pledge_date,
+        cost: req.cost,
+        insurance: req.insurance.unwrap_or(Insurance::Standard),
+        status: req.status.unwrap_or(ShipStatus::Available),
+        location: req.location,
+        created_at: now,
+        updated_at: now,
+    };
+    if let Err(e) = domain.fleet.add_ship(ship.clone()) {
+        return Err(AppError::internal(e));
+    }
+    // the service stamps its own timestamps, so answer with what was stored
+    let ship = match domain.fleet.get_ship(&ship.owner_id, &ship.id) {
+        Ok(Some(s)) => s,
+        Ok(None) => return Err(AppError::NotFound("ship not found".into())),
+        Err(e) => return Err(AppError::internal(e)),
+    };
+    Ok(created(format!("/v1/orgs/{}/ships/{}", id, ship.id), &ship))
+}
+
+/// Get a ship
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}/ships/{ship_id}",
+    tag = "fleet",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("ship_id" = String, Path, description = "Ship id"),
+        ("If-None-Match" = Option<String>, Header),
+    ),
+    security(("oauth2" = ["read"])),
+    responses(
+        (status = 200, description = "The ship", body = Ship, headers(("ETag" = String))),
+        (status = 304, description = "Not modified"),
+        (status = 403, description = "Not a member of the organization", body = ProblemDetails),
+        (status = 404, description = "Organization or ship not found", body = ProblemDetails),
+    )
+)]
+pub async fn get_ship(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    Path((id, ship_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    domain.member_org(&id, &auth.claims.sub)?;
+    let ship = find_ship(&domain, &id, &ship_id)?;
+    Ok(conditional_get(&headers, &ship))
+}
+
+/// Update a ship
+#[utoipa::path(
+    patch,
+    path = "/v1/orgs/{id}/ships/{ship_id}",
+    tag = "fleet",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("ship_id" = String, Path, description = "Ship id"),
+        ("If-Match" = String, Header, description = "ETag last read, or `*`"),
+    ),
+    request_body = UpdateShip,
+    security(("oauth2" = ["write"])),
+    responses(
+        (status = 200, description = "Updated ship", body = Ship, headers(("ETag" = String))),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing the `ManageFleet` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization or ship not found", body = ProblemDetails),
+        (status = 412, description = "The ship changed since it was read", body = ProblemDetails),
+        (status = 428, description = "If-Match is missing", body = ProblemDetails),
+    )
+)]
+pub async fn update_ship(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path((id, ship_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Result<Json<UpdateShip>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageFleet)?;
+    let mut ship = find_ship(&domain, &id, &ship_id)?;
+    require_match(&headers, &ship)?;
+    if req.name.is_some() {
+        ship.name = req.name;
+    }
+    if let Some(insurance) = req.insurance {
+        ship.insurance = insurance;
+    }
+    if let Some(status) = req.status {
+        ship.status = status;
+    }
+    if req.location.is_some() {
+        ship.location = req.location;
+    }
+    match domain.fleet.update_ship(ship) {
+        Ok(ship) => Ok(with_etag(StatusCode::OK, &ship)),
+        Err(e) => Err(AppError::internal(e)),
+    }
+}
+
+/// Delete a ship
+#[utoipa::path(
+    delete,
+    path = "/v1/orgs/{id}/ships/{ship_id}",
+    tag = "fleet",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("ship_id" = String, Path, description = "Ship id"),
+        ("If-Match" = String, Header, description = "ETag last read, or `*`"),
+    ),
+    security(("oauth2" = ["write"])),
+    responses(
+        (status = 204, description = "Deleted"),
+        (
+            status = 403,
+            description = "Missing the `ManageFleet` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization or ship not found", body = ProblemDetails),
+        (status = 412, description = "The ship changed since it was read", body = ProblemDetails),
+        (status = 428, description = "If-Match is missing", body = ProblemDetails),
+    )
+)]
+pub async fn delete_ship(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path((id, ship_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageFleet)?;
+    let ship = find_ship(&domain, &id, &ship_id)?;
+    require_match(&headers, &ship)?;
+    match domain.fleet.delete_ship(&ship.owner_id, &ship.id) {
+        Ok(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(e) => Err(AppError::internal(e)),
+    }
+}
+
+/// List loadouts of a ship, oldest first
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}/ships/{ship_id}/loadouts",
+    tag = "fleet",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("ship_id" = String, Path, description = "Ship id"),
+        PageParams,
+    ),
+    security(("oauth2" = ["read"])),
+    responses(
+        (status = 200, description = "One page of loadouts", body = Page<Loadout>),
+        (status = 400, description = "Invalid page parameters", body = ProblemDetails),
+        (status = 403, description = "Not a member of the organization", body = ProblemDetails),
+        (status = 404, description = "Organization or ship not found", body = ProblemDetails),
+    )
+)]
+pub async fn list_loadouts(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    Path((id, ship_id)): Path<(String, String)>,
+    page: Result<Query<PageParams>, QueryRejection>,
+) -> Result<Response, AppError> {
+    let page = query(page)?;
+    domain.member_org(&id, &auth.claims.sub)?;
+    let ship = find_ship(&domain, &id, &ship_id)?;
+    let mut loadouts = match domain.fleet.get_loadouts_for_ship(&ship.id) {
+        Ok(l) => l,
+        Err(e) => return Err(AppError::internal(e)),
+    };
+    loadouts.sort_by(|a, b| {
+        a.created_at
+            .cmp(&b.created_at)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    Ok(Json(page.apply(loadouts)?).into_response())
+}
+
+/// Add a loadout to a ship
+#[utoipa::path(
+    post,
+    path = "/v1/orgs/{id}/ships/{ship_id}/loadouts",
+    tag = "fleet",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("ship_id" = String, Path, description = "Ship id"),
+    ),
+    request_body = AddLoadout,
+    security(("oauth2" = ["write"])),
+    responses(
+        (status = 201, description = "Loadout added", body = Loadout),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing the `ManageFleet` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization or ship not found", body = ProblemDetails),
+        (status = 422, description = "Empty loadout name", body = ProblemDetails),
+    )
+)]
+pub async fn add_loadout(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path((id, ship_id)): Path<(String, String)>,
+    body: Result<Json<AddLoadout>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    if req.name.is_empty() {
+        return Err(AppError::Validation(
+            "loadout name must not be empty".into(),
+        ));
+    }
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageFleet)?;
+    let ship = find_ship(&domain, &id, &ship_id)?;
+    let now = Utc::now();
+    let loadout = Loadout {
+        id: Uuid::new_v4().to_string(),
+        ship_id: ship.id,
+        name: req.name,
+        components: req.components,
+        created_at: now,
+        updated_at: now,
+    };
+    if let Err(e) = domain.fleet.add_loadout(loadout.clone()) {
+        return Err(AppError::internal(e));
+    }
+    let stored = match domain.fleet.get_loadouts_for_ship(&loadout.ship_id) {
+        Ok(l) => l.into_iter().find(|l| l.id == loadout.id),
+        Err(e) => return Err(AppError::internal(e)),
+    };
+    Ok((StatusCode::CREATED, Json(stored.unwrap_or(loadout))).into_response())
+}
@@ -0,0 +1,282 @@
+//! Versioned domain API (`/v1/orgs/...`) on top of the organization, fleet and operations
+//! services.
+//!
+//! Conventions shared by every route in this module:
+//! - reads need the `read` scope, writes the `write` scope
+//! - the token's subject has to own or be a member of the organization; writes additionally
+//!   need the matching [`Permission`] of their rank. Other tenants get `403`
+//! - collections are paginated with `limit`/`offset` and return a [`Page`]
+//! - single resources carry an `ETag`; `PATCH` and `DELETE` require a matching `If-Match`
+//! - errors are [`AppError`]s, rendered as `application/problem+json`
+
+use axum::{
+    extract::rejection::{JsonRejection, QueryRejection},
+    extract::{Json, Query},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use plugins_base_fleet::FleetService;
+use plugins_base_operations::OperationsService;
+use plugins_base_organization::types::{Organization, Permission};
+use plugins_base_organization::OrganizationService;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::{Mutex, MutexGuard};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
+use verseguy_shared_error::AppError;
+use verseguy_storage::Storage;
+
+pub mod fleet;
+pub mod operations;
+pub mod orgs;
+
+/// Page size used when the request does not send `limit`
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+/// Largest `limit` a client may ask for
+pub const MAX_PAGE_SIZE: usize = 200;
+
+/// Services backing the `/v1` routes, all sharing one storage handle
+pub struct DomainServices {
+    pub orgs: OrganizationService,
+    pub fleet: FleetService,
+    pub operations: OperationsService,
+    /// The services read, modify and write whole records; holding this across the `If-Match`
+    /// check and the write keeps concurrent requests in this process from losing updates
+    write_lock: Mutex<()>,
+}
+
+impl DomainServices {
+    pub fn new(storage: Storage) -> Self {
+        DomainServices {
+            orgs: OrganizationService::new(storage.clone()),
+            fleet: FleetService::new(storage.clone()),
+            operations: OperationsService::new(storage),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, ()>, AppError> {
+        match self.write_lock.lock() {
+            Ok(g) => Ok(g),
+            Err(_) => Err(AppError::internal("domain write lock poisoned")),
+        }
+    }
+
+    /// Load an organization or fail with 404; every nested route starts here
+    fn org(&self, id: &str) -> Result<Organization, AppError> {
+        match self.orgs.get_organization(id) {
+            Ok(Some(org)) => Ok(org),
+            Ok(None) => Err(AppError::NotFound("organization not found".into())),
+            Err(e) => Err(AppError::internal(e)),
+        }
+    }
+
+    /// Load an organization `user_id` owns or is a member of; reads of nested resources
+    /// start here
+    fn member_org(&self, id: &str, user_id: &str) -> Result<Organization, AppError> {
+        let org = self.org(id)?;
+        match self.orgs.is_member(id, user_id) {
+            Ok(true) => Ok(org),
+            Ok(false) => Err(AppError::Forbidden(
+                "not a member of this organization".into(),
+            )),
+            Err(e) => Err(AppError::internal(e)),
+        }
+    }
+
+    /// Load an organization in which `user_id` holds `perm`; writes start here
+    fn permitted_org(
+        &self,
+        id: &str,
+        user_id: &str,
+        perm: Permission,
+    ) -> Result<Organization, AppError> {
+        let org = self.org(id)?;
+        match self.orgs.has_org_permission(id, user_id, perm) {
+            Ok(true) => Ok(org),
+            Ok(false) => Err(AppError::Forbidden(format!(
+                "missing organization permission: {}",
+                perm.as_str()
+            ))),
+            Err(e) => Err(AppError::internal(e)),
+        }
+    }
+}
+
+/// Routes of the domain API with their OpenAPI operations; `build_app_with_domain` merges
+/// them into the main router and layers the [`DomainServices`] on top
+pub fn routes() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(orgs::list_orgs, orgs::create_org))
+        .routes(routes!(orgs::get_org, orgs::update_org))
+        .routes(routes!(orgs::list_members, orgs::add_member))
+        .routes(routes!(
+            orgs::get_member,
+            orgs::update_member,
+            orgs::remove_member
+        ))
+        .routes(routes!(orgs::list_ranks, orgs::create_rank))
+        .routes(routes!(
+            orgs::get_rank,
+            orgs::update_rank,
+            orgs::delete_rank
+        ))
+        .routes(routes!(fleet::list_ships, fleet::add_ship))
+        .routes(routes!(
+            fleet::get_ship,
+            fleet::update_ship,
+            fleet::delete_ship
+        ))
+        .routes(routes!(fleet::list_loadouts, fleet::add_loadout))
+        .routes(routes!(
+            operations::list_operations,
+            operations::create_operation
+        ))
+        .routes(routes!(
+            operations::get_operation,
+            operations::update_operation,
+            operations::delete_operation
+        ))
+        .routes(routes!(
+            operations::list_participants,
+            operations::add_participant
+        ))
+        .routes(routes!(
+            operations::get_participant,
+            operations::update_participant,
+            operations::remove_participant
+        ))
+}
+
+/// Unwrap a JSON body, turning axum's plain-text rejection into an [`AppError`]
+pub fn json_body<T>(body: Result<Json<T>, JsonRejection>) -> Result<T, AppError> {
+    match body {
+        Ok(Json(v)) => Ok(v),
+        Err(e) => Err(AppError::BadRequest(e.body_text())),
+    }
+}
+
+/// Unwrap query parameters, turning axum's plain-text rejection into an [`AppError`]
+pub fn query<T>(q: Result<Query<T>, QueryRejection>) -> Result<T, AppError> {
+    match q {
+        Ok(Query(v)) => Ok(v),
+        Err(e) => Err(AppError::BadRequest(e.body_text())),
+    }
+}
+
+/// `limit`/`offset` query parameters shared by all collections
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageParams {
+    /// Page size, 1 to 200; defaults to 50
+    #[param(minimum = 1, maximum = 200)]
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// One page of a collection
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Number of items matching the filters, across all pages
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
+    /// Offset of the next page, absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+}
+
+impl PageParams {
+    /// Slice an already filtered and ordered collection
+    pub fn apply<T>(&self, items: Vec<T>) -> Result<Page<T>, AppError> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if limit == 0 || limit > MAX_PAGE_SIZE {
+            return Err(AppError::BadRequest(format!(
+                "limit must be between 1 and {}",
+                MAX_PAGE_SIZE
+            )));
+        }
+        let offset = self.offset.unwrap_or(0);
+        let total = items.len();
+        let items: Vec<T> = items.into_iter().skip(offset).take(limit).collect();
+        let next_offset = if offset + items.len() < total {
+            Some(offset + items.len())
+        } else {
+            None
+        };
+        Ok(Page {
+            items,
+            total,
+            limit,
+            offset,
+            next_offset,
+        })
+    }
+}
+
+/// Strong entity tag over the JSON representation of a resource
+pub fn etag<T: Serialize>(value: &T) -> String {
+    let bytes = serde_json::to_vec(value).unwrap_or_default();
+    format!("\"{:x}\"", Sha256::digest(&bytes))
+}
+
+fn etag_listed(header_value: &HeaderValue, tag: &str) -> bool {
+    match header_value.to_str() {
+        Ok(v) => v.split(',').map(|t| t.trim()).any(|t| t == "*" || t == tag),
+        Err(_) => false,
+    }
+}
+
+/// JSON response carrying the resource's `ETag`
+pub fn with_etag<T: Serialize>(status: StatusCode, value: &T) -> Response {
+    let tag = etag(value);
+    let mut resp = (status, Json(value)).into_response();
+    if let Ok(v) = HeaderValue::from_str(&tag) {
+        resp.headers_mut().insert(header::ETAG, v);
+    }
+    resp
+}
+
+/// `201 Created` with `Location` and `ETag`
+pub fn created<T: Serialize>(location: String, value: &T) -> Response {
+    let mut resp = with_etag(StatusCode::CREATED, value);
+    if let Ok(v) = HeaderValue::from_str(&location) {
+        resp.headers_mut().insert(header::LOCATION, v);
+    }
+    resp
+}
+
+/// Answer a GET, or `304 Not Modified` when `If-None-Match` lists the current tag
+pub fn conditional_get<T: Serialize>(headers: &HeaderMap, value: &T) -> Response {
+    let tag = etag(value);
+    match headers.get(header::IF_NONE_MATCH) {
+        Some(h) if etag_listed(h, &tag) => {
+            let mut resp = StatusCode::NOT_MODIFIED.into_response();
+            if let Ok(v) = HeaderValue::from_str(&tag) {
+                resp.headers_mut().insert(header::ETAG, v);
+            }
+            resp
+        }
+        _ => with_etag(StatusCode::OK, value),
+    }
+}
+
+/// Optimistic concurrency: writes must send the `ETag` they last saw in `If-Match`
+pub fn require_match<T: Serialize>(headers: &HeaderMap, current: &T) -> Result<(), AppError> {
+    match headers.get(header::IF_MATCH) {
+        Some(h) if etag_listed(h, &etag(current)) => Ok(()),
+        Some(_) => Err(AppError::PreconditionFailed(
+            "the resource was modified since it was read".into(),
+        )),
+        None => Err(AppError::PreconditionRequired(
+            "send the resource's ETag in If-Match".into(),
+        )),
+    }
+}
+
+/// Case-insensitive substring match used by the text filters
+fn contains_ci(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
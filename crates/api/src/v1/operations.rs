@@ -0,0 +1,615 @@
+use axum::{
+    extract::rejection::{JsonRejection, QueryRejection},
+    extract::{Json, Path, Query},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use plugins_base_operations::{Operation, OperationStatus, OperationType, Participant};
+use plugins_base_organization::types::Permission;
+use serde::Deserialize;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use verseguy_shared_error::{AppError, ProblemDetails};
+
+use super::{
+    conditional_get, contains_ci, created, json_body, query, require_match, with_etag,
+    DomainServices, Page, PageParams,
+};
+use crate::auth::{Bearer, ReadScope, WriteScope};
+
+type Domain = Extension<Arc<DomainServices>>;
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OperationFilter {
+    pub status: Option<OperationStatus>,
+    #[serde(rename = "type")]
+    pub operation_type: Option<OperationType>,
+    pub leader_id: Option<String>,
+    /// Only operations scheduled at or after this instant (RFC 3339)
+    pub from: Option<DateTime<Utc>>,
+    /// Only operations scheduled before this instant (RFC 3339)
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateOperation {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub operation_type: OperationType,
+    pub scheduled_at: DateTime<Utc>,
+    pub duration_minutes: i32,
+    /// Defaults to the token's subject
+    pub leader_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateOperation {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub operation_type: Option<OperationType>,
+    pub scheduled_at: Option<DateTime<Utc>>,
+    pub duration_minutes: Option<i32>,
+    pub leader_id: Option<String>,
+    pub status: Option<OperationStatus>,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ParticipantFilter {
+    pub confirmed: Option<bool>,
+    /// Substring of the role, case-insensitive
+    pub role: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddParticipant {
+    pub user_id: String,
+    pub role: String,
+    pub ship_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateParticipant {
+    pub role: Option<String>,
+    pub ship_id: Option<String>,
+    pub confirmed: Option<bool>,
+}
+
+fn load_operation(
+    domain: &DomainServices,
+    org_id: &str,
+    op_id: &str,
+) -> Result<Operation, AppError> {
+    match domain.operations.get_operation(org_id, op_id) {
+        Ok(Some(op)) => Ok(op),
+        Ok(None) => Err(AppError::NotFound("operation not found".into())),
+        Err(e) => Err(AppError::internal(e)),
+    }
+}
+
+/// Write through the service and answer with the stored copy, which carries the new
+/// `updated_at` and therefore the new ETag
+fn store_operation(domain: &DomainServices, op: &Operation) -> Result<Operation, AppError> {
+    if let Err(e) = domain.operations.update_operation(op) {
+        return Err(AppError::internal(e));
+    }
+    load_operation(domain, &op.org_id, &op.id)
+}
+
+fn find_participant(op: &Operation, user_id: &str) -> Result<Participant, AppError> {
+    match op.participants.iter().find(|p| p.user_id == user_id) {
+        Some(p) => Ok(p.clone()),
+        None => Err(AppError::NotFound("participant not found".into())),
+    }
+}
+
+fn validate_schedule(duration_minutes: i32) -> Result<(), AppError> {
+    if duration_minutes <= 0 {
+        return Err(AppError::Validation(
+            "duration_minutes must be positive".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// List operations, soonest first
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}/operations",
+    tag = "operations",
+    params(("id" = String, Path, description = "Organization id"), PageParams, OperationFilter),
+    security(("oauth2" = ["read"])),
+    responses(
+        (status = 200, description = "One page of operations", body = Page<Operation>),
+        (status = 400, description = "Invalid filter or page parameters", body = ProblemDetails),
+        (status = 403, description = "Not a member of the organization", body = ProblemDetails),
+        (status = 404, description = "Organization not found", body = ProblemDetails),
+    )
+)]
+pub async fn list_operations(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    Path(id): Path<String>,
+    page: Result<Query<PageParams>, QueryRejection>,
+    filter: Result<Query<OperationFilter>, QueryRejection>,
+) -> Result<Response, AppError> {
+    let (page, filter) = (query(page)?, query(filter)?);
+    domain.member_org(&id, &auth.claims.sub)?;
+    // already ordered by scheduled time
+    let mut ops = match domain.operations.list_operations(&id) {
+        Ok(o) => o,
+        Err(e) => return Err(AppError::internal(e)),
+    };
+    ops.retain(|o| {
+        filter.status.is_none_or(|s| o.status == s)
+            && filter.operation_type.is_none_or(|t| o.operation_type == t)
+            && filter.leader_id.as_deref().is_none_or(|l| o.leader_id == l)
+            && filter.from.is_none_or(|f| o.scheduled_at >= f)
+            && filter.to.is_none_or(|t| o.scheduled_at < t)
+    });
+    Ok(Json(page.apply(ops)?).into_response())
+}
+
+/// Schedule an operation
+#[utoipa::path(
+    post,
+    path = "/v1/orgs/{id}/operations",
+    tag = "operations",
+    params(("id" = String, Path, description = "Organization id")),
+    request_body = CreateOperation,
+    security(("oauth2" = ["write"])),
+    responses(
+        (
+            status = 201,
+            description = "Operation scheduled",
+            body = Operation,
+            headers(("ETag" = String))
+        ),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing the `CreateOperations` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization not found", body = ProblemDetails),
+        (status = 422, description = "Empty title or non-positive duration", body = ProblemDetails),
+    )
+)]
+pub async fn create_operation(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path(id): Path<String>,
+    body: Result<Json<CreateOperation>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    if req.title.is_empty() {
+        return Err(AppError::Validation("title must not be empty".into()));
+    }
+    validate_schedule(req.duration_minutes)?;
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::CreateOperations)?;
+    let op = match domain.operations.create_operation(
+        id.clone(),
+        req.title,
+        req.description,
+        req.operation_type,
+        req.scheduled_at,
+        req.duration_minutes,
+        req.leader_id.unwrap_or(auth.claims.sub),
+    ) {
+        Ok(op) => op,
+        Err(e) => return Err(AppError::internal(e)),
+    };
+    Ok(created(
+        format!("/v1/orgs/{}/operations/{}", id, op.id),
+        &op,
+    ))
+}
+
+/// Get an operation
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}/operations/{op_id}",
+    tag = "operations",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("op_id" = String, Path, description = "Operation id"),
+        ("If-None-Match" = Option<String>, Header),
+    ),
+    security(("oauth2" = ["read"])),
+    responses(
+        (status = 200, description = "The operation", body = Operation, headers(("ETag" = String))),
+        (status = 304, description = "Not modified"),
+        (status = 403, description = "Not a member of the organization", body = ProblemDetails),
+        (status = 404, description = "Organization or operation not found", body = ProblemDetails),
+    )
+)]
+pub async fn get_operation(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    Path((id, op_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    domain.member_org(&id, &auth.claims.sub)?;
+    let op = load_operation(&domain, &id, &op_id)?;
+    Ok(conditional_get(&headers, &op))
+}
+
+/// Update an operation
+#[utoipa::path(
+    patch,
+    path = "/v1/orgs/{id}/operations/{op_id}",
+    tag = "operations",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("op_id" = String, Path, description = "Operation id"),
+        ("If-Match" = String, Header, description = "ETag last read, or `*`"),
+    ),
+    request_body = UpdateOperation,
+    security(("oauth2" = ["write"])),
+    responses(
+        (
+            status = 200,
+            description = "Updated operation",
+            body = Operation,
+            headers(("ETag" = String))
+        ),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing the `ManageOperations` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization or operation not found", body = ProblemDetails),
+        (
+            status = 412,
+            description = "The operation changed since it was read",
+            body = ProblemDetails
+        ),
+        (status = 422, description = "Non-positive duration", body = ProblemDetails),
+        (status = 428, description = "If-Match is missing", body = ProblemDetails),
+    )
+)]
+pub async fn update_operation(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path((id, op_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Result<Json<UpdateOperation>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageOperations)?;
+    let mut op = load_operation(&domain, &id, &op_id)?;
+    require_match(&headers, &op)?;
+    if let Some(title) = req.title {
+        op.title = title;
+    }
+    if let Some(description) = req.description {
+        op.description = description;
+    }
+    if let Some(operation_type) = req.operation_type {
+        op.operation_type = operation_type;
+    }
+    if let Some(scheduled_at) = req.scheduled_at {
+        op.scheduled_at = scheduled_at;
+    }
+    if let Some(duration) = req.duration_minutes {
+        validate_schedule(duration)?;
+        op.duration_minutes = duration;
+    }
+    if let Some(leader_id) = req.leader_id {
+        op.leader_id = leader_id;
+    }
+    if let Some(status) = req.status {
+        op.status = status;
+    }
+    let op = store_operation(&domain, &op)?;
+    Ok(with_etag(StatusCode::OK, &op))
+}
+
+/// Delete an operation
+#[utoipa::path(
+    delete,
+    path = "/v1/orgs/{id}/operations/{op_id}",
+    tag = "operations",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("op_id" = String, Path, description = "Operation id"),
+        ("If-Match" = String, Header, description = "ETag last read, or `*`"),
+    ),
+    security(("oauth2" = ["write"])),
+    responses(
+        (status = 204, description = "Deleted"),
+        (
+            status = 403,
+            description = "Missing the `ManageOperations` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization or operation not found", body = ProblemDetails),
+        (
+            status = 412,
+            description = "The operation changed since it was read",
+            body = ProblemDetails
+        ),
+        (status = 428, description = "If-Match is missing", body = ProblemDetails),
+    )
+)]
+pub async fn delete_operation(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path((id, op_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageOperations)?;
+    let op = load_operation(&domain, &id, &op_id)?;
+    require_match(&headers, &op)?;
+    match domain.operations.delete_operation(&id, &op_id) {
+        Ok(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(e) => Err(AppError::internal(e)),
+    }
+}
+
+/// List participants of an operation
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}/operations/{op_id}/participants",
+    tag = "operations",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("op_id" = String, Path, description = "Operation id"),
+        PageParams,
+        ParticipantFilter,
+    ),
+    security(("oauth2" = ["read"])),
+    responses(
+        (status = 200, description = "One page of participants", body = Page<Participant>),
+        (status = 400, description = "Invalid filter or page parameters", body = ProblemDetails),
+        (status = 403, description = "Not a member of the organization", body = ProblemDetails),
+        (status = 404, description = "Organization or operation not found", body = ProblemDetails),
+    )
+)]
+pub async fn list_participants(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    Path((id, op_id)): Path<(String, String)>,
+    page: Result<Query<PageParams>, QueryRejection>,
+    filter: Result<Query<ParticipantFilter>, QueryRejection>,
+) -> Result<Response, AppError> {
+    let (page, filter) = (query(page)?, query(filter)?);
+    domain.member_org(&id, &auth.claims.sub)?;
+    let mut participants = load_operation(&domain, &id, &op_id)?.participants;
+    participants.retain(|p| {
+        filter.confirmed.is_none_or(|c| p.confirmed == c)
+            && filter
+                .role
+                .as_deref()
+                .is_none_or(|r| contains_ci(&p.role, r))
+    });
+    Ok(Json(page.apply(participants)?).into_response())
+}
+
+/// Sign a member of the organization up for an operation
+#[utoipa::path(
+    post,
+    path = "/v1/orgs/{id}/operations/{op_id}/participants",
+    tag = "operations",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("op_id" = String, Path, description = "Operation id"),
+    ),
+    request_body = AddParticipant,
+    security(("oauth2" = ["write"])),
+    responses(
+        (
+            status = 201,
+            description = "Participant added",
+            body = Participant,
+            headers(("ETag" = String))
+        ),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing the `ManageOperations` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization or operation not found", body = ProblemDetails),
+        (status = 409, description = "The user is already participating", body = ProblemDetails),
+        (status = 422, description = "The user is not a member", body = ProblemDetails),
+    )
+)]
+pub async fn add_participant(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path((id, op_id)): Path<(String, String)>,
+    body: Result<Json<AddParticipant>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageOperations)?;
+    let op = load_operation(&domain, &id, &op_id)?;
+    if op.participants.iter().any(|p| p.user_id == req.user_id) {
+        return Err(AppError::Conflict("user is already participating".into()));
+    }
+    match domain.orgs.get_member(&id, &req.user_id) {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(AppError::Validation("user is not a member".into())),
+        Err(e) => return Err(AppError::internal(e)),
+    }
+    if let Err(e) =
+        domain
+            .operations
+            .add_participant(&id, &op_id, req.user_id.clone(), req.role, req.ship_id)
+    {
+        return Err(AppError::internal(e));
+    }
+    let op = load_operation(&domain, &id, &op_id)?;
+    let participant = find_participant(&op, &req.user_id)?;
+    let location = format!(
+        "/v1/orgs/{}/operations/{}/participants/{}",
+        id, op_id, participant.user_id
+    );
+    Ok(created(location, &participant))
+}
+
+/// Get a participant
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}/operations/{op_id}/participants/{user_id}",
+    tag = "operations",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("op_id" = String, Path, description = "Operation id"),
+        ("user_id" = String, Path, description = "Member user id"),
+        ("If-None-Match" = Option<String>, Header),
+    ),
+    security(("oauth2" = ["read"])),
+    responses(
+        (
+            status = 200,
+            description = "The participant",
+            body = Participant,
+            headers(("ETag" = String))
+        ),
+        (status = 304, description = "Not modified"),
+        (status = 403, description = "Not a member of the organization", body = ProblemDetails),
+        (
+            status = 404,
+            description = "Organization, operation or participant not found",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn get_participant(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    Path((id, op_id, user_id)): Path<(String, String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    domain.member_org(&id, &auth.claims.sub)?;
+    let op = load_operation(&domain, &id, &op_id)?;
+    let participant = find_participant(&op, &user_id)?;
+    Ok(conditional_get(&headers, &participant))
+}
+
+/// Update or confirm a participant
+#[utoipa::path(
+    patch,
+    path = "/v1/orgs/{id}/operations/{op_id}/participants/{user_id}",
+    tag = "operations",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("op_id" = String, Path, description = "Operation id"),
+        ("user_id" = String, Path, description = "Member user id"),
+        ("If-Match" = String, Header, description = "ETag last read, or `*`"),
+    ),
+    request_body = UpdateParticipant,
+    security(("oauth2" = ["write"])),
+    responses(
+        (
+            status = 200,
+            description = "Updated participant",
+            body = Participant,
+            headers(("ETag" = String))
+        ),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing the `ManageOperations` organization permission",
+            body = ProblemDetails
+        ),
+        (
+            status = 404,
+            description = "Organization, operation or participant not found",
+            body = ProblemDetails
+        ),
+        (
+            status = 412,
+            description = "The participant changed since it was read",
+            body = ProblemDetails
+        ),
+        (status = 428, description = "If-Match is missing", body = ProblemDetails),
+    )
+)]
+pub async fn update_participant(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path((id, op_id, user_id)): Path<(String, String, String)>,
+    headers: HeaderMap,
+    body: Result<Json<UpdateParticipant>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageOperations)?;
+    let mut op = load_operation(&domain, &id, &op_id)?;
+    require_match(&headers, &find_participant(&op, &user_id)?)?;
+    let participant = match op.participants.iter_mut().find(|p| p.user_id == user_id) {
+        Some(p) => p,
+        None => return Err(AppError::NotFound("participant not found".into())),
+    };
+    if let Some(role) = req.role {
+        participant.role = role;
+    }
+    if req.ship_id.is_some() {
+        participant.ship_id = req.ship_id;
+    }
+    if let Some(confirmed) = req.confirmed {
+        participant.confirmed = confirmed;
+    }
+    let op = store_operation(&domain, &op)?;
+    let participant = find_participant(&op, &user_id)?;
+    Ok(with_etag(StatusCode::OK, &participant))
+}
+
+/// Remove a participant
+#[utoipa::path(
+    delete,
+    path = "/v1/orgs/{id}/operations/{op_id}/participants/{user_id}",
+    tag = "operations",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("op_id" = String, Path, description = "Operation id"),
+        ("user_id" = String, Path, description = "Member user id"),
+        ("If-Match" = String, Header, description = "ETag last read, or `*`"),
+    ),
+    security(("oauth2" = ["write"])),
+    responses(
+        (status = 204, description = "Removed"),
+        (
+            status = 403,
+            description = "Missing the `ManageOperations` organization permission",
+            body = ProblemDetails
+        ),
+        (
+            status = 404,
+            description = "Organization, operation or participant not found",
+            body = ProblemDetails
+        ),
+        (
+            status = 412,
+            description = "The participant changed since it was read",
+            body = ProblemDetails
+        ),
+        (status = 428, description = "If-Match is missing", body = ProblemDetails),
+    )
+)]
+pub async fn remove_participant(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path((id, op_id, user_id)): Path<(String, String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageOperations)?;
+    let op = load_operation(&domain, &id, &op_id)?;
+    require_match(&headers, &find_participant(&op, &user_id)?)?;
+    match domain.operations.remove_participant(&id, &op_id, &user_id) {
+        Ok(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(e) => Err(AppError::internal(e)),
+    }
+}
@@ -0,0 +1,723 @@
+use axum::{
+    extract::rejection::{JsonRejection, QueryRejection},
+    extract::{Json, Path, Query},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::Utc;
+use plugins_base_organization::types::{Member, Organization, Permission, Rank};
+use serde::Deserialize;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use verseguy_shared_error::{AppError, ProblemDetails};
+
+use super::{
+    conditional_get, contains_ci, created, json_body, query, require_match, with_etag,
+    DomainServices, Page, PageParams,
+};
+use crate::auth::{Bearer, ReadScope, WriteScope};
+
+type Domain = Extension<Arc<DomainServices>>;
+
+// ---------------------------------------------------------------------------
+// Organizations
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OrgFilter {
+    /// Substring of the name, case-insensitive
+    pub name: Option<String>,
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateOrg {
+    pub name: String,
+    pub tag: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateOrg {
+    pub name: Option<String>,
+    pub tag: Option<String>,
+    pub description: Option<String>,
+}
+
+/// List the organizations the token's subject owns or is a member of, ordered by name
+#[utoipa::path(
+    get,
+    path = "/v1/orgs",
+    tag = "orgs",
+    params(PageParams, OrgFilter),
+    security(("oauth2" = ["read"])),
+    responses(
+        (status = 200, description = "One page of organizations", body = Page<Organization>),
+        (status = 400, description = "Invalid filter or page parameters", body = ProblemDetails),
+    )
+)]
+pub async fn list_orgs(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    page: Result<Query<PageParams>, QueryRejection>,
+    filter: Result<Query<OrgFilter>, QueryRejection>,
+) -> Result<Response, AppError> {
+    let (page, filter) = (query(page)?, query(filter)?);
+    let all = match domain.orgs.list_orgs_prefix("") {
+        Ok(o) => o,
+        Err(e) => return Err(AppError::internal(e)),
+    };
+    let mut orgs = Vec::new();
+    for org in all {
+        let member = org.owner_id == auth.claims.sub
+            || match domain.orgs.get_member(&org.id, &auth.claims.sub) {
+                Ok(m) => m.is_some(),
+                Err(e) => return Err(AppError::internal(e)),
+            };
+        if member {
+            orgs.push(org);
+        }
+    }
+    orgs.retain(|o| {
+        filter
+            .name
+            .as_deref()
+            .is_none_or(|n| contains_ci(&o.name, n))
+            && filter
+                .tag
+                .as_deref()
+                .is_none_or(|t| o.tag.eq_ignore_ascii_case(t))
+    });
+    orgs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Json(page.apply(orgs)?).into_response())
+}
+
+/// Create an organization owned by the token's subject
+#[utoipa::path(
+    post,
+    path = "/v1/orgs",
+    tag = "orgs",
+    request_body = CreateOrg,
+    security(("oauth2" = ["write"])),
+    responses(
+        (
+            status = 201,
+            description = "Organization created",
+            body = Organization,
+            headers(("ETag" = String))
+        ),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (status = 422, description = "Invalid name or tag", body = ProblemDetails),
+    )
+)]
+pub async fn create_org(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    body: Result<Json<CreateOrg>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    let _guard = domain.lock()?;
+    match domain
+        .orgs
+        .create_organization(req.name, req.tag, req.description, auth.claims.sub)
+    {
+        Ok(org) => Ok(created(format!("/v1/orgs/{}", org.id), &org)),
+        Err(e) => Err(AppError::Validation(e.to_string())),
+    }
+}
+
+/// Get an organization
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}",
+    tag = "orgs",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("If-None-Match" = Option<String>, Header),
+    ),
+    security(("oauth2" = ["read"])),
+    responses(
+        (
+            status = 200,
+            description = "The organization",
+            body = Organization,
+            headers(("ETag" = String))
+        ),
+        (status = 304, description = "Not modified"),
+        (status = 403, description = "Not a member of the organization", body = ProblemDetails),
+        (status = 404, description = "Organization not found", body = ProblemDetails),
+    )
+)]
+pub async fn get_org(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let org = domain.member_org(&id, &auth.claims.sub)?;
+    Ok(conditional_get(&headers, &org))
+}
+
+/// Update an organization
+#[utoipa::path(
+    patch,
+    path = "/v1/orgs/{id}",
+    tag = "orgs",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("If-Match" = String, Header, description = "ETag last read, or `*`"),
+    ),
+    request_body = UpdateOrg,
+    security(("oauth2" = ["write"])),
+    responses(
+        (
+            status = 200,
+            description = "Updated organization",
+            body = Organization,
+            headers(("ETag" = String))
+        ),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing the `ManageOrganization` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization not found", body = ProblemDetails),
+        (
+            status = 412,
+            description = "The organization changed since it was read",
+            body = ProblemDetails
+        ),
+        (status = 422, description = "Invalid name or tag", body = ProblemDetails),
+        (status = 428, description = "If-Match is missing", body = ProblemDetails),
+    )
+)]
+pub async fn update_org(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Result<Json<UpdateOrg>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    let _guard = domain.lock()?;
+    let org = domain.permitted_org(&id, &auth.claims.sub, Permission::ManageOrganization)?;
+    require_match(&headers, &org)?;
+    match domain
+        .orgs
+        .update_organization(&id, req.name, req.tag, req.description)
+    {
+        Ok(org) => Ok(with_etag(StatusCode::OK, &org)),
+        Err(e) => Err(AppError::Validation(e.to_string())),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Members
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MemberFilter {
+    pub rank_id: Option<String>,
+    /// Substring of the handle, case-insensitive
+    pub handle: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddMember {
+    pub user_id: String,
+    pub handle: String,
+    pub rank_id: String,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateMember {
+    pub handle: Option<String>,
+    pub rank_id: Option<String>,
+    pub notes: Option<String>,
+}
+
+fn load_member(domain: &DomainServices, org_id: &str, user_id: &str) -> Result<Member, AppError> {
+    match domain.orgs.get_member(org_id, user_id) {
+        Ok(Some(m)) => Ok(m),
+        Ok(None) => Err(AppError::NotFound("member not found".into())),
+        Err(e) => Err(AppError::internal(e)),
+    }
+}
+
+/// Members may only hold ranks defined by their own organization
+fn ensure_rank(domain: &DomainServices, org_id: &str, rank_id: &str) -> Result<(), AppError> {
+    match domain.orgs.get_rank(org_id, rank_id) {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(AppError::Validation(format!("unknown rank {}", rank_id))),
+        Err(e) => Err(AppError::internal(e)),
+    }
+}
+
+/// List members, oldest first
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}/members",
+    tag = "orgs",
+    params(("id" = String, Path, description = "Organization id"), PageParams, MemberFilter),
+    security(("oauth2" = ["read"])),
+    responses(
+        (status = 200, description = "One page of members", body = Page<Member>),
+        (status = 400, description = "Invalid filter or page parameters", body = ProblemDetails),
+        (status = 403, description = "Not a member of the organization", body = ProblemDetails),
+        (status = 404, description = "Organization not found", body = ProblemDetails),
+    )
+)]
+pub async fn list_members(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    Path(id): Path<String>,
+    page: Result<Query<PageParams>, QueryRejection>,
+    filter: Result<Query<MemberFilter>, QueryRejection>,
+) -> Result<Response, AppError> {
+    let (page, filter) = (query(page)?, query(filter)?);
+    domain.member_org(&id, &auth.claims.sub)?;
+    let mut members = match domain.orgs.list_members(&id) {
+        Ok(m) => m,
+        Err(e) => return Err(AppError::internal(e)),
+    };
+    members.retain(|m| {
+        filter.rank_id.as_deref().is_none_or(|r| m.rank_id == r)
+            && filter
+                .handle
+                .as_deref()
+                .is_none_or(|h| contains_ci(&m.handle, h))
+    });
+    members.sort_by(|a, b| {
+        a.joined_at
+            .cmp(&b.joined_at)
+            .then_with(|| a.user_id.cmp(&b.user_id))
+    });
+    Ok(Json(page.apply(members)?).into_response())
+}
+
+/// Add a member
+#[utoipa::path(
+    post,
+    path = "/v1/orgs/{id}/members",
+    tag = "orgs",
+    params(("id" = String, Path, description = "Organization id")),
+    request_body = AddMember,
+    security(("oauth2" = ["write"])),
+    responses(
+        (status = 201, description = "Member added", body = Member, headers(("ETag" = String))),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing the `InviteMembers` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization not found", body = ProblemDetails),
+        (status = 409, description = "The user is already a member", body = ProblemDetails),
+        (status = 422, description = "Unknown rank", body = ProblemDetails),
+    )
+)]
+pub async fn add_member(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path(id): Path<String>,
+    body: Result<Json<AddMember>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::InviteMembers)?;
+    match domain.orgs.get_member(&id, &req.user_id) {
+        Ok(Some(_)) => return Err(AppError::Conflict("user is already a member".into())),
+        Ok(None) => (),
+        Err(e) => return Err(AppError::internal(e)),
+    }
+    ensure_rank(&domain, &id, &req.rank_id)?;
+    let member = Member {
+        id: Uuid::new_v4().to_string(),
+        org_id: id.clone(),
+        user_id: req.user_id,
+        handle: req.handle,
+        rank_id: req.rank_id,
+        joined_at: Utc::now(),
+        notes: req.notes,
+    };
+    if let Err(e) = domain.orgs.add_member(member.clone()) {
+        return Err(AppError::Validation(e.to_string()));
+    }
+    let location = format!("/v1/orgs/{}/members/{}", id, member.user_id);
+    Ok(created(location, &member))
+}
+
+/// Get a member
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}/members/{user_id}",
+    tag = "orgs",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("user_id" = String, Path, description = "Member user id"),
+        ("If-None-Match" = Option<String>, Header),
+    ),
+    security(("oauth2" = ["read"])),
+    responses(
+        (status = 200, description = "The member", body = Member, headers(("ETag" = String))),
+        (status = 304, description = "Not modified"),
+        (status = 403, description = "Not a member of the organization", body = ProblemDetails),
+        (status = 404, description = "Organization or member not found", body = ProblemDetails),
+    )
+)]
+pub async fn get_member(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    Path((id, user_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    domain.member_org(&id, &auth.claims.sub)?;
+    let member = load_member(&domain, &id, &user_id)?;
+    Ok(conditional_get(&headers, &member))
+}
+
+/// Update a member's handle, rank or notes
+#[utoipa::path(
+    patch,
+    path = "/v1/orgs/{id}/members/{user_id}",
+    tag = "orgs",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("user_id" = String, Path, description = "Member user id"),
+        ("If-Match" = String, Header, description = "ETag last read, or `*`"),
+    ),
+    request_body = UpdateMember,
+    security(("oauth2" = ["write"])),
+    responses(
+        (status = 200, description = "Updated member", body = Member, headers(("ETag" = String))),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing the `ManageMembers` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization or member not found", body = ProblemDetails),
+        (status = 412, description = "The member changed since it was read", body = ProblemDetails),
+        (status = 422, description = "Unknown rank", body = ProblemDetails),
+        (status = 428, description = "If-Match is missing", body = ProblemDetails),
+    )
+)]
+pub async fn update_member(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path((id, user_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Result<Json<UpdateMember>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageMembers)?;
+    let mut member = load_member(&domain, &id, &user_id)?;
+    require_match(&headers, &member)?;
+    if let Some(rank_id) = req.rank_id {
+        ensure_rank(&domain, &id, &rank_id)?;
+        member.rank_id = rank_id;
+    }
+    if let Some(handle) = req.handle {
+        member.handle = handle;
+    }
+    if req.notes.is_some() {
+        member.notes = req.notes;
+    }
+    match domain.orgs.add_member(member.clone()) {
+        Ok(_) => Ok(with_etag(StatusCode::OK, &member)),
+        Err(e) => Err(AppError::Validation(e.to_string())),
+    }
+}
+
+/// Remove a member
+#[utoipa::path(
+    delete,
+    path = "/v1/orgs/{id}/members/{user_id}",
+    tag = "orgs",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("user_id" = String, Path, description = "Member user id"),
+        ("If-Match" = String, Header, description = "ETag last read, or `*`"),
+    ),
+    security(("oauth2" = ["write"])),
+    responses(
+        (status = 204, description = "Removed"),
+        (
+            status = 403,
+            description = "Missing the `KickMembers` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization or member not found", body = ProblemDetails),
+        (status = 412, description = "The member changed since it was read", body = ProblemDetails),
+        (status = 428, description = "If-Match is missing", body = ProblemDetails),
+    )
+)]
+pub async fn remove_member(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path((id, user_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::KickMembers)?;
+    let member = load_member(&domain, &id, &user_id)?;
+    require_match(&headers, &member)?;
+    match domain.orgs.remove_member(&id, &user_id) {
+        Ok(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(e) => Err(AppError::internal(e)),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Ranks
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RankFilter {
+    pub min_level: Option<i32>,
+    /// Only ranks granting this permission, e.g. `manage_members`
+    pub permission: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateRank {
+    pub name: String,
+    pub level: i32,
+    #[serde(default)]
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateRank {
+    pub name: Option<String>,
+    pub level: Option<i32>,
+    pub permissions: Option<Vec<Permission>>,
+}
+
+fn load_rank(domain: &DomainServices, org_id: &str, rank_id: &str) -> Result<Rank, AppError> {
+    match domain.orgs.get_rank(org_id, rank_id) {
+        Ok(Some(r)) => Ok(r),
+        Ok(None) => Err(AppError::NotFound("rank not found".into())),
+        Err(e) => Err(AppError::internal(e)),
+    }
+}
+
+/// List ranks, most senior first
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}/ranks",
+    tag = "orgs",
+    params(("id" = String, Path, description = "Organization id"), PageParams, RankFilter),
+    security(("oauth2" = ["read"])),
+    responses(
+        (status = 200, description = "One page of ranks", body = Page<Rank>),
+        (status = 400, description = "Invalid filter or page parameters", body = ProblemDetails),
+        (status = 403, description = "Not a member of the organization", body = ProblemDetails),
+        (status = 404, description = "Organization not found", body = ProblemDetails),
+    )
+)]
+pub async fn list_ranks(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    Path(id): Path<String>,
+    page: Result<Query<PageParams>, QueryRejection>,
+    filter: Result<Query<RankFilter>, QueryRejection>,
+) -> Result<Response, AppError> {
+    let (page, filter) = (query(page)?, query(filter)?);
+    domain.member_org(&id, &auth.claims.sub)?;
+    let mut ranks = match domain.orgs.list_ranks(&id) {
+        Ok(r) => r,
+        Err(e) => return Err(AppError::internal(e)),
+    };
+    ranks.retain(|r| {
+        filter.min_level.is_none_or(|l| r.level >= l)
+            && filter
+                .permission
+                .as_deref()
+                .is_none_or(|p| r.permissions.iter().any(|rp| rp.as_str() == p))
+    });
+    Ok(Json(page.apply(ranks)?).into_response())
+}
+
+/// Create a rank
+#[utoipa::path(
+    post,
+    path = "/v1/orgs/{id}/ranks",
+    tag = "orgs",
+    params(("id" = String, Path, description = "Organization id")),
+    request_body = CreateRank,
+    security(("oauth2" = ["write"])),
+    responses(
+        (status = 201, description = "Rank created", body = Rank, headers(("ETag" = String))),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing the `ManageRanks` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization not found", body = ProblemDetails),
+        (status = 422, description = "Invalid rank name", body = ProblemDetails),
+    )
+)]
+pub async fn create_rank(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path(id): Path<String>,
+    body: Result<Json<CreateRank>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageRanks)?;
+    let rank = Rank {
+        id: Uuid::new_v4().to_string(),
+        org_id: id.clone(),
+        name: req.name,
+        level: req.level,
+        permissions: req.permissions,
+        created_at: Utc::now(),
+    };
+    if let Err(e) = domain.orgs.save_rank(rank.clone()) {
+        return Err(AppError::Validation(e.to_string()));
+    }
+    Ok(created(format!("/v1/orgs/{}/ranks/{}", id, rank.id), &rank))
+}
+
+/// Get a rank
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}/ranks/{rank_id}",
+    tag = "orgs",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("rank_id" = String, Path, description = "Rank id"),
+        ("If-None-Match" = Option<String>, Header),
+    ),
+    security(("oauth2" = ["read"])),
+    responses(
+        (status = 200, description = "The rank", body = Rank, headers(("ETag" = String))),
+        (status = 304, description = "Not modified"),
+        (status = 403, description = "Not a member of the organization", body = ProblemDetails),
+        (status = 404, description = "Organization or rank not found", body = ProblemDetails),
+    )
+)]
+pub async fn get_rank(
+    auth: Bearer<ReadScope>,
+    Extension(domain): Domain,
+    Path((id, rank_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    domain.member_org(&id, &auth.claims.sub)?;
+    let rank = load_rank(&domain, &id, &rank_id)?;
+    Ok(conditional_get(&headers, &rank))
+}
+
+/// Update a rank
+#[utoipa::path(
+    patch,
+    path = "/v1/orgs/{id}/ranks/{rank_id}",
+    tag = "orgs",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("rank_id" = String, Path, description = "Rank id"),
+        ("If-Match" = String, Header, description = "ETag last read, or `*`"),
+    ),
+    request_body = UpdateRank,
+    security(("oauth2" = ["write"])),
+    responses(
+        (status = 200, description = "Updated rank", body = Rank, headers(("ETag" = String))),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing the `ManageRanks` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization or rank not found", body = ProblemDetails),
+        (status = 412, description = "The rank changed since it was read", body = ProblemDetails),
+        (status = 422, description = "Invalid rank name", body = ProblemDetails),
+        (status = 428, description = "If-Match is missing", body = ProblemDetails),
+    )
+)]
+pub async fn update_rank(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path((id, rank_id)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Result<Json<UpdateRank>, JsonRejection>,
+) -> Result<Response, AppError> {
+    let req = json_body(body)?;
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageRanks)?;
+    let mut rank = load_rank(&domain, &id, &rank_id)?;
+    require_match(&headers, &rank)?;
+    if let Some(name) = req.name {
+        rank.name = name;
+    }
+    if let Some(level) = req.level {
+        rank.level = level;
+    }
+    if let Some(permissions) = req.permissions {
+        rank.permissions = permissions;
+    }
+    match domain.orgs.save_rank(rank.clone()) {
+        Ok(_) => Ok(with_etag(StatusCode::OK, &rank)),
+        Err(e) => Err(AppError::Validation(e.to_string())),
+    }
+}
+
+/// Ranks still held by members cannot be deleted
+#[utoipa::path(
+    delete,
+    path = "/v1/orgs/{id}/ranks/{rank_id}",
+    tag = "orgs",
+    params(
+        ("id" = String, Path, description = "Organization id"),
+        ("rank_id" = String, Path, description = "Rank id"),
+        ("If-Match" = String, Header, description = "ETag last read, or `*`"),
+    ),
+    security(("oauth2" = ["write"])),
+    responses(
+        (status = 204, description = "Deleted"),
+        (
+            status = 403,
+            description = "Missing the `ManageRanks` organization permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Organization or rank not found", body = ProblemDetails),
+        (status = 409, description = "Members still hold the rank", body = ProblemDetails),
+        (status = 412, description = "The rank changed since it was read", body = ProblemDetails),
+        (status = 428, description = "If-Match is missing", body = ProblemDetails),
+    )
+)]
+pub async fn delete_rank(
+    auth: Bearer<WriteScope>,
+    Extension(domain): Domain,
+    Path((id, rank_id)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let _guard = domain.lock()?;
+    domain.permitted_org(&id, &auth.claims.sub, Permission::ManageRanks)?;
+    let rank = load_rank(&domain, &id, &rank_id)?;
+    require_match(&headers, &rank)?;
+    match domain.orgs.list_members(&id) {
+        Ok(members) if members.iter().any(|m| m.rank_id == rank_id) => {
+            return Err(AppError::Conflict(
+                "rank is still assigned to members".into(),
+            ))
+        }
+        Ok(_) => (),
+        Err(e) => return Err(AppError::internal(e)),
+    }
+    match domain.orgs.delete_rank(&id, &rank_id) {
+        Ok(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(e) => Err(AppError::internal(e)),
+    }
+}
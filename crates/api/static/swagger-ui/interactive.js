@@ -1,5 +1,5 @@
 // Interactive local Swagger-like UI for offline use
-// - renderInteractiveUI(url, dom_id) loads /openapi.yaml and renders a basic interactive UI
+// - renderInteractiveUI(url, dom_id) loads the OpenAPI document (/openapi.json) and renders a basic interactive UI
 // - Provides a lightweight "Try it" capability for simple GET requests
 
 (function (global) {
@@ -401,7 +401,7 @@
     };
 
     // Authorization Code helper - opens popup, listens for postMessage from callback, exchanges code
-    function performAuthorizationCodeFlow({ clientId, clientSecret, redirectUri = '/static/swagger-ui/oauth-callback.html', state = undefined, timeout = 60000 } = {}) {
+    function performAuthorizationCodeFlow({ clientId, clientSecret, redirectUri = new URL('/static/swagger-ui/oauth-callback.html', window.location.origin).href, state = undefined, timeout = 60000 } = {}) {
       return new Promise((resolve, reject) => {
         if (!clientId || !clientSecret) return reject(new Error('clientId and clientSecret are required'));
 
@@ -0,0 +1,214 @@
+use axum::body::Body;
+use axum::http::StatusCode;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+use verseguy_api::authorize::pkce_challenge;
+use verseguy_api::clients::{ClientStore, InMemoryClientStore};
+use verseguy_api::store::{InMemoryTokenStore, TokenStore};
+
+const SESSION_SECRET: &[u8] = b"test-session-secret";
+const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+struct Reply {
+    status: StatusCode,
+    location: Option<String>,
+    body: String,
+}
+
+impl Reply {
+    fn query(&self) -> HashMap<String, String> {
+        let loc = match &self.location {
+            Some(l) => l,
+            None => panic!("expected redirect, got {} {}", self.status, self.body),
+        };
+        match loc.split_once('?') {
+            Some((_, q)) => serde_urlencoded::from_str(q).unwrap_or_default(),
+            None => HashMap::new(),
+        }
+    }
+}
+
+async fn send(app: &axum::Router, req: axum::http::request::Builder, body: Body) -> Reply {
+    let req = match req.body(body) {
+        Ok(r) => r,
+        Err(e) => panic!("failed to build request: {}", e),
+    };
+    let resp = match app.clone().oneshot(req).await {
+        Ok(r) => r,
+        Err(e) => panic!("request failed: {}", e),
+    };
+    let status = resp.status();
+    let location = resp
+        .headers()
+        .get(axum::http::header::LOCATION)
+        .and_then(|h| h.to_str().ok())
+        .map(|s| s.to_string());
+    let bytes = match axum::body::to_bytes(resp.into_body(), 1024 * 1024).await {
+        Ok(b) => b,
+        Err(e) => panic!("failed to read body: {}", e),
+    };
+    Reply {
+        status,
+        location,
+        body: String::from_utf8_lossy(&bytes).into_owned(),
+    }
+}
+
+fn session_cookie(user: &str) -> String {
+    let claims = serde_json::json!({ "sub": user, "exp": chrono::Utc::now().timestamp() + 600 });
+    match jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(SESSION_SECRET),
+    ) {
+        Ok(t) => format!("verseguy_session={}", t),
+        Err(e) => panic!("failed to sign session: {}", e),
+    }
+}
+
+fn authorize(client_id: &str, pkce: bool, cookie: Option<&str>) -> axum::http::request::Builder {
+    let mut uri = format!(
+        "/oauth/authorize?response_type=code&client_id={}&redirect_uri=https%3A%2F%2Fspa.example%2Fcb&scope=read&state=a%20b%26c",
+        client_id
+    );
+    if pkce {
+        uri = format!(
+            "{}&code_challenge={}&code_challenge_method=S256",
+            uri,
+            pkce_challenge(VERIFIER)
+        );
+    }
+    let mut req = axum::http::Request::builder().method("GET").uri(uri);
+    if let Some(c) = cookie {
+        req = req.header("cookie", c);
+    }
+    req
+}
+
+fn consent(decision: &str, page: &str, cookie: &str) -> (axum::http::request::Builder, Body) {
+    let marker = "name=\"consent_id\" value=\"";
+    let consent_id = match page
+        .split_once(marker)
+        .and_then(|(_, rest)| rest.split_once('"'))
+    {
+        Some((id, _)) => id.to_string(),
+        None => panic!("consent page without consent_id: {}", page),
+    };
+    (
+        axum::http::Request::builder()
+            .method("POST")
+            .uri("/oauth/authorize")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .header("cookie", cookie),
+        Body::from(format!("consent_id={}&decision={}", consent_id, decision)),
+    )
+}
+
+fn exchange(client_id: &str, code: &str, verifier: &str) -> (axum::http::request::Builder, Body) {
+    (
+        axum::http::Request::builder()
+            .method("POST")
+            .uri("/oauth/token")
+            .header("content-type", "application/x-www-form-urlencoded"),
+        Body::from(format!(
+            "grant_type=authorization_code&code={}&redirect_uri=https%3A%2F%2Fspa.example%2Fcb&client_id={}&code_verifier={}",
+            code, client_id, verifier
+        )),
+    )
+}
+
+#[test]
+fn public_client_requires_pkce_session_and_consent() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        std::env::set_var("VERSEGUY_SESSION_SECRET", "test-session-secret");
+        std::env::set_var("VERSEGUY_API_ADMIN_TOKEN", "admintoken");
+        let tokens: Arc<dyn TokenStore> = Arc::new(InMemoryTokenStore::new());
+        let clients: Arc<dyn ClientStore> = Arc::new(InMemoryClientStore::new());
+        std::env::set_var("VERSEGUY_API_JWT_SECRET", "test-jwt-secret");
+        let app = match verseguy_api::build_app_with_stores(tokens, clients) {
+            Ok(app) => app,
+            Err(e) => panic!("failed to build app: {:?}", e),
+        };
+
+        let register = r#"{"client_name":"<b>SPA</b>","redirect_uris":["https://spa.example/cb"],"token_endpoint_auth_method":"none","scope":"read write"}"#;
+        let req = axum::http::Request::builder()
+            .method("POST")
+            .uri("/admin/clients")
+            .header("content-type", "application/json")
+            .header("x-admin-token", "admintoken");
+        let reply = send(&app, req, Body::from(register)).await;
+        assert_eq!(reply.status, StatusCode::CREATED);
+        let v: serde_json::Value = serde_json::from_str(&reply.body).unwrap_or_default();
+        let client_id = match v.get("client_id").and_then(|c| c.as_str()) {
+            Some(c) => c.to_string(),
+            None => panic!("missing client_id: {}", reply.body),
+        };
+        let cookie = session_cookie("user-42");
+
+        // No session: the client is told login is required, with state preserved
+        let reply = send(&app, authorize(&client_id, true, None), Body::empty()).await;
+        let q = reply.query();
+        assert_eq!(q.get("error").map(|s| s.as_str()), Some("login_required"));
+        assert_eq!(q.get("state").map(|s| s.as_str()), Some("a b&c"));
+        assert!(reply.location.as_deref().unwrap_or("").contains("state=a+b%26c"));
+
+        // Public clients must send a PKCE challenge
+        let reply = send(&app, authorize(&client_id, false, Some(&cookie)), Body::empty()).await;
+        assert_eq!(
+            reply.query().get("error").map(|s| s.as_str()),
+            Some("invalid_request")
+        );
+
+        // First authorization shows an escaped consent page; denying redirects with access_denied
+        let reply = send(&app, authorize(&client_id, true, Some(&cookie)), Body::empty()).await;
+        assert_eq!(reply.status, StatusCode::OK);
+        assert!(reply.body.contains("&lt;b&gt;SPA&lt;/b&gt;"));
+        let (req, body) = consent("deny", &reply.body, &cookie);
+        let denied = send(&app, req, body).await;
+        assert_eq!(
+            denied.query().get("error").map(|s| s.as_str()),
+            Some("access_denied")
+        );
+
+        // A consent id cannot be used from another user's session
+        let reply = send(&app, authorize(&client_id, true, Some(&cookie)), Body::empty()).await;
+        let (req, body) = consent("approve", &reply.body, &session_cookie("someone-else"));
+        assert_eq!(send(&app, req, body).await.status, StatusCode::BAD_REQUEST);
+
+        // Approve and exchange the code; a wrong verifier burns the code
+        let reply = send(&app, authorize(&client_id, true, Some(&cookie)), Body::empty()).await;
+        let (req, body) = consent("approve", &reply.body, &cookie);
+        let approved = send(&app, req, body).await;
+        let code = match approved.query().get("code") {
+            Some(c) => c.clone(),
+            None => panic!("no code in {:?}", approved.location),
+        };
+        let wrong = "x".repeat(43);
+        let (req, body) = exchange(&client_id, &code, &wrong);
+        assert_eq!(send(&app, req, body).await.status, StatusCode::BAD_REQUEST);
+        let (req, body) = exchange(&client_id, &code, VERIFIER);
+        assert_eq!(send(&app, req, body).await.status, StatusCode::BAD_REQUEST);
+
+        // Consent is remembered, so the next request redirects straight back with a code
+        let reply = send(&app, authorize(&client_id, true, Some(&cookie)), Body::empty()).await;
+        assert_eq!(reply.status, StatusCode::FOUND);
+        let code = match reply.query().get("code") {
+            Some(c) => c.clone(),
+            None => panic!("consent was not remembered: {:?}", reply.location),
+        };
+        let (req, body) = exchange(&client_id, &code, VERIFIER);
+        let reply = send(&app, req, body).await;
+        assert_eq!(reply.status, StatusCode::OK, "{}", reply.body);
+        let v: serde_json::Value = serde_json::from_str(&reply.body).unwrap_or_default();
+        assert_eq!(v.get("scope").and_then(|s| s.as_str()), Some("read"));
+    });
+}
@@ -0,0 +1,197 @@
+use axum::body::Body;
+use axum::http::StatusCode;
+use base64::Engine;
+use serde_json::Value;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+use verseguy_api::clients::{ClientStore, InMemoryClientStore};
+use verseguy_api::store::{InMemoryTokenStore, TokenStore};
+
+async fn send(
+    app: &axum::Router,
+    req: axum::http::request::Builder,
+    body: Body,
+) -> (StatusCode, axum::http::HeaderMap, Value) {
+    let req = match req.body(body) {
+        Ok(r) => r,
+        Err(e) => panic!("failed to build request: {}", e),
+    };
+    let resp = match app.clone().oneshot(req).await {
+        Ok(r) => r,
+        Err(e) => panic!("request failed: {}", e),
+    };
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let bytes = match axum::body::to_bytes(resp.into_body(), 1024 * 1024).await {
+        Ok(b) => b,
+        Err(e) => panic!("failed to read body: {}", e),
+    };
+    (
+        status,
+        headers,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+fn client_credentials(client_id: &str, secret: &str) -> (axum::http::request::Builder, Body) {
+    let basic =
+        base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", client_id, secret));
+    (
+        axum::http::Request::builder()
+            .method("POST")
+            .uri("/oauth/token")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .header("authorization", format!("Basic {}", basic)),
+        Body::from("grant_type=client_credentials&scope=read"),
+    )
+}
+
+#[test]
+fn register_authenticate_and_rotate_client() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        std::env::set_var("VERSEGUY_API_ADMIN_TOKEN", "admintoken");
+        let tokens: Arc<dyn TokenStore> = Arc::new(InMemoryTokenStore::new());
+        let clients: Arc<dyn ClientStore> = Arc::new(InMemoryClientStore::new());
+        std::env::set_var("VERSEGUY_API_JWT_SECRET", "test-jwt-secret");
+        let app = match verseguy_api::build_app_with_stores(tokens, clients) {
+            Ok(app) => app,
+            Err(e) => panic!("failed to build app: {:?}", e),
+        };
+
+        // Registration requires the admin token
+        let register = r#"{"client_name":"ci","redirect_uris":["https://ci.example/cb"],"grant_types":["client_credentials","authorization_code"],"scope":"read"}"#;
+        let req = axum::http::Request::builder()
+            .method("POST")
+            .uri("/admin/clients")
+            .header("content-type", "application/json");
+        let (status, _, _) = send(&app, req, Body::from(register)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let req = axum::http::Request::builder()
+            .method("POST")
+            .uri("/admin/clients")
+            .header("content-type", "application/json")
+            .header("x-admin-token", "admintoken");
+        let (status, _, v) = send(&app, req, Body::from(register)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let client_id = match v.get("client_id").and_then(|c| c.as_str()) {
+            Some(c) => c.to_string(),
+            None => panic!("missing client_id: {:?}", v),
+        };
+        let secret = match v.get("client_secret").and_then(|c| c.as_str()) {
+            Some(c) => c.to_string(),
+            None => panic!("missing client_secret: {:?}", v),
+        };
+
+        // The demo client does not exist in a fresh registry
+        let (req, body) = client_credentials("demo", "secret");
+        let (status, _, _) = send(&app, req, body).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (req, body) = client_credentials(&client_id, &secret);
+        let (status, _, v) = send(&app, req, body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(v.get("scope").and_then(|s| s.as_str()), Some("read"));
+
+        let (req, body) = client_credentials(&client_id, "wrong");
+        let (status, _, _) = send(&app, req, body).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Redirect URIs must match exactly
+        let uri = format!(
+            "/oauth/authorize?response_type=code&client_id={}&redirect_uri=https://ci.example/cb/other",
+            client_id
+        );
+        let req = axum::http::Request::builder().method("GET").uri(uri);
+        let (status, _, _) = send(&app, req, Body::empty()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Rotating the secret invalidates the old one
+        let req = axum::http::Request::builder()
+            .method("POST")
+            .uri(format!("/admin/clients/{}/secret", client_id))
+            .header("x-admin-token", "admintoken");
+        let (status, _, v) = send(&app, req, Body::empty()).await;
+        assert_eq!(status, StatusCode::OK);
+        let rotated = match v.get("client_secret").and_then(|c| c.as_str()) {
+            Some(c) => c.to_string(),
+            None => panic!("missing rotated secret: {:?}", v),
+        };
+        assert_ne!(rotated, secret);
+
+        let (req, body) = client_credentials(&client_id, &secret);
+        let (status, _, _) = send(&app, req, body).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (req, body) = client_credentials(&client_id, &rotated);
+        let (status, _, _) = send(&app, req, body).await;
+        assert_eq!(status, StatusCode::OK);
+    });
+}
+
+#[test]
+fn public_clients_cannot_use_client_credentials() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        std::env::set_var("VERSEGUY_API_ADMIN_TOKEN", "admintoken");
+        let tokens: Arc<dyn TokenStore> = Arc::new(InMemoryTokenStore::new());
+        let clients: Arc<dyn ClientStore> = Arc::new(InMemoryClientStore::new());
+        std::env::set_var("VERSEGUY_API_JWT_SECRET", "test-jwt-secret");
+        let app = match verseguy_api::build_app_with_stores(tokens, clients) {
+            Ok(app) => app,
+            Err(e) => panic!("failed to build app: {:?}", e),
+        };
+
+        let register = r#"{"redirect_uris":["https://spa.example/cb"],"token_endpoint_auth_method":"none","grant_types":["client_credentials"]}"#;
+        let req = axum::http::Request::builder()
+            .method("POST")
+            .uri("/admin/clients")
+            .header("content-type", "application/json")
+            .header("x-admin-token", "admintoken");
+        let (status, _, v) = send(&app, req, Body::from(register)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            v.get("code").and_then(|e| e.as_str()),
+            Some("invalid_client_metadata")
+        );
+
+        let register = r#"{"redirect_uris":["https://spa.example/cb"],"token_endpoint_auth_method":"none"}"#;
+        let req = axum::http::Request::builder()
+            .method("POST")
+            .uri("/admin/clients")
+            .header("content-type", "application/json")
+            .header("x-admin-token", "admintoken");
+        let (status, _, v) = send(&app, req, Body::from(register)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(v.get("client_secret").is_none());
+        assert_eq!(
+            v.get("token_endpoint_auth_method").and_then(|e| e.as_str()),
+            Some("none")
+        );
+
+        // Public clients can start the authorization code flow at a registered redirect
+        let client_id = v.get("client_id").and_then(|c| c.as_str()).unwrap_or("");
+        let uri = format!(
+            "/oauth/authorize?response_type=code&client_id={}&redirect_uri=https://spa.example/cb",
+            client_id
+        );
+        let req = axum::http::Request::builder().method("GET").uri(uri);
+        let (status, headers, _) = send(&app, req, Body::empty()).await;
+        assert_eq!(status, StatusCode::FOUND);
+        assert!(headers.contains_key(axum::http::header::LOCATION));
+    });
+}
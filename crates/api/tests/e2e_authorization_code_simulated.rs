@@ -14,13 +14,24 @@ fn e2e_authorization_code_simulated_flow() {
 
     rt.block_on(async {
         use verseguy_api::build_app;
+        std::env::set_var("VERSEGUY_SESSION_SECRET", "test-session-secret");
         let app = build_app();
+        let claims = serde_json::json!({ "sub": "user-1", "exp": chrono::Utc::now().timestamp() + 600 });
+        let session = match jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(b"test-session-secret"),
+        ) {
+            Ok(t) => t,
+            Err(e) => panic!("failed to sign session: {}", e),
+        };
 
         // Step 1: request authorization code
         let auth_uri = "/oauth/authorize?response_type=code&client_id=demo&redirect_uri=https://example.com/cb&state=xyz";
         let req_auth = match axum::http::Request::builder()
             .method("GET")
             .uri(auth_uri)
+            .header("cookie", format!("verseguy_session={}", session))
             .body(Body::empty())
         {
             Ok(r) => r,
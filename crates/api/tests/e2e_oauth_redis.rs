@@ -23,14 +23,25 @@ fn e2e_oauth_with_redis() {
             Err(e) => { eprintln!("Skipping e2e Redis test (redis client open failed): {:?}", e); return; }
         }
 
-        let rstore = match verseguy_api::store::RedisTokenStore::new(&url) {
+        let rstore = match verseguy_api::store::RedisTokenStore::connect(&url, 2).await {
             Ok(s) => s,
             Err(e) => { eprintln!("Skipping e2e Redis test (client init failed): {:?}", e); return; }
         };
 
         let store_arc: std::sync::Arc<dyn verseguy_api::store::TokenStore> = std::sync::Arc::new(rstore);
 
-        let app = verseguy_api::build_app_with_store(store_arc.clone());
+        std::env::set_var("VERSEGUY_SESSION_SECRET", "test-session-secret");
+        let clients = std::sync::Arc::new(verseguy_api::clients::InMemoryClientStore::with_demo_client());
+        let app = verseguy_api::build_app_with_store(store_arc.clone(), clients);
+        let claims = serde_json::json!({ "sub": "user-1", "exp": chrono::Utc::now().timestamp() + 600 });
+        let session = match jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(b"test-session-secret"),
+        ) {
+            Ok(t) => t,
+            Err(e) => panic!("failed to sign session: {}", e),
+        };
 
         // client_credentials flow
         let body = "grant_type=client_credentials&client_id=demo&client_secret=secret";
@@ -57,41 +68,15 @@ fn e2e_oauth_with_redis() {
             Ok(j) => j,
             Err(e) => panic!("invalid json response: {}", e),
         };
-        let rtok = match v.get("refresh_token").and_then(|r| r.as_str()) {
-            Some(s) => s.to_string(),
-            None => panic!("no refresh_token in response: {:?}", v),
-        };
-
-        // Ensure refresh token persists in Redis store
-        match store_arc.get(&rtok) {
-            Ok(Some(rec)) => assert_eq!(rec.refresh_token, rtok),
-            Ok(None) => panic!("refresh token not found in redis store"),
-            Err(e) => panic!("store get failed: {:?}", e),
-        }
-
-        // refresh flow
-        let body2 = format!("grant_type=refresh_token&refresh_token={}", rtok);
-        let req2 = match axum::http::Request::builder()
-            .method("POST")
-            .uri("/oauth/token")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from(body2))
-        {
-            Ok(r) => r,
-            Err(e) => panic!("failed to build request: {}", e),
-        };
-
-        let resp2 = match app.clone().oneshot(req2).await {
-            Ok(r) => r,
-            Err(e) => panic!("request failed: {}", e),
-        };
-        assert_eq!(resp2.status(), axum::http::StatusCode::OK);
+        assert!(v.get("access_token").is_some());
+        assert!(v.get("refresh_token").is_none());
 
         // authorization code flow
         let auth_uri = "/oauth/authorize?response_type=code&client_id=demo&redirect_uri=https://example.com/cb&state=xyz";
         let req_auth = match axum::http::Request::builder()
             .method("GET")
             .uri(auth_uri)
+            .header("cookie", format!("verseguy_session={}", session))
             .body(Body::empty())
         {
             Ok(r) => r,
@@ -147,5 +132,38 @@ fn e2e_oauth_with_redis() {
             Err(e) => panic!("invalid json response: {}", e),
         };
         assert!(v3.get("access_token").is_some());
+
+        let rtok = match v3.get("refresh_token").and_then(|r| r.as_str()) {
+            Some(s) => s.to_string(),
+            None => panic!("no refresh_token in response: {:?}", v3),
+        };
+
+        // Ensure refresh token persists in Redis store
+        match store_arc.get(&rtok).await {
+            Ok(Some(rec)) => assert_eq!(rec.refresh_token, rtok),
+            Ok(None) => panic!("refresh token not found in redis store"),
+            Err(e) => panic!("store get failed: {:?}", e),
+        }
+
+        // refresh flow
+        let body2 = format!(
+            "grant_type=refresh_token&refresh_token={}&client_id=demo&client_secret=secret",
+            rtok
+        );
+        let req2 = match axum::http::Request::builder()
+            .method("POST")
+            .uri("/oauth/token")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(body2))
+        {
+            Ok(r) => r,
+            Err(e) => panic!("failed to build request: {}", e),
+        };
+
+        let resp2 = match app.clone().oneshot(req2).await {
+            Ok(r) => r,
+            Err(e) => panic!("request failed: {}", e),
+        };
+        assert_eq!(resp2.status(), axum::http::StatusCode::OK);
     });
 }
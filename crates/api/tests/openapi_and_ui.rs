@@ -34,7 +34,7 @@ fn openapi_and_docs_are_served() {
             Err(e) => panic!("failed to read body: {}", e),
         };
         let s = String::from_utf8_lossy(&bytes);
-        assert!(s.contains("openapi: 3.0.1"));
+        assert!(s.contains("openapi: 3.1.0"));
 
         let req2 = match axum::http::Request::builder()
             .method("GET")
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+use uuid::Uuid;
+use verseguy_api::clients::{ClientStore, InMemoryClientStore};
+use verseguy_api::store::{InMemoryTokenStore, TokenStore};
+use verseguy_api::v1::DomainServices;
+use verseguy_storage::Storage;
+use verseguy_test_utils::openapi::{
+    assert_operations_routed, assert_path_parameters_documented, operations, served_spec,
+};
+
+/// Full application, with the `/v1` domain API
+fn app_with_domain() -> axum::Router {
+    let dir = std::env::temp_dir().join(format!("verseguy_openapi_{}", Uuid::new_v4()));
+    let storage = match Storage::open(&dir) {
+        Ok(s) => s,
+        Err(e) => panic!("failed to open storage: {}", e),
+    };
+    let tokens: Arc<dyn TokenStore> = Arc::new(InMemoryTokenStore::new());
+    let clients: Arc<dyn ClientStore> = Arc::new(InMemoryClientStore::with_demo_client());
+    std::env::set_var("VERSEGUY_API_JWT_SECRET", "test-jwt-secret");
+    let domain = Arc::new(DomainServices::new(storage));
+    match verseguy_api::build_app_with_domain(tokens, clients, domain) {
+        Ok(app) => app,
+        Err(e) => panic!("failed to build app: {:?}", e),
+    }
+}
+
+#[test]
+fn every_documented_operation_is_routed() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let app = app_with_domain();
+        let spec = served_spec(&app).await;
+        let ops = operations(&spec);
+        assert!(ops.iter().any(|(m, p, _)| m == "GET" && p == "/protected"));
+        assert!(ops
+            .iter()
+            .any(|(m, p, _)| m == "POST" && p == "/oauth/token"));
+        assert!(ops
+            .iter()
+            .any(|(m, p, _)| m == "PATCH" && p == "/v1/orgs/{id}"));
+
+        assert_operations_routed(&app, &spec).await;
+    });
+}
+
+#[test]
+fn path_parameters_are_documented() {
+    let spec = match serde_json::to_value(verseguy_api::openapi_spec()) {
+        Ok(v) => v,
+        Err(e) => panic!("failed to serialize spec: {}", e),
+    };
+    assert_path_parameters_documented(&spec);
+}
+
+#[test]
+fn served_spec_matches_exported_spec() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let exported = match serde_json::to_value(verseguy_api::openapi_spec()) {
+            Ok(v) => v,
+            Err(e) => panic!("failed to serialize spec: {}", e),
+        };
+        assert_eq!(served_spec(&app_with_domain()).await, exported);
+
+        // without domain services neither the routes nor their documentation exist
+        let spec = served_spec(&verseguy_api::build_app()).await;
+        assert!(operations(&spec)
+            .iter()
+            .all(|(_, p, _)| !p.starts_with("/v1/")));
+    });
+}
@@ -0,0 +1,60 @@
+use axum::body::Body;
+use axum::http::StatusCode;
+use tower::util::ServiceExt;
+
+/// `client_credentials` request authenticating with HTTP Basic (`demo:secret` or `other:x`)
+fn token_request(basic: &str) -> axum::http::Request<Body> {
+    match axum::http::Request::builder()
+        .method("POST")
+        .uri("/oauth/token")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .header("authorization", format!("Basic {}", basic))
+        .body(Body::from("grant_type=client_credentials&scope=read"))
+    {
+        Ok(r) => r,
+        Err(e) => panic!("failed to build request: {}", e),
+    }
+}
+
+async fn call(app: &axum::Router, req: axum::http::Request<Body>) -> (StatusCode, String) {
+    let resp = match app.clone().oneshot(req).await {
+        Ok(r) => r,
+        Err(e) => panic!("request failed: {}", e),
+    };
+    let retry_after = resp
+        .headers()
+        .get("retry-after")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    (resp.status(), retry_after)
+}
+
+#[test]
+fn token_endpoint_is_limited_per_client() {
+    // this file holds a single test, so the variable does not leak into other tests
+    std::env::set_var("VERSEGUY_API_RATE_LIMIT_OAUTH", "2/min");
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let app = verseguy_api::build_app();
+        let demo = "ZGVtbzpzZWNyZXQ=";
+        for _ in 0..2 {
+            let (status, _) = call(&app, token_request(demo)).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let (status, retry_after) = call(&app, token_request(demo)).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(retry_after, "30");
+
+        // another client has its own bucket (and fails authentication instead)
+        let (status, _) = call(&app, token_request("b3RoZXI6eA==")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    });
+}
@@ -0,0 +1,239 @@
+use axum::body::Body;
+use axum::http::StatusCode;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+use verseguy_api::clients::{hash_secret, ClientStore, InMemoryClientStore, OAuthClient};
+
+async fn call(app: &axum::Router, req: axum::http::Request<Body>) -> (StatusCode, Value) {
+    let resp = match app.clone().oneshot(req).await {
+        Ok(r) => r,
+        Err(e) => panic!("request failed: {}", e),
+    };
+    let status = resp.status();
+    let bytes = match axum::body::to_bytes(resp.into_body(), 1024 * 1024).await {
+        Ok(b) => b,
+        Err(e) => panic!("failed to read body: {}", e),
+    };
+    let body = String::from_utf8_lossy(&bytes).into_owned();
+    (
+        status,
+        serde_json::from_str(&body).unwrap_or(Value::String(body)),
+    )
+}
+
+fn token_request(body: String) -> axum::http::Request<Body> {
+    match axum::http::Request::builder()
+        .method("POST")
+        .uri("/oauth/token")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from(body))
+    {
+        Ok(r) => r,
+        Err(e) => panic!("failed to build request: {}", e),
+    }
+}
+
+fn protected(token: &str) -> axum::http::Request<Body> {
+    match axum::http::Request::builder()
+        .method("GET")
+        .uri("/protected")
+        .header("authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+    {
+        Ok(r) => r,
+        Err(e) => panic!("failed to build request: {}", e),
+    }
+}
+
+fn field(v: &Value, name: &str) -> String {
+    match v.get(name).and_then(|t| t.as_str()) {
+        Some(s) => s.to_string(),
+        None => panic!("missing {} in {:?}", name, v),
+    }
+}
+
+const DEMO: &str = "client_id=demo&client_secret=secret";
+
+async fn refresh(app: &axum::Router, token: &str) -> (StatusCode, Value) {
+    refresh_as(app, token, DEMO).await
+}
+
+/// Exchange `token` authenticating with the `client` form fields
+async fn refresh_as(app: &axum::Router, token: &str, client: &str) -> (StatusCode, Value) {
+    let body = format!(
+        "grant_type=refresh_token&refresh_token={}&{}",
+        token, client
+    );
+    call(app, token_request(body)).await
+}
+
+/// Run the authorization_code grant for `client_id`, authenticating with `client`
+async fn authorization_code(
+    app: &axum::Router,
+    client_id: &str,
+    redirect_uri: &str,
+    client: &str,
+) -> Value {
+    let claims =
+        serde_json::json!({ "sub": "user-1", "exp": chrono::Utc::now().timestamp() + 600 });
+    let session = match jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(b"test-session-secret"),
+    ) {
+        Ok(t) => t,
+        Err(e) => panic!("failed to sign session: {}", e),
+    };
+    let uri = format!(
+        "/oauth/authorize?response_type=code&client_id={}&redirect_uri={}&scope=read&state=s",
+        client_id, redirect_uri
+    );
+    let req = match axum::http::Request::builder()
+        .uri(uri)
+        .header("cookie", format!("verseguy_session={}", session))
+        .body(Body::empty())
+    {
+        Ok(r) => r,
+        Err(e) => panic!("failed to build request: {}", e),
+    };
+    let resp = match app.clone().oneshot(req).await {
+        Ok(r) => r,
+        Err(e) => panic!("authorize request failed: {}", e),
+    };
+    let location = match resp.headers().get(axum::http::header::LOCATION) {
+        Some(h) => h.to_str().unwrap_or_default().to_string(),
+        None => panic!("missing location header"),
+    };
+    let query = location.split('?').nth(1).unwrap_or_default();
+    let params: HashMap<String, String> = serde_urlencoded::from_str(query).unwrap_or_default();
+    let code = match params.get("code") {
+        Some(c) => c,
+        None => panic!("no code in {}", location),
+    };
+
+    let body = format!(
+        "grant_type=authorization_code&code={}&redirect_uri={}&{}",
+        code, redirect_uri, client
+    );
+    let (status, v) = call(app, token_request(body)).await;
+    assert_eq!(status, StatusCode::OK, "{:?}", v);
+    v
+}
+
+/// App whose registry holds `demo` and a second first-party client `other` / `other-secret`
+fn app_with_two_clients() -> axum::Router {
+    std::env::set_var("VERSEGUY_SESSION_SECRET", "test-session-secret");
+    let clients = InMemoryClientStore::with_demo_client();
+    let demo = match clients.get("demo") {
+        Ok(Some(c)) => c,
+        _ => panic!("demo client missing"),
+    };
+    let other = OAuthClient {
+        client_id: "other".into(),
+        secret_hash: Some(hash_secret("other-secret")),
+        redirect_uris: vec!["https://other.example/cb".into()],
+        ..demo
+    };
+    if let Err(e) = clients.insert(other) {
+        panic!("failed to register client: {:?}", e);
+    }
+    let store = Arc::new(verseguy_api::store::InMemoryTokenStore::new());
+    std::env::set_var("VERSEGUY_API_JWT_SECRET", "test-jwt-secret");
+    match verseguy_api::build_app_with_stores(store, Arc::new(clients)) {
+        Ok(app) => app,
+        Err(e) => panic!("failed to build app: {:?}", e),
+    }
+}
+
+#[test]
+fn refresh_tokens_rotate_and_reuse_revokes_the_family() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let app = app_with_two_clients();
+        let v = authorization_code(&app, "demo", "https://example.com/cb", DEMO).await;
+        let r1 = field(&v, "refresh_token");
+
+        // Each use returns a new refresh token
+        let (status, v) = refresh(&app, &r1).await;
+        assert_eq!(status, StatusCode::OK);
+        let r2 = field(&v, "refresh_token");
+        assert_ne!(r1, r2);
+        assert_eq!(field(&v, "scope"), "read");
+
+        let (status, v) = refresh(&app, &r2).await;
+        assert_eq!(status, StatusCode::OK);
+        let r3 = field(&v, "refresh_token");
+        let access = field(&v, "access_token");
+        let (status, _) = call(&app, protected(&access)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Replaying a rotated token revokes every token of the family
+        let (status, v) = refresh(&app, &r1).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            v.get("code").and_then(|c| c.as_str()),
+            Some("refresh_token_reused")
+        );
+        let (status, _) = refresh(&app, &r3).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = call(&app, protected(&access)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Other grants are unaffected
+        let v = authorization_code(&app, "demo", "https://example.com/cb", DEMO).await;
+        let (status, _) = refresh(&app, &field(&v, "refresh_token")).await;
+        assert_eq!(status, StatusCode::OK);
+    });
+}
+
+#[test]
+fn refresh_tokens_are_bound_to_their_client() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let app = app_with_two_clients();
+        let v = authorization_code(&app, "demo", "https://example.com/cb", DEMO).await;
+        let token = field(&v, "refresh_token");
+
+        // Without client authentication or with a wrong secret
+        let body = format!("grant_type=refresh_token&refresh_token={}", token);
+        let (status, _) = call(&app, token_request(body)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = refresh_as(&app, &token, "client_id=demo&client_secret=wrong").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Another client can neither exchange it nor revoke the family by replaying it
+        let other = "client_id=other&client_secret=other-secret";
+        let (status, v) = refresh_as(&app, &token, other).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            v.get("code").and_then(|c| c.as_str()),
+            Some("invalid_grant")
+        );
+        let (status, _) = refresh(&app, &token).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = refresh_as(&app, &token, other).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // client_credentials never returns a refresh token
+        let body = format!("grant_type=client_credentials&{}", DEMO);
+        let (status, v) = call(&app, token_request(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(v.get("refresh_token").is_none());
+    });
+}
@@ -0,0 +1,99 @@
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use verseguy_api::store::{
+    spawn_expiry_sweep, RocksTokenStore, Rotation, TokenRecord, TokenStore, TokenStoreConfig,
+};
+
+fn record(refresh: &str, ttl: i64) -> TokenRecord {
+    TokenRecord {
+        access_token: format!("access-{}", refresh),
+        refresh_token: refresh.into(),
+        expires_at: Utc::now() + chrono::Duration::seconds(ttl),
+        family_id: "family-1".into(),
+        rotated_at: None,
+    }
+}
+
+#[test]
+fn rocksdb_store_rotates_and_sweeps_expired_records() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let dir = std::env::temp_dir().join(format!("verseguy_tokens_{}", Uuid::new_v4()));
+        let path = match dir.to_str() {
+            Some(p) => p.to_string(),
+            None => panic!("failed to build temp path"),
+        };
+        let store = match RocksTokenStore::open(&path) {
+            Ok(s) => s,
+            Err(e) => panic!("failed to open rocksdb store: {:?}", e),
+        };
+
+        assert!(store.insert("r1".into(), record("r1", 60)).await.is_ok());
+        match store.rotate("r1", record("r2", 60)).await {
+            Ok(Rotation::Rotated(_)) => (),
+            other => panic!("expected rotation, got {:?}", other),
+        }
+        match store.rotate("r1", record("r3", 60)).await {
+            Ok(Rotation::Reused(_)) => (),
+            other => panic!("expected reuse, got {:?}", other),
+        }
+        match store.revoke_family("family-1").await {
+            Ok(removed) => assert_eq!(removed.len(), 2),
+            Err(e) => panic!("revoke_family failed: {:?}", e),
+        }
+
+        // expired records are hidden immediately and removed by the sweep
+        assert!(store.insert("old".into(), record("old", -1)).await.is_ok());
+        assert!(store.put_aux("code:x", "{}".into(), Some(-1)).await.is_ok());
+        assert!(store
+            .put_aux("consent:u:c", "{}".into(), None)
+            .await
+            .is_ok());
+        assert!(matches!(store.get("old").await, Ok(None)));
+        assert!(matches!(store.get_aux("code:x").await, Ok(None)));
+
+        let store: Arc<dyn TokenStore> = Arc::new(store);
+        let sweep = spawn_expiry_sweep(&store, Duration::from_millis(20));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(matches!(store.purge_expired().await, Ok(0)));
+        assert!(matches!(store.get_aux("consent:u:c").await, Ok(Some(_))));
+
+        // the sweep stops once the store is gone
+        drop(store);
+        match tokio::time::timeout(Duration::from_secs(1), sweep).await {
+            Ok(_) => (),
+            Err(_) => panic!("sweep task kept running after the store was dropped"),
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    });
+}
+
+#[test]
+fn memory_config_opens_a_working_store() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let store = match TokenStoreConfig::Memory.open().await {
+            Ok(s) => s,
+            Err(e) => panic!("failed to open store: {:?}", e),
+        };
+        assert!(store.insert("r1".into(), record("r1", 60)).await.is_ok());
+        assert!(matches!(store.get("r1").await, Ok(Some(_))));
+    });
+}
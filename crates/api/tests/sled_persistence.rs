@@ -1,7 +1,17 @@
 use chrono::Utc;
 use uuid::Uuid;
 
-use verseguy_api::store::{SledTokenStore, TokenRecord, TokenStore};
+use verseguy_api::store::{Rotation, SledTokenStore, TokenRecord, TokenStore};
+
+fn block_on<F: std::future::Future>(f: F) -> F::Output {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt.block_on(f),
+        Err(e) => panic!("failed to build runtime: {}", e),
+    }
+}
 
 #[test]
 fn sled_persistence_roundtrip() {
@@ -25,10 +35,12 @@ fn sled_persistence_roundtrip() {
     let rec = TokenRecord {
         access_token: "access-x".into(),
         refresh_token: "refresh-x".into(),
-        expires_at: Utc::now(),
+        expires_at: Utc::now() + chrono::Duration::seconds(60),
+        family_id: String::new(),
+        rotated_at: None,
     };
 
-    if s1.insert("refresh-x".into(), rec.clone()).is_err() {
+    if block_on(s1.insert("refresh-x".into(), rec.clone())).is_err() {
         eprintln!("Skipping Sled persistence test: insert failed");
         return;
     }
@@ -42,16 +54,61 @@ fn sled_persistence_roundtrip() {
         Err(e) => panic!("failed to re-open sled store: {:?}", e),
     };
 
-    match s2.get("refresh-x") {
+    match block_on(s2.get("refresh-x")) {
         Ok(Some(g)) => assert_eq!(g.access_token, "access-x"),
         Ok(None) => panic!("token missing after reopen"),
         Err(_) => panic!("store get failed"),
     }
 
     // Cleanup
-    match s2.remove("refresh-x") {
+    match block_on(s2.remove("refresh-x")) {
         Ok(Some(_)) => (),
         Ok(None) => panic!("expected removal"),
         Err(_) => panic!("store remove failed"),
     }
 }
+
+#[test]
+fn sled_rotation_survives_reopen_and_detects_reuse() {
+    let dir = std::env::temp_dir().join(format!("verseguy_tokens_{}", Uuid::new_v4()));
+    let path = match dir.to_str() {
+        Some(p) => p.to_string(),
+        None => panic!("failed to build temp path"),
+    };
+    let record = |refresh: &str| TokenRecord {
+        access_token: format!("access-{}", refresh),
+        refresh_token: refresh.into(),
+        expires_at: Utc::now() + chrono::Duration::seconds(60),
+        family_id: "family-1".into(),
+        rotated_at: None,
+    };
+
+    let s1 = match SledTokenStore::new(&path) {
+        Ok(s) => s,
+        Err(e) => panic!("failed to open sled store: {:?}", e),
+    };
+    if block_on(s1.insert("r1".into(), record("r1"))).is_err() {
+        eprintln!("Skipping Sled rotation test: insert failed");
+        return;
+    }
+    match block_on(s1.rotate("r1", record("r2"))) {
+        Ok(Rotation::Rotated(_)) => (),
+        other => panic!("expected rotation, got {:?}", other),
+    }
+    drop(s1);
+
+    let s2 = match SledTokenStore::new(&path) {
+        Ok(s) => s,
+        Err(e) => panic!("failed to re-open sled store: {:?}", e),
+    };
+    match block_on(s2.rotate("r1", record("r3"))) {
+        Ok(Rotation::Reused(_)) => (),
+        other => panic!("expected reuse after reopen, got {:?}", other),
+    }
+    match block_on(s2.revoke_family("family-1")) {
+        Ok(removed) => assert_eq!(removed.len(), 2),
+        Err(e) => panic!("revoke_family failed: {:?}", e),
+    }
+    assert!(matches!(block_on(s2.get("r2")), Ok(None)));
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,188 @@
+use axum::body::Body;
+use axum::http::StatusCode;
+use serde_json::Value;
+use std::collections::HashMap;
+use tower::util::ServiceExt;
+
+async fn call(app: &axum::Router, req: axum::http::Request<Body>) -> (StatusCode, Value) {
+    let resp = match app.clone().oneshot(req).await {
+        Ok(r) => r,
+        Err(e) => panic!("request failed: {}", e),
+    };
+    let status = resp.status();
+    let bytes = match axum::body::to_bytes(resp.into_body(), 1024 * 1024).await {
+        Ok(b) => b,
+        Err(e) => panic!("failed to read body: {}", e),
+    };
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+fn form(uri: &str, body: String) -> axum::http::Request<Body> {
+    match axum::http::Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from(body))
+    {
+        Ok(r) => r,
+        Err(e) => panic!("failed to build request: {}", e),
+    }
+}
+
+fn protected(token: &str) -> axum::http::Request<Body> {
+    match axum::http::Request::builder()
+        .method("GET")
+        .uri("/protected")
+        .header("authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+    {
+        Ok(r) => r,
+        Err(e) => panic!("failed to build request: {}", e),
+    }
+}
+
+const DEMO_AUTH: &str = "client_id=demo&client_secret=secret";
+
+async fn client_credentials(app: &axum::Router, scope: &str) -> Value {
+    let body = format!(
+        "grant_type=client_credentials&{}&scope={}",
+        DEMO_AUTH, scope
+    );
+    let (status, v) = call(app, form("/oauth/token", body)).await;
+    assert_eq!(status, StatusCode::OK);
+    v
+}
+
+/// Run the authorization_code grant for `demo`, the only grant that issues refresh tokens
+async fn authorization_code(app: &axum::Router) -> Value {
+    let claims =
+        serde_json::json!({ "sub": "user-1", "exp": chrono::Utc::now().timestamp() + 600 });
+    let session = match jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &jsonwebtoken::EncodingKey::from_secret(b"test-session-secret"),
+    ) {
+        Ok(t) => t,
+        Err(e) => panic!("failed to sign session: {}", e),
+    };
+    let uri = "/oauth/authorize?response_type=code&client_id=demo\
+               &redirect_uri=https://example.com/cb&scope=read";
+    let req = match axum::http::Request::builder()
+        .uri(uri)
+        .header("cookie", format!("verseguy_session={}", session))
+        .body(Body::empty())
+    {
+        Ok(r) => r,
+        Err(e) => panic!("failed to build request: {}", e),
+    };
+    let resp = match app.clone().oneshot(req).await {
+        Ok(r) => r,
+        Err(e) => panic!("authorize request failed: {}", e),
+    };
+    let location = match resp.headers().get(axum::http::header::LOCATION) {
+        Some(h) => h.to_str().unwrap_or_default().to_string(),
+        None => panic!("missing location header"),
+    };
+    let query = location.split('?').nth(1).unwrap_or_default();
+    let params: HashMap<String, String> = serde_urlencoded::from_str(query).unwrap_or_default();
+    let code = match params.get("code") {
+        Some(c) => c,
+        None => panic!("no code in {}", location),
+    };
+    let body = format!(
+        "grant_type=authorization_code&code={}&redirect_uri=https://example.com/cb&{}",
+        code, DEMO_AUTH
+    );
+    let (status, v) = call(app, form("/oauth/token", body)).await;
+    assert_eq!(status, StatusCode::OK);
+    v
+}
+
+fn field(v: &Value, name: &str) -> String {
+    match v.get(name).and_then(|t| t.as_str()) {
+        Some(s) => s.to_string(),
+        None => panic!("missing {} in {:?}", name, v),
+    }
+}
+
+#[test]
+fn jwt_access_tokens_introspection_and_revocation() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        std::env::set_var("VERSEGUY_SESSION_SECRET", "test-session-secret");
+        let app = verseguy_api::build_app();
+
+        let v = client_credentials(&app, "read").await;
+        let access = field(&v, "access_token");
+        let refresh = field(&authorization_code(&app).await, "refresh_token");
+        assert_eq!(access.split('.').count(), 3, "access token should be a JWT");
+
+        // Arbitrary bearer values are no longer accepted
+        let (status, _) = call(&app, protected("not-a-token")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = call(&app, protected(&access)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Tokens without the required scope are forbidden
+        let write_only = field(&client_credentials(&app, "write").await, "access_token");
+        let (status, _) = call(&app, protected(&write_only)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Introspection requires client authentication
+        let (status, _) = call(&app, form("/oauth/introspect", format!("token={}", access))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let body = format!("token={}&{}", access, DEMO_AUTH);
+        let (status, v) = call(&app, form("/oauth/introspect", body.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(v.get("active").and_then(|a| a.as_bool()), Some(true));
+        assert_eq!(field(&v, "scope"), "read");
+        assert_eq!(field(&v, "client_id"), "demo");
+
+        let refresh_body = format!(
+            "token={}&token_type_hint=refresh_token&{}",
+            refresh, DEMO_AUTH
+        );
+        let (_, v) = call(&app, form("/oauth/introspect", refresh_body.clone())).await;
+        assert_eq!(field(&v, "token_type"), "refresh_token");
+
+        // Revoke the access token
+        let (status, _) = call(&app, form("/oauth/revoke", body.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(&app, protected(&access)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (_, v) = call(&app, form("/oauth/introspect", body)).await;
+        assert_eq!(v.get("active").and_then(|a| a.as_bool()), Some(false));
+
+        // Revoking the refresh token makes it unusable
+        let (status, _) = call(&app, form("/oauth/revoke", refresh_body.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(
+            &app,
+            form(
+                "/oauth/token",
+                format!(
+                    "grant_type=refresh_token&refresh_token={}&{}",
+                    refresh, DEMO_AUTH
+                ),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Unknown tokens are accepted silently
+        let body = format!("token=unknown&{}", DEMO_AUTH);
+        let (status, _) = call(&app, form("/oauth/revoke", body)).await;
+        assert_eq!(status, StatusCode::OK);
+    });
+}
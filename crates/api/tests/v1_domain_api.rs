@@ -0,0 +1,423 @@
+use axum::body::Body;
+use axum::http::{header, StatusCode};
+use base64::Engine;
+use serde_json::Value;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+use uuid::Uuid;
+use verseguy_api::clients::{hash_secret, ClientStore, InMemoryClientStore, OAuthClient};
+use verseguy_api::store::{InMemoryTokenStore, TokenStore};
+use verseguy_api::v1::DomainServices;
+use verseguy_storage::Storage;
+
+async fn send(
+    app: &axum::Router,
+    req: axum::http::request::Builder,
+    body: Body,
+) -> (StatusCode, axum::http::HeaderMap, Value) {
+    let req = match req.body(body) {
+        Ok(r) => r,
+        Err(e) => panic!("failed to build request: {}", e),
+    };
+    let resp = match app.clone().oneshot(req).await {
+        Ok(r) => r,
+        Err(e) => panic!("request failed: {}", e),
+    };
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let bytes = match axum::body::to_bytes(resp.into_body(), 1024 * 1024).await {
+        Ok(b) => b,
+        Err(e) => panic!("failed to read body: {}", e),
+    };
+    (
+        status,
+        headers,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+async fn token(app: &axum::Router, scope: &str) -> String {
+    token_as(app, "demo:secret", scope).await
+}
+
+/// Access token of the client authenticating with `credentials` (`id:secret`)
+async fn token_as(app: &axum::Router, credentials: &str, scope: &str) -> String {
+    let basic = base64::engine::general_purpose::STANDARD.encode(credentials);
+    let req = axum::http::Request::builder()
+        .method("POST")
+        .uri("/oauth/token")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .header("authorization", format!("Basic {}", basic));
+    let body = Body::from(format!("grant_type=client_credentials&scope={}", scope));
+    let (status, _, v) = send(app, req, body).await;
+    assert_eq!(status, StatusCode::OK);
+    match v.get("access_token").and_then(|t| t.as_str()) {
+        Some(t) => t.to_string(),
+        None => panic!("missing access_token: {:?}", v),
+    }
+}
+
+fn request(method: &str, uri: &str, token: &str) -> axum::http::request::Builder {
+    axum::http::Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/json")
+}
+
+fn etag_of(headers: &axum::http::HeaderMap) -> String {
+    match headers.get(header::ETAG).and_then(|v| v.to_str().ok()) {
+        Some(t) => t.to_string(),
+        None => panic!("missing ETag"),
+    }
+}
+
+fn str_field(v: &Value, field: &str) -> String {
+    match v.get(field).and_then(|f| f.as_str()) {
+        Some(s) => s.to_string(),
+        None => panic!("missing {}: {:?}", field, v),
+    }
+}
+
+fn json(v: Value) -> Body {
+    Body::from(v.to_string())
+}
+
+#[test]
+fn members_ranks_ships_and_operations_over_v1() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let dir = std::env::temp_dir().join(format!("verseguy_v1_{}", Uuid::new_v4()));
+        let storage = match Storage::open(&dir) {
+            Ok(s) => s,
+            Err(e) => panic!("failed to open storage: {}", e),
+        };
+        let tokens: Arc<dyn TokenStore> = Arc::new(InMemoryTokenStore::new());
+        let clients: Arc<dyn ClientStore> = Arc::new(InMemoryClientStore::with_demo_client());
+        let domain = Arc::new(DomainServices::new(storage));
+        std::env::set_var("VERSEGUY_API_JWT_SECRET", "test-jwt-secret");
+        let app = match verseguy_api::build_app_with_domain(tokens, clients, domain) {
+            Ok(app) => app,
+            Err(e) => panic!("failed to build app: {:?}", e),
+        };
+
+        let write = token(&app, "read%20write").await;
+        let read = token(&app, "read").await;
+
+        // scopes: reads need `read`, writes need `write`
+        let (status, _, _) = send(&app, request("GET", "/v1/orgs", "bogus"), Body::empty()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let body = json(serde_json::json!({ "name": "Test Squadron", "tag": "TEST" }));
+        let (status, _, v) = send(&app, request("POST", "/v1/orgs", &read), body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(str_field(&v, "code"), "insufficient_scope");
+
+        let body = json(serde_json::json!({ "name": "Test Squadron", "tag": "TEST" }));
+        let (status, headers, org) = send(&app, request("POST", "/v1/orgs", &write), body).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let org_id = str_field(&org, "id");
+        let base = format!("/v1/orgs/{}", org_id);
+        assert_eq!(
+            headers.get(header::LOCATION).and_then(|l| l.to_str().ok()),
+            Some(base.as_str())
+        );
+
+        // unknown organizations and bad input get JSON errors
+        let req = request("GET", "/v1/orgs/nope/members", &read);
+        let (status, _, v) = send(&app, req, Body::empty()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(str_field(&v, "code"), "not_found");
+        let uri = format!("{}/members?limit=0", base);
+        let (status, _, v) = send(&app, request("GET", &uri, &read), Body::empty()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(str_field(&v, "code"), "invalid_request");
+        let uri = format!("{}/ranks", base);
+        let (status, _, v) = send(&app, request("POST", &uri, &write), Body::from("{")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(str_field(&v, "code"), "invalid_request");
+
+        // ranks
+        let body = json(serde_json::json!({
+            "name": "Officer", "level": 50, "permissions": ["ManageMembers"]
+        }));
+        let (status, _, rank) = send(&app, request("POST", &uri, &write), body).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let rank_id = str_field(&rank, "id");
+        let uri = format!("{}/ranks?permission=manage_members", base);
+        let (_, _, v) = send(&app, request("GET", &uri, &read), Body::empty()).await;
+        assert_eq!(v.get("total").and_then(|t| t.as_u64()), Some(1));
+
+        // members must hold a rank of the organization and join only once
+        let members = format!("{}/members", base);
+        let body = json(serde_json::json!({
+            "user_id": "u0", "handle": "ghost", "rank_id": "missing"
+        }));
+        let (status, _, _) = send(&app, request("POST", &members, &write), body).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        for (user, handle) in [("u1", "alpha"), ("u2", "bravo"), ("u3", "charlie")] {
+            let body = json(serde_json::json!({
+                "user_id": user, "handle": handle, "rank_id": rank_id
+            }));
+            let (status, _, _) = send(&app, request("POST", &members, &write), body).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+        let body = json(serde_json::json!({
+            "user_id": "u1", "handle": "alpha", "rank_id": rank_id
+        }));
+        let (status, _, v) = send(&app, request("POST", &members, &write), body).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(str_field(&v, "code"), "conflict");
+
+        // pagination and filtering
+        let uri = format!("{}?limit=2", members);
+        let (status, _, page) = send(&app, request("GET", &uri, &read), Body::empty()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page.get("total").and_then(|t| t.as_u64()), Some(3));
+        assert_eq!(page.get("next_offset").and_then(|t| t.as_u64()), Some(2));
+        assert_eq!(
+            page.get("items")
+                .and_then(|i| i.as_array())
+                .map(|i| i.len()),
+            Some(2)
+        );
+        let uri = format!("{}?limit=2&offset=2", members);
+        let (_, _, page) = send(&app, request("GET", &uri, &read), Body::empty()).await;
+        assert!(page.get("next_offset").is_none());
+        let uri = format!("{}?handle=BRA", members);
+        let (_, _, page) = send(&app, request("GET", &uri, &read), Body::empty()).await;
+        assert_eq!(page.get("total").and_then(|t| t.as_u64()), Some(1));
+
+        // ETag / If-None-Match / If-Match
+        let member = format!("{}/u2", members);
+        let (status, headers, _) = send(&app, request("GET", &member, &read), Body::empty()).await;
+        assert_eq!(status, StatusCode::OK);
+        let tag = etag_of(&headers);
+        let req = request("GET", &member, &read).header(header::IF_NONE_MATCH, tag.as_str());
+        let (status, _, _) = send(&app, req, Body::empty()).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+        let patch = serde_json::json!({ "handle": "bravo-two" });
+        let req = request("PATCH", &member, &write);
+        let (status, _, v) = send(&app, req, json(patch.clone())).await;
+        assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+        assert_eq!(str_field(&v, "code"), "precondition_required");
+        let req = request("PATCH", &member, &write).header(header::IF_MATCH, tag.as_str());
+        let (status, headers, v) = send(&app, req, json(patch.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(str_field(&v, "handle"), "bravo-two");
+        assert_ne!(etag_of(&headers), tag);
+        // a second writer holding the old tag loses
+        let req = request("PATCH", &member, &write).header(header::IF_MATCH, tag.as_str());
+        let (status, _, v) = send(&app, req, json(patch)).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        assert_eq!(str_field(&v, "code"), "precondition_failed");
+
+        // ranks in use cannot be deleted
+        let rank_uri = format!("{}/ranks/{}", base, rank_id);
+        let req = request("DELETE", &rank_uri, &write).header(header::IF_MATCH, "*");
+        let (status, _, _) = send(&app, req, Body::empty()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        // ships belong to members
+        let ships = format!("{}/ships", base);
+        let body = json(serde_json::json!({
+            "owner_id": "stranger", "model": "Carrack", "manufacturer": "Anvil Aerospace"
+        }));
+        let (status, _, _) = send(&app, request("POST", &ships, &write), body).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let body = json(serde_json::json!({
+            "owner_id": "u1", "model": "Carrack", "manufacturer": "Anvil Aerospace"
+        }));
+        let (status, headers, ship) = send(&app, request("POST", &ships, &write), body).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let ship_uri = format!("{}/{}", ships, str_field(&ship, "id"));
+        let (_, get_headers, _) = send(&app, request("GET", &ship_uri, &read), Body::empty()).await;
+        assert_eq!(etag_of(&headers), etag_of(&get_headers));
+        let uri = format!("{}?status=Maintenance", ships);
+        let (_, _, page) = send(&app, request("GET", &uri, &read), Body::empty()).await;
+        assert_eq!(page.get("total").and_then(|t| t.as_u64()), Some(0));
+        let uri = format!("{}?manufacturer=anvil", ships);
+        let (_, _, page) = send(&app, request("GET", &uri, &read), Body::empty()).await;
+        assert_eq!(page.get("total").and_then(|t| t.as_u64()), Some(1));
+
+        // operations and participants
+        let ops = format!("{}/operations", base);
+        let body = json(serde_json::json!({
+            "title": "Mining run",
+            "operation_type": "Mining",
+            "scheduled_at": "2030-01-01T18:00:00Z",
+            "duration_minutes": 90
+        }));
+        let (status, _, op) = send(&app, request("POST", &ops, &write), body).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(str_field(&op, "leader_id"), "demo");
+        let op_uri = format!("{}/{}", ops, str_field(&op, "id"));
+        let uri = format!("{}?type=Combat", ops);
+        let (_, _, page) = send(&app, request("GET", &uri, &read), Body::empty()).await;
+        assert_eq!(page.get("total").and_then(|t| t.as_u64()), Some(0));
+        let uri = format!("{}?status=Planned&from=2029-12-31T00:00:00Z", ops);
+        let (_, _, page) = send(&app, request("GET", &uri, &read), Body::empty()).await;
+        assert_eq!(page.get("total").and_then(|t| t.as_u64()), Some(1));
+
+        let participants = format!("{}/participants", op_uri);
+        let body = json(serde_json::json!({ "user_id": "u3", "role": "Pilot" }));
+        let (status, headers, _) = send(&app, request("POST", &participants, &write), body).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let body = json(serde_json::json!({ "user_id": "u3", "role": "Gunner" }));
+        let (status, _, _) = send(&app, request("POST", &participants, &write), body).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let participant = format!("{}/u3", participants);
+        let req =
+            request("PATCH", &participant, &write).header(header::IF_MATCH, etag_of(&headers));
+        let body = json(serde_json::json!({ "confirmed": true }));
+        let (status, _, v) = send(&app, req, body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(v.get("confirmed").and_then(|c| c.as_bool()), Some(true));
+        let uri = format!("{}?confirmed=true", participants);
+        let (_, _, page) = send(&app, request("GET", &uri, &read), Body::empty()).await;
+        assert_eq!(page.get("total").and_then(|t| t.as_u64()), Some(1));
+
+        // the operation's ETag changed with the participant update
+        let (_, headers, _) = send(&app, request("GET", &op_uri, &read), Body::empty()).await;
+        let req = request("DELETE", &op_uri, &write).header(header::IF_MATCH, etag_of(&headers));
+        let (status, _, _) = send(&app, req, Body::empty()).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _, _) = send(&app, request("GET", &op_uri, &read), Body::empty()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    });
+}
+
+#[test]
+fn organizations_are_isolated_between_tenants() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let dir = std::env::temp_dir().join(format!("verseguy_v1_{}", Uuid::new_v4()));
+        let storage = match Storage::open(&dir) {
+            Ok(s) => s,
+            Err(e) => panic!("failed to open storage: {}", e),
+        };
+        // a second tenant: tokens of client `other` have `other` as their subject
+        let clients = InMemoryClientStore::with_demo_client();
+        let demo = match clients.get("demo") {
+            Ok(Some(c)) => c,
+            _ => panic!("demo client missing"),
+        };
+        let other = OAuthClient {
+            client_id: "other".into(),
+            secret_hash: Some(hash_secret("other-secret")),
+            ..demo
+        };
+        if let Err(e) = clients.insert(other) {
+            panic!("failed to register client: {:?}", e);
+        }
+        let tokens: Arc<dyn TokenStore> = Arc::new(InMemoryTokenStore::new());
+        let domain = Arc::new(DomainServices::new(storage));
+        std::env::set_var("VERSEGUY_API_JWT_SECRET", "test-jwt-secret");
+        let app = match verseguy_api::build_app_with_domain(tokens, Arc::new(clients), domain) {
+            Ok(app) => app,
+            Err(e) => panic!("failed to build app: {:?}", e),
+        };
+
+        let owner = token(&app, "read%20write").await;
+        let stranger = token_as(&app, "other:other-secret", "read%20write").await;
+
+        let body = json(serde_json::json!({ "name": "Test Squadron", "tag": "TEST" }));
+        let (_, _, org) = send(&app, request("POST", "/v1/orgs", &owner), body).await;
+        let base = format!("/v1/orgs/{}", str_field(&org, "id"));
+        let ranks = format!("{}/ranks", base);
+        let body = json(serde_json::json!({
+            "name": "Pilot", "level": 10, "permissions": ["ManageFleet"]
+        }));
+        let (status, _, rank) = send(&app, request("POST", &ranks, &owner), body).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        // other tenants can neither see nor change the organization
+        let (_, _, page) = send(&app, request("GET", "/v1/orgs", &stranger), Body::empty()).await;
+        assert_eq!(page.get("total").and_then(|t| t.as_u64()), Some(0));
+        let members = format!("{}/members", base);
+        for uri in [&base, &members, &ranks] {
+            let (status, _, v) = send(&app, request("GET", uri, &stranger), Body::empty()).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "GET {}", uri);
+            assert_eq!(str_field(&v, "code"), "forbidden");
+        }
+        let req = request("PATCH", &base, &stranger).header(header::IF_MATCH, "*");
+        let body = json(serde_json::json!({ "name": "Hijacked" }));
+        let (status, _, _) = send(&app, req, body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let body = json(serde_json::json!({
+            "user_id": "other", "handle": "intruder", "rank_id": str_field(&rank, "id")
+        }));
+        let (status, _, _) = send(&app, request("POST", &members, &stranger), body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let ops = format!("{}/operations", base);
+        let body = json(serde_json::json!({
+            "title": "Raid",
+            "operation_type": "Combat",
+            "scheduled_at": "2030-01-01T18:00:00Z",
+            "duration_minutes": 60
+        }));
+        let (status, _, _) = send(&app, request("POST", &ops, &stranger), body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let req = request("GET", "/v1/orgs/nope", &stranger);
+        let (status, _, _) = send(&app, req, Body::empty()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // members read everything and write what their rank permits
+        let body = json(serde_json::json!({
+            "user_id": "other", "handle": "wingman", "rank_id": str_field(&rank, "id")
+        }));
+        let (status, _, _) = send(&app, request("POST", &members, &owner), body).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (_, _, page) = send(&app, request("GET", "/v1/orgs", &stranger), Body::empty()).await;
+        assert_eq!(page.get("total").and_then(|t| t.as_u64()), Some(1));
+        let (status, _, _) = send(&app, request("GET", &members, &stranger), Body::empty()).await;
+        assert_eq!(status, StatusCode::OK);
+        let body = json(serde_json::json!({
+            "owner_id": "other", "model": "Cutlass Black", "manufacturer": "Drake Interplanetary"
+        }));
+        let ships = format!("{}/ships", base);
+        let (status, _, _) = send(&app, request("POST", &ships, &stranger), body).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let body = json(serde_json::json!({ "name": "Admiral", "level": 99, "permissions": [] }));
+        let (status, _, v) = send(&app, request("POST", &ranks, &stranger), body).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(str_field(&v, "code"), "forbidden");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    });
+}
+
+#[test]
+fn v1_routes_are_absent_without_domain_services() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let app = verseguy_api::build_app();
+        let req = axum::http::Request::builder().method("GET").uri("/v1/orgs");
+        let (status, _, _) = send(&app, req, Body::empty()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    });
+}
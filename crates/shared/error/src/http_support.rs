@@ -0,0 +1,265 @@
+//! axum integration: [`AppError`] renders as an `application/problem+json` response, and the
+//! [`request_id`] middleware tags every response with `x-request-id` and completes problem
+//! documents with the request id and path.
+
+use crate::{AppError, ProblemDetails, PROBLEM_JSON};
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Largest plain-text error body that is carried over into a problem document
+const MAX_TEXT_BODY: usize = 64 * 1024;
+
+/// Id of the current request, inserted as a request extension by [`request_id`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        if self.status().is_server_error() {
+            tracing::error!(code = self.code(), "{}", self);
+        }
+        self.to_problem().into_response()
+    }
+}
+
+impl IntoResponse for ProblemDetails {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut resp = (
+            status,
+            [(header::CONTENT_TYPE, PROBLEM_JSON)],
+            render(&self),
+        )
+            .into_response();
+        resp.extensions_mut().insert(self);
+        resp
+    }
+}
+
+fn render(problem: &ProblemDetails) -> String {
+    serde_json::to_string(problem).unwrap_or_default()
+}
+
+/// Middleware assigning a request id (the caller's `x-request-id` when it is well-formed, a
+/// fresh UUID otherwise).
+///
+/// Problem documents get `request_id` and `instance` filled in. Error responses that are not
+/// problem documents yet, like axum's plain-text extractor rejections, are converted so that
+/// every error has the same shape; JSON and HTML error bodies are left alone.
+pub async fn request_id(mut req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| is_valid_request_id(v))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let path = req.uri().path().to_string();
+    req.extensions_mut().insert(RequestId(id.clone()));
+
+    let mut resp = complete_problem(next.run(req).await, &id, &path).await;
+    if let Ok(v) = HeaderValue::from_str(&id) {
+        resp.headers_mut().insert(REQUEST_ID_HEADER, v);
+    }
+    resp
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b))
+}
+
+async fn complete_problem(resp: Response, id: &str, path: &str) -> Response {
+    let status = resp.status();
+    let (mut parts, body) = resp.into_parts();
+    let mut problem = match parts.extensions.remove::<ProblemDetails>() {
+        Some(p) => p,
+        None if is_unshaped_error(status, &parts.headers) => {
+            let text = match axum::body::to_bytes(body, MAX_TEXT_BODY).await {
+                Ok(b) if status.is_client_error() => String::from_utf8_lossy(&b).trim().to_string(),
+                _ => String::new(),
+            };
+            let detail = if text.is_empty() {
+                status.canonical_reason().unwrap_or("error").to_lowercase()
+            } else {
+                text
+            };
+            ProblemDetails::new(status, code_for_status(status), detail)
+        }
+        None => return Response::from_parts(parts, body),
+    };
+    problem.request_id = Some(id.to_string());
+    problem.instance = Some(path.to_string());
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    let body = Body::from(render(&problem));
+    parts.extensions.insert(problem);
+    Response::from_parts(parts, body)
+}
+
+/// Error responses with an empty or plain-text body
+fn is_unshaped_error(status: StatusCode, headers: &axum::http::HeaderMap) -> bool {
+    if !(status.is_client_error() || status.is_server_error()) {
+        return false;
+    }
+    match headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+    {
+        None => true,
+        Some(ct) => ct.starts_with("text/plain"),
+    }
+}
+
+fn code_for_status(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "invalid_request",
+        StatusCode::UNAUTHORIZED => "unauthorized",
+        StatusCode::FORBIDDEN => "forbidden",
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+        StatusCode::CONFLICT => "conflict",
+        StatusCode::PRECONDITION_FAILED => "precondition_failed",
+        StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+        StatusCode::UNPROCESSABLE_ENTITY => "validation_failed",
+        StatusCode::PRECONDITION_REQUIRED => "precondition_required",
+        StatusCode::TOO_MANY_REQUESTS => "rate_limited",
+        StatusCode::NOT_IMPLEMENTED => "not_implemented",
+        s if s.is_server_error() => "internal_error",
+        _ => "client_error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::util::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/missing",
+                get(|| async { AppError::NotFound("widget not found".into()) }),
+            )
+            .route(
+                "/broken",
+                get(|| async { AppError::internal("disk /var/lib/db is full") }),
+            )
+            .route(
+                "/text",
+                get(|| async { (StatusCode::BAD_REQUEST, "bad input") }),
+            )
+            .route("/ok", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn(request_id))
+    }
+
+    async fn call(uri: &str, id: Option<&str>) -> (Response, Option<ProblemDetails>) {
+        let mut req = Request::builder().uri(uri);
+        if let Some(id) = id {
+            req = req.header(REQUEST_ID_HEADER, id);
+        }
+        let req = match req.body(Body::empty()) {
+            Ok(r) => r,
+            Err(e) => panic!("failed to build request: {}", e),
+        };
+        let resp = match app().oneshot(req).await {
+            Ok(r) => r,
+            Err(e) => panic!("request failed: {}", e),
+        };
+        let (parts, body) = resp.into_parts();
+        let bytes = match axum::body::to_bytes(body, MAX_TEXT_BODY).await {
+            Ok(b) => b,
+            Err(e) => panic!("failed to read body: {}", e),
+        };
+        let problem = serde_json::from_slice(&bytes).ok();
+        (Response::from_parts(parts, Body::empty()), problem)
+    }
+
+    fn header<'a>(resp: &'a Response, name: &str) -> &'a str {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        // `#[tokio::test]` expands to `Runtime::build().expect(..)`, which clippy.toml disallows
+        match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(rt) => rt.block_on(future),
+            Err(e) => panic!("failed to build runtime: {}", e),
+        }
+    }
+
+    #[test]
+    fn errors_are_problem_documents_with_request_id() {
+        block_on(async {
+            let (resp, problem) = call("/missing", Some("req-1")).await;
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            assert_eq!(header(&resp, "content-type"), PROBLEM_JSON);
+            assert_eq!(header(&resp, REQUEST_ID_HEADER), "req-1");
+            let problem = match problem {
+                Some(p) => p,
+                None => panic!("body is not a problem document"),
+            };
+            assert_eq!(problem.code, "not_found");
+            assert_eq!(problem.detail, "widget not found");
+            assert_eq!(problem.request_id.as_deref(), Some("req-1"));
+            assert_eq!(problem.instance.as_deref(), Some("/missing"));
+        });
+    }
+
+    #[test]
+    fn internal_details_are_not_leaked() {
+        block_on(async {
+            let (resp, problem) = call("/broken", None).await;
+            assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            let problem = match problem {
+                Some(p) => p,
+                None => panic!("body is not a problem document"),
+            };
+            assert_eq!(problem.code, "internal_error");
+            assert!(!problem.detail.contains("/var/lib"));
+            assert_eq!(
+                problem.request_id.as_deref(),
+                Some(header(&resp, REQUEST_ID_HEADER))
+            );
+        });
+    }
+
+    #[test]
+    fn plain_text_errors_and_bad_ids_are_normalized() {
+        block_on(async {
+            let (resp, problem) = call("/text", Some("bad id with spaces")).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            assert_ne!(header(&resp, REQUEST_ID_HEADER), "bad id with spaces");
+            let problem = match problem {
+                Some(p) => p,
+                None => panic!("body is not a problem document"),
+            };
+            assert_eq!(problem.code, "invalid_request");
+            assert_eq!(problem.detail, "bad input");
+
+            let (resp, problem) = call("/ok", None).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert!(!header(&resp, REQUEST_ID_HEADER).is_empty());
+            assert!(problem.is_none());
+        });
+    }
+}
@@ -1,13 +1,50 @@
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+#[cfg(feature = "axum")]
+pub mod http_support;
+
+#[cfg(feature = "axum")]
+pub use http_support::{request_id, RequestId, REQUEST_ID_HEADER};
+
+/// Media type of RFC 7807 problem documents
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Application error shared by the HTTP services.
+///
+/// Every variant maps to an HTTP status and a stable, machine-readable code (see
+/// [`AppError::code`]). Messages of server-side variants (`Io`, `External`, `Other`) are logged
+/// but never sent to clients.
 #[derive(Error, Debug)]
 pub enum AppError {
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
     #[error("Not found: {0}")]
     NotFound(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+
     #[error("Validation failed: {0}")]
     Validation(String),
 
+    #[error("Precondition required: {0}")]
+    PreconditionRequired(String),
+
+    #[error("Not implemented: {0}")]
+    NotImplemented(String),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -16,6 +53,14 @@ pub enum AppError {
 
     #[error(transparent)]
     Other(#[from] anyhow::Error),
+
+    /// Client error with a protocol-defined code, e.g. OAuth 2.0's `invalid_grant`
+    #[error("{code}: {detail}")]
+    Coded {
+        status: StatusCode,
+        code: &'static str,
+        detail: String,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, AppError>;
@@ -24,6 +69,111 @@ impl AppError {
     pub fn context<T>(self, _msg: &str) -> anyhow::Error {
         anyhow::anyhow!("{:?}", self)
     }
+
+    /// Server-side failure; `e` is logged, clients only see a generic message
+    pub fn internal(e: impl std::fmt::Display) -> Self {
+        AppError::Other(anyhow::anyhow!("{}", e))
+    }
+
+    pub fn coded(status: StatusCode, code: &'static str, detail: impl Into<String>) -> Self {
+        AppError::Coded {
+            status,
+            code,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::PreconditionRequired(_) => StatusCode::PRECONDITION_REQUIRED,
+            AppError::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            AppError::Io(_) | AppError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::External(_) => StatusCode::BAD_GATEWAY,
+            AppError::Coded { status, .. } => *status,
+        }
+    }
+
+    /// Stable machine-readable code; clients should branch on this, not on `detail`
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "invalid_request",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::PreconditionFailed(_) => "precondition_failed",
+            AppError::Validation(_) => "validation_failed",
+            AppError::PreconditionRequired(_) => "precondition_required",
+            AppError::NotImplemented(_) => "not_implemented",
+            AppError::Io(_) | AppError::Other(_) => "internal_error",
+            AppError::External(_) => "upstream_error",
+            AppError::Coded { code, .. } => code,
+        }
+    }
+
+    /// Message safe to show to clients
+    pub fn detail(&self) -> String {
+        match self {
+            AppError::BadRequest(m)
+            | AppError::Unauthorized(m)
+            | AppError::Forbidden(m)
+            | AppError::NotFound(m)
+            | AppError::Conflict(m)
+            | AppError::PreconditionFailed(m)
+            | AppError::Validation(m)
+            | AppError::PreconditionRequired(m)
+            | AppError::NotImplemented(m) => m.clone(),
+            AppError::Io(_) | AppError::Other(_) => "internal server error".to_string(),
+            AppError::External(_) => "upstream service unavailable".to_string(),
+            AppError::Coded { detail, .. } => detail.clone(),
+        }
+    }
+
+    pub fn to_problem(&self) -> ProblemDetails {
+        ProblemDetails::new(self.status(), self.code(), self.detail())
+    }
+}
+
+/// RFC 7807 problem document, extended with `code` and `request_id`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProblemDetails {
+    /// `urn:verseguy:error:<code>`
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// Reason phrase of `status`
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    /// Path of the request that failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Stable machine-readable error code
+    pub code: String,
+    /// Value of the `x-request-id` response header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ProblemDetails {
+    pub fn new(status: StatusCode, code: &str, detail: impl Into<String>) -> Self {
+        ProblemDetails {
+            problem_type: format!("urn:verseguy:error:{}", code),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            instance: None,
+            code: code.to_string(),
+            request_id: None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -35,4 +185,23 @@ mod tests {
         let e = AppError::NotFound("user/1".into());
         assert!(format!("{}", e).contains("Not found"));
     }
+
+    #[test]
+    fn internal_errors_hide_their_message() {
+        let e = AppError::internal("sled: corrupted page 42");
+        let p = e.to_problem();
+        assert_eq!(p.status, 500);
+        assert_eq!(p.code, "internal_error");
+        assert!(!p.detail.contains("sled"));
+    }
+
+    #[test]
+    fn coded_errors_keep_their_code() {
+        let e = AppError::coded(StatusCode::UNAUTHORIZED, "invalid_client", "unknown client");
+        let p = e.to_problem();
+        assert_eq!(p.status, 401);
+        assert_eq!(p.title, "Unauthorized");
+        assert_eq!(p.problem_type, "urn:verseguy:error:invalid_client");
+        assert_eq!(p.detail, "unknown client");
+    }
 }
@@ -0,0 +1,418 @@
+use axum::extract::{ConnectInfo, Request};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower_layer::Layer;
+use tower_service::Service;
+use verseguy_shared_error::AppError;
+
+use crate::{Decision, KeyBy, Policy, RateLimitStore, Subject};
+
+const RATELIMIT_LIMIT: &str = "ratelimit-limit";
+const RATELIMIT_REMAINING: &str = "ratelimit-remaining";
+const RATELIMIT_RESET: &str = "ratelimit-reset";
+
+type Resolver = Arc<dyn Fn(&HeaderMap) -> Subject + Send + Sync>;
+
+/// Tower layer enforcing a [`Policy`].
+///
+/// Allowed responses carry `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset`
+/// (the innermost layer's values when layers are nested); rejected requests get a
+/// `rate_limited` problem document with `Retry-After`. When the store fails the request is let
+/// through and the failure logged, so an unreachable Redis does not take the service down.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    policy: Arc<Policy>,
+    store: Arc<dyn RateLimitStore>,
+    resolver: Option<Resolver>,
+    trusted_proxies: usize,
+}
+
+impl RateLimitLayer {
+    pub fn new(policy: Policy, store: Arc<dyn RateLimitStore>) -> Self {
+        RateLimitLayer {
+            policy: Arc::new(policy),
+            store,
+            resolver: None,
+            trusted_proxies: 0,
+        }
+    }
+
+    /// Resolve user, OAuth client and license tier from the request headers. Without a
+    /// resolver `KeyBy::User` falls back to the client IP and tier limits never apply.
+    pub fn resolve_subject<F>(mut self, resolver: F) -> Self
+    where
+        F: Fn(&HeaderMap) -> Subject + Send + Sync + 'static,
+    {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Take the client IP from `X-Forwarded-For` instead of the peer address when the service
+    /// runs behind `hops` proxies that each append the address they got the request from. The
+    /// client is the entry `hops` places from the right; anything left of it came from the
+    /// client and is ignored.
+    pub fn trusted_proxies(mut self, hops: usize) -> Self {
+        self.trusted_proxies = hops;
+        self
+    }
+
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    fn key(&self, req: &Request, subject: &Subject) -> String {
+        let id = match self.policy.key {
+            KeyBy::Ip => None,
+            KeyBy::User => subject.user_id.clone(),
+            KeyBy::ApiToken => bearer_hash(req.headers()),
+            // client ids read from the request are unauthenticated, so they are counted per
+            // client IP too; otherwise anyone could use up another client's bucket
+            KeyBy::OAuthClient => subject.client_id.clone().or_else(|| {
+                basic_client_id(req.headers())
+                    .or_else(|| query_client_id(req.uri().query()))
+                    .map(|id| format!("{}@{}", id, self.client_ip(req)))
+            }),
+        };
+        match id {
+            Some(id) => format!("{}:{}:{}", self.policy.name, self.policy.key.as_str(), id),
+            None => format!("{}:ip:{}", self.policy.name, self.client_ip(req)),
+        }
+    }
+
+    fn client_ip(&self, req: &Request) -> String {
+        let hops: Vec<&str> = req
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .collect();
+        let forwarded = hops
+            .len()
+            .checked_sub(self.trusted_proxies)
+            .filter(|_| self.trusted_proxies > 0)
+            .and_then(|i| hops.get(i))
+            .filter(|ip| !ip.is_empty());
+        match forwarded {
+            Some(ip) => ip.to_string(),
+            None => req
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+                .unwrap_or_else(|| "unknown".into()),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    layer: RateLimitLayer,
+}
+
+impl<S> Service<Request> for RateLimitService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        // the clone is not ready yet, so call the instance `poll_ready` was called on
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+        Box::pin(async move {
+            // nested layers resolve the caller once
+            let subject = match (req.extensions().get::<Subject>(), &layer.resolver) {
+                (Some(subject), _) => subject.clone(),
+                (None, Some(resolve)) => {
+                    let subject = resolve(req.headers());
+                    req.extensions_mut().insert(subject.clone());
+                    subject
+                }
+                (None, None) => Subject::default(),
+            };
+            let limit = layer.policy.limit_for(subject.tier.as_deref());
+            let key = layer.key(&req, &subject);
+            match layer.store.acquire(&key, &limit).await {
+                Ok(decision) if !decision.allowed => {
+                    tracing::info!(policy = %layer.policy.name, key = %key, "rate limited");
+                    Ok(too_many_requests(&decision))
+                }
+                Ok(decision) => {
+                    let mut resp = inner.call(req).await?;
+                    set_headers(resp.headers_mut(), &decision);
+                    Ok(resp)
+                }
+                Err(e) => {
+                    tracing::warn!(policy = %layer.policy.name, "rate limit skipped: {}", e);
+                    inner.call(req).await
+                }
+            }
+        })
+    }
+}
+
+/// Whole seconds, rounded up, as HTTP headers count them
+fn header_secs(d: Duration) -> u64 {
+    d.as_millis().div_ceil(1000) as u64
+}
+
+fn set_headers(headers: &mut HeaderMap, decision: &Decision) {
+    let values = [
+        (RATELIMIT_LIMIT, u64::from(decision.limit)),
+        (RATELIMIT_REMAINING, u64::from(decision.remaining)),
+        (RATELIMIT_RESET, header_secs(decision.reset_after)),
+    ];
+    for (name, value) in values {
+        if !headers.contains_key(name) {
+            headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+        }
+    }
+}
+
+fn too_many_requests(decision: &Decision) -> Response {
+    let retry_after = header_secs(decision.retry_after).max(1);
+    let problem = AppError::coded(
+        StatusCode::TOO_MANY_REQUESTS,
+        "rate_limited",
+        format!("too many requests, retry in {} seconds", retry_after),
+    );
+    let mut resp = problem.into_response();
+    let headers = resp.headers_mut();
+    headers.insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    set_headers(headers, decision);
+    resp
+}
+
+fn bearer_hash(headers: &HeaderMap) -> Option<String> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+        .map(|(_, token)| token.trim())
+        .filter(|token| !token.is_empty())?;
+    let digest = Sha256::digest(token.as_bytes());
+    Some(digest[..16].iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn basic_client_id(headers: &HeaderMap) -> Option<String> {
+    let encoded = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("basic"))
+        .map(|(_, creds)| creds.trim())?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .ok()?;
+    let pair = String::from_utf8(decoded).ok()?;
+    pair.split_once(':')
+        .map(|(id, _)| id.to_string())
+        .filter(|id| !id.is_empty())
+}
+
+fn query_client_id(query: Option<&str>) -> Option<String> {
+    query?
+        .split('&')
+        .filter_map(|pair| pair.strip_prefix("client_id="))
+        .find(|id| !id.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Limit, MemoryStore, RateLimitError};
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::util::ServiceExt;
+
+    struct BrokenStore;
+
+    #[async_trait::async_trait]
+    impl RateLimitStore for BrokenStore {
+        async fn acquire(&self, _: &str, _: &Limit) -> Result<Decision, RateLimitError> {
+            Err(RateLimitError::Backend("connection refused".into()))
+        }
+    }
+
+    fn app(layer: RateLimitLayer) -> Router {
+        Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(layer)
+    }
+
+    async fn send(app: &Router, headers: &[(&str, &str)]) -> Response {
+        let mut req = Request::builder().uri("/");
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        let req = match req.body(Body::empty()) {
+            Ok(r) => r,
+            Err(e) => panic!("failed to build request: {}", e),
+        };
+        match app.clone().oneshot(req).await {
+            Ok(r) => r,
+            Err(e) => panic!("request failed: {}", e),
+        }
+    }
+
+    fn header<'a>(resp: &'a Response, name: &str) -> &'a str {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        // `#[tokio::test]` expands to `Runtime::build().expect(..)`, which clippy.toml disallows
+        match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(rt) => rt.block_on(future),
+            Err(e) => panic!("failed to build runtime: {}", e),
+        }
+    }
+
+    #[test]
+    fn requests_over_the_limit_get_429() {
+        block_on(async {
+            let policy = Policy::new("login", KeyBy::Ip, Limit::per_minute(2));
+            let app =
+                app(RateLimitLayer::new(policy, Arc::new(MemoryStore::new())).trusted_proxies(1));
+            let client = [("x-forwarded-for", "10.0.0.1, 203.0.113.7")];
+
+            let resp = send(&app, &client).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(header(&resp, "ratelimit-limit"), "2");
+            assert_eq!(header(&resp, "ratelimit-remaining"), "1");
+            assert_eq!(send(&app, &client).await.status(), StatusCode::OK);
+
+            let resp = send(&app, &client).await;
+            assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(header(&resp, "retry-after"), "30");
+            assert_eq!(
+                header(&resp, "content-type"),
+                verseguy_shared_error::PROBLEM_JSON
+            );
+
+            // entries the client added itself do not give it a fresh bucket
+            let spoofed = [("x-forwarded-for", "192.0.2.99, 203.0.113.7")];
+            assert_eq!(
+                send(&app, &spoofed).await.status(),
+                StatusCode::TOO_MANY_REQUESTS
+            );
+            let other = [("x-forwarded-for", "198.51.100.1")];
+            assert_eq!(send(&app, &other).await.status(), StatusCode::OK);
+        });
+    }
+
+    #[test]
+    fn unauthenticated_client_ids_are_counted_per_ip() {
+        block_on(async {
+            let policy = Policy::new("oauth", KeyBy::OAuthClient, Limit::per_minute(1));
+            let app =
+                app(RateLimitLayer::new(policy, Arc::new(MemoryStore::new())).trusted_proxies(1));
+            let demo = ("authorization", "Basic ZGVtbzpzZWNyZXQ=");
+
+            let attacker = [demo, ("x-forwarded-for", "192.0.2.1")];
+            assert_eq!(send(&app, &attacker).await.status(), StatusCode::OK);
+            assert_eq!(
+                send(&app, &attacker).await.status(),
+                StatusCode::TOO_MANY_REQUESTS
+            );
+            // the real client keeps its own bucket
+            let client = [demo, ("x-forwarded-for", "203.0.113.7")];
+            assert_eq!(send(&app, &client).await.status(), StatusCode::OK);
+        });
+    }
+
+    #[test]
+    fn tiers_and_tokens_select_the_bucket() {
+        block_on(async {
+            let policy = Policy::new("api", KeyBy::ApiToken, Limit::per_day(1))
+                .tier("pro", Limit::per_day(3));
+            let layer =
+                RateLimitLayer::new(policy, Arc::new(MemoryStore::new())).resolve_subject(|h| {
+                    Subject {
+                        tier: h
+                            .get("x-tier")
+                            .and_then(|v| v.to_str().ok())
+                            .map(str::to_string),
+                        ..Subject::default()
+                    }
+                });
+            let app = app(layer);
+
+            let free = [("authorization", "Bearer vgp_free")];
+            assert_eq!(send(&app, &free).await.status(), StatusCode::OK);
+            assert_eq!(
+                send(&app, &free).await.status(),
+                StatusCode::TOO_MANY_REQUESTS
+            );
+
+            let pro = [("authorization", "Bearer vgp_pro"), ("x-tier", "pro")];
+            for _ in 0..3 {
+                assert_eq!(send(&app, &pro).await.status(), StatusCode::OK);
+            }
+            assert_eq!(
+                send(&app, &pro).await.status(),
+                StatusCode::TOO_MANY_REQUESTS
+            );
+        });
+    }
+
+    #[test]
+    fn store_failures_let_requests_through() {
+        block_on(async {
+            let policy = Policy::new("login", KeyBy::Ip, Limit::per_minute(1));
+            let app = app(RateLimitLayer::new(policy, Arc::new(BrokenStore)));
+            assert_eq!(send(&app, &[]).await.status(), StatusCode::OK);
+            assert_eq!(send(&app, &[]).await.status(), StatusCode::OK);
+        });
+    }
+
+    #[test]
+    fn oauth_clients_are_read_from_basic_auth_and_query() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Basic ZGVtbzpzZWNyZXQ="),
+        );
+        assert_eq!(basic_client_id(&headers).as_deref(), Some("demo"));
+        let query = Some("response_type=code&client_id=spa&state=x");
+        assert_eq!(query_client_id(query).as_deref(), Some("spa"));
+        assert_eq!(query_client_id(Some("client_id=")), None);
+    }
+}
@@ -0,0 +1,283 @@
+//! Token-bucket rate limiting for the HTTP services.
+//!
+//! A [`Policy`] names a route group, says what requests are counted against ([`KeyBy`]) and
+//! how many they may make ([`Limit`]), optionally per license tier. [`RateLimitLayer`] applies
+//! a policy to a router; buckets live in a [`RateLimitStore`], either in process
+//! ([`MemoryStore`]) or shared between instances in Redis (`RedisStore`, feature `redis`).
+//! Rejected requests get `429 Too Many Requests` with `Retry-After`.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+
+mod layer;
+mod memory;
+#[cfg(feature = "redis")]
+mod redis_store;
+
+pub use layer::{RateLimitLayer, RateLimitService};
+pub use memory::MemoryStore;
+#[cfg(feature = "redis")]
+pub use redis_store::RedisStore;
+
+#[derive(Debug, Error)]
+pub enum RateLimitError {
+    #[error("invalid limit '{0}', expected <count>/<s|min|h|day>")]
+    InvalidLimit(String),
+    #[error("invalid proxy count '{0}', expected the number of proxies in front of the service")]
+    InvalidProxyCount(String),
+    #[error("rate limit backend error: {0}")]
+    Backend(String),
+}
+
+/// Bucket of `capacity` requests that refills continuously over `period`, so `60/min` allows a
+/// burst of 60 and then one request per second
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limit {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+impl Limit {
+    pub fn new(capacity: u32, period: Duration) -> Self {
+        Limit {
+            capacity: capacity.max(1),
+            period: period.max(Duration::from_millis(1)),
+        }
+    }
+
+    pub fn per_second(capacity: u32) -> Self {
+        Limit::new(capacity, Duration::from_secs(1))
+    }
+
+    pub fn per_minute(capacity: u32) -> Self {
+        Limit::new(capacity, Duration::from_secs(60))
+    }
+
+    pub fn per_hour(capacity: u32) -> Self {
+        Limit::new(capacity, Duration::from_secs(3600))
+    }
+
+    pub fn per_day(capacity: u32) -> Self {
+        Limit::new(capacity, Duration::from_secs(86_400))
+    }
+
+    /// Tokens added per millisecond
+    fn rate(&self) -> f64 {
+        f64::from(self.capacity) / self.period.as_millis() as f64
+    }
+
+    /// Take one token from a bucket holding `tokens` after `elapsed` without requests;
+    /// returns the new token count and the decision
+    pub(crate) fn take(&self, tokens: f64, elapsed: Duration) -> (f64, Decision) {
+        let capacity = f64::from(self.capacity);
+        let tokens = (tokens + elapsed.as_millis() as f64 * self.rate()).min(capacity);
+        if tokens >= 1.0 {
+            let left = tokens - 1.0;
+            let decision = Decision {
+                allowed: true,
+                limit: self.capacity,
+                remaining: left.floor() as u32,
+                retry_after: Duration::ZERO,
+                reset_after: self.millis_for(capacity - left),
+            };
+            (left, decision)
+        } else {
+            let decision = Decision {
+                allowed: false,
+                limit: self.capacity,
+                remaining: 0,
+                retry_after: self.millis_for(1.0 - tokens),
+                reset_after: self.millis_for(capacity - tokens),
+            };
+            (tokens, decision)
+        }
+    }
+
+    /// Time to refill `tokens`
+    fn millis_for(&self, tokens: f64) -> Duration {
+        Duration::from_millis((tokens / self.rate()).ceil().max(0.0) as u64)
+    }
+}
+
+impl FromStr for Limit {
+    type Err = RateLimitError;
+
+    /// Parse `<count>/<unit>` where unit is `s`, `min`, `h` or `day`, e.g. `10/min`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RateLimitError::InvalidLimit(s.to_string());
+        let (count, unit) = s.trim().split_once('/').ok_or_else(invalid)?;
+        let count: u32 = count.trim().parse().map_err(|_| invalid())?;
+        if count == 0 {
+            return Err(invalid());
+        }
+        match unit.trim() {
+            "s" | "sec" | "second" => Ok(Limit::per_second(count)),
+            "m" | "min" | "minute" => Ok(Limit::per_minute(count)),
+            "h" | "hour" => Ok(Limit::per_hour(count)),
+            "d" | "day" => Ok(Limit::per_day(count)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Limits are written as in the configuration, e.g. `"10/min"`
+impl Serialize for Limit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Limit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.period.as_secs() {
+            1 => write!(f, "{}/s", self.capacity),
+            60 => write!(f, "{}/min", self.capacity),
+            3600 => write!(f, "{}/h", self.capacity),
+            86_400 => write!(f, "{}/day", self.capacity),
+            _ => write!(f, "{}/{}ms", self.capacity, self.period.as_millis()),
+        }
+    }
+}
+
+/// What requests of a route group are counted against. Keys that cannot be determined for a
+/// request (an anonymous caller under `User`, say) fall back to the client IP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyBy {
+    /// Client IP address
+    Ip,
+    /// Authenticated user, as resolved by the layer's subject resolver
+    User,
+    /// Presented bearer credential (API token or access token), hashed
+    ApiToken,
+    /// OAuth client, from the resolver or the HTTP Basic client credentials
+    OAuthClient,
+}
+
+impl KeyBy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            KeyBy::Ip => "ip",
+            KeyBy::User => "user",
+            KeyBy::ApiToken => "token",
+            KeyBy::OAuthClient => "client",
+        }
+    }
+}
+
+/// Caller attributes a service resolves from request headers, see
+/// [`RateLimitLayer::resolve_subject`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Subject {
+    pub user_id: Option<String>,
+    pub client_id: Option<String>,
+    /// License tier (`free`, `pro`, ...) selecting a [`Policy::tier`] limit
+    pub tier: Option<String>,
+}
+
+/// Limits of one route group
+#[derive(Debug, Clone)]
+pub struct Policy {
+    /// Bucket namespace; route groups with the same name share buckets
+    pub name: String,
+    pub key: KeyBy,
+    /// Limit for callers without a tier or with a tier that has no limit of its own
+    pub limit: Limit,
+    pub tiers: HashMap<String, Limit>,
+}
+
+impl Policy {
+    pub fn new(name: impl Into<String>, key: KeyBy, limit: Limit) -> Self {
+        Policy {
+            name: name.into(),
+            key,
+            limit,
+            tiers: HashMap::new(),
+        }
+    }
+
+    /// Use `limit` for callers of license tier `tier`
+    pub fn tier(mut self, tier: impl Into<String>, limit: Limit) -> Self {
+        self.tiers.insert(tier.into().to_lowercase(), limit);
+        self
+    }
+
+    pub fn limit_for(&self, tier: Option<&str>) -> Limit {
+        tier.and_then(|t| self.tiers.get(&t.to_lowercase()))
+            .copied()
+            .unwrap_or(self.limit)
+    }
+}
+
+/// Outcome of taking a token from a bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub allowed: bool,
+    /// Bucket capacity
+    pub limit: u32,
+    /// Requests left right now
+    pub remaining: u32,
+    /// Wait before the next request is allowed; zero when allowed
+    pub retry_after: Duration,
+    /// Time until the bucket is full again
+    pub reset_after: Duration,
+}
+
+/// Storage of token buckets
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Take one token from the bucket `key` governed by `limit`
+    async fn acquire(&self, key: &str, limit: &Limit) -> Result<Decision, RateLimitError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_parse_and_display() {
+        let limit: Limit = match "10/min".parse() {
+            Ok(l) => l,
+            Err(e) => panic!("parse failed: {}", e),
+        };
+        assert_eq!(limit, Limit::per_minute(10));
+        assert_eq!(limit.to_string(), "10/min");
+        assert!("0/min".parse::<Limit>().is_err());
+        assert!("10/week".parse::<Limit>().is_err());
+        assert!("ten".parse::<Limit>().is_err());
+    }
+
+    #[test]
+    fn buckets_refill_over_the_period() {
+        let limit = Limit::per_minute(2);
+        let (tokens, d) = limit.take(2.0, Duration::ZERO);
+        assert!(d.allowed);
+        assert_eq!(d.remaining, 1);
+        let (tokens, _) = limit.take(tokens, Duration::ZERO);
+        let (tokens, d) = limit.take(tokens, Duration::ZERO);
+        assert!(!d.allowed);
+        assert_eq!(d.retry_after, Duration::from_secs(30));
+        let (_, d) = limit.take(tokens, Duration::from_secs(30));
+        assert!(d.allowed);
+    }
+
+    #[test]
+    fn tiers_override_the_default_limit() {
+        let policy = Policy::new("api", KeyBy::User, Limit::per_day(1_000))
+            .tier("Pro", Limit::per_day(50_000));
+        assert_eq!(policy.limit_for(Some("pro")), Limit::per_day(50_000));
+        assert_eq!(policy.limit_for(Some("free")), Limit::per_day(1_000));
+        assert_eq!(policy.limit_for(None), Limit::per_day(1_000));
+    }
+}
@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{Decision, Limit, RateLimitError, RateLimitStore};
+
+/// Buckets kept before idle ones are dropped
+const DEFAULT_MAX_KEYS: usize = 100_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    period: Duration,
+}
+
+/// Per-process buckets. Each instance of a service counts on its own, so use
+/// `RedisStore` when several instances sit behind a load balancer.
+pub struct MemoryStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+    max_keys: usize,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::with_max_keys(DEFAULT_MAX_KEYS)
+    }
+
+    /// Once more than `max_keys` buckets exist, buckets that have refilled completely are
+    /// dropped; they hold no state a fresh bucket would not
+    pub fn with_max_keys(max_keys: usize) -> Self {
+        MemoryStore {
+            buckets: Mutex::new(HashMap::new()),
+            max_keys: max_keys.max(1),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryStore {
+    async fn acquire(&self, key: &str, limit: &Limit) -> Result<Decision, RateLimitError> {
+        let now = Instant::now();
+        let mut buckets = match self.buckets.lock() {
+            Ok(b) => b,
+            Err(_) => return Err(RateLimitError::Backend("bucket lock poisoned".into())),
+        };
+        if buckets.len() >= self.max_keys && !buckets.contains_key(key) {
+            buckets.retain(|_, b| now.duration_since(b.updated) < b.period);
+        }
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: f64::from(limit.capacity),
+            updated: now,
+            period: limit.period,
+        });
+        let (tokens, decision) = limit.take(bucket.tokens, now.duration_since(bucket.updated));
+        bucket.tokens = tokens;
+        bucket.updated = now;
+        bucket.period = limit.period;
+        Ok(decision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn acquire(store: &MemoryStore, key: &str, limit: &Limit) -> Decision {
+        match store.acquire(key, limit).await {
+            Ok(d) => d,
+            Err(e) => panic!("acquire failed: {}", e),
+        }
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        // `#[tokio::test]` expands to `Runtime::build().expect(..)`, which clippy.toml disallows
+        match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(rt) => rt.block_on(future),
+            Err(e) => panic!("failed to build runtime: {}", e),
+        }
+    }
+
+    #[test]
+    fn keys_have_separate_buckets() {
+        block_on(async {
+            let store = MemoryStore::new();
+            let limit = Limit::per_minute(1);
+            assert!(acquire(&store, "a", &limit).await.allowed);
+            assert!(!acquire(&store, "a", &limit).await.allowed);
+            assert!(acquire(&store, "b", &limit).await.allowed);
+        });
+    }
+
+    #[test]
+    fn idle_buckets_are_evicted() {
+        block_on(async {
+            let store = MemoryStore::with_max_keys(1);
+            let limit = Limit::new(1, Duration::from_millis(20));
+            assert!(acquire(&store, "a", &limit).await.allowed);
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            assert!(acquire(&store, "b", &limit).await.allowed);
+            let len = match store.buckets.lock() {
+                Ok(b) => b.len(),
+                Err(_) => panic!("bucket lock poisoned"),
+            };
+            assert_eq!(len, 1);
+        });
+    }
+}
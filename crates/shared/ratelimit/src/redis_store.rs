@@ -0,0 +1,84 @@
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::Client as RedisClient;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+use crate::{Decision, Limit, RateLimitError, RateLimitStore};
+
+/// Key prefix of the buckets in Redis
+const REDIS_PREFIX: &str = "verseguy:ratelimit:";
+
+/// Token bucket stored as a hash of `tokens` and `ts` (ms), timed by the Redis clock so all
+/// instances agree (KEYS: bucket; ARGV: capacity, period in ms). Returns allowed (0/1),
+/// tokens left and the wait in ms until the next token.
+const ACQUIRE_SCRIPT: &str = r"
+local capacity = tonumber(ARGV[1])
+local period = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(state[1]) or capacity
+local ts = tonumber(state[2]) or now
+local rate = capacity / period
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * rate)
+local allowed = 0
+local wait = 0
+if tokens >= 1 then
+  tokens = tokens - 1
+  allowed = 1
+else
+  wait = math.ceil((1 - tokens) / rate)
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], period)
+return {allowed, math.floor(tokens), wait, math.ceil((capacity - tokens) / rate)}
+";
+
+/// Buckets shared by every instance through Redis. The connection is opened on first use and
+/// reconnects on its own; idle buckets expire after one period.
+pub struct RedisStore {
+    client: RedisClient,
+    conn: OnceCell<ConnectionManager>,
+}
+
+impl RedisStore {
+    pub fn new(url: &str) -> Result<Self, RateLimitError> {
+        let client = RedisClient::open(url)
+            .map_err(|e| RateLimitError::Backend(format!("redis open: {}", e)))?;
+        Ok(RedisStore {
+            client,
+            conn: OnceCell::new(),
+        })
+    }
+
+    async fn conn(&self) -> Result<ConnectionManager, RateLimitError> {
+        self.conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+            .map_err(|e| RateLimitError::Backend(format!("redis conn: {}", e)))
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for RedisStore {
+    async fn acquire(&self, key: &str, limit: &Limit) -> Result<Decision, RateLimitError> {
+        let mut conn = self.conn().await?;
+        let reply = redis::Script::new(ACQUIRE_SCRIPT)
+            .key(format!("{}{}", REDIS_PREFIX, key))
+            .arg(limit.capacity)
+            .arg(limit.period.as_millis() as u64)
+            .invoke_async::<(i64, i64, i64, i64)>(&mut conn)
+            .await
+            .map_err(|e| RateLimitError::Backend(format!("redis script: {}", e)))?;
+        let (allowed, remaining, wait, reset) = reply;
+        Ok(Decision {
+            allowed: allowed == 1,
+            limit: limit.capacity,
+            remaining: remaining.max(0) as u32,
+            retry_after: Duration::from_millis(wait.max(0) as u64),
+            reset_after: Duration::from_millis(reset.max(0) as u64),
+        })
+    }
+}
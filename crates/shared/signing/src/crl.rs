@@ -0,0 +1,291 @@
+//! Signed plugin revocation lists.
+//!
+//! The registry numbers revocations 1, 2, 3, … in the order it records them. A list's `version`
+//! is the number of its newest revocation. A list with `since` above 0 is a delta that carries
+//! only the revocations after that version. Lists are signed with a registry key, so a client
+//! can keep a [`RevocationCache`], bring it up to date with deltas and check plugins against it
+//! offline.
+
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::{SignatureEntry, SignatureError, TrustStore};
+
+/// One revoked plugin version
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Revocation {
+    /// Position in the registry's revocation sequence, from 1
+    pub seq: u64,
+    pub id: String,
+    pub version: String,
+    pub reason: String,
+    /// Unix timestamp (seconds)
+    pub revoked_at: i64,
+    /// SHA-256 of the version's artifact, so copies under another name are refused too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact_sha256: Option<String>,
+}
+
+/// Revocations after `since` up to `version`, signed by the registry
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RevocationList {
+    pub version: u64,
+    /// 0 for the full list
+    pub since: u64,
+    /// Unix timestamp (seconds)
+    pub issued_at: i64,
+    /// Ordered by `seq`
+    pub entries: Vec<Revocation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<SignatureEntry>,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CrlError {
+    #[error("revocation list signature: {0}")]
+    Signature(#[from] SignatureError),
+    #[error("revocation list entries are not numbered between {since} and {version}")]
+    Inconsistent { since: u64, version: u64 },
+    #[error("revocation list {received} is older than the cached list {cached}")]
+    Rollback { received: u64, cached: u64 },
+    #[error("delta since {since} does not apply to the cached list {cached}")]
+    Gap { since: u64, cached: u64 },
+}
+
+impl RevocationList {
+    /// Unsigned list of `entries`, which must be ordered by `seq`; its version is the last one's
+    /// `seq`, or `since` without entries
+    pub fn new(since: u64, issued_at: i64, entries: Vec<Revocation>) -> Self {
+        let version = entries.last().map_or(since, |e| e.seq.max(since));
+        RevocationList {
+            version,
+            since,
+            issued_at,
+            entries,
+            signature: None,
+        }
+    }
+
+    /// The signed document: the list without its signature
+    fn document(&self) -> Result<Value, SignatureError> {
+        let mut document = serde_json::to_value(self)
+            .map_err(|e| SignatureError::Canonicalization(e.to_string()))?;
+        if let Some(fields) = document.as_object_mut() {
+            fields.remove("signature");
+        }
+        Ok(document)
+    }
+
+    /// Sign the list with `key`
+    pub fn sign(mut self, key: &SigningKey) -> Result<Self, SignatureError> {
+        let signature = SignatureEntry::sign(&self.document()?, key, self.issued_at, None)?;
+        self.signature = Some(signature);
+        Ok(self)
+    }
+
+    /// Check the signature against `trust` and that the entries are numbered in order between
+    /// `since` and `version`
+    pub fn verify(&self, trust: &TrustStore) -> Result<(), CrlError> {
+        let signature = self
+            .signature
+            .as_ref()
+            .ok_or(SignatureError::Missing("revocation list"))?;
+        let key = trust
+            .get(&signature.key_id)
+            .ok_or_else(|| SignatureError::UnknownKey(signature.key_id.clone()))?;
+        signature.verify(&self.document()?, key, None)?;
+
+        let mut previous = self.since;
+        for entry in &self.entries {
+            if entry.seq <= previous || entry.seq > self.version {
+                return Err(CrlError::Inconsistent {
+                    since: self.since,
+                    version: self.version,
+                });
+            }
+            previous = entry.seq;
+        }
+        Ok(())
+    }
+}
+
+/// A client's copy of the registry's revocation list. Serializable, so it can be kept on disk
+/// between runs.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RevocationCache {
+    version: u64,
+    issued_at: i64,
+    entries: BTreeMap<u64, Revocation>,
+}
+
+impl RevocationCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Version of the newest list applied; 0 before the first
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// When the newest list applied was issued; clients decide how old is too old
+    pub fn issued_at(&self) -> i64 {
+        self.issued_at
+    }
+
+    /// Verify `list` and apply it: a full list replaces the cache, a delta must start at or
+    /// before the cached version. Lists older than the cache are refused, so a replayed list
+    /// cannot bring revoked plugins back.
+    pub fn apply(&mut self, list: &RevocationList, trust: &TrustStore) -> Result<(), CrlError> {
+        list.verify(trust)?;
+        if list.version < self.version {
+            return Err(CrlError::Rollback {
+                received: list.version,
+                cached: self.version,
+            });
+        }
+        if list.since > self.version {
+            return Err(CrlError::Gap {
+                since: list.since,
+                cached: self.version,
+            });
+        }
+        if list.since == 0 {
+            self.entries.clear();
+        }
+        for entry in &list.entries {
+            self.entries.insert(entry.seq, entry.clone());
+        }
+        self.version = list.version;
+        self.issued_at = self.issued_at.max(list.issued_at);
+        Ok(())
+    }
+
+    /// The revocation of `id` at `version`, if it was revoked
+    pub fn revocation(&self, id: &str, version: &str) -> Option<&Revocation> {
+        self.entries
+            .values()
+            .find(|e| e.id == id && e.version == version)
+    }
+
+    /// The revocation of the version whose artifact has hash `sha256`, if one was revoked
+    pub fn revoked_artifact(&self, sha256: &str) -> Option<&Revocation> {
+        self.entries.values().find(|e| {
+            e.artifact_sha256
+                .as_deref()
+                .is_some_and(|h| h.eq_ignore_ascii_case(sha256))
+        })
+    }
+
+    pub fn is_revoked(&self, id: &str, version: &str) -> bool {
+        self.revocation(id, version).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn revocation(seq: u64, version: &str) -> Revocation {
+        Revocation {
+            seq,
+            id: "org.test".into(),
+            version: version.into(),
+            reason: "compromised".into(),
+            revoked_at: 1_700_000_000,
+            artifact_sha256: Some(format!("{:064x}", seq)),
+        }
+    }
+
+    fn signed(since: u64, entries: Vec<Revocation>) -> RevocationList {
+        match RevocationList::new(since, 1_700_000_000 + since as i64, entries).sign(&key(1)) {
+            Ok(list) => list,
+            Err(e) => panic!("sign failed: {}", e),
+        }
+    }
+
+    #[test]
+    fn deltas_extend_the_cache() {
+        let trust = TrustStore::new().with_key(key(1).verifying_key());
+        let mut cache = RevocationCache::new();
+        let full = signed(0, vec![revocation(1, "1.0.0"), revocation(2, "1.1.0")]);
+        assert_eq!(full.version, 2);
+        assert_eq!(cache.apply(&full, &trust), Ok(()));
+        assert!(cache.is_revoked("org.test", "1.1.0"));
+        assert!(!cache.is_revoked("org.test", "1.2.0"));
+
+        let delta = signed(2, vec![revocation(3, "1.2.0")]);
+        assert_eq!(cache.apply(&delta, &trust), Ok(()));
+        assert_eq!((cache.version(), cache.len()), (3, 3));
+        let hash = format!("{:064X}", 3);
+        assert_eq!(
+            cache.revoked_artifact(&hash).map(|r| r.version.as_str()),
+            Some("1.2.0")
+        );
+
+        // an empty delta just confirms the cache is current
+        assert_eq!(cache.apply(&signed(3, vec![]), &trust), Ok(()));
+        assert_eq!(cache.version(), 3);
+    }
+
+    #[test]
+    fn stale_forged_and_gapped_lists_are_refused() {
+        let trust = TrustStore::new().with_key(key(1).verifying_key());
+        let mut cache = RevocationCache::new();
+        let full = signed(0, vec![revocation(1, "1.0.0"), revocation(2, "1.1.0")]);
+        assert_eq!(cache.apply(&full, &trust), Ok(()));
+
+        let old = signed(0, vec![revocation(1, "1.0.0")]);
+        assert_eq!(
+            cache.apply(&old, &trust),
+            Err(CrlError::Rollback {
+                received: 1,
+                cached: 2
+            })
+        );
+        let ahead = signed(5, vec![revocation(6, "2.0.0")]);
+        assert_eq!(
+            cache.apply(&ahead, &trust),
+            Err(CrlError::Gap {
+                since: 5,
+                cached: 2
+            })
+        );
+
+        // dropping an entry breaks the signature
+        let mut stripped = signed(0, vec![revocation(1, "1.0.0"), revocation(2, "1.1.0")]);
+        stripped.entries.pop();
+        assert_eq!(
+            cache.apply(&stripped, &trust),
+            Err(CrlError::Signature(SignatureError::Invalid))
+        );
+        let other = TrustStore::new().with_key(key(2).verifying_key());
+        assert!(matches!(
+            RevocationCache::new().apply(&full, &other),
+            Err(CrlError::Signature(SignatureError::UnknownKey(_)))
+        ));
+        let unordered = signed(0, vec![revocation(2, "1.1.0"), revocation(1, "1.0.0")]);
+        assert!(matches!(
+            unordered.verify(&trust),
+            Err(CrlError::Inconsistent { .. })
+        ));
+        assert_eq!(cache.version(), 2);
+    }
+}
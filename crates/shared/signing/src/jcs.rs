@@ -0,0 +1,212 @@
+//! JSON Canonicalization Scheme (RFC 8785): object members sorted by their UTF-16 code units,
+//! no insignificant whitespace, minimal string escaping and ECMAScript number formatting, so
+//! every conforming implementation produces the same bytes for the same data.
+
+use serde::Serialize;
+use serde_json::{Number, Value};
+use std::fmt::Write;
+
+/// Largest integer every IEEE 754 double represents exactly
+const MAX_SAFE_INTEGER: u64 = 1 << 53;
+
+/// Canonical form of `value`
+pub fn canonicalize(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value);
+    out
+}
+
+/// Canonical UTF-8 bytes of any serializable value
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, serde_json::Error> {
+    Ok(canonicalize(&serde_json::to_value(value)?).into_bytes())
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => write_number(out, n),
+        Value::String(s) => write_string(out, s),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut members: Vec<(&String, &Value)> = map.iter().collect();
+            members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (i, (key, item)) in members.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, key);
+                out.push(':');
+                write_value(out, item);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\u{c}' => out.push_str("\\f"),
+            '\r' => out.push_str("\\r"),
+            c if c < ' ' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_number(out: &mut String, n: &Number) {
+    if let Some(i) = n.as_i64() {
+        if i.unsigned_abs() <= MAX_SAFE_INTEGER {
+            let _ = write!(out, "{}", i);
+            return;
+        }
+    }
+    if let Some(u) = n.as_u64() {
+        if u <= MAX_SAFE_INTEGER {
+            let _ = write!(out, "{}", u);
+            return;
+        }
+    }
+    // larger integers are doubles to every other implementation
+    write_double(out, n.as_f64().unwrap_or(0.0));
+}
+
+/// ECMAScript `Number.prototype.toString` of a finite double
+fn write_double(out: &mut String, f: f64) {
+    if f == 0.0 {
+        // covers -0
+        out.push('0');
+        return;
+    }
+    if f < 0.0 {
+        out.push('-');
+    }
+    // Rust prints the shortest digits that round-trip, as ECMAScript requires
+    let sci = format!("{:e}", f.abs());
+    let Some((mantissa, exponent)) = sci.split_once('e') else {
+        out.push_str(&sci);
+        return;
+    };
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let k = digits.len() as i32;
+    // position of the decimal point relative to the digits
+    let n = exponent.parse::<i32>().unwrap_or(0) + 1;
+
+    if k <= n && n <= 21 {
+        out.push_str(&digits);
+        out.push_str(&"0".repeat((n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        let (int, frac) = digits.split_at(n as usize);
+        let _ = write!(out, "{}.{}", int, frac);
+    } else if -6 < n && n <= 0 {
+        out.push_str("0.");
+        out.push_str(&"0".repeat((-n) as usize));
+        out.push_str(&digits);
+    } else {
+        let (first, rest) = digits.split_at(1);
+        out.push_str(first);
+        if !rest.is_empty() {
+            let _ = write!(out, ".{}", rest);
+        }
+        let e = n - 1;
+        let _ = write!(out, "e{}{}", if e < 0 { '-' } else { '+' }, e.abs());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Value {
+        match serde_json::from_str(json) {
+            Ok(v) => v,
+            Err(e) => panic!("invalid test json: {}", e),
+        }
+    }
+
+    #[test]
+    fn rfc8785_example() {
+        let input = r#"{
+            "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+            "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+            "literals": [null, true, false]
+        }"#;
+        let expected = concat!(
+            r#"{"literals":[null,true,false],"#,
+            r#""numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"#,
+            r#""string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+        assert_eq!(canonicalize(&parse(input)), expected);
+    }
+
+    #[test]
+    fn members_sort_by_utf16_code_units() {
+        let input = r#"{
+            "\u20ac": "Euro Sign",
+            "\r": "Carriage Return",
+            "\ufb33": "Hebrew Letter Dalet With Dagesh",
+            "1": "One",
+            "\ud83d\ude00": "Emoji: Grinning Face",
+            "\u0080": "Control",
+            "\u00f6": "Latin Small Letter O With Diaeresis"
+        }"#;
+        let keys = [
+            "\\r",
+            "1",
+            "\u{80}",
+            "\u{f6}",
+            "\u{20ac}",
+            "\u{1f600}",
+            "\u{fb33}",
+        ];
+        let canonical = canonicalize(&parse(input));
+        let positions: Vec<usize> = keys
+            .iter()
+            .map(|key| match canonical.find(&format!("\"{}\":", key)) {
+                Some(pos) => pos,
+                None => panic!("{} missing from {}", key, canonical),
+            })
+            .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]), "{}", canonical);
+    }
+
+    #[test]
+    fn numbers_follow_ecmascript() {
+        let cases = [
+            ("0", "0"),
+            ("-0.0", "0"),
+            ("1", "1"),
+            ("-42", "-42"),
+            ("1.5", "1.5"),
+            ("0.000001", "0.000001"),
+            ("1e-7", "1e-7"),
+            ("123456789012345680000", "123456789012345680000"),
+            ("1e21", "1e+21"),
+            ("9007199254740993", "9007199254740992"),
+            ("-1.25e-10", "-1.25e-10"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(canonicalize(&parse(input)), expected, "{}", input);
+        }
+    }
+}
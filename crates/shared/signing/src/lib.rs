@@ -0,0 +1,336 @@
+//! Detached signatures over JSON documents such as plugin manifests.
+//!
+//! Documents are signed in their RFC 8785 canonical form ([`jcs`]), so any implementation can
+//! reproduce the signed bytes. A [`SignatureEntry`] names the key, algorithm and signing time
+//! and signs them together with the document. An [`Envelope`] carries the publisher's signature
+//! and the registry's counter-signature, which also covers the publisher's signature.
+//! Verifiers resolve key ids through a [`TrustStore`], which may hold several registry keys so
+//! signatures made before a key rotation still verify.
+//!
+//! [`crl`] has the registry's signed revocation lists, and [`plugin`] the offline check clients
+//! run before loading a plugin.
+
+use base64::{engine::general_purpose, Engine as _};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+pub mod crl;
+pub mod jcs;
+pub mod plugin;
+mod trust;
+
+pub use trust::TrustStore;
+
+/// The only algorithm signatures are made with
+pub const ALGORITHM_ED25519: &str = "ed25519";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SignatureError {
+    #[error("missing {0} signature")]
+    Missing(&'static str),
+    #[error("unsupported signature algorithm '{0}'")]
+    UnsupportedAlgorithm(String),
+    #[error("unknown signing key {0}")]
+    UnknownKey(String),
+    #[error("malformed {0}")]
+    Malformed(&'static str),
+    #[error("signature does not match")]
+    Invalid,
+    #[error("document cannot be canonicalized: {0}")]
+    Canonicalization(String),
+}
+
+/// Key id: hex of the first 16 bytes of the SHA-256 of the public key
+pub fn key_id(key: &VerifyingKey) -> String {
+    hex::encode(&Sha256::digest(key.as_bytes())[..16])
+}
+
+/// Decode a base64 Ed25519 public key
+pub fn decode_public_key(b64: &str) -> Result<VerifyingKey, SignatureError> {
+    let bytes = general_purpose::STANDARD
+        .decode(b64.trim())
+        .map_err(|_| SignatureError::Malformed("public key"))?;
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| SignatureError::Malformed("public key"))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| SignatureError::Malformed("public key"))
+}
+
+pub fn encode_public_key(key: &VerifyingKey) -> String {
+    general_purpose::STANDARD.encode(key.as_bytes())
+}
+
+/// What a signature signs: the document and the signature's own metadata, plus the signature it
+/// counter-signs
+#[derive(Serialize)]
+struct SigningInput<'a> {
+    document: &'a Value,
+    key_id: &'a str,
+    algorithm: &'a str,
+    signed_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    countersigns: Option<&'a str>,
+}
+
+/// One detached signature
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SignatureEntry {
+    pub key_id: String,
+    pub algorithm: String,
+    /// Unix timestamp (seconds)
+    pub signed_at: i64,
+    /// Base64 public key, for signers verifiers do not know in advance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    /// Base64 signature
+    pub signature: String,
+}
+
+impl SignatureEntry {
+    /// Sign `document` with `key`; `countersigns` is the signature this one vouches for
+    pub fn sign(
+        document: &Value,
+        key: &SigningKey,
+        signed_at: i64,
+        countersigns: Option<&SignatureEntry>,
+    ) -> Result<Self, SignatureError> {
+        let key_id = key_id(&key.verifying_key());
+        let input = SigningInput {
+            document,
+            key_id: &key_id,
+            algorithm: ALGORITHM_ED25519,
+            signed_at,
+            countersigns: countersigns.map(|s| s.signature.as_str()),
+        };
+        let bytes =
+            jcs::to_vec(&input).map_err(|e| SignatureError::Canonicalization(e.to_string()))?;
+        let signature = key.sign(&bytes);
+        Ok(SignatureEntry {
+            key_id,
+            algorithm: ALGORITHM_ED25519.to_string(),
+            signed_at,
+            public_key: None,
+            signature: general_purpose::STANDARD.encode(signature.to_bytes()),
+        })
+    }
+
+    /// Embed the signer's public key
+    pub fn with_public_key(mut self, key: &VerifyingKey) -> Self {
+        self.public_key = Some(encode_public_key(key));
+        self
+    }
+
+    /// The embedded public key, checked against `key_id`
+    pub fn embedded_key(&self) -> Result<Option<VerifyingKey>, SignatureError> {
+        let Some(b64) = &self.public_key else {
+            return Ok(None);
+        };
+        let key = decode_public_key(b64)?;
+        if key_id(&key) != self.key_id {
+            return Err(SignatureError::Malformed("key id"));
+        }
+        Ok(Some(key))
+    }
+
+    pub fn verify(
+        &self,
+        document: &Value,
+        key: &VerifyingKey,
+        countersigns: Option<&SignatureEntry>,
+    ) -> Result<(), SignatureError> {
+        if self.algorithm != ALGORITHM_ED25519 {
+            return Err(SignatureError::UnsupportedAlgorithm(self.algorithm.clone()));
+        }
+        if key_id(key) != self.key_id {
+            return Err(SignatureError::UnknownKey(self.key_id.clone()));
+        }
+        let signature = general_purpose::STANDARD
+            .decode(self.signature.trim())
+            .map_err(|_| SignatureError::Malformed("signature"))?;
+        let signature = Signature::from_slice(&signature)
+            .map_err(|_| SignatureError::Malformed("signature"))?;
+        let input = SigningInput {
+            document,
+            key_id: &self.key_id,
+            algorithm: &self.algorithm,
+            signed_at: self.signed_at,
+            countersigns: countersigns.map(|s| s.signature.as_str()),
+        };
+        let bytes =
+            jcs::to_vec(&input).map_err(|e| SignatureError::Canonicalization(e.to_string()))?;
+        key.verify(&bytes, &signature)
+            .map_err(|_| SignatureError::Invalid)
+    }
+}
+
+/// Signatures of one document: the publisher's, and the registry's counter-signature over the
+/// document and the publisher's signature
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Envelope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publisher: Option<SignatureEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub master: Option<SignatureEntry>,
+}
+
+impl Envelope {
+    /// Counter-sign `document` and the publisher signature (if any) with the registry key
+    pub fn countersign(
+        &mut self,
+        document: &Value,
+        key: &SigningKey,
+        signed_at: i64,
+    ) -> Result<(), SignatureError> {
+        let master = SignatureEntry::sign(document, key, signed_at, self.publisher.as_ref())?;
+        self.master = Some(master);
+        Ok(())
+    }
+
+    /// Verify the publisher signature with `key`, or with its embedded key when `key` is `None`
+    pub fn verify_publisher(
+        &self,
+        document: &Value,
+        key: Option<&VerifyingKey>,
+    ) -> Result<(), SignatureError> {
+        let publisher = self
+            .publisher
+            .as_ref()
+            .ok_or(SignatureError::Missing("publisher"))?;
+        let embedded = publisher.embedded_key()?;
+        let key = key
+            .or(embedded.as_ref())
+            .ok_or_else(|| SignatureError::UnknownKey(publisher.key_id.clone()))?;
+        publisher.verify(document, key, None)
+    }
+
+    /// Verify the registry counter-signature with a key from `trust`, and the publisher
+    /// signature it covers
+    pub fn verify(&self, document: &Value, trust: &TrustStore) -> Result<(), SignatureError> {
+        let master = self
+            .master
+            .as_ref()
+            .ok_or(SignatureError::Missing("master"))?;
+        let key = trust
+            .get(&master.key_id)
+            .ok_or_else(|| SignatureError::UnknownKey(master.key_id.clone()))?;
+        master.verify(document, key, self.publisher.as_ref())?;
+        if let Some(publisher) = &self.publisher {
+            let key = match publisher.embedded_key()? {
+                Some(key) => key,
+                None => *trust
+                    .get(&publisher.key_id)
+                    .ok_or_else(|| SignatureError::UnknownKey(publisher.key_id.clone()))?,
+            };
+            publisher.verify(document, &key, None)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::disallowed_methods)]
+mod tests {
+    use super::*;
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn manifest() -> Value {
+        serde_json::json!({"id": "org.test", "version": "1.0.0", "name": "Test"})
+    }
+
+    #[test]
+    fn countersigned_envelopes_verify() {
+        let (publisher, master) = (key(1), key(2));
+        let doc = manifest();
+        let signed = SignatureEntry::sign(&doc, &publisher, 1_700_000_000, None);
+        let signed = match signed {
+            Ok(s) => s.with_public_key(&publisher.verifying_key()),
+            Err(e) => panic!("sign failed: {}", e),
+        };
+        let mut envelope = Envelope {
+            publisher: Some(signed),
+            master: None,
+        };
+        assert_eq!(envelope.verify_publisher(&doc, None), Ok(()));
+        assert!(envelope.countersign(&doc, &master, 1_700_000_100).is_ok());
+
+        let trust = TrustStore::new().with_key(master.verifying_key());
+        assert_eq!(envelope.verify(&doc, &trust), Ok(()));
+
+        // member order and whitespace do not matter
+        let reordered: Value = match serde_json::from_str(
+            r#"{ "version": "1.0.0", "name": "Test", "id": "org.test" }"#,
+        ) {
+            Ok(v) => v,
+            Err(e) => panic!("invalid json: {}", e),
+        };
+        assert_eq!(envelope.verify(&reordered, &trust), Ok(()));
+
+        let mut tampered = doc.clone();
+        tampered["version"] = Value::from("1.0.1");
+        assert_eq!(
+            envelope.verify(&tampered, &trust),
+            Err(SignatureError::Invalid)
+        );
+    }
+
+    #[test]
+    fn countersignature_covers_the_publisher_signature() {
+        let (publisher, other, master) = (key(1), key(3), key(2));
+        let doc = manifest();
+        let mut envelope = Envelope {
+            publisher: SignatureEntry::sign(&doc, &publisher, 1, None)
+                .ok()
+                .map(|s| s.with_public_key(&publisher.verifying_key())),
+            master: None,
+        };
+        assert!(envelope.countersign(&doc, &master, 2).is_ok());
+
+        // swapping in another, valid publisher signature breaks the counter-signature
+        envelope.publisher = SignatureEntry::sign(&doc, &other, 1, None)
+            .ok()
+            .map(|s| s.with_public_key(&other.verifying_key()));
+        let trust = TrustStore::new().with_key(master.verifying_key());
+        assert_eq!(envelope.verify(&doc, &trust), Err(SignatureError::Invalid));
+    }
+
+    #[test]
+    fn unknown_keys_and_malformed_signatures_are_errors() {
+        let doc = manifest();
+        let mut envelope = Envelope::default();
+        assert!(envelope.countersign(&doc, &key(2), 1).is_ok());
+
+        let other = TrustStore::new().with_key(key(9).verifying_key());
+        assert!(matches!(
+            envelope.verify(&doc, &other),
+            Err(SignatureError::UnknownKey(_))
+        ));
+
+        let trust = TrustStore::new().with_key(key(2).verifying_key());
+        if let Some(master) = envelope.master.as_mut() {
+            master.signature = general_purpose::STANDARD.encode([0u8; 10]);
+        }
+        assert_eq!(
+            envelope.verify(&doc, &trust),
+            Err(SignatureError::Malformed("signature"))
+        );
+        if let Some(master) = envelope.master.as_mut() {
+            master.algorithm = "rsa".into();
+        }
+        assert!(matches!(
+            envelope.verify(&doc, &trust),
+            Err(SignatureError::UnsupportedAlgorithm(_))
+        ));
+        assert_eq!(
+            decode_public_key("c2hvcnQ="),
+            Err(SignatureError::Malformed("public key"))
+        );
+    }
+}
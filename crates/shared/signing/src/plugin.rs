@@ -0,0 +1,207 @@
+//! Offline checks a launcher runs before loading a downloaded plugin.
+//!
+//! [`verify_plugin`] checks the registry signature over the manifest, the artifact against the
+//! hash and size the manifest records, and both against a [`RevocationCache`]. It needs no
+//! network access beyond keeping the cache current.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::crl::RevocationCache;
+use crate::{Envelope, SignatureError, TrustStore};
+
+/// The JSON a manifest signature covers: the manifest without `published_at`, which the
+/// registry sets after the publisher has signed
+pub fn signing_document(manifest: &Value) -> Value {
+    let mut document = manifest.clone();
+    if let Some(fields) = document.as_object_mut() {
+        fields.remove("published_at");
+    }
+    document
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PluginError {
+    #[error("manifest signature: {0}")]
+    Signature(#[from] SignatureError),
+    #[error("manifest has no {0}")]
+    Manifest(&'static str),
+    #[error("artifact is {actual} bytes, the manifest says {expected}")]
+    Size { expected: u64, actual: u64 },
+    #[error("artifact does not match the manifest's SHA-256")]
+    Hash,
+    #[error("{id} {version} was revoked: {reason}")]
+    Revoked {
+        id: String,
+        version: String,
+        reason: String,
+    },
+}
+
+/// A plugin that passed [`verify_plugin`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedPlugin {
+    pub id: String,
+    pub version: String,
+    /// Hex SHA-256 of the artifact
+    pub sha256: String,
+}
+
+/// Check that `manifest` carries a valid registry signature in `envelope`, that `artifact` is
+/// the one the manifest names, and that neither was revoked according to `revocations`
+pub fn verify_plugin(
+    manifest: &Value,
+    envelope: &Envelope,
+    artifact: &[u8],
+    trust: &TrustStore,
+    revocations: &RevocationCache,
+) -> Result<VerifiedPlugin, PluginError> {
+    envelope.verify(&signing_document(manifest), trust)?;
+
+    let field = |name: &'static str| {
+        manifest
+            .get(name)
+            .and_then(Value::as_str)
+            .ok_or(PluginError::Manifest(name))
+    };
+    let (id, version) = (field("id")?, field("version")?);
+    let reference = manifest
+        .get("artifact")
+        .ok_or(PluginError::Manifest("artifact"))?;
+    let expected_hash = reference
+        .get("sha256")
+        .and_then(Value::as_str)
+        .ok_or(PluginError::Manifest("artifact sha256"))?;
+    let expected_size = reference
+        .get("size")
+        .and_then(Value::as_u64)
+        .ok_or(PluginError::Manifest("artifact size"))?;
+
+    let actual = artifact.len() as u64;
+    if actual != expected_size {
+        return Err(PluginError::Size {
+            expected: expected_size,
+            actual,
+        });
+    }
+    let sha256 = hex::encode(Sha256::digest(artifact));
+    if !sha256.eq_ignore_ascii_case(expected_hash) {
+        return Err(PluginError::Hash);
+    }
+
+    let revoked = revocations
+        .revocation(id, version)
+        .or_else(|| revocations.revoked_artifact(&sha256));
+    if let Some(r) = revoked {
+        return Err(PluginError::Revoked {
+            id: r.id.clone(),
+            version: r.version.clone(),
+            reason: r.reason.clone(),
+        });
+    }
+    Ok(VerifiedPlugin {
+        id: id.to_string(),
+        version: version.to_string(),
+        sha256,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crl::{Revocation, RevocationList};
+    use ed25519_dalek::SigningKey;
+
+    const ARTIFACT: &[u8] = b"plugin bytes";
+
+    fn registry() -> SigningKey {
+        SigningKey::from_bytes(&[4u8; 32])
+    }
+
+    #[allow(clippy::disallowed_methods)]
+    fn manifest(version: &str) -> Value {
+        serde_json::json!({
+            "id": "org.test",
+            "version": version,
+            "published_at": 1_700_000_000,
+            "artifact": {"sha256": hex::encode(Sha256::digest(ARTIFACT)), "size": 12},
+        })
+    }
+
+    fn signed(manifest: &Value) -> Envelope {
+        let mut envelope = Envelope::default();
+        if let Err(e) = envelope.countersign(&signing_document(manifest), &registry(), 1) {
+            panic!("countersign failed: {}", e);
+        }
+        envelope
+    }
+
+    fn revoked(trust: &TrustStore, version: &str, sha256: Option<String>) -> RevocationCache {
+        let entry = Revocation {
+            seq: 1,
+            id: "org.test".into(),
+            version: version.into(),
+            reason: "malware".into(),
+            revoked_at: 2,
+            artifact_sha256: sha256,
+        };
+        let list = match RevocationList::new(0, 2, vec![entry]).sign(&registry()) {
+            Ok(list) => list,
+            Err(e) => panic!("sign failed: {}", e),
+        };
+        let mut cache = RevocationCache::new();
+        assert_eq!(cache.apply(&list, trust), Ok(()));
+        cache
+    }
+
+    #[test]
+    fn signed_unrevoked_plugins_pass() {
+        let trust = TrustStore::new().with_key(registry().verifying_key());
+        let m = manifest("1.0.0");
+        let verified = verify_plugin(&m, &signed(&m), ARTIFACT, &trust, &RevocationCache::new());
+        assert_eq!(verified.map(|v| v.version), Ok("1.0.0".to_string()));
+
+        // published_at is not signed
+        let mut republished = m.clone();
+        republished["published_at"] = Value::from(1_800_000_000);
+        let cache = RevocationCache::new();
+        assert!(verify_plugin(&republished, &signed(&m), ARTIFACT, &trust, &cache).is_ok());
+    }
+
+    #[test]
+    fn tampered_mismatched_and_revoked_plugins_fail() {
+        let trust = TrustStore::new().with_key(registry().verifying_key());
+        let m = manifest("1.0.0");
+        let envelope = signed(&m);
+        let cache = RevocationCache::new();
+
+        let mut tampered = m.clone();
+        tampered["version"] = Value::from("1.0.1");
+        assert_eq!(
+            verify_plugin(&tampered, &envelope, ARTIFACT, &trust, &cache),
+            Err(PluginError::Signature(SignatureError::Invalid))
+        );
+        assert_eq!(
+            verify_plugin(&m, &envelope, b"other bytes!", &trust, &cache),
+            Err(PluginError::Hash)
+        );
+        assert!(matches!(
+            verify_plugin(&m, &envelope, b"short", &trust, &cache),
+            Err(PluginError::Size { .. })
+        ));
+
+        let by_version = revoked(&trust, "1.0.0", None);
+        assert!(matches!(
+            verify_plugin(&m, &envelope, ARTIFACT, &trust, &by_version),
+            Err(PluginError::Revoked { .. })
+        ));
+        // the same artifact published again under another version is still refused
+        let sha256 = hex::encode(Sha256::digest(ARTIFACT));
+        let by_hash = revoked(&trust, "0.9.0", Some(sha256));
+        assert!(matches!(
+            verify_plugin(&m, &envelope, ARTIFACT, &trust, &by_hash),
+            Err(PluginError::Revoked { .. })
+        ));
+    }
+}
@@ -0,0 +1,86 @@
+use ed25519_dalek::VerifyingKey;
+use std::collections::HashMap;
+
+use crate::{decode_public_key, key_id, SignatureError};
+
+/// Public keys trusted to sign, by key id
+#[derive(Debug, Clone, Default)]
+pub struct TrustStore {
+    keys: HashMap<String, VerifyingKey>,
+}
+
+impl TrustStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust `key`; returns its key id
+    pub fn add(&mut self, key: VerifyingKey) -> String {
+        let id = key_id(&key);
+        self.keys.insert(id.clone(), key);
+        id
+    }
+
+    pub fn with_key(mut self, key: VerifyingKey) -> Self {
+        self.add(key);
+        self
+    }
+
+    /// Trust a base64 public key; returns its key id
+    pub fn add_b64(&mut self, b64: &str) -> Result<String, SignatureError> {
+        Ok(self.add(decode_public_key(b64)?))
+    }
+
+    pub fn get(&self, key_id: &str) -> Option<&VerifyingKey> {
+        self.keys.get(key_id)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &VerifyingKey> {
+        self.keys.values()
+    }
+
+    /// Trusted key ids, sorted
+    pub fn key_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.keys.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode_public_key;
+    use ed25519_dalek::SigningKey;
+
+    #[test]
+    fn keys_are_found_by_id() {
+        let first = SigningKey::from_bytes(&[1; 32]).verifying_key();
+        let second = SigningKey::from_bytes(&[2; 32]).verifying_key();
+        let mut trust = TrustStore::new().with_key(first);
+        assert_eq!(trust.len(), 1);
+
+        let id = match trust.add_b64(&encode_public_key(&second)) {
+            Ok(id) => id,
+            Err(e) => panic!("valid key rejected: {}", e),
+        };
+        assert_eq!(id, key_id(&second));
+        assert_eq!(id.len(), 32);
+        assert_eq!(trust.get(&id), Some(&second));
+        assert_eq!(trust.get(&key_id(&first)), Some(&first));
+        assert_eq!(trust.key_ids().len(), 2);
+
+        // adding a key twice keeps one entry
+        trust.add(first);
+        assert_eq!(trust.len(), 2);
+        assert!(trust.add_b64("not base64!").is_err());
+    }
+}
@@ -11,3 +11,6 @@ pub fn must_opt<T>(o: Option<T>, msg: &str) -> T {
         None => panic!("{}", msg),
     }
 }
+
+#[cfg(feature = "openapi")]
+pub mod openapi;
@@ -0,0 +1,98 @@
+//! Consistency checks between an axum router and the OpenAPI document it serves
+
+use crate::{must, must_opt};
+use axum::body::{self, Body};
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use serde_json::Value;
+use tower::util::ServiceExt;
+
+/// The document served under `/openapi.json`
+pub async fn served_spec(app: &Router) -> Value {
+    let req = must(
+        Request::builder()
+            .method("GET")
+            .uri("/openapi.json")
+            .body(Body::empty()),
+    );
+    let resp = must(app.clone().oneshot(req).await);
+    assert_eq!(resp.status(), StatusCode::OK);
+    let bytes = must(body::to_bytes(resp.into_body(), 4 * 1024 * 1024).await);
+    must(serde_json::from_slice(&bytes))
+}
+
+/// `(method, path template, operation)` for every documented operation
+pub fn operations(spec: &Value) -> Vec<(String, String, Value)> {
+    let paths = must_opt(
+        spec.get("paths").and_then(|p| p.as_object()),
+        "spec has no paths",
+    );
+    let mut ops = Vec::new();
+    for (path, item) in paths {
+        let item = must_opt(item.as_object(), "path item is not an object");
+        for (method, op) in item {
+            if ["get", "post", "put", "patch", "delete"].contains(&method.as_str()) {
+                ops.push((method.to_uppercase(), path.clone(), op.clone()));
+            }
+        }
+    }
+    ops
+}
+
+/// Assert that `app` routes every operation of `spec`: unknown paths hit a fallback, and known
+/// paths with an undocumented method answer 405
+pub async fn assert_operations_routed(app: &Router, spec: &Value) {
+    let probe = app.clone().fallback(|| async { StatusCode::IM_A_TEAPOT });
+    for (method, path, _) in operations(spec) {
+        let uri = path
+            .split('/')
+            .map(|seg| if seg.starts_with('{') { "x" } else { seg })
+            .collect::<Vec<_>>()
+            .join("/");
+        let req = must(
+            Request::builder()
+                .method(method.as_str())
+                .uri(&uri)
+                .body(Body::empty()),
+        );
+        let status = must(probe.clone().oneshot(req).await).status();
+        assert_ne!(
+            status,
+            StatusCode::IM_A_TEAPOT,
+            "{} {} is not routed",
+            method,
+            path
+        );
+        assert_ne!(
+            status,
+            StatusCode::METHOD_NOT_ALLOWED,
+            "{} {} is not routed",
+            method,
+            path
+        );
+    }
+}
+
+/// Assert that every operation of `spec` declares exactly the parameters of its path template
+pub fn assert_path_parameters_documented(spec: &Value) {
+    for (method, path, op) in operations(spec) {
+        let mut declared: Vec<&str> = op
+            .get("parameters")
+            .and_then(|p| p.as_array())
+            .map(|params| {
+                params
+                    .iter()
+                    .filter(|p| p.get("in").and_then(|i| i.as_str()) == Some("path"))
+                    .filter_map(|p| p.get("name").and_then(|n| n.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut expected: Vec<&str> = path
+            .split('/')
+            .filter_map(|seg| seg.strip_prefix('{').and_then(|s| s.strip_suffix('}')))
+            .collect();
+        declared.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(declared, expected, "{} {}", method, path);
+    }
+}
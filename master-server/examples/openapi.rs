@@ -0,0 +1,13 @@
+//! Print the generated OpenAPI document of the master server.
+//!
+//! `cargo run -p master_server --example openapi > openapi.json`
+
+fn main() {
+    match master_server::openapi_spec().to_pretty_json() {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("failed to render the OpenAPI document: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
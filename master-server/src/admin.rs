@@ -0,0 +1,153 @@
+//! Authorization for admin endpoints.
+//!
+//! Admin endpoints take the same `Authorization: Bearer` credentials as the rest of the API: a
+//! session JWT or a personal access token. Each endpoint needs a permission such as
+//! [`ADMIN_KEYS`]. A `verseguy_authorization` policy stored under the permission's name decides
+//! who holds it. Without such a policy, the [`ADMIN_ROLE`] role grants it. Personal access tokens
+//! additionally need the permission as a scope.
+//!
+//! A user's roles are the role assigned under `assignment:{user_id}`, the roles synced from
+//! their identity provider, and [`ADMIN_ROLE`] for the user ids listed in `security.admin_users`,
+//! which is how the first administrators are set up. The list names ids rather than usernames:
+//! registration is open and provider usernames are neither unique nor reserved, so a username
+//! could be claimed by anyone before its owner signs up.
+//!
+//! Every admin action is recorded in the audit log under the acting user's id.
+
+use crate::state::AppState;
+use axum::http::HeaderMap;
+use serde::Deserialize;
+use serde_json::Value;
+use verseguy_auth::IdentityStore;
+use verseguy_authorization::policy::evaluate_policy;
+use verseguy_authorization::store::Policy;
+use verseguy_shared_error::AppError;
+
+/// Rotate, import and list master keys
+pub const ADMIN_KEYS: &str = "admin:keys";
+/// Publish and revoke legal documents
+pub const ADMIN_LEGAL: &str = "admin:legal";
+/// Revoke, yank and list plugin versions
+pub const ADMIN_PLUGINS: &str = "admin:plugins";
+/// Manage service accounts and their tokens
+pub const ADMIN_ACCOUNTS: &str = "admin:accounts";
+/// Look up, lock and unlock user accounts
+pub const ADMIN_USERS: &str = "admin:users";
+/// Verify the audit log's hash chain
+pub const ADMIN_AUDIT: &str = "admin:audit";
+/// Take and list database backups
+pub const ADMIN_BACKUPS: &str = "admin:backups";
+/// Issue, list and revoke license tokens
+pub const ADMIN_LICENSES: &str = "admin:licenses";
+/// Export a user's personal data
+pub const COMPLIANCE_EXPORT: &str = "compliance:export";
+
+/// Role that holds every permission no policy is stored for
+pub const ADMIN_ROLE: &str = "admin";
+
+/// `assignment:{user_id}` record written by `verseguy_authorization`
+#[derive(Deserialize)]
+struct AssignmentRec {
+    role_id: String,
+}
+
+/// `role:{id}` record written by `verseguy_authorization`
+#[derive(Deserialize)]
+struct RoleRec {
+    name: String,
+}
+
+/// Names of the roles `user_id` holds
+pub fn roles_of(state: &AppState, user_id: &str) -> Result<Vec<String>, AppError> {
+    let mut roles = Vec::new();
+    let assignment: Option<AssignmentRec> = state
+        .storage
+        .get(format!("assignment:{}", user_id).as_bytes())
+        .map_err(AppError::internal)?;
+    if let Some(a) = assignment {
+        let role: Option<RoleRec> = state
+            .storage
+            .get(format!("role:{}", a.role_id).as_bytes())
+            .map_err(AppError::internal)?;
+        roles.extend(role.map(|r| r.name));
+    }
+
+    let user = IdentityStore::new((*state.storage).clone())
+        .get_user(user_id)
+        .map_err(AppError::internal)?;
+    if let Some(user) = user {
+        if state.config.security.admin_users.contains(&user.id) {
+            roles.push(ADMIN_ROLE.to_string());
+        }
+        roles.extend(user.roles);
+    }
+    Ok(roles)
+}
+
+/// The stored policy named `name`; records that are not policies are skipped
+fn policy_named(state: &AppState, name: &str) -> Result<Option<Policy>, AppError> {
+    let records: Vec<Value> = state
+        .storage
+        .prefix_scan(b"policy:")
+        .map_err(AppError::internal)?;
+    Ok(records
+        .into_iter()
+        .filter_map(|r| serde_json::from_value::<Policy>(r).ok())
+        .find(|p| p.name == name))
+}
+
+/// Whether `user_id` holds `permission`: the policy named after it decides, or without one the
+/// [`ADMIN_ROLE`] role
+pub fn is_permitted(state: &AppState, user_id: &str, permission: &str) -> Result<bool, AppError> {
+    let roles = roles_of(state, user_id)?;
+    match policy_named(state, permission)? {
+        Some(policy) => {
+            let role_refs: Vec<&str> = roles.iter().map(String::as_str).collect();
+            evaluate_policy(&policy.policy, &role_refs).map_err(AppError::internal)
+        }
+        None => Ok(roles.iter().any(|r| r == ADMIN_ROLE)),
+    }
+}
+
+/// A caller allowed to perform an admin action
+#[derive(Debug, Clone)]
+pub struct Admin {
+    pub user_id: String,
+    pub permission: &'static str,
+}
+
+impl Admin {
+    /// Record `action` in the audit log; `details` must be a JSON object
+    pub fn audit(&self, state: &AppState, action: &str, details: Value) -> Result<(), AppError> {
+        let mut event = match details {
+            Value::Object(fields) => fields,
+            _ => serde_json::Map::new(),
+        };
+        event.insert("action".into(), Value::from(action));
+        event.insert("permission".into(), Value::from(self.permission));
+        verseguy_audit::AuditService::new(state.storage.clone())
+            .log_event(Some(self.user_id.clone()), Value::Object(event).to_string())
+            .map_err(AppError::internal)?;
+        Ok(())
+    }
+}
+
+/// Authenticate the caller and check that they hold `permission`
+pub fn require_admin(
+    state: &AppState,
+    headers: &HeaderMap,
+    permission: &'static str,
+) -> Result<Admin, AppError> {
+    let principal = crate::auth::authenticate(state, headers)?;
+    principal.require_scope(permission)?;
+    if !is_permitted(state, &principal.user_id, permission)? {
+        return Err(AppError::Forbidden(format!(
+            "missing permission: {}",
+            permission
+        )));
+    }
+    Ok(Admin {
+        user_id: principal.user_id,
+        permission,
+    })
+}
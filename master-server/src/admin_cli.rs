@@ -1,7 +1,17 @@
 use base64::Engine;
-use clap::{Parser, Subcommand};
-use reqwest::blocking::Client;
-// use serde_json::json; // removed - construct Value manually to avoid clippy disallowed_methods macro internals
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::{Method, Url};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// Environment variable holding the bearer token when `--token` is not given
+pub const TOKEN_ENV: &str = "VERSEGUY_ADMIN_TOKEN";
+/// Environment variable holding the password for `--username`
+pub const PASSWORD_ENV: &str = "VERSEGUY_ADMIN_PASSWORD";
+
+/// Widest a table cell gets before it is cut off
+const MAX_CELL: usize = 48;
 
 #[derive(Parser, Debug)]
 #[command(name = "verseguy-admin")]
@@ -10,14 +20,33 @@ pub struct Cli {
     #[arg(short, long, default_value = "http://127.0.0.1:3000")]
     pub server: String,
 
-    /// Admin token for auth (or set MASTER_ADMIN_TOKEN env)
+    /// Session JWT or `vgp_` personal access token (or set VERSEGUY_ADMIN_TOKEN env)
     #[arg(short, long)]
     pub token: Option<String>,
 
+    /// Log in as this user instead of passing a token; the password comes from
+    /// VERSEGUY_ADMIN_PASSWORD or, with --password-stdin, from the first line of stdin
+    #[arg(short, long, conflicts_with = "token")]
+    pub username: Option<String>,
+
+    /// Read the password for --username from stdin
+    #[arg(long, requires = "username")]
+    pub password_stdin: bool,
+
+    /// How responses are printed
+    #[arg(short, long, value_enum, default_value_t = Output::Table, global = true)]
+    pub output: Output,
+
     #[command(subcommand)]
     pub cmd: Commands,
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Table,
+    Json,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Show current master key public info
@@ -31,6 +60,179 @@ pub enum Commands {
         #[arg(long)]
         b64: Option<String>,
     },
+    /// Terms of service and other legal documents
+    #[command(subcommand)]
+    Legal(LegalCommands),
+    /// Plugin versions in the registry
+    #[command(subcommand)]
+    Plugin(PluginCommands),
+    /// User accounts
+    #[command(subcommand)]
+    User(UserCommands),
+    /// Personal data export and deletion
+    #[command(subcommand)]
+    Gdpr(GdprCommands),
+    /// The audit log
+    #[command(subcommand)]
+    Audit(AuditCommands),
+    /// Database backups
+    #[command(subcommand)]
+    Backup(BackupCommands),
+    /// License tokens
+    #[command(subcommand)]
+    License(LicenseCommands),
+    /// Print a completion script for `shell`
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum LegalCommands {
+    /// Publish a document version; it takes effect at `--effective-at`, or right away
+    Create {
+        /// Document type, such as `tos` or `privacy`
+        #[arg(long)]
+        doc_type: String,
+        #[arg(long)]
+        version: String,
+        #[arg(long)]
+        title: String,
+        /// File holding the document text
+        #[arg(long)]
+        file: PathBuf,
+        #[arg(long)]
+        author: Option<String>,
+        /// RFC 3339 time the version takes effect, such as `2026-01-01T00:00:00Z`
+        #[arg(long)]
+        effective_at: Option<chrono::DateTime<chrono::Utc>>,
+        /// Locale of the title and text; defaults to `en`
+        #[arg(long)]
+        locale: Option<String>,
+    },
+    /// Add the text of a published version in another locale
+    Translate {
+        /// Document id
+        id: String,
+        /// Language tag such as `de` or `de-AT`
+        #[arg(long)]
+        locale: String,
+        #[arg(long)]
+        title: String,
+        /// File holding the translated text
+        #[arg(long)]
+        file: PathBuf,
+    },
+    List,
+    /// Show the changes between two versions as a unified diff
+    Diff {
+        /// Document type, such as `tos` or `privacy`
+        doc_type: String,
+        from: String,
+        to: String,
+    },
+    Revoke {
+        /// Document id
+        id: String,
+        #[arg(long)]
+        reason: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PluginCommands {
+    /// Every version, including revoked and yanked ones
+    List {
+        /// Only versions of this plugin
+        #[arg(long)]
+        id: Option<String>,
+    },
+    /// Revoke a version as unsafe; it stops verifying
+    Revoke {
+        id: String,
+        version: String,
+        #[arg(long)]
+        reason: String,
+    },
+    /// Hide a version from search; it still verifies and downloads
+    Yank {
+        id: String,
+        version: String,
+        #[arg(long)]
+        reason: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UserCommands {
+    /// Show an account
+    Lookup {
+        /// User id or username
+        user: String,
+    },
+    /// Lock an account, ending its sessions and tokens
+    Lock {
+        /// User id or username
+        user: String,
+        #[arg(long)]
+        reason: String,
+    },
+    Unlock {
+        /// User id or username
+        user: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GdprCommands {
+    /// Export a user's personal data and audit trail
+    Export { user_id: String },
+    /// Delete a user's personal data and audit trail
+    Delete {
+        user_id: String,
+        /// Confirm the deletion, which cannot be undone
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuditCommands {
+    /// Check the audit log's hash chain
+    Verify,
+    /// Export a user's audit entries
+    Export { user_id: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BackupCommands {
+    /// Back up the database now
+    #[command(alias = "trigger")]
+    Create,
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum LicenseCommands {
+    /// Issue a signed license token
+    Issue {
+        #[arg(long, value_parser = ["Free", "Pro", "Enterprise"])]
+        tier: String,
+        /// Days until the license expires; the server defaults to 365
+        #[arg(long)]
+        days: Option<i64>,
+        /// Customer or account the license is for
+        #[arg(long)]
+        subject: Option<String>,
+    },
+    List,
+    Revoke {
+        /// License id
+        id: String,
+        #[arg(long)]
+        reason: Option<String>,
+    },
 }
 
 pub fn run_from_args<I, T>(args: I) -> anyhow::Result<()>
@@ -47,45 +249,131 @@ pub fn run_from_std_args() -> anyhow::Result<()> {
     run(cli)
 }
 
-fn run(cli: Cli) -> anyhow::Result<()> {
-    let token = cli
-        .token
-        .or_else(|| std::env::var("MASTER_ADMIN_TOKEN").ok());
-    let client = Client::builder().build()?;
+/// Bearer token for the admin requests: `--token`, a login with `--username`, or
+/// VERSEGUY_ADMIN_TOKEN
+fn bearer_token(client: &Client, cli: &Cli) -> anyhow::Result<Option<String>> {
+    if let Some(token) = &cli.token {
+        return Ok(Some(token.clone()));
+    }
+    let Some(username) = &cli.username else {
+        return Ok(std::env::var(TOKEN_ENV).ok());
+    };
 
-    match cli.cmd {
-        Commands::KeyList => {
-            let url = format!("{}/admin/keys", cli.server);
-            let mut req = client.get(&url);
-            if let Some(ref t) = token {
-                req = req.header("x-admin-token", t);
-            }
-            let resp = req.send()?;
-            let status = resp.status();
-            let txt = resp.text()?;
-            println!("[key-list] status: {}", status);
-            println!(
-                "[key-list] body (truncated 2k): {}",
-                &txt.chars().take(2048).collect::<String>()
-            );
+    let password = if cli.password_stdin {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        line.trim_end_matches(['\r', '\n']).to_string()
+    } else {
+        std::env::var(PASSWORD_ENV)
+            .map_err(|_| anyhow::anyhow!("set {} or pass --password-stdin", PASSWORD_ENV))?
+    };
+    let resp = client
+        .post(format!("{}/auth/login", cli.server))
+        .json(&json!({ "username": username, "password": password }))
+        .send()?;
+    if !resp.status().is_success() {
+        anyhow::bail!("login as {} failed: {}", username, resp.status());
+    }
+    let body: Value = resp.json()?;
+    let token = body
+        .get("token")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow::anyhow!("login response has no token"))?;
+    Ok(Some(token.to_string()))
+}
+
+/// The master server, called with the admin's bearer token
+struct Api {
+    client: Client,
+    server: String,
+    token: Option<String>,
+}
+
+impl Api {
+    /// `server` with the percent-encoded `segments` appended to its path
+    fn url(&self, segments: &[&str]) -> anyhow::Result<Url> {
+        let mut url = Url::parse(&self.server)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("{} cannot be a base URL", self.server))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    fn get(&self, segments: &[&str]) -> anyhow::Result<Value> {
+        self.call(Method::GET, segments, None)
+    }
+
+    fn post(&self, segments: &[&str], body: Value) -> anyhow::Result<Value> {
+        self.call(Method::POST, segments, Some(body))
+    }
+
+    fn delete(&self, segments: &[&str]) -> anyhow::Result<Value> {
+        self.call(Method::DELETE, segments, None)
+    }
+
+    fn call(
+        &self,
+        method: Method,
+        segments: &[&str],
+        body: Option<Value>,
+    ) -> anyhow::Result<Value> {
+        self.send(method, self.url(segments)?, body)
+    }
+
+    /// Send the request and return its JSON body; error statuses fail with the problem detail
+    fn send(&self, method: Method, url: Url, body: Option<Value>) -> anyhow::Result<Value> {
+        let label = format!("{} {}", method, url.path());
+        let mut req: RequestBuilder = self.client.request(method, url);
+        if let Some(token) = &self.token {
+            req = req.bearer_auth(token);
         }
-        Commands::KeyRotate => {
-            let url = format!("{}/admin/keys/rotate", cli.server);
-            let mut req = client.post(&url);
-            if let Some(ref t) = token {
-                req = req.header("x-admin-token", t);
-            }
-            let resp = req.send()?;
-            let status = resp.status();
-            let txt = resp.text()?;
-            println!("[key-rotate] status: {}", status);
-            println!(
-                "[key-rotate] body (truncated 2k): {}",
-                &txt.chars().take(2048).collect::<String>()
-            );
+        if let Some(body) = body {
+            req = req.json(&body);
         }
+        let resp = req.send()?;
+        let status = resp.status();
+        let text = resp.text()?;
+        let body = match serde_json::from_str(&text) {
+            Ok(body) => body,
+            Err(_) if text.is_empty() => Value::Null,
+            Err(_) => Value::String(text),
+        };
+        if !status.is_success() {
+            let detail = match body.get("detail").and_then(Value::as_str) {
+                Some(detail) => detail.to_string(),
+                None => body.to_string(),
+            };
+            anyhow::bail!("{} failed with {}: {}", label, status, detail);
+        }
+        Ok(body)
+    }
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
+    if let Commands::Completions { shell } = cli.cmd {
+        let mut cmd = Cli::command();
+        clap_complete::generate(shell, &mut cmd, "verseguy-admin", &mut std::io::stdout());
+        return Ok(());
+    }
+
+    let client = Client::builder().build()?;
+    let token = bearer_token(&client, &cli)?;
+    let api = Api {
+        client,
+        server: cli.server,
+        token,
+    };
+
+    // a table would cut a diff into cells, so it is printed as is
+    let raw_field = match &cli.cmd {
+        Commands::Legal(LegalCommands::Diff { .. }) => Some("diff"),
+        _ => None,
+    };
+    let body = match cli.cmd {
+        Commands::KeyList => api.get(&["admin", "keys"])?,
+        Commands::KeyRotate => api.call(Method::POST, &["admin", "keys", "rotate"], None)?,
         Commands::KeyImport { file, b64 } => {
-            let url = format!("{}/admin/keys/import", cli.server);
             let key_b64 = if let Some(f) = file {
                 let bytes = std::fs::read(f)?;
                 base64::engine::general_purpose::STANDARD.encode(&bytes)
@@ -94,23 +382,238 @@ fn run(cli: Cli) -> anyhow::Result<()> {
             } else {
                 anyhow::bail!("provide --file or --b64")
             };
-            let mut map = serde_json::Map::new();
-            map.insert("key_b64".to_string(), serde_json::Value::String(key_b64));
-            let body = serde_json::Value::Object(map);
-            let mut req = client.post(&url).json(&body);
-            if let Some(ref t) = token {
-                req = req.header("x-admin-token", t);
+            api.post(&["admin", "keys", "import"], json!({ "key_b64": key_b64 }))?
+        }
+        Commands::Legal(cmd) => legal(&api, cmd)?,
+        Commands::Plugin(cmd) => plugin(&api, cmd)?,
+        Commands::User(cmd) => user(&api, cmd)?,
+        Commands::Gdpr(cmd) => gdpr(&api, cmd)?,
+        Commands::Audit(AuditCommands::Verify) => api.get(&["admin", "audit", "verify"])?,
+        Commands::Audit(AuditCommands::Export { user_id }) => {
+            api.get(&["audit", "export", &user_id])?
+        }
+        Commands::Backup(BackupCommands::Create) => {
+            api.call(Method::POST, &["admin", "backups"], None)?
+        }
+        Commands::Backup(BackupCommands::List) => api.get(&["admin", "backups"])?,
+        Commands::License(cmd) => license(&api, cmd)?,
+        // printed before logging in
+        Commands::Completions { .. } => Value::Null,
+    };
+
+    match (cli.output, raw_field.and_then(|f| body[f].as_str())) {
+        (Output::Json, _) => println!("{}", serde_json::to_string_pretty(&body)?),
+        (Output::Table, Some(raw)) => print!("{}", raw),
+        (Output::Table, None) => print!("{}", render_table(&body)),
+    }
+    Ok(())
+}
+
+fn legal(api: &Api, cmd: LegalCommands) -> anyhow::Result<Value> {
+    match cmd {
+        LegalCommands::Create {
+            doc_type,
+            version,
+            title,
+            file,
+            author,
+            effective_at,
+            locale,
+        } => {
+            let content = std::fs::read_to_string(file)?;
+            let body = json!({
+                "doc_type": doc_type,
+                "version": version,
+                "title": title,
+                "content": content,
+                "author": author,
+                "effective_at": effective_at.map(|t| t.timestamp()),
+                "locale": locale,
+            });
+            api.post(&["admin", "legal"], body)
+        }
+        LegalCommands::Translate {
+            id,
+            locale,
+            title,
+            file,
+        } => {
+            let content = std::fs::read_to_string(file)?;
+            let body = json!({ "locale": locale, "title": title, "content": content });
+            api.post(&["admin", "legal", &id, "translations"], body)
+        }
+        LegalCommands::List => api.get(&["admin", "legal"]),
+        LegalCommands::Diff { doc_type, from, to } => {
+            let mut url = api.url(&["legal", &doc_type, "diff"])?;
+            url.query_pairs_mut()
+                .append_pair("from", &from)
+                .append_pair("to", &to);
+            api.send(Method::GET, url, None)
+        }
+        LegalCommands::Revoke { id, reason } => api.post(
+            &["admin", "legal", &id, "revoke"],
+            json!({ "id": id, "reason": reason }),
+        ),
+    }
+}
+
+fn plugin(api: &Api, cmd: PluginCommands) -> anyhow::Result<Value> {
+    match cmd {
+        PluginCommands::List { id } => {
+            let mut url = api.url(&["admin", "plugins"])?;
+            if let Some(id) = id {
+                url.query_pairs_mut().append_pair("id", &id);
             }
-            let resp = req.send()?;
-            let status = resp.status();
-            let txt = resp.text()?;
-            println!("[key-import] status: {}", status);
-            println!(
-                "[key-import] body (truncated 2k): {}",
-                &txt.chars().take(2048).collect::<String>()
-            );
+            api.send(Method::GET, url, None)
         }
+        PluginCommands::Revoke {
+            id,
+            version,
+            reason,
+        } => api.post(
+            &["verify", "revoke"],
+            json!({ "id": id, "version": version, "reason": reason }),
+        ),
+        PluginCommands::Yank {
+            id,
+            version,
+            reason,
+        } => api.post(
+            &["admin", "plugins", &id, &version, "yank"],
+            json!({ "reason": reason }),
+        ),
     }
+}
 
-    Ok(())
+fn user(api: &Api, cmd: UserCommands) -> anyhow::Result<Value> {
+    match cmd {
+        UserCommands::Lookup { user } => api.get(&["admin", "users", &user]),
+        UserCommands::Lock { user, reason } => api.post(
+            &["admin", "users", &user, "lock"],
+            json!({ "reason": reason }),
+        ),
+        UserCommands::Unlock { user } => api.delete(&["admin", "users", &user, "lock"]),
+    }
+}
+
+fn gdpr(api: &Api, cmd: GdprCommands) -> anyhow::Result<Value> {
+    match cmd {
+        GdprCommands::Export { user_id } => api.get(&["users", &user_id, "data"]),
+        GdprCommands::Delete { user_id, yes } => {
+            if !yes {
+                anyhow::bail!("deleting {}'s data cannot be undone; pass --yes", user_id);
+            }
+            api.delete(&["users", &user_id, "data"])
+        }
+    }
+}
+
+fn license(api: &Api, cmd: LicenseCommands) -> anyhow::Result<Value> {
+    match cmd {
+        LicenseCommands::Issue {
+            tier,
+            days,
+            subject,
+        } => api.post(
+            &["admin", "licenses"],
+            json!({ "tier": tier, "days": days, "subject": subject }),
+        ),
+        LicenseCommands::List => api.get(&["admin", "licenses"]),
+        LicenseCommands::Revoke { id, reason } => api.post(
+            &["admin", "licenses", &id, "revoke"],
+            json!({ "reason": reason }),
+        ),
+    }
+}
+
+/// Render a response for a terminal.
+///
+/// Lists, and objects whose only field is a list such as `{"documents": [...]}`, become one row
+/// per item with a column per field. Other objects become a field/value table. Nested values are
+/// shown as compact JSON, and cells are cut off at a fixed width.
+pub fn render_table(value: &Value) -> String {
+    let rows = match value {
+        Value::Array(items) => Some(items),
+        Value::Object(fields) if fields.len() == 1 => {
+            fields.values().next().and_then(Value::as_array)
+        }
+        _ => None,
+    };
+    match (rows, value) {
+        (Some(items), _) if items.is_empty() => "(none)\n".to_string(),
+        (Some(items), _) => {
+            let mut columns: Vec<&str> = Vec::new();
+            for item in items {
+                if let Value::Object(fields) = item {
+                    for key in fields.keys() {
+                        if !columns.contains(&key.as_str()) {
+                            columns.push(key);
+                        }
+                    }
+                }
+            }
+            if columns.is_empty() {
+                let lines: Vec<Vec<String>> = items.iter().map(|i| vec![cell(i)]).collect();
+                return layout(&["value"], &lines);
+            }
+            let lines: Vec<Vec<String>> = items
+                .iter()
+                .map(|item| {
+                    columns
+                        .iter()
+                        .map(|c| item.get(*c).map(cell).unwrap_or_default())
+                        .collect()
+                })
+                .collect();
+            layout(&columns, &lines)
+        }
+        (None, Value::Object(fields)) => {
+            let lines: Vec<Vec<String>> = fields
+                .iter()
+                .map(|(key, value)| vec![key.clone(), cell(value)])
+                .collect();
+            layout(&["field", "value"], &lines)
+        }
+        (None, Value::Null) => String::new(),
+        (None, other) => format!("{}\n", cell(other)),
+    }
+}
+
+/// One-line text of `value`, cut off at [`MAX_CELL`] characters
+fn cell(value: &Value) -> String {
+    let text = match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let text = text.replace(['\r', '\n', '\t'], " ");
+    if text.chars().count() > MAX_CELL {
+        let mut cut: String = text.chars().take(MAX_CELL - 1).collect();
+        cut.push('…');
+        cut
+    } else {
+        text
+    }
+}
+
+/// Left-aligned columns under an upper-case header
+fn layout(header: &[&str], lines: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for line in lines {
+        for (width, text) in widths.iter_mut().zip(line) {
+            *width = (*width).max(text.chars().count());
+        }
+    }
+    let header: Vec<String> = header.iter().map(|h| h.to_uppercase()).collect();
+    let mut out = String::new();
+    for line in std::iter::once(&header).chain(lines) {
+        let padded: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .map(|(text, width)| format!("{:<width$}", text, width = *width))
+            .collect();
+        out.push_str(padded.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
 }
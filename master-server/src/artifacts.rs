@@ -0,0 +1,367 @@
+//! Plugin artifacts: the binary or WASM module a manifest describes.
+//!
+//! Publishers upload the artifact first (`POST /plugins/artifacts` with the raw bytes as the
+//! body; chunked transfer encoding is fine) and get back its SHA-256. The manifest they publish
+//! then names that hash in `artifact`, so the master key signature covers the exact bytes
+//! clients fetch from `/plugins/{id}/{version}/download`. Blobs are stored once per hash under
+//! `storage.artifact_dir`; the upload size limit depends on the publisher's license tier.
+
+use crate::plugins::{get_manifest, is_revoked};
+use crate::state::AppState;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use verseguy_auth::License;
+use verseguy_shared_error::{AppError, ProblemDetails};
+
+/// Content address of an uploaded artifact, as bound into a plugin manifest
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct ArtifactRef {
+    /// Lowercase hex SHA-256 of the artifact bytes
+    pub sha256: String,
+    /// Size in bytes
+    pub size: u64,
+}
+
+/// Content-addressed blob storage on disk: `<root>/sha256/<first two hex digits>/<hash>`
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+fn is_sha256_hex(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn too_large(max_size: u64) -> AppError {
+    AppError::coded(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        "artifact_too_large",
+        format!("your license allows artifacts of up to {} bytes", max_size),
+    )
+}
+
+impl BlobStore {
+    pub fn open(root: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(root.join("sha256"))?;
+        std::fs::create_dir_all(root.join("tmp"))?;
+        Ok(BlobStore { root })
+    }
+
+    /// Path of the blob `sha256`; `None` when it is not a SHA-256 hex digest
+    pub fn path(&self, sha256: &str) -> Option<PathBuf> {
+        if !is_sha256_hex(sha256) {
+            return None;
+        }
+        Some(self.root.join("sha256").join(&sha256[..2]).join(sha256))
+    }
+
+    /// Size of the blob `sha256`, if stored
+    pub async fn size_of(&self, sha256: &str) -> std::io::Result<Option<u64>> {
+        let Some(path) = self.path(sha256) else {
+            return Ok(None);
+        };
+        match tokio::fs::metadata(path).await {
+            Ok(meta) => Ok(Some(meta.len())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Store `body`, hashing it on the way; rejects bodies over `max_size` bytes
+    pub async fn put(&self, body: Body, max_size: u64) -> Result<ArtifactRef, AppError> {
+        let tmp = self.root.join("tmp").join(Uuid::new_v4().to_string());
+        let written = write_hashed(&tmp, body, max_size).await;
+        let artifact = match written {
+            Ok(artifact) => artifact,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&tmp).await;
+                return Err(e);
+            }
+        };
+
+        let path = self
+            .path(&artifact.sha256)
+            .ok_or_else(|| AppError::internal("invalid digest"))?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .map_err(AppError::internal)?;
+        }
+        if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            // same content uploaded before
+            let _ = tokio::fs::remove_file(&tmp).await;
+        } else {
+            tokio::fs::rename(&tmp, &path)
+                .await
+                .map_err(AppError::internal)?;
+        }
+        Ok(artifact)
+    }
+}
+
+async fn write_hashed(
+    tmp: &std::path::Path,
+    body: Body,
+    max_size: u64,
+) -> Result<ArtifactRef, AppError> {
+    let mut file = tokio::fs::File::create(tmp)
+        .await
+        .map_err(AppError::internal)?;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    let mut chunks = body.into_data_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk =
+            chunk.map_err(|e| AppError::BadRequest(format!("failed to read body: {}", e)))?;
+        size += chunk.len() as u64;
+        if size > max_size {
+            return Err(too_large(max_size));
+        }
+        hasher.update(&chunk);
+        file.write_all(&chunk).await.map_err(AppError::internal)?;
+    }
+    file.sync_all().await.map_err(AppError::internal)?;
+    if size == 0 {
+        return Err(AppError::BadRequest("empty artifact".into()));
+    }
+    Ok(ArtifactRef {
+        sha256: hex::encode(hasher.finalize()),
+        size,
+    })
+}
+
+/// Upload limit of the caller: by the license of the bearer token's user, `Free` otherwise
+fn max_upload_size(state: &AppState, headers: &HeaderMap) -> u64 {
+    let license = crate::auth::authenticate(state, headers)
+        .ok()
+        .and_then(|principal| crate::auth::license_of(state, &principal.user_id))
+        .unwrap_or(License::Free);
+    state.config.plugins.max_artifact_size(license)
+}
+
+/// Upload a plugin artifact; reference the returned hash in the manifest's `artifact`
+#[utoipa::path(
+    post,
+    path = "/plugins/artifacts",
+    tag = "plugins",
+    params(
+        (
+            "x-user-id" = Option<String>,
+            Header,
+            description = "Without a bearer token, a user who must have accepted the ToS"
+        ),
+        ("x-plugin-token" = Option<String>, Header, description = "MASTER_PLUGIN_PUBLISH_KEY"),
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 201, description = "Stored (or already present)", body = ArtifactRef),
+        (status = 400, description = "Empty or unreadable body", body = ProblemDetails),
+        (
+            status = 403,
+            description = "ToS not accepted or invalid publish token",
+            body = ProblemDetails
+        ),
+        (
+            status = 413,
+            description = "Over the size limit of the caller's license",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn upload_artifact_handler(
+    State(state): State<Arc<AppState>>,
+    req: axum::http::Request<Body>,
+) -> Result<(StatusCode, Json<ArtifactRef>), AppError> {
+    crate::routes::require_publisher(&state, req.headers())?;
+    let max_size = max_upload_size(&state, req.headers());
+    let declared = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > max_size) {
+        return Err(too_large(max_size));
+    }
+
+    let artifact = state.artifacts.put(req.into_body(), max_size).await?;
+    tracing::info!(
+        "stored plugin artifact {} ({} bytes)",
+        artifact.sha256,
+        artifact.size
+    );
+    Ok((StatusCode::CREATED, Json(artifact)))
+}
+
+/// Byte range selected by a `Range` header
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Full,
+    /// Inclusive bounds
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// Parse a single `bytes=` range; multiple ranges and other units get the full body
+fn byte_range(range: Option<&str>, size: u64) -> ByteRange {
+    let Some(spec) = range.and_then(|r| r.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+    let last = size.saturating_sub(1);
+    let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=a-b
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(last)),
+        // bytes=a-
+        (Ok(start), Err(_)) if end.is_empty() => (start, last),
+        // bytes=-n: the last n bytes
+        (Err(_), Ok(n)) if start.is_empty() && n > 0 => (size.saturating_sub(n), last),
+        _ => return ByteRange::Full,
+    };
+    if start < size && start <= end {
+        ByteRange::Partial(start, end)
+    } else {
+        ByteRange::Unsatisfiable
+    }
+}
+
+/// Download the artifact of a plugin version; supports single `Range` requests
+#[utoipa::path(
+    get,
+    path = "/plugins/{id}/{version}/download",
+    tag = "plugins",
+    params(
+        ("id" = String, Path, description = "Plugin id"),
+        ("version" = String, Path, description = "Plugin version"),
+        ("range" = Option<String>, Header, description = "Single byte range, e.g. `bytes=0-1023`"),
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The artifact; `ETag` is its quoted SHA-256",
+            body = Vec<u8>,
+            content_type = "application/octet-stream"
+        ),
+        (
+            status = 206,
+            description = "The requested range",
+            body = Vec<u8>,
+            content_type = "application/octet-stream"
+        ),
+        (status = 304, description = "Matches `If-None-Match`"),
+        (
+            status = 404,
+            description = "Unknown plugin version or no artifact",
+            body = ProblemDetails
+        ),
+        (status = 410, description = "The version was revoked", body = ProblemDetails),
+        (status = 416, description = "Range outside the artifact", body = ProblemDetails),
+    )
+)]
+pub async fn download_artifact_handler(
+    State(state): State<Arc<AppState>>,
+    Path((id, version)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let manifest = get_manifest(&state.storage, &id, &version)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| AppError::NotFound("plugin version not found".into()))?;
+    if is_revoked(&state.storage, &id, &version).map_err(AppError::internal)? {
+        return Err(AppError::coded(
+            StatusCode::GONE,
+            "plugin_revoked",
+            format!("{} {} has been revoked", id, version),
+        ));
+    }
+    let artifact = manifest
+        .artifact
+        .ok_or_else(|| AppError::NotFound("plugin version has no artifact".into()))?;
+
+    let etag = format!("\"{}\"", artifact.sha256);
+    let header_value = |name: header::HeaderName| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+    };
+    if header_value(header::IF_NONE_MATCH).is_some_and(|v| v == etag || v == "*") {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    let path = state
+        .artifacts
+        .path(&artifact.sha256)
+        .ok_or_else(|| AppError::internal("invalid artifact digest in manifest"))?;
+    let mut file = tokio::fs::File::open(&path).await.map_err(|e| {
+        tracing::error!("artifact blob {} unreadable: {}", path.display(), e);
+        AppError::internal("artifact unavailable")
+    })?;
+
+    // `If-Range` with another validator asks for the full, current artifact
+    let range = match header_value(header::IF_RANGE) {
+        Some(v) if v != etag => None,
+        _ => header_value(header::RANGE),
+    };
+    let size = artifact.size;
+    let (status, start, len) = match byte_range(range, size) {
+        ByteRange::Full => (StatusCode::OK, 0, size),
+        ByteRange::Partial(start, end) => (StatusCode::PARTIAL_CONTENT, start, end - start + 1),
+        ByteRange::Unsatisfiable => {
+            let problem = AppError::coded(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                "range_not_satisfiable",
+                format!("the artifact is {} bytes", size),
+            );
+            let content_range = format!("bytes */{}", size);
+            return Ok(([(header::CONTENT_RANGE, content_range)], problem).into_response());
+        }
+    };
+    if start > 0 {
+        file.seek(SeekFrom::Start(start))
+            .await
+            .map_err(AppError::internal)?;
+    } else {
+        // resumed downloads are not counted again
+        state
+            .search
+            .record_download(&state.storage, &id)
+            .map_err(AppError::internal)?;
+    }
+
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, len)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, &etag)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}-{}\"", id, version),
+        );
+    if status == StatusCode::PARTIAL_CONTENT {
+        let content_range = format!("bytes {}-{}/{}", start, start + len - 1, size);
+        response = response.header(header::CONTENT_RANGE, content_range);
+    }
+    response
+        .body(Body::from_stream(ReaderStream::new(file.take(len))))
+        .map_err(AppError::internal)
+}
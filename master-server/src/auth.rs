@@ -0,0 +1,109 @@
+use crate::state::AppState;
+use axum::extract::{Request, State};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::sync::Arc;
+use verseguy_auth::access_token::{AccessTokenService, TOKEN_PREFIX};
+use verseguy_auth::{IdentityStore, License, SessionService};
+use verseguy_shared_error::AppError;
+
+/// Authenticated caller, resolved from a session JWT or a personal access token
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub user_id: String,
+    /// Scopes granted by a personal access token; `None` for interactive sessions
+    pub scopes: Option<Vec<String>>,
+    pub token_id: Option<String>,
+}
+
+impl Principal {
+    pub fn is_session(&self) -> bool {
+        self.scopes.is_none()
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match &self.scopes {
+            None => true,
+            Some(scopes) => scopes
+                .iter()
+                .any(|s| s == verseguy_auth::access_token::SCOPE_ALL || s == scope),
+        }
+    }
+
+    pub fn require_scope(&self, scope: &str) -> Result<(), AppError> {
+        if self.has_scope(scope) {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden(format!("missing scope: {}", scope)))
+        }
+    }
+
+    pub fn require_session(&self) -> Result<(), AppError> {
+        if self.is_session() {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden("interactive session required".into()))
+        }
+    }
+}
+
+/// Resolve the `Authorization: Bearer` header into a principal.
+///
+/// Tokens starting with `vgp_` are personal access tokens; anything else is
+/// validated as a session JWT. Credentials of locked accounts are rejected.
+pub fn authenticate(state: &AppState, headers: &HeaderMap) -> Result<Principal, AppError> {
+    let auth_header = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| AppError::Unauthorized("missing authorization".into()))?;
+
+    let token = auth_header
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| AppError::Unauthorized("invalid authorization format".into()))?;
+
+    if token.starts_with(TOKEN_PREFIX) {
+        let tokens = AccessTokenService::new((*state.storage).clone());
+        let record = tokens
+            .validate(token)
+            .map_err(|_| AppError::Unauthorized("invalid or expired token".into()))?;
+        crate::operations::ensure_unlocked(state, &record.user_id)?;
+        return Ok(Principal {
+            user_id: record.user_id,
+            scopes: Some(record.scopes),
+            token_id: Some(record.id),
+        });
+    }
+
+    let session_service = SessionService::new(state.license_secret.clone());
+    let token_data = session_service
+        .validate_token_and_storage(token, &state.storage)
+        .map_err(|_| AppError::Unauthorized("invalid or expired token".into()))?;
+    crate::operations::ensure_unlocked(state, &token_data.claims.sub)?;
+    Ok(Principal {
+        user_id: token_data.claims.sub,
+        scopes: None,
+        token_id: None,
+    })
+}
+
+/// License of `user_id`; `None` for unknown users
+pub fn license_of(state: &AppState, user_id: &str) -> Option<License> {
+    IdentityStore::new((*state.storage).clone())
+        .get_user(user_id)
+        .ok()
+        .flatten()
+        .map(|user| user.license)
+}
+
+/// Middleware that rejects unauthenticated requests and exposes the
+/// `Principal` as a request extension
+pub async fn require_auth(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let principal = authenticate(&state, req.headers())?;
+    req.extensions_mut().insert(principal);
+    Ok(next.run(req).await)
+}
@@ -0,0 +1,327 @@
+//! Server configuration: built-in defaults, overlaid by a TOML file, overlaid by `MASTER_*`
+//! environment variables.
+//!
+//! ```toml
+//! [server]
+//! bind = "0.0.0.0:3000"
+//! shutdown_timeout_secs = 30
+//!
+//! [server.tls]
+//! cert = "/etc/verseguy/cert.pem"
+//! key = "/etc/verseguy/key.pem"
+//!
+//! [storage]
+//! db_path = "/var/lib/verseguy/master"
+//!
+//! [security]
+//! license_secret = "..."
+//! admin_users = ["6f1c2a9e-4b7d-4e0a-9c3f-2d8b5e7a1f04"]
+//! key_file = "/var/lib/verseguy/master.key"
+//!
+//! [plugins]
+//! max_artifact_free = 10_485_760
+//!
+//! [rate_limits]
+//! auth = "10/min"
+//! quota_pro = "20000/day"
+//! ```
+//!
+//! The file is read from `--config <path>` or `MASTER_CONFIG`. Environment overrides:
+//!
+//! | Variable | Setting |
+//! |---|---|
+//! | `MASTER_BIND` | `server.bind` |
+//! | `MASTER_SERVER_PORT` | port of `server.bind` |
+//! | `MASTER_SHUTDOWN_TIMEOUT_SECS` | `server.shutdown_timeout_secs` |
+//! | `MASTER_TLS_CERT`, `MASTER_TLS_KEY` | `server.tls.cert`, `server.tls.key` |
+//! | `MASTER_TLS_SELF_SIGNED` | `server.tls.self_signed` |
+//! | `MASTER_DB_PATH` | `storage.db_path` |
+//! | `MASTER_ARTIFACT_DIR` | `storage.artifact_dir` |
+//! | `MASTER_BACKUP_DIR` | `storage.backup_dir` |
+//! | `MASTER_MAX_ARTIFACT_FREE`, `_PRO`, `_ENTERPRISE` | `plugins.max_artifact_*` |
+//! | `MASTER_LICENSE_SECRET` | `security.license_secret` |
+//! | `MASTER_ADMIN_USERS` (comma-separated) | `security.admin_users` |
+//! | `MASTER_PLUGIN_PUBLISH_KEY` | `security.plugin_publish_key` |
+//! | `MASTER_KEY_FILE` | `security.key_file` |
+//! | `MASTER_TRUSTED_KEYS` (comma-separated) | `security.trusted_keys` |
+//! | `MASTER_RATE_LIMIT_*`, `MASTER_QUOTA_*`, `MASTER_TRUST_PROXY` | `rate_limits` |
+//!
+//! See [`crate::rate_limit`] for the rate limit settings.
+
+use anyhow::Context;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::rate_limit::RateLimitConfig;
+use verseguy_auth::License;
+
+/// Environment variable naming the configuration file
+pub const CONFIG_ENV: &str = "MASTER_CONFIG";
+
+/// License secret used when none is configured; fine for development only
+const DEV_LICENSE_SECRET: &str = "master-secret";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub security: SecurityConfig,
+    pub plugins: PluginsConfig,
+    pub rate_limits: RateLimitConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    /// How long in-flight requests may take to finish after a shutdown signal
+    pub shutdown_timeout_secs: u64,
+    /// Serve HTTPS; plain HTTP when absent
+    pub tls: Option<TlsConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: SocketAddr::from(([127, 0, 0, 1], 3000)),
+            shutdown_timeout_secs: 30,
+            tls: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+}
+
+/// PEM certificate chain and private key, or a throwaway self-signed certificate for
+/// `localhost` when `self_signed` is set (development only)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    pub self_signed: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub db_path: PathBuf,
+    /// Plugin artifact blobs; `artifacts` inside `db_path` when unset
+    pub artifact_dir: Option<PathBuf>,
+    /// Database backups taken through `/admin/backups`; `db_path` with a `-backups` suffix when
+    /// unset
+    pub backup_dir: Option<PathBuf>,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            db_path: PathBuf::from("./master_server_db"),
+            artifact_dir: None,
+            backup_dir: None,
+        }
+    }
+}
+
+impl StorageConfig {
+    pub fn artifact_dir(&self) -> PathBuf {
+        self.artifact_dir
+            .clone()
+            .unwrap_or_else(|| self.db_path.join("artifacts"))
+    }
+
+    pub fn backup_dir(&self) -> PathBuf {
+        self.backup_dir.clone().unwrap_or_else(|| {
+            let mut dir = self.db_path.clone().into_os_string();
+            dir.push("-backups");
+            PathBuf::from(dir)
+        })
+    }
+}
+
+/// Plugin registry settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginsConfig {
+    /// Largest artifact, in bytes, a publisher of each license tier may upload
+    pub max_artifact_free: u64,
+    pub max_artifact_pro: u64,
+    pub max_artifact_enterprise: u64,
+}
+
+impl Default for PluginsConfig {
+    fn default() -> Self {
+        PluginsConfig {
+            max_artifact_free: 10 << 20,
+            max_artifact_pro: 100 << 20,
+            max_artifact_enterprise: 1 << 30,
+        }
+    }
+}
+
+impl PluginsConfig {
+    pub fn max_artifact_size(&self, license: License) -> u64 {
+        match license {
+            License::Free => self.max_artifact_free,
+            License::Pro => self.max_artifact_pro,
+            License::Enterprise => self.max_artifact_enterprise,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityConfig {
+    /// Secret signing license tokens and session JWTs
+    pub license_secret: String,
+    /// Ids of the users that hold the `admin` role, see [`crate::admin`]
+    pub admin_users: Vec<String>,
+    /// Token expected in `x-plugin-token` when publishing; publishing is open when unset
+    pub plugin_publish_key: Option<String>,
+    /// Persistent master signing key; an ephemeral key is generated when unset
+    pub key_file: Option<PathBuf>,
+    /// Base64 public keys of earlier master keys whose signatures still verify
+    pub trusted_keys: Vec<String>,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        SecurityConfig {
+            license_secret: DEV_LICENSE_SECRET.to_string(),
+            admin_users: Vec::new(),
+            plugin_publish_key: None,
+            key_file: None,
+            trusted_keys: Vec::new(),
+        }
+    }
+}
+
+/// Items of a comma-separated variable, trimmed, without empty ones
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn parse<T: FromStr>(name: &str, value: &str) -> anyhow::Result<T>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e| anyhow::anyhow!("{}={}: {}", name, value, e))
+}
+
+impl Config {
+    /// Load the configuration file at `path` (or `MASTER_CONFIG`), then apply the
+    /// environment overrides
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let from_env = std::env::var(CONFIG_ENV).ok().map(PathBuf::from);
+        let mut config = match path.map(Path::to_path_buf).or(from_env) {
+            Some(path) => Self::from_file(&path)?,
+            None => Config::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        if config.security.license_secret == DEV_LICENSE_SECRET {
+            tracing::warn!("no license secret configured; using the development default");
+        }
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        Self::from_toml(&text).with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    pub fn from_toml(text: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Overlay the `MASTER_*` variables returned by `var`
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> anyhow::Result<()> {
+        let var = |name: &str| var(name).filter(|v| !v.is_empty());
+
+        if let Some(v) = var("MASTER_BIND") {
+            self.server.bind = parse("MASTER_BIND", &v)?;
+        }
+        if let Some(v) = var("MASTER_SERVER_PORT") {
+            self.server.bind.set_port(parse("MASTER_SERVER_PORT", &v)?);
+        }
+        if let Some(v) = var("MASTER_SHUTDOWN_TIMEOUT_SECS") {
+            self.server.shutdown_timeout_secs = parse("MASTER_SHUTDOWN_TIMEOUT_SECS", &v)?;
+        }
+        let cert = var("MASTER_TLS_CERT");
+        let key = var("MASTER_TLS_KEY");
+        let self_signed =
+            var("MASTER_TLS_SELF_SIGNED").map(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+        if cert.is_some() || key.is_some() || self_signed.is_some() {
+            let tls = self.server.tls.get_or_insert_with(TlsConfig::default);
+            tls.cert = cert.map(PathBuf::from).or(tls.cert.take());
+            tls.key = key.map(PathBuf::from).or(tls.key.take());
+            tls.self_signed = self_signed.unwrap_or(tls.self_signed);
+        }
+
+        if let Some(v) = var("MASTER_DB_PATH") {
+            self.storage.db_path = PathBuf::from(v);
+        }
+        if let Some(v) = var("MASTER_ARTIFACT_DIR") {
+            self.storage.artifact_dir = Some(PathBuf::from(v));
+        }
+        if let Some(v) = var("MASTER_BACKUP_DIR") {
+            self.storage.backup_dir = Some(PathBuf::from(v));
+        }
+        let max_artifact = [
+            (
+                &mut self.plugins.max_artifact_free,
+                "MASTER_MAX_ARTIFACT_FREE",
+            ),
+            (
+                &mut self.plugins.max_artifact_pro,
+                "MASTER_MAX_ARTIFACT_PRO",
+            ),
+            (
+                &mut self.plugins.max_artifact_enterprise,
+                "MASTER_MAX_ARTIFACT_ENTERPRISE",
+            ),
+        ];
+        for (max, name) in max_artifact {
+            if let Some(v) = var(name) {
+                *max = parse(name, &v)?;
+            }
+        }
+        if let Some(v) = var("MASTER_LICENSE_SECRET") {
+            self.security.license_secret = v;
+        }
+        if var("MASTER_ADMIN_TOKEN").is_some() {
+            anyhow::bail!(
+                "MASTER_ADMIN_TOKEN was removed; list admin user ids in MASTER_ADMIN_USERS"
+            );
+        }
+        if let Some(v) = var("MASTER_ADMIN_USERS") {
+            self.security.admin_users = split_list(&v);
+        }
+        if let Some(v) = var("MASTER_PLUGIN_PUBLISH_KEY") {
+            self.security.plugin_publish_key = Some(v);
+        }
+        if let Some(v) = var("MASTER_KEY_FILE") {
+            self.security.key_file = Some(PathBuf::from(v));
+        }
+        if let Some(v) = var("MASTER_TRUSTED_KEYS") {
+            self.security.trusted_keys = split_list(&v);
+        }
+
+        self.rate_limits.apply_env(var)
+    }
+}
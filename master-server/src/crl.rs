@@ -0,0 +1,162 @@
+//! Signed revocation list feed for offline verification.
+//!
+//! Every revocation is numbered when it is recorded. Entries are stored under
+//! `crl_entry:{seq}`, with the number zero-padded so they scan in order.
+//! `crl_listed:{id}:{version}` maps a revoked version to its number, and `crl_seq` holds the
+//! newest number, which is the list's version. `GET /verify/crl` serves the full list, or the
+//! revocations after `since`, signed with the master key. Clients check them with
+//! [`verseguy_signing::crl::RevocationCache`].
+
+use crate::plugins::{get_manifest, PluginManifest};
+use crate::state::AppState;
+use anyhow::Result;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+use serde::Deserialize;
+use std::sync::{Arc, Mutex, PoisonError};
+use utoipa::IntoParams;
+use verseguy_shared_error::{AppError, ProblemDetails};
+use verseguy_signing::crl::{Revocation, RevocationList};
+use verseguy_storage::RocksDBStorage;
+
+/// Serializes numbering, so concurrent revocations get distinct numbers
+static SEQUENCE: Mutex<()> = Mutex::new(());
+
+fn entry_key(seq: u64) -> String {
+    format!("crl_entry:{:020}", seq)
+}
+
+/// Number of the newest revocation; 0 when nothing was revoked
+pub fn current_version(storage: &RocksDBStorage) -> Result<u64> {
+    Ok(storage.get::<_, u64>(b"crl_seq")?.unwrap_or(0))
+}
+
+/// Number and list the revocation of `id` at `version`; a version that is already listed keeps
+/// its entry
+pub fn record_revocation(
+    storage: &RocksDBStorage,
+    id: &str,
+    version: &str,
+    reason: &str,
+    revoked_at: i64,
+) -> Result<Revocation> {
+    let _numbering = SEQUENCE.lock().unwrap_or_else(PoisonError::into_inner);
+    let listed_key = format!("crl_listed:{}:{}", id, version);
+    if let Some(seq) = storage.get::<_, u64>(listed_key.as_bytes())? {
+        if let Some(existing) = storage.get(entry_key(seq).as_bytes())? {
+            return Ok(existing);
+        }
+    }
+
+    let seq = current_version(storage)? + 1;
+    let artifact_sha256 = get_manifest(storage, id, version)?
+        .and_then(|m| m.artifact)
+        .map(|a| a.sha256);
+    let revocation = Revocation {
+        seq,
+        id: id.to_string(),
+        version: version.to_string(),
+        reason: reason.to_string(),
+        revoked_at,
+        artifact_sha256,
+    };
+    storage.put(entry_key(seq).as_bytes(), &revocation)?;
+    storage.put(listed_key.as_bytes(), &seq)?;
+    storage.put(b"crl_seq", &seq)?;
+    Ok(revocation)
+}
+
+/// Revocations numbered after `since`, in order
+pub fn revocations_since(storage: &RocksDBStorage, since: u64) -> Result<Vec<Revocation>> {
+    let entries: Vec<Revocation> = storage.prefix_scan(b"crl_entry:")?;
+    Ok(entries.into_iter().filter(|e| e.seq > since).collect())
+}
+
+/// The revocations after `since`, signed with `key`
+pub fn signed_list(
+    storage: &RocksDBStorage,
+    key: &SigningKey,
+    since: u64,
+) -> Result<RevocationList> {
+    let entries = revocations_since(storage, since)?;
+    Ok(RevocationList::new(since, Utc::now().timestamp(), entries).sign(key)?)
+}
+
+/// `plugin_revoked:{id}:{version}` record
+#[derive(Deserialize)]
+struct RevokedRecord {
+    reason: String,
+    at: i64,
+}
+
+/// Number the revocations recorded before the feed existed, oldest first
+pub fn backfill(storage: &RocksDBStorage) -> Result<usize> {
+    let manifests: Vec<PluginManifest> = storage.prefix_scan(b"plugin:")?;
+    let mut unlisted = Vec::new();
+    for m in manifests {
+        let listed_key = format!("crl_listed:{}:{}", m.id, m.version);
+        if storage.get::<_, u64>(listed_key.as_bytes())?.is_some() {
+            continue;
+        }
+        let revoked_key = format!("plugin_revoked:{}:{}", m.id, m.version);
+        if let Some(record) = storage.get::<_, RevokedRecord>(revoked_key.as_bytes())? {
+            unlisted.push((record.at, m.id, m.version, record.reason));
+        }
+    }
+    unlisted.sort();
+    for (at, id, version, reason) in &unlisted {
+        record_revocation(storage, id, version, reason, *at)?;
+    }
+    Ok(unlisted.len())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CrlQuery {
+    /// List version the client already has; the full list when omitted or 0
+    pub since: Option<u64>,
+}
+
+/// Revocation list signed with the master key, or the delta after `since`
+#[utoipa::path(
+    get,
+    path = "/verify/crl",
+    tag = "plugins",
+    params(CrlQuery),
+    responses(
+        (status = 200, description = "The signed list", body = RevocationList),
+        (status = 404, description = "No master key configured", body = ProblemDetails),
+        (
+            status = 409,
+            description = "`since` is ahead of the list (`crl_version_ahead`); fetch the full list",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn crl_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CrlQuery>,
+) -> Result<Json<RevocationList>, AppError> {
+    let keypair = state
+        .keypair
+        .as_ref()
+        .ok_or_else(|| AppError::NotFound("no master key configured".into()))?;
+    let since = query.since.unwrap_or(0);
+    let version = current_version(&state.storage).map_err(AppError::internal)?;
+    if since > version {
+        return Err(AppError::coded(
+            StatusCode::CONFLICT,
+            "crl_version_ahead",
+            format!(
+                "the revocation list is at version {}, not {}",
+                version, since
+            ),
+        ));
+    }
+    let list =
+        signed_list(&state.storage, keypair.signing_key(), since).map_err(AppError::internal)?;
+    Ok(Json(list))
+}
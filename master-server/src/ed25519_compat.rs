@@ -37,6 +37,10 @@ impl Keypair {
         Ok(Keypair { signing, public })
     }
 
+    pub fn signing_key(&self) -> &ed::SigningKey {
+        &self.signing
+    }
+
     pub fn sign(&self, msg: &[u8]) -> Result<ed::Signature> {
         Ok(self.signing.try_sign(msg)?)
     }
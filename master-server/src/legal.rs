@@ -1,20 +1,80 @@
+//! Versioned legal documents and terms of service acceptance.
+//!
+//! Documents are immutable once published and stored under `legal:doc:{type}:{version}`. Each one
+//! takes effect at its `effective_at` time, so a new version can be scheduled ahead of time; the
+//! version in force is the most recently effective one that isn't revoked. Every acceptance of
+//! the terms of service is kept under `tos:accept:{user_id}:{accepted_at}:{version}`. Users
+//! accept on their own behalf: the ToS endpoints take the user from the session or token, and
+//! only holders of [`ADMIN_LEGAL`] can read other users' acceptances. Until the version in force
+//! has been accepted, [`require_tos`] blocks the authenticated route groups and
+//! [`ensure_tos_accepted`] the authenticated publish and upload calls. The ToS endpoints
+//! themselves, the admin endpoints and the GDPR endpoints stay usable.
+//!
+//! A version holds its text in one locale plus any number of translations. Clients get the
+//! variant that best matches `?locale=` or `Accept-Language`, falling back from `de-AT` to `de`,
+//! then to any `de-*` variant and finally to the document's own locale, with the Markdown
+//! rendered to HTML.
+
+use crate::admin::{is_permitted, require_admin, ADMIN_LEGAL};
+use crate::auth::Principal;
 use crate::state::AppState;
-use anyhow::Result;
 use axum::body::Body;
-use axum::http::Request;
-use axum::{extract::State, Json};
+use axum::extract::{Path, Query};
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::{extract::State, Extension, Json};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
+use verseguy_shared_error::{AppError, ProblemDetails};
+use verseguy_storage::RocksDBStorage;
+
+/// Document type of the terms of service
+pub const TOS: &str = "tos";
+
+/// Locale of documents published without one
+pub const DEFAULT_LOCALE: &str = "en";
 
-#[derive(Deserialize)]
+fn default_locale() -> String {
+    DEFAULT_LOCALE.to_string()
+}
+
+#[derive(Deserialize, Default, ToSchema)]
 pub struct AdminCreateLegalRequest {
     pub doc_type: String,
     pub version: String,
     pub title: String,
+    /// Markdown
     pub content: String,
     pub author: Option<String>,
+    /// Unix time the version takes effect; defaults to now
+    pub effective_at: Option<i64>,
+    /// Locale of `title` and `content`; defaults to `en`
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub translations: Vec<NewTranslation>,
+}
+
+/// The text of a version in another locale
+#[derive(Deserialize, ToSchema)]
+pub struct NewTranslation {
+    /// Language tag such as `de` or `de-AT`
+    pub locale: String,
+    pub title: String,
+    /// Markdown
+    pub content: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Translation {
+    pub title: String,
+    pub content_hash: String,
+    pub content: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -27,6 +87,184 @@ pub struct LegalDocument {
     pub content: String,
     pub author: Option<String>,
     pub created_at: i64,
+    /// Unix time the version takes effect; `0` for documents published before scheduling
+    #[serde(default)]
+    pub effective_at: i64,
+    /// Locale of `title` and `content`
+    #[serde(default = "default_locale")]
+    pub locale: String,
+    /// The same version in other locales, by language tag
+    #[serde(default)]
+    pub translations: BTreeMap<String, Translation>,
+}
+
+/// The text of a document version in one locale
+struct Variant<'a> {
+    locale: &'a str,
+    title: &'a str,
+    content: &'a str,
+    content_hash: &'a str,
+}
+
+/// `de` for `de-AT`
+fn language(tag: &str) -> &str {
+    tag.split('-').next().unwrap_or(tag)
+}
+
+impl LegalDocument {
+    /// Order in which versions supersede each other
+    fn precedence(&self) -> (i64, i64) {
+        (self.effective_at, self.created_at)
+    }
+
+    fn primary(&self) -> Variant<'_> {
+        Variant {
+            locale: &self.locale,
+            title: &self.title,
+            content: &self.content,
+            content_hash: &self.content_hash,
+        }
+    }
+
+    /// Every variant, the document's own locale first
+    fn variants(&self) -> impl Iterator<Item = Variant<'_>> {
+        std::iter::once(self.primary()).chain(self.translations.iter().map(|(locale, t)| Variant {
+            locale,
+            title: &t.title,
+            content: &t.content,
+            content_hash: &t.content_hash,
+        }))
+    }
+
+    /// The variant in exactly `locale`, ignoring case
+    fn variant(&self, locale: &str) -> Option<Variant<'_>> {
+        self.variants()
+            .find(|v| v.locale.eq_ignore_ascii_case(locale))
+    }
+
+    /// The variant for the first of `preferences` that matches one: exactly, by its language
+    /// alone, or by another variant of that language. Falls back to the document's own locale.
+    fn negotiate(&self, preferences: &[String]) -> Variant<'_> {
+        for wanted in preferences {
+            if wanted == "*" {
+                return self.primary();
+            }
+            let lang = language(wanted);
+            let found = self
+                .variant(wanted)
+                .or_else(|| self.variant(lang))
+                .or_else(|| {
+                    self.variants()
+                        .find(|v| language(v.locale).eq_ignore_ascii_case(lang))
+                });
+            if let Some(found) = found {
+                return found;
+            }
+        }
+        self.primary()
+    }
+
+    /// Add the text in another locale; each locale can be added once
+    fn add_translation(&mut self, t: NewTranslation) -> Result<(), AppError> {
+        if !valid_locale(&t.locale) {
+            return Err(AppError::BadRequest(format!("invalid locale {}", t.locale)));
+        }
+        if self.variant(&t.locale).is_some() {
+            return Err(AppError::Conflict(format!(
+                "{} version {} already has locale {}",
+                self.doc_type, self.version, t.locale
+            )));
+        }
+        let translation = Translation {
+            content_hash: sha256_hex(&t.content),
+            title: t.title,
+            content: t.content,
+        };
+        self.translations.insert(t.locale, translation);
+        Ok(())
+    }
+
+    /// This version in the locale best matching `preferences`
+    pub fn localized(&self, preferences: &[String]) -> LocalizedDocument {
+        let variant = self.negotiate(preferences);
+        LocalizedDocument {
+            id: self.id.clone(),
+            doc_type: self.doc_type.clone(),
+            version: self.version.clone(),
+            locale: variant.locale.to_string(),
+            title: variant.title.to_string(),
+            content_hash: variant.content_hash.to_string(),
+            content: variant.content.to_string(),
+            html: render_markdown(variant.content),
+            author: self.author.clone(),
+            created_at: self.created_at,
+            effective_at: self.effective_at,
+            locales: self.variants().map(|v| v.locale.to_string()).collect(),
+        }
+    }
+}
+
+/// A document version as served to clients, in one locale
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct LocalizedDocument {
+    pub id: String,
+    pub doc_type: String,
+    pub version: String,
+    /// Locale of `title`, `content` and `html`
+    pub locale: String,
+    pub title: String,
+    /// SHA-256 of `content`
+    pub content_hash: String,
+    /// Markdown
+    pub content: String,
+    /// `content` rendered to HTML
+    pub html: String,
+    pub author: Option<String>,
+    pub created_at: i64,
+    pub effective_at: i64,
+    /// Every locale the version is available in
+    pub locales: Vec<String>,
+}
+
+/// Render Markdown to HTML; raw HTML in the source is escaped rather than passed through
+pub fn render_markdown(markdown: &str) -> String {
+    use pulldown_cmark::{html, Event, Options, Parser};
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES | Options::ENABLE_STRIKETHROUGH;
+    let events = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+        other => other,
+    });
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    out
+}
+
+/// Language tags from an `Accept-Language` header, most preferred first
+pub fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut tags: Vec<(f32, &str)> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut pieces = part.split(';').map(str::trim);
+            let tag = pieces.next().filter(|t| !t.is_empty())?;
+            let q = pieces
+                .find_map(|p| p.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (q > 0.0).then_some((q, tag))
+        })
+        .collect();
+    // stable, so equal weights keep the header's order
+    tags.sort_by(|a, b| b.0.total_cmp(&a.0));
+    tags.into_iter().map(|(_, tag)| tag.to_string()).collect()
+}
+
+/// `en`, `de-AT`, `zh-Hant-TW`: alphanumeric subtags of up to 8 characters
+fn valid_locale(tag: &str) -> bool {
+    tag.len() <= 35
+        && tag
+            .split('-')
+            .all(|s| !s.is_empty() && s.len() <= 8 && s.chars().all(|c| c.is_ascii_alphanumeric()))
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -38,6 +276,24 @@ pub struct Revocation {
     pub revoked_at: i64,
 }
 
+/// One acceptance of the terms of service
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TosAcceptance {
+    /// Set by the server to the authenticated caller; a value in the request is ignored
+    #[serde(default)]
+    pub user_id: String,
+    /// Set by the server when the acceptance is recorded
+    #[serde(default)]
+    pub accepted_at: i64,
+    pub version: String,
+    /// Locale of the text the user saw; defaults to the document's own locale
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Hash of the text the user saw; checked against that locale's text, then recorded
+    #[serde(default)]
+    pub content_hash: Option<String>,
+}
+
 fn sha256_hex(s: &str) -> String {
     use sha2::{Digest, Sha256};
     let mut hasher = Sha256::new();
@@ -49,120 +305,203 @@ fn sha256_hex(s: &str) -> String {
 fn key_for_doc(doc_type: &str, version: &str) -> String {
     format!("legal:doc:{}:{}", doc_type, version)
 }
-fn key_latest(doc_type: &str) -> String {
-    format!("legal:latest:{}", doc_type)
-}
 fn key_revoked(id: &str) -> String {
     format!("legal:revoked:{}", id)
 }
+fn key_acceptance(a: &TosAcceptance) -> String {
+    format!(
+        "tos:accept:{}:{:020}:{}",
+        a.user_id, a.accepted_at, a.version
+    )
+}
+/// Single acceptance record written before the history was kept
+fn key_legacy_acceptance(user_id: &str) -> String {
+    format!("tos:{}", user_id)
+}
 
-// Admin: create legal doc
-#[allow(clippy::disallowed_methods)]
-pub async fn admin_create_legal_handler(
-    State(state): State<Arc<AppState>>,
-    req: Request<Body>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    // require admin
-    let headers = req.headers();
-    if let Ok(token) = std::env::var("MASTER_ADMIN_TOKEN") {
-        let header_token = headers
-            .get("x-admin-token")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("");
-        if header_token != token {
-            return Err((
-                axum::http::StatusCode::FORBIDDEN,
-                "invalid admin token".to_string(),
-            ));
+pub fn get_document(
+    storage: &RocksDBStorage,
+    doc_type: &str,
+    version: &str,
+) -> anyhow::Result<Option<LegalDocument>> {
+    storage.get(key_for_doc(doc_type, version).as_bytes())
+}
+
+pub fn is_revoked(storage: &RocksDBStorage, id: &str) -> anyhow::Result<bool> {
+    let rev: Option<Revocation> = storage.get(key_revoked(id).as_bytes())?;
+    Ok(rev.is_some())
+}
+
+/// Every version of `doc_type`, in the order they take effect
+pub fn versions_of(storage: &RocksDBStorage, doc_type: &str) -> anyhow::Result<Vec<LegalDocument>> {
+    let mut docs: Vec<LegalDocument> =
+        storage.prefix_scan(format!("legal:doc:{}:", doc_type).as_bytes())?;
+    docs.retain(|d| d.doc_type == doc_type);
+    docs.sort_by_key(LegalDocument::precedence);
+    Ok(docs)
+}
+
+/// The version of `doc_type` in force at `now`: the latest effective one that isn't revoked
+pub fn effective_document(
+    storage: &RocksDBStorage,
+    doc_type: &str,
+    now: i64,
+) -> anyhow::Result<Option<LegalDocument>> {
+    for doc in versions_of(storage, doc_type)?.into_iter().rev() {
+        if doc.effective_at <= now && !is_revoked(storage, &doc.id)? {
+            return Ok(Some(doc));
         }
-    } else {
-        return Err((
-            axum::http::StatusCode::FORBIDDEN,
-            "admin disabled".to_string(),
-        ));
     }
+    Ok(None)
+}
 
-    // read body
-    let bytes = axum::body::to_bytes(req.into_body(), 1024 * 1024)
-        .await
-        .map_err(|e| {
-            (
-                axum::http::StatusCode::BAD_REQUEST,
-                format!("failed to read body: {}", e),
-            )
-        })?;
-    let req_json: AdminCreateLegalRequest = serde_json::from_slice(&bytes).map_err(|e| {
-        (
-            axum::http::StatusCode::BAD_REQUEST,
-            format!("invalid json: {}", e),
-        )
-    })?;
-
-    if req_json.doc_type.trim().is_empty() || req_json.version.trim().is_empty() {
-        return Err((
-            axum::http::StatusCode::BAD_REQUEST,
-            "doc_type and version required".to_string(),
+/// The next scheduled version of `doc_type` after `now`, if any
+fn scheduled_document(
+    storage: &RocksDBStorage,
+    doc_type: &str,
+    now: i64,
+) -> anyhow::Result<Option<LegalDocument>> {
+    for doc in versions_of(storage, doc_type)? {
+        if doc.effective_at > now && !is_revoked(storage, &doc.id)? {
+            return Ok(Some(doc));
+        }
+    }
+    Ok(None)
+}
+
+/// The document version with `id`
+fn find_by_id(storage: &RocksDBStorage, id: &str) -> Result<LegalDocument, AppError> {
+    let items: Vec<LegalDocument> = storage
+        .prefix_scan(b"legal:doc:")
+        .map_err(|e| AppError::internal(format!("storage: {}", e)))?;
+    items
+        .into_iter()
+        .find(|d| d.id == id)
+        .ok_or_else(|| AppError::NotFound("document not found".into()))
+}
+
+/// Validate and store a new document version; versions are immutable once published
+pub fn create_document(
+    storage: &RocksDBStorage,
+    req: AdminCreateLegalRequest,
+) -> Result<LegalDocument, AppError> {
+    if req.doc_type.trim().is_empty() || req.version.trim().is_empty() {
+        return Err(AppError::BadRequest("doc_type and version required".into()));
+    }
+    // both end up in storage keys and URL paths
+    let invalid = |s: &str| s.contains([':', '/']);
+    if invalid(&req.doc_type) || invalid(&req.version) || req.version == "diff" {
+        return Err(AppError::BadRequest(
+            "doc_type and version must not contain ':' or '/', and `diff` is no version".into(),
         ));
     }
+    if get_document(storage, &req.doc_type, &req.version)
+        .map_err(AppError::internal)?
+        .is_some()
+    {
+        return Err(AppError::Conflict(format!(
+            "{} version {} already exists",
+            req.doc_type, req.version
+        )));
+    }
 
-    let id = Uuid::new_v4().to_string();
-    let hash = sha256_hex(&req_json.content);
-    let doc = LegalDocument {
-        id: id.clone(),
-        doc_type: req_json.doc_type.clone(),
-        version: req_json.version.clone(),
-        title: req_json.title.clone(),
-        content_hash: hash,
-        content: req_json.content.clone(),
-        author: req_json.author.clone(),
-        created_at: Utc::now().timestamp(),
+    let locale = req.locale.unwrap_or_else(default_locale);
+    if !valid_locale(&locale) {
+        return Err(AppError::BadRequest(format!("invalid locale {}", locale)));
+    }
+
+    let now = Utc::now().timestamp();
+    let mut doc = LegalDocument {
+        id: Uuid::new_v4().to_string(),
+        content_hash: sha256_hex(&req.content),
+        doc_type: req.doc_type,
+        version: req.version,
+        title: req.title,
+        content: req.content,
+        author: req.author,
+        created_at: now,
+        effective_at: req.effective_at.unwrap_or(now),
+        locale,
+        translations: BTreeMap::new(),
     };
+    for t in req.translations {
+        doc.add_translation(t)?;
+    }
+    storage
+        .put(key_for_doc(&doc.doc_type, &doc.version).as_bytes(), &doc)
+        .map_err(|e| AppError::internal(format!("storage error: {}", e)))?;
+    Ok(doc)
+}
 
-    let k = key_for_doc(&doc.doc_type, &doc.version);
-    state.storage.put(k.as_bytes(), &doc).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("storage error: {}", e),
-        )
-    })?;
-
-    // also update latest mapping
-    let latest_k = key_latest(&doc.doc_type);
-    state.storage.put(latest_k.as_bytes(), &doc).map_err(|e| {
+/// Admin: create legal doc
+#[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    post,
+    path = "/admin/legal",
+    tag = "legal",
+    request_body = AdminCreateLegalRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The stored document under `doc`", body = serde_json::Value),
         (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("storage error: {}", e),
-        )
-    })?;
+            status = 400,
+            description = "Malformed body or missing doc_type/version",
+            body = ProblemDetails
+        ),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:legal` permission", body = ProblemDetails),
+        (status = 409, description = "The version already exists", body = ProblemDetails),
+    )
+)]
+pub async fn admin_create_legal_handler(
+    State(state): State<Arc<AppState>>,
+    req: Request<Body>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, req.headers(), ADMIN_LEGAL)?;
 
-    Ok(Json(serde_json::json!({"ok": true, "id": id, "doc": doc})))
+    // read body
+    let bytes = axum::body::to_bytes(req.into_body(), 1024 * 1024)
+        .await
+        .map_err(|e| AppError::BadRequest(format!("failed to read body: {}", e)))?;
+    let req_json: AdminCreateLegalRequest = serde_json::from_slice(&bytes)
+        .map_err(|e| AppError::BadRequest(format!("invalid json: {}", e)))?;
+
+    let doc = create_document(&state.storage, req_json)?;
+    admin.audit(
+        &state,
+        "legal.create",
+        serde_json::json!({
+            "id": doc.id,
+            "doc_type": doc.doc_type,
+            "version": doc.version,
+            "effective_at": doc.effective_at,
+        }),
+    )?;
+    Ok(Json(
+        serde_json::json!({"ok": true, "id": doc.id, "doc": doc}),
+    ))
 }
 
-// Admin: get by id (scan)
+/// Admin: get by id (scan)
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    get,
+    path = "/admin/legal/{id}",
+    tag = "legal",
+    params(("id" = String, Path, description = "Document id")),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The document under `doc`", body = serde_json::Value),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:legal` permission", body = ProblemDetails),
+        (status = 404, description = "Document not found", body = ProblemDetails),
+    )
+)]
 pub async fn admin_get_legal_handler(
     State(state): State<Arc<AppState>>,
     req: Request<Body>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    // require admin
-    let headers = req.headers();
-    if let Ok(token) = std::env::var("MASTER_ADMIN_TOKEN") {
-        let header_token = headers
-            .get("x-admin-token")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("");
-        if header_token != token {
-            return Err((
-                axum::http::StatusCode::FORBIDDEN,
-                "invalid admin token".to_string(),
-            ));
-        }
-    } else {
-        return Err((
-            axum::http::StatusCode::FORBIDDEN,
-            "admin disabled".to_string(),
-        ));
-    }
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, req.headers(), ADMIN_LEGAL)?;
 
     // naive scan for key with id
     let id = req
@@ -172,170 +511,522 @@ pub async fn admin_get_legal_handler(
         .next()
         .unwrap_or("")
         .to_string();
-    let items: Vec<LegalDocument> = state.storage.prefix_scan(b"legal:doc:").map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("storage: {}", e),
-        )
-    })?;
+    let items: Vec<LegalDocument> = state
+        .storage
+        .prefix_scan(b"legal:doc:")
+        .map_err(|e| AppError::internal(format!("storage: {}", e)))?;
     for it in items {
         if it.id == id {
+            admin.audit(&state, "legal.get", serde_json::json!({"id": id}))?;
             return Ok(Json(serde_json::json!({"doc": it})));
         }
     }
-    Err((
-        axum::http::StatusCode::NOT_FOUND,
-        "document not found".to_string(),
-    ))
+    Err(AppError::NotFound("document not found".into()))
 }
 
-// Admin: list docs (by type optional)
+/// Admin: list docs (by type optional)
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    get,
+    path = "/admin/legal",
+    tag = "legal",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "All documents under `documents`", body = serde_json::Value),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:legal` permission", body = ProblemDetails),
+    )
+)]
 pub async fn admin_list_legal_handler(
     State(state): State<Arc<AppState>>,
     req: Request<Body>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    // require admin
-    let headers = req.headers();
-    if let Ok(token) = std::env::var("MASTER_ADMIN_TOKEN") {
-        let header_token = headers
-            .get("x-admin-token")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("");
-        if header_token != token {
-            return Err((
-                axum::http::StatusCode::FORBIDDEN,
-                "invalid admin token".to_string(),
-            ));
-        }
-    } else {
-        return Err((
-            axum::http::StatusCode::FORBIDDEN,
-            "admin disabled".to_string(),
-        ));
-    }
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, req.headers(), ADMIN_LEGAL)?;
 
-    let items: Vec<LegalDocument> = state.storage.prefix_scan(b"legal:doc:").map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("storage: {}", e),
-        )
-    })?;
+    let items: Vec<LegalDocument> = state
+        .storage
+        .prefix_scan(b"legal:doc:")
+        .map_err(|e| AppError::internal(format!("storage: {}", e)))?;
+    admin.audit(&state, "legal.list", serde_json::json!({}))?;
     Ok(Json(serde_json::json!({"documents": items})))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct RevokeReq {
     pub id: String,
     pub reason: String,
 }
 
+/// Admin: revoke a legal doc
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    post,
+    path = "/admin/legal/{id}/revoke",
+    tag = "legal",
+    params(("id" = String, Path, description = "Document id")),
+    request_body = RevokeReq,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Revocation recorded", body = serde_json::Value),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:legal` permission", body = ProblemDetails),
+        (status = 404, description = "Document not found", body = ProblemDetails),
+    )
+)]
 pub async fn admin_revoke_legal_handler(
     State(state): State<Arc<AppState>>,
     req: Request<Body>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    // require admin
-    let headers = req.headers();
-    if let Ok(token) = std::env::var("MASTER_ADMIN_TOKEN") {
-        let header_token = headers
-            .get("x-admin-token")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("");
-        if header_token != token {
-            return Err((
-                axum::http::StatusCode::FORBIDDEN,
-                "invalid admin token".to_string(),
-            ));
-        }
-    } else {
-        return Err((
-            axum::http::StatusCode::FORBIDDEN,
-            "admin disabled".to_string(),
-        ));
-    }
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, req.headers(), ADMIN_LEGAL)?;
 
     // read body
     let bytes = axum::body::to_bytes(req.into_body(), 1024 * 1024)
         .await
-        .map_err(|e| {
-            (
-                axum::http::StatusCode::BAD_REQUEST,
-                format!("failed to read body: {}", e),
-            )
-        })?;
-    let r: RevokeReq = serde_json::from_slice(&bytes).map_err(|e| {
-        (
-            axum::http::StatusCode::BAD_REQUEST,
-            format!("invalid json: {}", e),
-        )
-    })?;
+        .map_err(|e| AppError::BadRequest(format!("failed to read body: {}", e)))?;
+    let r: RevokeReq = serde_json::from_slice(&bytes)
+        .map_err(|e| AppError::BadRequest(format!("invalid json: {}", e)))?;
+
+    let doc = find_by_id(&state.storage, &r.id)?;
 
     // create revocation record
     let rev = Revocation {
         id: r.id.clone(),
-        doc_type: String::new(),
-        version: String::new(),
+        doc_type: doc.doc_type,
+        version: doc.version,
         reason: r.reason.clone(),
         revoked_at: Utc::now().timestamp(),
     };
     let k = key_revoked(&r.id);
-    state.storage.put(k.as_bytes(), &rev).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("storage: {}", e),
-        )
-    })?;
+    state
+        .storage
+        .put(k.as_bytes(), &rev)
+        .map_err(|e| AppError::internal(format!("storage: {}", e)))?;
+    admin.audit(
+        &state,
+        "legal.revoke",
+        serde_json::json!({"id": r.id, "reason": r.reason}),
+    )?;
     Ok(Json(serde_json::json!({"ok": true})))
 }
 
-// Client: get latest for type
+/// Admin: add the text of a document version in another locale
+#[utoipa::path(
+    post,
+    path = "/admin/legal/{id}/translations",
+    tag = "legal",
+    params(("id" = String, Path, description = "Document id")),
+    request_body = NewTranslation,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The updated document under `doc`", body = serde_json::Value),
+        (status = 400, description = "Malformed body or locale", body = ProblemDetails),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:legal` permission", body = ProblemDetails),
+        (status = 404, description = "Document not found", body = ProblemDetails),
+        (status = 409, description = "The locale already exists", body = ProblemDetails),
+    )
+)]
+pub async fn admin_translate_legal_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(translation): Json<NewTranslation>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_LEGAL)?;
+    let mut doc = find_by_id(&state.storage, &id)?;
+    let locale = translation.locale.clone();
+    doc.add_translation(translation)?;
+    state
+        .storage
+        .put(key_for_doc(&doc.doc_type, &doc.version).as_bytes(), &doc)
+        .map_err(|e| AppError::internal(format!("storage: {}", e)))?;
+    admin.audit(
+        &state,
+        "legal.translate",
+        serde_json::json!({"id": id, "locale": locale}),
+    )?;
+    Ok(Json(serde_json::json!({"ok": true, "doc": doc})))
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LocaleQuery {
+    /// Preferred locale; tried before `Accept-Language`
+    pub locale: Option<String>,
+}
+
+/// Preferred locales of a request, most preferred first
+fn preferences(headers: &HeaderMap, query: &LocaleQuery) -> Vec<String> {
+    let accept = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|v| v.to_str().ok())
+        .map(parse_accept_language)
+        .unwrap_or_default();
+    query.locale.iter().cloned().chain(accept).collect()
+}
+
+/// Body and headers of a response negotiated by locale
+pub type Negotiated = ([(header::HeaderName, String); 2], Json<serde_json::Value>);
+
+fn negotiated(doc: &LocalizedDocument, body: serde_json::Value) -> Negotiated {
+    let headers = [
+        (header::CONTENT_LANGUAGE, doc.locale.clone()),
+        (header::VARY, header::ACCEPT_LANGUAGE.to_string()),
+    ];
+    (headers, Json(body))
+}
+
+/// Client: get latest for type
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    get,
+    path = "/legal/latest/{type}",
+    tag = "legal",
+    params(
+        ("type" = String, Path, description = "Document type, e.g. `tos`"),
+        ("Accept-Language" = Option<String>, Header, description = "Preferred locales"),
+        LocaleQuery,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The version in force under `doc`, the next scheduled one under `next`",
+            body = serde_json::Value
+        ),
+        (status = 404, description = "No document of this type in force", body = ProblemDetails),
+    )
+)]
 pub async fn get_latest_legal_handler(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     axum::extract::Path(doc_type): axum::extract::Path<String>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    let k = key_latest(&doc_type);
-    let rec: Option<LegalDocument> = state.storage.get(k.as_bytes()).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("storage: {}", e),
-        )
-    })?;
+    Query(query): Query<LocaleQuery>,
+) -> Result<Negotiated, AppError> {
+    let now = Utc::now().timestamp();
+    let rec = effective_document(&state.storage, &doc_type, now)
+        .map_err(|e| AppError::internal(format!("storage: {}", e)))?;
     if let Some(r) = rec {
-        Ok(Json(serde_json::json!({"doc": r})))
+        let next = scheduled_document(&state.storage, &doc_type, now)
+            .map_err(|e| AppError::internal(format!("storage: {}", e)))?;
+        let prefs = preferences(&headers, &query);
+        let doc = r.localized(&prefs);
+        let next = next.map(|n| n.localized(&prefs));
+        let body = serde_json::json!({"doc": doc, "next": next});
+        Ok(negotiated(&doc, body))
     } else {
-        Err((axum::http::StatusCode::NOT_FOUND, "not found".to_string()))
+        Err(AppError::NotFound("not found".into()))
     }
 }
 
-// Helper: check if a given user has accepted latest ToS
-pub fn user_has_accepted_latest(storage: &crate::state::AppState, user_id: &str) -> Result<bool> {
-    // read latest tos for the user
-    let key = format!("tos:latest:{}", user_id);
-    let rec: Option<verseguy_compliance::tos_validator::TosAcceptance> = storage
-        .storage
-        .get(key.as_bytes())
-        .map_err(|e| anyhow::anyhow!("storage: {}", e))?;
-    Ok(rec.is_some())
-}
-
-// Client: get specific version
+/// Client: get specific version
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    get,
+    path = "/legal/{type}/{version}",
+    tag = "legal",
+    params(
+        ("type" = String, Path, description = "Document type, e.g. `tos`"),
+        ("version" = String, Path, description = "Document version"),
+        ("Accept-Language" = Option<String>, Header, description = "Preferred locales"),
+        LocaleQuery,
+    ),
+    responses(
+        (status = 200, description = "The document under `doc`", body = serde_json::Value),
+        (status = 404, description = "No such version", body = ProblemDetails),
+    )
+)]
 pub async fn get_legal_version_handler(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     axum::extract::Path((doc_type, version)): axum::extract::Path<(String, String)>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    let k = key_for_doc(&doc_type, &version);
-    let rec: Option<LegalDocument> = state.storage.get(k.as_bytes()).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("storage: {}", e),
-        )
-    })?;
+    Query(query): Query<LocaleQuery>,
+) -> Result<Negotiated, AppError> {
+    let rec = get_document(&state.storage, &doc_type, &version)
+        .map_err(|e| AppError::internal(format!("storage: {}", e)))?;
     if let Some(r) = rec {
-        Ok(Json(serde_json::json!({"doc": r})))
+        let doc = r.localized(&preferences(&headers, &query));
+        let body = serde_json::json!({"doc": doc});
+        Ok(negotiated(&doc, body))
     } else {
-        Err((axum::http::StatusCode::NOT_FOUND, "not found".to_string()))
+        Err(AppError::NotFound("not found".into()))
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DiffQuery {
+    /// Older version
+    pub from: String,
+    /// Newer version
+    pub to: String,
+}
+
+/// Line diff between two versions of a document
+#[derive(Serialize, ToSchema)]
+pub struct LegalDiff {
+    pub doc_type: String,
+    pub from: String,
+    pub to: String,
+    /// Lines only in `to`
+    pub added: usize,
+    /// Lines only in `from`
+    pub removed: usize,
+    /// Unified diff of the contents
+    pub diff: String,
+}
+
+/// Client: diff two versions of a document, in each version's own locale
+#[utoipa::path(
+    get,
+    path = "/legal/{type}/diff",
+    tag = "legal",
+    params(("type" = String, Path, description = "Document type, e.g. `tos`"), DiffQuery),
+    responses(
+        (status = 200, description = "The diff", body = LegalDiff),
+        (status = 404, description = "No such version", body = ProblemDetails),
+    )
+)]
+pub async fn legal_diff_handler(
+    State(state): State<Arc<AppState>>,
+    Path(doc_type): Path<String>,
+    Query(query): Query<DiffQuery>,
+) -> Result<Json<LegalDiff>, AppError> {
+    let find = |version: &str| {
+        get_document(&state.storage, &doc_type, version)
+            .map_err(AppError::internal)?
+            .ok_or_else(|| AppError::NotFound(format!("no {} version {}", doc_type, version)))
+    };
+    let (from, to) = (find(&query.from)?, find(&query.to)?);
+
+    let diff = TextDiff::from_lines(&from.content, &to.content);
+    let (mut added, mut removed) = (0, 0);
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => added += 1,
+            ChangeTag::Delete => removed += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    let unified = diff
+        .unified_diff()
+        .header(&from.version, &to.version)
+        .to_string();
+    Ok(Json(LegalDiff {
+        doc_type,
+        from: from.version,
+        to: to.version,
+        added,
+        removed,
+        diff: unified,
+    }))
+}
+
+/// Every ToS acceptance of `user_id`, oldest first
+pub fn acceptances_of(
+    storage: &RocksDBStorage,
+    user_id: &str,
+) -> anyhow::Result<Vec<TosAcceptance>> {
+    let mut all: Vec<TosAcceptance> = storage
+        .get::<_, TosAcceptance>(key_legacy_acceptance(user_id).as_bytes())?
+        .into_iter()
+        .collect();
+    let history: Vec<TosAcceptance> =
+        storage.prefix_scan(format!("tos:accept:{}:", user_id).as_bytes())?;
+    all.extend(history.into_iter().filter(|a| a.user_id == user_id));
+    Ok(all)
+}
+
+/// Remove every ToS acceptance of `user_id`; returns how many were removed
+pub fn delete_acceptances(storage: &RocksDBStorage, user_id: &str) -> anyhow::Result<usize> {
+    let all = acceptances_of(storage, user_id)?;
+    storage.delete(key_legacy_acceptance(user_id).as_bytes())?;
+    for a in &all {
+        storage.delete(key_acceptance(a).as_bytes())?;
+    }
+    Ok(all.len())
+}
+
+/// Reject `user_id` with `tos_acceptance_required` unless they accepted the ToS version in force
+/// or a later one; passes while no ToS is published
+pub fn ensure_tos_accepted(state: &AppState, user_id: &str) -> Result<(), AppError> {
+    let storage = &state.storage;
+    let now = Utc::now().timestamp();
+    let Some(current) = effective_document(storage, TOS, now).map_err(AppError::internal)? else {
+        return Ok(());
+    };
+    for a in acceptances_of(storage, user_id).map_err(AppError::internal)? {
+        let Some(doc) = get_document(storage, TOS, &a.version).map_err(AppError::internal)? else {
+            continue;
+        };
+        if doc.precedence() >= current.precedence()
+            && !is_revoked(storage, &doc.id).map_err(AppError::internal)?
+        {
+            return Ok(());
+        }
+    }
+    Err(AppError::coded(
+        StatusCode::FORBIDDEN,
+        "tos_acceptance_required",
+        format!(
+            "terms of service version {} must be accepted",
+            current.version
+        ),
+    ))
+}
+
+/// Middleware behind [`crate::auth::require_auth`] that blocks callers who haven't accepted the
+/// ToS version in force
+pub async fn require_tos(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    ensure_tos_accepted(&state, &principal.user_id)?;
+    Ok(next.run(req).await)
+}
+
+/// Record that `user_id` accepted the ToS version, locale and text named in `body`
+pub fn accept_tos(
+    storage: &RocksDBStorage,
+    user_id: &str,
+    mut body: TosAcceptance,
+) -> Result<TosAcceptance, AppError> {
+    let doc = get_document(storage, TOS, &body.version)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| {
+            AppError::BadRequest(format!("unknown terms of service version {}", body.version))
+        })?;
+    if is_revoked(storage, &doc.id).map_err(AppError::internal)? {
+        return Err(AppError::BadRequest(format!(
+            "terms of service version {} is revoked",
+            body.version
+        )));
+    }
+    let shown = match &body.locale {
+        Some(locale) => doc.variant(locale).ok_or_else(|| {
+            AppError::BadRequest(format!("version {} has no locale {}", body.version, locale))
+        })?,
+        None => doc.primary(),
+    };
+    if body
+        .content_hash
+        .as_ref()
+        .is_some_and(|hash| hash != shown.content_hash)
+    {
+        return Err(AppError::BadRequest(format!(
+            "content_hash does not match the {} text of version {}",
+            shown.locale, body.version
+        )));
+    }
+    body.user_id = user_id.to_string();
+    body.locale = Some(shown.locale.to_string());
+    body.content_hash = Some(shown.content_hash.to_string());
+    body.accepted_at = Utc::now().timestamp();
+    storage
+        .put(key_acceptance(&body).as_bytes(), &body)
+        .map_err(AppError::internal)?;
+    Ok(body)
+}
+
+/// Record the caller's acceptance of the terms of service
+#[utoipa::path(
+    post,
+    path = "/auth/tos",
+    tag = "legal",
+    request_body = TosAcceptance,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The recorded acceptance", body = TosAcceptance),
+        (
+            status = 400,
+            description = "Unknown or revoked version, unknown locale or mismatched content_hash",
+            body = ProblemDetails
+        ),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+    )
+)]
+pub async fn tos_accept_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Json(body): Json<TosAcceptance>,
+) -> Result<Json<TosAcceptance>, AppError> {
+    accept_tos(&state.storage, &principal.user_id, body).map(Json)
+}
+
+/// Let callers read their own acceptances, and holders of [`ADMIN_LEGAL`] anyone's
+fn ensure_may_read(state: &AppState, principal: &Principal, user_id: &str) -> Result<(), AppError> {
+    if principal.user_id == user_id {
+        return Ok(());
+    }
+    principal.require_scope(ADMIN_LEGAL)?;
+    if !is_permitted(state, &principal.user_id, ADMIN_LEGAL)? {
+        return Err(AppError::Forbidden(
+            "not allowed to read other users' acceptances".into(),
+        ));
     }
+    Ok(())
+}
+
+/// Get a user's most recent terms of service acceptance
+#[utoipa::path(
+    get,
+    path = "/auth/tos/{user_id}",
+    tag = "legal",
+    params(("user_id" = String, Path, description = "User id")),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The acceptance", body = TosAcceptance),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Another user's acceptance without `admin:legal`",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "No acceptance recorded", body = ProblemDetails),
+    )
+)]
+pub async fn tos_get_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Path(user_id): Path<String>,
+) -> Result<Json<TosAcceptance>, AppError> {
+    ensure_may_read(&state, &principal, &user_id)?;
+    acceptances_of(&state.storage, &user_id)
+        .map_err(AppError::internal)?
+        .pop()
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound("tos not found".into()))
+}
+
+/// Get every terms of service acceptance of a user
+#[utoipa::path(
+    get,
+    path = "/auth/tos/{user_id}/history",
+    tag = "legal",
+    params(("user_id" = String, Path, description = "User id")),
+    security(("bearer" = [])),
+    responses(
+        (
+            status = 200,
+            description = "Acceptances under `acceptances`, oldest first",
+            body = serde_json::Value
+        ),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Another user's acceptances without `admin:legal`",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn tos_history_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Path(user_id): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    ensure_may_read(&state, &principal, &user_id)?;
+    let acceptances = acceptances_of(&state.storage, &user_id).map_err(AppError::internal)?;
+    Ok(Json(serde_json::json!({ "acceptances": acceptances })))
 }
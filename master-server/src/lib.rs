@@ -1,15 +1,32 @@
-use axum::{
-    routing::{get, post},
-    Router,
-};
+use axum::{routing::get, Json, Router};
 use std::sync::Arc;
+use utoipa::OpenApi;
+use utoipa_axum::{router::OpenApiRouter, routes};
+use verseguy_ratelimit::{KeyBy, Limit, Policy};
 
+pub mod admin;
+pub mod artifacts;
+pub mod auth;
+pub mod config;
+pub mod crl;
 pub mod legal;
 pub mod observability;
+pub mod openapi;
+pub mod operations;
 pub mod plugins;
+pub mod publishers;
+pub mod rate_limit;
+pub mod registry;
 pub mod routes;
+pub mod search;
+#[cfg(feature = "run-server")]
+pub mod server;
 pub mod state;
+pub mod tokens;
+pub mod trust;
 
+#[cfg(feature = "run-server")]
+pub use server::run_server;
 use state::AppState;
 pub mod admin_cli;
 pub mod keystore;
@@ -17,59 +34,185 @@ pub mod manifest_tool;
 
 pub mod ed25519_compat;
 
-pub fn build_app(state: Arc<AppState>) -> Router {
-    Router::new()
-        .route("/auth/register", post(routes::register_handler))
-        .route("/auth/login", post(routes::login_handler))
-        .route("/license/validate", post(routes::license_validate_handler))
-        .route("/plugins/search", get(routes::plugins_search_handler))
-        .route("/plugins/publish", post(routes::plugins_publish_handler))
-        .route(
-            "/v1/orgs",
-            get(routes::orgs_list_handler).post(routes::orgs_create_handler),
-        )
-        .route("/v1/orgs/{id}", get(routes::orgs_get_handler))
-        .route("/admin/keys", get(routes::admin_get_keys))
-        .route("/admin/keys/rotate", post(routes::admin_rotate_key))
-        .route("/admin/keys/import", post(routes::admin_import_key))
-        .route("/auth/tos", post(routes::tos_accept_handler))
-        .route("/auth/tos/{user_id}", get(routes::tos_get_handler))
-        .route("/verify/plugin", post(routes::verify_plugin_handler))
-        .route("/verify/revoke", post(routes::revoke_handler))
-        .route("/verify/revocations", get(routes::revocations_list_handler))
-        // Observability
-        .route("/healthz", get(routes::health_handler))
-        .route("/metrics", get(routes::metrics_handler))
+/// Routes that require a session JWT or personal access token and an accepted ToS
+fn authenticated_routes() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::new()
+        .routes(routes!(
+            tokens::list_tokens_handler,
+            tokens::create_token_handler
+        ))
+        .routes(routes!(tokens::revoke_token_handler))
+}
+
+/// Terms of service acceptance; requires a session JWT or personal access token, but not an
+/// accepted ToS
+fn tos_routes() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::new()
+        .routes(routes!(legal::tos_accept_handler))
+        .routes(routes!(legal::tos_get_handler))
+        .routes(routes!(legal::tos_history_handler))
+}
+
+/// Credential checks, limited per client IP
+fn auth_routes() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::new()
+        .routes(routes!(routes::register_handler))
+        .routes(routes!(routes::login_handler))
+}
+
+fn publish_routes() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::new()
+        .routes(routes!(routes::plugins_publish_handler))
+        .routes(routes!(artifacts::upload_artifact_handler))
+}
+
+/// Publisher account and plugin ownership changes; require a session JWT or personal access
+/// token and an accepted ToS
+fn publisher_routes() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::new()
+        .routes(routes!(publishers::create_publisher_handler))
+        .routes(routes!(publishers::get_publisher_handler))
+        .routes(routes!(publishers::add_key_handler))
+        .routes(routes!(publishers::remove_key_handler))
+        .routes(routes!(publishers::claim_namespace_handler))
+        .routes(routes!(publishers::add_maintainer_handler))
+        .routes(routes!(publishers::remove_maintainer_handler))
+        .routes(routes!(
+            publishers::yank_handler,
+            publishers::unyank_handler
+        ))
+}
+
+/// Probes and scrapers; exempt from rate limits
+fn observability_routes() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::new()
+        .routes(routes!(routes::health_handler))
+        .routes(routes!(routes::metrics_handler))
+}
+
+fn api_routes() -> OpenApiRouter<Arc<AppState>> {
+    OpenApiRouter::with_openapi(openapi::ApiDoc::openapi())
+        .routes(routes!(routes::license_validate_handler))
+        .routes(routes!(routes::plugins_search_handler))
+        .routes(routes!(registry::versions_handler))
+        .routes(routes!(registry::latest_handler))
+        .routes(routes!(registry::resolve_handler))
+        .routes(routes!(trust::trust_handler))
+        .routes(routes!(routes::plugin_signature_handler))
+        .routes(routes!(artifacts::download_artifact_handler))
+        .routes(routes!(
+            routes::orgs_list_handler,
+            routes::orgs_create_handler
+        ))
+        .routes(routes!(routes::orgs_get_handler))
+        .routes(routes!(routes::admin_get_keys))
+        .routes(routes!(routes::admin_rotate_key))
+        .routes(routes!(routes::admin_import_key))
+        .routes(routes!(
+            tokens::admin_list_service_accounts_handler,
+            tokens::admin_create_service_account_handler
+        ))
+        .routes(routes!(tokens::admin_create_service_token_handler))
+        .routes(routes!(operations::admin_user_handler))
+        .routes(routes!(
+            operations::admin_lock_handler,
+            operations::admin_unlock_handler
+        ))
+        .routes(routes!(operations::admin_audit_verify_handler))
+        .routes(routes!(
+            operations::admin_list_backups_handler,
+            operations::admin_create_backup_handler
+        ))
+        .routes(routes!(
+            operations::admin_list_licenses_handler,
+            operations::admin_issue_license_handler
+        ))
+        .routes(routes!(operations::admin_revoke_license_handler))
+        .routes(routes!(operations::admin_list_plugins_handler))
+        .routes(routes!(operations::admin_yank_handler))
+        .routes(routes!(routes::verify_plugin_handler))
+        .routes(routes!(routes::revoke_handler))
+        .routes(routes!(routes::revocations_list_handler))
+        .routes(routes!(crl::crl_handler))
         // Legal / admin legal endpoints
-        .route("/admin/legal", post(legal::admin_create_legal_handler))
-        .route("/admin/legal/{id}", get(legal::admin_get_legal_handler))
-        .route("/admin/legal", get(legal::admin_list_legal_handler))
-        .route(
-            "/admin/legal/{id}/revoke",
-            post(legal::admin_revoke_legal_handler),
-        )
-        .route("/legal/latest/{type}", get(legal::get_latest_legal_handler))
-        .route(
-            "/legal/{type}/{version}",
-            get(legal::get_legal_version_handler),
-        )
+        .routes(routes!(
+            legal::admin_create_legal_handler,
+            legal::admin_list_legal_handler
+        ))
+        .routes(routes!(legal::admin_get_legal_handler))
+        .routes(routes!(legal::admin_revoke_legal_handler))
+        .routes(routes!(legal::admin_translate_legal_handler))
+        .routes(routes!(legal::get_latest_legal_handler))
+        .routes(routes!(legal::get_legal_version_handler))
+        .routes(routes!(legal::legal_diff_handler))
         // GDPR / Audit endpoints
-        .route("/audit/export/{user_id}", get(routes::audit_export_handler))
-        .route(
-            "/users/{user_id}/data",
-            axum::routing::delete(routes::user_data_delete_handler),
-        )
+        .routes(routes!(routes::audit_export_handler))
+        .routes(routes!(
+            operations::user_data_export_handler,
+            routes::user_data_delete_handler
+        ))
+}
+
+/// OpenAPI document of every route served by [`build_app`]
+pub fn openapi_spec() -> utoipa::openapi::OpenApi {
+    api_routes()
+        .merge(auth_routes())
+        .merge(publish_routes())
+        .merge(publisher_routes())
+        .merge(authenticated_routes())
+        .merge(tos_routes())
+        .merge(observability_routes())
+        .into_openapi()
+}
+
+pub fn build_app(state: Arc<AppState>) -> Router {
+    let limits = &state.config.rate_limits;
+    let limited = |name: &str, key: KeyBy, limit: Limit| {
+        rate_limit::layer(&state, Policy::new(name, key, limit))
+    };
+    let authenticated = authenticated_routes()
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            legal::require_tos,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_auth,
+        ))
+        .route_layer(limited("tokens", KeyBy::User, limits.tokens));
+    let tos = tos_routes().route_layer(axum::middleware::from_fn_with_state(
+        state.clone(),
+        auth::require_auth,
+    ));
+    let auth = auth_routes().route_layer(limited("auth", KeyBy::Ip, limits.auth));
+    let publish = publish_routes().route_layer(limited("publish", KeyBy::User, limits.publish));
+    let publisher = publisher_routes()
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            legal::require_tos,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_auth,
+        ))
+        .route_layer(limited("publish", KeyBy::User, limits.publish));
+    let (router, spec) = api_routes()
+        .merge(auth)
+        .merge(publish)
+        .merge(publisher)
+        .merge(authenticated)
+        .merge(tos)
+        .route_layer(rate_limit::layer(&state, limits.quota_policy()))
+        .merge(observability_routes())
+        .split_for_parts();
+    let spec = Arc::new(spec);
+
+    router
+        .route("/openapi.json", get(move || openapi_json(spec.clone())))
         .with_state(state)
+        .layer(axum::middleware::from_fn(verseguy_shared_error::request_id))
 }
 
-#[cfg(feature = "run-server")]
-pub async fn run_server(_state: Arc<AppState>, _addr: std::net::SocketAddr) -> anyhow::Result<()> {
-    // The CI and tests call the Router directly using ServiceExt::oneshot to avoid
-    // binding to network sockets and to prevent Hyper version conflicts during
-    // test builds. If you enable `run-server`, implement a platform-specific
-    // runner that uses a compatible hyper version.
-    tracing::info!("run_server() is a no-op in this build configuration");
-    Err(anyhow::anyhow!(
-        "run-server is not implemented in this build"
-    ))
+async fn openapi_json(spec: Arc<utoipa::openapi::OpenApi>) -> Json<utoipa::openapi::OpenApi> {
+    Json((*spec).clone())
 }
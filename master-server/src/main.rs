@@ -1,47 +1,43 @@
-#![allow(unused_imports)]
-use master_server::build_app;
+use clap::Parser;
+use master_server::config::Config;
 use master_server::state::AppState;
+use std::path::PathBuf;
 use std::sync::Arc;
 
-#[cfg(feature = "run-server")]
-use master_server::run_server;
+/// VerseGuy master server
+#[derive(Parser, Debug)]
+#[command(name = "master_server")]
+struct Args {
+    /// TOML configuration file (or set MASTER_CONFIG); `MASTER_*` env vars override it
+    #[arg(long)]
+    config: Option<PathBuf>,
+}
 
 #[cfg(not(test))]
-#[allow(clippy::disallowed_methods)]
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
-    let db_path =
-        std::env::var("MASTER_DB_PATH").unwrap_or_else(|_| "./master_server_db".to_string());
-    let secret = std::env::var("MASTER_LICENSE_SECRET")
-        .unwrap_or_else(|_| "master-secret".to_string())
-        .into_bytes();
+    let args = Args::parse();
+    let config = Config::load(args.config.as_deref())?;
+
+    let mut state = AppState::from_config(config)?;
     // Initialize observability (metrics + tracing)
-    let mut state = AppState::new(db_path, secret)?;
-    // install metrics recorder and expose handle
-    let metrics_handle = match master_server::observability::init_observability() {
+    state.metrics_handle = match master_server::observability::init_observability() {
         Ok(h) => Some(h),
         Err(e) => {
             tracing::error!("Failed to initialize observability: {}", e);
             None
         }
     };
-    state.metrics_handle = metrics_handle;
-
     let state = Arc::new(state);
-    let _app = build_app(state.clone());
-    tracing::info!("Master server built. To run, enable the 'run-server' feature or run via workspace run configuration.");
 
-    // If compiled with feature 'run-server', run it by default
     #[cfg(feature = "run-server")]
+    master_server::run_server(state).await?;
+
+    #[cfg(not(feature = "run-server"))]
     {
-        // Allow overriding default port via MASTER_SERVER_PORT env var for E2E tests and flexibility
-        let port: u16 = std::env::var("MASTER_SERVER_PORT")
-            .ok()
-            .and_then(|s| s.parse::<u16>().ok())
-            .unwrap_or(3000);
-        let addr = std::net::SocketAddr::from(([127, 0, 0, 1], port));
-        run_server(state, addr).await?;
+        let _app = master_server::build_app(state);
+        tracing::info!("Master server built. Enable the 'run-server' feature to serve it.");
     }
 
     Ok(())
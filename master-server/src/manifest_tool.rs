@@ -1,24 +1,37 @@
-use crate::ed25519_compat::{Keypair, PublicKey};
+use crate::ed25519_compat::Keypair;
 use anyhow::Result;
 use base64::{engine::general_purpose, Engine as _};
-use ed25519_dalek::{Signature, Verifier};
 use rand::rngs::OsRng;
 use std::fs;
+use verseguy_signing::{jcs, SignatureEntry};
 
-pub fn canonical_bytes_from_path(path: &str) -> Result<Vec<u8>> {
+/// The manifest at `path` as signed: without `published_at`
+fn signing_document_from_path(path: &str) -> Result<serde_json::Value> {
     let data = fs::read_to_string(path)?;
     let v: serde_json::Value = serde_json::from_str(&data)?;
-    let bytes = serde_json::to_vec(&v)?;
-    Ok(bytes)
+    Ok(crate::plugins::signing_document(&v))
+}
+
+/// RFC 8785 canonical bytes of the manifest at `path`
+pub fn canonical_bytes_from_path(path: &str) -> Result<Vec<u8>> {
+    Ok(jcs::canonicalize(&signing_document_from_path(path)?).into_bytes())
 }
 
+/// Sign the manifest with a new key. `out_sig` gets the signature entry as JSON, ready for the
+/// `signature` field of a publish request.
 pub fn sign_manifest(manifest: &str, out_sig: &str, out_key: &str, out_pub: &str) -> Result<()> {
     println!("manifest-tool: signing manifest: {}", manifest);
-    let bytes = canonical_bytes_from_path(manifest)?;
+    let document = signing_document_from_path(manifest)?;
     let mut csprng = OsRng {};
     let kp: Keypair = Keypair::generate(&mut csprng);
-    let sig = kp.sign(&bytes)?;
-    fs::write(out_sig, general_purpose::STANDARD.encode(sig.to_bytes()))?;
+    let entry = SignatureEntry::sign(
+        &document,
+        kp.signing_key(),
+        chrono::Utc::now().timestamp(),
+        None,
+    )?
+    .with_public_key(&kp.public);
+    fs::write(out_sig, serde_json::to_string_pretty(&entry)?)?;
     fs::write(out_key, kp.to_bytes())?;
     fs::write(
         out_pub,
@@ -36,24 +49,16 @@ pub fn verify_manifest(manifest: &str, sigfile: &str, pubfile: &str) -> Result<b
         "manifest-tool: verifying manifest: {} (sig={}, pub={})",
         manifest, sigfile, pubfile
     );
-    let bytes = canonical_bytes_from_path(manifest)?;
-    let sig_b64 = fs::read_to_string(sigfile)?;
-    let sig_bytes = general_purpose::STANDARD.decode(sig_b64.trim())?;
-    let mut sig_arr = [0u8; 64];
-    sig_arr.copy_from_slice(&sig_bytes[..64]);
-    let sig = Signature::from_bytes(&sig_arr);
-    let pub_b64 = fs::read_to_string(pubfile)?;
-    let pub_bytes = general_purpose::STANDARD.decode(pub_b64.trim())?;
-    let mut pub_arr = [0u8; 32];
-    pub_arr.copy_from_slice(&pub_bytes[..32]);
-    let pubk = PublicKey::from_bytes(&pub_arr)?;
-    match pubk.verify(&bytes, &sig) {
-        Ok(_) => {
+    let document = signing_document_from_path(manifest)?;
+    let entry: SignatureEntry = serde_json::from_str(&fs::read_to_string(sigfile)?)?;
+    let pubk = verseguy_signing::decode_public_key(&fs::read_to_string(pubfile)?)?;
+    match entry.verify(&document, &pubk, None) {
+        Ok(()) => {
             println!("manifest-tool: verification OK");
             Ok(true)
         }
-        Err(_) => {
-            println!("manifest-tool: verification FAILED");
+        Err(e) => {
+            println!("manifest-tool: verification FAILED: {}", e);
             Ok(false)
         }
     }
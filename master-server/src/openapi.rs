@@ -0,0 +1,46 @@
+//! OpenAPI document of the master server, generated from the `#[utoipa::path]` annotations of
+//! the handlers registered in [`crate::build_app`].
+
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "VerseGuy Master Server",
+        description = "Accounts, licensing, plugin registry, legal documents and GDPR requests"
+    ),
+    modifiers(&SecurityAddon),
+    tags(
+        (name = "auth", description = "Accounts, sessions and personal access tokens"),
+        (name = "license", description = "License validation"),
+        (name = "plugins", description = "Plugin registry and manifest verification"),
+        (name = "publishers", description = "Publisher accounts, namespaces and yanking"),
+        (name = "orgs", description = "Organizations"),
+        (name = "legal", description = "Terms of service and other legal documents"),
+        (name = "admin", description = "Administration, guarded by `admin:*` permissions"),
+        (name = "gdpr", description = "Data export and deletion"),
+        (name = "system", description = "Health and metrics"),
+    )
+)]
+pub struct ApiDoc;
+
+/// `bearer` scheme: a session JWT or a `vgp_` personal access token
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some(
+                        "Session JWT from /auth/login or a `vgp_` personal access token",
+                    ))
+                    .build(),
+            ),
+        );
+    }
+}
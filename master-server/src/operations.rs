@@ -0,0 +1,719 @@
+//! Operator endpoints used by `verseguy-admin`: account lookup and locking, GDPR export, audit log
+//! verification, database backups, license issuance and an admin view of the plugin registry.
+//!
+//! A locked account keeps its data, but it can no longer log in, and its sessions and tokens stop
+//! working. The lock is stored under `user_lock:{user_id}` and checked by
+//! [`crate::auth::authenticate`]. Each issued license is recorded under `license:{id}` so that it
+//! can be listed and revoked. A revoked license no longer passes `/license/validate`.
+
+use crate::admin::{
+    require_admin, roles_of, ADMIN_AUDIT, ADMIN_BACKUPS, ADMIN_LICENSES, ADMIN_PLUGINS,
+    ADMIN_USERS, COMPLIANCE_EXPORT,
+};
+use crate::plugins::{get_manifest, get_yank, is_revoked, PluginManifest, YankRecord};
+use crate::publishers::YankRequest;
+use crate::state::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use base64::engine::general_purpose;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use verseguy_auth::{AccountKind, IdentityStore, License, User};
+use verseguy_shared_error::{AppError, ProblemDetails};
+use verseguy_storage::backup::BackupService;
+use verseguy_storage::RocksDBStorage;
+
+/// Why, by whom and when an account was locked
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct UserLock {
+    pub reason: String,
+    pub by: String,
+    pub at: i64,
+}
+
+fn lock_key(user_id: &str) -> String {
+    format!("user_lock:{}", user_id)
+}
+
+pub fn lock_of(storage: &RocksDBStorage, user_id: &str) -> anyhow::Result<Option<UserLock>> {
+    storage.get(lock_key(user_id).as_bytes())
+}
+
+/// Reject `user_id` with `account_locked` while their account is locked
+pub fn ensure_unlocked(state: &AppState, user_id: &str) -> Result<(), AppError> {
+    match lock_of(&state.storage, user_id).map_err(AppError::internal)? {
+        Some(lock) => Err(AppError::coded(
+            StatusCode::FORBIDDEN,
+            "account_locked",
+            format!("account locked: {}", lock.reason),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The account with id or username `user`
+fn find_user(state: &AppState, user: &str) -> Result<User, AppError> {
+    let found = match IdentityStore::new((*state.storage).clone())
+        .get_user(user)
+        .map_err(AppError::internal)?
+    {
+        Some(found) => Some(found),
+        None => state
+            .storage
+            .get(format!("user:username:{}", user).as_bytes())
+            .map_err(AppError::internal)?,
+    };
+    found.ok_or_else(|| AppError::NotFound(format!("no user {}", user)))
+}
+
+/// Account details shown to operators; never includes credentials
+#[derive(Serialize, ToSchema)]
+pub struct UserSummary {
+    pub id: String,
+    pub username: String,
+    pub email: Option<String>,
+    /// `human` or `service`
+    #[schema(value_type = String)]
+    pub kind: AccountKind,
+    /// `Free`, `Pro` or `Enterprise`
+    #[schema(value_type = String)]
+    pub license: License,
+    /// Roles from assignments, the identity provider and `security.admin_users`
+    pub roles: Vec<String>,
+    /// Identity providers the account signs in with
+    pub providers: Vec<String>,
+    pub created_at: i64,
+    pub locked: Option<UserLock>,
+}
+
+fn summary(state: &AppState, user: User) -> Result<UserSummary, AppError> {
+    Ok(UserSummary {
+        roles: roles_of(state, &user.id)?,
+        locked: lock_of(&state.storage, &user.id).map_err(AppError::internal)?,
+        providers: user.identities.iter().map(|i| i.provider.clone()).collect(),
+        created_at: user.created_at.timestamp(),
+        id: user.id,
+        username: user.username,
+        email: user.email,
+        kind: user.kind,
+        license: user.license,
+    })
+}
+
+/// Admin: look up an account by id or username
+#[utoipa::path(
+    get,
+    path = "/admin/users/{user}",
+    tag = "admin",
+    params(("user" = String, Path, description = "User id or username")),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The account", body = UserSummary),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:users` permission", body = ProblemDetails),
+        (status = 404, description = "Unknown user", body = ProblemDetails),
+    )
+)]
+pub async fn admin_user_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user): Path<String>,
+) -> Result<Json<UserSummary>, AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_USERS)?;
+    let user = find_user(&state, &user)?;
+    admin.audit(&state, "users.lookup", serde_json::json!({ "id": user.id }))?;
+    Ok(Json(summary(&state, user)?))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LockRequest {
+    pub reason: String,
+}
+
+/// Admin: lock an account, ending its sessions and tokens
+#[utoipa::path(
+    post,
+    path = "/admin/users/{user}/lock",
+    tag = "admin",
+    params(("user" = String, Path, description = "User id or username")),
+    request_body = LockRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The locked account", body = UserSummary),
+        (
+            status = 400,
+            description = "Empty reason, or the caller's own account",
+            body = ProblemDetails
+        ),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:users` permission", body = ProblemDetails),
+        (status = 404, description = "Unknown user", body = ProblemDetails),
+    )
+)]
+pub async fn admin_lock_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user): Path<String>,
+    Json(req): Json<LockRequest>,
+) -> Result<Json<UserSummary>, AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_USERS)?;
+    let user = find_user(&state, &user)?;
+    if req.reason.trim().is_empty() {
+        return Err(AppError::BadRequest("a reason is required".into()));
+    }
+    if user.id == admin.user_id {
+        return Err(AppError::BadRequest("cannot lock your own account".into()));
+    }
+    let lock = UserLock {
+        reason: req.reason,
+        by: admin.user_id.clone(),
+        at: Utc::now().timestamp(),
+    };
+    state
+        .storage
+        .put(lock_key(&user.id).as_bytes(), &lock)
+        .map_err(AppError::internal)?;
+    admin.audit(
+        &state,
+        "users.lock",
+        serde_json::json!({ "id": user.id, "reason": lock.reason }),
+    )?;
+    Ok(Json(summary(&state, user)?))
+}
+
+/// Admin: unlock an account
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{user}/lock",
+    tag = "admin",
+    params(("user" = String, Path, description = "User id or username")),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The unlocked account", body = UserSummary),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:users` permission", body = ProblemDetails),
+        (status = 404, description = "Unknown user", body = ProblemDetails),
+    )
+)]
+pub async fn admin_unlock_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user): Path<String>,
+) -> Result<Json<UserSummary>, AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_USERS)?;
+    let user = find_user(&state, &user)?;
+    state
+        .storage
+        .delete(lock_key(&user.id).as_bytes())
+        .map_err(AppError::internal)?;
+    admin.audit(&state, "users.unlock", serde_json::json!({ "id": user.id }))?;
+    Ok(Json(summary(&state, user)?))
+}
+
+/// Export a user's personal data and audit trail
+#[utoipa::path(
+    get,
+    path = "/users/{user_id}/data",
+    tag = "gdpr",
+    params(("user_id" = String, Path, description = "User whose data is exported")),
+    security(("bearer" = [])),
+    responses(
+        (
+            status = 200,
+            description = "Account and sessions under `user`, plus `audit` and `tos` history",
+            body = serde_json::Value
+        ),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing `compliance:export` permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Unknown user", body = ProblemDetails),
+    )
+)]
+pub async fn user_data_export_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, &headers, COMPLIANCE_EXPORT)?;
+    if IdentityStore::new((*state.storage).clone())
+        .get_user(&user_id)
+        .map_err(AppError::internal)?
+        .is_none()
+    {
+        return Err(AppError::NotFound(format!("no user {}", user_id)));
+    }
+    let export = verseguy_compliance::gdpr::export_user_data(&state.storage, &user_id)
+        .map_err(AppError::internal)?;
+    let user: serde_json::Value = serde_json::from_str(&export).map_err(AppError::internal)?;
+    let audit = verseguy_audit::AuditService::new(state.storage.clone())
+        .export_for_user(&user_id)
+        .map_err(AppError::internal)?;
+    let tos = crate::legal::acceptances_of(&state.storage, &user_id).map_err(AppError::internal)?;
+    admin.audit(&state, "gdpr.export", serde_json::json!({ "id": user_id }))?;
+    Ok(Json(
+        serde_json::json!({ "user": user, "audit": audit, "tos": tos }),
+    ))
+}
+
+/// Admin: check the audit log's hash chain
+#[utoipa::path(
+    get,
+    path = "/admin/audit/verify",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (
+            status = 200,
+            description = "Whether the chain is intact under `valid`",
+            body = serde_json::Value
+        ),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:audit` permission", body = ProblemDetails),
+    )
+)]
+pub async fn admin_audit_verify_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_AUDIT)?;
+    let valid = verseguy_audit::AuditService::new(state.storage.clone())
+        .verify()
+        .map_err(AppError::internal)?;
+    admin.audit(
+        &state,
+        "audit.verify",
+        serde_json::json!({ "valid": valid }),
+    )?;
+    Ok(Json(serde_json::json!({ "valid": valid })))
+}
+
+/// A backup archive in `storage.backup_dir`
+#[derive(Serialize, ToSchema)]
+pub struct BackupInfo {
+    pub name: String,
+    pub size: u64,
+    pub created_at: i64,
+}
+
+fn backup_info(path: &std::path::Path) -> std::io::Result<BackupInfo> {
+    let meta = std::fs::metadata(path)?;
+    Ok(BackupInfo {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        size: meta.len(),
+        created_at: DateTime::<Utc>::from(meta.modified()?).timestamp(),
+    })
+}
+
+/// Archive a consistent snapshot of `storage` into `backup_dir`. Artifact blobs are not
+/// included.
+pub fn create_backup(
+    storage: &RocksDBStorage,
+    backup_dir: &std::path::Path,
+) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(backup_dir)?;
+    // a dot name keeps the snapshot out of listings and cleanups, which look for `backup_*`
+    let snapshot = backup_dir.join(format!(".snapshot-{}", Uuid::new_v4()));
+    storage.checkpoint(&snapshot)?;
+    let archive = BackupService::new(&snapshot, backup_dir).create_backup(None);
+    std::fs::remove_dir_all(&snapshot)?;
+    archive
+}
+
+/// Backups in `backup_dir`, newest first
+pub fn list_backups(backup_dir: &std::path::Path) -> anyhow::Result<Vec<BackupInfo>> {
+    if !backup_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(backup_dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with("backup_") {
+            paths.push(entry.path());
+        }
+    }
+    // names embed the creation time, so they sort chronologically
+    paths.sort();
+    paths.reverse();
+    Ok(paths
+        .iter()
+        .map(|p| backup_info(p))
+        .collect::<std::io::Result<_>>()?)
+}
+
+/// Admin: back up the database
+#[utoipa::path(
+    post,
+    path = "/admin/backups",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 201, description = "The new backup", body = BackupInfo),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:backups` permission", body = ProblemDetails),
+    )
+)]
+pub async fn admin_create_backup_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<BackupInfo>), AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_BACKUPS)?;
+    let storage = state.storage.clone();
+    let backup_dir = state.config.storage.backup_dir();
+    let info = tokio::task::spawn_blocking(move || {
+        create_backup(&storage, &backup_dir).and_then(|path| Ok(backup_info(&path)?))
+    })
+    .await
+    .map_err(AppError::internal)?
+    .map_err(AppError::internal)?;
+    admin.audit(
+        &state,
+        "backups.create",
+        serde_json::json!({ "name": info.name, "size": info.size }),
+    )?;
+    Ok((StatusCode::CREATED, Json(info)))
+}
+
+/// Admin: list database backups
+#[utoipa::path(
+    get,
+    path = "/admin/backups",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (
+            status = 200,
+            description = "Backups under `backups`, newest first",
+            body = serde_json::Value
+        ),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:backups` permission", body = ProblemDetails),
+    )
+)]
+pub async fn admin_list_backups_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_BACKUPS)?;
+    let backups = list_backups(&state.config.storage.backup_dir()).map_err(AppError::internal)?;
+    admin.audit(&state, "backups.list", serde_json::json!({}))?;
+    Ok(Json(serde_json::json!({ "backups": backups })))
+}
+
+/// `license:{id}` record of a license issued through `/admin/licenses`
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct IssuedLicense {
+    pub id: String,
+    /// `Free`, `Pro` or `Enterprise`
+    #[schema(value_type = String)]
+    pub tier: License,
+    /// Customer or account the license was issued to
+    pub subject: Option<String>,
+    pub issued_by: String,
+    pub issued_at: i64,
+    pub expires_at: i64,
+    #[serde(default)]
+    pub revoked_at: Option<i64>,
+    #[serde(default)]
+    pub revoke_reason: Option<String>,
+}
+
+fn license_key(id: &str) -> String {
+    format!("license:{}", id)
+}
+
+/// Whether `token` is a license issued here and revoked since; tokens without a record are not
+pub fn is_license_revoked(storage: &RocksDBStorage, token: &str) -> anyhow::Result<bool> {
+    let Some(payload_b64) = token.split('.').next() else {
+        return Ok(false);
+    };
+    let Ok(payload) = general_purpose::STANDARD.decode(payload_b64) else {
+        return Ok(false);
+    };
+    let payload: serde_json::Value = serde_json::from_slice(&payload).unwrap_or_default();
+    let Some(id) = payload.get("id").and_then(serde_json::Value::as_str) else {
+        return Ok(false);
+    };
+    let record: Option<IssuedLicense> = storage.get(license_key(id).as_bytes())?;
+    Ok(record.is_some_and(|r| r.revoked_at.is_some()))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct IssueLicenseRequest {
+    /// `Free`, `Pro` or `Enterprise`
+    #[schema(value_type = String)]
+    pub tier: License,
+    /// Days until the license expires; 365 when omitted
+    pub days: Option<i64>,
+    pub subject: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct IssueLicenseResponse {
+    /// Signed license token to hand to the customer
+    pub token: String,
+    pub license: IssuedLicense,
+}
+
+/// Admin: issue a signed license token
+#[utoipa::path(
+    post,
+    path = "/admin/licenses",
+    tag = "admin",
+    request_body = IssueLicenseRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, description = "The token and its record", body = IssueLicenseResponse),
+        (status = 400, description = "Unknown tier or non-positive `days`", body = ProblemDetails),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:licenses` permission", body = ProblemDetails),
+    )
+)]
+pub async fn admin_issue_license_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<IssueLicenseRequest>,
+) -> Result<(StatusCode, Json<IssueLicenseResponse>), AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_LICENSES)?;
+    let days = req.days.unwrap_or(365);
+    if days < 1 {
+        return Err(AppError::BadRequest("days must be positive".into()));
+    }
+    let now = Utc::now().timestamp();
+    let license = IssuedLicense {
+        id: Uuid::new_v4().to_string(),
+        tier: req.tier,
+        subject: req.subject,
+        issued_by: admin.user_id.clone(),
+        issued_at: now,
+        expires_at: now + days * 86_400,
+        revoked_at: None,
+        revoke_reason: None,
+    };
+    let payload = serde_json::json!({
+        "id": license.id,
+        "tier": license.tier,
+        "exp": license.expires_at,
+        "sub": license.subject,
+    });
+    let payload_b64 = general_purpose::STANDARD.encode(payload.to_string());
+    let token =
+        verseguy_licensing::validator::create_signed_token(&payload_b64, &state.license_secret)
+            .map_err(AppError::internal)?;
+    state
+        .storage
+        .put(license_key(&license.id).as_bytes(), &license)
+        .map_err(AppError::internal)?;
+    admin.audit(
+        &state,
+        "licenses.issue",
+        serde_json::json!({ "id": license.id, "tier": license.tier, "subject": license.subject }),
+    )?;
+    Ok((
+        StatusCode::CREATED,
+        Json(IssueLicenseResponse { token, license }),
+    ))
+}
+
+/// Admin: list issued licenses
+#[utoipa::path(
+    get,
+    path = "/admin/licenses",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Licenses under `licenses`", body = serde_json::Value),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:licenses` permission", body = ProblemDetails),
+    )
+)]
+pub async fn admin_list_licenses_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_LICENSES)?;
+    let mut licenses: Vec<IssuedLicense> = state
+        .storage
+        .prefix_scan(b"license:")
+        .map_err(AppError::internal)?;
+    licenses.sort_by_key(|l| l.issued_at);
+    admin.audit(&state, "licenses.list", serde_json::json!({}))?;
+    Ok(Json(serde_json::json!({ "licenses": licenses })))
+}
+
+#[derive(Deserialize, ToSchema, Default)]
+pub struct RevokeLicenseRequest {
+    pub reason: Option<String>,
+}
+
+/// Admin: revoke an issued license; revoking it again keeps the first revocation
+#[utoipa::path(
+    post,
+    path = "/admin/licenses/{id}/revoke",
+    tag = "admin",
+    params(("id" = String, Path, description = "License id")),
+    request_body = RevokeLicenseRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The revoked license", body = IssuedLicense),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:licenses` permission", body = ProblemDetails),
+        (status = 404, description = "Unknown license", body = ProblemDetails),
+    )
+)]
+pub async fn admin_revoke_license_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    body: Option<Json<RevokeLicenseRequest>>,
+) -> Result<Json<IssuedLicense>, AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_LICENSES)?;
+    let Json(req) = body.unwrap_or_default();
+    let mut license: IssuedLicense = state
+        .storage
+        .get(license_key(&id).as_bytes())
+        .map_err(AppError::internal)?
+        .ok_or_else(|| AppError::NotFound(format!("no license {}", id)))?;
+    if license.revoked_at.is_none() {
+        license.revoked_at = Some(Utc::now().timestamp());
+        license.revoke_reason = req.reason;
+        state
+            .storage
+            .put(license_key(&id).as_bytes(), &license)
+            .map_err(AppError::internal)?;
+    }
+    admin.audit(
+        &state,
+        "licenses.revoke",
+        serde_json::json!({ "id": id, "reason": license.revoke_reason }),
+    )?;
+    Ok(Json(license))
+}
+
+/// A plugin version as operators see it, including withdrawn ones
+#[derive(Serialize, ToSchema)]
+pub struct PluginVersionStatus {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub published_at: Option<i64>,
+    pub revoked: bool,
+    pub yanked: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AdminPluginsQuery {
+    /// Only versions of this plugin
+    pub id: Option<String>,
+}
+
+/// Admin: list every plugin version, including revoked and yanked ones
+#[utoipa::path(
+    get,
+    path = "/admin/plugins",
+    tag = "admin",
+    params(AdminPluginsQuery),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Versions under `plugins`", body = serde_json::Value),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:plugins` permission", body = ProblemDetails),
+    )
+)]
+pub async fn admin_list_plugins_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<AdminPluginsQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_PLUGINS)?;
+    let prefix = match &query.id {
+        Some(id) => format!("plugin:{}:", id),
+        None => "plugin:".to_string(),
+    };
+    let manifests: Vec<PluginManifest> = state
+        .storage
+        .prefix_scan(prefix.as_bytes())
+        .map_err(AppError::internal)?;
+    let mut plugins = Vec::with_capacity(manifests.len());
+    for m in manifests {
+        plugins.push(PluginVersionStatus {
+            revoked: is_revoked(&state.storage, &m.id, &m.version).map_err(AppError::internal)?,
+            yanked: get_yank(&state.storage, &m.id, &m.version)
+                .map_err(AppError::internal)?
+                .is_some(),
+            id: m.id,
+            name: m.name,
+            version: m.version,
+            published_at: m.published_at,
+        });
+    }
+    admin.audit(
+        &state,
+        "plugins.list",
+        serde_json::json!({ "id": query.id }),
+    )?;
+    Ok(Json(serde_json::json!({ "plugins": plugins })))
+}
+
+/// Admin: yank any plugin version, whoever maintains it
+#[utoipa::path(
+    post,
+    path = "/admin/plugins/{id}/{version}/yank",
+    tag = "admin",
+    params(
+        ("id" = String, Path, description = "Plugin id"),
+        ("version" = String, Path, description = "Plugin version"),
+    ),
+    request_body = YankRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The yank record", body = YankRecord),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:plugins` permission", body = ProblemDetails),
+        (status = 404, description = "Unknown version", body = ProblemDetails),
+    )
+)]
+pub async fn admin_yank_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((id, version)): Path<(String, String)>,
+    body: Option<Json<YankRequest>>,
+) -> Result<Json<YankRecord>, AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_PLUGINS)?;
+    if get_manifest(&state.storage, &id, &version)
+        .map_err(AppError::internal)?
+        .is_none()
+    {
+        return Err(AppError::NotFound("plugin version not found".into()));
+    }
+    let Json(req) = body.unwrap_or_default();
+    let record = YankRecord {
+        reason: req.reason,
+        by: admin.user_id.clone(),
+        at: Utc::now().timestamp(),
+    };
+    crate::plugins::yank_manifest(&state.storage, &id, &version, &record)
+        .map_err(AppError::internal)?;
+    state
+        .search
+        .reindex(&state.storage, &id)
+        .map_err(AppError::internal)?;
+    admin.audit(
+        &state,
+        "plugins.yank",
+        serde_json::json!({ "id": id, "version": version, "reason": record.reason }),
+    )?;
+    Ok(Json(record))
+}
@@ -1,12 +1,16 @@
-use crate::ed25519_compat::{Keypair, PublicKey};
+use crate::artifacts::ArtifactRef;
+use crate::ed25519_compat::Keypair;
 use anyhow::Result;
 use base64::{engine::general_purpose, Engine as _};
 use chrono::Utc;
 use ed25519_dalek::{Signature, Verifier};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use verseguy_auth::License;
+use verseguy_signing::{Envelope, SignatureEntry, TrustStore};
 use verseguy_storage::RocksDBStorage;
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
 pub struct PluginManifest {
     pub id: String,
     pub name: String,
@@ -14,6 +18,43 @@ pub struct PluginManifest {
     pub author: Option<String>,
     pub description: Option<String>,
     pub published_at: Option<i64>,
+    /// Uploaded artifact; covered by the manifest signature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<ArtifactRef>,
+    /// Oldest core version the plugin runs on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub core_version_min: Option<String>,
+    /// SDK version the plugin was built against; hosts with a semver-compatible SDK load it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sdk_version: Option<String>,
+    /// Other plugins this one needs installed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<Dependency>,
+    /// License tier needed to use the plugin; `Free` when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>)]
+    pub license_required: Option<License>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Keywords for search
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Capabilities the plugin requests, such as `storage:read`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capabilities: Vec<String>,
+}
+
+/// A plugin and the versions of it that will do
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct Dependency {
+    pub id: String,
+    /// Semver requirement such as `^1.2` or `>=1.0, <3`; any version when omitted
+    #[serde(default = "any_version")]
+    pub version: String,
+}
+
+fn any_version() -> String {
+    "*".to_string()
 }
 
 impl PluginManifest {
@@ -24,60 +65,136 @@ impl PluginManifest {
     }
 }
 
-fn canonical_manifest_bytes(manifest: &PluginManifest) -> Result<Vec<u8>> {
-    // Serialize deterministically
-    let bytes = serde_json::to_vec(manifest)?;
-    Ok(bytes)
+/// The JSON a manifest signature covers: the manifest without `published_at`, which the
+/// registry sets after the publisher has signed; the master signature's `signed_at` records it
+pub fn signing_document(manifest: &serde_json::Value) -> serde_json::Value {
+    verseguy_signing::plugin::signing_document(manifest)
 }
 
-#[allow(clippy::disallowed_methods)]
+fn manifest_document(manifest: &PluginManifest) -> Result<serde_json::Value> {
+    Ok(signing_document(&serde_json::to_value(manifest)?))
+}
+
+/// Signatures stored under `plugin_sig:{id}:{version}`
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StoredSignature {
+    Envelope(Envelope),
+    /// Base64 master signature over `serde_json::to_vec(manifest)`, written before envelopes
+    Legacy(String),
+}
+
+/// Returned when storing a version that was already published; versions are immutable
+#[derive(Debug)]
+pub struct VersionExists {
+    pub id: String,
+    pub version: String,
+}
+
+impl std::fmt::Display for VersionExists {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} has already been published", self.id, self.version)
+    }
+}
+
+impl std::error::Error for VersionExists {}
+
+/// Store `manifest`, counter-signed with `keypair` when one is given
 pub fn store_manifest(
     storage: &RocksDBStorage,
     manifest: &PluginManifest,
     keypair: Option<&Keypair>,
+) -> Result<()> {
+    store_signed_manifest(storage, manifest, None, keypair)
+}
+
+/// Store `manifest` with the publisher's signature, counter-signed with `keypair` when one is
+/// given. The publisher signature is stored as is; check it with
+/// [`Envelope::verify_publisher`] first. Fails with [`VersionExists`] when the version was
+/// already published.
+pub fn store_signed_manifest(
+    storage: &RocksDBStorage,
+    manifest: &PluginManifest,
+    publisher: Option<SignatureEntry>,
+    keypair: Option<&Keypair>,
 ) -> Result<()> {
     let key = format!("plugin:{}:{}", manifest.id, manifest.version);
+    if storage.get::<_, PluginManifest>(key.as_bytes())?.is_some() {
+        return Err(VersionExists {
+            id: manifest.id.clone(),
+            version: manifest.version.clone(),
+        }
+        .into());
+    }
     storage.put(key.as_bytes(), manifest)?;
 
+    let mut envelope = Envelope {
+        publisher,
+        master: None,
+    };
     if let Some(kp) = keypair {
-        let bytes = canonical_manifest_bytes(manifest)?;
-        let sig = kp.sign(&bytes)?;
-        let sig_b64 = general_purpose::STANDARD.encode(sig.to_bytes());
+        let signed_at = manifest
+            .published_at
+            .unwrap_or_else(|| Utc::now().timestamp());
+        envelope.countersign(&manifest_document(manifest)?, kp.signing_key(), signed_at)?;
+    }
+    if envelope != Envelope::default() {
         let sig_key = format!("plugin_sig:{}:{}", manifest.id, manifest.version);
-        storage.put(sig_key.as_bytes(), &sig_b64)?;
+        storage.put(sig_key.as_bytes(), &StoredSignature::Envelope(envelope))?;
     }
 
     Ok(())
 }
 
-pub fn search_manifests(storage: &RocksDBStorage, q: &str) -> Result<Vec<PluginManifest>> {
-    let mut results = Vec::new();
-    let items: Vec<PluginManifest> = storage.prefix_scan(b"plugin:")?;
-    let q_lower = q.to_lowercase();
-    for it in items {
-        if it.name.to_lowercase().contains(&q_lower) || it.id.to_lowercase().contains(&q_lower) {
-            results.push(it);
-        }
-    }
-    Ok(results)
+/// Signature envelope of a stored version; `None` for unsigned versions and versions signed
+/// before envelopes
+pub fn get_envelope(storage: &RocksDBStorage, id: &str, version: &str) -> Result<Option<Envelope>> {
+    let sig_key = format!("plugin_sig:{}:{}", id, version);
+    let stored: Option<StoredSignature> = storage.get(sig_key.as_bytes())?;
+    Ok(match stored {
+        Some(StoredSignature::Envelope(envelope)) => Some(envelope),
+        Some(StoredSignature::Legacy(_)) | None => None,
+    })
 }
 
+pub fn get_manifest(
+    storage: &RocksDBStorage,
+    id: &str,
+    version: &str,
+) -> Result<Option<PluginManifest>> {
+    let key = format!("plugin:{}:{}", id, version);
+    Ok(storage.get(key.as_bytes())?)
+}
+
+/// Whether the stored signatures of `manifest` verify: the master counter-signature with a key
+/// from `trust`, and the publisher signature it covers
 pub fn verify_manifest(
     storage: &RocksDBStorage,
     manifest: &PluginManifest,
-    pubkey: &PublicKey,
+    trust: &TrustStore,
 ) -> Result<bool> {
-    let bytes = canonical_manifest_bytes(manifest)?;
     let sig_key = format!("plugin_sig:{}:{}", manifest.id, manifest.version);
-    let sig_b64: Option<String> = storage.get(sig_key.as_bytes())?;
-    let sig_b64 = sig_b64.ok_or_else(|| anyhow::anyhow!("signature not found"))?;
-    let sig_bytes = general_purpose::STANDARD.decode(sig_b64)?;
-    let mut sig_arr = [0u8; 64];
-    sig_arr.copy_from_slice(&sig_bytes[..64]);
-    let sig = Signature::from_bytes(&sig_arr);
-    match pubkey.verify(&bytes, &sig) {
-        Ok(_) => Ok(true),
-        Err(_) => Ok(false),
+    let stored: Option<StoredSignature> = storage.get(sig_key.as_bytes())?;
+    match stored.ok_or_else(|| anyhow::anyhow!("signature not found"))? {
+        StoredSignature::Envelope(envelope) => {
+            match envelope.verify(&manifest_document(manifest)?, trust) {
+                Ok(()) => Ok(true),
+                Err(e) => {
+                    tracing::debug!("manifest {}@{}: {}", manifest.id, manifest.version, e);
+                    Ok(false)
+                }
+            }
+        }
+        StoredSignature::Legacy(sig_b64) => {
+            let bytes = serde_json::to_vec(manifest)?;
+            let Ok(sig_bytes) = general_purpose::STANDARD.decode(sig_b64.trim()) else {
+                return Ok(false);
+            };
+            let Ok(sig) = Signature::from_slice(&sig_bytes) else {
+                return Ok(false);
+            };
+            Ok(trust.keys().any(|key| key.verify(&bytes, &sig).is_ok()))
+        }
     }
 }
 
@@ -89,8 +206,10 @@ pub fn revoke_manifest(
     reason: &str,
 ) -> Result<()> {
     let key = format!("plugin_revoked:{}:{}", id, version);
-    let entry = serde_json::json!({"reason": reason, "at": Utc::now().timestamp()});
+    let at = Utc::now().timestamp();
+    let entry = serde_json::json!({"reason": reason, "at": at});
     storage.put(key.as_bytes(), &entry)?;
+    crate::crl::record_revocation(storage, id, version, reason, at)?;
     Ok(())
 }
 
@@ -99,3 +218,39 @@ pub fn is_revoked(storage: &RocksDBStorage, id: &str, version: &str) -> Result<b
     let v: Option<serde_json::Value> = storage.get(key.as_bytes())?;
     Ok(v.is_some())
 }
+
+/// Why and by whom a version was yanked
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct YankRecord {
+    pub reason: Option<String>,
+    pub by: String,
+    pub at: i64,
+}
+
+/// Withdraw a version from search. Unlike revocation, yanking says nothing about the
+/// version being unsafe: it still verifies and downloads, and its publisher can undo it.
+pub fn yank_manifest(
+    storage: &RocksDBStorage,
+    id: &str,
+    version: &str,
+    record: &YankRecord,
+) -> Result<()> {
+    let key = format!("plugin_yanked:{}:{}", id, version);
+    storage.put(key.as_bytes(), record)?;
+    Ok(())
+}
+
+pub fn unyank_manifest(storage: &RocksDBStorage, id: &str, version: &str) -> Result<()> {
+    let key = format!("plugin_yanked:{}:{}", id, version);
+    storage.delete(key.as_bytes())?;
+    Ok(())
+}
+
+pub fn get_yank(storage: &RocksDBStorage, id: &str, version: &str) -> Result<Option<YankRecord>> {
+    let key = format!("plugin_yanked:{}:{}", id, version);
+    Ok(storage.get(key.as_bytes())?)
+}
+
+pub fn is_yanked(storage: &RocksDBStorage, id: &str, version: &str) -> Result<bool> {
+    Ok(get_yank(storage, id, version)?.is_some())
+}
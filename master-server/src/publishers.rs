@@ -0,0 +1,639 @@
+//! Publisher accounts and plugin ownership.
+//!
+//! A user becomes a publisher with `POST /publishers`, registers the Ed25519 keys they sign
+//! manifests with and claims id namespaces such as `org.verseguy.*`. Only the owner of the
+//! namespace covering a plugin id, and the co-maintainers they add to a plugin, may publish or
+//! yank its versions. Personal access tokens need the `plugins:publish` scope for all of this.
+//!
+//! Publishing without a bearer token still works for ids outside every claimed namespace, so
+//! registries without publisher accounts keep working.
+
+use crate::auth::Principal;
+use crate::ed25519_compat::PublicKey;
+use crate::plugins::{get_manifest, YankRecord};
+use crate::state::AppState;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use verseguy_shared_error::{AppError, ProblemDetails};
+use verseguy_storage::RocksDBStorage;
+
+/// Scope personal access tokens need to publish and manage plugins
+pub const PUBLISH_SCOPE: &str = "plugins:publish";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct Publisher {
+    pub user_id: String,
+    pub name: String,
+    pub created_at: i64,
+    /// Keys the publisher signs manifests with; once one is registered, publishes must be
+    /// signed with one of them
+    pub keys: Vec<PublisherKey>,
+}
+
+impl Publisher {
+    /// Registered key with id `key_id`
+    pub fn key(&self, key_id: &str) -> Option<PublicKey> {
+        self.keys
+            .iter()
+            .find(|k| k.key_id == key_id)
+            .and_then(|k| verseguy_signing::decode_public_key(&k.public_key).ok())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct PublisherKey {
+    pub key_id: String,
+    /// Base64 Ed25519 public key
+    pub public_key: String,
+    pub added_at: i64,
+}
+
+/// Plugin id prefix owned by a publisher
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct Namespace {
+    /// `org.verseguy` covers `org.verseguy` and every id below it
+    pub prefix: String,
+    pub owner: String,
+    pub claimed_at: i64,
+}
+
+/// Who manages a plugin besides the namespace owner
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct PluginOwners {
+    pub id: String,
+    /// Publisher that first published the plugin
+    pub owner: String,
+    pub maintainers: Vec<String>,
+}
+
+pub fn get_publisher(storage: &RocksDBStorage, user_id: &str) -> anyhow::Result<Option<Publisher>> {
+    let key = format!("publisher:{}", user_id);
+    Ok(storage.get(key.as_bytes())?)
+}
+
+fn save_publisher(storage: &RocksDBStorage, publisher: &Publisher) -> anyhow::Result<()> {
+    let key = format!("publisher:{}", publisher.user_id);
+    storage.put(key.as_bytes(), publisher)
+}
+
+pub fn get_owners(storage: &RocksDBStorage, id: &str) -> anyhow::Result<Option<PluginOwners>> {
+    let key = format!("plugin_owners:{}", id);
+    Ok(storage.get(key.as_bytes())?)
+}
+
+fn save_owners(storage: &RocksDBStorage, owners: &PluginOwners) -> anyhow::Result<()> {
+    let key = format!("plugin_owners:{}", owners.id);
+    storage.put(key.as_bytes(), owners)
+}
+
+/// Validate a namespace as written by publishers (`org.verseguy` or `org.verseguy.*`):
+/// at least two dot-separated segments of lowercase letters, digits, `-` and `_`
+pub fn normalize_prefix(prefix: &str) -> Result<String, AppError> {
+    let prefix = prefix.trim();
+    let prefix = prefix.strip_suffix(".*").unwrap_or(prefix);
+    let segments: Vec<&str> = prefix.split('.').collect();
+    let valid_segment = |s: &&str| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+    };
+    if segments.len() < 2 || !segments.iter().all(valid_segment) {
+        return Err(AppError::BadRequest(format!(
+            "invalid namespace '{}': use at least two segments like org.example",
+            prefix
+        )));
+    }
+    Ok(prefix.to_string())
+}
+
+/// Whether the namespace `prefix` covers plugin id (or namespace) `id`
+pub fn covers(prefix: &str, id: &str) -> bool {
+    id == prefix
+        || id
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
+
+pub fn namespaces(storage: &RocksDBStorage) -> anyhow::Result<Vec<Namespace>> {
+    storage.prefix_scan(b"namespace:")
+}
+
+/// The most specific namespace covering `id`
+pub fn namespace_for(storage: &RocksDBStorage, id: &str) -> anyhow::Result<Option<Namespace>> {
+    Ok(namespaces(storage)?
+        .into_iter()
+        .filter(|ns| covers(&ns.prefix, id))
+        .max_by_key(|ns| ns.prefix.len()))
+}
+
+/// Whether `user_id` may publish and yank versions of `id`
+pub fn can_manage(storage: &RocksDBStorage, user_id: &str, id: &str) -> anyhow::Result<bool> {
+    if namespace_for(storage, id)?.is_some_and(|ns| ns.owner == user_id) {
+        return Ok(true);
+    }
+    Ok(get_owners(storage, id)?
+        .is_some_and(|o| o.owner == user_id || o.maintainers.iter().any(|m| m == user_id)))
+}
+
+/// Check that `caller` may publish a version of `id`, returning their publisher account.
+/// Without a caller only ids outside every namespace and without owners are open.
+pub fn authorize_publish(
+    storage: &RocksDBStorage,
+    caller: Option<&Principal>,
+    id: &str,
+) -> Result<Option<Publisher>, AppError> {
+    let claimed = namespace_for(storage, id)
+        .map_err(AppError::internal)?
+        .is_some()
+        || get_owners(storage, id)
+            .map_err(AppError::internal)?
+            .is_some();
+    let Some(caller) = caller else {
+        if claimed {
+            return Err(AppError::Forbidden(format!(
+                "{} belongs to a publisher; authenticate to publish it",
+                id
+            )));
+        }
+        return Ok(None);
+    };
+    caller.require_scope(PUBLISH_SCOPE)?;
+    let publisher = get_publisher(storage, &caller.user_id)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| AppError::Forbidden("publisher account required".into()))?;
+    if can_manage(storage, &caller.user_id, id).map_err(AppError::internal)? {
+        return Ok(Some(publisher));
+    }
+    if claimed {
+        Err(AppError::Forbidden(format!("not a maintainer of {}", id)))
+    } else {
+        Err(AppError::Forbidden(format!(
+            "claim a namespace covering {} before publishing it",
+            id
+        )))
+    }
+}
+
+/// Record the first publisher of `id` as its owner
+pub fn record_owner(storage: &RocksDBStorage, id: &str, user_id: &str) -> anyhow::Result<()> {
+    if get_owners(storage, id)?.is_none() {
+        save_owners(
+            storage,
+            &PluginOwners {
+                id: id.to_string(),
+                owner: user_id.to_string(),
+                maintainers: Vec::new(),
+            },
+        )?;
+    }
+    Ok(())
+}
+
+fn require_publisher_account(
+    state: &AppState,
+    principal: &Principal,
+) -> Result<Publisher, AppError> {
+    principal.require_scope(PUBLISH_SCOPE)?;
+    get_publisher(&state.storage, &principal.user_id)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| AppError::NotFound("no publisher account".into()))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreatePublisherRequest {
+    /// Display name
+    pub name: String,
+}
+
+/// Create a publisher account for the calling user
+#[utoipa::path(
+    post,
+    path = "/publishers",
+    tag = "publishers",
+    request_body = CreatePublisherRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, description = "The new account", body = Publisher),
+        (status = 400, description = "Empty name", body = ProblemDetails),
+        (
+            status = 401,
+            description = "Missing or invalid bearer credentials",
+            body = ProblemDetails
+        ),
+        (
+            status = 403,
+            description = "Token lacks the `plugins:publish` scope",
+            body = ProblemDetails
+        ),
+        (status = 409, description = "The user already is a publisher", body = ProblemDetails),
+    )
+)]
+pub async fn create_publisher_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Json(req): Json<CreatePublisherRequest>,
+) -> Result<(StatusCode, Json<Publisher>), AppError> {
+    principal.require_scope(PUBLISH_SCOPE)?;
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest(
+            "publisher name must not be empty".into(),
+        ));
+    }
+    if get_publisher(&state.storage, &principal.user_id)
+        .map_err(AppError::internal)?
+        .is_some()
+    {
+        return Err(AppError::Conflict(
+            "publisher account already exists".into(),
+        ));
+    }
+    let publisher = Publisher {
+        user_id: principal.user_id.clone(),
+        name: name.to_string(),
+        created_at: Utc::now().timestamp(),
+        keys: Vec::new(),
+    };
+    save_publisher(&state.storage, &publisher).map_err(AppError::internal)?;
+    Ok((StatusCode::CREATED, Json(publisher)))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PublisherView {
+    pub publisher: Publisher,
+    pub namespaces: Vec<Namespace>,
+}
+
+/// Publisher account of the calling user, with the namespaces it owns
+#[utoipa::path(
+    get,
+    path = "/publishers/me",
+    tag = "publishers",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Account and namespaces", body = PublisherView),
+        (
+            status = 401,
+            description = "Missing or invalid bearer credentials",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "The user is not a publisher", body = ProblemDetails),
+    )
+)]
+pub async fn get_publisher_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<PublisherView>, AppError> {
+    let publisher = require_publisher_account(&state, &principal)?;
+    let namespaces = namespaces(&state.storage)
+        .map_err(AppError::internal)?
+        .into_iter()
+        .filter(|ns| ns.owner == publisher.user_id)
+        .collect();
+    Ok(Json(PublisherView {
+        publisher,
+        namespaces,
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AddKeyRequest {
+    /// Base64 Ed25519 public key
+    pub public_key: String,
+}
+
+/// Register a key the calling publisher signs manifests with
+#[utoipa::path(
+    post,
+    path = "/publishers/me/keys",
+    tag = "publishers",
+    request_body = AddKeyRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, description = "The registered key", body = PublisherKey),
+        (status = 400, description = "Invalid public key", body = ProblemDetails),
+        (status = 404, description = "The user is not a publisher", body = ProblemDetails),
+        (status = 409, description = "Key already registered", body = ProblemDetails),
+    )
+)]
+pub async fn add_key_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Json(req): Json<AddKeyRequest>,
+) -> Result<(StatusCode, Json<PublisherKey>), AppError> {
+    let mut publisher = require_publisher_account(&state, &principal)?;
+    let key = verseguy_signing::decode_public_key(&req.public_key)
+        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let key_id = verseguy_signing::key_id(&key);
+    if publisher.keys.iter().any(|k| k.key_id == key_id) {
+        return Err(AppError::Conflict(format!(
+            "key {} already registered",
+            key_id
+        )));
+    }
+    let record = PublisherKey {
+        key_id,
+        public_key: verseguy_signing::encode_public_key(&key),
+        added_at: Utc::now().timestamp(),
+    };
+    publisher.keys.push(record.clone());
+    save_publisher(&state.storage, &publisher).map_err(AppError::internal)?;
+    Ok((StatusCode::CREATED, Json(record)))
+}
+
+/// Remove one of the calling publisher's keys
+#[utoipa::path(
+    delete,
+    path = "/publishers/me/keys/{key_id}",
+    tag = "publishers",
+    params(("key_id" = String, Path, description = "Key id")),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The updated account", body = Publisher),
+        (status = 404, description = "Not a publisher, or no such key", body = ProblemDetails),
+    )
+)]
+pub async fn remove_key_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Path(key_id): Path<String>,
+) -> Result<Json<Publisher>, AppError> {
+    let mut publisher = require_publisher_account(&state, &principal)?;
+    let before = publisher.keys.len();
+    publisher.keys.retain(|k| k.key_id != key_id);
+    if publisher.keys.len() == before {
+        return Err(AppError::NotFound(format!("no key {}", key_id)));
+    }
+    save_publisher(&state.storage, &publisher).map_err(AppError::internal)?;
+    Ok(Json(publisher))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ClaimNamespaceRequest {
+    /// `org.example` or `org.example.*`
+    pub prefix: String,
+}
+
+/// Claim a plugin id namespace for the calling publisher
+#[utoipa::path(
+    post,
+    path = "/publishers/me/namespaces",
+    tag = "publishers",
+    request_body = ClaimNamespaceRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, description = "The claimed namespace", body = Namespace),
+        (status = 400, description = "Invalid namespace", body = ProblemDetails),
+        (status = 404, description = "The user is not a publisher", body = ProblemDetails),
+        (
+            status = 409,
+            description = "Overlaps a namespace or plugin of another publisher",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn claim_namespace_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Json(req): Json<ClaimNamespaceRequest>,
+) -> Result<(StatusCode, Json<Namespace>), AppError> {
+    let publisher = require_publisher_account(&state, &principal)?;
+    let prefix = normalize_prefix(&req.prefix)?;
+
+    let overlapping = namespaces(&state.storage)
+        .map_err(AppError::internal)?
+        .into_iter()
+        .find(|ns| covers(&ns.prefix, &prefix) || covers(&prefix, &ns.prefix));
+    if let Some(ns) = overlapping {
+        return Err(AppError::Conflict(if ns.owner == publisher.user_id {
+            format!(
+                "{} is already covered by your namespace {}",
+                prefix, ns.prefix
+            )
+        } else {
+            format!(
+                "{} overlaps the namespace {} of another publisher",
+                prefix, ns.prefix
+            )
+        }));
+    }
+    // Plugins published without an account are adopted; those of other publishers are not
+    let owned: Vec<PluginOwners> = state
+        .storage
+        .prefix_scan(b"plugin_owners:")
+        .map_err(AppError::internal)?;
+    if let Some(plugin) = owned
+        .iter()
+        .find(|o| covers(&prefix, &o.id) && o.owner != publisher.user_id)
+    {
+        return Err(AppError::Conflict(format!(
+            "{} covers {}, which belongs to another publisher",
+            prefix, plugin.id
+        )));
+    }
+
+    let namespace = Namespace {
+        prefix,
+        owner: publisher.user_id,
+        claimed_at: Utc::now().timestamp(),
+    };
+    let key = format!("namespace:{}", namespace.prefix);
+    state
+        .storage
+        .put(key.as_bytes(), &namespace)
+        .map_err(AppError::internal)?;
+    Ok((StatusCode::CREATED, Json(namespace)))
+}
+
+/// Owners of `id`, for changes only the namespace owner or the plugin owner may make
+fn owned_plugin(
+    state: &AppState,
+    principal: &Principal,
+    id: &str,
+) -> Result<PluginOwners, AppError> {
+    principal.require_scope(PUBLISH_SCOPE)?;
+    let owners = get_owners(&state.storage, id)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| AppError::NotFound(format!("{} has no publisher", id)))?;
+    let namespace_owner = namespace_for(&state.storage, id)
+        .map_err(AppError::internal)?
+        .is_some_and(|ns| ns.owner == principal.user_id);
+    if owners.owner != principal.user_id && !namespace_owner {
+        return Err(AppError::Forbidden(format!(
+            "only the owner of {} may do this",
+            id
+        )));
+    }
+    Ok(owners)
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AddMaintainerRequest {
+    pub user_id: String,
+}
+
+/// Add a co-maintainer, who may publish and yank versions of the plugin
+#[utoipa::path(
+    post,
+    path = "/plugins/{id}/maintainers",
+    tag = "publishers",
+    params(("id" = String, Path, description = "Plugin id")),
+    request_body = AddMaintainerRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Owner and maintainers", body = PluginOwners),
+        (status = 403, description = "Caller does not own the plugin", body = ProblemDetails),
+        (
+            status = 404,
+            description = "Plugin without publisher, or user without publisher account",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn add_maintainer_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+    Json(req): Json<AddMaintainerRequest>,
+) -> Result<Json<PluginOwners>, AppError> {
+    let mut owners = owned_plugin(&state, &principal, &id)?;
+    if get_publisher(&state.storage, &req.user_id)
+        .map_err(AppError::internal)?
+        .is_none()
+    {
+        return Err(AppError::NotFound(format!(
+            "{} has no publisher account",
+            req.user_id
+        )));
+    }
+    if owners.owner != req.user_id && !owners.maintainers.contains(&req.user_id) {
+        owners.maintainers.push(req.user_id);
+        save_owners(&state.storage, &owners).map_err(AppError::internal)?;
+    }
+    Ok(Json(owners))
+}
+
+/// Remove a co-maintainer
+#[utoipa::path(
+    delete,
+    path = "/plugins/{id}/maintainers/{user_id}",
+    tag = "publishers",
+    params(
+        ("id" = String, Path, description = "Plugin id"),
+        ("user_id" = String, Path, description = "Maintainer to remove"),
+    ),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Owner and maintainers", body = PluginOwners),
+        (status = 403, description = "Caller does not own the plugin", body = ProblemDetails),
+        (status = 404, description = "Plugin without publisher", body = ProblemDetails),
+    )
+)]
+pub async fn remove_maintainer_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Path((id, user_id)): Path<(String, String)>,
+) -> Result<Json<PluginOwners>, AppError> {
+    let mut owners = owned_plugin(&state, &principal, &id)?;
+    owners.maintainers.retain(|m| *m != user_id);
+    save_owners(&state.storage, &owners).map_err(AppError::internal)?;
+    Ok(Json(owners))
+}
+
+#[derive(Deserialize, ToSchema, Default)]
+pub struct YankRequest {
+    pub reason: Option<String>,
+}
+
+fn managed_version(
+    state: &AppState,
+    principal: &Principal,
+    id: &str,
+    version: &str,
+) -> Result<(), AppError> {
+    principal.require_scope(PUBLISH_SCOPE)?;
+    if get_manifest(&state.storage, id, version)
+        .map_err(AppError::internal)?
+        .is_none()
+    {
+        return Err(AppError::NotFound("plugin version not found".into()));
+    }
+    if !can_manage(&state.storage, &principal.user_id, id).map_err(AppError::internal)? {
+        return Err(AppError::Forbidden(format!("not a maintainer of {}", id)));
+    }
+    Ok(())
+}
+
+/// Yank a version: hide it from search without revoking it
+#[utoipa::path(
+    post,
+    path = "/plugins/{id}/{version}/yank",
+    tag = "publishers",
+    params(
+        ("id" = String, Path, description = "Plugin id"),
+        ("version" = String, Path, description = "Plugin version"),
+    ),
+    request_body = YankRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The yank record", body = YankRecord),
+        (status = 403, description = "Caller does not maintain the plugin", body = ProblemDetails),
+        (status = 404, description = "Unknown version", body = ProblemDetails),
+    )
+)]
+pub async fn yank_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Path((id, version)): Path<(String, String)>,
+    body: Option<Json<YankRequest>>,
+) -> Result<Json<YankRecord>, AppError> {
+    managed_version(&state, &principal, &id, &version)?;
+    let Json(req) = body.unwrap_or_default();
+    let record = YankRecord {
+        reason: req.reason,
+        by: principal.user_id.clone(),
+        at: Utc::now().timestamp(),
+    };
+    crate::plugins::yank_manifest(&state.storage, &id, &version, &record)
+        .map_err(AppError::internal)?;
+    state
+        .search
+        .reindex(&state.storage, &id)
+        .map_err(AppError::internal)?;
+    Ok(Json(record))
+}
+
+/// Undo a yank
+#[utoipa::path(
+    delete,
+    path = "/plugins/{id}/{version}/yank",
+    tag = "publishers",
+    params(
+        ("id" = String, Path, description = "Plugin id"),
+        ("version" = String, Path, description = "Plugin version"),
+    ),
+    security(("bearer" = [])),
+    responses(
+        (status = 204, description = "The version is listed again"),
+        (status = 403, description = "Caller does not maintain the plugin", body = ProblemDetails),
+        (status = 404, description = "Unknown version", body = ProblemDetails),
+    )
+)]
+pub async fn unyank_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Path((id, version)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    managed_version(&state, &principal, &id, &version)?;
+    crate::plugins::unyank_manifest(&state.storage, &id, &version).map_err(AppError::internal)?;
+    state
+        .search
+        .reindex(&state.storage, &id)
+        .map_err(AppError::internal)?;
+    Ok(StatusCode::NO_CONTENT)
+}
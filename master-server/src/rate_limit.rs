@@ -0,0 +1,138 @@
+//! Rate limits and license-tier quotas of the master server.
+//!
+//! Route groups:
+//!
+//! - `auth` (register, login): per client IP
+//! - `tokens` (personal access tokens): per user
+//! - `publish` (plugin publishing): per user, per IP for anonymous publishers
+//! - `api` (everything but `/healthz`, `/metrics` and `/openapi.json`): a per-user budget that
+//!   depends on the account's license tier
+//!
+//! Limits are configured in the `[rate_limits]` section of the config file or through the
+//! environment, as `<count>/<s|min|h|day>`:
+//!
+//! - `auth` / `MASTER_RATE_LIMIT_AUTH` (default `10/min`), `tokens` /
+//!   `MASTER_RATE_LIMIT_TOKENS` (`60/h`), `publish` / `MASTER_RATE_LIMIT_PUBLISH` (`30/h`)
+//! - `quota_free` / `MASTER_QUOTA_FREE` (`1000/day`), `quota_pro` / `MASTER_QUOTA_PRO`
+//!   (`20000/day`), `quota_enterprise` / `MASTER_QUOTA_ENTERPRISE` (`200000/day`)
+//! - `redis_url` / `MASTER_RATE_LIMIT_REDIS_URL`: share buckets between instances through
+//!   Redis instead of counting in memory
+//! - `trusted_proxies` / `MASTER_TRUST_PROXY`: number of proxies in front of the server that
+//!   append to `X-Forwarded-For`; the client IP is the entry that many places from the right
+
+use crate::state::AppState;
+use axum::http::HeaderMap;
+use serde::Deserialize;
+use std::sync::Arc;
+use verseguy_auth::License;
+use verseguy_ratelimit::{
+    KeyBy, Limit, MemoryStore, Policy, RateLimitLayer, RateLimitStore, RedisStore, Subject,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub auth: Limit,
+    pub tokens: Limit,
+    pub publish: Limit,
+    pub quota_free: Limit,
+    pub quota_pro: Limit,
+    pub quota_enterprise: Limit,
+    pub redis_url: Option<String>,
+    pub trusted_proxies: usize,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            auth: Limit::per_minute(10),
+            tokens: Limit::per_hour(60),
+            publish: Limit::per_hour(30),
+            quota_free: Limit::per_day(1_000),
+            quota_pro: Limit::per_day(20_000),
+            quota_enterprise: Limit::per_day(200_000),
+            redis_url: None,
+            trusted_proxies: 0,
+        }
+    }
+}
+
+fn env_limit(limit: &mut Limit, name: &str, value: Option<String>) -> anyhow::Result<()> {
+    if let Some(v) = value {
+        *limit = v.parse().map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+impl RateLimitConfig {
+    /// Overlay the `MASTER_RATE_LIMIT_*`, `MASTER_QUOTA_*` and `MASTER_TRUST_PROXY` variables
+    /// returned by `var`
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> anyhow::Result<()> {
+        let limits = [
+            (&mut self.auth, "MASTER_RATE_LIMIT_AUTH"),
+            (&mut self.tokens, "MASTER_RATE_LIMIT_TOKENS"),
+            (&mut self.publish, "MASTER_RATE_LIMIT_PUBLISH"),
+            (&mut self.quota_free, "MASTER_QUOTA_FREE"),
+            (&mut self.quota_pro, "MASTER_QUOTA_PRO"),
+            (&mut self.quota_enterprise, "MASTER_QUOTA_ENTERPRISE"),
+        ];
+        for (limit, name) in limits {
+            env_limit(limit, name, var(name))?;
+        }
+        if let Some(url) = var("MASTER_RATE_LIMIT_REDIS_URL") {
+            self.redis_url = Some(url);
+        }
+        if let Some(v) = var("MASTER_TRUST_PROXY") {
+            self.trusted_proxies = v
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("MASTER_TRUST_PROXY: expected a number of proxies"))?;
+        }
+        Ok(())
+    }
+
+    pub fn open_store(&self) -> anyhow::Result<Arc<dyn RateLimitStore>> {
+        match &self.redis_url {
+            Some(url) => Ok(Arc::new(RedisStore::new(url)?)),
+            None => Ok(Arc::new(MemoryStore::new())),
+        }
+    }
+
+    /// API budget per user, by license tier
+    pub fn quota_policy(&self) -> Policy {
+        Policy::new("api", KeyBy::User, self.quota_free)
+            .tier(tier_name(License::Pro), self.quota_pro)
+            .tier(tier_name(License::Enterprise), self.quota_enterprise)
+    }
+}
+
+fn tier_name(license: License) -> &'static str {
+    match license {
+        License::Free => "free",
+        License::Pro => "pro",
+        License::Enterprise => "enterprise",
+    }
+}
+
+/// Caller of a request, from its session JWT or personal access token
+fn subject(state: &AppState, headers: &HeaderMap) -> Subject {
+    let principal = match crate::auth::authenticate(state, headers) {
+        Ok(p) => p,
+        Err(_) => return Subject::default(),
+    };
+    let tier = crate::auth::license_of(state, &principal.user_id)
+        .map(|license| tier_name(license).to_string());
+    Subject {
+        user_id: Some(principal.user_id),
+        client_id: None,
+        tier,
+    }
+}
+
+/// Layer enforcing `policy` with the server's bucket store
+pub fn layer(state: &Arc<AppState>, policy: Policy) -> RateLimitLayer {
+    let resolver_state = state.clone();
+    RateLimitLayer::new(policy, state.rate_limit_store.clone())
+        .trusted_proxies(state.config.rate_limits.trusted_proxies)
+        .resolve_subject(move |headers| subject(&resolver_state, headers))
+}
@@ -0,0 +1,545 @@
+//! Semver queries over published versions, and dependency resolution.
+//!
+//! `PluginManifest::version` is parsed as semver; publishing rejects versions, core/SDK
+//! versions and dependency requirements that do not parse. Stored versions that predate that
+//! check and do not parse are left out of every query here, as are yanked and revoked versions
+//! when picking what to install.
+//!
+//! `POST /plugins/resolve` picks, for a set of requested plugins, the newest versions that run
+//! on the client's core and SDK and satisfy every dependency requirement between them,
+//! backtracking to older versions when the newest ones conflict.
+
+use crate::plugins::{is_revoked, is_yanked, Dependency, PluginManifest};
+use crate::state::AppState;
+use anyhow::Result;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use verseguy_shared_error::{AppError, ProblemDetails};
+use verseguy_storage::RocksDBStorage;
+
+/// Demands and candidate versions the resolver works through before giving up
+const MAX_STEPS: usize = 10_000;
+
+/// Plugins a resolve request can name
+pub const MAX_REQUESTED: usize = 100;
+
+/// Plugins an install set can hold; bounds the resolver's recursion depth
+const MAX_INSTALL: usize = 500;
+
+/// Versions of the client installing plugins; unknown versions do not restrict anything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Host {
+    pub core: Option<Version>,
+    pub sdk: Option<Version>,
+}
+
+impl Host {
+    /// Parse the client's versions, as sent in a query or request body
+    pub fn parse(core: Option<&str>, sdk: Option<&str>) -> Result<Self, AppError> {
+        let parse = |field: &str, v: Option<&str>| {
+            v.map(|v| {
+                Version::parse(v.trim())
+                    .map_err(|e| AppError::BadRequest(format!("{} {:?}: {}", field, v, e)))
+            })
+            .transpose()
+        };
+        Ok(Host {
+            core: parse("core_version", core)?,
+            sdk: parse("sdk_version", sdk)?,
+        })
+    }
+
+    /// Why `manifest` does not run on this host, if it does not
+    pub fn incompatibility(&self, manifest: &PluginManifest) -> Option<String> {
+        if let (Some(core), Some(min)) = (&self.core, &manifest.core_version_min) {
+            match Version::parse(min) {
+                Ok(min) if *core < min => return Some(format!("needs core >= {}", min)),
+                Ok(_) => {}
+                Err(_) => return Some(format!("has an invalid core_version_min {:?}", min)),
+            }
+        }
+        if let (Some(sdk), Some(built)) = (&self.sdk, &manifest.sdk_version) {
+            match VersionReq::parse(&format!("^{}", built)) {
+                Ok(req) if !req.matches(sdk) => return Some(format!("needs SDK ^{}", built)),
+                Ok(_) => {}
+                Err(_) => return Some(format!("has an invalid sdk_version {:?}", built)),
+            }
+        }
+        None
+    }
+}
+
+/// Reject manifests whose versions or dependency requirements are not valid semver
+pub fn validate_manifest(manifest: &PluginManifest) -> Result<(), AppError> {
+    let invalid = |field: &str, value: &str, e: semver::Error| {
+        AppError::Validation(format!("{} {:?} is not valid semver: {}", field, value, e))
+    };
+    Version::parse(&manifest.version).map_err(|e| invalid("version", &manifest.version, e))?;
+    if let Some(min) = &manifest.core_version_min {
+        Version::parse(min).map_err(|e| invalid("core_version_min", min, e))?;
+    }
+    if let Some(sdk) = &manifest.sdk_version {
+        Version::parse(sdk).map_err(|e| invalid("sdk_version", sdk, e))?;
+    }
+    let mut seen = HashSet::new();
+    for dep in &manifest.dependencies {
+        if dep.id == manifest.id {
+            return Err(AppError::Validation(format!(
+                "{} depends on itself",
+                dep.id
+            )));
+        }
+        if !seen.insert(dep.id.as_str()) {
+            return Err(AppError::Validation(format!("{} is listed twice", dep.id)));
+        }
+        VersionReq::parse(&dep.version).map_err(|e| invalid(&dep.id, &dep.version, e))?;
+    }
+    Ok(())
+}
+
+/// A stored version whose version and dependency requirements parse
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub version: Version,
+    pub manifest: PluginManifest,
+    pub dependencies: Vec<(String, VersionReq)>,
+}
+
+impl Release {
+    fn parse(manifest: PluginManifest) -> Option<Self> {
+        let version = Version::parse(&manifest.version).ok()?;
+        let dependencies = manifest
+            .dependencies
+            .iter()
+            .map(|d| Some((d.id.clone(), VersionReq::parse(&d.version).ok()?)))
+            .collect::<Option<Vec<_>>>()?;
+        Some(Release {
+            version,
+            manifest,
+            dependencies,
+        })
+    }
+}
+
+/// Every stored version of `id` that parses, newest first
+pub fn stored_releases(storage: &RocksDBStorage, id: &str) -> Result<Vec<Release>> {
+    let prefix = format!("plugin:{}:", id);
+    let items: Vec<PluginManifest> = storage.prefix_scan(prefix.as_bytes())?;
+    let mut releases: Vec<Release> = items
+        .into_iter()
+        .filter(|m| m.id == id)
+        .filter_map(Release::parse)
+        .collect();
+    releases.sort_by(|a, b| b.version.cmp(&a.version));
+    Ok(releases)
+}
+
+/// Versions of `id` that may be installed: neither yanked nor revoked, newest first
+pub fn releases(storage: &RocksDBStorage, id: &str) -> Result<Vec<Release>> {
+    let mut installable = Vec::new();
+    for release in stored_releases(storage, id)? {
+        let version = &release.manifest.version;
+        if !is_yanked(storage, id, version)? && !is_revoked(storage, id, version)? {
+            installable.push(release);
+        }
+    }
+    Ok(installable)
+}
+
+/// Newest installable release of `id` that runs on `host`; pre-releases are never the latest
+pub fn latest(storage: &RocksDBStorage, id: &str, host: &Host) -> Result<Option<PluginManifest>> {
+    Ok(releases(storage, id)?
+        .into_iter()
+        .find(|r| r.version.pre.is_empty() && host.incompatibility(&r.manifest).is_none())
+        .map(|r| r.manifest))
+}
+
+/// Returned when no install set satisfies a resolve request; explains why
+#[derive(Debug)]
+pub struct Conflict(pub String);
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Conflict {}
+
+/// A requirement on `id` and who made it
+#[derive(Clone)]
+struct Demand {
+    id: String,
+    req: VersionReq,
+    by: String,
+}
+
+/// A picked release and the requirement that picked it
+#[derive(Clone)]
+struct Pick {
+    release: Release,
+    by: String,
+}
+
+struct Resolver<'a> {
+    storage: &'a RocksDBStorage,
+    host: &'a Host,
+    releases: HashMap<String, Vec<Release>>,
+    steps: usize,
+}
+
+impl Resolver<'_> {
+    fn releases(&mut self, id: &str) -> Result<&[Release]> {
+        if !self.releases.contains_key(id) {
+            let found = releases(self.storage, id)?;
+            self.releases.insert(id.to_string(), found);
+        }
+        Ok(self.releases.get(id).map(Vec::as_slice).unwrap_or_default())
+    }
+
+    /// Count one demand or candidate; fails once [`MAX_STEPS`] are used up
+    fn step(&mut self) -> Result<()> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err(Conflict(format!(
+                "gave up after {} steps; pin versions to narrow the search",
+                MAX_STEPS
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Work through `pending`; only picking a version for a new plugin recurses, so the depth is
+    /// bounded by the size of the install set
+    fn solve(
+        &mut self,
+        picked: BTreeMap<String, Pick>,
+        mut pending: VecDeque<Demand>,
+    ) -> Result<BTreeMap<String, Pick>> {
+        let demand = loop {
+            let Some(demand) = pending.pop_front() else {
+                return Ok(picked);
+            };
+            self.step()?;
+            match picked.get(&demand.id) {
+                None => break demand,
+                Some(pick) if demand.req.matches(&pick.release.version) => {}
+                Some(pick) => {
+                    return Err(Conflict(format!(
+                        "{} requires {} {}, but {} {} was picked for {}",
+                        demand.by, demand.id, demand.req, demand.id, pick.release.version, pick.by
+                    ))
+                    .into());
+                }
+            }
+        };
+        if picked.len() >= MAX_INSTALL {
+            return Err(Conflict(format!(
+                "the install set would hold more than {} plugins",
+                MAX_INSTALL
+            ))
+            .into());
+        }
+
+        let host = self.host;
+        let candidates: Vec<Release> = self
+            .releases(&demand.id)?
+            .iter()
+            .filter(|r| {
+                demand.req.matches(&r.version) && host.incompatibility(&r.manifest).is_none()
+            })
+            .cloned()
+            .collect();
+        if candidates.is_empty() {
+            return Err(self.unmatched(&demand)?.into());
+        }
+
+        let mut first_conflict = None;
+        for release in candidates {
+            self.step()?;
+            let by = format!("{} {}", demand.id, release.version);
+            let mut next = pending.clone();
+            next.extend(release.dependencies.iter().map(|(id, req)| Demand {
+                id: id.clone(),
+                req: req.clone(),
+                by: by.clone(),
+            }));
+            let mut next_picked = picked.clone();
+            next_picked.insert(
+                demand.id.clone(),
+                Pick {
+                    release,
+                    by: demand.by.clone(),
+                },
+            );
+            match self.solve(next_picked, next) {
+                Ok(done) => return Ok(done),
+                Err(e) if e.is::<Conflict>() => {
+                    first_conflict.get_or_insert(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(first_conflict.unwrap_or_else(|| anyhow::anyhow!("no candidates tried")))
+    }
+
+    /// Explain why no release of `demand.id` was a candidate
+    fn unmatched(&mut self, demand: &Demand) -> Result<Conflict> {
+        let host = self.host;
+        let releases = self.releases(&demand.id)?;
+        if releases.is_empty() {
+            return Ok(Conflict(format!(
+                "{} (required by {}) has no installable versions",
+                demand.id, demand.by
+            )));
+        }
+        let incompatible: Vec<String> = releases
+            .iter()
+            .filter(|r| demand.req.matches(&r.version))
+            .filter_map(|r| {
+                let why = host.incompatibility(&r.manifest)?;
+                Some(format!("{} {}", r.version, why))
+            })
+            .collect();
+        if incompatible.is_empty() {
+            let available: Vec<String> = releases
+                .iter()
+                .take(10)
+                .map(|r| r.version.to_string())
+                .collect();
+            return Ok(Conflict(format!(
+                "no version of {} matches {} (required by {}); available: {}",
+                demand.id,
+                demand.req,
+                demand.by,
+                available.join(", ")
+            )));
+        }
+        Ok(Conflict(format!(
+            "no version of {} matching {} (required by {}) runs on this host: {}",
+            demand.id,
+            demand.req,
+            demand.by,
+            incompatible.join("; ")
+        )))
+    }
+}
+
+/// Pick one release of every requested plugin and of everything they depend on. The install
+/// set lists dependencies before the plugins that need them. Fails with [`Conflict`] when no
+/// such set exists.
+pub fn resolve(
+    storage: &RocksDBStorage,
+    requested: &[Dependency],
+    host: &Host,
+) -> Result<Vec<PluginManifest>> {
+    let mut pending = VecDeque::new();
+    for dep in requested {
+        let req = VersionReq::parse(&dep.version).map_err(|e| {
+            Conflict(format!(
+                "{:?} is not a valid requirement for {}: {}",
+                dep.version, dep.id, e
+            ))
+        })?;
+        pending.push_back(Demand {
+            id: dep.id.clone(),
+            req,
+            by: "the request".to_string(),
+        });
+    }
+    let mut resolver = Resolver {
+        storage,
+        host,
+        releases: HashMap::new(),
+        steps: 0,
+    };
+    let picked = resolver.solve(BTreeMap::new(), pending)?;
+
+    let mut seen = HashSet::new();
+    let mut order = Vec::with_capacity(picked.len());
+    for dep in requested {
+        install_order(&dep.id, &picked, &mut seen, &mut order);
+    }
+    Ok(order)
+}
+
+/// Append `id` to `order` after everything it depends on; cycles are cut where they close
+fn install_order(
+    id: &str,
+    picked: &BTreeMap<String, Pick>,
+    seen: &mut HashSet<String>,
+    order: &mut Vec<PluginManifest>,
+) {
+    let Some(pick) = picked.get(id) else {
+        return;
+    };
+    if !seen.insert(id.to_string()) {
+        return;
+    }
+    for (dep, _) in &pick.release.dependencies {
+        install_order(dep, picked, seen, order);
+    }
+    order.push(pick.release.manifest.clone());
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HostQuery {
+    /// Only versions whose `core_version_min` this core version meets
+    pub core_version: Option<String>,
+    /// Only versions built against an SDK semver-compatible with this one
+    pub sdk_version: Option<String>,
+}
+
+impl HostQuery {
+    pub fn host(&self) -> Result<Host, AppError> {
+        Host::parse(self.core_version.as_deref(), self.sdk_version.as_deref())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct VersionSummary {
+    pub version: String,
+    pub published_at: Option<i64>,
+    pub core_version_min: Option<String>,
+    pub sdk_version: Option<String>,
+    pub yanked: bool,
+    pub revoked: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct VersionsResponse {
+    pub id: String,
+    /// Newest first
+    pub versions: Vec<VersionSummary>,
+}
+
+/// Every published version of a plugin in semver order
+#[utoipa::path(
+    get,
+    path = "/plugins/{id}/versions",
+    tag = "plugins",
+    params(("id" = String, Path, description = "Plugin id")),
+    responses(
+        (status = 200, description = "Versions, newest first", body = VersionsResponse),
+        (status = 404, description = "Unknown plugin", body = ProblemDetails),
+    )
+)]
+pub async fn versions_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<VersionsResponse>, AppError> {
+    let releases = stored_releases(&state.storage, &id).map_err(AppError::internal)?;
+    if releases.is_empty() {
+        return Err(AppError::NotFound(format!("plugin {} not found", id)));
+    }
+    let mut versions = Vec::with_capacity(releases.len());
+    for r in releases {
+        let m = r.manifest;
+        versions.push(VersionSummary {
+            yanked: is_yanked(&state.storage, &id, &m.version).map_err(AppError::internal)?,
+            revoked: is_revoked(&state.storage, &id, &m.version).map_err(AppError::internal)?,
+            version: m.version,
+            published_at: m.published_at,
+            core_version_min: m.core_version_min,
+            sdk_version: m.sdk_version,
+        });
+    }
+    Ok(Json(VersionsResponse { id, versions }))
+}
+
+/// Newest stable version of a plugin, optionally the newest one a given core/SDK can run
+#[utoipa::path(
+    get,
+    path = "/plugins/{id}/latest",
+    tag = "plugins",
+    params(("id" = String, Path, description = "Plugin id"), HostQuery),
+    responses(
+        (status = 200, description = "The manifest of that version", body = PluginManifest),
+        (status = 400, description = "Malformed core or SDK version", body = ProblemDetails),
+        (status = 404, description = "No installable version", body = ProblemDetails),
+    )
+)]
+pub async fn latest_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<HostQuery>,
+) -> Result<Json<PluginManifest>, AppError> {
+    let host = query.host()?;
+    latest(&state.storage, &id, &host)
+        .map_err(AppError::internal)?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound(format!("no installable version of {}", id)))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ResolveRequest {
+    /// Plugins to install, with optional version requirements; at most 100
+    pub plugins: Vec<Dependency>,
+    pub core_version: Option<String>,
+    pub sdk_version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ResolveResponse {
+    /// Manifests to install, dependencies before the plugins that need them
+    pub install: Vec<PluginManifest>,
+}
+
+/// Resolve requested plugins and their dependencies to one consistent install set
+#[utoipa::path(
+    post,
+    path = "/plugins/resolve",
+    tag = "plugins",
+    request_body = ResolveRequest,
+    responses(
+        (status = 200, description = "The install set", body = ResolveResponse),
+        (
+            status = 400,
+            description = "Malformed version or requirement, or more than 100 plugins",
+            body = ProblemDetails
+        ),
+        (
+            status = 409,
+            description = "No consistent install set; `detail` explains the conflict",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn resolve_handler(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ResolveRequest>,
+) -> Result<Json<ResolveResponse>, AppError> {
+    let host = Host::parse(req.core_version.as_deref(), req.sdk_version.as_deref())?;
+    if req.plugins.len() > MAX_REQUESTED {
+        return Err(AppError::BadRequest(format!(
+            "at most {} plugins can be resolved at once",
+            MAX_REQUESTED
+        )));
+    }
+    for dep in &req.plugins {
+        VersionReq::parse(&dep.version).map_err(|e| {
+            AppError::BadRequest(format!("{} requirement {:?}: {}", dep.id, dep.version, e))
+        })?;
+    }
+    let storage = state.storage.clone();
+    let install = tokio::task::spawn_blocking(move || resolve(&storage, &req.plugins, &host))
+        .await
+        .map_err(AppError::internal)?
+        .map_err(|e| match e.downcast_ref::<Conflict>() {
+            Some(conflict) => AppError::coded(
+                StatusCode::CONFLICT,
+                "resolution_conflict",
+                conflict.to_string(),
+            ),
+            None => AppError::internal(e),
+        })?;
+    Ok(Json(ResolveResponse { install }))
+}
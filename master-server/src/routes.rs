@@ -1,307 +1,532 @@
 #![allow(clippy::disallowed_methods)]
 
+use crate::admin::{require_admin, ADMIN_KEYS, ADMIN_PLUGINS};
 use crate::state::AppState;
 use anyhow::Result;
 use axum::{extract::State, Json};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 use verseguy_auth::local::LocalAuth;
 use verseguy_auth::SessionService;
 use verseguy_licensing::validate_license;
+use verseguy_shared_error::{AppError, ProblemDetails};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct RegisterRequest {
     pub username: String,
     pub password: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct RegisterResponse {
     pub id: String,
     pub username: String,
 }
 
+/// Register a local account
+#[utoipa::path(
+    post,
+    path = "/auth/register",
+    tag = "auth",
+    request_body = RegisterRequest,
+    responses(
+        (status = 200, description = "Account created", body = RegisterResponse),
+        (
+            status = 400,
+            description = "Invalid or taken username, or weak password",
+            body = ProblemDetails
+        ),
+    )
+)]
 pub async fn register_handler(
     State(state): State<Arc<AppState>>,
     Json(req): Json<RegisterRequest>,
-) -> Result<Json<RegisterResponse>, (axum::http::StatusCode, String)> {
+) -> Result<Json<RegisterResponse>, AppError> {
     let auth = LocalAuth::new((*state.storage).clone());
     let user = auth
         .register(req.username, req.password)
         .await
-        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, format!("{}", e)))?;
+        .map_err(|e| AppError::BadRequest(format!("{}", e)))?;
     Ok(Json(RegisterResponse {
         id: user.id,
         username: user.username,
     }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct LoginResponse {
     pub token: String,
 }
 
+/// Log in and receive a session JWT
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Session token", body = LoginResponse),
+        (status = 401, description = "Invalid credentials", body = ProblemDetails),
+        (status = 403, description = "Account locked (`account_locked`)", body = ProblemDetails),
+    )
+)]
 pub async fn login_handler(
     State(state): State<Arc<AppState>>,
     Json(req): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, (axum::http::StatusCode, String)> {
+) -> Result<Json<LoginResponse>, AppError> {
     let auth = LocalAuth::new((*state.storage).clone());
     let user = auth
         .login(&req.username, &req.password)
         .await
-        .map_err(|e| (axum::http::StatusCode::UNAUTHORIZED, format!("{}", e)))?;
+        .map_err(|_| AppError::Unauthorized("invalid credentials".into()))?;
+    crate::operations::ensure_unlocked(&state, &user.id)?;
 
     let session_service = SessionService::new(state.license_secret.clone());
     let token = session_service
         .create_and_store_session(&user.id, &user.license, 7, &(*state.storage).clone())
-        .map_err(|e| {
-            (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                format!("{}", e),
-            )
-        })?;
+        .map_err(AppError::internal)?;
 
     Ok(Json(LoginResponse { token }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct LicenseValidateRequest {
     pub token: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct LicenseValidateResponse {
     pub valid: bool,
 }
 
+/// Validate a license token
+#[utoipa::path(
+    post,
+    path = "/license/validate",
+    tag = "license",
+    request_body = LicenseValidateRequest,
+    responses(
+        (
+            status = 200,
+            description = "Validation result; revoked licenses are not valid",
+            body = LicenseValidateResponse
+        ),
+        (status = 400, description = "Malformed license token", body = ProblemDetails),
+    )
+)]
 pub async fn license_validate_handler(
     State(state): State<Arc<AppState>>,
     Json(req): Json<LicenseValidateRequest>,
-) -> Result<Json<LicenseValidateResponse>, (axum::http::StatusCode, String)> {
+) -> Result<Json<LicenseValidateResponse>, AppError> {
     // Use licensing validate_license
     let valid = validate_license(
         &req.token,
         &state.license_secret,
         chrono::Utc::now().timestamp(),
     )
-    .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, format!("{}", e)))?;
-    Ok(Json(LicenseValidateResponse { valid }))
+    .map_err(|_| AppError::BadRequest("malformed license token".into()))?;
+    let revoked = crate::operations::is_license_revoked(&state.storage, &req.token)
+        .map_err(AppError::internal)?;
+    Ok(Json(LicenseValidateResponse {
+        valid: valid && !revoked,
+    }))
 }
 
-use crate::plugins::{search_manifests, store_manifest, PluginManifest};
+use crate::plugins::{signing_document, store_signed_manifest, PluginManifest};
+use crate::search::{SearchParams, SearchResults};
 use axum::extract::Query;
 use base64::engine::general_purpose;
 use base64::Engine;
+use verseguy_signing::{Envelope, SignatureEntry, TrustStore};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct SearchQuery {
+    /// Words to look for in names, ids, tags, authors and descriptions
     pub q: Option<String>,
-}
-
-#[allow(clippy::disallowed_methods)]
+    /// Only offer versions whose `core_version_min` this core version meets
+    pub core_version: Option<String>,
+    /// Only offer versions built against an SDK semver-compatible with this one
+    pub sdk_version: Option<String>,
+    /// Only plugins usable with this license tier (`Free`, `Pro` or `Enterprise`)
+    pub license: Option<String>,
+    /// Only plugins requesting this capability
+    pub capability: Option<String>,
+    pub category: Option<String>,
+    pub tag: Option<String>,
+    /// 1-based page number
+    pub page: Option<usize>,
+    /// Results per page, at most 100
+    pub per_page: Option<usize>,
+}
+
+/// Search published plugins by relevance
+#[utoipa::path(
+    get,
+    path = "/plugins/search",
+    tag = "plugins",
+    params(SearchQuery),
+    responses(
+        (
+            status = 200,
+            description = "Latest installable version of each matching plugin",
+            body = SearchResults
+        ),
+        (
+            status = 400,
+            description = "Malformed core or SDK version, or unknown license tier",
+            body = ProblemDetails
+        ),
+    )
+)]
 pub async fn plugins_search_handler(
     State(state): State<Arc<AppState>>,
     Query(query): Query<SearchQuery>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    let q = query.q.unwrap_or_default();
-    let items = search_manifests(&state.storage, &q).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("{}", e),
-        )
-    })?;
-    Ok(Json(serde_json::json!({"results": items})))
+) -> Result<Json<SearchResults>, AppError> {
+    let host =
+        crate::registry::Host::parse(query.core_version.as_deref(), query.sdk_version.as_deref())?;
+    let license = query
+        .license
+        .as_deref()
+        .map(|name| {
+            crate::search::parse_license(name)
+                .ok_or_else(|| AppError::BadRequest(format!("unknown license tier {:?}", name)))
+        })
+        .transpose()?;
+    let params = SearchParams {
+        q: query.q.unwrap_or_default(),
+        host,
+        filters: crate::search::Filters {
+            license,
+            capability: query.capability,
+            category: query.category,
+            tag: query.tag,
+        },
+        page: query.page.unwrap_or(1),
+        per_page: query.per_page.unwrap_or(crate::search::DEFAULT_PER_PAGE),
+    };
+    Ok(Json(crate::search::search(&state.search, &params)))
 }
 
 // --- Admin key management handlers ---
 
+/// Public part of the master signing key
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    get,
+    path = "/admin/keys",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (
+            status = 200,
+            description = "Base64 public key and key file path",
+            body = serde_json::Value
+        ),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:keys` permission", body = ProblemDetails),
+        (status = 404, description = "No master key configured", body = ProblemDetails),
+    )
+)]
 pub async fn admin_get_keys(
-    State(_state): State<Arc<AppState>>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    if let Ok(key_path) = std::env::var("MASTER_KEY_FILE") {
-        let pk_b64 = crate::keystore::public_key_b64_from_path(std::path::Path::new(&key_path))
-            .map_err(|e| {
-                (
-                    axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("{}", e),
-                )
-            })?;
-        Ok(Json(
-            serde_json::json!({"exists": true, "public_key_b64": pk_b64, "path": key_path}),
-        ))
-    } else {
-        Err((
-            axum::http::StatusCode::NOT_FOUND,
-            "no master key configured".to_string(),
-        ))
-    }
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_KEYS)?;
+    let key_path = key_file(&state)?;
+    let pk_b64 = crate::keystore::public_key_b64_from_path(key_path).map_err(AppError::internal)?;
+    admin.audit(&state, "keys.list", serde_json::json!({}))?;
+    Ok(Json(
+        serde_json::json!({"exists": true, "public_key_b64": pk_b64, "path": key_path}),
+    ))
 }
 
-fn require_admin(headers: &axum::http::HeaderMap) -> Result<(), (axum::http::StatusCode, String)> {
-    if let Ok(token) = std::env::var("MASTER_ADMIN_TOKEN") {
-        let header_token = headers
-            .get("x-admin-token")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("");
-        if header_token != token {
-            return Err((
-                axum::http::StatusCode::FORBIDDEN,
-                "invalid admin token".to_string(),
-            ));
-        }
-        Ok(())
-    } else {
-        Err((
-            axum::http::StatusCode::FORBIDDEN,
-            "admin disabled".to_string(),
-        ))
-    }
+fn key_file(state: &AppState) -> Result<&std::path::Path, AppError> {
+    state
+        .config
+        .security
+        .key_file
+        .as_deref()
+        .ok_or_else(|| AppError::NotFound("no master key configured".into()))
 }
 
+/// Rotate the master signing key
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    post,
+    path = "/admin/keys/rotate",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (
+            status = 200,
+            description = "Base64 public key of the new key",
+            body = serde_json::Value
+        ),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:keys` permission", body = ProblemDetails),
+        (status = 404, description = "No master key configured", body = ProblemDetails),
+    )
+)]
 pub async fn admin_rotate_key(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     req: axum::http::Request<axum::body::Body>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    let headers = req.headers();
-    require_admin(headers)?;
-
-    let key_path = std::env::var("MASTER_KEY_FILE").map_err(|_| {
-        (
-            axum::http::StatusCode::NOT_FOUND,
-            "no master key configured".to_string(),
-        )
-    })?;
-    let kp = crate::keystore::rotate_key(std::path::Path::new(&key_path)).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("{}", e),
-        )
-    })?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, req.headers(), ADMIN_KEYS)?;
+
+    let key_path = key_file(&state)?;
+    let kp = crate::keystore::rotate_key(key_path).map_err(AppError::internal)?;
+    let trusted =
+        crate::trust::record_key(&state.storage, &kp.public).map_err(AppError::internal)?;
+    admin.audit(
+        &state,
+        "keys.rotate",
+        serde_json::json!({"key_id": trusted.key_id}),
+    )?;
     let pk_b64 = general_purpose::STANDARD.encode(kp.public.to_bytes());
     Ok(Json(
         serde_json::json!({"ok": true, "public_key_b64": pk_b64}),
     ))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ImportBody {
     pub key_b64: String,
 }
 
+/// Replace the master signing key
+#[utoipa::path(
+    post,
+    path = "/admin/keys/import",
+    tag = "admin",
+    request_body = ImportBody,
+    security(("bearer" = [])),
+    responses(
+        (
+            status = 200,
+            description = "Base64 public key of the imported key",
+            body = serde_json::Value
+        ),
+        (status = 400, description = "Malformed body or key", body = ProblemDetails),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:keys` permission", body = ProblemDetails),
+        (status = 404, description = "No master key configured", body = ProblemDetails),
+    )
+)]
 pub async fn admin_import_key(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     req: axum::http::Request<axum::body::Body>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    let headers = req.headers();
-    require_admin(headers)?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, req.headers(), ADMIN_KEYS)?;
 
     // read body
     let bytes = axum::body::to_bytes(req.into_body(), 1024 * 1024)
         .await
-        .map_err(|e| {
-            (
-                axum::http::StatusCode::BAD_REQUEST,
-                format!("failed to read body: {}", e),
-            )
-        })?;
-    let b: ImportBody = serde_json::from_slice(&bytes).map_err(|e| {
-        (
-            axum::http::StatusCode::BAD_REQUEST,
-            format!("invalid json: {}", e),
-        )
-    })?;
-
-    let key_path = std::env::var("MASTER_KEY_FILE").map_err(|_| {
-        (
-            axum::http::StatusCode::NOT_FOUND,
-            "no master key configured".to_string(),
-        )
+        .map_err(|e| AppError::BadRequest(format!("failed to read body: {}", e)))?;
+    let b: ImportBody = serde_json::from_slice(&bytes)
+        .map_err(|e| AppError::BadRequest(format!("invalid json: {}", e)))?;
+
+    let key_path = key_file(&state)?;
+    let kp = crate::keystore::import_key_b64(key_path, &b.key_b64).map_err(|e| {
+        tracing::warn!("key import failed: {}", e);
+        AppError::BadRequest("invalid key material".into())
     })?;
-    let kp = crate::keystore::import_key_b64(std::path::Path::new(&key_path), &b.key_b64)
-        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, format!("{}", e)))?;
+    let trusted =
+        crate::trust::record_key(&state.storage, &kp.public).map_err(AppError::internal)?;
+    admin.audit(
+        &state,
+        "keys.import",
+        serde_json::json!({"key_id": trusted.key_id}),
+    )?;
     let pk_b64 = general_purpose::STANDARD.encode(kp.public.to_bytes());
     Ok(Json(
         serde_json::json!({"ok": true, "public_key_b64": pk_b64}),
     ))
 }
 
-#[derive(Deserialize)]
-pub struct PublishRequest {
-    pub manifest: PluginManifest,
-}
-
-#[allow(clippy::disallowed_methods)]
-pub async fn plugins_publish_handler(
-    State(state): State<Arc<AppState>>,
-    req: axum::http::Request<axum::body::Body>,
-) -> Result<(axum::http::StatusCode, Json<serde_json::Value>), (axum::http::StatusCode, String)> {
-    // If X-User-Id header present, require ToS acceptance for that user
-    if let Some(user_id) = req.headers().get("x-user-id").and_then(|v| v.to_str().ok()) {
-        let tos_key = format!("tos:{}", user_id);
-        let tos: Option<serde_json::Value> =
-            (*state.storage).get(tos_key.as_bytes()).map_err(|e| {
-                (
-                    axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("{}", e),
-                )
-            })?;
-        if tos.is_none() {
-            return Err((
-                axum::http::StatusCode::FORBIDDEN,
-                "ToS acceptance required".to_string(),
-            ));
-        }
+/// Checks for publishing plugins and uploading artifacts: the bearer token's user, or without a
+/// token a user named in `x-user-id`, must have accepted the ToS in force, and with a publish
+/// key configured `x-plugin-token` must match it. Returns the authenticated caller, if any.
+pub(crate) fn require_publisher(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<Option<crate::auth::Principal>, AppError> {
+    let caller = if headers.contains_key("authorization") {
+        Some(crate::auth::authenticate(state, headers)?)
+    } else {
+        None
+    };
+    let user_id = match &caller {
+        Some(principal) => Some(principal.user_id.as_str()),
+        None => headers.get("x-user-id").and_then(|v| v.to_str().ok()),
+    };
+    if let Some(user_id) = user_id {
+        crate::legal::ensure_tos_accepted(state, user_id)?;
     }
 
-    // Simple publisher auth: if MASTER_PLUGIN_PUBLISH_KEY is set, require X-Plugin-Token header to match
-    if let Ok(key) = std::env::var("MASTER_PLUGIN_PUBLISH_KEY") {
-        let header_token = req
-            .headers()
+    if let Some(key) = &state.config.security.plugin_publish_key {
+        let header_token = headers
             .get("x-plugin-token")
             .and_then(|v| v.to_str().ok())
             .unwrap_or("");
         if header_token != key {
-            return Err((
-                axum::http::StatusCode::FORBIDDEN,
-                "Invalid plugin publish token".to_string(),
-            ));
+            return Err(AppError::Forbidden("Invalid plugin publish token".into()));
         }
     }
+    Ok(caller)
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PublishRequest {
+    pub manifest: PluginManifest,
+    /// Publisher signature over the manifest without `published_at`, with the public key
+    /// embedded
+    #[serde(default)]
+    pub signature: Option<SignatureEntry>,
+}
+
+/// Publish a plugin manifest, counter-signed with the master key when one is loaded
+#[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    post,
+    path = "/plugins/publish",
+    tag = "plugins",
+    params(
+        (
+            "x-user-id" = Option<String>,
+            Header,
+            description = "Without a bearer token, a user who must have accepted the ToS"
+        ),
+        ("x-plugin-token" = Option<String>, Header, description = "MASTER_PLUGIN_PUBLISH_KEY"),
+    ),
+    request_body = PublishRequest,
+    security((), ("bearer" = [])),
+    responses(
+        (
+            status = 201,
+            description = "The stored manifest under `manifest`",
+            body = serde_json::Value
+        ),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (status = 401, description = "Invalid bearer credentials", body = ProblemDetails),
+        (
+            status = 403,
+            description = "ToS, publish token or plugin ownership check failed",
+            body = ProblemDetails
+        ),
+        (
+            status = 409,
+            description = "The version was already published (`version_exists`)",
+            body = ProblemDetails
+        ),
+        (
+            status = 422,
+            description = "Invalid semver, unknown or mis-sized `artifact`, or bad `signature`",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn plugins_publish_handler(
+    State(state): State<Arc<AppState>>,
+    req: axum::http::Request<axum::body::Body>,
+) -> Result<(axum::http::StatusCode, Json<serde_json::Value>), AppError> {
+    // Publisher accounts authenticate; anonymous publishes are limited to unclaimed ids
+    let caller = require_publisher(&state, req.headers())?;
 
     // Parse JSON body into PublishRequest
     let bytes = axum::body::to_bytes(req.into_body(), 1024 * 1024)
         .await
-        .map_err(|e| {
-            (
-                axum::http::StatusCode::BAD_REQUEST,
-                format!("failed to read body: {}", e),
-            )
-        })?;
-    let req_json: PublishRequest = serde_json::from_slice(&bytes).map_err(|e| {
-        (
-            axum::http::StatusCode::BAD_REQUEST,
-            format!("invalid json: {}", e),
-        )
-    })?;
+        .map_err(|e| AppError::BadRequest(format!("failed to read body: {}", e)))?;
+    let req_json: PublishRequest = serde_json::from_slice(&bytes)
+        .map_err(|e| AppError::BadRequest(format!("invalid json: {}", e)))?;
+    crate::registry::validate_manifest(&req_json.manifest)?;
+
+    // The signature vouches for the artifact, so it must be the one uploaded
+    if let Some(artifact) = &req_json.manifest.artifact {
+        let stored = state
+            .artifacts
+            .size_of(&artifact.sha256)
+            .await
+            .map_err(AppError::internal)?;
+        match stored {
+            None => {
+                return Err(AppError::Validation(format!(
+                    "artifact {} has not been uploaded",
+                    artifact.sha256
+                )))
+            }
+            Some(size) if size != artifact.size => {
+                return Err(AppError::Validation(format!(
+                    "artifact {} is {} bytes, not {}",
+                    artifact.sha256, size, artifact.size
+                )))
+            }
+            Some(_) => {}
+        }
+    }
 
+    let account = crate::publishers::authorize_publish(
+        &state.storage,
+        caller.as_ref(),
+        &req_json.manifest.id,
+    )?;
     let manifest = req_json.manifest.with_published();
 
-    // Sign the manifest with master server keypair if available
+    // The master signature vouches for the publisher's, so check it first. Publishers with
+    // registered keys must sign with one of them.
+    let signature = req_json.signature;
+    let registered_key = match (&account, &signature) {
+        (Some(account), _) if account.keys.is_empty() => None,
+        (Some(account), Some(signature)) => {
+            Some(account.key(&signature.key_id).ok_or_else(|| {
+                AppError::Validation(format!("key {} is not registered", signature.key_id))
+            })?)
+        }
+        (Some(_), None) => {
+            return Err(AppError::Validation(
+                "manifests must be signed with a registered publisher key".into(),
+            ))
+        }
+        (None, _) => None,
+    };
+    if signature.is_some() {
+        let document = serde_json::to_value(&manifest).map_err(AppError::internal)?;
+        let envelope = Envelope {
+            publisher: signature.clone(),
+            master: None,
+        };
+        envelope
+            .verify_publisher(&signing_document(&document), registered_key.as_ref())
+            .map_err(|e| AppError::Validation(format!("publisher signature: {}", e)))?;
+    }
+
+    // Counter-sign with the master server keypair if available
     let kp_opt = state.keypair.as_ref();
-    store_manifest(&state.storage, &manifest, kp_opt).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("{}", e),
-        )
+    store_signed_manifest(&state.storage, &manifest, signature, kp_opt).map_err(|e| {
+        match e.downcast_ref::<crate::plugins::VersionExists>() {
+            Some(exists) => AppError::coded(
+                axum::http::StatusCode::CONFLICT,
+                "version_exists",
+                exists.to_string(),
+            ),
+            None => AppError::internal(e),
+        }
     })?;
+    if let Some(account) = &account {
+        crate::publishers::record_owner(&state.storage, &manifest.id, &account.user_id)
+            .map_err(AppError::internal)?;
+    }
+    state
+        .search
+        .reindex(&state.storage, &manifest.id)
+        .map_err(AppError::internal)?;
     Ok((
         axum::http::StatusCode::CREATED,
         Json(serde_json::json!({"ok": true, "manifest": manifest})),
@@ -310,341 +535,336 @@ pub async fn plugins_publish_handler(
 
 // --- Organization endpoints ---
 use axum::extract::Path;
-use axum::http::StatusCode;
 use plugins_base_organization::service::OrganizationService;
 use plugins_base_organization::types::Organization as OrgType;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct OrgListResponse {
     pub orgs: Vec<OrgType>,
 }
 
+/// List organizations
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    get,
+    path = "/v1/orgs",
+    tag = "orgs",
+    responses(
+        (status = 200, description = "All organizations", body = OrgListResponse),
+    )
+)]
 pub async fn orgs_list_handler(
     State(state): State<Arc<AppState>>,
-) -> Result<Json<OrgListResponse>, (axum::http::StatusCode, String)> {
+) -> Result<Json<OrgListResponse>, AppError> {
     let svc = OrganizationService::new((*state.storage).clone());
-    let orgs = svc
-        .list_orgs_prefix("")
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
+    let orgs = svc.list_orgs_prefix("").map_err(AppError::internal)?;
     Ok(Json(OrgListResponse { orgs }))
 }
 
 /// Simple health check endpoint for orchestration / k8s
 #[allow(clippy::disallowed_methods)]
-pub async fn health_handler(
-) -> Result<(axum::http::StatusCode, Json<serde_json::Value>), (axum::http::StatusCode, String)> {
-    Ok((
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "system",
+    responses(
+        (status = 200, description = "Service is up", body = serde_json::Value),
+    )
+)]
+pub async fn health_handler() -> (axum::http::StatusCode, Json<serde_json::Value>) {
+    (
         axum::http::StatusCode::OK,
         Json(serde_json::json!({"status":"ok"})),
-    ))
+    )
 }
 
 use crate::observability;
 
 /// Metrics endpoint for Prometheus scraping
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "system",
+    responses(
+        (status = 200, description = "Prometheus text exposition", body = String),
+        (status = 501, description = "Metrics not enabled", body = ProblemDetails),
+    )
+)]
 pub async fn metrics_handler(
     State(state): State<Arc<AppState>>,
-) -> Result<(axum::http::StatusCode, String), (axum::http::StatusCode, String)> {
+) -> Result<(axum::http::StatusCode, String), AppError> {
     if let Some(handle) = &state.metrics_handle {
         let body = observability::render_metrics(handle);
         Ok((axum::http::StatusCode::OK, body))
     } else {
-        Err((
-            axum::http::StatusCode::NOT_IMPLEMENTED,
-            "metrics not enabled".to_string(),
-        ))
+        Err(AppError::NotImplemented("metrics not enabled".into()))
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreateOrgRequest {
     pub name: String,
     pub tag: String,
 }
 
+/// Create an organization
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    post,
+    path = "/v1/orgs",
+    tag = "orgs",
+    request_body = CreateOrgRequest,
+    responses(
+        (status = 200, description = "The new organization", body = OrgType),
+        (status = 500, description = "Storage error", body = ProblemDetails),
+    )
+)]
 pub async fn orgs_create_handler(
     State(state): State<Arc<AppState>>,
     Json(req): Json<CreateOrgRequest>,
-) -> Result<Json<OrgType>, (axum::http::StatusCode, String)> {
+) -> Result<Json<OrgType>, AppError> {
     let svc = OrganizationService::new((*state.storage).clone());
     let created = svc
         .create_organization(req.name, req.tag, "".into(), "system".into())
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
+        .map_err(AppError::internal)?;
     Ok(Json(created))
 }
 
+/// Get an organization; `null` when it does not exist
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    get,
+    path = "/v1/orgs/{id}",
+    tag = "orgs",
+    params(("id" = String, Path, description = "Organization id")),
+    responses(
+        (status = 200, description = "The organization or `null`", body = Option<OrgType>),
+    )
+)]
 pub async fn orgs_get_handler(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Result<Json<Option<OrgType>>, (axum::http::StatusCode, String)> {
+) -> Result<Json<Option<OrgType>>, AppError> {
     let svc = OrganizationService::new((*state.storage).clone());
-    let org = svc
-        .get_organization(&id)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
+    let org = svc.get_organization(&id).map_err(AppError::internal)?;
     Ok(Json(org))
 }
 
-// --- Stubs for handlers referenced by older routes ---
-#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
-pub struct TosAcceptance {
-    pub user_id: String,
-    pub accepted_at: i64,
-    pub version: String,
-}
-
-#[allow(clippy::disallowed_methods)]
-pub async fn tos_accept_handler(
-    State(state): State<Arc<AppState>>,
-    Json(body): Json<TosAcceptance>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    // Store ToS acceptance in storage under key tos:{user_id}
-    let key = format!("tos:{}", body.user_id);
-    (*state.storage).put(key.as_bytes(), &body).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("{}", e),
-        )
-    })?;
-    Ok(Json(serde_json::json!({"ok": true})))
-}
-
-#[allow(clippy::disallowed_methods)]
-pub async fn tos_get_handler(
-    State(state): State<Arc<AppState>>,
-    Path(user_id): Path<String>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    let key = format!("tos:{}", user_id);
-    let got: Option<TosAcceptance> = (*state.storage).get(key.as_bytes()).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("{}", e),
-        )
-    })?;
-    match got {
-        Some(t) => Ok(Json(serde_json::json!(t))),
-        None => Err((
-            axum::http::StatusCode::NOT_FOUND,
-            "tos not found".to_string(),
-        )),
-    }
-}
-
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, ToSchema)]
 pub struct VerifyRequest {
     pub manifest: PluginManifest,
-    pub public_key_b64: String,
+    /// Master key to verify with; defaults to every key in `/plugins/trust`
+    #[serde(default)]
+    pub public_key_b64: Option<String>,
 }
 
+/// Verify the signatures of a stored manifest
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    post,
+    path = "/verify/plugin",
+    tag = "plugins",
+    request_body = VerifyRequest,
+    responses(
+        (
+            status = 200,
+            description = "Whether the signatures are valid, under `valid`",
+            body = serde_json::Value
+        ),
+        (status = 400, description = "Invalid public key", body = ProblemDetails),
+    )
+)]
 pub async fn verify_plugin_handler(
     State(state): State<Arc<AppState>>,
     Json(req): Json<VerifyRequest>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    use crate::ed25519_compat::PublicKey;
-    let pub_bytes = base64::engine::general_purpose::STANDARD
-        .decode(&req.public_key_b64)
-        .map_err(|e| {
-            (
-                axum::http::StatusCode::BAD_REQUEST,
-                format!("invalid base64: {}", e),
-            )
-        })?;
-    let mut pk_arr = [0u8; 32];
-    pk_arr.copy_from_slice(&pub_bytes[..32]);
-    let pubkey = PublicKey::from_bytes(&pk_arr).map_err(|e| {
-        (
-            axum::http::StatusCode::BAD_REQUEST,
-            format!("invalid public key: {}", e),
-        )
-    })?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let trust = match &req.public_key_b64 {
+        Some(b64) => {
+            let key = verseguy_signing::decode_public_key(b64)
+                .map_err(|e| AppError::BadRequest(e.to_string()))?;
+            TrustStore::new().with_key(key)
+        }
+        None => crate::trust::trust_store(&state.storage).map_err(AppError::internal)?,
+    };
 
-    let ok =
-        crate::plugins::verify_manifest(&state.storage, &req.manifest, &pubkey).map_err(|e| {
-            (
-                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                format!("{}", e),
-            )
-        })?;
+    let ok = crate::plugins::verify_manifest(&state.storage, &req.manifest, &trust)
+        .map_err(AppError::internal)?;
     Ok(Json(serde_json::json!({"valid": ok})))
 }
 
-#[derive(serde::Deserialize)]
+/// Signature envelope of a plugin version, for verifying it offline
+#[utoipa::path(
+    get,
+    path = "/plugins/{id}/{version}/signature",
+    tag = "plugins",
+    params(
+        ("id" = String, Path, description = "Plugin id"),
+        ("version" = String, Path, description = "Plugin version"),
+    ),
+    responses(
+        (status = 200, description = "Publisher and master signatures", body = Envelope),
+        (status = 404, description = "Unknown or unsigned version", body = ProblemDetails),
+    )
+)]
+pub async fn plugin_signature_handler(
+    State(state): State<Arc<AppState>>,
+    Path((id, version)): Path<(String, String)>,
+) -> Result<Json<Envelope>, AppError> {
+    crate::plugins::get_envelope(&state.storage, &id, &version)
+        .map_err(AppError::internal)?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound(format!("no signature for {}@{}", id, version)))
+}
+
+#[derive(serde::Deserialize, ToSchema)]
 struct RevokeRequest {
     id: String,
     version: String,
     reason: String,
 }
 
+/// Revoke a plugin version
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    post,
+    path = "/verify/revoke",
+    tag = "plugins",
+    request_body = RevokeRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Recorded", body = serde_json::Value),
+        (status = 400, description = "Malformed body", body = ProblemDetails),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (status = 403, description = "Missing `admin:plugins` permission", body = ProblemDetails),
+    )
+)]
 pub async fn revoke_handler(
     State(state): State<Arc<AppState>>,
     req: axum::http::Request<axum::body::Body>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    require_admin(req.headers())?;
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, req.headers(), ADMIN_PLUGINS)?;
     let bytes = axum::body::to_bytes(req.into_body(), 1024 * 1024)
         .await
-        .map_err(|e| {
-            (
-                axum::http::StatusCode::BAD_REQUEST,
-                format!("failed to read body: {}", e),
-            )
-        })?;
-    let r: RevokeRequest = serde_json::from_slice(&bytes).map_err(|e| {
-        (
-            axum::http::StatusCode::BAD_REQUEST,
-            format!("invalid json: {}", e),
-        )
-    })?;
-    crate::plugins::revoke_manifest(&state.storage, &r.id, &r.version, &r.reason).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("{}", e),
-        )
-    })?;
+        .map_err(|e| AppError::BadRequest(format!("failed to read body: {}", e)))?;
+    let r: RevokeRequest = serde_json::from_slice(&bytes)
+        .map_err(|e| AppError::BadRequest(format!("invalid json: {}", e)))?;
+    crate::plugins::revoke_manifest(&state.storage, &r.id, &r.version, &r.reason)
+        .map_err(AppError::internal)?;
+    state
+        .search
+        .reindex(&state.storage, &r.id)
+        .map_err(AppError::internal)?;
+    admin.audit(
+        &state,
+        "plugins.revoke",
+        serde_json::json!({"id": r.id, "version": r.version, "reason": r.reason}),
+    )?;
     Ok(Json(serde_json::json!({"ok": true})))
 }
 
+/// List revoked plugin versions
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    get,
+    path = "/verify/revocations",
+    tag = "plugins",
+    responses(
+        (
+            status = 200,
+            description = "Revocation records under `revocations`",
+            body = serde_json::Value
+        ),
+    )
+)]
 pub async fn revocations_list_handler(
     State(state): State<Arc<AppState>>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<serde_json::Value>, AppError> {
     // Prefix scan for plugin_revoked:
-    let items: Vec<serde_json::Value> =
-        (*state.storage)
-            .prefix_scan(b"plugin_revoked:")
-            .map_err(|e| {
-                (
-                    axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("{}", e),
-                )
-            })?;
+    let items: Vec<serde_json::Value> = (*state.storage)
+        .prefix_scan(b"plugin_revoked:")
+        .map_err(AppError::internal)?;
     Ok(Json(serde_json::json!({"revocations": items})))
 }
 
+/// Export a user's audit trail
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    get,
+    path = "/audit/export/{user_id}",
+    tag = "gdpr",
+    params(("user_id" = String, Path, description = "User id")),
+    responses(
+        (status = 200, description = "Audit entries under `entries`", body = serde_json::Value),
+    )
+)]
 pub async fn audit_export_handler(
     State(state): State<Arc<AppState>>,
     Path(user_id): Path<String>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
+) -> Result<Json<serde_json::Value>, AppError> {
     let audit = verseguy_audit::AuditService::new(state.storage.clone());
-    let entries = audit.export_for_user(&user_id).map_err(|e| {
-        (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("{}", e),
-        )
-    })?;
+    let entries = audit
+        .export_for_user(&user_id)
+        .map_err(AppError::internal)?;
     Ok(Json(serde_json::json!({"entries": entries})))
 }
 
 use axum::http::HeaderMap;
 use uuid::Uuid;
 
-#[derive(serde::Deserialize)]
-#[allow(dead_code)]
-struct AssignmentRec {
-    pub user_id: String,
-    pub role_id: String,
-    pub version: u64,
-}
-
-#[allow(dead_code)]
-#[derive(serde::Deserialize)]
-struct RoleRec {
-    pub id: String,
-    pub name: String,
-    pub version: u64,
-}
-
+/// Delete a user's personal data and audit trail
 #[allow(clippy::disallowed_methods)]
+#[utoipa::path(
+    delete,
+    path = "/users/{user_id}/data",
+    tag = "gdpr",
+    params(("user_id" = String, Path, description = "User whose data is deleted")),
+    security(("bearer" = [])),
+    responses(
+        (
+            status = 200,
+            description = "Number of deleted records under `deleted`",
+            body = serde_json::Value
+        ),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing `users:delete` scope or denied by `compliance:delete`",
+            body = ProblemDetails
+        ),
+    )
+)]
 pub async fn user_data_delete_handler(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(user_id): Path<String>,
-) -> Result<Json<serde_json::Value>, (axum::http::StatusCode, String)> {
-    // Extract Authorization header (Bearer)
-    let auth_header = headers
-        .get("authorization")
-        .and_then(|v| v.to_str().ok())
-        .ok_or((
-            StatusCode::UNAUTHORIZED,
-            "missing authorization".to_string(),
-        ))?;
-
-    let token = auth_header.strip_prefix("Bearer ").ok_or((
-        StatusCode::UNAUTHORIZED,
-        "invalid authorization format".to_string(),
-    ))?;
-
-    // Validate session token
-    let session_service = SessionService::new(state.license_secret.clone());
-    let token_data = session_service
-        .validate_token_and_storage(token, &state.storage)
-        .map_err(|e| (StatusCode::UNAUTHORIZED, format!("{}", e)))?;
-    let actor_id = token_data.claims.sub;
-
-    // Evaluate a named policy `compliance:delete` for the actor (fallback to admin role if policy not found)
-    // Gather the actor's role names
-    let assignments: Vec<AssignmentRec> = (*state.storage)
-        .prefix_scan(b"assignment:")
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
-    let mut actor_roles: Vec<String> = Vec::new();
-    for a in assignments.into_iter().filter(|a| a.user_id == actor_id) {
-        let role_opt: Option<RoleRec> = (*state.storage)
-            .get(format!("role:{}", a.role_id).as_bytes())
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
-        if let Some(r) = role_opt {
-            actor_roles.push(r.name);
-        }
-    }
+) -> Result<Json<serde_json::Value>, AppError> {
+    // Authenticate via session JWT or personal access token
+    let principal = crate::auth::authenticate(&state, &headers)?;
+    principal.require_scope("users:delete")?;
+    let actor_id = principal.user_id;
 
-    // Try to find a policy named `compliance:delete`
-    let mut policy_opt: Option<verseguy_authorization::store::Policy> = None;
-    let policies: Vec<serde_json::Value> = (*state.storage)
-        .prefix_scan(b"policy:")
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
-    for p in policies {
-        // Attempt to deserialize into Policy; ignore deserialization errors
-        if let Ok(pol) = serde_json::from_value::<verseguy_authorization::store::Policy>(
-            serde_json::to_value(&p).unwrap_or(serde_json::Value::Null),
-        ) {
-            if pol.name == "compliance:delete" {
-                policy_opt = Some(pol);
-                break;
-            }
-        }
-    }
-
-    let authorized = if let Some(pol) = policy_opt {
-        // Evaluate using the existing policy engine
-        let role_refs: Vec<&str> = actor_roles.iter().map(String::as_str).collect();
-        verseguy_authorization::policy::evaluate_policy(&pol.policy, &role_refs)
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?
-    } else {
-        // Fallback: require admin role
-        actor_roles.iter().any(|r| r == "admin")
-    };
+    // The `compliance:delete` policy decides; without one, the admin role does
+    let authorized = crate::admin::is_permitted(&state, &actor_id, "compliance:delete")?;
 
     if !authorized {
-        return Err((StatusCode::FORBIDDEN, "forbidden".to_string()));
+        return Err(AppError::Forbidden(
+            "not allowed to delete user data".into(),
+        ));
     }
 
     // Proceed with deletion of personal data
     let deleted_records = verseguy_compliance::gdpr::delete_user_data(&state.storage, &user_id)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
+        .map_err(AppError::internal)?;
 
     // Delete audit entries for the principal and capture how many were deleted
     let audit = verseguy_audit::AuditService::new(state.storage.clone());
     let deleted_audit = audit
         .delete_for_user(&user_id)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
+        .map_err(AppError::internal)?;
 
-    // Delete ToS acceptance if present
-    let tos_key = format!("tos:{}", user_id);
-    let _ = (*state.storage).delete(tos_key.as_bytes());
+    // Delete ToS acceptances if present
+    let _ = crate::legal::delete_acceptances(&state.storage, &user_id);
 
     // Record an immutable audit event for this delete action in the protected namespace
     let deleted_count = deleted_audit + if deleted_records { 1 } else { 0 };
@@ -663,7 +883,7 @@ pub async fn user_data_delete_handler(
 
     let _ = audit
         .log_delete_event(Some(actor_id), event)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e)))?;
+        .map_err(AppError::internal)?;
 
     Ok(Json(serde_json::json!({ "deleted": deleted_count })))
 }
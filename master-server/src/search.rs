@@ -0,0 +1,424 @@
+//! Full-text plugin search.
+//!
+//! Every plugin is indexed in memory by the name, id, tags, author and description of its
+//! newest installable version. Results are ranked with BM25: matches in the name count most,
+//! then the id and tags, then the author and description. Download counts give an extra boost.
+//! The index is built from storage when the server starts. A plugin is reindexed whenever one
+//! of its versions is published, revoked, yanked or unyanked. The index also keeps the
+//! manifests of each plugin's installable stable versions, so searches are answered without
+//! reading storage.
+//!
+//! Download counts are kept under `downloads:{id}`.
+
+use crate::plugins::PluginManifest;
+use crate::registry::{releases, Host};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use utoipa::ToSchema;
+use verseguy_auth::License;
+use verseguy_storage::RocksDBStorage;
+
+/// BM25 term frequency saturation
+const K1: f32 = 1.2;
+/// BM25 document length normalisation
+const B: f32 = 0.75;
+const NAME_WEIGHT: f32 = 3.0;
+const ID_WEIGHT: f32 = 2.0;
+const TAG_WEIGHT: f32 = 2.0;
+const TEXT_WEIGHT: f32 = 1.0;
+/// Share of an exact match's score that a prefix match earns
+const PREFIX_WEIGHT: f32 = 0.5;
+/// Shortest query term that also matches as a prefix
+const MIN_PREFIX: usize = 2;
+/// Score multiplier per e-fold of downloads
+const DOWNLOAD_BOOST: f32 = 0.1;
+
+pub const DEFAULT_PER_PAGE: usize = 20;
+pub const MAX_PER_PAGE: usize = 100;
+
+/// Lowercase alphanumeric runs of `text`
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Downloads of a plugin, stored under `downloads:{id}`
+#[derive(Serialize, Deserialize)]
+struct DownloadCount {
+    id: String,
+    count: u64,
+}
+
+struct Document {
+    /// Sum of the field-weighted term frequencies
+    len: f32,
+    terms: Vec<String>,
+    /// Installable stable versions, newest first; the first one that runs on the searching host
+    /// represents the plugin in results, filters and facets
+    versions: Vec<PluginManifest>,
+}
+
+#[derive(Default)]
+struct Index {
+    /// Term -> plugin id -> field-weighted frequency
+    postings: BTreeMap<String, HashMap<String, f32>>,
+    documents: HashMap<String, Document>,
+    total_len: f32,
+    downloads: HashMap<String, u64>,
+}
+
+impl Index {
+    /// Index the newest of `versions`, which must be non-empty and sorted newest first
+    fn insert(&mut self, versions: Vec<PluginManifest>) {
+        let Some(manifest) = versions.first() else {
+            return;
+        };
+        self.remove(&manifest.id);
+        let mut fields = vec![
+            (manifest.name.as_str(), NAME_WEIGHT),
+            (manifest.id.as_str(), ID_WEIGHT),
+        ];
+        fields.extend(manifest.tags.iter().map(|t| (t.as_str(), TAG_WEIGHT)));
+        fields.extend(manifest.author.as_deref().map(|a| (a, TEXT_WEIGHT)));
+        fields.extend(manifest.description.as_deref().map(|d| (d, TEXT_WEIGHT)));
+
+        let mut frequencies: HashMap<String, f32> = HashMap::new();
+        for (text, weight) in fields {
+            for term in tokenize(text) {
+                *frequencies.entry(term).or_default() += weight;
+            }
+        }
+        let len = frequencies.values().sum();
+        for (term, frequency) in &frequencies {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(manifest.id.clone(), *frequency);
+        }
+        self.total_len += len;
+        self.documents.insert(
+            manifest.id.clone(),
+            Document {
+                len,
+                terms: frequencies.into_keys().collect(),
+                versions,
+            },
+        );
+    }
+
+    fn remove(&mut self, id: &str) {
+        let Some(document) = self.documents.remove(id) else {
+            return;
+        };
+        for term in document.terms {
+            if let Some(postings) = self.postings.get_mut(&term) {
+                postings.remove(id);
+                if postings.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+        self.total_len -= document.len;
+    }
+
+    /// BM25 score of every document containing all terms of `q`, exactly or as a prefix;
+    /// every document scores 1 for a query without terms
+    fn matches(&self, q: &str) -> HashMap<String, f32> {
+        let terms: BTreeSet<String> = tokenize(q).collect();
+        if terms.is_empty() {
+            return self.documents.keys().map(|id| (id.clone(), 1.0)).collect();
+        }
+        let count = self.documents.len() as f32;
+        let avg_len = if count > 0.0 {
+            self.total_len / count
+        } else {
+            1.0
+        };
+
+        let mut scores: Option<HashMap<&str, f32>> = None;
+        for term in &terms {
+            // best match of this query term per document
+            let mut best: HashMap<&str, f32> = HashMap::new();
+            let range = (Bound::Included(term.as_str()), Bound::Unbounded);
+            let indexed = self
+                .postings
+                .range::<str, _>(range)
+                .take_while(|(t, _)| t.starts_with(term.as_str()));
+            for (indexed_term, postings) in indexed {
+                let weight = if indexed_term == term {
+                    1.0
+                } else if term.chars().count() >= MIN_PREFIX {
+                    PREFIX_WEIGHT
+                } else {
+                    continue;
+                };
+                let df = postings.len() as f32;
+                let idf = (1.0 + (count - df + 0.5) / (df + 0.5)).ln();
+                for (id, tf) in postings {
+                    let len = self.documents.get(id).map_or(avg_len, |d| d.len);
+                    let norm = K1 * (1.0 - B + B * len / avg_len);
+                    let score = weight * idf * tf * (K1 + 1.0) / (tf + norm);
+                    let entry = best.entry(id.as_str()).or_default();
+                    *entry = entry.max(score);
+                }
+            }
+            scores = Some(match scores {
+                None => best,
+                Some(previous) => previous
+                    .into_iter()
+                    .filter_map(|(id, score)| best.get(id).map(|b| (id, score + b)))
+                    .collect(),
+            });
+        }
+        scores
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(id, score)| (id.to_string(), score))
+            .collect()
+    }
+}
+
+/// In-memory full-text index over published plugins
+#[derive(Default)]
+pub struct SearchIndex {
+    index: RwLock<Index>,
+}
+
+impl SearchIndex {
+    /// Index every plugin in `storage`
+    pub fn build(storage: &RocksDBStorage) -> Result<Self> {
+        let search = SearchIndex::default();
+        let manifests: Vec<PluginManifest> = storage.prefix_scan(b"plugin:")?;
+        let ids: BTreeSet<String> = manifests.into_iter().map(|m| m.id).collect();
+        for id in ids {
+            search.reindex(storage, &id)?;
+        }
+        let counts: Vec<DownloadCount> = storage.prefix_scan(b"downloads:")?;
+        search
+            .write()
+            .downloads
+            .extend(counts.into_iter().map(|c| (c.id, c.count)));
+        Ok(search)
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Index> {
+        self.index.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Index> {
+        self.index.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Index the newest installable version of `id`, or drop `id` when it has none
+    pub fn reindex(&self, storage: &RocksDBStorage, id: &str) -> Result<()> {
+        // pre-releases are never the latest version
+        let versions: Vec<PluginManifest> = releases(storage, id)?
+            .into_iter()
+            .filter(|r| r.version.pre.is_empty())
+            .map(|r| r.manifest)
+            .collect();
+        let mut index = self.write();
+        if versions.is_empty() {
+            index.remove(id);
+        } else {
+            index.insert(versions);
+        }
+        Ok(())
+    }
+
+    /// Count a download of `id`; returns the new total
+    pub fn record_download(&self, storage: &RocksDBStorage, id: &str) -> Result<u64> {
+        let mut index = self.write();
+        let count = index.downloads.get(id).copied().unwrap_or(0) + 1;
+        let key = format!("downloads:{}", id);
+        let record = DownloadCount {
+            id: id.to_string(),
+            count,
+        };
+        storage.put(key.as_bytes(), &record)?;
+        index.downloads.insert(id.to_string(), count);
+        Ok(count)
+    }
+
+    pub fn downloads(&self, id: &str) -> u64 {
+        self.read().downloads.get(id).copied().unwrap_or(0)
+    }
+
+    /// Relevance of every indexed plugin matching all terms of `q`
+    pub fn matches(&self, q: &str) -> HashMap<String, f32> {
+        self.read().matches(q)
+    }
+
+    /// Every plugin matching `params`, unsorted. A plugin is represented by its newest version
+    /// that runs on `params.host` and only matches when that version passes the filters.
+    fn hits(&self, params: &SearchParams) -> Vec<SearchHit> {
+        let index = self.read();
+        let mut hits = Vec::new();
+        for (id, relevance) in index.matches(&params.q) {
+            let Some(document) = index.documents.get(&id) else {
+                continue;
+            };
+            let Some(manifest) = document
+                .versions
+                .iter()
+                .find(|m| params.host.incompatibility(m).is_none())
+            else {
+                continue;
+            };
+            if !params.filters.matches(manifest) {
+                continue;
+            }
+            let downloads = index.downloads.get(&id).copied().unwrap_or(0);
+            let score = relevance * (1.0 + DOWNLOAD_BOOST * (downloads as f32).ln_1p());
+            hits.push(SearchHit {
+                manifest: manifest.clone(),
+                downloads,
+                score,
+            });
+        }
+        hits
+    }
+}
+
+/// License tier names as used in manifests and queries
+pub fn license_name(license: License) -> &'static str {
+    match license {
+        License::Free => "Free",
+        License::Pro => "Pro",
+        License::Enterprise => "Enterprise",
+    }
+}
+
+/// Parse a license tier name, ignoring case
+pub fn parse_license(name: &str) -> Option<License> {
+    [License::Free, License::Pro, License::Enterprise]
+        .into_iter()
+        .find(|l| license_name(*l).eq_ignore_ascii_case(name.trim()))
+}
+
+/// Restrictions on search results besides the query
+#[derive(Debug, Clone, Default)]
+pub struct Filters {
+    /// Only plugins usable with this license tier
+    pub license: Option<License>,
+    /// Only plugins requesting this capability
+    pub capability: Option<String>,
+    pub category: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl Filters {
+    pub fn matches(&self, manifest: &PluginManifest) -> bool {
+        let required = manifest.license_required.unwrap_or(License::Free);
+        self.license.is_none_or(|tier| required <= tier)
+            && self
+                .capability
+                .as_ref()
+                .is_none_or(|c| manifest.capabilities.contains(c))
+            && self.category.as_ref().is_none_or(|c| {
+                manifest
+                    .category
+                    .as_ref()
+                    .is_some_and(|own| own.eq_ignore_ascii_case(c))
+            })
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|t| manifest.tags.iter().any(|own| own.eq_ignore_ascii_case(t)))
+    }
+}
+
+/// Number of matching plugins per value
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, ToSchema)]
+pub struct Facets {
+    pub category: BTreeMap<String, usize>,
+    pub tags: BTreeMap<String, usize>,
+    pub capabilities: BTreeMap<String, usize>,
+    /// Per required license tier
+    pub license: BTreeMap<String, usize>,
+}
+
+impl Facets {
+    fn of<'a>(manifests: impl Iterator<Item = &'a PluginManifest>) -> Self {
+        let mut facets = Facets::default();
+        for m in manifests {
+            if let Some(category) = &m.category {
+                *facets.category.entry(category.clone()).or_default() += 1;
+            }
+            for tag in &m.tags {
+                *facets.tags.entry(tag.clone()).or_default() += 1;
+            }
+            for capability in &m.capabilities {
+                *facets.capabilities.entry(capability.clone()).or_default() += 1;
+            }
+            let license = license_name(m.license_required.unwrap_or(License::Free));
+            *facets.license.entry(license.to_string()).or_default() += 1;
+        }
+        facets
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub manifest: PluginManifest,
+    pub downloads: u64,
+    /// Relevance to the query with the download boost applied
+    pub score: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct SearchResults {
+    /// The requested page of matches, best first
+    pub results: Vec<SearchHit>,
+    /// Matches on all pages
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+    /// Counts over all matches
+    pub facets: Facets,
+}
+
+/// A search request
+#[derive(Debug, Clone)]
+pub struct SearchParams {
+    pub q: String,
+    /// Offer the newest version that runs on this host
+    pub host: Host,
+    pub filters: Filters,
+    /// 1-based
+    pub page: usize,
+    pub per_page: usize,
+}
+
+/// Rank the plugins matching `params` and return the requested page. Each plugin is
+/// represented by its newest version that runs on `params.host`; filters apply to that version.
+pub fn search(index: &SearchIndex, params: &SearchParams) -> SearchResults {
+    let mut hits = index.hits(params);
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.manifest.id.cmp(&b.manifest.id))
+    });
+
+    let facets = Facets::of(hits.iter().map(|h| &h.manifest));
+    let total = hits.len();
+    let page = params.page.max(1);
+    let per_page = params.per_page.clamp(1, MAX_PER_PAGE);
+    let results = hits
+        .into_iter()
+        .skip((page - 1).saturating_mul(per_page))
+        .take(per_page)
+        .collect();
+    SearchResults {
+        results,
+        total,
+        page,
+        per_page,
+        facets,
+    }
+}
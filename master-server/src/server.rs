@@ -0,0 +1,140 @@
+//! HTTP(S) listener for the router built by [`crate::build_app`].
+//!
+//! The server binds `server.bind`, serves HTTPS when `server.tls` is configured and stops
+//! accepting connections on SIGTERM or Ctrl-C, giving in-flight requests up to
+//! `server.shutdown_timeout_secs` to finish.
+
+use anyhow::Context;
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{ServerConfig, TlsConfig};
+use crate::state::AppState;
+
+/// Bound listener, ready to serve
+pub struct Server {
+    listener: TcpListener,
+    tls: Option<RustlsConfig>,
+    shutdown_timeout: Duration,
+}
+
+impl Server {
+    /// Bind `config.bind` and load the TLS certificate, so configuration errors surface
+    /// before the server starts
+    pub async fn bind(config: &ServerConfig) -> anyhow::Result<Self> {
+        let tls = match &config.tls {
+            Some(tls) => Some(load_tls(tls).await?),
+            None => None,
+        };
+        let listener = TcpListener::bind(config.bind)
+            .with_context(|| format!("failed to bind {}", config.bind))?;
+        listener.set_nonblocking(true)?;
+        Ok(Server {
+            listener,
+            tls,
+            shutdown_timeout: config.shutdown_timeout(),
+        })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve `app` until `shutdown` completes, then drain in-flight requests
+    pub async fn serve<F>(self, app: Router, shutdown: F) -> anyhow::Result<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let addr = self.local_addr()?;
+        let handle = Handle::new();
+        let timeout = self.shutdown_timeout;
+        let drain = handle.clone();
+        tokio::spawn(async move {
+            shutdown.await;
+            tracing::info!("shutting down, draining requests for up to {:?}", timeout);
+            drain.graceful_shutdown(Some(timeout));
+        });
+
+        let service = app.into_make_service_with_connect_info::<SocketAddr>();
+        match self.tls {
+            Some(tls) => {
+                tracing::info!("master server listening on https://{}", addr);
+                axum_server::from_tcp_rustls(self.listener, tls)
+                    .handle(handle)
+                    .serve(service)
+                    .await?
+            }
+            None => {
+                tracing::info!("master server listening on http://{}", addr);
+                axum_server::from_tcp(self.listener)
+                    .handle(handle)
+                    .serve(service)
+                    .await?
+            }
+        }
+        tracing::info!("master server stopped");
+        Ok(())
+    }
+}
+
+async fn load_tls(tls: &TlsConfig) -> anyhow::Result<RustlsConfig> {
+    match (&tls.cert, &tls.key) {
+        (Some(cert), Some(key)) => RustlsConfig::from_pem_file(cert, key)
+            .await
+            .with_context(|| format!("failed to load TLS certificate {}", cert.display())),
+        (None, None) if tls.self_signed => {
+            tracing::warn!("serving a self-signed certificate for localhost; development only");
+            let names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+            let certified = rcgen::generate_simple_self_signed(names)?;
+            let cert = certified.cert.pem().into_bytes();
+            let key = certified.key_pair.serialize_pem().into_bytes();
+            Ok(RustlsConfig::from_pem(cert, key).await?)
+        }
+        _ => Err(anyhow::anyhow!(
+            "server.tls needs both cert and key, or self_signed = true"
+        )),
+    }
+}
+
+/// Completes on SIGTERM or Ctrl-C
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Serve the application on `state.config.server` until SIGTERM or Ctrl-C
+pub async fn run_server(state: Arc<AppState>) -> anyhow::Result<()> {
+    let server = Server::bind(&state.config.server).await?;
+    let app = crate::build_app(state);
+    server.serve(app, shutdown_signal()).await
+}
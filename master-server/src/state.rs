@@ -1,37 +1,79 @@
+use crate::artifacts::BlobStore;
+use crate::config::Config;
 use crate::ed25519_compat::Keypair;
+use crate::search::SearchIndex;
 use std::sync::Arc;
+use verseguy_ratelimit::RateLimitStore;
 use verseguy_storage::RocksDBStorage;
 
 pub struct AppState {
     pub storage: Arc<RocksDBStorage>,
+    /// Uploaded plugin artifacts
+    pub artifacts: BlobStore,
     pub license_secret: Vec<u8>,
     pub keypair: Option<Keypair>,
     /// Optional Prometheus metrics handle used by the /metrics endpoint
     pub metrics_handle: Option<metrics_exporter_prometheus::PrometheusHandle>,
+    /// Settings the server runs with; handlers read these instead of the environment
+    pub config: Config,
+    /// Buckets of the rate limits in `config.rate_limits`
+    pub rate_limit_store: Arc<dyn RateLimitStore>,
+    /// Full-text index behind `/plugins/search`
+    pub search: SearchIndex,
 }
 
 impl AppState {
+    /// State with the default configuration, a database at `path` and `license_secret`
     pub fn new<P: AsRef<std::path::Path>>(
         path: P,
         license_secret: Vec<u8>,
     ) -> anyhow::Result<Self> {
-        let storage = RocksDBStorage::open(path)?;
-
-        // If MASTER_KEY_FILE is set, load (or generate) the signing key there; otherwise generate ephemeral keypair
-        let keypair = if let Ok(key_path) = std::env::var("MASTER_KEY_FILE") {
-            let kp = crate::keystore::load_or_generate(std::path::Path::new(&key_path))?;
-            Some(kp)
-        } else {
-            let mut csprng = rand::rngs::OsRng {};
-            let kp = Keypair::generate(&mut csprng);
-            Some(kp)
+        let mut config = Config::default();
+        config.storage.db_path = path.as_ref().to_path_buf();
+        Self::open(config, license_secret)
+    }
+
+    pub fn from_config(config: Config) -> anyhow::Result<Self> {
+        let license_secret = config.security.license_secret.clone().into_bytes();
+        Self::open(config, license_secret)
+    }
+
+    fn open(config: Config, license_secret: Vec<u8>) -> anyhow::Result<Self> {
+        let storage = RocksDBStorage::open(&config.storage.db_path)?;
+        let artifacts = BlobStore::open(config.storage.artifact_dir())?;
+
+        // With a key file, load (or generate) the signing key there; otherwise generate an
+        // ephemeral keypair
+        let keypair = match &config.security.key_file {
+            Some(key_path) => crate::keystore::load_or_generate(key_path)?,
+            None => {
+                let mut csprng = rand::rngs::OsRng {};
+                Keypair::generate(&mut csprng)
+            }
         };
 
+        // Plugins signed with earlier keys keep verifying after a rotation
+        crate::trust::record_key(&storage, &keypair.public)?;
+        for key in &config.security.trusted_keys {
+            crate::trust::record_key_b64(&storage, key)
+                .map_err(|e| anyhow::anyhow!("security.trusted_keys: {}", e))?;
+        }
+
+        // Revocations recorded before the signed list existed get numbered once
+        crate::crl::backfill(&storage)?;
+
+        let rate_limit_store = config.rate_limits.open_store()?;
+        let search = SearchIndex::build(&storage)?;
+
         Ok(Self {
             storage: Arc::new(storage),
+            artifacts,
             license_secret,
-            keypair,
+            keypair: Some(keypair),
             metrics_handle: None,
+            config,
+            rate_limit_store,
+            search,
         })
     }
 }
@@ -0,0 +1,334 @@
+use crate::admin::{require_admin, ADMIN_ACCOUNTS};
+use crate::auth::Principal;
+use crate::state::AppState;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use verseguy_auth::{AccessTokenService, License, PersonalAccessToken};
+use verseguy_shared_error::{AppError, ProblemDetails};
+
+/// Longest lifetime a token can be created with
+pub const MAX_TOKEN_LIFETIME_DAYS: i64 = 365;
+/// Lifetime of tokens created without `expires_in_days`
+pub const DEFAULT_TOKEN_LIFETIME_DAYS: i64 = 90;
+/// Scope personal access tokens need to list and revoke the user's tokens
+pub const TOKENS_SCOPE: &str = "tokens:manage";
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// Lifetime in days, 1 to 365; 90 when omitted
+    pub expires_in_days: Option<i64>,
+}
+
+/// Token metadata returned by the API (never includes the hash)
+#[derive(Serialize, ToSchema)]
+pub struct TokenView {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub hint: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub active: bool,
+}
+
+impl From<PersonalAccessToken> for TokenView {
+    fn from(t: PersonalAccessToken) -> Self {
+        Self {
+            active: t.is_active(),
+            id: t.id,
+            user_id: t.user_id,
+            name: t.name,
+            scopes: t.scopes,
+            hint: t.hint,
+            created_at: t.created_at,
+            expires_at: t.expires_at,
+            last_used_at: t.last_used_at,
+            revoked_at: t.revoked_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateTokenResponse {
+    /// Plaintext token; only returned once
+    pub token: String,
+    pub record: TokenView,
+}
+
+fn create_token_for(
+    state: &AppState,
+    user_id: &str,
+    req: CreateTokenRequest,
+) -> Result<(StatusCode, Json<CreateTokenResponse>), AppError> {
+    let days = req.expires_in_days.unwrap_or(DEFAULT_TOKEN_LIFETIME_DAYS);
+    if !(1..=MAX_TOKEN_LIFETIME_DAYS).contains(&days) {
+        return Err(AppError::BadRequest(format!(
+            "expires_in_days must be between 1 and {}",
+            MAX_TOKEN_LIFETIME_DAYS
+        )));
+    }
+    let tokens = AccessTokenService::new((*state.storage).clone());
+    let (token, record) = tokens
+        .create(user_id, &req.name, req.scopes, Some(days))
+        .map_err(|e| AppError::BadRequest(format!("{}", e)))?;
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateTokenResponse {
+            token,
+            record: record.into(),
+        }),
+    ))
+}
+
+/// Create a personal access token for the calling user (session login required)
+#[utoipa::path(
+    post,
+    path = "/auth/tokens",
+    tag = "auth",
+    request_body = CreateTokenRequest,
+    security(("bearer" = [])),
+    responses(
+        (
+            status = 201,
+            description = "Token created; the plaintext token is only returned here",
+            body = CreateTokenResponse
+        ),
+        (status = 400, description = "Invalid scopes or expiry", body = ProblemDetails),
+        (
+            status = 401,
+            description = "Missing or invalid bearer credentials",
+            body = ProblemDetails
+        ),
+        (
+            status = 403,
+            description = "Personal access tokens cannot mint new tokens",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn create_token_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Json(req): Json<CreateTokenRequest>,
+) -> Result<(StatusCode, Json<CreateTokenResponse>), AppError> {
+    principal.require_session()?;
+    create_token_for(&state, &principal.user_id, req)
+}
+
+/// List the personal access tokens of the calling user
+#[utoipa::path(
+    get,
+    path = "/auth/tokens",
+    tag = "auth",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Token metadata under `tokens`", body = serde_json::Value),
+        (
+            status = 401,
+            description = "Missing or invalid bearer credentials",
+            body = ProblemDetails
+        ),
+        (
+            status = 403,
+            description = "Personal access token without the `tokens:manage` scope",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn list_tokens_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    principal.require_scope(TOKENS_SCOPE)?;
+    let tokens = AccessTokenService::new((*state.storage).clone());
+    let list: Vec<TokenView> = tokens
+        .list(&principal.user_id)
+        .map_err(AppError::internal)?
+        .into_iter()
+        .map(TokenView::from)
+        .collect();
+    Ok(Json(serde_json::json!({ "tokens": list })))
+}
+
+/// Revoke one of the calling user's personal access tokens
+#[utoipa::path(
+    delete,
+    path = "/auth/tokens/{id}",
+    tag = "auth",
+    params(("id" = String, Path, description = "Token id")),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "The revoked token", body = TokenView),
+        (
+            status = 401,
+            description = "Missing or invalid bearer credentials",
+            body = ProblemDetails
+        ),
+        (
+            status = 403,
+            description = "Personal access token without the `tokens:manage` scope",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Token not found", body = ProblemDetails),
+    )
+)]
+pub async fn revoke_token_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Path(id): Path<String>,
+) -> Result<Json<TokenView>, AppError> {
+    principal.require_scope(TOKENS_SCOPE)?;
+    let tokens = AccessTokenService::new((*state.storage).clone());
+    let record = tokens
+        .revoke(&principal.user_id, &id)
+        .map_err(|e| AppError::NotFound(format!("{}", e)))?;
+    Ok(Json(record.into()))
+}
+
+// --- Service accounts (admin) ---
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateServiceAccountRequest {
+    pub name: String,
+    /// `Free` (default), `Pro` or `Enterprise`
+    #[schema(value_type = Option<String>)]
+    pub license: Option<License>,
+}
+
+/// Admin: create a service account
+#[utoipa::path(
+    post,
+    path = "/admin/service-accounts",
+    tag = "admin",
+    request_body = CreateServiceAccountRequest,
+    security(("bearer" = [])),
+    responses(
+        (
+            status = 201,
+            description = "The new account as `id` and `username`",
+            body = serde_json::Value
+        ),
+        (status = 400, description = "Invalid account name", body = ProblemDetails),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing `admin:accounts` permission",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn admin_create_service_account_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<CreateServiceAccountRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_ACCOUNTS)?;
+    let tokens = AccessTokenService::new((*state.storage).clone());
+    let user = tokens
+        .create_service_account(&req.name, req.license.unwrap_or(License::Free))
+        .map_err(|e| AppError::BadRequest(format!("{}", e)))?;
+    admin.audit(
+        &state,
+        "accounts.create",
+        serde_json::json!({ "id": user.id, "username": user.username }),
+    )?;
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({ "id": user.id, "username": user.username })),
+    ))
+}
+
+/// Admin: list service accounts
+#[utoipa::path(
+    get,
+    path = "/admin/service-accounts",
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, description = "Accounts under `service_accounts`", body = serde_json::Value),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing `admin:accounts` permission",
+            body = ProblemDetails
+        ),
+    )
+)]
+pub async fn admin_list_service_accounts_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_ACCOUNTS)?;
+    let tokens = AccessTokenService::new((*state.storage).clone());
+    let accounts: Vec<serde_json::Value> = tokens
+        .list_service_accounts()
+        .map_err(AppError::internal)?
+        .into_iter()
+        .map(|u| serde_json::json!({ "id": u.id, "username": u.username, "license": u.license }))
+        .collect();
+    admin.audit(&state, "accounts.list", serde_json::json!({}))?;
+    Ok(Json(serde_json::json!({ "service_accounts": accounts })))
+}
+
+/// Admin: issue a token for a service account
+#[utoipa::path(
+    post,
+    path = "/admin/service-accounts/{id}/tokens",
+    tag = "admin",
+    params(("id" = String, Path, description = "Service account id")),
+    request_body = CreateTokenRequest,
+    security(("bearer" = [])),
+    responses(
+        (
+            status = 201,
+            description = "Token created; the plaintext token is only returned here",
+            body = CreateTokenResponse
+        ),
+        (status = 400, description = "Invalid scopes or expiry", body = ProblemDetails),
+        (status = 401, description = "Missing or invalid token", body = ProblemDetails),
+        (
+            status = 403,
+            description = "Missing `admin:accounts` permission",
+            body = ProblemDetails
+        ),
+        (status = 404, description = "Service account not found", body = ProblemDetails),
+    )
+)]
+pub async fn admin_create_service_token_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(req): Json<CreateTokenRequest>,
+) -> Result<(StatusCode, Json<CreateTokenResponse>), AppError> {
+    let admin = require_admin(&state, &headers, ADMIN_ACCOUNTS)?;
+    let tokens = AccessTokenService::new((*state.storage).clone());
+    let is_service = tokens
+        .list_service_accounts()
+        .map_err(AppError::internal)?
+        .iter()
+        .any(|u| u.id == id);
+    if !is_service {
+        return Err(AppError::NotFound("service account not found".into()));
+    }
+    let (status, Json(created)) = create_token_for(&state, &id, req)?;
+    admin.audit(
+        &state,
+        "accounts.token.create",
+        serde_json::json!({
+            "account_id": id,
+            "token_id": created.record.id,
+            "scopes": created.record.scopes,
+        }),
+    )?;
+    Ok((status, Json(created)))
+}
@@ -0,0 +1,86 @@
+//! Master keys that plugin signatures are verified against.
+//!
+//! Every master key the server signs with is recorded under `trust_key:{key_id}` when it is
+//! loaded, rotated in or imported, so plugins signed before a rotation keep verifying. Keys of
+//! other registries can be added with `security.trusted_keys`.
+
+use anyhow::Result;
+use axum::extract::State;
+use axum::Json;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use verseguy_shared_error::AppError;
+use verseguy_signing::TrustStore;
+use verseguy_storage::RocksDBStorage;
+
+use crate::ed25519_compat::PublicKey;
+use crate::state::AppState;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct TrustedKey {
+    pub key_id: String,
+    /// Base64 Ed25519 public key
+    pub public_key: String,
+    /// Unix timestamp (seconds) the key was first trusted
+    pub added_at: i64,
+}
+
+/// Trust `key`; keeps the original record when it is already trusted
+pub fn record_key(storage: &RocksDBStorage, key: &PublicKey) -> Result<TrustedKey> {
+    let key_id = verseguy_signing::key_id(key);
+    let db_key = format!("trust_key:{}", key_id);
+    if let Some(existing) = storage.get::<_, TrustedKey>(db_key.as_bytes())? {
+        return Ok(existing);
+    }
+    let record = TrustedKey {
+        key_id,
+        public_key: verseguy_signing::encode_public_key(key),
+        added_at: Utc::now().timestamp(),
+    };
+    storage.put(db_key.as_bytes(), &record)?;
+    Ok(record)
+}
+
+/// Trust a base64 public key
+pub fn record_key_b64(storage: &RocksDBStorage, b64: &str) -> Result<TrustedKey> {
+    let key = verseguy_signing::decode_public_key(b64)?;
+    record_key(storage, &key)
+}
+
+pub fn trusted_keys(storage: &RocksDBStorage) -> Result<Vec<TrustedKey>> {
+    let mut keys: Vec<TrustedKey> = storage.prefix_scan(b"trust_key:")?;
+    keys.sort_by(|a, b| (a.added_at, &a.key_id).cmp(&(b.added_at, &b.key_id)));
+    Ok(keys)
+}
+
+/// Every trusted key, for verification
+pub fn trust_store(storage: &RocksDBStorage) -> Result<TrustStore> {
+    let mut trust = TrustStore::new();
+    for key in trusted_keys(storage)? {
+        trust.add_b64(&key.public_key)?;
+    }
+    Ok(trust)
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TrustResponse {
+    pub keys: Vec<TrustedKey>,
+}
+
+/// Master public keys plugin signatures may be made with
+#[utoipa::path(
+    get,
+    path = "/plugins/trust",
+    tag = "plugins",
+    responses(
+        (status = 200, description = "Trusted keys, oldest first", body = TrustResponse),
+    )
+)]
+pub async fn trust_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<TrustResponse>, AppError> {
+    let keys = trusted_keys(&state.storage).map_err(AppError::internal)?;
+    Ok(Json(TrustResponse { keys }))
+}
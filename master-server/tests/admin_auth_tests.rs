@@ -0,0 +1,223 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use master_server::build_app;
+use master_server::config::Config;
+use master_server::state::AppState;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::tempdir;
+use tower::util::ServiceExt;
+use verseguy_audit::AuditService;
+use verseguy_auth::local::LocalAuth;
+use verseguy_auth::{AccountKind, AuthMethod, IdentityStore, License, SessionService, User};
+use verseguy_authorization::rbac::{Assignment, Role};
+use verseguy_authorization::store::Policy;
+use verseguy_test_utils::{must, must_opt};
+
+async fn send(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    token: Option<&str>,
+    body: Value,
+) -> (StatusCode, Value) {
+    let mut req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(token) = token {
+        req = req.header("authorization", format!("Bearer {}", token));
+    }
+    let resp = must(
+        app.clone()
+            .oneshot(must(req.body(Body::from(body.to_string()))))
+            .await,
+    );
+    let status = resp.status();
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+/// Register `username` and log in; returns the user id and session token
+async fn session(app: &Router, username: &str) -> (String, String) {
+    let creds = json!({"username": username, "password": "correct-horse"});
+    let (status, body) = send(app, "POST", "/auth/register", None, creds).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let id = must_opt(body["id"].as_str(), "missing id").to_string();
+    (id, login(app, username).await)
+}
+
+/// Session token of the already registered `username`
+async fn login(app: &Router, username: &str) -> String {
+    let creds = json!({"username": username, "password": "correct-horse"});
+    let (_, body) = send(app, "POST", "/auth/login", None, creds).await;
+    must_opt(body["token"].as_str(), "missing token").to_string()
+}
+
+async fn revoke(app: &Router, token: Option<&str>) -> StatusCode {
+    let body = json!({"id": "org.admin.test", "version": "1.0.0", "reason": "malware"});
+    send(app, "POST", "/verify/revoke", token, body).await.0
+}
+
+/// Actions recorded in the audit log for `user_id`
+fn audited(state: &AppState, user_id: &str) -> Vec<String> {
+    must(AuditService::new(state.storage.clone()).export_for_user(user_id))
+        .into_iter()
+        .filter_map(|e| serde_json::from_str::<Value>(&e.event).ok())
+        .filter_map(|e| e["action"].as_str().map(str::to_string))
+        .collect()
+}
+
+/// State whose administrator, through `security.admin_users`, is the local account `root`
+async fn state_at(dir: &std::path::Path) -> Arc<AppState> {
+    let mut config = Config::default();
+    config.storage.db_path = dir.join("db");
+    let mut state = must(AppState::from_config(config));
+    let root = must(
+        LocalAuth::new((*state.storage).clone())
+            .register("root".into(), "correct-horse".into())
+            .await,
+    );
+    state.config.security.admin_users = vec![root.id];
+    Arc::new(state)
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    }
+}
+
+#[test]
+fn admin_users_hold_every_permission_and_are_audited() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = state_at(dir.path()).await;
+        let app = build_app(state.clone());
+        let root_id = &state.config.security.admin_users[0];
+        let root = login(&app, "root").await;
+        let (_, player) = session(&app, "player").await;
+
+        assert_eq!(revoke(&app, None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(revoke(&app, Some("forged")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(revoke(&app, Some(&player)).await, StatusCode::FORBIDDEN);
+        assert_eq!(revoke(&app, Some(&root)).await, StatusCode::OK);
+
+        let legal = json!({"doc_type": "tos", "version": "1.0", "title": "ToS", "content": "…"});
+        let (status, _) = send(&app, "POST", "/admin/legal", Some(&root), legal).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, "GET", "/admin/legal", Some(&player), Value::Null).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        assert_eq!(audited(&state, root_id), ["plugins.revoke", "legal.create"]);
+    });
+}
+
+#[test]
+fn policies_decide_and_tokens_need_the_scope() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = state_at(dir.path()).await;
+        let app = build_app(state.clone());
+        let root = login(&app, "root").await;
+        let (manager_id, manager) = session(&app, "manager").await;
+
+        // a policy moves `admin:plugins` to release managers only
+        let role = Role {
+            id: "r-release".into(),
+            name: "release-manager".into(),
+            version: 0,
+        };
+        must(state.storage.put(b"role:r-release", &role));
+        let assignment = Assignment {
+            user_id: manager_id.clone(),
+            role_id: role.id.clone(),
+            version: 0,
+        };
+        must(
+            state
+                .storage
+                .put(format!("assignment:{}", manager_id).as_bytes(), &assignment),
+        );
+        let policy = Policy {
+            id: "p-plugins".into(),
+            name: "admin:plugins".into(),
+            policy: "role:release-manager".into(),
+            version: 0,
+        };
+        must(state.storage.put(b"policy:p-plugins", &policy));
+
+        assert_eq!(revoke(&app, Some(&root)).await, StatusCode::FORBIDDEN);
+        assert_eq!(revoke(&app, Some(&manager)).await, StatusCode::OK);
+        // other permissions keep falling back to the admin role
+        let (status, _) = send(&app, "GET", "/admin/legal", Some(&manager), Value::Null).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // personal access tokens carry only the permissions in their scopes
+        let mut tokens = Vec::new();
+        for scopes in [json!(["plugins:publish"]), json!(["admin:plugins"])] {
+            let body = json!({"name": "ci", "scopes": scopes});
+            let (status, body) = send(&app, "POST", "/auth/tokens", Some(&manager), body).await;
+            assert_eq!(status, StatusCode::CREATED, "{}", body);
+            tokens.push(must_opt(body["token"].as_str(), "missing token").to_string());
+        }
+        assert_eq!(revoke(&app, Some(&tokens[0])).await, StatusCode::FORBIDDEN);
+        assert_eq!(revoke(&app, Some(&tokens[1])).await, StatusCode::OK);
+    });
+}
+
+#[test]
+fn usernames_do_not_grant_admin() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = state_at(dir.path()).await;
+        let app = build_app(state.clone());
+
+        // an OAuth sign-in reporting the admin's username under another id
+        let now = chrono::Utc::now();
+        let impostor = User {
+            id: "oauth-root".into(),
+            username: "root".into(),
+            email: None,
+            password_hash: None,
+            auth_method: AuthMethod::OAuth {
+                provider: "discord".into(),
+                token: "t".into(),
+                refresh_token: None,
+                expires_at: now,
+            },
+            identities: Vec::new(),
+            roles: Vec::new(),
+            kind: AccountKind::Human,
+            license: License::Pro,
+            created_at: now,
+            updated_at: now,
+        };
+        must(IdentityStore::new((*state.storage).clone()).save_user(&impostor));
+        let token = must(
+            SessionService::new(state.license_secret.clone()).create_and_store_session(
+                &impostor.id,
+                &impostor.license,
+                1,
+                &state.storage,
+            ),
+        );
+
+        assert_eq!(revoke(&app, Some(&token)).await, StatusCode::FORBIDDEN);
+        let (status, _) = send(&app, "GET", "/admin/legal", Some(&token), Value::Null).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // the account listed by id still is an administrator
+        let root = login(&app, "root").await;
+        assert_eq!(revoke(&app, Some(&root)).await, StatusCode::OK);
+    });
+}
@@ -1,13 +1,34 @@
 #![allow(clippy::disallowed_methods)]
 use axum::body::Body;
-use axum::http::{Method, Request};
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
 use base64::Engine;
 use master_server::build_app;
+use master_server::config::Config;
 use master_server::state::AppState;
 use std::sync::Arc;
 use tower::util::ServiceExt;
+use verseguy_auth::local::LocalAuth;
 use verseguy_test_utils::{must, must_opt};
 
+async fn post_json(app: &Router, uri: &str, body: &str) -> serde_json::Value {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()));
+    let resp = must(app.clone().oneshot(must(req)).await);
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null)
+}
+
+/// Session token of `admin`, registered by the test as an administrator
+async fn admin_session(app: &Router) -> String {
+    let creds = r#"{"username":"admin","password":"adm1n-pass"}"#;
+    let v = post_json(app, "/auth/login", creds).await;
+    must_opt(v["token"].as_str(), "missing token").to_string()
+}
+
 #[test]
 fn rotate_and_import_key() {
     let rt = match tokio::runtime::Builder::new_current_thread()
@@ -21,22 +42,40 @@ fn rotate_and_import_key() {
     rt.block_on(async {
         let dir = must(tempfile::tempdir());
         let key_path = dir.path().join("master.key");
-        let key_path_str = must_opt(key_path.to_str(), "key path not utf8").to_string();
-
-        // Set env vars
-        std::env::set_var("MASTER_KEY_FILE", &key_path_str);
-        std::env::set_var("MASTER_ADMIN_TOKEN", "testtoken");
 
-        let db_path = must_opt(dir.path().join("db").to_str(), "db path not utf8").to_string();
-        let state = Arc::new(must(AppState::new(db_path, b"secret".to_vec())));
+        let mut config = Config::default();
+        config.storage.db_path = dir.path().join("db");
+        config.security.key_file = Some(key_path.clone());
+        let mut state = must(AppState::from_config(config));
+        let admin = must(
+            LocalAuth::new((*state.storage).clone())
+                .register("admin".into(), "adm1n-pass".into())
+                .await,
+        );
+        state.config.security.admin_users = vec![admin.id];
+        let state = Arc::new(state);
         let app = build_app(state.clone());
+        let token = admin_session(&app).await;
+        let bearer = format!("Bearer {}", token);
+
+        // Only administrators may manage keys
+        let req = must(Request::builder().uri("/admin/keys").body(Body::empty()));
+        let resp = must(app.clone().oneshot(req).await);
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let req = must(
+            Request::builder()
+                .uri("/admin/keys")
+                .header("authorization", &bearer)
+                .body(Body::empty()),
+        );
+        assert!(must(app.clone().oneshot(req).await).status().is_success());
 
         // Rotate key
         let req: Request<axum::body::Body> = must(
             Request::builder()
                 .method(Method::POST)
                 .uri("/admin/keys/rotate")
-                .header("x-admin-token", "testtoken")
+                .header("authorization", &bearer)
                 .body(Body::empty()),
         );
 
@@ -63,7 +102,7 @@ fn rotate_and_import_key() {
                 .method(Method::POST)
                 .uri("/admin/keys/import")
                 .header("content-type", "application/json")
-                .header("x-admin-token", "testtoken")
+                .header("authorization", &bearer)
                 .body(Body::from(body)),
         );
 
@@ -0,0 +1,290 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use master_server::build_app;
+use master_server::config::Config;
+use master_server::plugins::store_manifest;
+use master_server::state::AppState;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::tempdir;
+use tower::util::ServiceExt;
+use verseguy_auth::local::LocalAuth;
+use verseguy_test_utils::{must, must_opt};
+
+async fn send(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    token: Option<&str>,
+    body: Value,
+) -> (StatusCode, Value) {
+    let mut req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(token) = token {
+        req = req.header("authorization", format!("Bearer {}", token));
+    }
+    let resp = must(
+        app.clone()
+            .oneshot(must(req.body(Body::from(body.to_string()))))
+            .await,
+    );
+    let status = resp.status();
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+/// Register `username` and log in; returns the user id and session token
+async fn session(app: &Router, username: &str) -> (String, String) {
+    let creds = json!({"username": username, "password": "correct-horse"});
+    let (status, body) = send(app, "POST", "/auth/register", None, creds).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let id = must_opt(body["id"].as_str(), "missing id").to_string();
+    (id, login(app, username).await)
+}
+
+/// Session token of the already registered `username`
+async fn login(app: &Router, username: &str) -> String {
+    let creds = json!({"username": username, "password": "correct-horse"});
+    let (_, body) = send(app, "POST", "/auth/login", None, creds).await;
+    must_opt(body["token"].as_str(), "missing token").to_string()
+}
+
+/// State whose administrator, through `security.admin_users`, is the local account `root`
+async fn state_at(dir: &std::path::Path) -> Arc<AppState> {
+    let mut config = Config::default();
+    config.storage.db_path = dir.join("db");
+    let mut state = must(AppState::from_config(config));
+    let root = must(
+        LocalAuth::new((*state.storage).clone())
+            .register("root".into(), "correct-horse".into())
+            .await,
+    );
+    state.config.security.admin_users = vec![root.id];
+    Arc::new(state)
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    }
+}
+
+#[test]
+fn locked_accounts_lose_sessions_tokens_and_logins() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let app = build_app(state_at(dir.path()).await);
+        let root = login(&app, "root").await;
+        let (player_id, player) = session(&app, "player").await;
+        let body = json!({"name": "ci", "scopes": ["tokens:manage"]});
+        let (_, body) = send(&app, "POST", "/auth/tokens", Some(&player), body).await;
+        let pat = must_opt(body["token"].as_str(), "missing token").to_string();
+
+        // lookup works by username and by id
+        let (status, body) =
+            send(&app, "GET", "/admin/users/player", Some(&root), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["id"], player_id.as_str());
+        assert_eq!(body["locked"], Value::Null);
+        let (_, body) = send(&app, "GET", "/admin/users/root", Some(&root), Value::Null).await;
+        assert_eq!(body["roles"], json!(["admin"]));
+        let uri = format!("/admin/users/{}", player_id);
+        let (_, body) = send(&app, "GET", &uri, Some(&root), Value::Null).await;
+        assert_eq!(body["username"], "player");
+        let (status, _) = send(&app, "GET", "/admin/users/nobody", Some(&root), Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, "GET", "/admin/users/root", Some(&player), Value::Null).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let lock = json!({"reason": "chargeback"});
+        let (status, body) =
+            send(&app, "POST", "/admin/users/player/lock", Some(&root), lock).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["locked"]["reason"], "chargeback");
+        for token in [&player, &pat] {
+            let (status, body) = send(&app, "GET", "/auth/tokens", Some(token), Value::Null).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert_eq!(body["code"], "account_locked");
+        }
+        let creds = json!({"username": "player", "password": "correct-horse"});
+        let (status, _) = send(&app, "POST", "/auth/login", None, creds.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        // a wrong password still reads as invalid credentials
+        let wrong = json!({"username": "player", "password": "wrong-horse"});
+        let (status, _) = send(&app, "POST", "/auth/login", None, wrong).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // admins cannot lock themselves out
+        let lock = json!({"reason": "oops"});
+        let (status, _) = send(&app, "POST", "/admin/users/root/lock", Some(&root), lock).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let uri = "/admin/users/player/lock";
+        let (status, body) = send(&app, "DELETE", uri, Some(&root), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["locked"], Value::Null);
+        let (status, _) = send(&app, "GET", "/auth/tokens", Some(&pat), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, "POST", "/auth/login", None, creds).await;
+        assert_eq!(status, StatusCode::OK);
+    });
+}
+
+#[test]
+fn gdpr_export_and_audit_verification() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = state_at(dir.path()).await;
+        let root_id = state.config.security.admin_users[0].clone();
+        let app = build_app(state);
+        let root = login(&app, "root").await;
+        let (player_id, player) = session(&app, "player").await;
+
+        let uri = format!("/users/{}/data", player_id);
+        let (status, _) = send(&app, "GET", &uri, Some(&player), Value::Null).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = send(&app, "GET", &uri, Some(&root), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["user"]["username"], "player");
+        assert_eq!(body["user"]["sessions"].as_array().map(Vec::len), Some(1));
+        let (status, _) = send(&app, "GET", "/users/nobody/data", Some(&root), Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let uri = "/admin/audit/verify";
+        let (status, body) = send(&app, "GET", uri, Some(&root), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["valid"], true);
+
+        // both reads are in the admin's audit trail
+        let uri = format!("/audit/export/{}", root_id);
+        let (_, body) = send(&app, "GET", &uri, None, Value::Null).await;
+        let actions: Vec<String> = must_opt(body["entries"].as_array(), "missing entries")
+            .iter()
+            .filter_map(|e| e["event"].as_str())
+            .filter_map(|e| serde_json::from_str::<Value>(e).ok())
+            .filter_map(|e| e["action"].as_str().map(str::to_string))
+            .collect();
+        assert_eq!(actions, ["gdpr.export", "audit.verify"]);
+    });
+}
+
+#[test]
+fn backups_are_taken_and_listed_newest_first() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = state_at(dir.path()).await;
+        let app = build_app(state.clone());
+        let root = login(&app, "root").await;
+
+        let (_, body) = send(&app, "GET", "/admin/backups", Some(&root), Value::Null).await;
+        assert_eq!(body["backups"], json!([]));
+
+        let mut names = Vec::new();
+        for _ in 0..2 {
+            let (status, body) =
+                send(&app, "POST", "/admin/backups", Some(&root), Value::Null).await;
+            assert_eq!(status, StatusCode::CREATED, "{}", body);
+            assert!(must_opt(body["size"].as_u64(), "missing size") > 0);
+            names.push(must_opt(body["name"].as_str(), "missing name").to_string());
+        }
+        assert!(state.config.storage.backup_dir().join(&names[0]).is_file());
+
+        let (_, body) = send(&app, "GET", "/admin/backups", Some(&root), Value::Null).await;
+        let listed: Vec<&str> = must_opt(body["backups"].as_array(), "missing backups")
+            .iter()
+            .filter_map(|b| b["name"].as_str())
+            .collect();
+        assert_eq!(listed, [names[1].as_str(), names[0].as_str()]);
+    });
+}
+
+#[test]
+fn revoked_licenses_stop_validating() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let app = build_app(state_at(dir.path()).await);
+        let root = login(&app, "root").await;
+
+        let issue = json!({"tier": "Pro", "days": 30, "subject": "acme"});
+        let (status, body) = send(&app, "POST", "/admin/licenses", Some(&root), issue).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        let token = must_opt(body["token"].as_str(), "missing token").to_string();
+        let id = must_opt(body["license"]["id"].as_str(), "missing id").to_string();
+        let validate = json!({ "token": token });
+        let (_, body) = send(&app, "POST", "/license/validate", None, validate.clone()).await;
+        assert_eq!(body["valid"], true);
+
+        let bad = json!({"tier": "Pro", "days": 0});
+        let (status, _) = send(&app, "POST", "/admin/licenses", Some(&root), bad).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let uri = format!("/admin/licenses/{}/revoke", id);
+        let reason = json!({"reason": "refunded"});
+        let (status, body) = send(&app, "POST", &uri, Some(&root), reason).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["revoke_reason"], "refunded");
+        let (_, body) = send(&app, "POST", "/license/validate", None, validate).await;
+        assert_eq!(body["valid"], false);
+
+        let (_, body) = send(&app, "GET", "/admin/licenses", Some(&root), Value::Null).await;
+        assert_eq!(body["licenses"][0]["subject"], "acme");
+        let uri = "/admin/licenses/unknown/revoke";
+        let (status, _) = send(&app, "POST", uri, Some(&root), json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    });
+}
+
+#[test]
+fn admins_list_and_yank_any_plugin() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = state_at(dir.path()).await;
+        for (id, version) in [
+            ("org.ops.a", "1.0.0"),
+            ("org.ops.a", "1.1.0"),
+            ("org.ops.b", "1.0.0"),
+        ] {
+            let manifest = json!({"id": id, "name": "Ops", "version": version});
+            must(store_manifest(
+                &state.storage,
+                &must(serde_json::from_value(manifest)),
+                None,
+            ));
+        }
+        let app = build_app(state);
+        let root = login(&app, "root").await;
+        let (_, player) = session(&app, "player").await;
+
+        let uri = "/admin/plugins/org.ops.a/1.1.0/yank";
+        let reason = json!({"reason": "broken build"});
+        let (status, _) = send(&app, "POST", uri, Some(&player), reason.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, body) = send(&app, "POST", uri, Some(&root), reason).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["reason"], "broken build");
+        let uri = "/admin/plugins/org.ops.a/9.9.9/yank";
+        let (status, _) = send(&app, "POST", uri, Some(&root), json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_, body) = send(&app, "GET", "/admin/plugins", Some(&root), Value::Null).await;
+        assert_eq!(body["plugins"].as_array().map(Vec::len), Some(3));
+        let uri = "/admin/plugins?id=org.ops.a";
+        let (_, body) = send(&app, "GET", uri, Some(&root), Value::Null).await;
+        let status: Vec<(&str, bool)> = must_opt(body["plugins"].as_array(), "missing plugins")
+            .iter()
+            .filter_map(|p| Some((p["version"].as_str()?, p["yanked"].as_bool()?)))
+            .collect();
+        assert_eq!(status, [("1.0.0", false), ("1.1.0", true)]);
+    });
+}
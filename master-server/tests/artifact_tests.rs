@@ -0,0 +1,258 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use master_server::artifacts::ArtifactRef;
+use master_server::build_app;
+use master_server::state::AppState;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tempfile::tempdir;
+use tower::util::ServiceExt;
+use verseguy_auth::{AccessTokenService, License};
+use verseguy_shared_error::ProblemDetails;
+use verseguy_test_utils::must;
+
+struct Reply {
+    status: StatusCode,
+    headers: axum::http::HeaderMap,
+    body: Vec<u8>,
+}
+
+async fn send(app: &Router, req: Request<Body>) -> Reply {
+    let resp = must(app.clone().oneshot(req).await);
+    let status = resp.status();
+    let headers = resp.headers().clone();
+    let body = must(axum::body::to_bytes(resp.into_body(), 16 * 1024 * 1024).await).to_vec();
+    Reply {
+        status,
+        headers,
+        body,
+    }
+}
+
+fn upload(bytes: Vec<u8>, bearer: Option<&str>) -> Request<Body> {
+    let mut req = Request::builder()
+        .method("POST")
+        .uri("/plugins/artifacts")
+        .header("content-type", "application/octet-stream");
+    if let Some(token) = bearer {
+        req = req.header("authorization", format!("Bearer {}", token));
+    }
+    must(req.body(Body::from(bytes)))
+}
+
+fn publish(version: &str, artifact: Option<&ArtifactRef>) -> Request<Body> {
+    let body = serde_json::json!({"manifest": {
+        "id": "org.test.artifact",
+        "name": "ArtifactTest",
+        "version": version,
+        "author": null,
+        "description": null,
+        "published_at": null,
+        "artifact": artifact,
+    }});
+    must(
+        Request::builder()
+            .method("POST")
+            .uri("/plugins/publish")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string())),
+    )
+}
+
+fn download(version: &str, headers: &[(&str, &str)]) -> Request<Body> {
+    let mut req =
+        Request::builder().uri(format!("/plugins/org.test.artifact/{}/download", version));
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    must(req.body(Body::empty()))
+}
+
+fn header<'a>(reply: &'a Reply, name: &str) -> &'a str {
+    reply
+        .headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    }
+}
+
+#[test]
+fn uploaded_artifacts_are_bound_and_downloadable() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let app = build_app(state.clone());
+
+        let bytes: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+        let reply = send(&app, upload(bytes.clone(), None)).await;
+        assert_eq!(reply.status, StatusCode::CREATED);
+        let artifact: ArtifactRef = must(serde_json::from_slice(&reply.body));
+        assert_eq!(artifact.sha256, hex::encode(Sha256::digest(&bytes)));
+        assert_eq!(artifact.size, 1000);
+
+        // the same content is stored once
+        let reply = send(&app, upload(bytes.clone(), None)).await;
+        assert_eq!(reply.status, StatusCode::CREATED);
+
+        let reply = send(&app, publish("1.0.0", Some(&artifact))).await;
+        assert_eq!(reply.status, StatusCode::CREATED);
+
+        // the signature covers the artifact reference
+        let stored = must(master_server::plugins::get_manifest(
+            &state.storage,
+            "org.test.artifact",
+            "1.0.0",
+        ));
+        let stored = match stored {
+            Some(m) => m,
+            None => panic!("manifest not stored"),
+        };
+        assert_eq!(stored.artifact.as_ref(), Some(&artifact));
+        let trust = must(master_server::trust::trust_store(&state.storage));
+        let mut tampered = stored.clone();
+        tampered.artifact = Some(ArtifactRef {
+            sha256: "0".repeat(64),
+            size: 1000,
+        });
+        assert!(must(master_server::plugins::verify_manifest(
+            &state.storage,
+            &stored,
+            &trust
+        )));
+        assert!(!must(master_server::plugins::verify_manifest(
+            &state.storage,
+            &tampered,
+            &trust
+        )));
+
+        let reply = send(&app, download("1.0.0", &[])).await;
+        assert_eq!(reply.status, StatusCode::OK);
+        assert_eq!(reply.body, bytes);
+        assert_eq!(header(&reply, "accept-ranges"), "bytes");
+        let etag = format!("\"{}\"", artifact.sha256);
+        assert_eq!(header(&reply, "etag"), etag);
+
+        let reply = send(&app, download("1.0.0", &[("range", "bytes=10-19")])).await;
+        assert_eq!(reply.status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(reply.body, bytes[10..20]);
+        assert_eq!(header(&reply, "content-range"), "bytes 10-19/1000");
+
+        let reply = send(&app, download("1.0.0", &[("range", "bytes=-100")])).await;
+        assert_eq!(reply.status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(reply.body, bytes[900..]);
+
+        let reply = send(&app, download("1.0.0", &[("range", "bytes=1000-")])).await;
+        assert_eq!(reply.status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(header(&reply, "content-range"), "bytes */1000");
+
+        // a stale If-Range gets the whole artifact
+        let stale = [("range", "bytes=0-9"), ("if-range", "\"other\"")];
+        let reply = send(&app, download("1.0.0", &stale)).await;
+        assert_eq!(reply.status, StatusCode::OK);
+        assert_eq!(reply.body.len(), 1000);
+
+        let reply = send(&app, download("1.0.0", &[("if-none-match", etag.as_str())])).await;
+        assert_eq!(reply.status, StatusCode::NOT_MODIFIED);
+    });
+}
+
+#[test]
+fn manifests_must_reference_uploaded_artifacts() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let app = build_app(state);
+
+        let missing = ArtifactRef {
+            sha256: hex::encode(Sha256::digest(b"never uploaded")),
+            size: 14,
+        };
+        let reply = send(&app, publish("1.0.0", Some(&missing))).await;
+        assert_eq!(reply.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let reply = send(&app, upload(b"plugin".to_vec(), None)).await;
+        let mut artifact: ArtifactRef = must(serde_json::from_slice(&reply.body));
+        artifact.size += 1;
+        let reply = send(&app, publish("1.0.0", Some(&artifact))).await;
+        assert_eq!(reply.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        // versions without an artifact have nothing to download
+        let reply = send(&app, publish("2.0.0", None)).await;
+        assert_eq!(reply.status, StatusCode::CREATED);
+        let reply = send(&app, download("2.0.0", &[])).await;
+        assert_eq!(reply.status, StatusCode::NOT_FOUND);
+    });
+}
+
+#[test]
+fn revoked_versions_are_not_served() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let app = build_app(state.clone());
+
+        let reply = send(&app, upload(b"plugin".to_vec(), None)).await;
+        let artifact: ArtifactRef = must(serde_json::from_slice(&reply.body));
+        let reply = send(&app, publish("1.0.0", Some(&artifact))).await;
+        assert_eq!(reply.status, StatusCode::CREATED);
+        must(master_server::plugins::revoke_manifest(
+            &state.storage,
+            "org.test.artifact",
+            "1.0.0",
+            "compromised",
+        ));
+
+        let reply = send(&app, download("1.0.0", &[])).await;
+        assert_eq!(reply.status, StatusCode::GONE);
+        let problem: ProblemDetails = must(serde_json::from_slice(&reply.body));
+        assert_eq!(problem.code, "plugin_revoked");
+    });
+}
+
+#[test]
+fn upload_size_is_limited_by_license_tier() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let mut state = must(AppState::new(dir.path(), vec![0u8; 32]));
+        state.config.plugins.max_artifact_free = 16;
+        state.config.plugins.max_artifact_pro = 64;
+        let tokens = AccessTokenService::new((*state.storage).clone());
+        let account = must(tokens.create_service_account("pro-publisher", License::Pro));
+        let (pro, _) = must(tokens.create(&account.id, "ci", vec!["*".into()], None));
+        let app = build_app(Arc::new(state));
+
+        let reply = send(&app, upload(vec![7u8; 32], None)).await;
+        assert_eq!(reply.status, StatusCode::PAYLOAD_TOO_LARGE);
+        let problem: ProblemDetails = must(serde_json::from_slice(&reply.body));
+        assert_eq!(problem.code, "artifact_too_large");
+
+        let reply = send(&app, upload(vec![7u8; 32], Some(&pro))).await;
+        assert_eq!(reply.status, StatusCode::CREATED);
+        let reply = send(&app, upload(vec![7u8; 65], Some(&pro))).await;
+        assert_eq!(reply.status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        // chunked uploads without a Content-Length are cut off while streaming
+        let chunks =
+            futures_util::stream::iter((0..4).map(|_| Ok::<_, std::io::Error>(vec![1u8; 8])));
+        let req = must(
+            Request::builder()
+                .method("POST")
+                .uri("/plugins/artifacts")
+                .body(Body::from_stream(chunks)),
+        );
+        let reply = send(&app, req).await;
+        assert_eq!(reply.status, StatusCode::PAYLOAD_TOO_LARGE);
+    });
+}
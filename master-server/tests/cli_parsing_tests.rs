@@ -1,6 +1,9 @@
-use clap::Parser;
-use master_server::admin_cli::{Cli, Commands};
-use verseguy_test_utils::must_opt;
+use clap::{CommandFactory, Parser};
+use master_server::admin_cli::{
+    render_table, Cli, Commands, GdprCommands, LegalCommands, LicenseCommands, Output, UserCommands,
+};
+use serde_json::json;
+use verseguy_test_utils::{must, must_opt};
 
 #[test]
 fn parse_key_import_b64() {
@@ -20,3 +23,165 @@ fn parse_key_import_b64() {
         _ => panic!("unexpected cmd"),
     }
 }
+
+#[test]
+fn parse_login_credentials() {
+    let args = vec![
+        "verseguy-admin",
+        "--username",
+        "root",
+        "--password-stdin",
+        "key-list",
+    ];
+    let cli = Cli::parse_from(args);
+    assert_eq!(cli.username.as_deref(), Some("root"));
+    assert!(cli.password_stdin);
+    assert!(matches!(cli.cmd, Commands::KeyList));
+
+    // a token and a login exclude each other
+    let args = [
+        "verseguy-admin",
+        "--token",
+        "t",
+        "--username",
+        "root",
+        "key-list",
+    ];
+    assert!(Cli::try_parse_from(args).is_err());
+}
+
+#[test]
+fn parse_operations_subcommands() {
+    Cli::command().debug_assert();
+
+    let cli = Cli::parse_from([
+        "verseguy-admin",
+        "user",
+        "lock",
+        "player",
+        "--reason",
+        "chargeback",
+        "-o",
+        "json",
+    ]);
+    assert_eq!(cli.output, Output::Json);
+    match cli.cmd {
+        Commands::User(UserCommands::Lock { user, reason }) => {
+            assert_eq!((user.as_str(), reason.as_str()), ("player", "chargeback"))
+        }
+        other => panic!("unexpected cmd {:?}", other),
+    }
+
+    let cli = Cli::parse_from(["verseguy-admin", "gdpr", "delete", "u1", "--yes"]);
+    assert_eq!(cli.output, Output::Table);
+    assert!(matches!(
+        cli.cmd,
+        Commands::Gdpr(GdprCommands::Delete { yes: true, .. })
+    ));
+
+    let args = [
+        "verseguy-admin",
+        "license",
+        "issue",
+        "--tier",
+        "Pro",
+        "--days",
+        "30",
+    ];
+    assert!(matches!(
+        Cli::parse_from(args).cmd,
+        Commands::License(LicenseCommands::Issue { days: Some(30), .. })
+    ));
+    // tiers are checked before anything is sent
+    let args = ["verseguy-admin", "license", "issue", "--tier", "Gold"];
+    assert!(Cli::try_parse_from(args).is_err());
+}
+
+#[test]
+fn parse_legal_subcommands() {
+    let create = |at: &'static str| {
+        Cli::try_parse_from([
+            "verseguy-admin",
+            "legal",
+            "create",
+            "--doc-type",
+            "tos",
+            "--version",
+            "2.0",
+            "--title",
+            "Terms",
+            "--file",
+            "tos.md",
+            "--effective-at",
+            at,
+        ])
+    };
+    match must(create("2026-01-01T00:00:00Z")).cmd {
+        Commands::Legal(LegalCommands::Create { effective_at, .. }) => {
+            assert_eq!(effective_at.map(|t| t.timestamp()), Some(1_767_225_600))
+        }
+        other => panic!("unexpected cmd {:?}", other),
+    }
+    assert!(create("next tuesday").is_err());
+
+    let args = [
+        "verseguy-admin",
+        "legal",
+        "translate",
+        "doc-1",
+        "--locale",
+        "de",
+        "--title",
+        "Nutzungsbedingungen",
+        "--file",
+        "tos.de.md",
+    ];
+    assert!(matches!(
+        Cli::parse_from(args).cmd,
+        Commands::Legal(LegalCommands::Translate { ref locale, .. }) if locale == "de"
+    ));
+
+    let cli = Cli::parse_from(["verseguy-admin", "legal", "diff", "tos", "1.0", "2.0"]);
+    assert!(matches!(
+        cli.cmd,
+        Commands::Legal(LegalCommands::Diff { ref from, .. }) if from == "1.0"
+    ));
+}
+
+#[test]
+fn completions_cover_the_subcommands() {
+    let mut out = Vec::new();
+    clap_complete::generate(
+        clap_complete::Shell::Bash,
+        &mut Cli::command(),
+        "verseguy-admin",
+        &mut out,
+    );
+    let script = String::from_utf8_lossy(&out);
+    for word in ["legal", "backup", "license", "unlock"] {
+        assert!(script.contains(word), "missing {}", word);
+    }
+}
+
+#[test]
+fn tables_have_a_row_per_item() {
+    let body = json!({"backups": [
+        {"name": "backup_2", "size": 2048},
+        {"name": "backup_1", "size": 10, "note": "line one\nline two"},
+    ]});
+    assert_eq!(
+        render_table(&body),
+        "NAME      SIZE  NOTE\n\
+         backup_2  2048\n\
+         backup_1  10    line one line two\n"
+    );
+    assert_eq!(render_table(&json!({"plugins": []})), "(none)\n");
+
+    let body = json!({"user": {"id": "u1"}, "valid": true});
+    assert_eq!(
+        render_table(&body),
+        "FIELD  VALUE\n\
+         user   {\"id\":\"u1\"}\n\
+         valid  true\n"
+    );
+}
@@ -0,0 +1,96 @@
+#![allow(clippy::disallowed_methods)]
+use master_server::config::Config;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use verseguy_ratelimit::Limit;
+use verseguy_test_utils::{must, must_opt};
+
+const FILE: &str = r#"
+[server]
+bind = "0.0.0.0:8443"
+shutdown_timeout_secs = 5
+
+[server.tls]
+cert = "/etc/verseguy/cert.pem"
+key = "/etc/verseguy/key.pem"
+
+[security]
+license_secret = "from-file"
+admin_users = ["file-admin"]
+
+[rate_limits]
+auth = "5/min"
+"#;
+
+#[test]
+fn file_values_override_defaults() {
+    let config = must(Config::from_toml(FILE));
+    assert_eq!(config.server.bind.to_string(), "0.0.0.0:8443");
+    assert_eq!(config.server.shutdown_timeout_secs, 5);
+    let tls = must_opt(config.server.tls, "tls missing");
+    assert_eq!(tls.cert, Some(PathBuf::from("/etc/verseguy/cert.pem")));
+    assert!(!tls.self_signed);
+    assert_eq!(config.security.license_secret, "from-file");
+    assert_eq!(config.rate_limits.auth, Limit::per_minute(5));
+    // unset sections keep their defaults
+    assert_eq!(config.storage.db_path, PathBuf::from("./master_server_db"));
+    assert_eq!(
+        config.storage.backup_dir(),
+        PathBuf::from("./master_server_db-backups")
+    );
+    assert_eq!(
+        config.rate_limits.tokens,
+        Config::default().rate_limits.tokens
+    );
+}
+
+#[test]
+fn env_overrides_the_file() {
+    let mut config = must(Config::from_toml(FILE));
+    let env: HashMap<&str, &str> = [
+        ("MASTER_SERVER_PORT", "9000"),
+        ("MASTER_ADMIN_USERS", "alice, bob"),
+        ("MASTER_DB_PATH", "/tmp/master"),
+        ("MASTER_RATE_LIMIT_AUTH", "7/h"),
+        ("MASTER_PLUGIN_PUBLISH_KEY", ""),
+        ("MASTER_TRUSTED_KEYS", "a2V5MQ==, a2V5Mg=="),
+        ("MASTER_TRUST_PROXY", "2"),
+    ]
+    .into_iter()
+    .collect();
+    must(config.apply_env(|name| env.get(name).map(|v| v.to_string())));
+
+    assert_eq!(config.server.bind.to_string(), "0.0.0.0:9000");
+    assert_eq!(config.security.admin_users, ["alice", "bob"]);
+    assert_eq!(config.security.license_secret, "from-file");
+    assert_eq!(config.storage.db_path, PathBuf::from("/tmp/master"));
+    assert_eq!(
+        config.storage.backup_dir(),
+        PathBuf::from("/tmp/master-backups")
+    );
+    assert_eq!(config.rate_limits.auth, Limit::per_hour(7));
+    assert_eq!(config.security.trusted_keys, ["a2V5MQ==", "a2V5Mg=="]);
+    assert_eq!(config.rate_limits.trusted_proxies, 2);
+    // empty variables count as unset
+    assert_eq!(config.security.plugin_publish_key, None);
+}
+
+#[test]
+fn invalid_settings_are_rejected() {
+    assert!(Config::from_toml("[server]\nport = 3000\n").is_err());
+    assert!(Config::from_toml("[rate_limits]\nauth = \"lots\"\n").is_err());
+    let mut config = Config::default();
+    assert!(config
+        .apply_env(|name| (name == "MASTER_BIND").then(|| "nowhere".to_string()))
+        .is_err());
+    assert!(config
+        .apply_env(|name| (name == "MASTER_TRUST_PROXY").then(|| "yes".to_string()))
+        .is_err());
+    // `trust_forwarded_for` was replaced by the number of proxies
+    assert!(Config::from_toml("[rate_limits]\ntrust_forwarded_for = true\n").is_err());
+    // the shared admin token was replaced by admin accounts
+    assert!(Config::from_toml("[security]\nadmin_token = \"x\"\n").is_err());
+    assert!(config
+        .apply_env(|name| (name == "MASTER_ADMIN_TOKEN").then(|| "x".to_string()))
+        .is_err());
+}
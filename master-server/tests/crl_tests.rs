@@ -0,0 +1,216 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use master_server::artifacts::ArtifactRef;
+use master_server::build_app;
+use master_server::config::Config;
+use master_server::plugins::{get_manifest, store_manifest};
+use master_server::state::AppState;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::tempdir;
+use tower::util::ServiceExt;
+use verseguy_auth::local::LocalAuth;
+use verseguy_signing::crl::{CrlError, RevocationCache, RevocationList};
+use verseguy_signing::plugin::{verify_plugin, PluginError};
+use verseguy_signing::{Envelope, TrustStore};
+use verseguy_test_utils::{must, must_opt};
+
+async fn send(app: &Router, req: Request<Body>) -> (StatusCode, Value) {
+    let resp = must(app.clone().oneshot(req).await);
+    let status = resp.status();
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+async fn post(app: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
+    post_as(app, None, uri, body).await
+}
+
+async fn post_as(app: &Router, token: Option<&str>, uri: &str, body: Value) -> (StatusCode, Value) {
+    let mut req = Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(token) = token {
+        req = req.header("authorization", format!("Bearer {}", token));
+    }
+    send(app, must(req.body(Body::from(body.to_string())))).await
+}
+
+/// Session token of `admin`, registered by [`state_at`] as an administrator
+async fn admin_session(app: &Router) -> String {
+    let creds = json!({"username": "admin", "password": "adm1n-pass"});
+    let (status, body) = post(app, "/auth/login", creds).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    must_opt(body["token"].as_str(), "missing token").to_string()
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, Value) {
+    send(app, must(Request::builder().uri(uri).body(Body::empty()))).await
+}
+
+async fn revoke(app: &Router, admin: &str, id: &str, version: &str) {
+    let body = json!({"id": id, "version": version, "reason": "malware"});
+    let (status, body) = post_as(app, Some(admin), "/verify/revoke", body).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+async fn crl(app: &Router, since: u64) -> RevocationList {
+    let (status, body) = get(app, &format!("/verify/crl?since={}", since)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    must(serde_json::from_value(body))
+}
+
+/// What a launcher keeps: the registry's trusted keys, fetched once
+async fn trust(app: &Router) -> TrustStore {
+    let (_, body) = get(app, "/plugins/trust").await;
+    let mut trust = TrustStore::new();
+    for key in must_opt(body["keys"].as_array(), "missing keys") {
+        must(trust.add_b64(must_opt(key["public_key"].as_str(), "missing public_key")));
+    }
+    trust
+}
+
+/// State whose administrator, through `security.admin_users`, is the local account `admin`
+async fn state_at(dir: &std::path::Path) -> Arc<AppState> {
+    let mut config = Config::default();
+    config.storage.db_path = dir.join("db");
+    config.security.key_file = Some(dir.join("master.key"));
+    let mut state = must(AppState::from_config(config));
+    let admin = must(
+        LocalAuth::new((*state.storage).clone())
+            .register("admin".into(), "adm1n-pass".into())
+            .await,
+    );
+    state.config.security.admin_users = vec![admin.id];
+    Arc::new(state)
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    }
+}
+
+#[test]
+fn signed_deltas_let_clients_refuse_revoked_plugins_offline() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = state_at(dir.path()).await;
+        let app = build_app(state.clone());
+        let trust = trust(&app).await;
+        let admin = admin_session(&app).await;
+
+        let bytes = vec![3u8; 256];
+        let upload = Request::builder()
+            .method("POST")
+            .uri("/plugins/artifacts")
+            .header("content-type", "application/octet-stream")
+            .body(Body::from(bytes.clone()));
+        let (status, body) = send(&app, must(upload)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let artifact: ArtifactRef = must(serde_json::from_value(body));
+        for (version, artifact) in [("1.0.0", Some(&artifact)), ("1.1.0", None)] {
+            let manifest = json!({
+                "id": "org.crl.radar", "name": "Radar", "version": version, "artifact": artifact,
+            });
+            let (status, body) =
+                post(&app, "/plugins/publish", json!({ "manifest": manifest })).await;
+            assert_eq!(status, StatusCode::CREATED, "{}", body);
+        }
+
+        let mut cache = RevocationCache::new();
+        let empty = crl(&app, 0).await;
+        assert_eq!((empty.version, empty.entries.len()), (0, 0));
+        assert_eq!(cache.apply(&empty, &trust), Ok(()));
+
+        let manifest = must_opt(
+            must(get_manifest(&state.storage, "org.crl.radar", "1.0.0")),
+            "missing manifest",
+        );
+        let manifest = must(serde_json::to_value(manifest));
+        let (_, envelope) = get(&app, "/plugins/org.crl.radar/1.0.0/signature").await;
+        let envelope: Envelope = must(serde_json::from_value(envelope));
+        let verified = verify_plugin(&manifest, &envelope, &bytes, &trust, &cache);
+        assert_eq!(verified.map(|v| v.sha256), Ok(artifact.sha256.clone()));
+
+        revoke(&app, &admin, "org.crl.radar", "1.0.0").await;
+        let full = crl(&app, 0).await;
+        assert_eq!(full.version, 1);
+        assert_eq!(
+            full.entries[0].artifact_sha256.as_deref(),
+            Some(artifact.sha256.as_str())
+        );
+        assert_eq!(cache.apply(&full, &trust), Ok(()));
+        assert!(matches!(
+            verify_plugin(&manifest, &envelope, &bytes, &trust, &cache),
+            Err(PluginError::Revoked { .. })
+        ));
+
+        // revoking again keeps the number; the next version gets the next one
+        revoke(&app, &admin, "org.crl.radar", "1.0.0").await;
+        revoke(&app, &admin, "org.crl.radar", "1.1.0").await;
+        let delta = crl(&app, 1).await;
+        assert_eq!((delta.since, delta.version), (1, 2));
+        assert_eq!(delta.entries.len(), 1);
+        assert_eq!(cache.apply(&delta, &trust), Ok(()));
+        assert!(cache.is_revoked("org.crl.radar", "1.1.0"));
+
+        // a replayed older list cannot undo a revocation
+        assert_eq!(
+            cache.apply(&full, &trust),
+            Err(CrlError::Rollback {
+                received: 1,
+                cached: 2
+            })
+        );
+        let (status, body) = get(&app, "/verify/crl?since=5").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "crl_version_ahead");
+    });
+}
+
+#[test]
+fn revocations_from_before_the_list_are_numbered_on_start() {
+    let dir = must(tempdir());
+    {
+        let state = state_at(dir.path()).await;
+        for version in ["1.0.0", "2.0.0"] {
+            let manifest = json!({"id": "org.crl.old", "name": "Old", "version": version});
+            must(store_manifest(
+                &state.storage,
+                &must(serde_json::from_value(manifest)),
+                None,
+            ));
+        }
+        // the record revocations had before they were numbered
+        let at = json!({"reason": "compromised", "at": 1_600_000_000});
+        must(state.storage.put(b"plugin_revoked:org.crl.old:2.0.0", &at));
+    }
+
+    runtime().block_on(async {
+        let app = build_app(state_at(dir.path()).await);
+        let list = crl(&app, 0).await;
+        assert_eq!(list.version, 1);
+        assert_eq!(
+            (list.entries[0].version.as_str(), list.entries[0].revoked_at),
+            ("2.0.0", 1_600_000_000)
+        );
+        assert_eq!(list.verify(&trust(&app).await), Ok(()));
+    });
+
+    // and only once
+    runtime().block_on(async {
+        let app = build_app(state_at(dir.path()).await);
+        assert_eq!(crl(&app, 0).await.version, 1);
+    });
+}
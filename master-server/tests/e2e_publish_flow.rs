@@ -86,9 +86,9 @@ fn register_login_publish_verify() {
     let manifest: master_server::plugins::PluginManifest =
         must(serde_json::from_value(manifest_json.clone()));
 
-    // verify signature using server public key
-    let pubkey = must_opt(state.keypair.as_ref(), "missing keypair").public;
-    let ok = must(verify_manifest(&state.storage, &manifest, &pubkey));
+    // verify signature using the server's trusted keys
+    let trust = must(master_server::trust::trust_store(&state.storage));
+    let ok = must(verify_manifest(&state.storage, &manifest, &trust));
     assert!(ok);
     });
 }
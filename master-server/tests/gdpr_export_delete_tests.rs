@@ -48,13 +48,32 @@ fn audit_export_and_delete_user_data() {
         assert_eq!(entries.len(), 2);
 
         // Add ToS acceptance
-        let tos_body =
-            r#"{"user_id":"user-42","accepted_at": 1234567890, "version": "1.0.0"}"#.to_string();
+        must(master_server::legal::create_document(
+            &state.storage,
+            master_server::legal::AdminCreateLegalRequest {
+                doc_type: "tos".into(),
+                version: "1.0.0".into(),
+                title: "Terms of Service".into(),
+                content: "These are the terms".into(),
+                ..Default::default()
+            },
+        ));
+        let user_session = must(
+            verseguy_auth::SessionService::new(state.license_secret.clone())
+                .create_and_store_session(
+                    "user-42",
+                    &verseguy_auth::License::Free,
+                    1,
+                    &state.storage,
+                ),
+        );
+        let tos_body = r#"{"accepted_at": 1234567890, "version": "1.0.0"}"#.to_string();
         let req2 = must(
             Request::builder()
                 .method("POST")
                 .uri("/auth/tos")
                 .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {}", user_session))
                 .body(Body::from(tos_body)),
         );
         let resp2 = must(app.clone().oneshot(req2).await);
@@ -187,6 +206,7 @@ fn audit_export_and_delete_user_data() {
             Request::builder()
                 .method("GET")
                 .uri("/auth/tos/user-42")
+                .header("authorization", format!("Bearer {}", user_session))
                 .body(Body::empty()),
         );
         let resp5 = must(app.oneshot(req5).await);
@@ -29,6 +29,14 @@ fn isolate_store_manifest() {
             author: Some("Tester".to_string()),
             description: Some("Isolate store_manifest test".to_string()),
             published_at: None,
+            artifact: None,
+            core_version_min: None,
+            sdk_version: None,
+            dependencies: Vec::new(),
+            license_required: None,
+            category: None,
+            tags: Vec::new(),
+            capabilities: Vec::new(),
         };
 
         // Call store_manifest synchronously
@@ -0,0 +1,214 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use master_server::build_app;
+use master_server::config::Config;
+use master_server::legal::{parse_accept_language, render_markdown};
+use master_server::state::AppState;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::tempdir;
+use tower::util::ServiceExt;
+use verseguy_auth::local::LocalAuth;
+use verseguy_test_utils::{must, must_opt};
+
+async fn send(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    token: Option<&str>,
+    body: Value,
+) -> (StatusCode, Value) {
+    let mut req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(token) = token {
+        req = req.header("authorization", format!("Bearer {}", token));
+    }
+    let resp = must(
+        app.clone()
+            .oneshot(must(req.body(Body::from(body.to_string()))))
+            .await,
+    );
+    let status = resp.status();
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+/// `GET uri` with `Accept-Language: accept`; returns `Content-Language` and the `doc`
+async fn negotiate(app: &Router, uri: &str, accept: &str) -> (String, Value) {
+    let req = Request::builder()
+        .uri(uri)
+        .header("accept-language", accept)
+        .body(Body::empty());
+    let resp = must(app.clone().oneshot(must(req)).await);
+    assert_eq!(resp.status(), StatusCode::OK);
+    let language = must_opt(
+        resp.headers()
+            .get("content-language")
+            .and_then(|v| v.to_str().ok()),
+        "missing content-language",
+    )
+    .to_string();
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    let body: Value = must(serde_json::from_slice(&bytes));
+    (language, body["doc"].clone())
+}
+
+/// Register `username` and log in; returns the user id and session token
+async fn session(app: &Router, username: &str) -> (String, String) {
+    let creds = json!({"username": username, "password": "correct-horse"});
+    let (status, body) = send(app, "POST", "/auth/register", None, creds).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let id = must_opt(body["id"].as_str(), "missing id").to_string();
+    (id, login(app, username).await)
+}
+
+/// Session token of the already registered `username`
+async fn login(app: &Router, username: &str) -> String {
+    let creds = json!({"username": username, "password": "correct-horse"});
+    let (_, body) = send(app, "POST", "/auth/login", None, creds).await;
+    must_opt(body["token"].as_str(), "missing token").to_string()
+}
+
+/// Publish ToS 1.0 in English with a German translation; returns the document id
+async fn publish(app: &Router, root: &str) -> String {
+    let doc = json!({
+        "doc_type": "tos",
+        "version": "1.0",
+        "title": "Terms of Service",
+        "content": "# Terms\n\nBe **nice**. <script>alert(1)</script>\n",
+        "translations": [{
+            "locale": "de",
+            "title": "Nutzungsbedingungen",
+            "content": "# Bedingungen\n\nSei **nett**.\n",
+        }],
+    });
+    let (status, body) = send(app, "POST", "/admin/legal", Some(root), doc).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    must_opt(body["id"].as_str(), "missing id").to_string()
+}
+
+/// State whose administrator, through `security.admin_users`, is the local account `root`
+async fn state_at(dir: &std::path::Path) -> Arc<AppState> {
+    let mut config = Config::default();
+    config.storage.db_path = dir.join("db");
+    let mut state = must(AppState::from_config(config));
+    let root = must(
+        LocalAuth::new((*state.storage).clone())
+            .register("root".into(), "correct-horse".into())
+            .await,
+    );
+    state.config.security.admin_users = vec![root.id];
+    Arc::new(state)
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    }
+}
+
+#[test]
+fn accept_language_is_parsed_by_weight() {
+    let tags = parse_accept_language("en;q=0.5, de-AT, de;q=0.9, fr;q=0, *;q=0.1");
+    assert_eq!(tags, ["de-AT", "de", "en", "*"]);
+    assert!(parse_accept_language("").is_empty());
+
+    let html = render_markdown("# Terms\n\n<b>raw</b> and **bold**\n");
+    assert!(html.contains("<h1>Terms</h1>"), "{}", html);
+    assert!(html.contains("&lt;b&gt;raw&lt;/b&gt;"), "{}", html);
+    assert!(html.contains("<strong>bold</strong>"), "{}", html);
+}
+
+#[test]
+fn documents_are_served_in_the_best_matching_locale() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let app = build_app(state_at(dir.path()).await);
+        let root = login(&app, "root").await;
+        let id = publish(&app, &root).await;
+
+        let (language, doc) = negotiate(&app, "/legal/latest/tos", "de-AT,en;q=0.8").await;
+        assert_eq!(language, "de");
+        assert_eq!(doc["title"], "Nutzungsbedingungen");
+        assert_eq!(doc["locales"], json!(["en", "de"]));
+        let html = must_opt(doc["html"].as_str(), "missing html");
+        assert!(html.contains("<strong>nett</strong>"), "{}", html);
+
+        // unknown locales fall back to the document's own; `?locale=` goes first
+        let (language, doc) = negotiate(&app, "/legal/latest/tos", "fr").await;
+        assert_eq!(
+            (language.as_str(), &doc["title"]),
+            ("en", &json!("Terms of Service"))
+        );
+        let html = must_opt(doc["html"].as_str(), "missing html");
+        assert!(!html.contains("<script>"), "{}", html);
+        let (language, _) = negotiate(&app, "/legal/tos/1.0?locale=en", "de").await;
+        assert_eq!(language, "en");
+
+        // translations can follow later, once per locale
+        let uri = format!("/admin/legal/{}/translations", id);
+        let de_ch = json!({"locale": "de-CH", "title": "Nutzungsbedingungen", "content": "Sali"});
+        let (status, _) = send(&app, "POST", &uri, Some(&root), de_ch.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, "POST", &uri, Some(&root), de_ch).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let bad = json!({"locale": "de_CH", "title": "x", "content": "x"});
+        let (status, _) = send(&app, "POST", &uri, Some(&root), bad).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (language, doc) = negotiate(&app, "/legal/tos/1.0", "de-ch").await;
+        assert_eq!(
+            (language.as_str(), &doc["content"]),
+            ("de-CH", &json!("Sali"))
+        );
+        let (language, _) = negotiate(&app, "/legal/tos/1.0", "de-LI").await;
+        assert_eq!(language, "de");
+    });
+}
+
+#[test]
+fn acceptances_record_the_locale_and_text_seen() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let app = build_app(state_at(dir.path()).await);
+        let root = login(&app, "root").await;
+        let (player_id, player) = session(&app, "player").await;
+        publish(&app, &root).await;
+        let (_, german) = negotiate(&app, "/legal/latest/tos", "de").await;
+
+        let accept = json!({
+            "version": "1.0",
+            "locale": "de",
+            "content_hash": german["content_hash"],
+        });
+        let (status, body) = send(&app, "POST", "/auth/tos", Some(&player), accept).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["user_id"].as_str(), Some(player_id.as_str()));
+        assert_eq!(body["locale"], "de");
+        assert_eq!(body["content_hash"], german["content_hash"]);
+
+        // the hash has to be that of the locale's text
+        let accept = json!({"version": "1.0", "content_hash": german["content_hash"]});
+        let (status, _) = send(&app, "POST", "/auth/tos", Some(&player), accept).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let accept = json!({"version": "1.0", "locale": "fr"});
+        let (status, _) = send(&app, "POST", "/auth/tos", Some(&player), accept).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let accept = json!({"version": "1.0"});
+        let (_, body) = send(&app, "POST", "/auth/tos", Some(&player), accept).await;
+        assert_eq!(body["locale"], "en");
+        assert_ne!(body["content_hash"], german["content_hash"]);
+    });
+}
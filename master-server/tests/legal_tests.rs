@@ -1,6 +1,8 @@
 use master_server::state::AppState;
 use std::sync::Arc;
 use tempfile::tempdir;
+use verseguy_auth::local::LocalAuth;
+use verseguy_auth::SessionService;
 use verseguy_test_utils::must;
 
 #[test]
@@ -16,10 +18,23 @@ fn create_and_get_latest_legal_doc() {
 
     rt.block_on(async {
         let dir = must(tempdir());
-    let state = Arc::new(must(AppState::new(dir.path(), b"test-secret".to_vec())));
-
-    // set admin token so handler allows operation
-    std::env::set_var("MASTER_ADMIN_TOKEN", "admintoken");
+    let mut state = must(AppState::new(dir.path(), b"test-secret".to_vec()));
+    let admin = must(
+        LocalAuth::new((*state.storage).clone())
+            .register("admin".into(), "adm1n-pass".into())
+            .await,
+    );
+    // make `admin` an administrator so the handler allows the operation
+    state.config.security.admin_users = vec![admin.id.clone()];
+    let state = Arc::new(state);
+    let token = must(
+        SessionService::new(state.license_secret.clone()).create_and_store_session(
+            &admin.id,
+            &admin.license,
+            1,
+            &(*state.storage).clone(),
+        ),
+    );
 
     // Use raw JSON string to avoid needing Serialize on the request type
     let req_json = r#"{"doc_type":"tos","version":"1.0.0","title":"Terms of Service","content":"These are the terms","author":"Legal Team"}"#;
@@ -28,7 +43,7 @@ fn create_and_get_latest_legal_doc() {
             .method(axum::http::Method::POST)
             .uri("/admin/legal/create")
             .header("content-type", "application/json")
-            .header("x-admin-token", "admintoken")
+            .header("authorization", format!("Bearer {}", token))
             .body(axum::body::Body::from(req_json)),
     );
 
@@ -39,21 +54,23 @@ fn create_and_get_latest_legal_doc() {
     .await
     {
         Ok(_) => {}
-        Err((status, msg)) => panic!("create failed: {} {}", status, msg),
+        Err(e) => panic!("create failed: {}", e),
     }
 
     // check latest
     let latest_res = match master_server::legal::get_latest_legal_handler(
         axum::extract::State(state.clone()),
+        axum::http::HeaderMap::new(),
         axum::extract::Path("tos".to_string()),
+        axum::extract::Query(Default::default()),
     )
     .await
     {
         Ok(r) => r,
-        Err((status, msg)) => panic!("get latest failed: {} {}", status, msg),
+        Err(e) => panic!("get latest failed: {}", e),
     };
 
-    let axum::Json(val) = latest_res;
+    let (_, axum::Json(val)) = latest_res;
     let s = must(serde_json::to_string(&val));
     assert!(s.contains("Terms of Service"));
     });
@@ -0,0 +1,239 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use master_server::build_app;
+use master_server::config::Config;
+use master_server::state::AppState;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::tempdir;
+use tower::util::ServiceExt;
+use verseguy_auth::local::LocalAuth;
+use verseguy_test_utils::{must, must_opt};
+
+async fn send(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    token: Option<&str>,
+    body: Value,
+) -> (StatusCode, Value) {
+    let mut req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(token) = token {
+        req = req.header("authorization", format!("Bearer {}", token));
+    }
+    let resp = must(
+        app.clone()
+            .oneshot(must(req.body(Body::from(body.to_string()))))
+            .await,
+    );
+    let status = resp.status();
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+/// Register `username` and log in; returns the user id and session token
+async fn session(app: &Router, username: &str) -> (String, String) {
+    let creds = json!({"username": username, "password": "correct-horse"});
+    let (status, body) = send(app, "POST", "/auth/register", None, creds).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let id = must_opt(body["id"].as_str(), "missing id").to_string();
+    (id, login(app, username).await)
+}
+
+/// Session token of the already registered `username`
+async fn login(app: &Router, username: &str) -> String {
+    let creds = json!({"username": username, "password": "correct-horse"});
+    let (_, body) = send(app, "POST", "/auth/login", None, creds).await;
+    must_opt(body["token"].as_str(), "missing token").to_string()
+}
+
+/// Publish a ToS version taking effect `offset` seconds from now; returns the document
+async fn publish(app: &Router, root: &str, version: &str, content: &str, offset: i64) -> Value {
+    let doc = json!({
+        "doc_type": "tos",
+        "version": version,
+        "title": "Terms of Service",
+        "content": content,
+        "effective_at": chrono::Utc::now().timestamp() + offset,
+    });
+    let (status, body) = send(app, "POST", "/admin/legal", Some(root), doc).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body["doc"].clone()
+}
+
+/// Accept ToS `version` as the user holding `token`
+async fn accept(app: &Router, token: &str, version: &str) -> StatusCode {
+    let body = json!({"version": version});
+    send(app, "POST", "/auth/tos", Some(token), body).await.0
+}
+
+/// State whose administrator, through `security.admin_users`, is the local account `root`
+async fn state_at(dir: &std::path::Path) -> Arc<AppState> {
+    let mut config = Config::default();
+    config.storage.db_path = dir.join("db");
+    let mut state = must(AppState::from_config(config));
+    let root = must(
+        LocalAuth::new((*state.storage).clone())
+            .register("root".into(), "correct-horse".into())
+            .await,
+    );
+    state.config.security.admin_users = vec![root.id];
+    Arc::new(state)
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    }
+}
+
+#[test]
+fn newly_effective_tos_versions_must_be_accepted() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let app = build_app(state_at(dir.path()).await);
+        let root = login(&app, "root").await;
+        let (player_id, player) = session(&app, "player").await;
+
+        // without a published ToS nothing is blocked
+        let (status, _) = send(&app, "GET", "/auth/tokens", Some(&player), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+
+        publish(&app, &root, "1.0", "terms", -100).await;
+        let (status, body) = send(&app, "GET", "/auth/tokens", Some(&player), Value::Null).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "tos_acceptance_required");
+        assert_eq!(accept(&app, &player, "9.9").await, StatusCode::BAD_REQUEST);
+        assert_eq!(accept(&app, &player, "1.0").await, StatusCode::OK);
+        let (status, _) = send(&app, "GET", "/auth/tokens", Some(&player), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // a scheduled version is announced but not yet in force
+        publish(&app, &root, "2.0", "future terms", 3600).await;
+        let (_, body) = send(&app, "GET", "/legal/latest/tos", None, Value::Null).await;
+        assert_eq!(body["doc"]["version"], "1.0");
+        assert_eq!(body["next"]["version"], "2.0");
+        let (status, _) = send(&app, "GET", "/auth/tokens", Some(&player), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // once a newer version takes effect it has to be accepted again
+        let current = publish(&app, &root, "1.1", "new terms", -10).await;
+        let (_, body) = send(&app, "GET", "/legal/latest/tos", None, Value::Null).await;
+        assert_eq!(body["doc"]["version"], "1.1");
+        let (status, _) = send(&app, "GET", "/auth/tokens", Some(&player), Value::Null).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(accept(&app, &player, "1.1").await, StatusCode::OK);
+        let (status, _) = send(&app, "GET", "/auth/tokens", Some(&player), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let uri = format!("/auth/tos/{}/history", player_id);
+        let (_, body) = send(&app, "GET", &uri, Some(&player), Value::Null).await;
+        let versions: Vec<&str> = must_opt(body["acceptances"].as_array(), "missing acceptances")
+            .iter()
+            .filter_map(|a| a["version"].as_str())
+            .collect();
+        assert_eq!(versions, ["1.0", "1.1"]);
+        let uri = format!("/auth/tos/{}", player_id);
+        let (_, body) = send(&app, "GET", &uri, Some(&player), Value::Null).await;
+        assert_eq!(body["version"], "1.1");
+        let (status, body) = send(&app, "GET", &uri, Some(&root), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["version"], "1.1");
+
+        // revoking a version puts the previous one back in force
+        let id = must_opt(current["id"].as_str(), "missing id");
+        let uri = format!("/admin/legal/{}/revoke", id);
+        let revoke = json!({"id": id, "reason": "drafting error"});
+        let (status, _) = send(&app, "POST", &uri, Some(&root), revoke).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = send(&app, "GET", "/legal/latest/tos", None, Value::Null).await;
+        assert_eq!(body["doc"]["version"], "1.0");
+        assert_eq!(accept(&app, &player, "1.1").await, StatusCode::BAD_REQUEST);
+
+        // published versions are immutable
+        let doc = json!({"doc_type": "tos", "version": "1.0", "title": "ToS", "content": "x"});
+        let (status, _) = send(&app, "POST", "/admin/legal", Some(&root), doc).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    });
+}
+
+#[test]
+fn acceptances_belong_to_the_authenticated_user() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let app = build_app(state_at(dir.path()).await);
+        let root = login(&app, "root").await;
+        let (player_id, player) = session(&app, "player").await;
+        let (intruder_id, intruder) = session(&app, "intruder").await;
+        publish(&app, &root, "1.0", "terms", -100).await;
+
+        let body = json!({"user_id": player_id, "version": "1.0"});
+        let (status, _) = send(&app, "POST", "/auth/tos", None, body.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // naming another user in the body records the caller's own acceptance
+        let (status, body) = send(&app, "POST", "/auth/tos", Some(&intruder), body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["user_id"].as_str(), Some(intruder_id.as_str()));
+        let (status, body) = send(&app, "GET", "/auth/tokens", Some(&player), Value::Null).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "tos_acceptance_required");
+        let (status, _) = send(&app, "GET", "/auth/tokens", Some(&intruder), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // acceptances are only readable by their user and legal administrators
+        assert_eq!(accept(&app, &player, "1.0").await, StatusCode::OK);
+        for uri in [
+            format!("/auth/tos/{}", player_id),
+            format!("/auth/tos/{}/history", player_id),
+        ] {
+            let (status, _) = send(&app, "GET", &uri, None, Value::Null).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", uri);
+            let (status, _) = send(&app, "GET", &uri, Some(&intruder), Value::Null).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+            let (status, _) = send(&app, "GET", &uri, Some(&root), Value::Null).await;
+            assert_eq!(status, StatusCode::OK, "{}", uri);
+        }
+    });
+}
+
+#[test]
+fn versions_are_diffed_line_by_line() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let app = build_app(state_at(dir.path()).await);
+        let root = login(&app, "root").await;
+        publish(&app, &root, "1.0", "one\ntwo\nthree\n", 0).await;
+        publish(&app, &root, "2.0", "one\nTWO\nthree\nfour\n", 0).await;
+
+        let uri = "/legal/tos/diff?from=1.0&to=2.0";
+        let (status, body) = send(&app, "GET", uri, None, Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            (body["added"].as_u64(), body["removed"].as_u64()),
+            (Some(2), Some(1))
+        );
+        let diff = must_opt(body["diff"].as_str(), "missing diff");
+        assert!(diff.starts_with("--- 1.0\n+++ 2.0\n"), "{}", diff);
+        assert!(diff.contains("\n-two\n+TWO\n"), "{}", diff);
+
+        // plain version lookups still work next to the diff route
+        let (status, _) = send(&app, "GET", "/legal/tos/2.0", None, Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        let uri = "/legal/tos/diff?from=1.0&to=9.9";
+        let (status, _) = send(&app, "GET", uri, None, Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    });
+}
@@ -0,0 +1,210 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use base64::engine::general_purpose;
+use base64::Engine;
+use ed25519_dalek::SigningKey;
+use master_server::build_app;
+use master_server::config::Config;
+use master_server::plugins::{get_manifest, signing_document, verify_manifest, PluginManifest};
+use master_server::state::AppState;
+use master_server::trust::{trust_store, TrustedKey};
+use std::sync::Arc;
+use tempfile::tempdir;
+use tower::util::ServiceExt;
+use verseguy_auth::local::LocalAuth;
+use verseguy_signing::{Envelope, SignatureEntry};
+use verseguy_test_utils::{must, must_opt};
+
+async fn send(app: &Router, req: Request<Body>) -> (StatusCode, serde_json::Value) {
+    let resp = must(app.clone().oneshot(req).await);
+    let status = resp.status();
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    let body = serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null);
+    (status, body)
+}
+
+fn post(uri: &str, body: &serde_json::Value) -> Request<Body> {
+    must(
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string())),
+    )
+}
+
+/// `req` with the session token of `admin`, registered by the test as an administrator
+async fn as_admin(app: &Router, mut req: Request<Body>) -> Request<Body> {
+    let creds = serde_json::json!({"username": "admin", "password": "adm1n-pass"});
+    let (_, body) = send(app, post("/auth/login", &creds)).await;
+    let token = must_opt(body["token"].as_str(), "missing token");
+    let bearer = must(format!("Bearer {}", token).parse());
+    req.headers_mut().insert("authorization", bearer);
+    req
+}
+
+fn get(uri: &str) -> Request<Body> {
+    must(Request::builder().uri(uri).body(Body::empty()))
+}
+
+fn manifest(version: &str) -> PluginManifest {
+    PluginManifest {
+        id: "org.test.signing".to_string(),
+        name: "SigningTest".to_string(),
+        version: version.to_string(),
+        author: Some("Dev".to_string()),
+        description: None,
+        published_at: None,
+        artifact: None,
+        core_version_min: None,
+        sdk_version: None,
+        dependencies: Vec::new(),
+        license_required: None,
+        category: None,
+        tags: Vec::new(),
+        capabilities: Vec::new(),
+    }
+}
+
+fn publisher_signature(manifest: &PluginManifest, key: &SigningKey) -> SignatureEntry {
+    let document = signing_document(&must(serde_json::to_value(manifest)));
+    must(SignatureEntry::sign(&document, key, 1_700_000_000, None))
+        .with_public_key(&key.verifying_key())
+}
+
+fn stored(state: &AppState, version: &str) -> PluginManifest {
+    must_opt(
+        must(get_manifest(&state.storage, "org.test.signing", version)),
+        "manifest not stored",
+    )
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    }
+}
+
+#[test]
+fn publisher_signatures_are_countersigned() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let app = build_app(state.clone());
+        let publisher = SigningKey::from_bytes(&[7u8; 32]);
+
+        let m = manifest("1.0.0");
+        let signature = publisher_signature(&m, &publisher);
+        let body = serde_json::json!({"manifest": m, "signature": signature});
+        let (status, _) = send(&app, post("/plugins/publish", &body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, body) = send(&app, get("/plugins/org.test.signing/1.0.0/signature")).await;
+        assert_eq!(status, StatusCode::OK);
+        let envelope: Envelope = must(serde_json::from_value(body));
+        assert_eq!(envelope.publisher.as_ref(), Some(&signature));
+        let master = must_opt(envelope.master.as_ref(), "missing master signature");
+        let keypair = must_opt(state.keypair.as_ref(), "missing keypair");
+        assert_eq!(master.key_id, verseguy_signing::key_id(&keypair.public));
+
+        // published_at is set after signing and is not part of the signed document
+        let published = stored(&state, "1.0.0");
+        assert!(published.published_at.is_some());
+        let trust = must(trust_store(&state.storage));
+        assert!(must(verify_manifest(&state.storage, &published, &trust)));
+        let mut tampered = published.clone();
+        tampered.description = Some("changed".into());
+        assert!(!must(verify_manifest(&state.storage, &tampered, &trust)));
+
+        // a signature over another manifest is rejected
+        let body = serde_json::json!({"manifest": manifest("1.0.1"), "signature": signature});
+        let (status, body) = send(&app, post("/plugins/publish", &body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+    });
+}
+
+#[test]
+fn rotated_keys_still_verify_earlier_plugins() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let mut config = Config::default();
+        config.storage.db_path = dir.path().join("db");
+        config.security.key_file = Some(dir.path().join("master.key"));
+        let mut state = must(AppState::from_config(config));
+        let admin = must(
+            LocalAuth::new((*state.storage).clone())
+                .register("admin".into(), "adm1n-pass".into())
+                .await,
+        );
+        state.config.security.admin_users = vec![admin.id];
+        let state = Arc::new(state);
+        let app = build_app(state.clone());
+
+        let body = serde_json::json!({"manifest": manifest("1.0.0")});
+        let (status, _) = send(&app, post("/plugins/publish", &body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let rotate = as_admin(&app, post("/admin/keys/rotate", &serde_json::json!({}))).await;
+        let (status, body) = send(&app, rotate).await;
+        assert_eq!(status, StatusCode::OK);
+        let new_key = must_opt(body["public_key_b64"].as_str(), "missing key").to_string();
+
+        let (status, body) = send(&app, get("/plugins/trust")).await;
+        assert_eq!(status, StatusCode::OK);
+        let keys: Vec<TrustedKey> = must(serde_json::from_value(body["keys"].clone()));
+        assert_eq!(keys.len(), 2);
+        assert!(keys.iter().any(|k| k.public_key == new_key));
+
+        let published = stored(&state, "1.0.0");
+        let body = serde_json::json!({"manifest": published});
+        let (status, body) = send(&app, post("/verify/plugin", &body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["valid"], true);
+
+        // the new key alone did not sign it
+        let body = serde_json::json!({"manifest": published, "public_key_b64": new_key});
+        let (_, body) = send(&app, post("/verify/plugin", &body)).await;
+        assert_eq!(body["valid"], false);
+    });
+}
+
+#[test]
+fn malformed_signatures_and_keys_do_not_panic() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let app = build_app(state.clone());
+        let keypair = must_opt(state.keypair.as_ref(), "missing keypair");
+
+        // signatures stored before envelopes are plain base64
+        let legacy = manifest("0.9.0").with_published();
+        must(master_server::plugins::store_manifest(
+            &state.storage,
+            &legacy,
+            None,
+        ));
+        let sig = must(keypair.sign(&must(serde_json::to_vec(&legacy))));
+        let sig_key = "plugin_sig:org.test.signing:0.9.0";
+        let encoded = general_purpose::STANDARD.encode(sig.to_bytes());
+        must(state.storage.put(sig_key.as_bytes(), &encoded));
+        let trust = must(trust_store(&state.storage));
+        assert!(must(verify_manifest(&state.storage, &legacy, &trust)));
+
+        // a truncated signature is invalid rather than a panic
+        let short = general_purpose::STANDARD.encode(&sig.to_bytes()[..10]);
+        must(state.storage.put(sig_key.as_bytes(), &short));
+        assert!(!must(verify_manifest(&state.storage, &legacy, &trust)));
+
+        let short_key = general_purpose::STANDARD.encode([1u8; 8]);
+        let body = serde_json::json!({"manifest": legacy, "public_key_b64": short_key});
+        let (status, _) = send(&app, post("/verify/plugin", &body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    });
+}
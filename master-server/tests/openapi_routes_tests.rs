@@ -0,0 +1,47 @@
+#![allow(clippy::disallowed_methods)]
+use master_server::state::AppState;
+use master_server::{build_app, openapi_spec};
+use std::sync::Arc;
+use tempfile::tempdir;
+use verseguy_test_utils::must;
+use verseguy_test_utils::openapi::{
+    assert_operations_routed, assert_path_parameters_documented, operations, served_spec,
+};
+
+#[test]
+fn every_documented_operation_is_routed() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let dir = must(tempdir());
+        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let app = build_app(state);
+        let spec = served_spec(&app).await;
+        assert_eq!(spec, must(serde_json::to_value(openapi_spec())));
+
+        let ops = operations(&spec);
+        assert!(ops
+            .iter()
+            .any(|(m, p, _)| m == "POST" && p == "/auth/login"));
+        assert!(ops
+            .iter()
+            .any(|(m, p, _)| m == "DELETE" && p == "/auth/tokens/{id}"));
+        assert!(ops
+            .iter()
+            .any(|(m, p, _)| m == "GET" && p == "/legal/{type}/{version}"));
+
+        assert_operations_routed(&app, &spec).await;
+    });
+}
+
+#[test]
+fn path_parameters_are_documented() {
+    let spec = must(serde_json::to_value(openapi_spec()));
+    assert_path_parameters_documented(&spec);
+}
@@ -18,12 +18,11 @@ fn publish_requires_plugin_token_when_set() {
     };
 
     rt.block_on(async {
-    // set env var
-    std::env::set_var("MASTER_PLUGIN_PUBLISH_KEY", "secrettoken123");
-
     let dir = must(TempDir::new());
     let db_path = must_opt(dir.path().to_str(), "tempdir path not utf8").to_string();
-    let state = Arc::new(must(master_server::state::AppState::new(db_path, b"secret".to_vec())));
+    let mut state = must(master_server::state::AppState::new(db_path, b"secret".to_vec()));
+    state.config.security.plugin_publish_key = Some("secrettoken123".into());
+    let state = Arc::new(state);
     let app = build_app(state.clone());
 
     let manifest_body = r#"{ "manifest": { "id": "org.auth.test", "name": "AuthTest", "version": "0.1.0", "author": "Test", "description": "Auth test", "published_at": null } }"#;
@@ -7,6 +7,7 @@ use master_server::state::AppState;
 use std::sync::Arc;
 use tempfile::tempdir;
 use tower::util::ServiceExt;
+use verseguy_auth::{License, SessionService};
 use verseguy_test_utils::must;
 
 #[test]
@@ -23,6 +24,16 @@ fn publish_requires_tos_when_user_header_present() {
         let dir = must(tempdir());
         let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
         let app = build_app(state.clone());
+        must(master_server::legal::create_document(
+            &state.storage,
+            master_server::legal::AdminCreateLegalRequest {
+                doc_type: "tos".into(),
+                version: "1.0.0".into(),
+                title: "Terms of Service".into(),
+                content: "These are the terms".into(),
+                ..Default::default()
+            },
+        ));
 
         let manifest = PluginManifest {
             id: "org.test.publishtos".to_string(),
@@ -31,6 +42,14 @@ fn publish_requires_tos_when_user_header_present() {
             author: Some("Dev".to_string()),
             description: Some("Test".to_string()),
             published_at: None,
+            artifact: None,
+            core_version_min: None,
+            sdk_version: None,
+            dependencies: Vec::new(),
+            license_required: None,
+            category: None,
+            tags: Vec::new(),
+            capabilities: Vec::new(),
         };
 
         // Attempt publish with x-user-id header but without ToS acceptance => Forbidden
@@ -48,18 +67,41 @@ fn publish_requires_tos_when_user_header_present() {
         assert_eq!(resp.status(), StatusCode::FORBIDDEN);
 
         // Accept ToS for user
-        let tos_body =
-            r#"{"user_id":"user-123","accepted_at": 1234567890, "version": "1.0.0"}"#.to_string();
+        let session = |user_id: &str| {
+            must(
+                SessionService::new(state.license_secret.clone()).create_and_store_session(
+                    user_id,
+                    &License::Free,
+                    1,
+                    &state.storage,
+                ),
+            )
+        };
+        let tos_body = r#"{"accepted_at": 1234567890, "version": "1.0.0"}"#.to_string();
         let req2 = must(
             Request::builder()
                 .method("POST")
                 .uri("/auth/tos")
                 .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {}", session("user-123")))
                 .body(Body::from(tos_body)),
         );
         let resp2 = must(app.clone().oneshot(req2).await);
         assert_eq!(resp2.status(), StatusCode::OK);
 
+        // An authenticated caller is checked, not the user it names in x-user-id
+        let req = must(
+            Request::builder()
+                .method("POST")
+                .uri("/plugins/publish")
+                .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {}", session("user-456")))
+                .header("x-user-id", "user-123")
+                .body(Body::from(body.clone())),
+        );
+        let resp = must(app.clone().oneshot(req).await);
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
         // Now publish should succeed
         let req3 = must(
             Request::builder()
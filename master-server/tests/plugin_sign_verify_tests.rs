@@ -1,6 +1,7 @@
 use master_server::plugins::{verify_manifest, PluginManifest};
 use master_server::state::AppState;
 use tempfile::tempdir;
+use verseguy_signing::TrustStore;
 use verseguy_test_utils::{must, must_opt};
 
 #[test]
@@ -16,6 +17,14 @@ fn sign_and_verify() {
         author: Some("Dev".to_string()),
         description: Some("Test".to_string()),
         published_at: None,
+        artifact: None,
+        core_version_min: None,
+        sdk_version: None,
+        dependencies: Vec::new(),
+        license_required: None,
+        category: None,
+        tags: Vec::new(),
+        capabilities: Vec::new(),
     };
 
     // store and sign
@@ -27,10 +36,11 @@ fn sign_and_verify() {
 
     // verify
     let pubkey = must_opt(state.keypair.as_ref(), "missing keypair").public;
+    let trust = TrustStore::new().with_key(pubkey);
     let ok = must(verify_manifest(
         &state.storage,
         &manifest.with_published(),
-        &pubkey,
+        &trust,
     ));
     assert!(ok);
 
@@ -9,6 +9,8 @@ use master_server::state::AppState;
 use std::sync::Arc;
 use tempfile::tempdir;
 use tower::util::ServiceExt;
+use verseguy_auth::local::LocalAuth;
+use verseguy_auth::SessionService;
 use verseguy_test_utils::{must, must_opt};
 
 #[test]
@@ -23,8 +25,23 @@ fn verify_and_revoke_flow() {
 
     rt.block_on(async {
         let dir = must(tempdir());
-        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let mut state = must(AppState::new(dir.path(), vec![0u8; 32]));
+        let admin = must(
+            LocalAuth::new((*state.storage).clone())
+                .register("admin".into(), "adm1n-pass".into())
+                .await,
+        );
+        state.config.security.admin_users = vec![admin.id.clone()];
+        let state = Arc::new(state);
         let app = build_app(state.clone());
+        let token = must(
+            SessionService::new(state.license_secret.clone()).create_and_store_session(
+                &admin.id,
+                &admin.license,
+                1,
+                &(*state.storage).clone(),
+            ),
+        );
 
         let manifest = PluginManifest {
             id: "org.test.signhttp".to_string(),
@@ -33,6 +50,14 @@ fn verify_and_revoke_flow() {
             author: Some("Dev".to_string()),
             description: Some("Test".to_string()),
             published_at: None,
+            artifact: None,
+            core_version_min: None,
+            sdk_version: None,
+            dependencies: Vec::new(),
+            license_required: None,
+            category: None,
+            tags: Vec::new(),
+            capabilities: Vec::new(),
         };
 
         // store and sign
@@ -72,7 +97,6 @@ fn verify_and_revoke_flow() {
         ));
 
         // Revoke via admin endpoint
-        std::env::set_var("MASTER_ADMIN_TOKEN", "admintoken");
         let revoke_body =
             r#"{"id":"org.test.signhttp","version":"0.1.0","reason":"compromised"}"#.to_string();
         let req2 = must(
@@ -80,7 +104,7 @@ fn verify_and_revoke_flow() {
                 .method("POST")
                 .uri("/verify/revoke")
                 .header("content-type", "application/json")
-                .header("x-admin-token", "admintoken")
+                .header("authorization", format!("Bearer {}", token))
                 .body(Body::from(revoke_body)),
         );
         let resp2 = must(app.clone().oneshot(req2).await);
@@ -0,0 +1,103 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::{self, Body};
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use master_server::build_app;
+use master_server::state::AppState;
+use std::sync::Arc;
+use tempfile::tempdir;
+use tower::util::ServiceExt;
+use verseguy_shared_error::{ProblemDetails, PROBLEM_JSON, REQUEST_ID_HEADER};
+use verseguy_test_utils::must;
+
+async fn send(app: &Router, req: Request<Body>) -> (StatusCode, String, String, Vec<u8>) {
+    let resp = must(app.clone().oneshot(req).await);
+    let status = resp.status();
+    let header = |name: &str| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string()
+    };
+    let (content_type, request_id) = (header("content-type"), header(REQUEST_ID_HEADER));
+    let bytes = must(body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    (status, content_type, request_id, bytes.to_vec())
+}
+
+#[test]
+fn errors_are_problem_documents() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let dir = must(tempdir());
+        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let app = build_app(state);
+
+        // Handler error: the caller's request id is echoed in the header and the body
+        let req = must(
+            Request::builder()
+                .uri("/legal/tos/9.9")
+                .header(REQUEST_ID_HEADER, "trace-42")
+                .body(Body::empty()),
+        );
+        let (status, content_type, request_id, bytes) = send(&app, req).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type, PROBLEM_JSON);
+        assert_eq!(request_id, "trace-42");
+        let problem: ProblemDetails = must(serde_json::from_slice(&bytes));
+        assert_eq!(problem.status, 404);
+        assert_eq!(problem.code, "not_found");
+        assert_eq!(problem.request_id.as_deref(), Some("trace-42"));
+        assert_eq!(problem.instance.as_deref(), Some("/legal/tos/9.9"));
+
+        // Middleware error: a fresh request id is assigned
+        let req = must(Request::builder().uri("/auth/tokens").body(Body::empty()));
+        let (status, content_type, request_id, bytes) = send(&app, req).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(content_type, PROBLEM_JSON);
+        let problem: ProblemDetails = must(serde_json::from_slice(&bytes));
+        assert_eq!(problem.code, "unauthorized");
+        assert!(!request_id.is_empty());
+        assert_eq!(problem.request_id, Some(request_id));
+
+        // Login failures do not reveal whether the account exists
+        let req = must(
+            Request::builder()
+                .method("POST")
+                .uri("/auth/login")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"username":"ghost","password":"whatever1"}"#)),
+        );
+        let (status, _, _, bytes) = send(&app, req).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let problem: ProblemDetails = must(serde_json::from_slice(&bytes));
+        assert_eq!(problem.detail, "invalid credentials");
+
+        // Extractor rejections get the same shape
+        let req = must(
+            Request::builder()
+                .method("POST")
+                .uri("/auth/register")
+                .header("content-type", "application/json")
+                .body(Body::from("{not json")),
+        );
+        let (status, content_type, _, bytes) = send(&app, req).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(content_type, PROBLEM_JSON);
+        let problem: ProblemDetails = must(serde_json::from_slice(&bytes));
+        assert_eq!(problem.code, "invalid_request");
+
+        // Successful responses carry the request id too
+        let req = must(Request::builder().uri("/healthz").body(Body::empty()));
+        let (status, _, request_id, _) = send(&app, req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!request_id.is_empty());
+    });
+}
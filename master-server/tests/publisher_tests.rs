@@ -0,0 +1,271 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use ed25519_dalek::SigningKey;
+use master_server::build_app;
+use master_server::plugins::{signing_document, PluginManifest};
+use master_server::state::AppState;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::tempdir;
+use tower::util::ServiceExt;
+use verseguy_auth::{AccessTokenService, License};
+use verseguy_signing::SignatureEntry;
+use verseguy_test_utils::must;
+
+async fn send(app: &Router, method: &str, uri: &str, token: Option<&str>, body: Value) -> Reply {
+    let mut req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(token) = token {
+        req = req.header("authorization", format!("Bearer {}", token));
+    }
+    let resp = must(
+        app.clone()
+            .oneshot(must(req.body(Body::from(body.to_string()))))
+            .await,
+    );
+    let status = resp.status();
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    Reply {
+        status,
+        body: serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    }
+}
+
+async fn post(app: &Router, uri: &str, token: Option<&str>, body: Value) -> Reply {
+    send(app, "POST", uri, token, body).await
+}
+
+async fn delete(app: &Router, uri: &str, token: Option<&str>) -> Reply {
+    send(app, "DELETE", uri, token, Value::Null).await
+}
+
+async fn get(app: &Router, uri: &str, token: Option<&str>) -> Reply {
+    send(app, "GET", uri, token, Value::Null).await
+}
+
+struct Reply {
+    status: StatusCode,
+    body: Value,
+}
+
+fn manifest(id: &str, version: &str) -> PluginManifest {
+    PluginManifest {
+        id: id.to_string(),
+        name: format!("Plugin {}", id),
+        version: version.to_string(),
+        author: None,
+        description: None,
+        published_at: None,
+        artifact: None,
+        core_version_min: None,
+        sdk_version: None,
+        dependencies: Vec::new(),
+        license_required: None,
+        category: None,
+        tags: Vec::new(),
+        capabilities: Vec::new(),
+    }
+}
+
+fn publish(manifest: &PluginManifest) -> Value {
+    json!({ "manifest": manifest })
+}
+
+/// Service account with a `plugins:publish` token; returns `(user_id, token)`
+fn account(state: &AppState, name: &str) -> (String, String) {
+    let tokens = AccessTokenService::new((*state.storage).clone());
+    let user = must(tokens.create_service_account(name, License::Free));
+    let (token, _) = must(tokens.create(&user.id, "ci", vec!["plugins:publish".into()], None));
+    (user.id, token)
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    }
+}
+
+#[test]
+fn namespaces_belong_to_their_publisher() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let (alice_id, alice) = account(&state, "alice");
+        let (bob_id, bob) = account(&state, "bob");
+        let app = build_app(state.clone());
+
+        // accounts need a publisher profile before they claim anything
+        let ns = json!({"prefix": "org.verseguy.*"});
+        let r = post(&app, "/publishers/me/namespaces", Some(&alice), ns.clone()).await;
+        assert_eq!(r.status, StatusCode::NOT_FOUND);
+        for token in [&alice, &bob] {
+            let r = post(&app, "/publishers", Some(token), json!({"name": "p"})).await;
+            assert_eq!(r.status, StatusCode::CREATED);
+        }
+        let r = post(&app, "/publishers", Some(&alice), json!({"name": "p"})).await;
+        assert_eq!(r.status, StatusCode::CONFLICT);
+
+        let r = post(&app, "/publishers/me/namespaces", Some(&alice), ns).await;
+        assert_eq!(r.status, StatusCode::CREATED);
+        assert_eq!(r.body["prefix"], "org.verseguy");
+        for prefix in ["org.verseguy.tools", "org", "org.Verse"] {
+            let r = post(
+                &app,
+                "/publishers/me/namespaces",
+                Some(&bob),
+                json!({ "prefix": prefix }),
+            )
+            .await;
+            assert!(r.status.is_client_error(), "{} {}", prefix, r.status);
+        }
+
+        // only the namespace owner publishes into it
+        let m = manifest("org.verseguy.fleet", "1.0.0");
+        let r = post(&app, "/plugins/publish", None, publish(&m)).await;
+        assert_eq!(r.status, StatusCode::FORBIDDEN);
+        let r = post(&app, "/plugins/publish", Some(&bob), publish(&m)).await;
+        assert_eq!(r.status, StatusCode::FORBIDDEN);
+        let r = post(&app, "/plugins/publish", Some(&alice), publish(&m)).await;
+        assert_eq!(r.status, StatusCode::CREATED);
+
+        // published versions are immutable
+        let r = post(&app, "/plugins/publish", Some(&alice), publish(&m)).await;
+        assert_eq!(r.status, StatusCode::CONFLICT);
+        assert_eq!(r.body["code"], "version_exists");
+
+        // co-maintainers may publish new versions
+        let uri = "/plugins/org.verseguy.fleet/maintainers";
+        let r = post(&app, uri, Some(&bob), json!({"user_id": bob_id})).await;
+        assert_eq!(r.status, StatusCode::FORBIDDEN);
+        let r = post(&app, uri, Some(&alice), json!({"user_id": bob_id})).await;
+        assert_eq!(r.status, StatusCode::OK);
+        assert_eq!(r.body["owner"], alice_id.as_str());
+        let m2 = manifest("org.verseguy.fleet", "1.1.0");
+        let r = post(&app, "/plugins/publish", Some(&bob), publish(&m2)).await;
+        assert_eq!(r.status, StatusCode::CREATED);
+
+        let uri = format!("/plugins/org.verseguy.fleet/maintainers/{}", bob_id);
+        let r = delete(&app, &uri, Some(&alice)).await;
+        assert_eq!(r.status, StatusCode::OK);
+        let m3 = manifest("org.verseguy.fleet", "1.2.0");
+        let r = post(&app, "/plugins/publish", Some(&bob), publish(&m3)).await;
+        assert_eq!(r.status, StatusCode::FORBIDDEN);
+
+        // ids outside every namespace stay open to anonymous publishing, but accounts
+        // need a namespace for them
+        let open = manifest("net.example.tool", "0.1.0");
+        let r = post(&app, "/plugins/publish", Some(&bob), publish(&open)).await;
+        assert_eq!(r.status, StatusCode::FORBIDDEN);
+        let r = post(&app, "/plugins/publish", None, publish(&open)).await;
+        assert_eq!(r.status, StatusCode::CREATED);
+    });
+}
+
+#[test]
+fn registered_keys_must_sign_publishes() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let (_, alice) = account(&state, "alice");
+        let app = build_app(state.clone());
+        must_created(&post(&app, "/publishers", Some(&alice), json!({"name": "A"})).await);
+        let ns = json!({"prefix": "org.alice"});
+        must_created(&post(&app, "/publishers/me/namespaces", Some(&alice), ns).await);
+
+        let key = SigningKey::from_bytes(&[5u8; 32]);
+        let public = verseguy_signing::encode_public_key(&key.verifying_key());
+        let body = json!({ "public_key": public });
+        let r = post(&app, "/publishers/me/keys", Some(&alice), body).await;
+        must_created(&r);
+        let key_id = verseguy_signing::key_id(&key.verifying_key());
+        assert_eq!(r.body["key_id"], key_id.as_str());
+
+        let m = manifest("org.alice.tool", "1.0.0");
+        let r = post(&app, "/plugins/publish", Some(&alice), publish(&m)).await;
+        assert_eq!(r.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        // a valid signature by an unregistered key is not enough
+        let other = SigningKey::from_bytes(&[6u8; 32]);
+        let document = signing_document(&must(serde_json::to_value(&m)));
+        let unregistered = must(SignatureEntry::sign(&document, &other, 1, None))
+            .with_public_key(&other.verifying_key());
+        let body = json!({ "manifest": m, "signature": unregistered });
+        let r = post(&app, "/plugins/publish", Some(&alice), body).await;
+        assert_eq!(r.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let signature = must(SignatureEntry::sign(&document, &key, 1, None));
+        let body = json!({ "manifest": m, "signature": signature });
+        let r = post(&app, "/plugins/publish", Some(&alice), body).await;
+        must_created(&r);
+
+        let r = get(&app, "/publishers/me", Some(&alice)).await;
+        assert_eq!(r.status, StatusCode::OK);
+        assert_eq!(r.body["namespaces"][0]["prefix"], "org.alice");
+        let uri = format!("/publishers/me/keys/{}", key_id);
+        let r = delete(&app, &uri, Some(&alice)).await;
+        assert_eq!(r.status, StatusCode::OK);
+        assert_eq!(r.body["keys"], json!([]));
+    });
+}
+
+#[test]
+fn yanked_versions_leave_search_but_stay_verifiable() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let (_, alice) = account(&state, "alice");
+        let (_, bob) = account(&state, "bob");
+        let app = build_app(state.clone());
+        for token in [&alice, &bob] {
+            must_created(&post(&app, "/publishers", Some(token), json!({"name": "p"})).await);
+        }
+        let ns = json!({"prefix": "org.yank"});
+        must_created(&post(&app, "/publishers/me/namespaces", Some(&alice), ns).await);
+        let m = manifest("org.yank.plugin", "1.0.0");
+        must_created(&post(&app, "/plugins/publish", Some(&alice), publish(&m)).await);
+
+        let uri = "/plugins/org.yank.plugin/1.0.0/yank";
+        let reason = json!({"reason": "broken build"});
+        let r = post(&app, uri, Some(&bob), reason.clone()).await;
+        assert_eq!(r.status, StatusCode::FORBIDDEN);
+        let r = post(&app, uri, None, reason.clone()).await;
+        assert_eq!(r.status, StatusCode::UNAUTHORIZED);
+        let r = post(&app, uri, Some(&alice), reason).await;
+        assert_eq!(r.status, StatusCode::OK);
+        assert_eq!(r.body["reason"], "broken build");
+
+        let r = get(&app, "/plugins/search?q=org.yank", None).await;
+        assert_eq!(r.body["results"], json!([]));
+        // yanking is not revocation
+        let stored = must(master_server::plugins::get_manifest(
+            &state.storage,
+            "org.yank.plugin",
+            "1.0.0",
+        ));
+        let body = json!({ "manifest": stored });
+        let r = post(&app, "/verify/plugin", None, body).await;
+        assert_eq!(r.body["valid"], true);
+        assert!(!must(master_server::plugins::is_revoked(
+            &state.storage,
+            "org.yank.plugin",
+            "1.0.0"
+        )));
+
+        let r = delete(&app, uri, Some(&alice)).await;
+        assert_eq!(r.status, StatusCode::NO_CONTENT);
+        let r = get(&app, "/plugins/search?q=org.yank", None).await;
+        assert_eq!(r.body["results"].as_array().map(Vec::len), Some(1));
+    });
+}
+
+fn must_created(reply: &Reply) {
+    assert_eq!(reply.status, StatusCode::CREATED, "{}", reply.body);
+}
@@ -0,0 +1,113 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use master_server::build_app;
+use master_server::state::AppState;
+use std::sync::Arc;
+use tempfile::tempdir;
+use tower::util::ServiceExt;
+use verseguy_auth::{AccessTokenService, License};
+use verseguy_ratelimit::Limit;
+use verseguy_shared_error::ProblemDetails;
+use verseguy_test_utils::must;
+
+async fn send(app: &Router, req: Request<Body>) -> (StatusCode, String, Vec<u8>) {
+    let resp = must(app.clone().oneshot(req).await);
+    let status = resp.status();
+    let retry_after = resp
+        .headers()
+        .get("retry-after")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    (status, retry_after, bytes.to_vec())
+}
+
+fn login() -> Request<Body> {
+    must(
+        Request::builder()
+            .method("POST")
+            .uri("/auth/login")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"username":"ghost","password":"whatever1"}"#)),
+    )
+}
+
+fn list_tokens(token: &str) -> Request<Body> {
+    must(
+        Request::builder()
+            .uri("/auth/tokens")
+            .header("authorization", format!("Bearer {}", token))
+            .body(Body::empty()),
+    )
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    }
+}
+
+#[test]
+fn login_attempts_are_throttled() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let mut state = must(AppState::new(dir.path(), vec![0u8; 32]));
+        state.config.rate_limits.auth = Limit::per_minute(2);
+        let app = build_app(Arc::new(state));
+
+        for _ in 0..2 {
+            let (status, _, _) = send(&app, login()).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+        let (status, retry_after, bytes) = send(&app, login()).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(retry_after, "30");
+        let problem: ProblemDetails = must(serde_json::from_slice(&bytes));
+        assert_eq!(problem.code, "rate_limited");
+        assert!(problem.request_id.is_some());
+
+        // probes are never limited
+        for _ in 0..3 {
+            let req = must(Request::builder().uri("/healthz").body(Body::empty()));
+            let (status, _, _) = send(&app, req).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+    });
+}
+
+#[test]
+fn api_quota_follows_the_license_tier() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let mut state = must(AppState::new(dir.path(), vec![0u8; 32]));
+        state.config.rate_limits.quota_free = Limit::per_day(1);
+        state.config.rate_limits.quota_pro = Limit::per_day(3);
+        let tokens = AccessTokenService::new((*state.storage).clone());
+        let mut bearer = Vec::new();
+        for (name, license) in [("free-bot", License::Free), ("pro-bot", License::Pro)] {
+            let account = must(tokens.create_service_account(name, license));
+            let (token, _) = must(tokens.create(&account.id, "ci", vec!["*".into()], None));
+            bearer.push(token);
+        }
+        let app = build_app(Arc::new(state));
+
+        let (status, _, _) = send(&app, list_tokens(&bearer[0])).await;
+        assert_ne!(status, StatusCode::TOO_MANY_REQUESTS);
+        let (status, _, _) = send(&app, list_tokens(&bearer[0])).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+        for _ in 0..3 {
+            let (status, _, _) = send(&app, list_tokens(&bearer[1])).await;
+            assert_ne!(status, StatusCode::TOO_MANY_REQUESTS);
+        }
+        let (status, _, _) = send(&app, list_tokens(&bearer[1])).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    });
+}
@@ -0,0 +1,232 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use master_server::build_app;
+use master_server::plugins::{yank_manifest, YankRecord};
+use master_server::state::AppState;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::tempdir;
+use tower::util::ServiceExt;
+use verseguy_test_utils::must;
+
+async fn send(app: &Router, req: Request<Body>) -> (StatusCode, Value) {
+    let resp = must(app.clone().oneshot(req).await);
+    let status = resp.status();
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+async fn post(app: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
+    let req = Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()));
+    send(app, must(req)).await
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, Value) {
+    send(app, must(Request::builder().uri(uri).body(Body::empty()))).await
+}
+
+/// Publish `id` at `version` with the given extra manifest fields
+async fn publish(app: &Router, id: &str, version: &str, extra: Value) -> StatusCode {
+    let mut manifest = json!({"id": id, "name": id, "version": version});
+    if let (Some(fields), Some(extra)) = (manifest.as_object_mut(), extra.as_object()) {
+        fields.extend(extra.clone());
+    }
+    post(app, "/plugins/publish", json!({ "manifest": manifest }))
+        .await
+        .0
+}
+
+fn versions(install: &Value) -> Vec<String> {
+    install
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .map(|m| {
+                    format!(
+                        "{}@{}",
+                        m["id"].as_str().unwrap_or(""),
+                        m["version"].as_str().unwrap_or("")
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    }
+}
+
+#[test]
+fn latest_follows_semver_and_the_host() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let app = build_app(state.clone());
+        let id = "org.res.ui";
+        for (version, extra) in [
+            ("1.2.0", json!({})),
+            ("1.9.0", json!({})),
+            ("1.10.0", json!({"core_version_min": "2.0.0"})),
+            ("2.0.0-beta.1", json!({})),
+        ] {
+            assert_eq!(publish(&app, id, version, extra).await, StatusCode::CREATED);
+        }
+
+        // 1.10.0 sorts after 1.9.0, and pre-releases are never the latest
+        let (status, body) = get(&app, "/plugins/org.res.ui/latest").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["version"], "1.10.0");
+        let (_, body) = get(&app, "/plugins/org.res.ui/latest?core_version=1.5.0").await;
+        assert_eq!(body["version"], "1.9.0");
+        let (status, _) = get(&app, "/plugins/org.res.ui/latest?core_version=two").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get(&app, "/plugins/org.res.nothing/latest").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) = get(&app, "/plugins/org.res.ui/versions").await;
+        assert_eq!(status, StatusCode::OK);
+        let listed: Vec<&str> = body["versions"]
+            .as_array()
+            .map(|v| v.iter().filter_map(|s| s["version"].as_str()).collect())
+            .unwrap_or_default();
+        assert_eq!(listed, ["2.0.0-beta.1", "1.10.0", "1.9.0", "1.2.0"]);
+
+        // search offers one version per plugin
+        let (_, body) = get(&app, "/plugins/search?q=org.res").await;
+        assert_eq!(versions(&body["results"]), ["org.res.ui@1.10.0"]);
+        let (_, body) = get(&app, "/plugins/search?q=org.res&core_version=1.0.0").await;
+        assert_eq!(versions(&body["results"]), ["org.res.ui@1.9.0"]);
+
+        // versions and requirements must be valid semver
+        for (version, extra) in [
+            ("v3", json!({})),
+            ("3.0.0", json!({"sdk_version": "1.x"})),
+            (
+                "3.0.0",
+                json!({"dependencies": [{"id": "org.res.core", "version": "one"}]}),
+            ),
+            ("3.0.0", json!({"dependencies": [{"id": "org.res.ui"}]})),
+        ] {
+            let status = publish(&app, id, version, extra.clone()).await;
+            assert_eq!(
+                status,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "{} {}",
+                version,
+                extra
+            );
+        }
+    });
+}
+
+#[test]
+fn resolver_backtracks_and_explains_conflicts() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
+        let app = build_app(state.clone());
+        let core = |req: &str| json!({"dependencies": [{"id": "org.res.core", "version": req}]});
+        for (id, version, extra) in [
+            ("org.res.core", "1.0.0", json!({})),
+            ("org.res.core", "1.5.0", json!({})),
+            (
+                "org.res.core",
+                "2.0.0",
+                json!({"core_version_min": "3.0.0"}),
+            ),
+            ("org.res.app", "1.0.0", core("^1")),
+            ("org.res.map", "1.0.0", core(">=1.2")),
+            ("org.res.map", "2.0.0", core("^2")),
+        ] {
+            assert_eq!(publish(&app, id, version, extra).await, StatusCode::CREATED);
+        }
+
+        // map 2.0.0 needs core 2, which app rules out, so map falls back to 1.0.0
+        let request = json!({"plugins": [{"id": "org.res.app"}, {"id": "org.res.map"}]});
+        let (status, body) = post(&app, "/plugins/resolve", request).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(
+            versions(&body["install"]),
+            [
+                "org.res.core@1.5.0",
+                "org.res.app@1.0.0",
+                "org.res.map@1.0.0"
+            ]
+        );
+
+        let request = json!({"plugins": [
+            {"id": "org.res.app"},
+            {"id": "org.res.map", "version": "^2"},
+        ]});
+        let (status, body) = post(&app, "/plugins/resolve", request).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "resolution_conflict");
+        let detail = body["detail"].as_str().unwrap_or_default();
+        assert!(
+            detail.contains("org.res.map 2.0.0 requires org.res.core ^2"),
+            "{}",
+            detail
+        );
+
+        let request = json!({
+            "plugins": [{"id": "org.res.core", "version": "^2"}],
+            "core_version": "2.5.0",
+        });
+        let (status, body) = post(&app, "/plugins/resolve", request).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let detail = body["detail"].as_str().unwrap_or_default();
+        assert!(detail.contains("2.0.0 needs core >= 3.0.0"), "{}", detail);
+
+        let request = json!({"plugins": [{"id": "org.res.missing"}]});
+        let (status, _) = post(&app, "/plugins/resolve", request).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let request = json!({"plugins": [{"id": "org.res.app", "version": "latest"}]});
+        let (status, _) = post(&app, "/plugins/resolve", request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // requests are capped; repeated demands on a picked plugin are consumed in a loop
+        let request = json!({"plugins": vec![json!({"id": "org.res.core"}); 101]});
+        let (status, body) = post(&app, "/plugins/resolve", request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        let request = json!({"plugins": vec![json!({"id": "org.res.core"}); 100]});
+        let (status, body) = post(&app, "/plugins/resolve", request).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(versions(&body["install"]).len(), 1);
+
+        // yanked versions are not picked
+        let record = YankRecord {
+            reason: None,
+            by: "test".into(),
+            at: 0,
+        };
+        must(yank_manifest(
+            &state.storage,
+            "org.res.core",
+            "1.5.0",
+            &record,
+        ));
+        let request = json!({"plugins": [{"id": "org.res.app"}]});
+        let (_, body) = post(&app, "/plugins/resolve", request).await;
+        assert_eq!(
+            versions(&body["install"]),
+            ["org.res.core@1.0.0", "org.res.app@1.0.0"]
+        );
+    });
+}
@@ -0,0 +1,262 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use master_server::artifacts::ArtifactRef;
+use master_server::build_app;
+use master_server::config::Config;
+use master_server::state::AppState;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tempfile::tempdir;
+use tower::util::ServiceExt;
+use verseguy_auth::local::LocalAuth;
+use verseguy_test_utils::{must, must_opt};
+
+async fn send(app: &Router, req: Request<Body>) -> (StatusCode, Value) {
+    let resp = must(app.clone().oneshot(req).await);
+    let status = resp.status();
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+async fn post(app: &Router, uri: &str, body: Value) -> (StatusCode, Value) {
+    post_as(app, None, uri, body).await
+}
+
+async fn post_as(app: &Router, token: Option<&str>, uri: &str, body: Value) -> (StatusCode, Value) {
+    let mut req = Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("content-type", "application/json");
+    if let Some(token) = token {
+        req = req.header("authorization", format!("Bearer {}", token));
+    }
+    send(app, must(req.body(Body::from(body.to_string())))).await
+}
+
+/// Session token of `admin`, registered by [`state_at`] as an administrator
+async fn admin_session(app: &Router) -> String {
+    let creds = json!({"username": "admin", "password": "adm1n-pass"});
+    let (status, body) = post(app, "/auth/login", creds).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    must_opt(body["token"].as_str(), "missing token").to_string()
+}
+
+async fn get(app: &Router, uri: &str, headers: &[(&str, &str)]) -> (StatusCode, Value) {
+    let mut req = Request::builder().uri(uri);
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    send(app, must(req.body(Body::empty()))).await
+}
+
+async fn publish(app: &Router, manifest: Value) {
+    let (status, body) = post(app, "/plugins/publish", json!({ "manifest": manifest })).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+}
+
+/// Ids of the results, in order
+fn ids(body: &Value) -> Vec<String> {
+    body["results"]
+        .as_array()
+        .map(|r| {
+            r.iter()
+                .filter_map(|m| m["id"].as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// State whose administrator, through `security.admin_users`, is the local account `admin`
+async fn state_at(dir: &std::path::Path) -> Arc<AppState> {
+    let mut config = Config::default();
+    config.storage.db_path = dir.join("db");
+    let mut state = must(AppState::from_config(config));
+    let admin = must(
+        LocalAuth::new((*state.storage).clone())
+            .register("admin".into(), "adm1n-pass".into())
+            .await,
+    );
+    state.config.security.admin_users = vec![admin.id];
+    Arc::new(state)
+}
+
+fn runtime() -> tokio::runtime::Runtime {
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    }
+}
+
+#[test]
+fn search_ranks_filters_and_pages() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let app = build_app(state_at(dir.path()).await);
+        publish(
+            &app,
+            json!({
+                "id": "org.s.fleet", "name": "Fleet Manager", "version": "1.0.0",
+                "description": "Track ships", "tags": ["fleet", "ships"], "category": "Fleet",
+                "capabilities": ["storage:read"],
+            }),
+        )
+        .await;
+        publish(
+            &app,
+            json!({
+                "id": "org.s.trade", "name": "Trade Routes", "version": "1.0.0",
+                "description": "Plan trade routes for your fleet", "tags": ["trading"],
+                "category": "Economy", "license_required": "Pro",
+                "capabilities": ["network:p2p"],
+            }),
+        )
+        .await;
+        publish(
+            &app,
+            json!({
+                "id": "org.s.mining", "name": "Mining Log", "version": "1.0.0",
+                "description": "Log mining runs", "category": "Economy",
+                "license_required": "Enterprise",
+            }),
+        )
+        .await;
+
+        // a name and tag match outranks a description match
+        let (status, body) = get(&app, "/plugins/search?q=fleet", &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids(&body), ["org.s.fleet", "org.s.trade"]);
+        assert_eq!(body["total"], 2);
+        let (_, body) = get(&app, "/plugins/search?q=trad", &[]).await;
+        assert_eq!(ids(&body), ["org.s.trade"]);
+        // every term has to match
+        let (_, body) = get(&app, "/plugins/search?q=fleet+mining", &[]).await;
+        assert_eq!(body["total"], 0);
+
+        let (_, body) = get(&app, "/plugins/search", &[]).await;
+        assert_eq!(ids(&body), ["org.s.fleet", "org.s.mining", "org.s.trade"]);
+        assert_eq!(
+            body["facets"]["category"],
+            json!({"Economy": 2, "Fleet": 1})
+        );
+        assert_eq!(
+            body["facets"]["license"],
+            json!({"Enterprise": 1, "Free": 1, "Pro": 1})
+        );
+
+        let (_, body) = get(&app, "/plugins/search?license=pro", &[]).await;
+        assert_eq!(ids(&body), ["org.s.fleet", "org.s.trade"]);
+        let (status, _) = get(&app, "/plugins/search?license=gold", &[]).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (_, body) = get(&app, "/plugins/search?category=economy", &[]).await;
+        assert_eq!(ids(&body), ["org.s.mining", "org.s.trade"]);
+        let (_, body) = get(&app, "/plugins/search?capability=network:p2p", &[]).await;
+        assert_eq!(ids(&body), ["org.s.trade"]);
+        let (_, body) = get(&app, "/plugins/search?tag=Ships", &[]).await;
+        assert_eq!(ids(&body), ["org.s.fleet"]);
+
+        let (_, body) = get(&app, "/plugins/search?per_page=1&page=2", &[]).await;
+        assert_eq!(ids(&body), ["org.s.mining"]);
+        assert_eq!(body["total"], 3);
+        assert_eq!(body["page"], 2);
+    });
+}
+
+#[test]
+fn search_offers_the_newest_version_for_the_host() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let app = build_app(state_at(dir.path()).await);
+        for (version, core, category) in [
+            ("1.0.0", "1.0.0", "Legacy"),
+            ("2.0.0", "2.0.0", "Fleet"),
+            ("3.0.0-beta.1", "1.0.0", "Fleet"),
+        ] {
+            publish(
+                &app,
+                json!({
+                    "id": "org.s.hosted", "name": "Hosted", "version": version,
+                    "core_version_min": core, "category": category,
+                }),
+            )
+            .await;
+        }
+
+        let (_, body) = get(&app, "/plugins/search?q=hosted", &[]).await;
+        assert_eq!(body["results"][0]["version"], "2.0.0");
+        let (_, body) = get(&app, "/plugins/search?q=hosted&core_version=1.5.0", &[]).await;
+        assert_eq!(body["results"][0]["version"], "1.0.0");
+        // filters and facets apply to the version offered to the host
+        assert_eq!(body["facets"]["category"], json!({"Legacy": 1}));
+        let uri = "/plugins/search?q=hosted&core_version=1.5.0&category=fleet";
+        let (_, body) = get(&app, uri, &[]).await;
+        assert_eq!(body["total"], 0);
+        let (_, body) = get(&app, "/plugins/search?q=hosted&core_version=0.9.0", &[]).await;
+        assert_eq!(body["total"], 0);
+    });
+}
+
+#[test]
+fn downloads_rank_and_outlive_restarts() {
+    runtime().block_on(async {
+        let dir = must(tempdir());
+        let app = build_app(state_at(dir.path()).await);
+
+        let upload = Request::builder()
+            .method("POST")
+            .uri("/plugins/artifacts")
+            .header("content-type", "application/octet-stream")
+            .body(Body::from(vec![7u8; 64]));
+        let (status, body) = send(&app, must(upload)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let artifact: ArtifactRef = must(serde_json::from_value(body));
+        publish(
+            &app,
+            json!({"id": "org.d.alpha", "name": "Radar Alpha", "version": "1.0.0"}),
+        )
+        .await;
+        publish(
+            &app,
+            json!({
+                "id": "org.d.beta", "name": "Radar Beta", "version": "1.0.0",
+                "artifact": artifact,
+            }),
+        )
+        .await;
+
+        // equally relevant, so the id decides
+        let (_, body) = get(&app, "/plugins/search?q=radar", &[]).await;
+        assert_eq!(ids(&body), ["org.d.alpha", "org.d.beta"]);
+
+        let uri = "/plugins/org.d.beta/1.0.0/download";
+        assert_eq!(get(&app, uri, &[]).await.0, StatusCode::OK);
+        assert_eq!(get(&app, uri, &[]).await.0, StatusCode::OK);
+        let (status, _) = get(&app, uri, &[("range", "bytes=10-20")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        let (_, body) = get(&app, "/plugins/search?q=radar", &[]).await;
+        assert_eq!(ids(&body), ["org.d.beta", "org.d.alpha"]);
+        // resumed downloads count once
+        assert_eq!(body["results"][0]["downloads"], 2);
+
+        // the index is rebuilt from storage on start
+        drop(app);
+        let app = build_app(state_at(dir.path()).await);
+        let (_, body) = get(&app, "/plugins/search?q=radar", &[]).await;
+        assert_eq!(ids(&body), ["org.d.beta", "org.d.alpha"]);
+        assert_eq!(body["results"][0]["downloads"], 2);
+
+        let admin = admin_session(&app).await;
+        let revoke = json!({"id": "org.d.beta", "version": "1.0.0", "reason": "malware"});
+        let (status, _) = post_as(&app, Some(&admin), "/verify/revoke", revoke).await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = get(&app, "/plugins/search?q=radar", &[]).await;
+        assert_eq!(ids(&body), ["org.d.alpha"]);
+    });
+}
@@ -61,3 +61,52 @@ fn starts_and_serves_plugins_search() {
         let _ = must(app.clone().oneshot(req3).await);
     });
 }
+
+#[cfg(feature = "run-server")]
+#[test]
+fn serves_https_and_shuts_down_gracefully() {
+    use master_server::config::{Config, TlsConfig};
+    use master_server::server::Server;
+
+    let rt = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let dir = must(tempdir());
+        let mut config = Config::default();
+        config.storage.db_path = dir.path().to_path_buf();
+        config.server.bind = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
+        config.server.shutdown_timeout_secs = 5;
+        config.server.tls = Some(TlsConfig {
+            self_signed: true,
+            ..TlsConfig::default()
+        });
+
+        let server = must(Server::bind(&config.server).await);
+        let port = must(server.local_addr()).port();
+        let app = build_app(Arc::new(must(AppState::from_config(config))));
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let running = tokio::spawn(server.serve(app, async {
+            let _ = stopped.await;
+        }));
+
+        let client = must(
+            reqwest::Client::builder()
+                .danger_accept_invalid_certs(true)
+                .build(),
+        );
+        let url = format!("https://127.0.0.1:{}/healthz", port);
+        let resp = must(client.get(&url).send().await);
+        assert!(resp.status().is_success());
+
+        let _ = stop.send(());
+        let served = must(tokio::time::timeout(std::time::Duration::from_secs(10), running).await);
+        must(must(served));
+        assert!(client.get(&url).send().await.is_err());
+    });
+}
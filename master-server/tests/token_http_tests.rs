@@ -0,0 +1,138 @@
+#![allow(clippy::disallowed_methods)]
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use master_server::build_app;
+use master_server::state::AppState;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+use verseguy_test_utils::{must, must_opt};
+
+async fn send(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    bearer: Option<&str>,
+    body: Option<String>,
+) -> (StatusCode, serde_json::Value) {
+    let mut builder = Request::builder().method(method).uri(uri);
+    if let Some(token) = bearer {
+        builder = builder.header("authorization", format!("Bearer {}", token));
+    }
+    let req = match body {
+        Some(b) => must(
+            builder
+                .header("content-type", "application/json")
+                .body(Body::from(b)),
+        ),
+        None => must(builder.body(Body::empty())),
+    };
+    let resp = must(app.clone().oneshot(req).await);
+    let status = resp.status();
+    let bytes = must(axum::body::to_bytes(resp.into_body(), 1024 * 1024).await);
+    let v = serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null);
+    (status, v)
+}
+
+#[test]
+fn personal_access_token_lifecycle() {
+    let rt = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => panic!("failed to build runtime: {}", e),
+    };
+
+    rt.block_on(async {
+        let dir = must(tempfile::tempdir());
+        let db_path = must_opt(dir.path().to_str(), "tempdir path not utf8").to_string();
+        let state = Arc::new(must(AppState::new(db_path, b"secret".to_vec())));
+        let app = build_app(state);
+
+        let creds = r#"{"username":"scripter","password":"s3cretpass"}"#.to_string();
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/auth/register",
+            None,
+            Some(creds.clone()),
+        )
+        .await;
+        assert!(status.is_success());
+        let (_, v) = send(&app, Method::POST, "/auth/login", None, Some(creds)).await;
+        let session =
+            must_opt(v.get("token").and_then(|t| t.as_str()), "missing token").to_string();
+
+        // Unauthenticated requests are rejected
+        let (status, _) = send(&app, Method::GET, "/auth/tokens", None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Lifetimes outside 1..=365 days are refused
+        for days in ["0", "-1", "366", "9223372036854775807"] {
+            let body = format!(
+                r#"{{"name":"ci","scopes":["plugins:publish"],"expires_in_days":{}}}"#,
+                days
+            );
+            let req = Some(body);
+            let (status, _) = send(&app, Method::POST, "/auth/tokens", Some(&session), req).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "expires_in_days {}", days);
+        }
+
+        // Create a token with the session
+        let body = r#"{"name":"ci","scopes":["plugins:publish"],"expires_in_days":30}"#.to_string();
+        let (status, v) = send(
+            &app,
+            Method::POST,
+            "/auth/tokens",
+            Some(&session),
+            Some(body.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let pat = must_opt(v.get("token").and_then(|t| t.as_str()), "missing pat").to_string();
+        let id = must_opt(v["record"].get("id").and_then(|t| t.as_str()), "missing id").to_string();
+        assert!(v["record"].get("token_hash").is_none());
+
+        // Without `tokens:manage` the PAT can neither list nor revoke tokens
+        let uri = format!("/auth/tokens/{}", id);
+        let (status, _) = send(&app, Method::GET, "/auth/tokens", Some(&pat), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&app, Method::DELETE, &uri, Some(&pat), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Tokens without a lifetime get the default one
+        let manage = r#"{"name":"tokens","scopes":["tokens:manage"]}"#.to_string();
+        let (status, v) = send(
+            &app,
+            Method::POST,
+            "/auth/tokens",
+            Some(&session),
+            Some(manage),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(v["record"]["expires_at"].is_string());
+        let manager = must_opt(v.get("token").and_then(|t| t.as_str()), "missing pat").to_string();
+
+        // A PAT with the scope can list tokens but cannot mint new ones
+        let (status, v) = send(&app, Method::GET, "/auth/tokens", Some(&manager), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(v["tokens"].as_array().map(|a| a.len()), Some(2));
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/auth/tokens",
+            Some(&manager),
+            Some(body),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Revoke and confirm the PAT no longer works
+        let (status, _) = send(&app, Method::DELETE, &uri, Some(&session), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, Method::GET, "/auth/tokens", Some(&pat), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    });
+}
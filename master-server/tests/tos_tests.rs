@@ -22,14 +22,31 @@ fn accept_and_get_tos() {
         let dir = must(tempdir());
         let state = Arc::new(must(AppState::new(dir.path(), vec![0u8; 32])));
         let app = build_app(state.clone());
+        // only published versions can be accepted
+        must(master_server::legal::create_document(
+            &state.storage,
+            master_server::legal::AdminCreateLegalRequest {
+                doc_type: "tos".into(),
+                version: "1.0".into(),
+                title: "Terms of Service".into(),
+                content: "These are the terms".into(),
+                ..Default::default()
+            },
+        ));
+
+        let token = must(
+            verseguy_auth::SessionService::new(state.license_secret.clone())
+                .create_and_store_session("u1", &verseguy_auth::License::Free, 1, &state.storage),
+        );
 
         // POST /auth/tos
-        let body = r#"{"user_id":"u1","accepted_at": 1609459200, "version":"1.0"}"#;
+        let body = r#"{"user_id":"u2","accepted_at": 1609459200, "version":"1.0"}"#;
         let req = must(
             Request::builder()
                 .method("POST")
                 .uri("/auth/tos")
                 .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {}", token))
                 .body(Body::from(body)),
         );
         let resp = must(app.clone().oneshot(req).await);
@@ -40,6 +57,7 @@ fn accept_and_get_tos() {
             Request::builder()
                 .method("GET")
                 .uri("/auth/tos/u1")
+                .header("authorization", format!("Bearer {}", token))
                 .body(Body::empty()),
         );
         let resp2 = must(app.oneshot(req2).await);
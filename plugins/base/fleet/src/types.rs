@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 
 /// Ship in hangar
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Ship {
     pub id: String,
     pub owner_id: String,
@@ -20,6 +21,7 @@ pub struct Ship {
 
 /// Ship insurance type
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum Insurance {
     None,
     Standard,
@@ -28,6 +30,7 @@ pub enum Insurance {
 
 /// Ship operational status
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum ShipStatus {
     Available,
     InUse,
@@ -38,6 +41,7 @@ pub enum ShipStatus {
 
 /// Ship loadout
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Loadout {
     pub id: String,
     pub ship_id: String,
@@ -49,6 +53,7 @@ pub struct Loadout {
 
 /// Ship component
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Component {
     pub slot: String, // e.g., "PowerPlant", "Shield", "Weapon_01"
     pub item: String, // e.g., "Genoa", "FR-76 Shield"
@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 
 /// Operation/Event
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Operation {
     pub id: String,
     pub org_id: String,
@@ -20,6 +21,7 @@ pub struct Operation {
 
 /// Operation type
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum OperationType {
     Combat,
     Mining,
@@ -33,6 +35,7 @@ pub enum OperationType {
 
 /// Operation status
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum OperationStatus {
     Planned,
     InProgress,
@@ -42,6 +45,7 @@ pub enum OperationStatus {
 
 /// Participant in operation
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Participant {
     pub user_id: String,
     pub role: String, // e.g., "Pilot", "Gunner", "Engineer"
@@ -171,6 +171,62 @@ impl OrganizationService {
         Ok(results)
     }
 
+    pub fn get_member(&self, org_id: &str, user_id: &str) -> Result<Option<Member>> {
+        let member: Option<Member> = self
+            .storage
+            .get(keys::member(org_id, user_id))
+            .context("Failed to get member")?;
+        Ok(member)
+    }
+
+    pub fn remove_member(&self, org_id: &str, user_id: &str) -> Result<()> {
+        self.storage
+            .delete(keys::member(org_id, user_id))
+            .context("Failed to remove member")?;
+        Ok(())
+    }
+
+    // ===========================================================================
+    // RANK MANAGEMENT
+    // ===========================================================================
+
+    /// Create or replace a rank
+    pub fn save_rank(&self, rank: Rank) -> Result<()> {
+        if rank.name.is_empty() || rank.name.len() > 32 {
+            anyhow::bail!("Rank name must be 1-32 characters");
+        }
+
+        self.storage
+            .put(keys::rank(&rank.org_id, &rank.id), &rank)
+            .context("Failed to save rank")?;
+        Ok(())
+    }
+
+    pub fn get_rank(&self, org_id: &str, rank_id: &str) -> Result<Option<Rank>> {
+        let rank: Option<Rank> = self
+            .storage
+            .get(keys::rank(org_id, rank_id))
+            .context("Failed to get rank")?;
+        Ok(rank)
+    }
+
+    /// List ranks of an organization, most senior first
+    pub fn list_ranks(&self, org_id: &str) -> Result<Vec<Rank>> {
+        let mut ranks: Vec<Rank> = self
+            .storage
+            .prefix_scan(keys::ranks_prefix(org_id))
+            .context("Failed to scan ranks")?;
+        ranks.sort_by(|a, b| b.level.cmp(&a.level).then_with(|| a.name.cmp(&b.name)));
+        Ok(ranks)
+    }
+
+    pub fn delete_rank(&self, org_id: &str, rank_id: &str) -> Result<()> {
+        self.storage
+            .delete(keys::rank(org_id, rank_id))
+            .context("Failed to delete rank")?;
+        Ok(())
+    }
+
     pub fn has_permission(&self, user_id: &str, perm: Permission) -> Result<bool> {
         // Simplified: check if any rank assigned to user includes the permission
         // Scan all members under the root member prefix
@@ -187,6 +243,36 @@ impl OrganizationService {
         Ok(false)
     }
 
+    /// Whether `user_id` is the owner or a member of organization `org_id`
+    pub fn is_member(&self, org_id: &str, user_id: &str) -> Result<bool> {
+        match self.get_organization(org_id)? {
+            Some(org) if org.owner_id == user_id => Ok(true),
+            Some(_) => Ok(self.get_member(org_id, user_id)?.is_some()),
+            None => Ok(false),
+        }
+    }
+
+    /// Whether `user_id` holds `perm` in organization `org_id`: the owner holds every
+    /// permission, members those of their rank in that organization
+    pub fn has_org_permission(
+        &self,
+        org_id: &str,
+        user_id: &str,
+        perm: Permission,
+    ) -> Result<bool> {
+        match self.get_organization(org_id)? {
+            Some(org) if org.owner_id == user_id => return Ok(true),
+            Some(_) => {}
+            None => return Ok(false),
+        }
+        let member = match self.get_member(org_id, user_id)? {
+            Some(m) => m,
+            None => return Ok(false),
+        };
+        let rank = self.get_rank(org_id, &member.rank_id)?;
+        Ok(rank.is_some_and(|r| r.permissions.contains(&perm)))
+    }
+
     pub fn list_orgs_prefix(&self, prefix: &str) -> Result<Vec<Organization>> {
         // Use organization's key prefix to scan for organizations matching prefix (empty string for all)
         let out: Vec<Organization> = self
@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 
 /// Organization
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Organization {
     pub id: String,
     pub name: String,
@@ -17,6 +18,7 @@ pub struct Organization {
 
 /// Organization member
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Member {
     pub id: String,
     pub org_id: String,
@@ -29,6 +31,7 @@ pub struct Member {
 
 /// Rank in organization
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Rank {
     pub id: String,
     pub org_id: String,
@@ -40,6 +43,7 @@ pub struct Rank {
 
 /// Permission flags
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub enum Permission {
     // Members
     ViewMembers,
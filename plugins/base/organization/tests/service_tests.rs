@@ -238,6 +238,86 @@ fn test_has_permission_checks_ranks() {
     assert!(has);
 }
 
+#[test]
+fn test_org_permissions_are_scoped_to_the_organization() {
+    use plugins_base_organization::types::{Member, Permission, Rank};
+
+    let tmp = verseguy_test_utils::must(TempDir::new());
+    let storage = verseguy_test_utils::must(Storage::open(tmp.path()));
+    let svc = OrganizationService::new(storage.clone());
+
+    let alpha = verseguy_test_utils::must(svc.create_organization(
+        "Alpha".into(),
+        "AL".into(),
+        "d".into(),
+        "owner-a".into(),
+    ));
+    let beta = verseguy_test_utils::must(svc.create_organization(
+        "Beta".into(),
+        "BE".into(),
+        "d".into(),
+        "owner-b".into(),
+    ));
+
+    // owners hold every permission in their own organization only
+    let owns = verseguy_test_utils::must(svc.has_org_permission(
+        &alpha.id,
+        "owner-a",
+        Permission::DeleteOrganization,
+    ));
+    assert!(owns);
+    let foreign = verseguy_test_utils::must(svc.has_org_permission(
+        &beta.id,
+        "owner-a",
+        Permission::ViewFleet,
+    ));
+    assert!(!foreign);
+    assert!(!verseguy_test_utils::must(
+        svc.is_member(&beta.id, "owner-a")
+    ));
+
+    // members hold the permissions of their rank in that organization
+    let rank = Rank {
+        id: "officer".into(),
+        org_id: alpha.id.clone(),
+        name: "Officer".into(),
+        level: 50,
+        permissions: vec![Permission::ManageFleet],
+        created_at: chrono::Utc::now(),
+    };
+    verseguy_test_utils::must(svc.save_rank(rank));
+    let member = Member {
+        id: "m1".into(),
+        org_id: alpha.id.clone(),
+        user_id: "user1".into(),
+        handle: "user1".into(),
+        rank_id: "officer".into(),
+        joined_at: chrono::Utc::now(),
+        notes: None,
+    };
+    verseguy_test_utils::must(svc.add_member(member));
+
+    assert!(verseguy_test_utils::must(svc.is_member(&alpha.id, "user1")));
+    let fleet = verseguy_test_utils::must(svc.has_org_permission(
+        &alpha.id,
+        "user1",
+        Permission::ManageFleet,
+    ));
+    assert!(fleet);
+    let ranks = verseguy_test_utils::must(svc.has_org_permission(
+        &alpha.id,
+        "user1",
+        Permission::ManageRanks,
+    ));
+    assert!(!ranks);
+    let elsewhere = verseguy_test_utils::must(svc.has_org_permission(
+        &beta.id,
+        "user1",
+        Permission::ManageFleet,
+    ));
+    assert!(!elsewhere);
+}
+
 #[test]
 fn test_update_org_changes_name_and_description() {
     let tmp = verseguy_test_utils::must(TempDir::new());
@@ -322,3 +402,54 @@ fn test_list_orgs_paged_returns_non_overlapping_slices() {
 
     assert!(ids1.is_disjoint(&ids2));
 }
+
+#[test]
+fn test_ranks_crud_and_member_removal() {
+    use plugins_base_organization::types::{Permission, Rank};
+
+    let tmp = verseguy_test_utils::must(TempDir::new());
+    let storage = verseguy_test_utils::must(Storage::open(tmp.path()));
+    let svc = OrganizationService::new(storage);
+
+    for (id, name, level) in [("r1", "Recruit", 1), ("r2", "Admiral", 100)] {
+        verseguy_test_utils::must(svc.save_rank(Rank {
+            id: id.into(),
+            org_id: "org1".into(),
+            name: name.into(),
+            level,
+            permissions: vec![Permission::ViewMembers],
+            created_at: chrono::Utc::now(),
+        }));
+    }
+
+    // most senior first
+    let ranks = verseguy_test_utils::must(svc.list_ranks("org1"));
+    let names: Vec<String> = ranks.into_iter().map(|r| r.name).collect();
+    assert_eq!(names, vec!["Admiral".to_string(), "Recruit".to_string()]);
+
+    let invalid = svc.save_rank(Rank {
+        id: "r3".into(),
+        org_id: "org1".into(),
+        name: String::new(),
+        level: 0,
+        permissions: vec![],
+        created_at: chrono::Utc::now(),
+    });
+    assert!(invalid.is_err());
+
+    verseguy_test_utils::must(svc.delete_rank("org1", "r1"));
+    assert!(verseguy_test_utils::must(svc.get_rank("org1", "r1")).is_none());
+
+    verseguy_test_utils::must(svc.add_member(Member {
+        id: "m1".into(),
+        org_id: "org1".into(),
+        user_id: "u1".into(),
+        handle: "alice".into(),
+        rank_id: "r2".into(),
+        joined_at: chrono::Utc::now(),
+        notes: None,
+    }));
+    assert!(verseguy_test_utils::must(svc.get_member("org1", "u1")).is_some());
+    verseguy_test_utils::must(svc.remove_member("org1", "u1"));
+    assert!(verseguy_test_utils::must(svc.get_member("org1", "u1")).is_none());
+}